@@ -0,0 +1,52 @@
+// benches/png_compression.rs
+// Encoding time and file size of one rendered certificate at each PNG compression level.
+//
+//   cargo bench --bench png_compression [-- <template.png> [<iterations>]]
+//
+// Without arguments it uses the first template in Template/ and 5 iterations per level.
+use certificate_maker::editpng::{PngCompression, encode_png, render_text_with_custom_options};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // cargo passes --bench to custom harnesses
+    let args: Vec<String> = std::env::args().skip(1).filter(|arg| !arg.starts_with("--")).collect();
+    let template = match args.first() {
+        Some(path) => PathBuf::from(path),
+        None => std::fs::read_dir("Template")?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("png")))
+            .min()
+            .ok_or("no PNG template in Template/; pass one as an argument")?,
+    };
+    let iterations: usize = args.get(1).map(|count| count.parse()).transpose()?.unwrap_or(5).max(1);
+
+    let certificate = render_text_with_custom_options(&template, "Alexandra Montgomery-Smith", 0, 0, "DejaVuSans.ttf", 60.0, "#1A1A40")?;
+    println!("{} ({}x{}), {} iterations per level\n", template.display(), certificate.width(), certificate.height(), iterations);
+    println!("{:<10} {:>12} {:>12} {:>12} {:>10}", "level", "median", "min", "size", "vs balanced");
+
+    let mut results = Vec::new();
+    for level in PngCompression::ALL {
+        let mut times = Vec::new();
+        let mut size = 0;
+        for _ in 0..iterations {
+            let started = Instant::now();
+            size = encode_png(&certificate, level)?.len();
+            times.push(started.elapsed());
+        }
+        times.sort();
+        results.push((level, times[times.len() / 2], times[0], size));
+    }
+
+    let balanced = results.iter().find(|(level, ..)| *level == PngCompression::Balanced).map_or(1, |(.., size)| *size);
+    for (level, median, min, size) in results {
+        println!("{:<10} {:>12} {:>12} {:>9.1} KB {:>+9.0}%",
+                 level.name(), milliseconds(median), milliseconds(min), size as f64 / 1024.0,
+                 (size as f64 / balanced as f64 - 1.0) * 100.0);
+    }
+    Ok(())
+}
+
+fn milliseconds(duration: Duration) -> String {
+    format!("{:.1} ms", duration.as_secs_f64() * 1000.0)
+}
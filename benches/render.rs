@@ -0,0 +1,72 @@
+// benches/render.rs
+// Criterion benchmarks of the rendering pipeline's core steps, on the small fixtures in
+// tests/fixtures/ so the numbers don't depend on what is in Template/ or assets/.
+//
+//   cargo bench --bench render [-- <filter>]
+//
+// Criterion keeps the previous run's results in target/criterion/ and reports the change
+// against them, so run it before and after touching the rendering code.
+use certificate_maker::editpng::{
+    Anchor, PngCompression, SyntheticStyle, TextOptions, draw_text, encode_png, hex_to_rgba, load_font, load_template,
+    render_text_on,
+};
+use certificate_maker::measure::measure_text;
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use std::hint::black_box;
+
+const TEMPLATE: &str = "tests/fixtures/template.png";
+const FONT: &str = "tests/fixtures/DejaVuSans-Latin.ttf";
+// A typical length, with kerning pairs, a hyphen and an accent
+const NAME: &str = "Zoë Okonkwo-Brandt";
+
+fn template_decode(c: &mut Criterion) {
+    c.bench_function("template_decode", |b| b.iter(|| load_template(black_box(TEMPLATE)).unwrap()));
+}
+
+fn text_measure(c: &mut Criterion) {
+    let font = load_font(FONT).unwrap();
+    c.bench_function("text_measure", |b| b.iter(|| measure_text(&font, black_box(NAME), 48.0, 0.0, None).unwrap()));
+}
+
+fn draw_name(c: &mut Criterion) {
+    let template = load_template(TEMPLATE).unwrap();
+    let font = load_font(FONT).unwrap();
+    let options = TextOptions {
+        font: &font,
+        size: 48.0,
+        color: hex_to_rgba("#1A1A40").unwrap(),
+        x: template.width() as i32 / 2,
+        y: template.height() as i32 / 2,
+        anchor: Anchor::Center,
+        decorations: &[],
+        synthetic: SyntheticStyle::default(),
+        variation: None,
+        effect: None,
+    };
+    // The copy of the template is made outside the timed part
+    c.bench_function("draw_name", |b| {
+        b.iter_batched_ref(|| template.clone(), |img| draw_text(img, black_box(NAME), &options).unwrap(), BatchSize::SmallInput)
+    });
+}
+
+fn png_encode(c: &mut Criterion) {
+    let certificate = render_text_on(load_template(TEMPLATE).unwrap(), NAME, 150, 170, FONT, 48.0, "#1A1A40").unwrap();
+    let mut group = c.benchmark_group("png_encode");
+    for level in PngCompression::ALL {
+        group.bench_function(level.name(), |b| b.iter(|| encode_png(black_box(&certificate), level).unwrap()));
+    }
+    group.finish();
+}
+
+// Everything one certificate takes from files to PNG bytes, as a batch of one would
+fn single_certificate(c: &mut Criterion) {
+    c.bench_function("single_certificate", |b| {
+        b.iter(|| {
+            let certificate = render_text_on(load_template(TEMPLATE).unwrap(), black_box(NAME), 150, 170, FONT, 48.0, "#1A1A40").unwrap();
+            encode_png(&certificate, PngCompression::default()).unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, template_decode, text_measure, draw_name, png_encode, single_certificate);
+criterion_main!(benches);
@@ -1,136 +1,852 @@
 // src/analysis.rs
-use anyhow::{Context, Result};
-use image::{open, GenericImageView};
-use png::{Decoder, ColorType, BitDepth};
-use std::fs::File;
-use std::path::Path;
-
-#[derive(Debug)]
-pub struct PngAnalysis {
+//! Image analysis: properties, color management, transparency, diffs and ink coverage.
+use crate::dpi::mm_to_pixels;
+use crate::error::{CertError, IoContext, Result};
+use crate::imposition::PAPER_SIZES;
+use crate::pdf::DEFAULT_PDF_DPI;
+use crate::sanitize::csv_safe;
+use crate::orientation::{Orientation, exif_ignored, exif_orientation_of};
+use image::codecs::jpeg::JpegEncoder;
+use image::io::Reader as ImageReader;
+use image::{ColorType, DynamicImage, GenericImageView, GrayImage, ImageFormat, Luma};
+use png::{Decoder, BitDepth};
+use rayon::prelude::*;
+use serde::{Serialize, Serializer};
+use std::fmt::Debug;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+// image/png enums have no serde support, so export them by their Debug names
+fn serialize_debug<T: Debug, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format!("{:?}", value))
+}
+
+fn serialize_debug_option<T: Debug, S: Serializer>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => serialize_debug(value, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Details only a real PNG file can provide.
+#[derive(Debug, Serialize)]
+pub struct PngDetails {
+    #[serde(serialize_with = "serialize_debug")]
+    pub color_type: png::ColorType,
+    #[serde(serialize_with = "serialize_debug")]
+    pub bit_depth: BitDepth,
+    pub has_trns: bool,
+    pub interlaced: bool,
+    pub chunks: Vec<String>,
+    // Color management chunks (iCCP / sRGB / gAMA / cHRM)
+    pub icc_profile_name: Option<String>,
+    pub icc_profile_size: Option<usize>,
+    /// What the profile calls itself inside, such as "sRGB IEC61966-2.1"; the chunk's own
+    /// name is often just "ICC profile".
+    pub icc_profile_description: Option<String>,
+    pub srgb_intent: Option<String>,
+    pub gamma: Option<f32>,
+    pub has_chromaticities: bool,
+}
+
+impl PngDetails {
+    /// An embedded profile that isn't sRGB means colors may shift, since text drawing ignores it.
+    pub fn has_non_srgb_profile(&self) -> bool {
+        match &self.icc_profile_name {
+            Some(name) => self.srgb_intent.is_none()
+                && !name.to_lowercase().contains("srgb")
+                && !self.icc_profile_description.as_ref().is_some_and(|description| description.to_lowercase().contains("srgb")),
+            None => false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImageAnalysis {
     pub filename: String,
     pub file_size_bytes: u64,
+    #[serde(serialize_with = "serialize_debug_option")]
+    pub format: Option<ImageFormat>,
+    /// Upright, once [`orientation`](Self::orientation) is applied.
     pub width: u32,
     pub height: u32,
+    pub aspect_ratio: f64,
+    pub center_x: u32,
+    pub center_y: u32,
+    #[serde(serialize_with = "serialize_debug")]
     pub color_type: ColorType,
-    pub bit_depth: BitDepth,
     pub has_transparency: bool,
     pub pixel_count: u64,
     pub bytes_per_pixel: u8,
+    pub theoretical_size_bytes: u64,
+    pub compression_ratio: f64,
+    pub size_category: String,
+    /// What the file's EXIF orientation asks for; `None` for an upright image.
+    pub orientation: Option<Orientation>,
+    /// Whether the orientation was applied, rather than ignored as asked.
+    pub orientation_applied: bool,
+    pub png: Option<PngDetails>,
+    // Only present when the image has an alpha channel
+    pub alpha: Option<AlphaStats>,
 }
 
-pub fn analyze_png_file(file_path: &str) -> Result<PngAnalysis> {
-    let path = Path::new(file_path);
-    
-    // Get file size
-    let file_size_bytes = std::fs::metadata(path)
-        .with_context(|| format!("Failed to read file metadata for {}", file_path))?
-        .len();
+// Longest side of the transparency heatmap, in cells
+const HEATMAP_MAX_SIDE: u32 = 256;
 
-    // Basic image analysis using image crate
-    let img = open(path)
-        .with_context(|| format!("Failed to open image file {}", file_path))?;
+/// How much of the image is transparent, and where.
+#[derive(Debug, Serialize)]
+pub struct AlphaStats {
+    pub transparent_fraction: f64,
+    pub partial_fraction: f64,
+    pub opaque_fraction: f64,
+    // Downscaled average-alpha grid, used to render the heatmap
+    #[serde(skip)]
+    pub heatmap: GrayImage,
+}
 
+// Single pass over the decoded pixels: counts and per-cell alpha averages together
+fn compute_alpha_stats(img: &DynamicImage) -> AlphaStats {
     let (width, height) = img.dimensions();
+    let scale = (width.max(height) as f64 / HEATMAP_MAX_SIDE as f64).max(1.0);
+    let cells_x = ((width as f64 / scale).ceil() as u32).max(1);
+    let cells_y = ((height as f64 / scale).ceil() as u32).max(1);
 
-    // Detailed PNG analysis using png crate
-    let file = File::open(path)
-        .with_context(|| format!("Failed to open file {}", file_path))?;
-    
-    let decoder = Decoder::new(file);
+    let mut sums = vec![0u64; (cells_x * cells_y) as usize];
+    let mut counts = vec![0u64; (cells_x * cells_y) as usize];
+    let (mut transparent, mut partial, mut opaque) = (0u64, 0u64, 0u64);
+
+    // pixels() converts one pixel at a time, so no second full-size buffer is allocated
+    for (x, y, pixel) in img.pixels() {
+        let alpha = pixel[3];
+        match alpha {
+            0 => transparent += 1,
+            255 => opaque += 1,
+            _ => partial += 1,
+        }
+        let cell_x = ((x as f64 / scale) as u32).min(cells_x - 1);
+        let cell_y = ((y as f64 / scale) as u32).min(cells_y - 1);
+        let index = (cell_y * cells_x + cell_x) as usize;
+        sums[index] += alpha as u64;
+        counts[index] += 1;
+    }
+
+    let heatmap = GrayImage::from_fn(cells_x, cells_y, |x, y| {
+        let index = (y * cells_x + x) as usize;
+        let average = sums[index].checked_div(counts[index]).unwrap_or(0);
+        Luma([average as u8])
+    });
+
+    let total = (transparent + partial + opaque).max(1) as f64;
+    AlphaStats {
+        transparent_fraction: transparent as f64 / total,
+        partial_fraction: partial as f64 / total,
+        opaque_fraction: opaque as f64 / total,
+        heatmap,
+    }
+}
+
+/// Save the alpha heatmap (black = fully transparent, white = opaque).
+pub fn write_alpha_heatmap(analysis: &ImageAnalysis, output_path: impl AsRef<Path>) -> Result<()> {
+    let output_path = output_path.as_ref();
+    let alpha = analysis.alpha.as_ref()
+        .ok_or_else(|| CertError::NoAlphaChannel(analysis.filename.clone()))?;
+
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent)
+            .io_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+
+    alpha.heatmap.save_with_format(output_path, ImageFormat::Png)
+        .map_err(|source| CertError::ImageEncode { path: output_path.to_path_buf(), source })
+}
+
+// Classify image size
+fn size_category(width: u32, height: u32) -> &'static str {
+    match (width, height) {
+        (w, h) if w <= 128 && h <= 128 => "Thumbnail",
+        (w, h) if w <= 512 && h <= 512 => "Small",
+        (w, h) if w <= 1920 && h <= 1080 => "Medium (HD)",
+        (w, h) if w <= 3840 && h <= 2160 => "Large (4K)",
+        _ => "Very Large",
+    }
+}
+
+// Walk the chunks of a PNG file, returning (type, data) pairs in file order
+fn png_chunks(bytes: &[u8]) -> Vec<(String, &[u8])> {
+    let mut chunks = Vec::new();
+    // Skip the 8-byte PNG signature
+    let mut offset = 8;
+
+    // Each chunk: 4-byte length, 4-byte type, data, 4-byte CRC
+    while offset + 8 <= bytes.len() {
+        let length = u32::from_be_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]]) as usize;
+        let chunk_type = String::from_utf8_lossy(&bytes[offset + 4..offset + 8]).to_string();
+        let data_start = offset + 8;
+        let data = match data_start.checked_add(length).and_then(|end| bytes.get(data_start..end)) {
+            Some(data) => data,
+            None => break,
+        };
+        let is_end = chunk_type == "IEND";
+        chunks.push((chunk_type, data));
+        if is_end {
+            break;
+        }
+        offset = data_start + length + 4;
+    }
+
+    chunks
+}
+
+// Profile name from the raw iCCP chunk (a null-terminated keyword before the data)
+fn read_icc_profile_name(chunks: &[(String, &[u8])]) -> Option<String> {
+    let (_, data) = chunks.iter().find(|(chunk_type, _)| chunk_type == "iCCP")?;
+    let name_end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    Some(String::from_utf8_lossy(&data[..name_end]).to_string())
+}
+
+// PNG-specific details using the png crate, from the file's bytes already in memory
+fn analyze_png_details(file_path: &Path, bytes: &[u8]) -> Result<PngDetails> {
+    let decoder = Decoder::new(bytes);
     let reader = decoder.read_info()
-        .with_context(|| "Failed to read PNG info")?;
+        .map_err(|source| CertError::PngDecode { path: file_path.to_path_buf(), source })?;
 
     let info = reader.info();
-    let color_type = info.color_type;
-    let bit_depth = info.bit_depth;
+    let chunks = png_chunks(bytes);
+    let icc_profile_size = info.icc_profile.as_ref().map(|profile| profile.len());
+    let icc_profile_name = icc_profile_size
+        .map(|_| read_icc_profile_name(&chunks).unwrap_or_else(|| "unnamed".to_string()));
+
+    Ok(PngDetails {
+        color_type: info.color_type,
+        bit_depth: info.bit_depth,
+        has_trns: info.trns.is_some(),
+        interlaced: info.interlaced,
+        chunks: chunks.iter().map(|(chunk_type, _)| chunk_type.clone()).collect(),
+        icc_profile_name,
+        icc_profile_size,
+        icc_profile_description: info.icc_profile.as_ref().and_then(|profile| icc_description(profile)),
+        srgb_intent: info.srgb.map(|intent| format!("{:?}", intent)),
+        gamma: info.gama_chunk.map(|gamma| gamma.into_value()),
+        has_chromaticities: info.chrm_chunk.is_some(),
+    })
+}
+
+/// Analyze any image the image crate can open; PNG files get extra details.
+pub fn analyze_image_file(file_path: impl AsRef<Path>) -> Result<ImageAnalysis> {
+    decode_and_analyze(file_path).map(|(analysis, _)| analysis)
+}
+
+/// [`analyze_image_file`], also returning the decoded image, for callers that go on to draw
+/// on it: the file is read and decoded once for both.
+pub fn decode_and_analyze(file_path: impl AsRef<Path>) -> Result<(ImageAnalysis, DynamicImage)> {
+    let path = file_path.as_ref();
+    let bytes = std::fs::read(path)
+        .io_context(|| format!("Failed to read image file {}", path.display()))?;
+    analyze_bytes(path, &bytes)
+}
+
+// The analysis of a file already read into memory; `path` names it
+fn analyze_bytes(path: &Path, bytes: &[u8]) -> Result<(ImageAnalysis, DynamicImage)> {
+    let file_size_bytes = bytes.len() as u64;
+
+    // Detect the real format from content, not just the extension
+    let reader = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .io_context(|| format!("Failed to detect image format of {}", path.display()))?;
+    let format = reader.format();
+
+    // Basic image analysis using image crate
+    let img = reader.decode()
+        .map_err(|source| CertError::ImageDecode { path: path.to_path_buf(), source })?;
+    // Measured and drawn on upright, as every template is loaded
+    let orientation = exif_orientation_of(bytes);
+    let orientation_applied = orientation.is_some() && !exif_ignored();
+    let img = match orientation {
+        Some(orientation) if orientation_applied => orientation.apply(img),
+        _ => img,
+    };
+
+    let (width, height) = img.dimensions();
+    let color_type = img.color();
+    let alpha = if color_type.has_alpha() { Some(compute_alpha_stats(&img)) } else { None };
+
+    // Detailed PNG analysis using png crate, only for real PNGs
+    let png = if format == Some(ImageFormat::Png) {
+        Some(analyze_png_details(path, bytes)?)
+    } else {
+        None
+    };
     
     // Calculate additional metrics
     let pixel_count = (width as u64) * (height as u64);
-    let bytes_per_pixel = match color_type {
-        ColorType::Grayscale => 1,
-        ColorType::Rgb => 3,
-        ColorType::Indexed => 1,
-        ColorType::GrayscaleAlpha => 2,
-        ColorType::Rgba => 4,
+    let bytes_per_pixel = match &png {
+        Some(details) => match details.color_type {
+            png::ColorType::Grayscale => 1,
+            png::ColorType::Rgb => 3,
+            png::ColorType::Indexed => 1,
+            png::ColorType::GrayscaleAlpha => 2,
+            png::ColorType::Rgba => 4,
+        },
+        None => color_type.bytes_per_pixel(),
     };
 
-    let has_transparency = matches!(color_type, ColorType::GrayscaleAlpha | ColorType::Rgba) 
-        || info.trns.is_some();
+    let has_transparency = color_type.has_alpha()
+        || png.as_ref().is_some_and(|details| details.has_trns);
+    
+    let theoretical_size_bytes = pixel_count * bytes_per_pixel as u64;
+    let compression_ratio = theoretical_size_bytes as f64 / file_size_bytes as f64;
 
-    Ok(PngAnalysis {
-        filename: file_path.to_string(),
+    let analysis = ImageAnalysis {
+        filename: path.display().to_string(),
         file_size_bytes,
+        format,
         width,
         height,
+        aspect_ratio: width as f64 / height as f64,
+        center_x: width / 2,
+        center_y: height / 2,
         color_type,
-        bit_depth,
         has_transparency,
         pixel_count,
         bytes_per_pixel,
-    })
+        theoretical_size_bytes,
+        compression_ratio,
+        size_category: size_category(width, height).to_string(),
+        orientation,
+        orientation_applied,
+        png,
+        alpha,
+    };
+    Ok((analysis, img))
 }
 
-pub fn print_analysis(analysis: &PngAnalysis) {
-    println!("=== PNG File Analysis ===");
-    println!("File: {}", analysis.filename);
-    println!("File size: {} bytes ({:.2} KB)", 
-             analysis.file_size_bytes, 
-             analysis.file_size_bytes as f64 / 1024.0);
-    
-    println!("\n--- Image Properties ---");
-    println!("Dimensions: {}x{} pixels", analysis.width, analysis.height);
-    println!("Total pixels: {}", analysis.pixel_count);
-    println!("Aspect ratio: {:.3}", analysis.width as f64 / analysis.height as f64);
-    
-    // Separate coordinates section with useful positioning information
-    println!("\n--- Useful Coordinates ---");
-    let middle_x = analysis.width / 2;
-    let middle_y = analysis.height / 2;
-    let quarter_x = analysis.width / 4;
-    let quarter_y = analysis.height / 4;
-    let three_quarter_x = (analysis.width * 3) / 4;
-    let three_quarter_y = (analysis.height * 3) / 4;
-    
-    println!("🎯 Center point: ({}, {})", middle_x, middle_y);
-    println!("📍 Corner coordinates:");
-    println!("  • Top-left: (0, 0)");
-    println!("  • Top-right: ({}, 0)", analysis.width - 1);
-    println!("  • Bottom-left: (0, {})", analysis.height - 1);
-    println!("  • Bottom-right: ({}, {})", analysis.width - 1, analysis.height - 1);
-    
-    println!("📐 Edge midpoints:");
-    println!("  • Top edge center: ({}, 0)", middle_x);
-    println!("  • Bottom edge center: ({}, {})", middle_x, analysis.height - 1);
-    println!("  • Left edge center: (0, {})", middle_y);
-    println!("  • Right edge center: ({}, {})", analysis.width - 1, middle_y);
-    
-    println!("🔳 Quarter positions:");
-    println!("  • Top-left quarter: ({}, {})", quarter_x, quarter_y);
-    println!("  • Top-right quarter: ({}, {})", three_quarter_x, quarter_y);
-    println!("  • Bottom-left quarter: ({}, {})", quarter_x, three_quarter_y);
-    println!("  • Bottom-right quarter: ({}, {})", three_quarter_x, three_quarter_y);
-
-    println!("\n--- Color Information ---");
-    println!("Color type: {:?}", analysis.color_type);
-    println!("Bit depth: {:?}", analysis.bit_depth);
-    println!("Bytes per pixel: {}", analysis.bytes_per_pixel);
-    println!("Has transparency: {}", analysis.has_transparency);
-    
-    println!("\n--- Technical Details ---");
-    let theoretical_size = analysis.pixel_count * analysis.bytes_per_pixel as u64;
-    let compression_ratio = theoretical_size as f64 / analysis.file_size_bytes as f64;
-    println!("Theoretical uncompressed size: {} bytes ({:.2} KB)", 
-             theoretical_size, 
-             theoretical_size as f64 / 1024.0);
-    println!("Compression ratio: {:.2}:1", compression_ratio);
-    
-    // Classify image size
-    let size_category = match (analysis.width, analysis.height) {
-        (w, h) if w <= 128 && h <= 128 => "Thumbnail",
-        (w, h) if w <= 512 && h <= 512 => "Small",
-        (w, h) if w <= 1920 && h <= 1080 => "Medium (HD)",
-        (w, h) if w <= 3840 && h <= 2160 => "Large (4K)",
-        _ => "Very Large",
+/// Analyze an image and return the result as pretty-printed JSON.
+pub fn analyze_image_file_json(file_path: impl AsRef<Path>) -> Result<String> {
+    let file_path = file_path.as_ref();
+    let analysis = analyze_image_file(file_path)?;
+    serde_json::to_string_pretty(&analysis)
+        .map_err(|source| CertError::Json { context: format!("Failed to serialize analysis for {}", file_path.display()), source })
+}
+
+/// Write the analysis JSON next to the image (photo.png -> photo.analysis.json).
+pub fn write_analysis_json(file_path: impl AsRef<Path>) -> Result<PathBuf> {
+    let file_path = file_path.as_ref();
+    let json = analyze_image_file_json(file_path)?;
+    let json_path = file_path.with_extension("analysis.json");
+    std::fs::write(&json_path, json)
+        .io_context(|| format!("Failed to write {}", json_path.display()))?;
+    Ok(json_path)
+}
+
+/// Analyze several images in parallel, keeping failures as error rows.
+pub fn analyze_image_files(file_paths: &[PathBuf]) -> Vec<(PathBuf, Result<ImageAnalysis>)> {
+    let mut results: Vec<(PathBuf, Result<ImageAnalysis>)> = file_paths
+        .par_iter()
+        .map(|file_path| (file_path.clone(), analyze_image_file(file_path)))
+        .collect();
+    results.sort_by_key(|(file_path, _)| file_path.to_string_lossy().to_lowercase());
+    results
+}
+
+/// File name of a path, for display in tables and reports.
+pub fn short_name(file_path: impl AsRef<Path>) -> String {
+    let file_path = file_path.as_ref();
+    file_path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| file_path.display().to_string())
+}
+
+pub fn write_comparison_csv(results: &[(PathBuf, Result<ImageAnalysis>)], output_path: impl AsRef<Path>) -> Result<()> {
+    let output_path = output_path.as_ref();
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent)
+            .io_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+
+    let csv_error = |source| CertError::Csv { path: output_path.to_path_buf(), source };
+    let mut writer = csv::Writer::from_path(output_path).map_err(csv_error)?;
+    writer.write_record(["file", "width", "height", "aspect_ratio", "color_type",
+                         "has_transparency", "file_size_bytes", "center_x", "center_y", "error"]).map_err(csv_error)?;
+
+    for (file_path, result) in results {
+        // File names are text a spreadsheet would run, same as names
+        let name = csv_safe(&short_name(file_path)).into_owned();
+        match result {
+            Ok(analysis) => writer.write_record([
+                name,
+                analysis.width.to_string(),
+                analysis.height.to_string(),
+                format!("{:.3}", analysis.aspect_ratio),
+                format!("{:?}", analysis.color_type),
+                analysis.has_transparency.to_string(),
+                analysis.file_size_bytes.to_string(),
+                analysis.center_x.to_string(),
+                analysis.center_y.to_string(),
+                String::new(),
+            ]).map_err(csv_error)?,
+            Err(e) => {
+                let mut row = vec![name];
+                row.extend(std::iter::repeat_n(String::new(), 8));
+                row.push(csv_safe(&e.to_string()).into_owned());
+                writer.write_record(row).map_err(csv_error)?;
+            }
+        }
+    }
+
+    writer.flush()
+        .io_context(|| format!("Failed to write report: {}", output_path.display()))?;
+    Ok(())
+}
+
+/// Result of comparing two images pixel by pixel.
+#[derive(Debug, Serialize)]
+pub struct DiffReport {
+    pub first: String,
+    pub second: String,
+    pub first_dimensions: (u32, u32),
+    pub second_dimensions: (u32, u32),
+    pub dimensions_match: bool,
+    pub differing_pixels: u64,
+    pub max_channel_delta: u8,
+}
+
+impl DiffReport {
+    pub fn is_identical(&self) -> bool {
+        self.dimensions_match && self.differing_pixels == 0
+    }
+}
+
+/// Compare two images; when diff_output is given, write a PNG with changed pixels in red.
+pub fn compare_images(first: impl AsRef<Path>, second: impl AsRef<Path>, diff_output: Option<&Path>) -> Result<DiffReport> {
+    let (first, second) = (first.as_ref(), second.as_ref());
+    let a = image::open(first)
+        .map_err(|source| CertError::ImageDecode { path: first.to_path_buf(), source })?
+        .into_rgba8();
+    let b = image::open(second)
+        .map_err(|source| CertError::ImageDecode { path: second.to_path_buf(), source })?
+        .into_rgba8();
+
+    let mut report = DiffReport {
+        first: first.display().to_string(),
+        second: second.display().to_string(),
+        first_dimensions: a.dimensions(),
+        second_dimensions: b.dimensions(),
+        dimensions_match: a.dimensions() == b.dimensions(),
+        differing_pixels: 0,
+        max_channel_delta: 0,
+    };
+
+    // Pixel comparison only makes sense on equally sized images
+    if !report.dimensions_match {
+        return Ok(report);
+    }
+
+    let mut diff_image = diff_output.map(|_| image::RgbaImage::new(a.width(), a.height()));
+
+    for ((x, y, pa), pb) in a.enumerate_pixels().zip(b.pixels()) {
+        let delta = pa.0.iter()
+            .zip(pb.0.iter())
+            .map(|(ca, cb)| ca.abs_diff(*cb))
+            .max()
+            .unwrap_or(0);
+
+        if delta > 0 {
+            report.differing_pixels += 1;
+            report.max_channel_delta = report.max_channel_delta.max(delta);
+        }
+
+        if let Some(diff) = diff_image.as_mut() {
+            let pixel = if delta > 0 {
+                image::Rgba([255, 0, 0, 255])
+            } else {
+                // Faded grayscale of the original for context
+                let gray = ((pa[0] as u16 + pa[1] as u16 + pa[2] as u16) / 3) as u8;
+                let faded = 255 - (255 - gray) / 4;
+                image::Rgba([faded, faded, faded, 255])
+            };
+            diff.put_pixel(x, y, pixel);
+        }
+    }
+
+    if let (Some(diff), Some(output_path)) = (diff_image, diff_output) {
+        if let Some(parent) = output_path.parent() {
+            std::fs::create_dir_all(parent)
+                .io_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+        diff.save_with_format(output_path, ImageFormat::Png)
+            .map_err(|source| CertError::ImageEncode { path: output_path.to_path_buf(), source })?;
+    }
+
+    Ok(report)
+}
+
+// Channel difference above which a pixel counts as "ink" rather than background
+const INK_DELTA_THRESHOLD: u8 = 48;
+/// Coverage above which the text region is considered already occupied.
+pub const INK_COVERAGE_WARNING: f64 = 0.02;
+
+/// How much of a rectangle of the template is already covered by non-background pixels.
+#[derive(Debug, Serialize)]
+pub struct InkCoverage {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub background: [u8; 4],
+    pub coverage: f64,
+}
+
+impl InkCoverage {
+    pub fn exceeds_threshold(&self) -> bool {
+        self.coverage > INK_COVERAGE_WARNING
+    }
+}
+
+/// Sample a region and compare every pixel against the region's dominant color.
+pub fn measure_ink_coverage(img: &image::RgbaImage, x: i32, y: i32, width: u32, height: u32) -> InkCoverage {
+    // Clip the rectangle to the image
+    let x0 = x.clamp(0, img.width() as i32) as u32;
+    let y0 = y.clamp(0, img.height() as i32) as u32;
+    let x1 = (x + width as i32).clamp(0, img.width() as i32) as u32;
+    let y1 = (y + height as i32).clamp(0, img.height() as i32) as u32;
+
+    // Dominant color via a coarse (4 bits per channel) histogram
+    let mut buckets: std::collections::HashMap<[u8; 4], (u64, [u64; 4])> = std::collections::HashMap::new();
+    for py in y0..y1 {
+        for px in x0..x1 {
+            let pixel = img.get_pixel(px, py).0;
+            let key = pixel.map(|c| c >> 4);
+            let entry = buckets.entry(key).or_insert((0, [0; 4]));
+            entry.0 += 1;
+            for (sum, c) in entry.1.iter_mut().zip(pixel) {
+                *sum += c as u64;
+            }
+        }
+    }
+
+    let background = buckets.values()
+        .max_by_key(|(count, _)| *count)
+        .map(|(count, sums)| sums.map(|sum| (sum / count) as u8))
+        .unwrap_or([255, 255, 255, 255]);
+
+    let total = (x1 - x0) as u64 * (y1 - y0) as u64;
+    let mut ink = 0u64;
+    for py in y0..y1 {
+        for px in x0..x1 {
+            let pixel = img.get_pixel(px, py).0;
+            let delta = pixel.iter().zip(background.iter()).map(|(a, b)| a.abs_diff(*b)).max().unwrap_or(0);
+            if delta > INK_DELTA_THRESHOLD {
+                ink += 1;
+            }
+        }
+    }
+
+    InkCoverage {
+        x: x0,
+        y: y0,
+        width: x1 - x0,
+        height: y1 - y0,
+        background,
+        coverage: if total > 0 { ink as f64 / total as f64 } else { 0.0 },
+    }
+}
+
+/// What a template is checked against for print: a paper size from
+/// [`PAPER_SIZES`](crate::imposition::PAPER_SIZES) and the resolution it should reach on it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PrintTarget {
+    pub paper: &'static str,
+    /// Portrait width and height in millimetres; a landscape template is checked against
+    /// the paper turned on its side.
+    pub width_mm: f64,
+    pub height_mm: f64,
+    pub dpi: f32,
+}
+
+impl Default for PrintTarget {
+    /// A4 at 300 DPI.
+    fn default() -> Self {
+        let (paper, width_mm, height_mm) = PAPER_SIZES[1];
+        PrintTarget { paper, width_mm, height_mm, dpi: DEFAULT_PDF_DPI }
+    }
+}
+
+impl PrintTarget {
+    /// `paper` is one of [`PAPER_SIZES`](crate::imposition::PAPER_SIZES) by name, in any case.
+    pub fn new(paper: &str, dpi: f32) -> Option<PrintTarget> {
+        let &(paper, width_mm, height_mm) = PAPER_SIZES.iter().find(|(name, _, _)| name.eq_ignore_ascii_case(paper.trim()))?;
+        (dpi.is_finite() && dpi > 0.0).then_some(PrintTarget { paper, width_mm, height_mm, dpi })
+    }
+
+    /// Width and height in pixels at the target resolution, turned like the template.
+    pub fn pixels(&self, landscape: bool) -> (u32, u32) {
+        let (width, height) = (mm_to_pixels(self.width_mm, self.dpi).round() as u32, mm_to_pixels(self.height_mm, self.dpi).round() as u32);
+        if landscape { (height, width) } else { (width, height) }
+    }
+}
+
+/// Difference in aspect ratio from the closest paper size below which a template counts as
+/// that paper's shape: 2%.
+pub const PAPER_ASPECT_TOLERANCE: f64 = 0.02;
+
+/// JPEG quality at or below which a template counts as heavily compressed.
+pub const HEAVY_JPEG_QUALITY: u8 = 50;
+
+// Qualities a JPEG is re-encoded at to find the one it was saved at
+const JPEG_QUALITY_LADDER: [u8; 9] = [10, 20, 30, 40, 50, 60, 70, 80, 90];
+// Side of the square re-encoded, cut on the 16-pixel block grid so the blocks line up
+const JPEG_PROBE_SIDE: u32 = 512;
+
+/// A kind of problem [`check_print_readiness`] looks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrintIssue {
+    LowResolution,
+    Transparency,
+    ColorProfile,
+    PaperAspect,
+    JpegCompression,
+}
+
+/// One problem found, with what to do about it in one line.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PrintFinding {
+    pub issue: PrintIssue,
+    pub message: String,
+    pub hint: String,
+}
+
+/// How ready a template is to be printed on its [`PrintTarget`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PrintReadiness {
+    pub target: PrintTarget,
+    /// Resolution the template prints at when fitted on the target paper.
+    pub effective_dpi: f32,
+    /// The paper size whose shape is closest to the template's.
+    pub closest_paper: &'static str,
+    pub findings: Vec<PrintFinding>,
+}
+
+impl PrintReadiness {
+    pub fn is_ready(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+/// Check a template for what goes wrong when its certificates are printed on `target`: too
+/// few pixels for the paper at the target DPI, transparency (which some print RIPs render
+/// badly), a missing or non-sRGB color profile, a shape no standard paper has, and heavy JPEG
+/// compression. The JPEG quality is estimated by re-encoding part of the image at several
+/// qualities: re-encoding at the quality it was saved at changes it least.
+///
+/// ```
+/// use certificate_maker::analysis::{PrintIssue, PrintTarget, check_print_readiness};
+///
+/// // 600x400 pixels: landscape, far too few for A4 at 300 DPI, and not A4's shape
+/// let report = check_print_readiness("tests/fixtures/template.png", &PrintTarget::default())?;
+/// assert!(report.effective_dpi < 80.0);
+/// let issues: Vec<PrintIssue> = report.findings.iter().map(|finding| finding.issue).collect();
+/// assert!(issues.contains(&PrintIssue::LowResolution));
+/// assert!(issues.contains(&PrintIssue::PaperAspect));
+/// assert!(report.findings.iter().all(|finding| !finding.hint.is_empty()));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn check_print_readiness(file_path: impl AsRef<Path>, target: &PrintTarget) -> Result<PrintReadiness> {
+    let path = file_path.as_ref();
+    let bytes = std::fs::read(path)
+        .io_context(|| format!("Failed to read image file {}", path.display()))?;
+    let (analysis, _) = analyze_bytes(path, &bytes)?;
+    let (width, height) = (analysis.width, analysis.height);
+    let landscape = width > height;
+    let mut findings = Vec::new();
+
+    // Fitted on the paper turned like the template
+    let (paper_width, paper_height) = if landscape { (target.height_mm, target.width_mm) } else { (target.width_mm, target.height_mm) };
+    let effective_dpi = (pixels_to_dpi(width, paper_width)).max(pixels_to_dpi(height, paper_height)) as f32;
+    if effective_dpi.round() < target.dpi {
+        let (needed_width, needed_height) = target.pixels(landscape);
+        findings.push(PrintFinding {
+            issue: PrintIssue::LowResolution,
+            message: format!("{}x{} pixels print at {:.0} DPI on {}, below the {:.0} DPI wanted", width, height, effective_dpi, target.paper, target.dpi),
+            hint: format!("Use a template of at least {}x{} pixels; scaling this one up won't add detail", needed_width, needed_height),
+        });
+    }
+
+    let see_through = match &analysis.alpha {
+        Some(alpha) => 1.0 - alpha.opaque_fraction,
+        None if analysis.has_transparency => 1.0,
+        None => 0.0,
+    };
+    if see_through > 0.0 {
+        findings.push(PrintFinding {
+            issue: PrintIssue::Transparency,
+            message: match &analysis.alpha {
+                Some(_) => format!("{:.1}% of it is transparent or see-through, which some print RIPs render as black or drop", see_through * 100.0),
+                None => "It has a transparent color, which some print RIPs render as black or drop".to_string(),
+            },
+            hint: "Flatten it onto a white background (or the paper's color) before generating".to_string(),
+        });
+    }
+
+    let profile = match analysis.format {
+        Some(ImageFormat::Png) => analysis.png.as_ref().and_then(|png| match &png.icc_profile_name {
+            _ if png.srgb_intent.is_some() => Some(ColorProfile::Srgb),
+            Some(name) => Some(ColorProfile::classify(png.icc_profile_description.as_deref().unwrap_or(name), None)),
+            None => None,
+        }),
+        Some(ImageFormat::Jpeg) => jpeg_icc_profile(&bytes).map(|profile| {
+            ColorProfile::classify(&icc_description(&profile).unwrap_or_default(), icc_color_space(&profile))
+        }),
+        _ => None,
+    };
+    match profile {
+        Some(ColorProfile::Srgb) => {}
+        Some(ColorProfile::Other(description)) => findings.push(PrintFinding {
+            issue: PrintIssue::ColorProfile,
+            message: format!("Its color profile is {}, not sRGB; the certificates don't carry it, so its colors print as if they were sRGB", description),
+            hint: "Convert it to sRGB in your image editor before generating".to_string(),
+        }),
+        None => findings.push(PrintFinding {
+            issue: PrintIssue::ColorProfile,
+            message: "It has no color profile, so printers have to guess its colors".to_string(),
+            hint: "Export it with an embedded sRGB profile (sRGB IEC61966-2.1)".to_string(),
+        }),
+    }
+
+    let aspect = width.max(height) as f64 / width.min(height).max(1) as f64;
+    let (closest_paper, paper_aspect) = PAPER_SIZES.iter()
+        .map(|&(name, short, long)| (name, long / short))
+        .min_by(|(_, a), (_, b)| (aspect / a - 1.0).abs().total_cmp(&(aspect / b - 1.0).abs()))
+        .unwrap_or(("A4", std::f64::consts::SQRT_2));
+    if (aspect / paper_aspect - 1.0).abs() > PAPER_ASPECT_TOLERANCE {
+        let (target_width, target_height) = target.pixels(landscape);
+        findings.push(PrintFinding {
+            issue: PrintIssue::PaperAspect,
+            message: format!("Its shape (1:{:.3}) is no standard paper's; the closest is {} (1:{:.3}), so it prints with margins or cropped", aspect, closest_paper, paper_aspect),
+            hint: format!("Crop or pad it to the paper's proportions, for example with --normalize {}x{}:pad for {}", target_width, target_height, target.paper),
+        });
+    }
+
+    if analysis.format == Some(ImageFormat::Jpeg)
+        && let Some(quality) = estimate_jpeg_quality(path, &bytes)?
+        && quality <= HEAVY_JPEG_QUALITY {
+        findings.push(PrintFinding {
+            issue: PrintIssue::JpegCompression,
+            message: format!("It looks saved at JPEG quality {} or so, heavy enough for blocky artifacts to show in print", quality),
+            hint: "Use the original artwork, or a PNG or a JPEG saved at quality 90 or more".to_string(),
+        });
+    }
+
+    Ok(PrintReadiness { target: *target, effective_dpi, closest_paper, findings })
+}
+
+// The rung of the quality ladder whose re-encode changes the image least; `None` for images
+// smaller than a block. Decoded again as stored, since turning it would move the block grid
+fn estimate_jpeg_quality(path: &Path, bytes: &[u8]) -> Result<Option<u8>> {
+    let img = image::load_from_memory_with_format(bytes, ImageFormat::Jpeg)
+        .map_err(|source| CertError::ImageDecode { path: path.to_path_buf(), source })?
+        .to_rgb8();
+    let side = |length: u32| length.min(JPEG_PROBE_SIDE) / 16 * 16;
+    let (width, height) = (side(img.width()), side(img.height()));
+    if width == 0 || height == 0 {
+        return Ok(None);
+    }
+    // From the middle, on the grid
+    let (x, y) = ((img.width() - width) / 32 * 16, (img.height() - height) / 32 * 16);
+    let probe = image::imageops::crop_imm(&img, x, y, width, height).to_image();
+    let mut deltas = Vec::new();
+    for quality in JPEG_QUALITY_LADDER {
+        let mut jpeg = Vec::new();
+        JpegEncoder::new_with_quality(&mut jpeg, quality).encode_image(&probe)
+            .map_err(|source| CertError::ImageEncode { path: path.to_path_buf(), source })?;
+        let again = image::load_from_memory_with_format(&jpeg, ImageFormat::Jpeg)
+            .map_err(|source| CertError::ImageDecode { path: path.to_path_buf(), source })?
+            .to_rgb8();
+        let delta: u64 = probe.as_raw().iter().zip(again.as_raw()).map(|(a, b)| a.abs_diff(*b) as u64).sum();
+        deltas.push((quality, delta));
+    }
+    Ok(deltas.into_iter().min_by_key(|(_, delta)| *delta).map(|(quality, _)| quality))
+}
+
+// Pixels across `mm` millimetres, per inch
+fn pixels_to_dpi(pixels: u32, mm: f64) -> f64 {
+    pixels as f64 / (mm / 25.4)
+}
+
+// An embedded color profile, told apart by its description
+enum ColorProfile {
+    Srgb,
+    Other(String),
+}
+
+impl ColorProfile {
+    fn classify(description: &str, color_space: Option<String>) -> ColorProfile {
+        let rgb = color_space.as_deref().is_none_or(|space| space == "RGB");
+        if rgb && description.to_lowercase().contains("srgb") {
+            return ColorProfile::Srgb;
+        }
+        let description = if description.is_empty() { "unnamed".to_string() } else { format!("\"{}\"", description) };
+        ColorProfile::Other(match color_space {
+            Some(space) if space != "RGB" => format!("{} ({})", description, space),
+            _ => description,
+        })
+    }
+}
+
+// The ICC profile of a JPEG, put back together from its APP2 segments
+fn jpeg_icc_profile(bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut chunks = Vec::new();
+    let mut offset = 2;
+    // Each segment: FF, marker, 2-byte length (counting itself), data; the image data follows SOS
+    while let (Some(0xFF), Some(&marker)) = (bytes.get(offset), bytes.get(offset + 1)) {
+        if marker == 0xFF {
+            offset += 1;
+            continue;
+        }
+        if marker == 0xDA || marker == 0xD9 {
+            break;
+        }
+        let length = u16::from_be_bytes([*bytes.get(offset + 2)?, *bytes.get(offset + 3)?]) as usize;
+        let data = bytes.get(offset + 4..offset + 2 + length)?;
+        if marker == 0xE2 && data.starts_with(b"ICC_PROFILE\0") && data.len() > 14 {
+            chunks.push((data[12], &data[14..]));
+        }
+        offset += 2 + length;
+    }
+    if chunks.is_empty() {
+        return None;
+    }
+    chunks.sort_by_key(|(sequence, _)| *sequence);
+    Some(chunks.into_iter().flat_map(|(_, data)| data.iter().copied()).collect())
+}
+
+// The profile's data color space from its header, such as "RGB" or "CMYK"
+fn icc_color_space(profile: &[u8]) -> Option<String> {
+    let space = profile.get(16..20)?;
+    Some(String::from_utf8_lossy(space).trim().to_string())
+}
+
+// The profile's description tag, as a v2 "desc" or a v4 "mluc" (its first record)
+fn icc_description(profile: &[u8]) -> Option<String> {
+    let be32 = |at: usize| profile.get(at..at + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize);
+    let count = be32(128)?;
+    let tag = (0..count.min(1024)).map(|index| 132 + index * 12)
+        .find(|&entry| profile.get(entry..entry + 4) == Some(b"desc"))?;
+    let start = be32(tag + 4)?;
+    let text = match profile.get(start..start + 4)? {
+        b"desc" => {
+            let length = be32(start + 8)?;
+            String::from_utf8_lossy(profile.get(start + 12..start + 12 + length)?).to_string()
+        }
+        b"mluc" => {
+            let (length, offset) = (be32(start + 20)?, be32(start + 24)?);
+            let utf16: Vec<u16> = profile.get(start + offset..start + offset + length)?
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect();
+            String::from_utf16_lossy(&utf16)
+        }
+        _ => return None,
     };
-    println!("Size category: {}", size_category);
+    Some(text.trim_end_matches('\0').trim().to_string())
 }
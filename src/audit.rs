@@ -0,0 +1,280 @@
+// src/audit.rs
+//! Near-duplicate certificates left by batches run over differently spelled lists, such as
+//! `certificate_Jose_Garcia.png` and `certificate_José_García.png` in the same directory.
+//!
+//! [`audit_directory`] groups the certificates of each directory by [`fold_name`] of their file
+//! names and looks every file up in the nearest `manifest.json` above it, for the row and list
+//! that produced it; files no manifest records are audited too. [`set_aside`] moves all but one
+//! file of a group, with their previews and backs, into a [`DUPLICATES_DIR`] next to them, and
+//! their manifest entries into a manifest of its own there. Nothing is ever deleted.
+//!
+//! ```
+//! use certificate_maker::audit::fold_name;
+//!
+//! assert_eq!(fold_name("certificate_José_García.png"), fold_name("Certificate_Jose_Garcia.PNG"));
+//! assert_eq!(fold_name("Zoë  Ångström"), "zoe angstrom");
+//! assert_eq!(fold_name("Søren Łukasz Straße"), "soren lukasz strasse");
+//! assert_ne!(fold_name("certificate_Ana.png"), fold_name("certificate_Ana_1.png"));
+//! ```
+//!
+//! ```no_run
+//! use certificate_maker::audit::{audit_directory, set_aside};
+//!
+//! let report = audit_directory("certificates")?;
+//! for set in &report.sets {
+//!     // The suggested file to keep comes first
+//!     let moved = set_aside(set, 0)?;
+//!     println!("kept {}, set aside {}", set.files[0].path.display(), moved.len());
+//! }
+//! # Ok::<(), certificate_maker::error::CertError>(())
+//! ```
+use icu_normalizer::DecomposingNormalizerBorrowed;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use unicode_canonical_combining_class::{CanonicalCombiningClass, get_canonical_combining_class};
+
+use crate::error::{CertError, IoContext, Result};
+use crate::files::{list_files_with_extensions, next_free_path};
+use crate::manifest::{MANIFEST_FILENAME, Manifest, ManifestEntry};
+
+/// Where [`set_aside`] moves the duplicates of a directory's certificates, inside that directory.
+/// Files already in one are left out of audits.
+pub const DUPLICATES_DIR: &str = "_duplicates";
+
+/// Files [`audit_directory`] compares: certificates as PNG or PDF.
+pub const AUDITED_EXTENSIONS: [&str; 2] = ["png", "pdf"];
+
+/// `text` folded so names that only differ in accents, case, Unicode form or spacing are equal:
+/// compatibility-decomposed (NFKD) with the combining marks dropped, letters that don't
+/// decompose such as `ø`, `ł` and `ß` spelled out in ASCII, lowercased, and every run of spaces
+/// and underscores made one space.
+pub fn fold_name(text: &str) -> String {
+    let decomposed = DecomposingNormalizerBorrowed::new_nfkd().normalize(text);
+    let mut folded = String::with_capacity(decomposed.len());
+    let mut gap = false;
+    for c in decomposed.chars() {
+        if get_canonical_combining_class(c) != CanonicalCombiningClass::NotReordered {
+            continue;
+        }
+        if c.is_whitespace() || c == '_' {
+            gap = !folded.is_empty();
+            continue;
+        }
+        if gap {
+            folded.push(' ');
+            gap = false;
+        }
+        match c {
+            'ß' | 'ẞ' => folded.push_str("ss"),
+            'æ' | 'Æ' => folded.push_str("ae"),
+            'œ' | 'Œ' => folded.push_str("oe"),
+            'þ' | 'Þ' => folded.push_str("th"),
+            'ø' | 'Ø' => folded.push('o'),
+            'đ' | 'Đ' | 'ð' | 'Ð' => folded.push('d'),
+            'ł' | 'Ł' => folded.push('l'),
+            'ı' => folded.push('i'),
+            c => folded.extend(c.to_lowercase()),
+        }
+    }
+    folded
+}
+
+/// One file of a [`DuplicateSet`].
+#[derive(Debug, Clone)]
+pub struct AuditedFile {
+    pub path: PathBuf,
+    /// Directory of the manifest that governs the file: the nearest one above it.
+    pub manifest_dir: Option<PathBuf>,
+    /// What that manifest recorded for the file, including the row and list it came from;
+    /// `None` when no manifest records it.
+    pub entry: Option<ManifestEntry>,
+}
+
+/// Certificates in one directory whose names fold to the same [`key`](Self::key).
+#[derive(Debug, Clone)]
+pub struct DuplicateSet {
+    pub key: String,
+    /// The suggested one to keep first: recorded files before unrecorded ones, then the most
+    /// recently issued.
+    pub files: Vec<AuditedFile>,
+}
+
+/// Everything [`audit_directory`] found.
+#[derive(Debug, Clone, Default)]
+pub struct AuditReport {
+    pub dir: PathBuf,
+    /// Certificates compared, outside [`DUPLICATES_DIR`]s and not counting previews, backs
+    /// and other files made from the certificates.
+    pub scanned: usize,
+    /// How many of them no manifest records.
+    pub unrecorded: usize,
+    pub manifests: Vec<PathBuf>,
+    pub sets: Vec<DuplicateSet>,
+}
+
+/// A file [`set_aside`] moved.
+#[derive(Debug, Clone)]
+pub struct MovedFile {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+/// Group the certificates under `dir`, and in its subdirectories, whose names collide once
+/// folded with [`fold_name`]. Only files in the same directory are compared, so the same name
+/// in two group subdirectories isn't a duplicate.
+pub fn audit_directory(dir: impl AsRef<Path>) -> Result<AuditReport> {
+    let dir = dir.as_ref();
+    if !dir.is_dir() {
+        return Err(CertError::DirectoryNotFound { dir: dir.to_path_buf(), contents: "certificates" });
+    }
+    let in_duplicates = |path: &Path| path.components().any(|part| part.as_os_str() == DUPLICATES_DIR);
+
+    let mut manifests = Vec::new();
+    for path in list_files_with_extensions(dir, &["json"])? {
+        if path.file_name().is_some_and(|name| name == MANIFEST_FILENAME) && !in_duplicates(&path) {
+            let manifest_dir = dir.join(path.parent().unwrap_or(Path::new("")));
+            let manifest = Manifest::load_file(dir.join(&path))?;
+            manifests.push((manifest_dir, manifest));
+        }
+    }
+    // Deepest first, so the first one above a file is the nearest
+    manifests.sort_by_key(|(manifest_dir, _)| std::cmp::Reverse(manifest_dir.components().count()));
+
+    let mut report = AuditReport {
+        dir: dir.to_path_buf(),
+        manifests: manifests.iter().map(|(manifest_dir, _)| manifest_dir.join(MANIFEST_FILENAME)).collect(),
+        ..Default::default()
+    };
+    let indexed: Vec<(&PathBuf, ManifestPaths)> = manifests.iter()
+        .map(|(manifest_dir, manifest)| (manifest_dir, ManifestPaths::new(manifest)))
+        .collect();
+    let mut groups: BTreeMap<(PathBuf, String), Vec<AuditedFile>> = BTreeMap::new();
+    for path in list_files_with_extensions(dir, &AUDITED_EXTENSIONS)? {
+        if in_duplicates(&path) {
+            continue;
+        }
+        let path = dir.join(path);
+        let governing = indexed.iter().find(|(manifest_dir, _)| path.starts_with(manifest_dir));
+        let mut audited = AuditedFile { path: path.clone(), manifest_dir: None, entry: None };
+        if let Some((manifest_dir, paths)) = governing {
+            let relative = path.strip_prefix(manifest_dir).unwrap_or(&path);
+            if paths.made_from_certificates.contains(relative) {
+                continue;
+            }
+            audited.manifest_dir = Some((*manifest_dir).clone());
+            audited.entry = paths.entries.get(relative).map(|entry| (*entry).clone());
+        }
+        report.scanned += 1;
+        if audited.entry.is_none() {
+            report.unrecorded += 1;
+        }
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let parent = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        groups.entry((parent, fold_name(&file_name))).or_default().push(audited);
+    }
+
+    report.sets = groups.into_iter()
+        .filter(|(_, files)| files.len() > 1)
+        .map(|((_, key), mut files)| {
+            files.sort_by(|a, b| {
+                b.entry.is_some().cmp(&a.entry.is_some())
+                    .then_with(|| b.entry.as_ref().and_then(|e| e.issued).cmp(&a.entry.as_ref().and_then(|e| e.issued)))
+                    .then_with(|| a.path.cmp(&b.path))
+            });
+            DuplicateSet { key, files }
+        })
+        .collect();
+    Ok(report)
+}
+
+// A manifest's certificates, and the previews, backs, sheets, zips and PDFs it records as made
+// from them, keyed by every tail of their recorded paths. A file's path relative to the
+// manifest's directory is then looked up directly, wherever the directory was when it was
+// written; where two entries share a tail the first one wins.
+struct ManifestPaths<'a> {
+    entries: HashMap<PathBuf, &'a ManifestEntry>,
+    made_from_certificates: HashSet<PathBuf>,
+}
+
+impl<'a> ManifestPaths<'a> {
+    fn new(manifest: &'a Manifest) -> Self {
+        let mut entries = HashMap::new();
+        for entry in &manifest.entries {
+            for tail in tails(&entry.output_file) {
+                entries.entry(tail).or_insert(entry);
+            }
+        }
+        let made_from_certificates = manifest.entries.iter()
+            .flat_map(|entry| entry.preview.iter().chain(&entry.back))
+            .chain(manifest.last_batch.iter().flat_map(|record| &record.artifacts))
+            .flat_map(|artifact| tails(&artifact.path))
+            .collect();
+        ManifestPaths { entries, made_from_certificates }
+    }
+}
+
+// `a/b/c.png`, `b/c.png` and `c.png`: the paths `path` ends with
+fn tails(path: &Path) -> impl Iterator<Item = PathBuf> + '_ {
+    let components: Vec<_> = path.components().collect();
+    (0..components.len()).map(move |start| components[start..].iter().collect())
+}
+
+/// Keep `set.files[keep]` and move every other file of `set` into the [`DUPLICATES_DIR`] of
+/// its directory, along with its preview and back, with a `_1`, `_2`, ... added to a name
+/// that is already taken there. A recorded file's entry leaves its manifest, so later batches
+/// neither skip its row nor clean it up as stale, and is added to the manifest in
+/// [`DUPLICATES_DIR`] with its new path, so `verify` still recognises the file there.
+pub fn set_aside(set: &DuplicateSet, keep: usize) -> Result<Vec<MovedFile>> {
+    if keep >= set.files.len() {
+        return Err(CertError::InvalidSetAside(format!("there is no file {} among the {} duplicates of '{}'", keep + 1, set.files.len(), set.key)));
+    }
+    let mut moved = Vec::new();
+    for file in set.files.iter().enumerate().filter(|(index, _)| *index != keep).map(|(_, file)| file) {
+        let parent = file.path.parent().unwrap_or(Path::new(""));
+        let duplicates_dir = parent.join(DUPLICATES_DIR);
+        std::fs::create_dir_all(&duplicates_dir)
+            .io_context(|| format!("Failed to create {}", duplicates_dir.display()))?;
+
+        let to = move_into(&file.path, &duplicates_dir)?;
+        moved.push(MovedFile { from: file.path.clone(), to: to.clone() });
+        let (Some(manifest_dir), Some(entry)) = (&file.manifest_dir, &file.entry) else {
+            continue;
+        };
+
+        let mut set_aside_entry = entry.clone();
+        set_aside_entry.output_file = to;
+        // The preview and back are looked for next to the certificate, where they are written
+        for (artifact, recorded) in [(&mut set_aside_entry.preview, &entry.preview), (&mut set_aside_entry.back, &entry.back)] {
+            if let (Some(artifact), Some(recorded)) = (artifact, recorded) {
+                let beside = parent.join(recorded.path.file_name().unwrap_or_default());
+                if beside.exists() {
+                    artifact.path = move_into(&beside, &duplicates_dir)?;
+                    moved.push(MovedFile { from: beside, to: artifact.path.clone() });
+                }
+            }
+        }
+
+        let mut manifest = Manifest::load(manifest_dir)?;
+        manifest.entries.retain(|recorded| recorded.output_file != entry.output_file);
+        if let Some(record) = &mut manifest.last_batch {
+            record.outputs.retain(|output| *output != entry.output_file);
+        }
+        manifest.save(manifest_dir)?;
+
+        let mut duplicates = Manifest::load(&duplicates_dir)?;
+        duplicates.entries.push(set_aside_entry);
+        duplicates.save(&duplicates_dir)?;
+    }
+    Ok(moved)
+}
+
+// `file` moved into `dir` under the first free name
+fn move_into(file: &Path, dir: &Path) -> Result<PathBuf> {
+    let mut to = dir.join(file.file_name().unwrap_or_default());
+    if to.exists() {
+        to = next_free_path(&to, Path::exists);
+    }
+    std::fs::rename(file, &to)
+        .io_context(|| format!("Failed to move {} to {}", file.display(), to.display()))?;
+    Ok(to)
+}
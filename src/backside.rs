@@ -0,0 +1,148 @@
+// src/backside.rs
+//! Double-sided certificates: a back with its own template and fields, such as the terms and
+//! a verification code, filled in from the same row as the front so both sides always belong
+//! to the same recipient.
+//!
+//! Saved as PNGs, the sides become `certificate_<name>_front.png` and `certificate_<name>_back.png`;
+//! saved as a PDF ([`CertificateFormat::Pdf`](crate::job::CertificateFormat::Pdf)), they are
+//! the two pages of one `certificate_<name>.pdf`. The back is recorded in the front's manifest
+//! entry, so undo, `verify` and stale-file cleanup treat the pair as one certificate.
+//!
+//! ```
+//! use certificate_maker::backside::{BackSide, back_path, front_path};
+//! use certificate_maker::barcode::BarcodeStyle;
+//! use certificate_maker::graphics::{GraphicField, GraphicKind};
+//! use certificate_maker::job::{Anchor, CertificateJob};
+//! use certificate_maker::source::Record;
+//! use std::path::Path;
+//!
+//! let code = GraphicField {
+//!     kind: GraphicKind::Code128(BarcodeStyle::default()),
+//!     data: "ID-{Id}".to_string(),
+//!     x: 300,
+//!     y: 200,
+//!     anchor: Anchor::Center,
+//! };
+//! let record = Record { name: "Ada Lovelace".to_string(), fields: vec![("Id".to_string(), "0042".to_string())] };
+//! let job = CertificateJob::new("tests/fixtures/template.png", vec!["Ada Lovelace".to_string()])
+//!     .font("tests/fixtures/DejaVuSans-Latin.ttf")
+//!     .records(vec![record])
+//!     .back(BackSide::new("tests/fixtures/template.png").graphics(vec![code]));
+//! let back = job.render_back_image(0)?.expect("the job has a back");
+//! assert_eq!(back.get_pixel(300, 200).0[..3], [0, 0, 0]);
+//!
+//! let front = front_path(Path::new("certs/certificate_Ada.png"));
+//! assert_eq!(front, Path::new("certs/certificate_Ada_front.png"));
+//! assert_eq!(back_path(&front), Path::new("certs/certificate_Ada_back.png"));
+//! assert_eq!(back_path(Path::new("certs/certificate_Ada_front_1.png")), Path::new("certs/certificate_Ada_back_1.png"));
+//! # Ok::<(), certificate_maker::error::CertError>(())
+//! ```
+use image::RgbaImage;
+use std::path::{Path, PathBuf};
+
+use crate::editpng::{BorderStyle, draw_border, load_template};
+use crate::error::Result;
+use crate::graphics::GraphicField;
+use crate::normalize::NormalizePlan;
+use crate::orientation::oriented_dimensions;
+use crate::patch::{Patch, apply_patches};
+
+/// Added to the file name of the front of a double-sided certificate saved as PNG.
+pub const FRONT_SUFFIX: &str = "_front";
+
+/// Added to the file name of the back, in place of [`FRONT_SUFFIX`].
+pub const BACK_SUFFIX: &str = "_back";
+
+/// The back of a double-sided certificate. It has no name of its own: its graphic fields are
+/// filled in from the front's row, `{Name}` included, so a code on the back matches the front.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackSide {
+    pub template: PathBuf,
+    pub graphics: Vec<GraphicField>,
+    /// Painted over the back's template first, as the front's are.
+    pub patches: Vec<Patch>,
+    /// Drawn on the back's template before its graphics, as the front's are.
+    pub borders: Vec<BorderStyle>,
+}
+
+impl BackSide {
+    /// A blank back: just the template.
+    pub fn new(template: impl Into<PathBuf>) -> Self {
+        BackSide { template: template.into(), graphics: Vec::new(), patches: Vec::new(), borders: Vec::new() }
+    }
+
+    pub fn graphics(mut self, graphics: Vec<GraphicField>) -> Self {
+        self.graphics = graphics;
+        self
+    }
+
+    pub fn patches(mut self, patches: Vec<Patch>) -> Self {
+        self.patches = patches;
+        self
+    }
+
+    pub fn borders(mut self, borders: Vec<BorderStyle>) -> Self {
+        self.borders = borders;
+        self
+    }
+
+    /// Check the template, the graphics, and that the patches and borders fit, returning every problem,
+    /// each saying it is about the back.
+    pub fn validate(&self) -> std::result::Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+        match oriented_dimensions(&self.template) {
+            Ok((width, height)) => {
+                for patch in &self.patches {
+                    if let Err(e) = patch.validate(width, height) {
+                        problems.push(format!("Back side: {}", e));
+                    }
+                }
+                for border in &self.borders {
+                    if let Err(e) = border.validate(width, height) {
+                        problems.push(format!("Back side: {}", e));
+                    }
+                }
+            }
+            Err(e) => problems.push(format!("Back template is not a readable image: {} ({})", self.template.display(), e)),
+        }
+        for graphic in &self.graphics {
+            if let Err(e) = graphic.validate() {
+                problems.push(format!("Back side: {}", e));
+            }
+        }
+        if problems.is_empty() { Ok(()) } else { Err(problems) }
+    }
+
+    // The decoded template, patched, normalized by `plan` when there is one, and with the
+    // borders drawn on it
+    pub(crate) fn load_template(&self, plan: Option<&NormalizePlan>) -> Result<RgbaImage> {
+        let mut template = load_template(&self.template)?;
+        apply_patches(&mut template, &self.patches)?;
+        let mut template = match plan {
+            Some(plan) => plan.apply(&template),
+            None => template,
+        };
+        for border in &self.borders {
+            draw_border(&mut template, border)?;
+        }
+        Ok(template)
+    }
+}
+
+/// Where the front of a double-sided certificate goes when it is saved as PNG: `certificate`,
+/// the single-sided path, with [`FRONT_SUFFIX`] added to its name.
+pub fn front_path(certificate: &Path) -> PathBuf {
+    let stem = certificate.file_stem().unwrap_or_default().to_string_lossy();
+    certificate.with_file_name(format!("{}{}.png", stem, FRONT_SUFFIX))
+}
+
+/// Where the back that goes with `front` is saved: the last [`FRONT_SUFFIX`] of its name
+/// becomes [`BACK_SUFFIX`], so a renamed `_front_1` gets a `_back_1`.
+pub fn back_path(front: &Path) -> PathBuf {
+    let stem = front.file_stem().unwrap_or_default().to_string_lossy();
+    let back = match stem.rfind(FRONT_SUFFIX) {
+        Some(at) => format!("{}{}{}", &stem[..at], BACK_SUFFIX, &stem[at + FRONT_SUFFIX.len()..]),
+        None => format!("{}{}", stem, BACK_SUFFIX),
+    };
+    front.with_file_name(format!("{}.png", back))
+}
@@ -0,0 +1,213 @@
+// src/barcode.rs
+//! Code128 barcodes, drawn as black bars on white for badge printers that scan 1D codes.
+//!
+//! ```
+//! use certificate_maker::barcode::{BarcodeStyle, encode_code128, render_code128};
+//!
+//! // Digits are packed two per symbol: start, 6 pairs, check digit and stop
+//! let modules = encode_code128("202600001234")?;
+//! assert_eq!(modules.len(), 11 * 8 + 13);
+//!
+//! let style = BarcodeStyle { module_width: 2, height: 60, ..BarcodeStyle::default() };
+//! let image = render_code128("202600001234", &style, None)?;
+//! assert_eq!((image.width(), image.height()), ((11 * 8 + 13 + 20) * 2, 60));
+//! # Ok::<(), certificate_maker::error::CertError>(())
+//! ```
+use image::{Rgba, RgbaImage};
+use rusttype::Font;
+use serde::{Deserialize, Serialize};
+
+use crate::editpng::{Anchor, SyntheticStyle, TextOptions, draw_text, layout_text};
+use crate::error::{CertError, Result};
+
+// Bar and space widths of every symbol value, 0 to 105, then the stop pattern
+const PATTERNS: [&str; 107] = [
+    "212222", "222122", "222221", "121223", "121322", "131222", "122213", "122312", "132212", "221213",
+    "221312", "231212", "112232", "122132", "122231", "113222", "123122", "123221", "223211", "221132",
+    "221231", "213212", "223112", "312131", "311222", "321122", "321221", "312212", "322112", "322211",
+    "212123", "212321", "232121", "111323", "131123", "131321", "112313", "132113", "132311", "211313",
+    "231113", "231311", "112133", "112331", "132131", "113123", "113321", "133121", "313121", "211331",
+    "231131", "213113", "213311", "213131", "311123", "311321", "331121", "312113", "312311", "332111",
+    "314111", "221411", "431111", "111224", "111422", "121124", "121421", "141122", "141221", "112214",
+    "112412", "122114", "122411", "142112", "142211", "241211", "221114", "413111", "241112", "134111",
+    "111242", "121142", "121241", "114212", "124112", "124211", "411212", "421112", "421211", "212141",
+    "214121", "412121", "111143", "111341", "131141", "114113", "114311", "411113", "411311", "113141",
+    "114131", "311141", "411131", "211412", "211214", "211232", "2331112",
+];
+const CODE_C: u8 = 99;
+const CODE_B: u8 = 100;
+const CODE_A: u8 = 101;
+const START: [u8; 3] = [103, 104, 105];
+const STOP: usize = 106;
+/// Blank modules required on each side for scanners to find the code.
+pub const QUIET_ZONE_MODULES: u32 = 10;
+
+/// How big a barcode is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BarcodeStyle {
+    /// Width of the narrowest bar, in pixels.
+    pub module_width: u32,
+    /// Height of the bars, in pixels.
+    pub height: u32,
+    /// Pixel size of the human-readable data under the bars; `None` leaves it out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_size: Option<f32>,
+}
+
+impl Default for BarcodeStyle {
+    fn default() -> Self {
+        BarcodeStyle { module_width: 2, height: 60, text_size: None }
+    }
+}
+
+impl BarcodeStyle {
+    /// Check the sizes are drawable.
+    pub fn validate(&self) -> Result<()> {
+        if self.module_width == 0 || self.height == 0 {
+            return Err(CertError::InvalidBarcode("the module width and bar height must be at least 1 pixel".to_string()));
+        }
+        if let Some(size) = self.text_size
+            && (!size.is_finite() || size <= 0.0) {
+            return Err(CertError::InvalidBarcode(format!("the text size must be a positive number, got {}", size)));
+        }
+        Ok(())
+    }
+}
+
+// Code sets: A has control characters and uppercase, B printable ASCII, C digit pairs
+#[derive(Clone, Copy, PartialEq)]
+enum CodeSet {
+    A,
+    B,
+    C,
+}
+
+/// Encode `data` as Code128 modules, `true` for black, from the start symbol to the stop
+/// pattern, without quiet zones. Runs of four or more digits switch to code set C, which packs
+/// two digits per symbol. Only ASCII can be encoded.
+pub fn encode_code128(data: &str) -> Result<Vec<bool>> {
+    if data.is_empty() {
+        return Err(CertError::InvalidBarcode("there is no data to encode".to_string()));
+    }
+    if let Some(c) = data.chars().find(|c| !c.is_ascii()) {
+        return Err(CertError::InvalidBarcode(format!("'{}' has '{}', but Code128 only encodes ASCII", data, c)));
+    }
+
+    let bytes = data.as_bytes();
+    let digits_from = |start: usize| bytes[start..].iter().take_while(|b| b.is_ascii_digit()).count();
+    let text_set = |b: u8| if b < 32 { CodeSet::A } else { CodeSet::B };
+    // Four digits gain a symbol in set C; at the very start two do, when they are all there is
+    let wants_c = |start: usize| {
+        let run = digits_from(start);
+        run >= 4 || (start == 0 && run == bytes.len() && run % 2 == 0)
+    };
+
+    let mut set = if wants_c(0) { CodeSet::C } else { text_set(bytes[0]) };
+    let mut values = vec![START[set as usize]];
+    let mut i = 0;
+    while i < bytes.len() {
+        if set != CodeSet::C && wants_c(i) {
+            // An odd run keeps its first digit in the current set
+            if digits_from(i) % 2 == 1 {
+                values.push(text_value(bytes[i], set));
+                i += 1;
+            }
+            values.push(CODE_C);
+            set = CodeSet::C;
+        }
+        if set == CodeSet::C {
+            if digits_from(i) >= 2 {
+                values.push((bytes[i] - b'0') * 10 + (bytes[i + 1] - b'0'));
+                i += 2;
+                continue;
+            }
+            set = text_set(bytes[i]);
+            values.push(if set == CodeSet::A { CODE_A } else { CODE_B });
+        }
+        // Lowercase can't be written in A, nor control characters in B
+        let needed = match bytes[i] {
+            0..=31 => CodeSet::A,
+            96..=127 => CodeSet::B,
+            _ => set,
+        };
+        if needed != set {
+            values.push(if needed == CodeSet::A { CODE_A } else { CODE_B });
+            set = needed;
+        }
+        values.push(text_value(bytes[i], set));
+        i += 1;
+    }
+
+    let check = values.iter().enumerate()
+        .map(|(position, &value)| position.max(1) * value as usize)
+        .sum::<usize>() % 103;
+    values.push(check as u8);
+
+    let mut modules = Vec::with_capacity((values.len() + 1) * 11 + 2);
+    for pattern in values.iter().map(|&value| PATTERNS[value as usize]).chain([PATTERNS[STOP]]) {
+        for (index, width) in pattern.bytes().enumerate() {
+            modules.extend(std::iter::repeat_n(index % 2 == 0, (width - b'0') as usize));
+        }
+    }
+    Ok(modules)
+}
+
+// Symbol value of an ASCII byte in code set A or B
+fn text_value(byte: u8, set: CodeSet) -> u8 {
+    match (set, byte) {
+        (CodeSet::A, 0..=31) => byte + 64,
+        _ => byte - 32,
+    }
+}
+
+/// Draw `data` as a Code128 barcode with its quiet zones, black on white; with a text size and
+/// a font, the data is written centered under the bars.
+pub fn render_code128(data: &str, style: &BarcodeStyle, font: Option<&Font>) -> Result<RgbaImage> {
+    style.validate()?;
+    let modules = encode_code128(data)?;
+    let quiet = QUIET_ZONE_MODULES as usize;
+    let width = (modules.len() + 2 * quiet) as u32 * style.module_width;
+
+    let label = match (style.text_size, font) {
+        (Some(size), Some(font)) => {
+            let options = TextOptions {
+                font,
+                size,
+                color: Rgba([0, 0, 0, 255]),
+                x: width as i32 / 2,
+                y: 0,
+                anchor: Anchor::TopLeft,
+                decorations: &[],
+                synthetic: SyntheticStyle::default(),
+                variation: None,
+                effect: None,
+            };
+            Some(options)
+        }
+        _ => None,
+    };
+    let text_height = match &label {
+        Some(options) => layout_text(data, options)?.height as u32 + style.module_width * 2,
+        None => 0,
+    };
+
+    let mut image = RgbaImage::from_pixel(width, style.height + text_height, Rgba([255, 255, 255, 255]));
+    for (index, _) in modules.iter().enumerate().filter(|(_, black)| **black) {
+        let left = (index + quiet) as u32 * style.module_width;
+        for x in left..left + style.module_width {
+            for y in 0..style.height {
+                image.put_pixel(x, y, Rgba([0, 0, 0, 255]));
+            }
+        }
+    }
+    if let Some(options) = label {
+        let bounds = layout_text(data, &options)?;
+        let options = TextOptions {
+            x: (width as i32 - bounds.width) / 2,
+            y: (style.height + style.module_width * 2) as i32,
+            ..options
+        };
+        draw_text(&mut image, data, &options)?;
+    }
+    Ok(image)
+}
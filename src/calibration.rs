@@ -0,0 +1,156 @@
+// src/calibration.rs
+//! One render that shows whether every name of a batch will fit: the longest and shortest
+//! names and a ruler of wide letters, drawn over each other at the batch's settings.
+//!
+//! Everything is measured and drawn with the same [`layout_text`] and [`draw_text`] the batch
+//! uses, so the sheet shows exactly where the real certificates put their text.
+//!
+//! ```no_run
+//! use certificate_maker::calibration::render_calibration_sheet;
+//! use certificate_maker::csvexcelparser::BatchSettings;
+//!
+//! let settings = BatchSettings::load_last_run()?.unwrap_or_default();
+//! let names = vec!["Alice Johnson".to_string(), "Bo".to_string()];
+//! let sheet = render_calibration_sheet(&settings, &names, "output")?;
+//! for sample in sheet.samples.iter().filter(|sample| !sample.fits) {
+//!     println!("{} runs off the template", sample.text);
+//! }
+//! # Ok::<(), certificate_maker::error::CertError>(())
+//! ```
+use image::{ImageFormat, Rgba, RgbaImage};
+use imageproc::drawing::{draw_hollow_rect_mut, draw_line_segment_mut};
+use imageproc::rect::Rect;
+use std::path::{Path, PathBuf};
+
+use crate::csvexcelparser::BatchSettings;
+use crate::editpng::{Anchor, TextBounds, TextOptions, draw_text, hex_to_rgba, layout_text, load_font, load_template, save_image};
+use crate::error::{CertError, IoContext, Result};
+use crate::variation::load_font_instance;
+
+/// Distance between tick marks on the rulers, in pixels.
+pub const TICK_SPACING: i32 = 50;
+
+const RULER_COLOR: Rgba<u8> = Rgba([46, 125, 50, 255]);
+const LONGEST_COLOR: Rgba<u8> = Rgba([198, 40, 40, 255]);
+const SHORTEST_COLOR: Rgba<u8> = Rgba([21, 101, 192, 255]);
+const TICK_COLOR: Rgba<u8> = Rgba([60, 60, 60, 255]);
+// Gap between the text and the rulers around it
+const MARGIN: i32 = 20;
+const LONG_TICK: i32 = 12;
+
+/// One piece of text on the sheet and where it landed.
+#[derive(Debug, Clone)]
+pub struct CalibrationSample {
+    /// `longest`, `shortest` or `ruler`.
+    pub label: &'static str,
+    pub text: String,
+    pub color: Rgba<u8>,
+    pub bounds: TextBounds,
+    /// Whether the whole text lies on the template.
+    pub fits: bool,
+}
+
+/// What [`render_calibration_sheet`] drew, and where it saved it.
+#[derive(Debug, Clone)]
+pub struct CalibrationSheet {
+    pub path: PathBuf,
+    /// The ruler, then the longest and the shortest name, in drawing order.
+    pub samples: Vec<CalibrationSample>,
+}
+
+/// `<output_dir>/<template name>_calibration.png` for a template.
+pub fn calibration_path(output_dir: impl AsRef<Path>, template: impl AsRef<Path>) -> PathBuf {
+    let stem = template.as_ref().file_stem().unwrap_or_default().to_string_lossy();
+    output_dir.as_ref().join(format!("{}_calibration.png", stem))
+}
+
+/// Draw the widest and narrowest of `names` (after the name case), and a ruler of as many `M`s
+/// as the longest name has characters, over each other at the settings' position, size and
+/// font, each in its own color and outlined. Tick marks every [`TICK_SPACING`] pixels run
+/// below and left of the text. Saved to [`calibration_path`] in `output_dir`.
+pub fn render_calibration_sheet(settings: &BatchSettings, names: &[String], output_dir: impl AsRef<Path>) -> Result<CalibrationSheet> {
+    let font = load_font(&settings.font_filename)?;
+    let instance = load_font_instance(&settings.font_filename, &settings.font_variation)?;
+    let options = TextOptions {
+        font: &font,
+        size: settings.font_size,
+        color: hex_to_rgba(&settings.hex_color)?,
+        x: settings.x_pos,
+        y: settings.y_pos,
+        anchor: Anchor::Center,
+        decorations: &[],
+        synthetic: settings.synthetic,
+        variation: instance.as_ref(),
+        effect: None,
+    };
+
+    // Widest and narrowest as drawn, not by character count
+    let mut measured = Vec::new();
+    for name in names {
+        let text = settings.typography.apply(&settings.name_case.apply(name));
+        let bounds = layout_text(&text, &options)?;
+        measured.push((text, bounds));
+    }
+    let (Some(longest), Some(shortest)) = (
+        measured.iter().max_by_key(|(_, bounds)| bounds.width),
+        measured.iter().min_by_key(|(_, bounds)| bounds.width),
+    ) else {
+        return Err(CertError::InvalidJob(vec!["a calibration sheet needs at least one name".to_string()]));
+    };
+    let ruler = "M".repeat(longest.0.chars().count().max(1));
+
+    let mut img = load_template(&settings.template_file)?;
+    let mut samples = Vec::new();
+    // The ruler goes underneath, so the names stay readable on top of it
+    for (label, text, color) in [
+        ("ruler", ruler.as_str(), RULER_COLOR),
+        ("longest", longest.0.as_str(), LONGEST_COLOR),
+        ("shortest", shortest.0.as_str(), SHORTEST_COLOR),
+    ] {
+        let bounds = draw_text(&mut img, text, &TextOptions { color, ..options })?;
+        if bounds.width > 0 && bounds.height > 0 {
+            draw_hollow_rect_mut(&mut img, Rect::at(bounds.x, bounds.y).of_size(bounds.width as u32, bounds.height as u32), color);
+        }
+        samples.push(CalibrationSample {
+            label,
+            text: text.to_string(),
+            color,
+            bounds,
+            fits: bounds.fits_within(img.width(), img.height()),
+        });
+    }
+    draw_rulers(&mut img, &samples);
+
+    let output_dir = output_dir.as_ref();
+    std::fs::create_dir_all(output_dir)
+        .io_context(|| format!("Failed to create directory: {}", output_dir.display()))?;
+    let path = calibration_path(output_dir, &settings.template_file);
+    save_image(&img, &path, ImageFormat::Png)?;
+    Ok(CalibrationSheet { path, samples })
+}
+
+// A horizontal ruler below and a vertical one left of everything drawn, with a tick every
+// TICK_SPACING pixels of the template's coordinates and a longer one every second tick. Text
+// running off the template pushes the rulers to its edges rather than off it
+fn draw_rulers(img: &mut RgbaImage, samples: &[CalibrationSample]) {
+    let (width, height) = (img.width() as i32, img.height() as i32);
+    let left = (samples.iter().map(|sample| sample.bounds.x).min().unwrap_or(0) - MARGIN).max(LONG_TICK);
+    let right = (samples.iter().map(|sample| sample.bounds.x + sample.bounds.width).max().unwrap_or(0) + MARGIN).min(width - 1);
+    let top = (samples.iter().map(|sample| sample.bounds.y).min().unwrap_or(0) - MARGIN).max(0);
+    let bottom = (samples.iter().map(|sample| sample.bounds.bottom()).max().unwrap_or(0) + MARGIN).min(height - 1 - LONG_TICK);
+
+    let line = |img: &mut RgbaImage, from: (i32, i32), to: (i32, i32)| {
+        draw_line_segment_mut(img, (from.0 as f32, from.1 as f32), (to.0 as f32, to.1 as f32), TICK_COLOR);
+    };
+    line(img, (left, bottom), (right, bottom));
+    line(img, (left, top), (left, bottom));
+
+    let tick_length = |position: i32| if position % (TICK_SPACING * 2) == 0 { LONG_TICK } else { LONG_TICK / 2 };
+    let first_tick = |start: i32| start.div_euclid(TICK_SPACING) * TICK_SPACING + TICK_SPACING;
+    for x in (first_tick(left)..right).step_by(TICK_SPACING as usize) {
+        line(img, (x, bottom), (x, bottom + tick_length(x)));
+    }
+    for y in (first_tick(top)..bottom).step_by(TICK_SPACING as usize) {
+        line(img, (left, y), (left - tick_length(y), y));
+    }
+}
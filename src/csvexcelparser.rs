@@ -1,630 +1,1461 @@
 // src/csvexcelparser.rs
-use anyhow::{Context, Result};
-use csv::ReaderBuilder;
-use std::fs::File;
-use std::path::Path;
-use std::io::{self, Write};
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
-use rayon::prelude::*;
-use rusttype::{Font, Scale, point};
-
-use crate::editpng::add_text_with_custom_options;
-use crate::analysis::analyze_png_file;
-
-
-// Function to get user input
-fn get_user_input(prompt: &str) -> String {
-    print!("{}", prompt);
-    io::stdout().flush().unwrap();
-    
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
-    input.trim().to_string()
-}
-
-// Parse CSV file and extract names with better error handling and debugging
-pub fn parse_csv_names(file_path: &str) -> Result<Vec<String>> {
-    let file = File::open(file_path)
-        .with_context(|| format!("Failed to open CSV file: {}", file_path))?;
-    
-    let mut reader = ReaderBuilder::new()
-        .has_headers(true)
-        .from_reader(file);
-    
-    // First, let's check the headers
-    let headers = reader.headers()
-        .with_context(|| "Failed to read CSV headers")?;
-    
-    println!("📋 CSV Headers found: {:?}", headers);
-    
-    // Look for name column (case insensitive)
-    let mut name_column_index = None;
-    for (index, header) in headers.iter().enumerate() {
-        if header.trim().to_lowercase() == "name" {
-            name_column_index = Some(index);
-            break;
+//! Name list parsing and batch certificate generation.
+use crate::error::{CertError, IoContext, Result};
+use crate::depth::{DeepColor, DepthPolicy};
+use csv::{ReaderBuilder, StringRecord};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use image::{Rgba, RgbaImage};
+use crate::editpng::{PngCompression, SyntheticStyle, TextBounds, TextOptions, encode_png, layout_text, load_font, load_template, render_text_on};
+use crate::effects::find_effect;
+use crate::variation::{AxisValue, load_font_instance};
+use crate::analysis::{measure_ink_coverage, InkCoverage};
+use crate::files::{list_files_with_extensions, local_staging_dir, portable_file_name};
+use crate::fingerprint::{Fingerprint, FingerprintMode};
+use crate::fontsize::{FontSizeCheck, FontSizeLimits};
+use crate::graphics::GraphicField;
+use crate::duplicates::DuplicateDecision;
+use crate::job::{Anchor, CertificateFormat, CertificateJob, OverwritePolicy};
+use crate::imposition::{ImposedSheets, Imposition};
+use crate::manifest::{EllipsizedText, ManifestEntry};
+use crate::memory::MemoryPlan;
+use crate::namecase::NameCase;
+use crate::normalize::{NormalizePlan, Normalization};
+use crate::patch::{Patch, apply_patches};
+use crate::watermark::WatermarkStyle;
+use crate::sanitize::{FlaggedValue, clean_value};
+use crate::selection::RowSelection;
+use crate::progress::{FnSink, ProgressSink};
+use crate::source::{NameColumn, Record, source_for_path_with};
+use crate::timing::{PerformanceBreakdown, StageTimings};
+use crate::typography::{RetouchedText, Typography};
+
+/// Default directory for CSV files (see [`crate::paths::AppPaths`]).
+pub const CSV_DIR: &str = "excelcsvs";
+/// Default directory for templates (see [`crate::paths::AppPaths`]).
+pub const TEMPLATE_DIR: &str = "Template";
+
+/// A data row that did not produce a name, with the reason.
+#[derive(Debug, Clone)]
+pub struct SkippedRow {
+    /// 1-based line number in the file (the header is line 1).
+    pub row: usize,
+    pub reason: String,
+}
+
+/// A data row with more or fewer fields than the header, e.g. from a trailing comma.
+#[derive(Debug, Clone)]
+pub struct MalformedRow {
+    /// 1-based line number in the file (the header is line 1).
+    pub row: usize,
+    pub fields: usize,
+    /// Whether the row still produced a name.
+    pub recovered: bool,
+}
+
+/// Everything learned while parsing a name list: a CSV, or a JSON or text file read into the
+/// same shape (see [`parse_name_list_with`]).
+#[derive(Debug, Clone)]
+pub struct ParsedCsv {
+    /// The file that was parsed.
+    pub path: PathBuf,
+    pub headers: Vec<String>,
+    pub name_column: usize,
+    pub names: Vec<String>,
+    /// 1-based line number of each name (its position in the array for JSON), in the same
+    /// order as `names`.
+    pub rows: Vec<usize>,
+    /// Every column of each row that produced a name, in the same order as `names`.
+    pub records: Vec<Record>,
+    pub skipped_rows: Vec<SkippedRow>,
+    /// Rows whose field count differs from the header's, recovered or not.
+    pub malformed_rows: Vec<MalformedRow>,
+    /// Values of the rows that produced a name that had control characters removed, start like
+    /// a spreadsheet formula or are very long; see [`crate::sanitize`].
+    pub flagged_values: Vec<FlaggedValue>,
+    /// How the file was read; `None` for JSON and text lists.
+    pub format: Option<DetectedFormat>,
+}
+
+/// Text encodings a CSV file can be read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CsvEncoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// What Excel writes on most Western European Windows systems.
+    Windows1252,
+}
+
+impl CsvEncoding {
+    fn encoding(self) -> &'static encoding_rs::Encoding {
+        match self {
+            CsvEncoding::Utf8 => encoding_rs::UTF_8,
+            CsvEncoding::Utf16Le => encoding_rs::UTF_16LE,
+            CsvEncoding::Utf16Be => encoding_rs::UTF_16BE,
+            CsvEncoding::Windows1252 => encoding_rs::WINDOWS_1252,
         }
     }
-    
-    if name_column_index.is_none() {
-        println!("❌ Available columns: {:?}", headers);
-        return Err(anyhow::anyhow!("No 'Name' column found. Make sure your CSV has a column named 'Name'"));
+
+    /// Standard name of the encoding, e.g. `UTF-8` or `windows-1252`.
+    pub fn name(self) -> &'static str {
+        self.encoding().name()
     }
-    
-    let name_col_index = name_column_index.unwrap();
-    println!("✅ Found 'Name' column at index {}", name_col_index);
-    
-    let mut names = Vec::new();
-    
-    // Parse records manually instead of using serde
-    for (row_num, result) in reader.records().enumerate() {
-        match result {
-            Ok(record) => {
-                if let Some(name_field) = record.get(name_col_index) {
-                    let name = name_field.trim().to_string();
-                    if !name.is_empty() {
-                        names.push(name);
-                        println!("  Row {}: '{}'", row_num + 2, names.last().unwrap()); // +2 because of header and 0-indexing
-                    } else {
-                        println!("  Row {}: Empty name, skipping", row_num + 2);
-                    }
-                } else {
-                    println!("  Row {}: No data in name column", row_num + 2);
-                }
+
+    /// Parse a label such as `utf-8`, `utf-16le`, `cp1252` or `latin1`.
+    ///
+    /// ```
+    /// use certificate_maker::csvexcelparser::CsvEncoding;
+    /// assert_eq!(CsvEncoding::from_label("cp1252"), Some(CsvEncoding::Windows1252));
+    /// assert_eq!(CsvEncoding::from_label("UTF-16LE"), Some(CsvEncoding::Utf16Le));
+    /// assert_eq!(CsvEncoding::from_label("ebcdic"), None);
+    /// ```
+    pub fn from_label(label: &str) -> Option<Self> {
+        let encoding = encoding_rs::Encoding::for_label(label.trim().as_bytes())?;
+        [CsvEncoding::Utf8, CsvEncoding::Utf16Le, CsvEncoding::Utf16Be, CsvEncoding::Windows1252]
+            .into_iter()
+            .find(|candidate| candidate.encoding() == encoding)
+    }
+
+    // Byte order mark first, then the zero bytes ASCII leaves in UTF-16, then whether it is valid UTF-8
+    fn detect(bytes: &[u8]) -> Self {
+        if let Some((encoding, _)) = encoding_rs::Encoding::for_bom(bytes) {
+            if encoding == encoding_rs::UTF_16LE {
+                return CsvEncoding::Utf16Le;
             }
-            Err(e) => {
-                println!("❌ Error reading row {}: {}", row_num + 2, e);
-                println!("💡 This might be due to formatting issues in your CSV");
+            if encoding == encoding_rs::UTF_16BE {
+                return CsvEncoding::Utf16Be;
             }
+            return CsvEncoding::Utf8;
+        }
+
+        let sample = &bytes[..bytes.len().min(1024)];
+        let pairs = sample.len() / 2;
+        let zeros = |offset: usize| sample.iter().skip(offset).step_by(2).filter(|&&byte| byte == 0).count();
+        if pairs > 0 && zeros(1) * 2 > pairs {
+            CsvEncoding::Utf16Le
+        } else if pairs > 0 && zeros(0) * 2 > pairs {
+            CsvEncoding::Utf16Be
+        } else if std::str::from_utf8(bytes).is_ok() {
+            CsvEncoding::Utf8
+        } else {
+            CsvEncoding::Windows1252
         }
     }
-    
-    if names.is_empty() {
-        return Err(anyhow::anyhow!("No valid names found in CSV file"));
-    }
-    
-    println!("✅ Successfully parsed {} names", names.len());
-    Ok(names)
 }
 
-// Function to debug CSV file contents
-pub fn debug_csv_file(file_path: &str) -> Result<()> {
-    println!("\n🔍 === CSV File Debug Info ===");
-    
-    // Read raw file content first
-    let content = std::fs::read_to_string(file_path)
-        .with_context(|| format!("Failed to read file: {}", file_path))?;
-    
-    println!("📄 File size: {} bytes", content.len());
-    println!("📄 First 200 characters:");
-    println!("{}", content.chars().take(200).collect::<String>());
-    
-    if content.len() > 200 {
-        println!("... (truncated)");
-    }
-    
-    // Count lines
-    let lines: Vec<&str> = content.lines().collect();
-    println!("📄 Total lines: {}", lines.len());
-    
-    if !lines.is_empty() {
-        println!("📄 First line (header): '{}'", lines[0]);
-        if lines.len() > 1 {
-            println!("📄 Second line (first data): '{}'", lines[1]);
+/// Delimiters tried when sniffing a CSV, in order of preference.
+pub const CSV_DELIMITERS: [u8; 4] = [b',', b';', b'\t', b'|'];
+
+/// Manual overrides for reading a CSV; whatever is `None` is detected from the file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CsvFormat {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delimiter: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<CsvEncoding>,
+}
+
+impl CsvFormat {
+    /// Fill in the settings this format leaves open, overrides first.
+    pub fn or(self, other: CsvFormat) -> CsvFormat {
+        CsvFormat {
+            delimiter: self.delimiter.or(other.delimiter),
+            encoding: self.encoding.or(other.encoding),
         }
     }
-    
-    // Try to parse with CSV reader
-    let file = File::open(file_path)?;
-    let mut reader = ReaderBuilder::new()
+}
+
+/// The delimiter and encoding a CSV was actually read with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DetectedFormat {
+    pub delimiter: u8,
+    pub encoding: CsvEncoding,
+}
+
+/// Sniff the delimiter from the first lines of `text`.
+///
+/// A candidate must appear in the header line (quoted text aside). Each is scored by how many
+/// of the sampled lines have it as often as the header does, so a ragged row with a stray
+/// trailing delimiter or an extra field doesn't throw it off; the best-scoring candidate wins,
+/// the earlier one on a tie. With none in the header it is a comma, so single-column files
+/// read exactly as before.
+///
+/// ```
+/// use certificate_maker::csvexcelparser::sniff_delimiter;
+/// assert_eq!(sniff_delimiter("Name;Email\nAlice;a@example.com\n"), b';');
+/// assert_eq!(sniff_delimiter("Name,Email\n\"Smith; Jr.\",s@example.com\n"), b',');
+/// assert_eq!(sniff_delimiter("Name\nAlice; Bob\n"), b',');
+/// // Ragged rows, one with an extra field and one with a trailing delimiter
+/// assert_eq!(sniff_delimiter("Full Name;Course\nANNE O'CONNOR;Art;extra\n"), b';');
+/// assert_eq!(sniff_delimiter("Name;Course\nAlice;Art\nBob;Music;\nCarol;Drama\n"), b';');
+/// // Decimal commas in the data don't make it comma-separated
+/// assert_eq!(sniff_delimiter("Name;Score\nAlice;9,5\nBob;7,25\n"), b';');
+/// ```
+pub fn sniff_delimiter(text: &str) -> u8 {
+    let lines: Vec<&str> = text.lines().filter(|line| !line.trim().is_empty()).take(5).collect();
+    let count_outside_quotes = |line: &str, delimiter: u8| {
+        let mut quoted = false;
+        line.bytes().filter(|&byte| {
+            if byte == b'"' {
+                quoted = !quoted;
+            }
+            !quoted && byte == delimiter
+        }).count()
+    };
+
+    CSV_DELIMITERS.into_iter()
+        .filter_map(|delimiter| {
+            let header = count_outside_quotes(lines.first()?, delimiter);
+            if header == 0 {
+                return None;
+            }
+            let counts: Vec<usize> = lines.iter().map(|line| count_outside_quotes(line, delimiter)).collect();
+            let matching = counts.iter().filter(|&&count| count == header).count();
+            let present = counts.iter().filter(|&&count| count > 0).count();
+            Some((delimiter, (matching, present, header)))
+        })
+        // max_by_key keeps the last of equal scores, so compare in reverse to prefer earlier delimiters
+        .rev()
+        .max_by_key(|&(_, score)| score)
+        .map_or(b',', |(delimiter, _)| delimiter)
+}
+
+/// Read a CSV file as UTF-8 text, detecting whatever `format` leaves open.
+///
+/// ```
+/// use certificate_maker::csvexcelparser::{CsvEncoding, CsvFormat, parse_csv_file, read_csv_text};
+///
+/// // A semicolon export of a European Excel, in Windows-1252, with a ragged row
+/// let path = std::env::temp_dir().join(format!("certmaker-ragged-{}.csv", std::process::id()));
+/// std::fs::write(&path, b"Full Name;Course\nZO\xc9 O'CONNOR;Art;extra\nJos\xe9 Ruiz;Music\n")?;
+/// let (_, detected) = read_csv_text(&path, &CsvFormat::default())?;
+/// assert_eq!((detected.delimiter, detected.encoding), (b';', CsvEncoding::Windows1252));
+/// assert_eq!(parse_csv_file(&path)?.names, ["ZOÉ O'CONNOR", "José Ruiz"]);
+/// std::fs::remove_file(&path)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn read_csv_text(file_path: impl AsRef<Path>, format: &CsvFormat) -> Result<(String, DetectedFormat)> {
+    let file_path = file_path.as_ref();
+    let bytes = std::fs::read(file_path)
+        .io_context(|| format!("Failed to open CSV file: {}", file_path.display()))?;
+
+    let encoding = format.encoding.unwrap_or_else(|| CsvEncoding::detect(&bytes));
+    let (text, _) = encoding.encoding().decode_with_bom_removal(&bytes);
+    let text = text.into_owned();
+    let delimiter = format.delimiter.unwrap_or_else(|| sniff_delimiter(&text));
+    Ok((text, DetectedFormat { delimiter, encoding }))
+}
+
+/// Parse a CSV file and extract names, keeping per-row diagnostics.
+///
+/// The name column is the first header matching one of the [`NameColumn`] default aliases.
+/// The delimiter and encoding are detected (see [`read_csv_text`]).
+pub fn parse_csv_file(file_path: impl AsRef<Path>) -> Result<ParsedCsv> {
+    parse_csv_file_with(file_path, &NameColumn::default(), &CsvFormat::default())
+}
+
+// Reader over a CSV file decoded to UTF-8, header row included
+fn open_csv(file_path: &Path, format: &CsvFormat) -> Result<(csv::Reader<Cursor<Vec<u8>>>, DetectedFormat)> {
+    let (text, detected) = read_csv_text(file_path, format)?;
+    let reader = ReaderBuilder::new()
         .has_headers(true)
-        .from_reader(file);
-    
-    match reader.headers() {
-        Ok(headers) => {
-            println!("📋 Parsed headers: {:?}", headers);
-            println!("📋 Number of columns: {}", headers.len());
-        }
-        Err(e) => {
-            println!("❌ Failed to parse headers: {}", e);
-        }
-    }
-    
-    Ok(())
+        .flexible(true)
+        .delimiter(detected.delimiter)
+        .from_reader(Cursor::new(text.into_bytes()));
+    Ok((reader, detected))
 }
 
-// Auto-detect file type and parse names (CSV only)
-pub fn parse_names_from_file(file_path: &str) -> Result<Vec<String>> {
-    let path = Path::new(file_path);
-    let extension = path.extension()
-        .and_then(|ext| ext.to_str())
-        .unwrap_or("")
-        .to_lowercase();
-    
-    match extension.as_str() {
-        "csv" => parse_csv_names(file_path),
-        _ => Err(anyhow::anyhow!(
-            "Unsupported file type. Please use .csv files only"
-        )),
-    }
-}
-
-// Function to list CSV files in excelcsvs directory
-fn list_csv_files() -> Result<Vec<String>> {
-    let csv_dir = "excelcsvs";
-    let mut csv_files = Vec::new();
-    
-    if !Path::new(csv_dir).exists() {
-        return Err(anyhow::anyhow!("Directory 'excelcsvs' not found. Please create it and add CSV files."));
-    }
-    
-    let entries = std::fs::read_dir(csv_dir)
-        .with_context(|| "Failed to read excelcsvs directory")?;
-    
-    for entry in entries {
-        let entry = entry?;
-        let path = entry.path();
-        if let Some(extension) = path.extension() {
-            if extension.to_string_lossy().to_lowercase() == "csv" {
-                if let Some(filename) = path.file_name() {
-                    csv_files.push(filename.to_string_lossy().to_string());
-                }
+// Header row, without the UTF-8 byte order mark Excel puts in front of the first header
+fn read_headers(reader: &mut csv::Reader<Cursor<Vec<u8>>>, file_path: &Path) -> Result<Vec<String>> {
+    Ok(reader.headers()
+        .map_err(|source| CertError::Csv { path: file_path.to_path_buf(), source })?
+        .iter()
+        .enumerate()
+        .map(|(index, header)| if index == 0 { header.trim_start_matches('\u{feff}') } else { header }.to_string())
+        .collect())
+}
+
+/// Like [`parse_csv_file`], finding the name column with `name_column` and taking the
+/// delimiter and encoding from `format` where it sets them.
+pub fn parse_csv_file_with(file_path: impl AsRef<Path>, name_column: &NameColumn, format: &CsvFormat) -> Result<ParsedCsv> {
+    let file_path = file_path.as_ref();
+    let (mut reader, detected) = open_csv(file_path, format)?;
+    let headers = read_headers(&mut reader, file_path)?;
+
+    let name_column = name_column.find(&headers)
+        .ok_or_else(|| name_column.missing(&headers))?;
+
+    let mut names = Vec::new();
+    let mut rows = Vec::new();
+    let mut records = Vec::new();
+    let mut skipped_rows = Vec::new();
+
+    let mut malformed_rows = Vec::new();
+    let mut flagged_values = Vec::new();
+
+    // Parse records manually instead of using serde; the reader is flexible, so rows with a
+    // stray trailing comma or a missing last field arrive here instead of as errors. One record
+    // is read into again and again, so a large file doesn't allocate a row for every line
+    let mut record = StringRecord::new();
+    for row in 2.. { // +2 because of header and 0-indexing
+        match reader.read_record(&mut record) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => {
+                skipped_rows.push(SkippedRow { row, reason: format!("Error reading row: {}", e) });
+                continue;
             }
         }
+
+        // Missing trailing fields read as empty; extra ones have no header and are dropped. Cleaned
+        // before anything else sees them, so a name of nothing but control characters is empty
+        let mut flagged = Vec::new();
+        let fields: Vec<(String, String)> = headers.iter().enumerate()
+            .map(|(index, header)| (header.clone(), clean_field(record.get(index).unwrap_or_default(), row, header, &mut flagged)))
+            .collect();
+        let name = fields[name_column].1.clone();
+        if record.len() != headers.len() {
+            malformed_rows.push(MalformedRow { row, fields: record.len(), recovered: !name.is_empty() });
+        }
+        if name.is_empty() {
+            let reason = if name_column < record.len() { "Empty name" } else { "No data in name column" };
+            skipped_rows.push(SkippedRow { row, reason: reason.to_string() });
+            continue;
+        }
+
+        flagged_values.extend(flagged);
+        names.push(name.clone());
+        records.push(Record { name, fields });
+        rows.push(row);
     }
-    
-    if csv_files.is_empty() {
-        return Err(anyhow::anyhow!("No CSV files found in 'excelcsvs' directory. Please add CSV files first."));
+
+    if names.is_empty() {
+        return Err(CertError::NoNames(file_path.to_path_buf()));
     }
-    
-    csv_files.sort();
-    Ok(csv_files)
+
+    Ok(ParsedCsv { path: file_path.to_path_buf(), headers, name_column, names, rows, records, skipped_rows, malformed_rows, flagged_values, format: Some(detected) })
+}
+
+// `value` cleaned by `clean_value`, with what was wrong with it added to `flagged`
+fn clean_field(value: &str, row: usize, column: &str, flagged: &mut Vec<FlaggedValue>) -> String {
+    let (cleaned, issues) = clean_value(value);
+    flagged.extend(issues.into_iter().map(|issue| FlaggedValue { row, column: column.to_string(), issue }));
+    cleaned
+}
+
+/// Several CSVs parsed with the same settings, concatenated in the order given.
+#[derive(Debug, Clone)]
+pub struct MergedCsv {
+    pub files: Vec<ParsedCsv>,
 }
 
-// Function to select CSV file interactively
-pub fn select_csv_file() -> Result<String> {
-    println!("\n📄 Available CSV Files in 'excelcsvs' directory:");
-    let csv_files = list_csv_files()?;
-    
-    for (i, file) in csv_files.iter().enumerate() {
-        println!("  {}. {}", i + 1, file);
+impl MergedCsv {
+    /// Every name, file by file.
+    pub fn names(&self) -> Vec<String> {
+        self.files.iter().flat_map(|file| file.names.iter().cloned()).collect()
     }
-    
-    loop {
-        let input = get_user_input("\nSelect CSV file (enter number or filename): ");
-        
-        // Try to parse as number first
-        if let Ok(num) = input.parse::<usize>() {
-            if num > 0 && num <= csv_files.len() {
-                let selected_file = &csv_files[num - 1];
-                let full_path = format!("excelcsvs/{}", selected_file);
-                println!("✅ Selected: {}", selected_file);
-                return Ok(full_path);
+
+    /// Line number of each name in its own file, in the same order as [`names`](Self::names).
+    pub fn rows(&self) -> Vec<usize> {
+        self.files.iter().flat_map(|file| file.rows.iter().copied()).collect()
+    }
+
+    /// The file each name came from, in the same order as [`names`](Self::names).
+    pub fn sources(&self) -> Vec<PathBuf> {
+        self.files.iter()
+            .flat_map(|file| std::iter::repeat_n(file.path.clone(), file.names.len()))
+            .collect()
+    }
+
+    /// The recipient address (see [`Record::email`]) of each name given by its row and, when
+    /// files were merged, its source; an empty string where the list has none.
+    pub fn emails(&self, rows: &[usize], sources: &[PathBuf]) -> Vec<String> {
+        self.records(rows, sources).iter()
+            .map(|record| record.email().unwrap_or_default().to_string())
+            .collect()
+    }
+
+    /// Every column of each name given by its row and, when files were merged, its source;
+    /// an empty record where the row isn't in the files.
+    pub fn records(&self, rows: &[usize], sources: &[PathBuf]) -> Vec<Record> {
+        let mut records = HashMap::new();
+        for file in &self.files {
+            for (row, record) in file.rows.iter().zip(&file.records) {
+                records.insert((file.path.as_path(), *row), record);
             }
         }
-        
-        // Try to find by filename (case insensitive)
-        for file in &csv_files {
-            if file.to_lowercase() == input.to_lowercase() {
-                let full_path = format!("excelcsvs/{}", file);
-                println!("✅ Selected: {}", file);
-                return Ok(full_path);
+        let Some(first) = self.files.first() else { return Vec::new() };
+        rows.iter().enumerate()
+            .map(|(index, row)| {
+                let path = sources.get(index).map_or(first.path.as_path(), PathBuf::as_path);
+                records.get(&(path, *row)).map(|record| (*record).clone()).unwrap_or_default()
+            })
+            .collect()
+    }
+
+    /// Ways the files disagree: a differently named name column, or columns missing from some
+    /// files (their records would have empty values there).
+    pub fn header_differences(&self) -> Vec<String> {
+        let Some(first) = self.files.first() else { return Vec::new() };
+        let lowercase = |headers: &[String]| -> Vec<String> { headers.iter().map(|header| header.trim().to_lowercase()).collect() };
+        let first_headers = lowercase(&first.headers);
+
+        let mut differences = Vec::new();
+        for file in &self.files[1..] {
+            let name_header = &file.headers[file.name_column];
+            let first_name_header = &first.headers[first.name_column];
+            if !name_header.trim().eq_ignore_ascii_case(first_name_header.trim()) {
+                differences.push(format!("{} takes names from '{}', {} from '{}'",
+                                         file.path.display(), name_header, first.path.display(), first_name_header));
             }
-        }
-        
-        println!("❌ Invalid selection. Please try again.");
-    }
-}
-
-// Function to list PNG files in Template directory
-fn list_template_files() -> Result<Vec<String>> {
-    let template_dir = "Template";
-    let mut template_files = Vec::new();
-    
-    if !Path::new(template_dir).exists() {
-        return Err(anyhow::anyhow!("Directory 'Template' not found. Please create it and add PNG template files."));
-    }
-    
-    let entries = std::fs::read_dir(template_dir)
-        .with_context(|| "Failed to read Template directory")?;
-    
-    for entry in entries {
-        let entry = entry?;
-        let path = entry.path();
-        if let Some(extension) = path.extension() {
-            let ext = extension.to_string_lossy().to_lowercase();
-            if ext == "png" || ext == "jpg" || ext == "jpeg" {
-                if let Some(filename) = path.file_name() {
-                    template_files.push(filename.to_string_lossy().to_string());
-                }
+            let headers = lowercase(&file.headers);
+            if headers != first_headers {
+                let missing: Vec<&String> = first_headers.iter().filter(|header| !headers.contains(header)).collect();
+                let extra: Vec<&String> = headers.iter().filter(|header| !first_headers.contains(header)).collect();
+                differences.push(format!("{} has different columns than {} (missing: {:?}, extra: {:?})",
+                                         file.path.display(), first.path.display(), missing, extra));
             }
         }
+        differences
     }
-    
-    if template_files.is_empty() {
-        return Err(anyhow::anyhow!("No PNG/JPG template files found in 'Template' directory. Please add template files first."));
+}
+
+/// Count the names that came from each file, in order of first appearance.
+pub fn count_by_source(sources: &[PathBuf]) -> Vec<(&Path, usize)> {
+    let mut counts: Vec<(&Path, usize)> = Vec::new();
+    for source in sources {
+        match counts.iter_mut().find(|(path, _)| *path == source.as_path()) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((source, 1)),
+        }
     }
-    
-    template_files.sort();
-    Ok(template_files)
+    counts
 }
 
-// Function to select template file interactively
-pub fn select_template_file() -> Result<String> {
-    println!("\n🖼️ Available Template Files in 'Template' directory:");
-    let template_files = list_template_files()?;
-    
-    for (i, file) in template_files.iter().enumerate() {
-        println!("  {}. {}", i + 1, file);
+/// Parse a CSV, JSON or text name list, by extension; anything else is read as a CSV.
+///
+/// An `https://` address is downloaded first when built with the `remote` feature (see
+/// [`crate::remote`]); `path` of the result is then the URL.
+pub fn parse_name_list_with(file_path: impl AsRef<Path>, name_column: &NameColumn, format: &CsvFormat) -> Result<ParsedCsv> {
+    let file_path = file_path.as_ref();
+    if let Some(url) = name_list_url(file_path) {
+        return parse_remote_list(url, name_column, format);
     }
-    
-    loop {
-        let input = get_user_input("\nSelect template file (enter number or filename): ");
-        
-        // Try to parse as number first
-        if let Ok(num) = input.parse::<usize>() {
-            if num > 0 && num <= template_files.len() {
-                let selected_file = &template_files[num - 1];
-                let full_path = format!("Template/{}", selected_file);
-                println!("✅ Selected template: {}", selected_file);
-                return Ok(full_path);
-            }
+    match list_extension(file_path).as_str() {
+        "json" => parse_json_list_with(file_path, name_column),
+        "txt" => parse_text_list(file_path),
+        _ => parse_csv_file_with(file_path, name_column, format),
+    }
+}
+
+/// The address when `file_path` is an `https://` URL rather than a file.
+pub fn name_list_url(file_path: &Path) -> Option<&str> {
+    file_path.to_str().filter(|path| path.starts_with("https://"))
+}
+
+#[cfg(feature = "remote")]
+fn parse_remote_list(url: &str, name_column: &NameColumn, format: &CsvFormat) -> Result<ParsedCsv> {
+    let download = crate::remote::download_name_list(url)?;
+    let mut parsed = parse_name_list_with(download.path(), name_column, format).map_err(|e| match e {
+        CertError::NoNames(_) => CertError::NoNames(PathBuf::from(url)),
+        e => e,
+    })?;
+    parsed.path = PathBuf::from(url);
+    Ok(parsed)
+}
+
+#[cfg(not(feature = "remote"))]
+fn parse_remote_list(url: &str, _name_column: &NameColumn, _format: &CsvFormat) -> Result<ParsedCsv> {
+    Err(CertError::RemoteDisabled(url.to_string()))
+}
+
+// Lowercase extension, empty when there is none
+fn list_extension(file_path: &Path) -> String {
+    file_path.extension().and_then(|ext| ext.to_str()).unwrap_or("").to_lowercase()
+}
+
+/// Parse a text file with one name per line. Blank lines and lines starting with `#` are
+/// skipped; each name becomes a record with a single `Name` column.
+pub fn parse_text_list(file_path: impl AsRef<Path>) -> Result<ParsedCsv> {
+    let file_path = file_path.as_ref();
+    let content = std::fs::read_to_string(file_path)
+        .io_context(|| format!("Failed to read name list: {}", file_path.display()))?;
+
+    let headers = vec!["Name".to_string()];
+    let mut names = Vec::new();
+    let mut rows = Vec::new();
+    let mut records = Vec::new();
+    let mut flagged_values = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let mut flagged = Vec::new();
+        let name = clean_field(line.trim_start_matches('\u{feff}'), index + 1, &headers[0], &mut flagged);
+        if name.is_empty() || name.starts_with('#') {
+            continue;
         }
-        
-        // Try to find by filename (case insensitive)
-        for file in &template_files {
-            if file.to_lowercase() == input.to_lowercase() {
-                let full_path = format!("Template/{}", file);
-                println!("✅ Selected template: {}", file);
-                return Ok(full_path);
+        flagged_values.extend(flagged);
+        records.push(Record { name: name.clone(), fields: vec![(headers[0].clone(), name.clone())] });
+        names.push(name);
+        rows.push(index + 1);
+    }
+
+    if names.is_empty() {
+        return Err(CertError::NoNames(file_path.to_path_buf()));
+    }
+    Ok(ParsedCsv {
+        path: file_path.to_path_buf(), headers, name_column: 0, names, rows, records,
+        skipped_rows: Vec::new(), malformed_rows: Vec::new(), flagged_values, format: None,
+    })
+}
+
+/// Parse a JSON array of names (`["Ada", "Grace"]`) or of objects
+/// (`[{"name": "Ada", "course": "Math"}]`), finding the name key with `name_column`.
+///
+/// Object keys become the headers, in order of first appearance; numbers and booleans are
+/// read as text and `null` as empty.
+pub fn parse_json_list_with(file_path: impl AsRef<Path>, name_column: &NameColumn) -> Result<ParsedCsv> {
+    let file_path = file_path.as_ref();
+    let (keys, items) = read_json_list(file_path)?;
+    let (headers, name_index) = if keys.is_empty() {
+        (vec!["Name".to_string()], 0)
+    } else {
+        let index = name_column.find(&keys).ok_or_else(|| name_column.missing(&keys))?;
+        (keys, index)
+    };
+
+    let mut names = Vec::new();
+    let mut rows = Vec::new();
+    let mut records = Vec::new();
+    let mut skipped_rows = Vec::new();
+    let mut flagged_values = Vec::new();
+    for (index, item) in items.iter().enumerate() {
+        let row = index + 1;
+        let mut flagged = Vec::new();
+        let fields: Vec<(String, String)> = match item {
+            serde_json::Value::String(name) => headers.iter().enumerate()
+                .map(|(column, header)| (header.clone(), if column == name_index { clean_field(name, row, header, &mut flagged) } else { String::new() }))
+                .collect(),
+            serde_json::Value::Object(object) => headers.iter()
+                .map(|header| (header.clone(), clean_field(&object.get(header).map(json_text).unwrap_or_default(), row, header, &mut flagged)))
+                .collect(),
+            other => {
+                skipped_rows.push(SkippedRow { row, reason: format!("Not a name or an object ({})", json_kind(other)) });
+                continue;
             }
+        };
+        let name = fields[name_index].1.clone();
+        if name.is_empty() {
+            skipped_rows.push(SkippedRow { row, reason: "Empty name".to_string() });
+            continue;
         }
-        
-        println!("❌ Invalid selection. Please try again.");
+        flagged_values.extend(flagged);
+        records.push(Record { name: name.clone(), fields });
+        names.push(name);
+        rows.push(row);
+    }
+
+    if names.is_empty() {
+        return Err(CertError::NoNames(file_path.to_path_buf()));
     }
+    Ok(ParsedCsv {
+        path: file_path.to_path_buf(), headers, name_column: name_index, names, rows, records, skipped_rows,
+        malformed_rows: Vec::new(), flagged_values, format: None,
+    })
 }
 
-// Function to debug template file
-pub fn debug_template_file(file_path: &str) -> Result<()> {
-    println!("\n🔍 === Template File Debug Info ===");
-    
-    let path = Path::new(file_path);
-    
-    if !path.exists() {
-        return Err(anyhow::anyhow!("Template file not found: {}", file_path));
-    }
-    
-    // Get file size
-    let metadata = std::fs::metadata(path)?;
-    println!("📄 File size: {} bytes ({:.2} KB)", metadata.len(), metadata.len() as f64 / 1024.0);
-    
-    // Try to analyze with our existing PNG analysis
-    match analyze_png_file(file_path) {
-        Ok(analysis) => {
-            println!("✅ Template analysis:");
-            println!("  📐 Dimensions: {}x{} pixels", analysis.width, analysis.height);
-            println!("  🎨 Color type: {:?}", analysis.color_type);
-            println!("  📊 Suggested center coordinates: ({}, {})", 
-                    analysis.width / 2, analysis.height / 2);
+// The elements of a JSON array and the keys of its objects, in order of first appearance
+fn read_json_list(file_path: &Path) -> Result<(Vec<String>, Vec<serde_json::Value>)> {
+    let content = std::fs::read_to_string(file_path)
+        .io_context(|| format!("Failed to read name list: {}", file_path.display()))?;
+    let json = content.strip_prefix('\u{feff}').unwrap_or(&content);
+
+    let value: serde_json::Value = serde_json::from_str(json).map_err(|source| {
+        let offset = json_error_offset(json, &source);
+        CertError::MalformedJson {
+            path: file_path.to_path_buf(),
+            offset: offset + (content.len() - json.len()),
+            snippet: snippet_around(json, offset),
+            source,
         }
-        Err(e) => {
-            println!("❌ Failed to analyze template: {}", e);
+    })?;
+    let serde_json::Value::Array(items) = value else {
+        return Err(CertError::UnexpectedJson { path: file_path.to_path_buf(), found: json_kind(&value) });
+    };
+
+    let mut keys: Vec<String> = Vec::new();
+    for object in items.iter().filter_map(serde_json::Value::as_object) {
+        for key in object.keys() {
+            if !keys.contains(key) {
+                keys.push(key.clone());
+            }
         }
     }
-    
-    Ok(())
+    Ok((keys, items))
 }
 
-// Function to list font files in assets directory
-fn list_font_files() -> Result<Vec<String>, String> {
-    let assets_dir = "assets";
-    let mut font_files = Vec::new();
-    
-    if !Path::new(assets_dir).exists() {
-        return Err("Directory 'assets' not found. Please create it and add font files.".to_string());
-    }
-    
-    let entries = std::fs::read_dir(assets_dir)
-        .map_err(|_| "Failed to read assets directory".to_string())?;
-    
-    for entry in entries {
-        if let Ok(entry) = entry {
-            let path = entry.path();
-            if let Some(extension) = path.extension() {
-                let ext = extension.to_string_lossy().to_lowercase();
-                if ext == "ttf" || ext == "otf" || ext == "woff" || ext == "woff2" {
-                    if let Some(filename) = path.file_name() {
-                        font_files.push(filename.to_string_lossy().to_string());
-                    }
+// A JSON value as it would be typed into a spreadsheet cell
+fn json_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(text) => text.trim().to_string(),
+        serde_json::Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+fn json_kind(value: &serde_json::Value) -> &'static str {
+    match value {
+        serde_json::Value::Null => "null",
+        serde_json::Value::Bool(_) => "a boolean",
+        serde_json::Value::Number(_) => "a number",
+        serde_json::Value::String(_) => "a string",
+        serde_json::Value::Array(_) => "an array",
+        serde_json::Value::Object(_) => "an object",
+    }
+}
+
+// serde_json reports 1-based lines and byte columns; turn them back into a byte offset
+fn json_error_offset(json: &str, error: &serde_json::Error) -> usize {
+    let line_start: usize = json.split_inclusive('\n').take(error.line().saturating_sub(1)).map(str::len).sum();
+    (line_start + error.column().saturating_sub(1)).min(json.len())
+}
+
+// Up to 30 bytes either side of `offset` on one line, with » marking the offset
+fn snippet_around(text: &str, offset: usize) -> String {
+    let boundary_before = |index: usize| (0..=index).rev().find(|&i| text.is_char_boundary(i)).unwrap_or(0);
+    let offset = boundary_before(offset);
+    let start = boundary_before(offset.saturating_sub(30));
+    let end = boundary_before((offset + 30).min(text.len()));
+    let flatten = |part: &str| part.split_whitespace().collect::<Vec<_>>().join(" ");
+    format!("{}»{}", flatten(&text[start..offset]), flatten(&text[offset..end]))
+}
+
+/// Parse several name lists with the same name column and format and merge them.
+///
+/// Every file must have a recognizable name column; see [`MergedCsv::header_differences`]
+/// for the softer checks.
+pub fn parse_csv_files_with(file_paths: &[PathBuf], name_column: &NameColumn, format: &CsvFormat) -> Result<MergedCsv> {
+    let files = file_paths.iter()
+        .map(|path| parse_name_list_with(path, name_column, format))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(MergedCsv { files })
+}
+
+/// Parse a CSV file and return only the names.
+pub fn parse_csv_names(file_path: impl AsRef<Path>) -> Result<Vec<String>> {
+    Ok(parse_csv_file(file_path)?.names)
+}
+
+/// Auto-detect file type from the extension and parse names.
+pub fn parse_names_from_file(file_path: impl AsRef<Path>) -> Result<Vec<String>> {
+    parse_names_from_file_with(file_path, &NameColumn::default(), &CsvFormat::default())
+}
+
+/// Like [`parse_names_from_file`], finding the name column with `name_column` and reading
+/// CSVs with `format`.
+pub fn parse_names_from_file_with(file_path: impl AsRef<Path>, name_column: &NameColumn, format: &CsvFormat) -> Result<Vec<String>> {
+    source_for_path_with(file_path, name_column, format)?.names()
+}
+
+/// The header and first rows of a CSV, for choosing a column by eye.
+#[derive(Debug, Clone)]
+pub struct CsvPreview {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl CsvPreview {
+    /// Values of one column in the previewed rows, skipping rows too short to have it.
+    pub fn column(&self, index: usize) -> Vec<&str> {
+        self.rows.iter().filter_map(|row| row.get(index)).map(String::as_str).collect()
+    }
+}
+
+/// Read the header and up to `rows` data rows; unreadable rows are left out. For a JSON list
+/// these are the object keys and the first objects.
+pub fn preview_csv(file_path: impl AsRef<Path>, rows: usize, format: &CsvFormat) -> Result<CsvPreview> {
+    let file_path = file_path.as_ref();
+    if let Some(url) = name_list_url(file_path) {
+        #[cfg(feature = "remote")]
+        return preview_csv(crate::remote::download_name_list(url)?.path(), rows, format);
+        #[cfg(not(feature = "remote"))]
+        return Err(CertError::RemoteDisabled(url.to_string()));
+    }
+    if list_extension(file_path) == "json" {
+        let (headers, items) = read_json_list(file_path)?;
+        let rows = items.iter()
+            .filter_map(serde_json::Value::as_object)
+            .take(rows)
+            .map(|object| headers.iter().map(|header| object.get(header).map(json_text).unwrap_or_default()).collect())
+            .collect();
+        return Ok(CsvPreview { headers, rows });
+    }
+    let (mut reader, _) = open_csv(file_path, format)?;
+    let headers = read_headers(&mut reader, file_path)?;
+    let rows = reader.records()
+        .take(rows)
+        .filter_map(|record| record.ok())
+        .map(|record| record.iter().map(|value| value.trim().to_string()).collect())
+        .collect();
+    Ok(CsvPreview { headers, rows })
+}
+
+/// Extensions of the name lists offered by [`list_csv_files`].
+pub const NAME_LIST_EXTENSIONS: &[&str] = &["csv", "json", "txt"];
+
+/// List CSV, JSON and text name lists in `dir` and its subdirectories, as paths relative to it.
+pub fn list_csv_files(dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+    let dir = dir.as_ref();
+    if !dir.exists() {
+        return Err(CertError::DirectoryNotFound { dir: dir.to_path_buf(), contents: "CSV files" });
+    }
+
+    let csv_files = list_files_with_extensions(dir, NAME_LIST_EXTENSIONS)?;
+    if csv_files.is_empty() {
+        return Err(CertError::NoFilesFound { dir: dir.to_path_buf(), contents: "CSV files" });
+    }
+    Ok(csv_files)
+}
+
+/// Parse a pick of several entries of a numbered list: `all`, or 1-based numbers like `1,3,4`.
+///
+/// Returns 0-based positions in the order typed, without repeats, or `None` when the input
+/// is neither or a number is out of range.
+///
+/// ```
+/// use certificate_maker::csvexcelparser::parse_multi_selection;
+///
+/// assert_eq!(parse_multi_selection("3, 1,3", 4), Some(vec![2, 0]));
+/// assert_eq!(parse_multi_selection("all", 3), Some(vec![0, 1, 2]));
+/// assert_eq!(parse_multi_selection("1,5", 4), None);
+/// ```
+pub fn parse_multi_selection(input: &str, count: usize) -> Option<Vec<usize>> {
+    if input.trim().eq_ignore_ascii_case("all") {
+        return Some((0..count).collect());
+    }
+    let mut picked = Vec::new();
+    for number in input.split(',') {
+        match number.trim().parse::<usize>() {
+            Ok(number) if number > 0 && number <= count => {
+                if !picked.contains(&(number - 1)) {
+                    picked.push(number - 1);
                 }
             }
+            _ => return None,
         }
     }
-    
-    if font_files.is_empty() {
-        return Err("No font files found in 'assets' directory. Please add .ttf, .otf, .woff, or .woff2 files.".to_string());
+    Some(picked)
+}
+
+/// List PNG/JPG templates in `dir` and its subdirectories, as paths relative to it.
+pub fn list_template_files(dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+    let dir = dir.as_ref();
+    if !dir.exists() {
+        return Err(CertError::DirectoryNotFound { dir: dir.to_path_buf(), contents: "PNG template files" });
     }
-    
-    font_files.sort();
-    Ok(font_files)
+
+    let template_files = list_files_with_extensions(dir, &["png", "jpg", "jpeg"])?;
+    if template_files.is_empty() {
+        return Err(CertError::NoFilesFound { dir: dir.to_path_buf(), contents: "PNG/JPG template files" });
+    }
+    Ok(template_files)
 }
 
-// Function to select font file interactively
-pub fn select_font_file() -> Result<String, String> {
-    println!("\n🔤 Available Font Files in 'assets' directory:");
-    let font_files = list_font_files()?;
-    
-    for (i, file) in font_files.iter().enumerate() {
-        println!("  {}. {}", i + 1, file);
+/// Output path for a given name inside the output directory: spaces become `_`, and the
+/// name is made a [`portable_file_name`], so any name from a list gives a file that every
+/// system can write and nothing outside `output_dir`.
+///
+/// ```
+/// use certificate_maker::csvexcelparser::certificate_output_path;
+/// use std::path::Path;
+///
+/// assert_eq!(certificate_output_path("certs", "Ada Lovelace"), Path::new("certs/certificate_Ada_Lovelace.png"));
+/// assert_eq!(certificate_output_path("certs", "R. \"Bob\" Smith: Jr?"), Path::new("certs/certificate_R.__Bob__Smith__Jr_.png"));
+/// assert_eq!(certificate_output_path("certs", "../../x"), Path::new("certs/certificate_.._.._x.png"));
+/// ```
+pub fn certificate_output_path(output_dir: impl AsRef<Path>, name: &str) -> PathBuf {
+    output_dir.as_ref().join(format!("certificate_{}.png", portable_file_name(&name.replace(' ', "_"))))
+}
+
+/// Projected disk and memory usage of a batch.
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchEstimate {
+    pub certificates: usize,
+    pub bytes_per_certificate: u64,
+    pub total_bytes: u64,
+    pub peak_memory_bytes: u64,
+    pub threads: usize,
+    /// The budget `threads` was cut down to fit; see [`with_memory_budget`](Self::with_memory_budget).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_budget: Option<u64>,
+    #[serde(skip)]
+    pub template_dimensions: (u32, u32),
+    /// `None` when the free space of the output volume can't be determined.
+    pub free_space_bytes: Option<u64>,
+    /// How long rendering and encoding the sample certificate took.
+    #[serde(rename = "sample_render_seconds", serialize_with = "serialize_seconds")]
+    pub sample_render_time: Duration,
+    /// Compression level the sample was encoded at.
+    pub compression: PngCompression,
+    /// Size of the sample at [`PngCompression::Balanced`], to compare other levels against;
+    /// `None` when that is the level in use.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balanced_bytes_per_certificate: Option<u64>,
+}
+
+impl BatchEstimate {
+    pub fn fits_on_disk(&self) -> bool {
+        self.free_space_bytes.is_none_or(|free| self.total_bytes <= free)
+    }
+
+    /// Rough wall-clock time of the whole batch, assuming every row costs as much as the sample.
+    pub fn estimated_duration(&self) -> Duration {
+        self.sample_render_time * self.certificates as u32 / self.threads.max(1) as u32
+    }
+
+    /// The estimate for a batch limited to `budget` bytes of memory, as
+    /// [`CertificateJob::memory_budget`] runs it: fewer threads, a lower peak, a longer time.
+    pub fn with_memory_budget(mut self, budget: u64) -> std::result::Result<Self, String> {
+        self.plan_memory(Some(budget))?;
+        Ok(self)
+    }
+
+    // Threads and peak memory for the current thread pool, or what of it fits in the budget
+    fn plan_memory(&mut self, budget: Option<u64>) -> std::result::Result<(), String> {
+        let plan = MemoryPlan::for_template(self.template_dimensions, rayon::current_num_threads(), budget)?;
+        self.threads = plan.threads.min(self.certificates.max(1));
+        self.peak_memory_bytes = MemoryPlan { threads: self.threads, ..plan }.peak_bytes();
+        self.memory_budget = budget;
+        Ok(())
+    }
+
+    /// How much larger (positive) or smaller (negative) a certificate is than at balanced
+    /// compression, as a fraction; `None` at balanced compression.
+    pub fn size_change_vs_balanced(&self) -> Option<f64> {
+        self.balanced_bytes_per_certificate
+            .filter(|&balanced| balanced > 0)
+            .map(|balanced| self.bytes_per_certificate as f64 / balanced as f64 - 1.0)
     }
-    
+}
+
+// Durations go into JSON reports as fractional seconds
+fn serialize_seconds<S: serde::Serializer>(duration: &Duration, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+// Free space of the volume holding `dir`, using the nearest existing ancestor
+fn available_space_for(dir: &Path) -> Option<u64> {
+    let mut path = dir;
     loop {
-        let input = get_user_input("\nSelect font file (enter number or filename): ");
-        
-        // Try to parse as number first
-        if let Ok(num) = input.parse::<usize>() {
-            if num > 0 && num <= font_files.len() {
-                let selected_file = &font_files[num - 1];
-                println!("✅ Selected font: {}", selected_file);
-                return Ok(selected_file.clone());
-            }
-        }
-        
-        // Try to find by filename (case insensitive)
-        for file in &font_files {
-            if file.to_lowercase() == input.to_lowercase() {
-                println!("✅ Selected font: {}", file);
-                return Ok(file.clone());
-            }
+        if path.exists() {
+            return fs2::available_space(path).ok();
         }
-        
-        println!("❌ Invalid selection. Please try again.");
+        path = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
     }
 }
 
-// Helper function to calculate text size
-fn calculate_text_size(font: &Font, scale: Scale, text: &str) -> (i32, i32) {
-    let v_metrics = font.v_metrics(scale);
-    let glyphs: Vec<_> = font.layout(text, scale, point(0.0, 0.0 + v_metrics.ascent)).collect();
+/// Render and encode one certificate in memory to project the size of the whole batch. At
+/// any `compression` but balanced, the sample is encoded at balanced too for comparison.
+#[allow(clippy::too_many_arguments)]
+pub fn estimate_batch(
+    template_path: impl AsRef<Path>,
+    output_dir: impl AsRef<Path>,
+    sample_name: &str,
+    count: usize,
+    font_filename: impl AsRef<Path>,
+    font_size: f32,
+    hex_color: &str,
+    compression: PngCompression,
+) -> Result<BatchEstimate> {
+    let template = load_template(template_path)?;
+    estimate_batch_for_template(&template, output_dir, sample_name, count, font_filename, font_size, hex_color, compression)
+}
 
-    if glyphs.is_empty() {
-        return (0, 0);
-    }
+/// [`estimate_batch`] with a template that is already decoded, such as the one a batch draws
+/// on. Decoding isn't part of the sample's time, since a batch decodes its template only once.
+#[allow(clippy::too_many_arguments)]
+pub fn estimate_batch_for_template(
+    template: &RgbaImage,
+    output_dir: impl AsRef<Path>,
+    sample_name: &str,
+    count: usize,
+    font_filename: impl AsRef<Path>,
+    font_size: f32,
+    hex_color: &str,
+    compression: PngCompression,
+) -> Result<BatchEstimate> {
+    let started = Instant::now();
+    let sample = render_text_on(template.clone(), sample_name, 0, 0, font_filename, font_size, hex_color)?;
 
-    let min_x = glyphs
-        .iter()
-        .filter_map(|g| g.pixel_bounding_box().map(|b| b.min.x))
-        .min()
-        .unwrap_or(0);
-    
-    let max_x = glyphs
-        .iter()
-        .filter_map(|g| g.pixel_bounding_box().map(|b| b.max.x))
-        .max()
-        .unwrap_or(0);
+    let encode = |compression| encode_png(&sample, compression)
+        .map_err(|source| CertError::ImageEncode { path: PathBuf::from("<sample certificate>"), source });
+    let bytes_per_certificate = encode(compression)?.len() as u64;
+    let sample_render_time = started.elapsed();
+    let balanced_bytes_per_certificate = match compression {
+        PngCompression::Balanced => None,
+        _ => Some(encode(PngCompression::Balanced)?.len() as u64),
+    };
 
-    let width = max_x - min_x;
-    let height = (v_metrics.ascent - v_metrics.descent).ceil() as i32;
+    let mut estimate = BatchEstimate {
+        certificates: count,
+        bytes_per_certificate,
+        total_bytes: bytes_per_certificate * count as u64,
+        peak_memory_bytes: 0,
+        threads: 0,
+        memory_budget: None,
+        template_dimensions: sample.dimensions(),
+        free_space_bytes: available_space_for(output_dir.as_ref()),
+        sample_render_time,
+        compression,
+        balanced_bytes_per_certificate,
+    };
+    estimate.plan_memory(None).map_err(|problem| CertError::InvalidJob(vec![problem]))?;
+    Ok(estimate)
+}
 
-    (width, height)
+/// What happened to a single rendered row.
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchItem {
+    /// Position of the row among the names, or the images, the batch was given.
+    #[serde(skip)]
+    pub index: usize,
+    pub name: String,
+    pub output_file: PathBuf,
+    /// Top-left position the text was drawn at.
+    pub position: (i32, i32),
+    pub error: Option<String>,
+    /// Problems that didn't stop the row: text off the template, glyphs the font lacks, a
+    /// file name taken by another row.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// Values drawn cut short to fit, each with its whole text; also among the warnings.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ellipsized: Vec<EllipsizedText>,
+    /// Values the typography pass changed, as they were and as drawn.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub retouched: Vec<RetouchedText>,
+    /// How long each stage of rendering the row took.
+    pub timings: StageTimings,
+    /// The name as it was laid out; `None` when it couldn't be, and for stamped images.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub drawn: Option<DrawnText>,
 }
 
-// Helper function to load font data
-fn load_font_data(font_filename: &str) -> Result<Vec<u8>> {
-    let font_path = format!("assets/{}", font_filename);
-    std::fs::read(&font_path)
-        .with_context(|| format!("Failed to read font file: {}", font_path))
+/// A name as laid out on its certificate.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DrawnText {
+    /// The text drawn, after the case transform, the typography pass and cutting short.
+    pub text: String,
+    /// Font size it was set at, smaller than the batch's when it was shrunk to fit.
+    pub font_size: f32,
+    pub width: i32,
+    pub height: i32,
 }
 
+/// File a dry run writes its [`BatchReport`] to, in the output directory.
+pub const DRY_RUN_REPORT_FILENAME: &str = "dry-run-report.json";
+
+/// Outcome of a batch run, broken down by what happened to each row.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct BatchReport {
+    /// Nothing was drawn or saved; `generated` counts the certificates a real run would write.
+    pub dry_run: bool,
+    pub generated: usize,
+    pub skipped_unchanged: usize,
+    /// Rows left alone because a file not written by this tool was in the way.
+    pub skipped_existing: usize,
+    /// Rows written under a `_1`, `_2`, ... name because their file was taken.
+    pub renamed: usize,
+    pub failed: usize,
+    /// Rows that were rendered (or attempted) in this run. A real run under a
+    /// [`CertificateJob::memory_budget`] keeps only the first
+    /// [`BUDGET_REPORTED_ROWS`](crate::job::BUDGET_REPORTED_ROWS) that failed or drew a
+    /// warning; the counts above still cover every row.
+    pub items: Vec<BatchItem>,
+    /// Manifest entries whose rows disappeared from the CSV.
+    pub stale: Vec<ManifestEntry>,
+    /// Manifest entries of this run's names, unchanged ones included and failed ones left out.
+    pub entries: Vec<ManifestEntry>,
+    /// Projection made before rendering the rows that needed it.
+    pub estimate: Option<BatchEstimate>,
+    pub threads: usize,
+    /// Wall-clock time of the run, hashing and saving the manifest included.
+    #[serde(rename = "duration_seconds", serialize_with = "serialize_seconds")]
+    pub duration: Duration,
+    /// Spread of the rendered rows' stage timings; empty when nothing was rendered.
+    #[serde(skip_serializing_if = "PerformanceBreakdown::is_empty")]
+    pub performance: PerformanceBreakdown,
+    /// How the template was brought to the requested size; `None` when it was drawn on as it is.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normalization: Option<NormalizePlan>,
+    /// What became of a 16-bit template's depth; `None` for an 8-bit template.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deep_color: Option<DeepColor>,
+    /// Text of the watermark drawn over this run's certificates; `None` for final ones.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watermark: Option<String>,
+    /// The fingerprint this run's certificates were made with; `None` when they carry none.
+    /// See [`CertificateJob::fingerprint`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<Box<Fingerprint>>,
+    /// What is wrong with the font size for the template; `None` when it suits it. See
+    /// [`CertificateJob::strict_font_size`] to refuse the batch instead.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font_size: Option<FontSizeCheck>,
+    /// The print sheets made from this run's certificates; `None` when it made none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sheets: Option<Box<ImposedSheets>>,
+    /// Panics caught from the progress sink; the batch itself kept going.
+    pub progress_errors: Vec<String>,
+}
+
+impl BatchReport {
+    /// Rows that failed or drew a warning, the ones to look at before trusting the run.
+    pub fn problem_items(&self) -> impl Iterator<Item = &BatchItem> {
+        self.items.iter().filter(|item| item.error.is_some() || !item.warnings.is_empty())
+    }
+
+    /// Write the report as JSON, e.g. to [`DRY_RUN_REPORT_FILENAME`] for a CI job to read.
+    pub fn save_json(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|source| CertError::Json { context: "Failed to serialize batch report".to_string(), source })?;
+        std::fs::write(path, content)
+            .io_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// Generate one certificate per name, skipping rows unchanged since the last run.
+#[allow(clippy::too_many_arguments)]
 pub fn generate_certificates_batch(
-    template_path: &str,
-    output_dir: &str,
+    template_path: impl AsRef<Path>,
+    output_dir: impl AsRef<Path>,
     names: &[String],
     x_pos: i32,
     y_pos: i32,
-    font_filename: &str,
+    font_filename: impl AsRef<Path>,
     font_size: f32,
     hex_color: &str,
-) -> Result<()> {
-    std::fs::create_dir_all(output_dir)
-        .with_context(|| format!("Failed to create output directory: {}", output_dir))?;
-    
-    // Load font once for text size calculations
-    let font_data = load_font_data(font_filename)?;
-    let font = Font::try_from_bytes(&font_data)
-        .ok_or_else(|| anyhow::anyhow!("Failed to load font: {}", font_filename))?;
-    
-    let scale = Scale::uniform(font_size);
-    let total = names.len();
-    let completed = Arc::new(AtomicUsize::new(0));
-    
-    println!("\n🎓 Generating {} certificates in parallel using {} cores...", 
-             total, 
-             rayon::current_num_threads());
-    println!("🎯 Text will be centered around coordinates ({}, {})", x_pos, y_pos);
-    
-    let results: Vec<Result<(), anyhow::Error>> = names
-        .par_iter()
-        .map(|name| {
-            let completed_clone = Arc::clone(&completed);
-            
-            let output_filename = format!("{}/certificate_{}.png", output_dir, 
-                                        name.replace(" ", "_").replace("/", "_").replace("\\", "_"));
-            
-            // Calculate text size for centering
-            let (text_width, text_height) = calculate_text_size(&font, scale, name);
-            
-            // Calculate centered position
-            let centered_x = x_pos - text_width / 2;
-            let centered_y = y_pos - text_height / 2;
-            
-            let result = add_text_with_custom_options(
-                template_path,
-                &output_filename,
-                name,
-                centered_x,  // Use centered coordinates
-                centered_y,  // Use centered coordinates
-                font_filename,
-                font_size,
-                hex_color,
-            );
-            
-            let current_completed = completed_clone.fetch_add(1, Ordering::Relaxed) + 1;
-            let progress = (current_completed as f64 / total as f64) * 100.0;
-            
-            match result {
-                Ok(()) => {
-                    println!("✅ [{:6.2}%] Generated: {} (centered at {}, {})", 
-                            progress, name, centered_x, centered_y);
-                    Ok(())
-                }
-                Err(e) => {
-                    println!("❌ [{:6.2}%] Failed: {} - {}", progress, name, e);
-                    Err(e)
-                }
-            }
-        })
-        .collect();
-    
-    // Summary
-    let success_count = results.iter().filter(|r| r.is_ok()).count();
-    let error_count = results.len() - success_count;
-    
-    println!("\n🎉 Parallel certificate generation complete!");
-    println!("⚡ Used {} CPU cores", rayon::current_num_threads());
-    println!("🎯 All text was centered around ({}, {})", x_pos, y_pos);
-    println!("✅ Successfully generated: {} certificates", success_count);
-    if error_count > 0 {
-        println!("❌ Failed to generate: {} certificates", error_count);
-    }
-    println!("📁 Certificates saved in: {}", output_dir);
-    
-    Ok(())
+) -> Result<BatchReport> {
+    generate_certificates_batch_with_progress(
+        template_path, output_dir, names, x_pos, y_pos, font_filename, font_size, hex_color,
+        &|_, _, _| {},
+    )
 }
 
+/// Like [`generate_certificates_batch`], calling `progress(completed, total, item)` after each row.
+///
+/// The callback runs on rayon worker threads; use [`CertificateJob::run_with_progress`] for a full [`ProgressSink`].
+#[allow(clippy::too_many_arguments)]
+pub fn generate_certificates_batch_with_progress(
+    template_path: impl AsRef<Path>,
+    output_dir: impl AsRef<Path>,
+    names: &[String],
+    x_pos: i32,
+    y_pos: i32,
+    font_filename: impl AsRef<Path>,
+    font_size: f32,
+    hex_color: &str,
+    progress: &(dyn Fn(usize, usize, &BatchItem) + Sync + Send),
+) -> Result<BatchReport> {
+    CertificateJob::new(template_path.as_ref(), names.to_vec())
+        .font(font_filename.as_ref())
+        .font_size(font_size)
+        .color(hex_color)
+        .position(Anchor::Center, x_pos, y_pos)
+        .output_dir(output_dir.as_ref())
+        .run_with_progress(&FnSink::new(progress))
+}
 
-// Interactive certificate generation with template and font selection
-pub fn generate_certificates_interactive() -> Result<()> {
-    println!("🎓 === Certificate Generator (CSV Files Only) ===");
-    
-    // Automatically look in excelcsvs directory and let user select
-    let input_file = match select_csv_file() {
-        Ok(file) => file,
-        Err(e) => {
-            println!("❌ {}", e);
-            println!("\n💡 Tips:");
-            println!("  • Create an 'excelcsvs' directory in your project root");
-            println!("  • Add CSV files with a 'Name' column");
-            println!("  • Example CSV format:");
-            println!("    Name");
-            println!("    Alice Johnson");
-            println!("    Bob Smith");
-            return Err(e);
-        }
-    };
-    
-    // Parse names
-    println!("\n📄 Parsing names from CSV file...");
-    let names = parse_names_from_file(&input_file)?;
-    
-    println!("✅ Found {} names:", names.len());
-    for (i, name) in names.iter().enumerate() {
-        println!("  {}. {}", i + 1, name);
-    }
-    
-    // Automatically look in Template directory and let user select
-    let template_file = match select_template_file() {
-        Ok(file) => file,
-        Err(e) => {
-            println!("❌ {}", e);
-            println!("\n💡 Tips:");
-            println!("  • Create a 'Template' directory in your project root");
-            println!("  • Add PNG/JPG template files for certificates");
-            println!("  • Supported formats: .png, .jpg, .jpeg");
-            return Err(e);
+/// File in the working directory that remembers the settings of the last successful batch,
+/// and the [`RecentColors`] picked at the color prompt.
+pub const LAST_RUN_FILE: &str = ".certmaker_state.json";
+
+/// How many custom colors [`RecentColors`] keeps.
+pub const RECENT_COLOR_COUNT: usize = 5;
+
+/// Key of [`RecentColors`] in the [`LAST_RUN_FILE`], beside the batch settings.
+const RECENT_COLORS_KEY: &str = "recent_colors";
+
+/// Everything needed to run a batch.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatchSettings {
+    pub csv_file: PathBuf,
+    /// More CSV files merged after `csv_file` into the same batch.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_csv_files: Vec<PathBuf>,
+    pub template_file: PathBuf,
+    pub x_pos: i32,
+    pub y_pos: i32,
+    /// The position was placed outside the template on purpose; see
+    /// [`CertificateJob::allow_bleed`]. Remembered with the position it was allowed for.
+    #[serde(default)]
+    pub allow_bleed: bool,
+    pub font_filename: PathBuf,
+    pub font_size: f32,
+    /// What counts as too large or too small a font size; see [`CertificateJob::font_size_limits`].
+    /// Given on the command line for a run, so it isn't remembered.
+    #[serde(skip)]
+    pub font_size_limits: FontSizeLimits,
+    /// Refuse the batch when the font size is outside the limits; see
+    /// [`CertificateJob::strict_font_size`].
+    #[serde(skip)]
+    pub strict_font_size: bool,
+    pub hex_color: String,
+    pub output_dir: PathBuf,
+    /// Write the certificates to [`local_staging_dir`] and move them into `output_dir`
+    /// afterwards, for an output directory on a network share; see
+    /// [`CertificateJob::stage_locally`].
+    #[serde(default)]
+    pub stage_locally: bool,
+    #[serde(default)]
+    pub overwrite: OverwritePolicy,
+    /// Header of the name column when it was picked by hand, so it isn't asked again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name_column: Option<String>,
+    /// Delimiter and encoding forced on the command line; detected when unset.
+    #[serde(default)]
+    pub csv_format: CsvFormat,
+    /// Case transform for the drawn names.
+    #[serde(default)]
+    pub name_case: NameCase,
+    /// Whether filenames and the manifest follow `name_case` too.
+    #[serde(default)]
+    pub case_filenames: bool,
+    /// Typographic cleanup of the drawn text; see [`CertificateJob::typography`].
+    #[serde(default, skip_serializing_if = "Typography::is_none")]
+    pub typography: Typography,
+    /// Faux bold and italic for a font without those faces.
+    #[serde(default, skip_serializing_if = "SyntheticStyle::is_none")]
+    pub synthetic: SyntheticStyle,
+    /// Axis values of a variable font; empty draws its default instance, as for static fonts.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub font_variation: Vec<AxisValue>,
+    /// Name of the text effect the names are drawn with, looked up with [`find_effect`] when
+    /// the batch runs, so a repeat follows edits to the palette's effects.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effect: Option<String>,
+    /// Rectangles of the template painted over before anything is drawn; see
+    /// [`CertificateJob::patches`]. Remembered, so a repeated batch patches the template again.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub patches: Vec<Patch>,
+    /// Barcodes and other graphics drawn besides the name.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub graphics: Vec<GraphicField>,
+    /// Column whose values name a subdirectory for each certificate; see [`CertificateJob::group_by`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_by: Option<String>,
+    /// PNG compression level of the certificates.
+    #[serde(default)]
+    pub compression: PngCompression,
+    /// Whether a 16-bit template's certificates keep 16 bits per channel.
+    #[serde(default)]
+    pub depth: DepthPolicy,
+    /// Whether the certificates are saved as PNGs or PDFs.
+    #[serde(default)]
+    pub format: CertificateFormat,
+    /// Longest side of the JPEG preview saved with each certificate; `None` makes none.
+    /// See [`CertificateJob::preview`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview: Option<u32>,
+    /// Size the template is brought to before drawing; see [`CertificateJob::normalize`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalize: Option<Normalization>,
+    /// Whether the certificates record how they were made; see [`CertificateJob::fingerprint`].
+    #[serde(default)]
+    pub fingerprint: FingerprintMode,
+    /// How repeated names in this run's list were resolved; asked again on every run.
+    #[serde(skip)]
+    pub duplicates: Vec<DuplicateDecision>,
+    /// Line number of each name passed to [`run_batch`], recorded in the manifest.
+    #[serde(skip)]
+    pub rows: Vec<usize>,
+    /// File of each name passed to [`run_batch`] when several files were merged.
+    #[serde(skip)]
+    pub sources: Vec<PathBuf>,
+    /// Recipient address of each name passed to [`run_batch`], empty where the list has none.
+    #[serde(skip)]
+    pub emails: Vec<String>,
+    /// Every column of each name passed to [`run_batch`], for the graphics' placeholders.
+    #[serde(skip)]
+    pub records: Vec<Record>,
+    /// Which rows of the list the names are; anything but all rows leaves other certificates alone.
+    #[serde(skip)]
+    pub selection: RowSelection,
+    /// Check and plan everything but write no certificates; see [`CertificateJob::dry_run`].
+    #[serde(skip)]
+    pub dry_run: bool,
+    /// List every value the typography pass changed as the rows finish. Only about the output
+    /// of this run, so it isn't remembered.
+    #[serde(skip)]
+    pub verbose: bool,
+    /// Same inputs, same bytes; see [`CertificateJob::deterministic`].
+    #[serde(skip)]
+    pub deterministic: bool,
+    /// Memory the batch may use, in bytes; see [`CertificateJob::memory_budget`]. A property
+    /// of the machine rather than the batch, so it isn't remembered.
+    #[serde(skip)]
+    pub memory_budget: Option<u64>,
+    /// Drawn over every certificate; see [`CertificateJob::watermark`]. Never remembered, so
+    /// repeating a draft batch without the flag makes the final certificates.
+    #[serde(skip)]
+    pub watermark: Option<WatermarkStyle>,
+    /// Print sheets made after the batch; see [`CertificateJob::impose`]. Only for the run it
+    /// was asked for, like the watermark.
+    #[serde(skip)]
+    pub imposition: Option<Imposition>,
+}
+
+impl BatchSettings {
+    /// Every CSV file of the batch, `csv_file` first.
+    pub fn csv_files(&self) -> Vec<PathBuf> {
+        std::iter::once(self.csv_file.clone()).chain(self.extra_csv_files.iter().cloned()).collect()
+    }
+
+    /// Settings of the last successful batch, or `None` if nothing has been run here yet.
+    pub fn load_last_run() -> Result<Option<BatchSettings>> {
+        let Some(mut state) = read_state(LAST_RUN_FILE)? else {
+            return Ok(None);
+        };
+        // Only recent colors: colors were picked here, but no batch has finished
+        state.remove(RECENT_COLORS_KEY);
+        if state.is_empty() {
+            return Ok(None);
         }
-    };
-    
-    // Analyze template
-    println!("\n📊 Analyzing template...");
-    if let Ok(analysis) = analyze_png_file(&template_file) {
-        println!("Template dimensions: {}x{} pixels", analysis.width, analysis.height);
-        println!("Suggested coordinates for centering: ({}, {})", 
-                analysis.width / 2, analysis.height / 2);
-    }
-    
-    // Get positioning
-    let x_input = get_user_input("\nEnter X position for name (or press Enter for center): ");
-    let y_input = get_user_input("Enter Y position for name (or press Enter for center): ");
-    
-    // Default to center if no input
-    let (default_x, default_y) = if let Ok(analysis) = analyze_png_file(&template_file) {
-        (analysis.width as i32 / 2, analysis.height as i32 / 2)
-    } else {
-        (400, 300)
-    };
-    
-    let x_pos = if x_input.is_empty() { default_x } else { x_input.parse().unwrap_or(default_x) };
-    let y_pos = if y_input.is_empty() { default_y } else { y_input.parse().unwrap_or(default_y) };
-    
-    // Font selection from assets directory
-    let font_input = match select_font_file() {
-        Ok(font) => font,
-        Err(e) => {
-            println!("❌ {}", e);
-            println!("\n💡 Tips:");
-            println!("  • Create an 'assets' directory in your project root");
-            println!("  • Add font files (.ttf, .otf, .woff, .woff2)");
-            println!("  • You can download fonts from Google Fonts");
-            
-            // Fallback to manual input
-            let manual_font = get_user_input("\nOr enter font filename manually (e.g., DejaVuSans.ttf): ");
-            if manual_font.is_empty() {
-                return Err(anyhow::anyhow!("No font selected"));
-            }
-            manual_font
+        serde_json::from_value(serde_json::Value::Object(state))
+            .map(Some)
+            .map_err(|source| CertError::Json { context: format!("Failed to parse {}", LAST_RUN_FILE), source })
+    }
+
+    /// Remember these settings for "Repeat last generation", keeping the recent colors.
+    pub fn save_last_run(&self) -> Result<()> {
+        let serialize_error = |source| CertError::Json { context: "Failed to serialize batch settings".to_string(), source };
+        let mut state = match serde_json::to_value(self).map_err(serialize_error)? {
+            serde_json::Value::Object(state) => state,
+            _ => unreachable!("batch settings serialize to an object"),
+        };
+        // An unreadable old file is replaced, as it always was
+        if let Some(recent) = read_state(LAST_RUN_FILE).ok().flatten().and_then(|mut old| old.remove(RECENT_COLORS_KEY)) {
+            state.insert(RECENT_COLORS_KEY.to_string(), recent);
         }
+        write_state(LAST_RUN_FILE, state)
+    }
+}
+
+/// The last custom colors picked at the color prompt, newest first, kept in the
+/// [`LAST_RUN_FILE`] beside the last batch's settings.
+///
+/// ```
+/// use certificate_maker::csvexcelparser::RecentColors;
+///
+/// let mut recent = RecentColors::default();
+/// for color in ["#111111", "#222222", "#111111"] {
+///     recent.remember(color);
+/// }
+/// assert_eq!(recent.colors, vec!["#111111", "#222222"]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RecentColors {
+    pub colors: Vec<String>,
+}
+
+impl RecentColors {
+    /// The recent colors of the [`LAST_RUN_FILE`]; none when it doesn't exist.
+    pub fn load() -> Result<Self> {
+        RecentColors::load_from(LAST_RUN_FILE)
+    }
+
+    /// The recent colors of a state file like the [`LAST_RUN_FILE`].
+    pub fn load_from(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let Some(recent) = read_state(path)?.and_then(|mut state| state.remove(RECENT_COLORS_KEY)) else {
+            return Ok(RecentColors::default());
+        };
+        let colors = serde_json::from_value(recent)
+            .map_err(|source| CertError::Json { context: format!("Failed to parse the recent colors of {}", path.display()), source })?;
+        Ok(RecentColors { colors })
+    }
+
+    /// Put `color` first, once, keeping the newest [`RECENT_COLOR_COUNT`].
+    pub fn remember(&mut self, color: &str) {
+        self.colors.retain(|recent| !recent.eq_ignore_ascii_case(color));
+        self.colors.insert(0, color.to_string());
+        self.colors.truncate(RECENT_COLOR_COUNT);
+    }
+
+    /// Save into the [`LAST_RUN_FILE`], keeping the batch settings in it.
+    pub fn save(&self) -> Result<()> {
+        self.save_to(LAST_RUN_FILE)
+    }
+
+    /// Save into a state file like the [`LAST_RUN_FILE`], keeping everything else in it.
+    pub fn save_to(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let mut state = read_state(path)?.unwrap_or_default();
+        state.insert(RECENT_COLORS_KEY.to_string(), serde_json::Value::from(self.colors.clone()));
+        write_state(path, state)
+    }
+}
+
+// The state file as a JSON object; None when it doesn't exist
+fn read_state(path: impl AsRef<Path>) -> Result<Option<serde_json::Map<String, serde_json::Value>>> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = std::fs::read_to_string(path)
+        .io_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|source| CertError::Json { context: format!("Failed to parse {}", path.display()), source })
+}
+
+fn write_state(path: impl AsRef<Path>, state: serde_json::Map<String, serde_json::Value>) -> Result<()> {
+    let path = path.as_ref();
+    let content = serde_json::to_string_pretty(&state)
+        .map_err(|source| CertError::Json { context: format!("Failed to serialize {}", path.display()), source })?;
+    std::fs::write(path, content)
+        .io_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Run a batch with previously collected settings.
+pub fn run_batch(
+    settings: &BatchSettings,
+    names: &[String],
+    progress: &dyn ProgressSink,
+) -> Result<BatchReport> {
+    run_job(settings, batch_job(settings, names)?, progress)
+}
+
+// Run a job made from `settings`, then clear its staging directory
+pub(crate) fn run_job(settings: &BatchSettings, job: CertificateJob, progress: &dyn ProgressSink) -> Result<BatchReport> {
+    let report = job.run_with_progress(progress);
+    // Left behind only if some file couldn't be moved
+    if settings.stage_locally {
+        let _ = std::fs::remove_dir(local_staging_dir());
+    }
+    report
+}
+
+// The job `run_batch` runs for these settings and names
+pub(crate) fn batch_job(settings: &BatchSettings, names: &[String]) -> Result<CertificateJob> {
+    let mut job = CertificateJob::new(&settings.template_file, names.to_vec())
+        .font(&settings.font_filename)
+        .font_size(settings.font_size)
+        .font_size_limits(settings.font_size_limits)
+        .strict_font_size(settings.strict_font_size)
+        .color(&settings.hex_color)
+        .position(Anchor::Center, settings.x_pos, settings.y_pos)
+        .allow_bleed(settings.allow_bleed)
+        .output_dir(&settings.output_dir)
+        .overwrite(settings.overwrite)
+        .duplicates(settings.duplicates.clone())
+        .name_case(settings.name_case)
+        .typography(settings.typography)
+        .case_filenames(settings.case_filenames)
+        .synthetic(settings.synthetic)
+        .font_variation(settings.font_variation.clone())
+        .graphics(settings.graphics.clone())
+        .patches(settings.patches.clone())
+        .compression(settings.compression)
+        .depth(settings.depth)
+        .format(settings.format)
+        .fingerprint(settings.fingerprint)
+        .rows(settings.rows.clone())
+        .sources(settings.sources.clone())
+        .emails(settings.emails.clone())
+        .records(settings.records.clone())
+        .partial(settings.selection.is_partial())
+        .dry_run(settings.dry_run)
+        .deterministic(settings.deterministic);
+    if let Some(effect) = &settings.effect {
+        job = job.effect(find_effect(effect)?);
+    }
+    if let Some(column) = &settings.group_by {
+        job = job.group_by(column);
+    }
+    if let Some(budget) = settings.memory_budget {
+        job = job.memory_budget(budget);
+    }
+    if let Some(preview) = settings.preview {
+        job = job.preview(preview);
+    }
+    if let Some(normalization) = settings.normalize {
+        job = job.normalize(normalization);
+    }
+    if let Some(watermark) = &settings.watermark {
+        job = job.watermark(watermark.clone());
+    }
+    if let Some(imposition) = &settings.imposition {
+        job = job.impose(imposition.clone());
+    }
+    if settings.stage_locally {
+        job = job.stage_locally(local_staging_dir());
+    }
+    Ok(job)
+}
+
+/// Measure the template, once patched, under the largest rectangle any name will occupy.
+pub fn check_text_region(settings: &BatchSettings, names: &[String]) -> Result<InkCoverage> {
+    let mut template = load_template(&settings.template_file)?;
+    apply_patches(&mut template, &settings.patches)?;
+    check_text_region_on(&template, settings, names)
+}
+
+/// [`check_text_region`] on `settings.template_file` already decoded and patched.
+pub fn check_text_region_on(template: &RgbaImage, settings: &BatchSettings, names: &[String]) -> Result<InkCoverage> {
+    let region = largest_text_box(settings, names)?;
+    Ok(measure_ink_coverage(template, region.x, region.y, region.width.max(0) as u32, region.height.max(0) as u32))
+}
+
+/// The box centered on the batch's position as wide as its widest name and as tall as its
+/// tallest, drawn as the batch draws them.
+pub fn largest_text_box(settings: &BatchSettings, names: &[String]) -> Result<TextBounds> {
+    let font = load_font(&settings.font_filename)?;
+    let instance = load_font_instance(&settings.font_filename, &settings.font_variation)?;
+    let options = TextOptions {
+        font: &font,
+        size: settings.font_size,
+        color: Rgba([0, 0, 0, 255]),
+        x: 0,
+        y: 0,
+        anchor: Anchor::Center,
+        decorations: &[],
+        synthetic: settings.synthetic,
+        variation: instance.as_ref(),
+        effect: None,
     };
-    
-    let font_size_input = get_user_input("Enter font size (default 40): ");
-    let font_size = if font_size_input.is_empty() { 40.0 } else { font_size_input.parse().unwrap_or(40.0) };
-    
-    let color_input = get_user_input("Enter text color (only hex like #000000 : ");
-    let hex_color = if color_input.is_empty() { "#000000".to_string() } else { color_input };
-    
-    // Get output directory
-    let output_dir = get_user_input("\nEnter output directory (default 'certificates'): ");
-    let output_dir = if output_dir.is_empty() { "certificates" } else { &output_dir };
-    
-    // Generate certificates
-    generate_certificates_batch(
-        &template_file,
-        output_dir,
-        &names,
-        x_pos,
-        y_pos,
-        &font_input,
-        font_size,
-        &hex_color,
-    )?;
-    
-    Ok(())
+
+    let mut max_width = 0;
+    let mut max_height = 0;
+    for name in names {
+        let bounds = layout_text(&settings.typography.apply(&settings.name_case.apply(name)), &options)?;
+        max_width = max_width.max(bounds.width);
+        max_height = max_height.max(bounds.height);
+    }
+
+    Ok(TextBounds { x: settings.x_pos - max_width / 2, y: settings.y_pos - max_height / 2, width: max_width, height: max_height })
 }
 
-// Function to create sample CSV files for testing
-pub fn create_sample_csv(filename: &str) -> Result<()> {
+/// Write a small sample CSV with a `Name` column, creating parent directories.
+pub fn create_sample_csv(filename: impl AsRef<Path>) -> Result<()> {
+    let filename = filename.as_ref();
     // Create parent directory if it doesn't exist
-    if let Some(parent) = Path::new(filename).parent() {
+    if let Some(parent) = filename.parent() {
         std::fs::create_dir_all(parent)
-            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+            .io_context(|| format!("Failed to create directory: {}", parent.display()))?;
     }
-    
+
     let csv_content = "Name\nAlice Johnson\nBob Smith\nCharlie Brown\nDiana Prince\nEva Martinez";
-    
+
     std::fs::write(filename, csv_content)
-        .with_context(|| format!("Failed to create sample CSV: {}", filename))?;
-    
-    println!("✅ Sample CSV created: {}", filename);
+        .io_context(|| format!("Failed to create sample CSV: {}", filename.display()))?;
+
     Ok(())
 }
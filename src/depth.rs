@@ -0,0 +1,191 @@
+// src/depth.rs
+//! Templates with 16 bits per channel, such as museum scans, kept at their depth.
+//!
+//! Names, graphics and effects are drawn on an 8-bit copy of the template as always. A
+//! [`DeepTemplate`] keeps the original's 16-bit pixels, and [`DeepTemplate::restore`] puts them
+//! back wherever a certificate still shows the template untouched, widening only the drawn
+//! pixels from 8 bits. Smooth gradients in the scan don't band, at the cost of larger files.
+//! PDFs, and templates normalized to another size, are drawn and saved at 8 bits; see
+//! [`deep_color`].
+//!
+//! Indexed and grayscale PNGs, with or without tRNS transparency, need none of this: the
+//! decoder expands them to RGBA with their transparency intact.
+//!
+//! ```
+//! use certificate_maker::depth::{DeepTemplate, encode_png16};
+//! use certificate_maker::editpng::PngCompression;
+//! use image::{DynamicImage, ImageBuffer, Rgb, Rgba};
+//!
+//! // Two shades 8 bits can't tell apart
+//! let scan = DynamicImage::ImageRgb16(ImageBuffer::from_fn(4, 1, |x, _| Rgb([1000 + x as u16, 0, 0])));
+//! let base = scan.to_rgba8();
+//! let deep = DeepTemplate::new(&scan, &base).unwrap();
+//!
+//! let mut drawn = base.clone();
+//! drawn.put_pixel(3, 0, Rgba([255, 255, 255, 255]));
+//! let restored = deep.restore(&base, &drawn);
+//! assert_eq!(restored.get_pixel(0, 0).0, [1000, 0, 0, 65535]);
+//! assert_eq!(restored.get_pixel(1, 0).0, [1001, 0, 0, 65535]);
+//! assert_eq!(restored.get_pixel(3, 0).0, [65535; 4]);
+//!
+//! let png = encode_png16(&restored, deep.has_alpha(), PngCompression::Balanced)?;
+//! assert_eq!(image::load_from_memory(&png)?.color(), image::ColorType::Rgb16);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use image::codecs::png::PngEncoder;
+use image::{ColorType, DynamicImage, ImageBuffer, ImageEncoder, ImageResult, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use crate::editpng::{PngCompression, png_encoder_settings};
+use crate::error::{CertError, IoContext, Result};
+use crate::job::CertificateFormat;
+
+/// An RGBA image with 16 bits per channel.
+pub type Rgba16Image = ImageBuffer<Rgba<u16>, Vec<u16>>;
+
+/// Whether a 16-bit template is drawn on at its depth or reduced to 8 bits first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DepthPolicy {
+    /// Save 16 bits per channel wherever the template and the output format have them.
+    #[default]
+    Keep,
+    /// Reduce every template to 8 bits, as certificates were before.
+    Reduce,
+}
+
+impl DepthPolicy {
+    pub const ALL: [DepthPolicy; 2] = [DepthPolicy::Keep, DepthPolicy::Reduce];
+
+    /// Name used on the command line: the most bits per channel a certificate gets.
+    pub fn name(self) -> &'static str {
+        match self {
+            DepthPolicy::Keep => "16",
+            DepthPolicy::Reduce => "8",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<DepthPolicy> {
+        DepthPolicy::ALL.into_iter().find(|policy| policy.name() == name.trim())
+    }
+}
+
+/// What became of a 16-bit template's depth in a batch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "depth", rename_all = "snake_case")]
+pub enum DeepColor {
+    /// Certificates were saved with 16 bits per channel.
+    Kept,
+    /// The template was reduced to 8 bits, for the reason given.
+    Reduced { reason: String },
+}
+
+/// Whether `path` is a PNG with 16 bits per channel, from its header alone, as the
+/// [analysis](crate::analysis::PngDetails) reports it.
+pub fn is_16_bit_png(path: impl AsRef<Path>) -> Result<bool> {
+    let path = path.as_ref();
+    let file = File::open(path).io_context(|| format!("Failed to open {}", path.display()))?;
+    let Ok(reader) = png::Decoder::new(BufReader::new(file)).read_info() else {
+        return Ok(false);
+    };
+    Ok(reader.info().bit_depth == png::BitDepth::Sixteen)
+}
+
+/// What a batch does with the depth of `template`: `None` for an 8-bit template, otherwise
+/// whether its 16 bits are kept under `policy`, saved as `format`, and `normalized` or not.
+pub fn deep_color(template: impl AsRef<Path>, policy: DepthPolicy, format: CertificateFormat, normalized: bool) -> Result<Option<DeepColor>> {
+    if !is_16_bit_png(template)? {
+        return Ok(None);
+    }
+    let reason = match (policy, format) {
+        (DepthPolicy::Reduce, _) => "8 bits per channel were asked for",
+        (_, CertificateFormat::Pdf) => "PDF pages are saved with 8 bits per channel",
+        _ if normalized => "the template is normalized at 8 bits per channel",
+        _ => return Ok(Some(DeepColor::Kept)),
+    };
+    Ok(Some(DeepColor::Reduced { reason: reason.to_string() }))
+}
+
+/// Whether a decoded image has 16 bits per channel.
+pub fn is_deep(color: ColorType) -> bool {
+    matches!(color, ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16)
+}
+
+/// A template's 16-bit pixels, lined up with the 8-bit copy certificates are drawn on.
+#[derive(Debug, Clone)]
+pub struct DeepTemplate {
+    pixels: Rgba16Image,
+    alpha: bool,
+}
+
+impl DeepTemplate {
+    /// The 16-bit pixels of `original` wherever `prepared`, the 8-bit template as it will be
+    /// drawn on, still matches them; pixels patched or bordered since are taken from
+    /// `prepared`. `None` when `original` isn't 16-bit or `prepared` has another size.
+    pub fn new(original: &DynamicImage, prepared: &RgbaImage) -> Option<DeepTemplate> {
+        if !is_deep(original.color()) || original.width() != prepared.width() || original.height() != prepared.height() {
+            return None;
+        }
+        let reduced = original.to_rgba8();
+        let mut pixels = original.to_rgba16();
+        for ((deep, reduced), prepared) in pixels.pixels_mut().zip(reduced.pixels()).zip(prepared.pixels()) {
+            if reduced != prepared {
+                *deep = widen(prepared);
+            }
+        }
+        Some(DeepTemplate { pixels, alpha: original.color().has_alpha() })
+    }
+
+    /// The certificate `drawn` on a copy of `base` at 16 bits: the template's own pixels where
+    /// nothing was drawn over them, the drawn ones widened.
+    pub fn restore(&self, base: &RgbaImage, drawn: &RgbaImage) -> Rgba16Image {
+        let mut restored = self.pixels.clone();
+        for ((deep, base), drawn) in restored.pixels_mut().zip(base.pixels()).zip(drawn.pixels()) {
+            if base != drawn {
+                *deep = widen(drawn);
+            }
+        }
+        restored
+    }
+
+    /// Whether the template had an alpha channel, so certificates keep one.
+    pub fn has_alpha(&self) -> bool {
+        self.alpha
+    }
+
+    /// Memory the 16-bit pixels take.
+    pub fn bytes(&self) -> u64 {
+        self.pixels.as_raw().len() as u64 * 2
+    }
+}
+
+/// Encode a 16-bit image as PNG, as RGBA or, without `alpha`, as RGB.
+pub fn encode_png16(img: &Rgba16Image, alpha: bool, compression: PngCompression) -> ImageResult<Vec<u8>> {
+    let (compression_type, filter) = png_encoder_settings(compression);
+    let (samples, color) = if alpha {
+        (img.as_raw().clone(), ColorType::Rgba16)
+    } else {
+        (img.as_raw().chunks_exact(4).flat_map(|pixel| [pixel[0], pixel[1], pixel[2]]).collect(), ColorType::Rgb16)
+    };
+    let bytes: Vec<u8> = samples.iter().flat_map(|sample| sample.to_ne_bytes()).collect();
+    let mut encoded = Vec::new();
+    PngEncoder::new_with_quality(&mut encoded, compression_type, filter)
+        .write_image(&bytes, img.width(), img.height(), color)?;
+    Ok(encoded)
+}
+
+/// Decode `path` for a [`DeepTemplate`] against `prepared`; `None` when it isn't 16-bit.
+pub fn load_deep_template(path: impl AsRef<Path>, prepared: &RgbaImage) -> Result<Option<DeepTemplate>> {
+    let path = path.as_ref();
+    let original = crate::orientation::open_oriented(path)
+        .map_err(|source| CertError::ImageDecode { path: path.to_path_buf(), source })?;
+    Ok(DeepTemplate::new(&original, prepared))
+}
+
+// 8 bits to 16, so 255 becomes 65535
+fn widen(pixel: &Rgba<u8>) -> Rgba<u16> {
+    Rgba(pixel.0.map(|channel| channel as u16 * 257))
+}
@@ -0,0 +1,1107 @@
+// src/display.rs
+// Console rendering of library results for the interactive binary
+// Text for people goes through say!/tr! and is translated; println! is kept for values printed
+// as they are (names, paths, the library's English messages) and for output meant for programs
+use certificate_maker::audit::{AuditReport, DuplicateSet};
+use certificate_maker::analysis::{DiffReport, ImageAnalysis, PrintReadiness, short_name};
+use certificate_maker::calibration::CalibrationSheet;
+use certificate_maker::csvexcelparser::{
+    BatchEstimate, BatchItem, BatchReport, BatchSettings, CsvFormat, DetectedFormat, ParsedCsv,
+    count_by_source,
+};
+use certificate_maker::depth::{DeepColor, deep_color};
+use certificate_maker::duplicates::find_duplicates;
+use certificate_maker::error::{CertError, Result};
+use certificate_maker::files::local_staging_dir;
+use certificate_maker::fingerprint::FingerprintMode;
+use certificate_maker::fontsize::{FontSizeCheck, FontSizeIssue};
+use certificate_maker::graphics::{GraphicField, GraphicKind};
+use certificate_maker::grouping::{UNGROUPED_DIR, entries_by_group};
+use certificate_maker::layout::{Coordinate, Layout, LayoutField, TemplateScale};
+use certificate_maker::editpng::{BorderSides, BorderStyle};
+use certificate_maker::imposition::{ImposedSheets, SHEET_PREFIX, SHEETS_PDF_FILENAME};
+use certificate_maker::job::{CertificateFormat, OverwritePolicy, PngCompression, SyntheticStyle};
+use certificate_maker::manifest::{DRY_RUN_MANIFEST_FILENAME, MANIFEST_FILENAME, ManifestEntry};
+use certificate_maker::memory::format_size;
+use certificate_maker::namecase::NameCase;
+use certificate_maker::normalize::NormalizePlan;
+use certificate_maker::orientation::oriented_dimensions;
+use certificate_maker::effects::{TextEffect, find_effect};
+use certificate_maker::palette::{PALETTE_PREFIX, Palette, installed as installed_palette, to_hex};
+use certificate_maker::patch::{Patch, PatchFill};
+use certificate_maker::perftest::PerfTestResult;
+use certificate_maker::placeholder::PlaceholderMatch;
+use certificate_maker::plan::BatchPlan;
+use certificate_maker::preview::preview_path;
+use certificate_maker::progress::ProgressSink;
+use certificate_maker::runlog::utc_timestamp;
+use certificate_maker::stamp::StampStyle;
+use certificate_maker::variation::format_axis_values;
+use certificate_maker::verify::{DirectoryVerification, Verification, VerifyStatus};
+use certificate_maker::{say, tr};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, UNIX_EPOCH};
+
+pub fn print_analysis(analysis: &ImageAnalysis) {
+    say!("=== Image File Analysis ===");
+    say!("File: {}", analysis.filename);
+    match analysis.format {
+        Some(format) => say!("Format: {}", format!("{:?}", format)),
+        None => say!("Format: unknown"),
+    }
+    say!("File size: {} bytes ({} KB)", 
+         analysis.file_size_bytes, 
+         format!("{:.2}", analysis.file_size_bytes as f64 / 1024.0));
+    
+    say!("\n--- Image Properties ---");
+    say!("Dimensions: {}x{} pixels", analysis.width, analysis.height);
+    match analysis.orientation {
+        Some(orientation) if analysis.orientation_applied => say!(
+            "Orientation: {} as its EXIF asks; dimensions and coordinates are of the upright image", orientation.describe()),
+        Some(orientation) => say!(
+            "Orientation: its EXIF asks for it to be {}, ignored (--ignore-exif-orientation); used as stored", orientation.describe()),
+        None => {}
+    }
+    say!("Total pixels: {}", analysis.pixel_count);
+    say!("Aspect ratio: {}", format!("{:.3}", analysis.aspect_ratio));
+    
+    // Separate coordinates section with useful positioning information
+    say!("\n--- Useful Coordinates ---");
+    let middle_x = analysis.center_x;
+    let middle_y = analysis.center_y;
+    let quarter_x = analysis.width / 4;
+    let quarter_y = analysis.height / 4;
+    let three_quarter_x = (analysis.width * 3) / 4;
+    let three_quarter_y = (analysis.height * 3) / 4;
+    
+    say!("🎯 Center point: ({}, {})", middle_x, middle_y);
+    say!("📍 Corner coordinates:");
+    say!("  • Top-left: (0, 0)");
+    say!("  • Top-right: ({}, 0)", analysis.width - 1);
+    say!("  • Bottom-left: (0, {})", analysis.height - 1);
+    say!("  • Bottom-right: ({}, {})", analysis.width - 1, analysis.height - 1);
+    
+    say!("📐 Edge midpoints:");
+    say!("  • Top edge center: ({}, 0)", middle_x);
+    say!("  • Bottom edge center: ({}, {})", middle_x, analysis.height - 1);
+    say!("  • Left edge center: (0, {})", middle_y);
+    say!("  • Right edge center: ({}, {})", analysis.width - 1, middle_y);
+    
+    say!("🔳 Quarter positions:");
+    say!("  • Top-left quarter: ({}, {})", quarter_x, quarter_y);
+    say!("  • Top-right quarter: ({}, {})", three_quarter_x, quarter_y);
+    say!("  • Bottom-left quarter: ({}, {})", quarter_x, three_quarter_y);
+    say!("  • Bottom-right quarter: ({}, {})", three_quarter_x, three_quarter_y);
+
+    say!("\n--- Color Information ---");
+    say!("Color type: {}", format!("{:?}", analysis.color_type));
+    say!("Bytes per pixel: {}", analysis.bytes_per_pixel);
+    say!("Has transparency: {}", analysis.has_transparency);
+    if let Some(alpha) = &analysis.alpha {
+        say!("  • Fully transparent: {}%", format!("{:.2}", alpha.transparent_fraction * 100.0));
+        say!("  • Partially transparent: {}%", format!("{:.2}", alpha.partial_fraction * 100.0));
+        say!("  • Opaque: {}%", format!("{:.2}", alpha.opaque_fraction * 100.0));
+    }
+    
+    if let Some(png) = &analysis.png {
+        say!("\n--- PNG Details ---");
+        say!("PNG color type: {}", format!("{:?}", png.color_type));
+        say!("Bit depth: {}", format!("{:?}", png.bit_depth));
+        say!("tRNS chunk: {}", if png.has_trns { tr!("present") } else { tr!("none") });
+        say!("Interlaced (Adam7): {}", png.interlaced);
+        say!("Chunks: {}", png.chunks.join(", "));
+        
+        say!("\n--- Color management ---");
+        match (&png.icc_profile_name, png.icc_profile_size) {
+            (Some(name), Some(size)) => match &png.icc_profile_description {
+                Some(description) if description != name => say!("ICC profile: {} ({} bytes): {}", name, size, description),
+                _ => say!("ICC profile: {} ({} bytes)", name, size),
+            },
+            _ => say!("ICC profile: none"),
+        }
+        match &png.srgb_intent {
+            Some(intent) => say!("sRGB chunk: present (rendering intent: {})", intent),
+            None => say!("sRGB chunk: none"),
+        }
+        match png.gamma {
+            Some(gamma) => say!("Gamma: {}", format!("{:.5}", gamma)),
+            None => say!("Gamma: none"),
+        }
+        say!("Chromaticities (cHRM): {}", if png.has_chromaticities { tr!("present") } else { tr!("none") });
+        if png.has_non_srgb_profile() {
+            say!("⚠️ Non-sRGB ICC profile present: text colors are drawn without color management and may not match on print");
+        }
+    }
+    
+    say!("\n--- Technical Details ---");
+    say!("Theoretical uncompressed size: {} bytes ({} KB)", 
+         analysis.theoretical_size_bytes, 
+         format!("{:.2}", analysis.theoretical_size_bytes as f64 / 1024.0));
+    say!("Compression ratio: {}:1", format!("{:.2}", analysis.compression_ratio));
+    say!("Size category: {}", analysis.size_category);
+}
+
+
+pub fn print_comparison_table(results: &[(PathBuf, Result<ImageAnalysis>)]) {
+    say!("\n=== Template Comparison ===");
+    println!("{:<32} {:>11} {:>7} {:<8} {:<6} {:>10} {:>12}",
+             tr!("File"), tr!("Dimensions"), tr!("Aspect"), tr!("Color"), tr!("Alpha"), tr!("Size (KB)"), tr!("Center"));
+    println!("{}", "-".repeat(92));
+
+    for (file_path, result) in results {
+        let name = short_name(file_path);
+        match result {
+            Ok(analysis) => {
+                println!("{:<32} {:>11} {:>7.3} {:<8} {:<6} {:>10.2} {:>12}",
+                         name,
+                         format!("{}x{}", analysis.width, analysis.height),
+                         analysis.aspect_ratio,
+                         format!("{:?}", analysis.color_type),
+                         if analysis.has_transparency { tr!("yes") } else { tr!("no") },
+                         analysis.file_size_bytes as f64 / 1024.0,
+                         format!("({}, {})", analysis.center_x, analysis.center_y));
+            }
+            Err(e) => println!("{:<32} ❌ {}", name, e),
+        }
+    }
+}
+
+
+pub fn print_diff_report(report: &DiffReport) {
+    say!("\n=== Image Comparison ===");
+    say!("First:  {} ({}x{})", report.first, report.first_dimensions.0, report.first_dimensions.1);
+    say!("Second: {} ({}x{})", report.second, report.second_dimensions.0, report.second_dimensions.1);
+
+    if !report.dimensions_match {
+        say!("❌ Dimensions differ - pixel comparison skipped");
+        return;
+    }
+
+    let total = report.first_dimensions.0 as u64 * report.first_dimensions.1 as u64;
+    say!("Differing pixels: {} of {} ({}%)",
+         report.differing_pixels,
+         total,
+         format!("{:.4}", report.differing_pixels as f64 / total.max(1) as f64 * 100.0));
+    say!("Max per-channel delta: {}", report.max_channel_delta);
+    if report.is_identical() {
+        say!("✅ Images are identical");
+    }
+}
+
+// One line per checked file: who it was issued to, or why it doesn't match
+pub fn print_verification(verification: &Verification) {
+    let file = verification.file.display();
+    match &verification.status {
+        VerifyStatus::Issued(entry) => match &entry.watermark {
+            Some(text) => say!("✅ {}: issued to {} (a draft watermarked {})", file, recipient_label(entry), text),
+            None => say!("✅ {}: issued to {}", file, recipient_label(entry)),
+        },
+        VerifyStatus::Artifact(artifact) => say!("✅ {}: made from the last batch as {}", file, artifact.path.display()),
+        VerifyStatus::Modified(entry) => say!("⚠️ {}: modified since it was issued to {}", file, recipient_label(entry)),
+        VerifyStatus::Unrecorded(entry) =>
+            say!("❔ {}: named like {}'s certificate, which was written before hashes were recorded; re-run the batch with --overwrite to record it",
+                 file, entry.name),
+        VerifyStatus::NotFound => say!("❌ {}: not found; no certificate in the manifest has this content or name", file),
+    }
+}
+
+pub fn print_directory_verification(report: &DirectoryVerification) {
+    for verification in report.discrepancies() {
+        print_verification(verification);
+    }
+    for entry in &report.missing {
+        say!("🕳️ {}: missing (issued to {})", entry.output_file.display(), recipient_label(entry));
+    }
+    say!("\n🔏 {} of {} files verified, {} modified, {} unknown or unrecorded, {} missing",
+         report.issued(), report.files.len(), report.modified(),
+         report.files.len() - report.issued() - report.modified(), report.missing.len());
+    if report.is_clean() {
+        say!("✅ Every file is exactly as it was issued.");
+    }
+}
+
+// "Ada Lovelace (row 3 of Names.csv, ada@example.org) on 2026-10-17 19:00:12 as certificates/..."
+fn recipient_label(entry: &ManifestEntry) -> String {
+    format!("{} as {}", recipient_details(entry), entry.output_file.display())
+}
+
+// The recipient label without the file it was issued as
+fn recipient_details(entry: &ManifestEntry) -> String {
+    let mut details = Vec::new();
+    match (entry.row, &entry.source) {
+        (Some(row), Some(source)) => details.push(format!("row {} of {}", row, source.display())),
+        (Some(row), None) => details.push(format!("row {}", row)),
+        _ => {}
+    }
+    if let Some(email) = &entry.email {
+        details.push(email.clone());
+    }
+    let mut label = entry.name.clone();
+    if !details.is_empty() {
+        label.push_str(&format!(" ({})", details.join(", ")));
+    }
+    if let Some(issued) = entry.issued {
+        let issued = utc_timestamp(UNIX_EPOCH + Duration::from_secs(issued));
+        label.push_str(&format!(" on {}", &issued[..19].replace('T', " ")));
+    }
+    if let Some(sent) = &entry.emailed {
+        label.push_str(&format!(", emailed to {}", sent.to));
+    }
+    label
+}
+
+// Where a placeholder was found, and the image to check it on
+pub fn print_placeholder(text: &str, found: &PlaceholderMatch, preview: &Path) {
+    let bounds = found.bounds;
+    say!("🎯 Found '{}' in the box ({}, {}) {}x{}, about {} px in this font ({}% match)",
+         text, bounds.x, bounds.y, bounds.width, bounds.height, found.font_size, format!("{:.0}", found.score * 100.0));
+    if found.score < 0.7 {
+        say!("⚠️ A loose match, often just another font; make sure the box is around the placeholder");
+    }
+    say!("👀 Check {}: the red box is what was found, the cross where names will be centered", preview.display());
+}
+
+// Where the audit looked and what it compared
+pub fn print_audit_report(report: &AuditReport) {
+    match report.manifests.as_slice() {
+        [] => say!("📜 No {} under {}; every file is unrecorded", MANIFEST_FILENAME, report.dir.display()),
+        manifests => {
+            let manifests: Vec<String> = manifests.iter().map(|manifest| manifest.display().to_string()).collect();
+            say!("📜 Looked files up in {}", manifests.join(", "));
+        }
+    }
+    say!("🔍 Compared {} certificates ({} not in any manifest)", report.scanned, report.unrecorded);
+    if report.sets.is_empty() {
+        say!("✅ No two certificates have names that only differ in accents, case or spacing.");
+        return;
+    }
+    let files: usize = report.sets.iter().map(|set| set.files.len()).sum();
+    say!("👯 {} sets of near-duplicate names, {} files in all:", report.sets.len(), files);
+    for (index, set) in report.sets.iter().enumerate() {
+        println!("\n{}. '{}'", index + 1, set.key);
+        print_duplicate_set(set);
+    }
+}
+
+// The files of a set, numbered from 1, with the row each came from; the first is the suggested one to keep
+pub fn print_duplicate_set(set: &DuplicateSet) {
+    for (index, file) in set.files.iter().enumerate() {
+        let suggested = if index == 0 { " ⭐" } else { "" };
+        println!("  {}. {}{}", index + 1, file.path.display(), suggested);
+        match &file.entry {
+            Some(entry) => say!("     issued to {}", recipient_details(entry)),
+            None => say!("     not in any manifest"),
+        }
+    }
+}
+
+// "semicolon (;)" rather than a bare character that may be invisible, like a tab
+fn delimiter_label(delimiter: u8) -> String {
+    match delimiter {
+        b',' => "comma (,)".to_string(),
+        b';' => "semicolon (;)".to_string(),
+        b'\t' => "tab".to_string(),
+        b'|' => "pipe (|)".to_string(),
+        other => format!("'{}'", other as char),
+    }
+}
+
+// How a CSV was read, marking which parts were detected and which were forced
+pub fn print_csv_format(detected: &DetectedFormat, forced: &CsvFormat) {
+    let source = |was_forced: bool| if was_forced { tr!("forced") } else { tr!("detected") };
+    say!("🔎 Encoding: {} ({}), delimiter: {} ({})",
+         detected.encoding.name(), source(forced.encoding.is_some()),
+         delimiter_label(detected.delimiter), source(forced.delimiter.is_some()));
+}
+
+// "1 extra field", "2 missing fields"
+fn field_difference(fields: usize, expected: usize) -> String {
+    let (count, kind) = if fields > expected { (fields - expected, "extra") } else { (expected - fields, "missing") };
+    format!("{} {} field{}", count, kind, if count == 1 { "" } else { "s" })
+}
+
+// Row warnings printed before the rest are only counted, unless --verbose
+const ROW_WARNINGS_SHOWN: usize = 5;
+
+// Names printed from each end of a long list; a list of twice as many or fewer is printed whole
+pub const NAMES_SHOWN: usize = 5;
+
+// The header and name column, and the rows that were skipped or recovered: all of them when
+// `verbose`, otherwise the first few and a count, so a list of thousands doesn't scroll away
+pub fn print_parsed_csv(parsed: &ParsedCsv, verbose: bool) {
+    say!("📋 Headers found: {}", format!("{:?}", parsed.headers));
+    say!("✅ Found name column '{}' at index {}", parsed.headers[parsed.name_column], parsed.name_column);
+    let skipped = parsed.skipped_rows.iter()
+        .map(|skipped| (skipped.row, format!("skipped, {}", skipped.reason.to_lowercase())));
+    let recovered = parsed.malformed_rows.iter()
+        .filter(|malformed| malformed.recovered)
+        .map(|malformed| (malformed.row, format!("recovered despite {}", field_difference(malformed.fields, parsed.headers.len()))));
+    let flagged = parsed.flagged_values.iter().map(|flagged| (flagged.row, flagged.describe()));
+    let mut warnings: Vec<(usize, String)> = skipped.chain(recovered).chain(flagged).collect();
+    warnings.sort_by_key(|&(row, _)| row);
+    let shown = if verbose { warnings.len() } else { warnings.len().min(ROW_WARNINGS_SHOWN) };
+    for (row, warning) in &warnings[..shown] {
+        say!("  ⚠️ Row {}: {}", row, warning);
+    }
+    if shown < warnings.len() {
+        say!("  ⚠️ … and {} more row warnings (--verbose lists every row)", warnings.len() - shown);
+    }
+    say!("✅ Successfully parsed {} names", parsed.names.len());
+}
+
+// How many names a list has, with the first and last few numbered by their place in it
+pub fn print_name_overview(names: &[String]) {
+    say!("✅ Found {} names:", names.len());
+    if names.len() <= NAMES_SHOWN * 2 {
+        print_names_from(names, 0);
+        return;
+    }
+    print_names_from(&names[..NAMES_SHOWN], 0);
+    say!("     … {} more …", names.len() - NAMES_SHOWN * 2);
+    print_names_from(&names[names.len() - NAMES_SHOWN..], names.len() - NAMES_SHOWN);
+}
+
+// Numbered names, the first being number `start + 1` of the list
+pub fn print_names_from(names: &[String], start: usize) {
+    for (i, name) in names.iter().enumerate() {
+        println!("  {}. {}", start + i + 1, name);
+    }
+}
+
+// "Ada Lovelace, Alan Turing, Grace Hopper and 997 more"
+fn name_examples(names: &[String]) -> String {
+    let examples = names.iter().take(3).map(String::as_str).collect::<Vec<_>>().join(", ");
+    match names.len().saturating_sub(3) {
+        0 => examples,
+        more => format!("{} and {} more", examples, more),
+    }
+}
+
+// Every row whose field count doesn't match the header, so the source file can be fixed
+pub fn print_malformed_rows(parsed: &ParsedCsv) {
+    if parsed.malformed_rows.is_empty() {
+        say!("📋 Malformed rows: none");
+        return;
+    }
+    say!("📋 Malformed rows: {} (header has {} fields)", parsed.malformed_rows.len(), parsed.headers.len());
+    for malformed in &parsed.malformed_rows {
+        say!("  Row {}: {} ({})",
+             malformed.row,
+             field_difference(malformed.fields, parsed.headers.len()),
+             if malformed.recovered { tr!("name recovered") } else { tr!("skipped") });
+    }
+}
+
+// Names that repeat (ignoring case and spacing), with the rows they are on
+pub fn print_duplicate_groups(parsed: &ParsedCsv) {
+    let groups = find_duplicates(&parsed.names);
+    if groups.is_empty() {
+        say!("📋 Duplicate names: none");
+        return;
+    }
+    say!("📋 Duplicate names: {} group(s)", groups.len());
+    for group in &groups {
+        let rows: Vec<String> = group.indices.iter().map(|&index| parsed.rows[index].to_string()).collect();
+        say!("  '{}' on rows {}", parsed.names[group.indices[0]], rows.join(", "));
+    }
+}
+
+pub fn print_estimate(estimate: &BatchEstimate) {
+    say!("💾 Estimated output: {} certificates × {} KB ≈ {} MB",
+         estimate.certificates,
+         format!("{:.1}", estimate.bytes_per_certificate as f64 / 1024.0),
+         format!("{:.1}", estimate.total_bytes as f64 / (1024.0 * 1024.0)));
+    match estimate.memory_budget {
+        Some(budget) => {
+            say!("🧠 Estimated peak memory: {} of a {} budget ({} threads)",
+                 format_size(estimate.peak_memory_bytes), format_size(budget), estimate.threads);
+            let available = rayon::current_num_threads().min(estimate.certificates.max(1));
+            if estimate.threads < available {
+                say!("   The budget leaves {} of {} threads; the batch will take longer", estimate.threads, available);
+            }
+        }
+        None => say!("🧠 Estimated peak memory: {} ({} threads)",
+                     format_size(estimate.peak_memory_bytes), estimate.threads),
+    }
+    match estimate.free_space_bytes {
+        Some(free) => say!("📦 Free space on output volume: {} MB", format!("{:.1}", free as f64 / (1024.0 * 1024.0))),
+        None => say!("📦 Free space on output volume: unknown"),
+    }
+    if let Some(line) = compression_comparison(estimate) {
+        println!("{}", line);
+    }
+}
+
+pub fn print_perf_test(result: &PerfTestResult) {
+    say!("🚀 {} certificates per second ({} in {} s on {} threads, {} KB each)",
+         format!("{:.1}", result.certificates_per_second()), result.certificates, format!("{:.2}", result.duration.as_secs_f64()), result.threads,
+         format!("{:.1}", result.bytes_per_certificate as f64 / 1024.0));
+    if let Some(performance) = result.performance.summary() {
+        println!("⏱️ {}", performance);
+    }
+}
+
+// "🗜️ Fast compression: certificates are about 38% larger than at balanced (412.0 KB vs 298.5 KB)"
+fn compression_comparison(estimate: &BatchEstimate) -> Option<String> {
+    let change = estimate.size_change_vs_balanced()?;
+    let balanced = estimate.balanced_bytes_per_certificate?;
+    let name = estimate.compression.name();
+    Some(format!("🗜️ {}{} compression: certificates are about {:.0}% {} than at balanced ({:.1} KB vs {:.1} KB)",
+                 name[..1].to_uppercase(), &name[1..], change.abs() * 100.0,
+                 if change >= 0.0 { tr!("larger") } else { tr!("smaller") },
+                 estimate.bytes_per_certificate as f64 / 1024.0, balanced as f64 / 1024.0))
+}
+
+// Everything a batch is about to do, numbered like the prompts so a field can be edited
+pub fn print_batch_plan(settings: &BatchSettings, names: &[String], estimate: Option<&BatchEstimate>) {
+    say!("\n📋 === Batch Summary ===");
+    let rows = names.len();
+    if settings.sources.is_empty() {
+        say!("  1. CSV file:     {} ({} rows)", settings.csv_file.display(), rows);
+    } else {
+        say!("  1. CSV files:    {} files merged ({} rows)", settings.csv_files().len(), rows);
+        for (source, count) in count_by_source(&settings.sources) {
+            say!("     • {}: {} rows", source.display(), count);
+        }
+    }
+    if !names.is_empty() {
+        say!("     Names:        {}", name_examples(names));
+    }
+    if settings.selection.is_partial() {
+        say!("     Rows:         {} (other certificates in the output are left alone)", settings.selection);
+    }
+    match oriented_dimensions(&settings.template_file) {
+        Ok((width, height)) => say!("  2. Template:     {} ({}x{} pixels)", settings.template_file.display(), width, height),
+        Err(_) => say!("  2. Template:     {} (dimensions unknown)", settings.template_file.display()),
+    }
+    let normalized = settings.normalize.is_some_and(|normalization| oriented_dimensions(&settings.template_file)
+        .is_ok_and(|dimensions| !normalization.plan(dimensions).is_identity()));
+    match deep_color(&settings.template_file, settings.depth, settings.format, normalized) {
+        Ok(Some(DeepColor::Kept)) => say!("     Depth:        16 bits per channel, kept in the certificates (--bit-depth 8 to reduce)"),
+        Ok(Some(DeepColor::Reduced { reason })) => say!("     Depth:        ⚠️ 16 bits per channel, reduced to 8 since {}", reason),
+        _ => {}
+    }
+    for patch in &settings.patches {
+        say!("     Patched:      {}", patch_label(patch));
+    }
+    if let Some(normalization) = &settings.normalize {
+        match oriented_dimensions(&settings.template_file) {
+            Ok(dimensions) => print_normalization(&normalization.plan(dimensions)),
+            Err(_) => say!("     Normalized to: {}", normalization),
+        }
+    }
+    say!("  3. Position:     text centered on ({}, {})", settings.x_pos, settings.y_pos);
+    if settings.font_variation.is_empty() {
+        say!("  4. Font:         {}", settings.font_filename.display());
+    } else {
+        say!("  4. Font:         {} ({})", settings.font_filename.display(), format_axis_values(&settings.font_variation));
+    }
+    if !settings.synthetic.is_none() {
+        say!("     Synthetic:    {} (drawn from the regular glyphs, not the font's own styles)", synthetic_label(&settings.synthetic));
+    }
+    say!("  5. Font size:    {}", settings.font_size);
+    say!("  6. Color:        {}", color_label(&settings.hex_color));
+    // The pattern of the file names, written out rather than made portable like a real name
+    let output = |dir: &Path| dir.join(format!("certificate_<Name>.{}", settings.format.name()));
+    match settings.effect.as_deref().map(|name| (name, find_effect(name))) {
+        Some((_, Ok(effect))) => say!("  7. Effect:       {} ({})", effect.name, effect_label(&effect)),
+        Some((name, Err(e))) => say!("  7. Effect:       {} ⚠️ {}", name, e),
+        None => say!("  7. Effect:       none"),
+    }
+    say!("  8. Output:       {}", output(&settings.output_dir).display());
+    if settings.stage_locally {
+        say!("     Staged:       written to {} first, then moved there at the end", local_staging_dir().display());
+    }
+    let case_scope = if settings.case_filenames { " (drawn text and filenames)" } else { "" };
+    say!("  9. Name case:    {}{}", settings.name_case.label(), case_scope);
+    if !settings.typography.is_none() {
+        say!("     Typography:   {}", settings.typography.label());
+    }
+    let existing = match settings.overwrite {
+        OverwritePolicy::Overwrite => "overwrite (--overwrite)",
+        OverwritePolicy::Rename => "save as _1, _2, ... (--rename)",
+        OverwritePolicy::SkipExisting => "skip (use --overwrite or --rename to change)",
+    };
+    say!("  📂 Existing files: {}", existing);
+    for graphic in &settings.graphics {
+        print_graphic(graphic);
+    }
+    if let Some(column) = &settings.group_by {
+        say!("  🗂️ Grouped by:   {} ({})", column, output(&settings.output_dir.join(format!("<{}>", column))).display());
+    }
+    if settings.compression != PngCompression::Balanced {
+        say!("  🗜️ Compression:  {}", settings.compression.name());
+    }
+    if let Some(preview) = settings.preview {
+        say!("  🖼️ Previews:     {} ({}px JPEG)", preview_path(&output(&settings.output_dir)).display(), preview);
+    }
+    match settings.fingerprint {
+        FingerprintMode::Off => {}
+        FingerprintMode::Hidden => say!("  🔏 Fingerprint:  in each PNG's metadata and the manifest, not drawn"),
+        FingerprintMode::Visible => say!("  🔏 Fingerprint:  drawn in the corner, and in each PNG's metadata and the manifest"),
+    }
+    if let Some(watermark) = &settings.watermark {
+        say!("  💧 Watermark:    {} (this run only; not remembered)", watermark.describe());
+    }
+    if let Some(imposition) = &settings.imposition {
+        let sheets = match imposition.format {
+            CertificateFormat::Png => format!("{}0001.png, ...", SHEET_PREFIX),
+            CertificateFormat::Pdf => SHEETS_PDF_FILENAME.to_string(),
+        };
+        say!("  🗞️ Sheets:       {} → {} (this run only; not remembered)", imposition.describe(), settings.output_dir.join(sheets).display());
+    }
+    if settings.deterministic {
+        say!("  🔒 Deterministic: same inputs write byte-identical certificates and manifest");
+    }
+    match estimate {
+        Some(estimate) => {
+            let seconds = estimate.estimated_duration().as_secs_f64();
+            say!("  ⚙️ Threads:      {}", estimate.threads);
+            say!("  ⏱️ Estimated time: {} s", format!("{:.1}", seconds.max(0.1)));
+            print_estimate(estimate);
+        }
+        None => say!("  ⚙️ Threads:      {}", rayon::current_num_threads()),
+    }
+}
+
+// The template's normalization and, when it scales too far, a warning
+fn print_normalization(plan: &NormalizePlan) {
+    if plan.is_identity() {
+        say!("  📏 Normalized:   already {}x{}, left as it is", plan.target.0, plan.target.1);
+        return;
+    }
+    say!("  📏 Normalized:   {} (positions and sizes scaled to match)", plan.describe());
+    if let Some(warning) = plan.warning() {
+        println!("  ⚠️ {}", warning);
+    }
+}
+
+// What an effect draws, its lengths in fractions of the font size
+pub fn effect_label(effect: &TextEffect) -> String {
+    let mut parts = Vec::new();
+    if let Some(gradient) = &effect.gradient {
+        parts.push(format!("gradient {} to {}", to_hex(gradient.top), to_hex(gradient.bottom)));
+    }
+    if let Some(stroke) = &effect.stroke {
+        parts.push(format!("{} outline {:.3} em", to_hex(stroke.color), stroke.width));
+    }
+    match effect.shadows.len() {
+        0 => {}
+        1 => parts.push("a shadow".to_string()),
+        count => parts.push(format!("{} shadows", count)),
+    }
+    if parts.is_empty() { "plain".to_string() } else { parts.join(", ") }
+}
+
+// The effects a batch can use, numbered after 0 for none
+pub fn print_effect_choices(names: &[String]) {
+    say!("  0. none");
+    for (i, name) in names.iter().enumerate() {
+        match find_effect(name) {
+            Ok(effect) => println!("  {}. {:<12} {}", i + 1, name, effect_label(&effect)),
+            Err(_) => println!("  {}. {}", i + 1, name),
+        }
+    }
+}
+
+// One plan line per graphic field
+fn print_graphic(graphic: &GraphicField) {
+    match &graphic.kind {
+        GraphicKind::Code128(style) => say!("  ▮ Barcode: {} at ({}, {}), {} px modules, {} px tall{}",
+                                            graphic.data, graphic.x, graphic.y, style.module_width, style.height,
+                                            if style.text_size.is_some() { ", with text" } else { "" }),
+        GraphicKind::Text(style) => say!("  🔤 Text: {} at ({}, {}), {} {} px {}{}",
+                                         graphic.data, graphic.x, graphic.y, style.font.display(), style.size, color_label(&style.color),
+                                         style.effect.as_ref().map(|effect| format!(", {}", effect.name)).unwrap_or_default()),
+        GraphicKind::Image(_) => say!("  🖼️ Image: {} at ({}, {})", graphic.data, graphic.x, graphic.y),
+        GraphicKind::Table(style) => {
+            let rows = if style.rows.is_empty() { format!("cells from {}", graphic.data) } else { format!("{} rows", style.rows.len()) };
+            let widths: Vec<String> = style.columns.iter().map(|column| column.width.to_string()).collect();
+            say!("  ▦ Table: {} at ({}, {}), columns {} px{}, {} {} px {}",
+                 rows, graphic.x, graphic.y, widths.join(" + "), if style.has_header() { " with a header" } else { "" },
+                 style.font.display(), style.size, color_label(&style.color));
+        }
+    }
+}
+
+// One plan line per border, in pixels
+pub fn print_border(border: &BorderStyle) {
+    let BorderSides { top, right, bottom, left } = border.thickness;
+    let thickness = if top == right && top == bottom && top == left {
+        format!("{:.0} px", top)
+    } else {
+        format!("{:.0}/{:.0}/{:.0}/{:.0} px", top, right, bottom, left)
+    };
+    let [r, g, b, a] = border.color.0;
+    say!("  ▭ Border: {} {} #{}, inset {} px, corner radius {} px (under the text)",
+         border.line.name(), thickness, format!("{:02X}{:02X}{:02X}{:02X}", r, g, b, a), format!("{:.0}", border.inset), format!("{:.0}", border.radius));
+}
+
+// A color as typed, with a palette reference shown as its name and hex, and a hex in the
+// palette followed by its name
+fn color_label(color: &str) -> String {
+    let Some(palette) = installed_palette() else {
+        return color.to_uppercase();
+    };
+    if let Some(name) = color.trim().strip_prefix(PALETTE_PREFIX) {
+        return match palette.get(name) {
+            Some(entry) => format!("{} ({})", entry.name, entry.hex()),
+            None => color.to_string(),
+        };
+    }
+    match palette.resolve(color).ok().and_then(|rgba| palette.name_of(rgba)) {
+        Some(name) => format!("{} ({})", color.to_uppercase(), name),
+        None => color.to_uppercase(),
+    }
+}
+
+// The palette's colors numbered for the color prompts
+pub fn print_palette(palette: &Palette) {
+    say!("  Palette{}:", if palette.strict { " (only these colors are allowed)" } else { "" });
+    for (i, entry) in palette.colors.iter().enumerate() {
+        println!("  {:>2}. {:<16} {}", i + 1, entry.name, entry.hex());
+    }
+}
+
+// A patch's rectangle, in the template's pixels, and its fill
+fn patch_label(patch: &Patch) -> String {
+    let fill = match &patch.fill {
+        PatchFill::Sample => "the color sampled around it".to_string(),
+        PatchFill::Clone => "a copy of the strip beside it".to_string(),
+        PatchFill::Color(color) => color_label(color),
+    };
+    format!("{}x{} at ({}, {}), filled with {}", patch.width, patch.height, patch.x, patch.y, fill)
+}
+
+// One plan line per patch
+pub fn print_patch(patch: &Patch) {
+    say!("  🩹 Patch: {} (before everything else)", patch_label(patch));
+}
+
+// The template and every field of a validated layout, positions in pixels
+pub fn print_layout(layout: &Layout) {
+    say!("\n📐 === Layout ===");
+    let dimensions = oriented_dimensions(&layout.template).unwrap_or_default();
+    say!("  Template: {} ({}x{} pixels)", layout.template.display(), dimensions.0, dimensions.1);
+    if let Some(header) = &layout.name_column {
+        say!("  Name column: {}", header);
+    }
+    if !layout.mapping.is_empty() {
+        say!("  Column mapping: {}", layout.mapping);
+    }
+    if let Some(compression) = layout.compression {
+        say!("  Compression: {}", compression.name());
+    }
+    if let Some(format) = layout.format {
+        say!("  Format: {}", format.name().to_uppercase());
+    }
+    let scale = layout.scale().unwrap_or_else(|_| TemplateScale::pixels(dimensions));
+    if layout.uses_dpi() {
+        let source = if layout.template_dpi.is_some() { tr!("set by the layout") } else if scale.dpi_assumed { tr!("assumed") } else { tr!("from the template") };
+        match layout.dpi {
+            Some(dpi) => say!("  DPI: {} ({}); pixel numbers were measured at {} DPI, so they are scaled by {}", scale.dpi, source, dpi, format!("{:.2}", scale.pixel_scale)),
+            None => say!("  DPI: {} ({})", scale.dpi, source),
+        }
+    }
+    if let Some(warning) = layout.dpi_warning() {
+        println!("  ⚠️ {}", warning);
+    }
+    print_layout_fields(&layout.fields, &scale);
+    say!("  The first text field is drawn as the name");
+    if let Some(back) = &layout.back {
+        let dimensions = oriented_dimensions(&back.template).unwrap_or_default();
+        say!("  Back: {} ({}x{} pixels), filled in from the same row as the front", back.template.display(), dimensions.0, dimensions.1);
+        let scale = back.scale(layout.dpi).unwrap_or_else(|_| TemplateScale::pixels(dimensions));
+        print_layout_fields(&back.fields, &scale);
+    }
+}
+
+// One line per field of a layout, or of its back
+fn print_layout_fields(fields: &[LayoutField], scale: &TemplateScale) {
+    for field in fields {
+        match field.graphic(scale) {
+            Ok(Some(graphic)) => print_graphic(&graphic),
+            Ok(None) => match (field.patch(scale), field.border(scale)) {
+                (Ok(Some(patch)), _) => print_patch(&patch),
+                (_, Ok(Some(border))) => print_border(&border),
+                (Err(problem), _) | (_, Err(problem)) => println!("  ⚠️ {}: {}", field.label(), problem),
+                _ => {}
+            },
+            Err(problem) => println!("  ⚠️ {}: {}", field.label(), problem),
+        }
+    }
+}
+
+// "faux bold +1.5 px, faux italic 12°"
+pub fn synthetic_label(style: &SyntheticStyle) -> String {
+    let mut parts = Vec::new();
+    if style.embolden > 0.0 {
+        parts.push(format!("faux bold +{} px", style.embolden));
+    }
+    if style.slant != 0.0 {
+        parts.push(format!("faux italic {}°", style.slant));
+    }
+    parts.join(", ")
+}
+
+// First five names as they will be drawn, so a bad transform is caught before the run
+pub fn print_name_case_preview(name_case: NameCase, names: &[String]) {
+    if name_case == NameCase::Preserve || names.is_empty() {
+        return;
+    }
+    say!("  🔤 Preview ({}):", name_case.label());
+    for name in names.iter().take(5) {
+        println!("     {} → {}", name, name_case.apply(name));
+    }
+}
+
+// What a stamp run is about to do, before the per-image lines
+pub fn print_stamp_plan(style: &StampStyle, images: usize, input_dir: &Path, output_dir: &Path) {
+    say!("📂 {} images in {} → {}", images, input_dir.display(), output_dir.display());
+    if !style.text.is_empty() {
+        say!("🔤 \"{}\" with its {} at ({}, {}), {} {} tall, {}",
+             style.text, style.anchor.name(), coordinate_label(&style.x), coordinate_label(&style.y),
+             style.font.display(), coordinate_label(&style.size), color_label(&style.color));
+    }
+    if let Some(logo) = &style.logo {
+        let width = logo.width.as_ref().map_or("its own size".to_string(), |width| format!("{} wide", coordinate_label(width)));
+        say!("🖼️ Logo {} with its {} at ({}, {}), {}",
+             logo.path.display(), logo.anchor.name(), coordinate_label(&logo.x), coordinate_label(&logo.y), width);
+    }
+}
+
+fn coordinate_label(coordinate: &Coordinate) -> String {
+    match coordinate {
+        Coordinate::Pixels(pixels) => format!("{}px", pixels),
+        Coordinate::Relative(text) => text.clone(),
+    }
+}
+
+// What a stamp run did or, for a dry run, would do
+pub fn print_stamp_summary(summary: &BatchReport, output_dir: &Path) {
+    if summary.dry_run {
+        say!("\n🧪 === Dry Run Complete: nothing was saved ===");
+        say!("✅ Would stamp: {} images", summary.generated);
+    } else {
+        say!("\n🎉 Stamping complete!");
+        say!("⚡ Used {} CPU cores", summary.threads);
+        say!("✅ Stamped: {} images", summary.generated);
+    }
+    if summary.skipped_existing > 0 {
+        say!("⏭️ Skipped (file already existed): {} images; use --overwrite or --rename to change", summary.skipped_existing);
+    }
+    if summary.renamed > 0 {
+        say!("🔀 Renamed (file already existed): {} images", summary.renamed);
+    }
+    if summary.failed > 0 {
+        say!("❌ Failed: {} images", summary.failed);
+        for item in summary.items.iter().filter(|item| item.error.is_some()) {
+            println!("   {}: {}", item.name, item.error.as_deref().unwrap_or_default());
+        }
+    }
+    if !summary.dry_run {
+        say!("📁 Stamped images saved in: {}", output_dir.display());
+    }
+    if let Some(performance) = summary.performance.summary() {
+        say!("⏱️ {}. Stage times are summed over the worker threads, so together they can exceed the {} s the run took.",
+             performance, format!("{:.1}", summary.duration.as_secs_f64()));
+    }
+}
+
+// Live progress line for one rendered row
+// Prints one line per row, like the batch always has; a dry run only prints rows with problems
+#[derive(Default)]
+pub struct ConsoleProgress {
+    total: AtomicUsize,
+    dry_run: bool,
+    // --verbose: also list what the typography pass changed in each row
+    verbose: bool,
+}
+
+impl ConsoleProgress {
+    pub fn new(dry_run: bool) -> Self {
+        ConsoleProgress { dry_run, ..ConsoleProgress::default() }
+    }
+
+    pub fn verbose(self, verbose: bool) -> Self {
+        ConsoleProgress { verbose, ..self }
+    }
+}
+
+impl ProgressSink for ConsoleProgress {
+    fn on_start(&self, total: usize) {
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    fn on_item(&self, index: usize, item: &BatchItem) {
+        if self.dry_run {
+            print_dry_run_item(item);
+        } else {
+            print_batch_item(index, self.total.load(Ordering::Relaxed), item);
+        }
+        if self.verbose {
+            print_retouched(item);
+        }
+    }
+
+    fn on_finish(&self, report: &BatchReport) {
+        for error in &report.progress_errors {
+            println!("⚠️ {}", error);
+        }
+    }
+}
+
+fn print_batch_item(completed: usize, total: usize, item: &BatchItem) {
+    let progress = (completed as f64 / total as f64) * 100.0;
+    match &item.error {
+        None => say!("✅ [{}%] Generated: {} (centered at {}, {})",
+                     format!("{:6.2}", progress), item.name, item.position.0, item.position.1),
+        Some(e) => say!("❌ [{}%] Failed: {} - {}", format!("{:6.2}", progress), item.name, e),
+    }
+    for warning in &item.warnings {
+        println!("   ⚠️ {}", warning);
+    }
+}
+
+fn print_retouched(item: &BatchItem) {
+    for value in &item.retouched {
+        println!("   ✒️ {}: \"{}\" → \"{}\"", value.field, value.before, value.after);
+    }
+}
+
+fn print_dry_run_item(item: &BatchItem) {
+    if let Some(e) = &item.error {
+        say!("❌ {} would fail: {}", item.name, e);
+    }
+    for warning in &item.warnings {
+        println!("⚠️ {}: {}", item.name, warning);
+    }
+}
+
+// Where each calibration sample landed, flagging any that run off the template
+pub fn print_calibration_sheet(sheet: &CalibrationSheet) {
+    say!("\n📏 Calibration sheet saved to: {}", sheet.path.display());
+    for sample in &sheet.samples {
+        let [r, g, b, _] = sample.color.0;
+        say!("  {} {} #{}  box ({}, {}) {}x{}  '{}'",
+             if sample.fits { "✅" } else { "⚠️" }, format!("{:<8}", sample.label), format!("{:02X}{:02X}{:02X}", r, g, b),
+             sample.bounds.x, sample.bounds.y, sample.bounds.width, sample.bounds.height, sample.text);
+    }
+    if sheet.samples.iter().any(|sample| !sample.fits) {
+        say!("⚠️ Text marked ⚠️ runs off the template; move it or lower the font size.");
+    }
+}
+
+// Findings of the print check, each with its remedy
+pub fn print_print_readiness(file: &Path, report: &PrintReadiness) {
+    let target = &report.target;
+    say!("🖨️ Print readiness of {} for {} at {} DPI:", short_name(file), target.paper, format!("{:.0}", target.dpi));
+    say!("  📏 Prints at {} DPI; closest paper shape: {}", format!("{:.0}", report.effective_dpi), report.closest_paper);
+    if report.is_ready() {
+        say!("  ✅ Ready to print");
+        return;
+    }
+    for finding in &report.findings {
+        println!("  ⚠️ {}", finding.message);
+        println!("     💡 {}", finding.hint);
+    }
+}
+
+// A font size too large or too small for the template: the measured height, in points at the
+// template's DPI when it records one, and the size to use instead
+pub fn print_font_size_check(check: &FontSizeCheck) {
+    let Some(issue) = check.issue else { return };
+    let share = format!("{:.0}", check.text_height as f32 / check.template_height.max(1) as f32 * 100.0);
+    match issue {
+        FontSizeIssue::TooLarge => say!("⚠️ Font size {} is too large for the template: the text is {} px tall, {}% of its {} px height (at most {}%)",
+                                        check.font_size, check.text_height, share, check.template_height,
+                                        format!("{:.0}", check.max_height / check.template_height.max(1) as f32 * 100.0)),
+        FontSizeIssue::TooSmall => say!("⚠️ Font size {} is too small to read: the text is {} px tall, under the legibility floor of {} px",
+                                        check.font_size, check.text_height, format!("{:.0}", check.min_height)),
+    }
+    match (check.points, check.dpi) {
+        (Some(points), Some(dpi)) => say!("   📏 That is {} pt at the template's {} DPI", format!("{:.1}", points), format!("{:.0}", dpi)),
+        _ => say!("   📏 The template records no DPI, so its size in print is unknown"),
+    }
+    if let Some(suggested) = check.suggested_size {
+        say!("   💡 Size {} is within the limits", suggested);
+    }
+}
+
+// What a real run with the same settings would do
+pub fn print_dry_run_summary(summary: &BatchReport, output_dir: &Path) {
+    say!("\n🧪 === Dry Run Complete: nothing was drawn or saved ===");
+    if let Some(plan) = &summary.normalization {
+        say!("📏 Template normalized: {}", plan.describe());
+        if let Some(warning) = plan.warning() {
+            println!("⚠️ {}", warning);
+        }
+    }
+    match &summary.deep_color {
+        Some(DeepColor::Kept) => say!("🎨 16-bit template: certificates keep 16 bits per channel"),
+        Some(DeepColor::Reduced { reason }) => say!("⚠️ 16-bit template reduced to 8 bits per channel since {}", reason),
+        None => {}
+    }
+    if let Some(watermark) = &summary.watermark {
+        say!("💧 Watermarked \"{}\": drafts only; run again without the flag for the final certificates", watermark);
+    }
+    if let Some(fingerprint) = &summary.fingerprint {
+        say!("🔏 Would carry the fingerprint {}", fingerprint.label());
+    }
+    if let Some(check) = &summary.font_size {
+        print_font_size_check(check);
+    }
+    say!("✅ Would generate: {} certificates", summary.generated);
+    say!("⏭️ Unchanged since the last run: {} certificates", summary.skipped_unchanged);
+    if summary.skipped_existing > 0 {
+        say!("⏭️ Would skip (file already existed): {} certificates", summary.skipped_existing);
+    }
+    if summary.renamed > 0 {
+        say!("🔀 Would rename (file already existed): {} certificates", summary.renamed);
+    }
+    if summary.failed > 0 {
+        say!("❌ Would fail: {} certificates", summary.failed);
+    }
+    if !summary.stale.is_empty() {
+        say!("🧹 {} certificates belong to names no longer in the CSV; a real run offers to delete them", summary.stale.len());
+    }
+    if let Some(estimate) = &summary.estimate {
+        print_estimate(estimate);
+    }
+    say!("📝 Would-be manifest: {}", output_dir.join(DRY_RUN_MANIFEST_FILENAME).display());
+
+    let problems = summary.problem_items().count();
+    if problems == 0 && summary.font_size.is_some() {
+        say!("⚠️ No row has problems, but the font size doesn't suit the template (see above); change it before the real run.");
+    } else if problems == 0 {
+        say!("🎉 No problems found; a real run with these settings should go through cleanly.");
+    } else {
+        say!("⚠️ {} rows have problems (listed above); fix them before the real run.", problems);
+    }
+}
+
+// What a plan will write and what it leaves alone, with the first rows that have problems
+pub fn print_plan_summary(plan: &BatchPlan) {
+    say!("\n📋 === Plan of {} certificates: nothing was drawn or saved ===", plan.rows.len());
+    say!("✅ Will generate: {} certificates into {}", plan.renderable(), plan.settings.output_dir.display());
+    say!("⏭️ Left alone: {} unchanged since the last run, {} with a file already in the way",
+         plan.skipped_unchanged, plan.skipped_existing);
+    let problems: Vec<String> = plan.rows.iter()
+        .filter_map(|row| match &row.error {
+            Some(error) => Some(format!("  ❌ {}: {}", row.name, error)),
+            None if !row.warnings.is_empty() => Some(format!("  ⚠️ {}: {}", row.name, row.warnings.join("; "))),
+            None => None,
+        })
+        .collect();
+    for line in problems.iter().take(ROW_WARNINGS_SHOWN) {
+        println!("{}", line);
+    }
+    if problems.len() > ROW_WARNINGS_SHOWN {
+        say!("  … and {} more rows with problems (every row is in the plan)", problems.len() - ROW_WARNINGS_SHOWN);
+    }
+}
+
+// The print sheets a batch made and where they went
+fn print_sheets(sheets: &ImposedSheets) {
+    say!("🗞️ Put {} certificates on {} sheet{} ({}x{} px at {} DPI{})",
+         sheets.certificates, sheets.sheets, if sheets.sheets == 1 { "" } else { "s" }, sheets.sheet_size.0, sheets.sheet_size.1, sheets.dpi,
+         if sheets.empty_cells > 0 { tr!(", {} empty cell{}", sheets.empty_cells, if sheets.empty_cells == 1 { "" } else { "s" }) } else { String::new() });
+    match sheets.files.as_slice() {
+        [file] => println!("   {}", file.display()),
+        [first, .., last] => println!("   {} … {}", first.display(), last.display()),
+        [] => {}
+    }
+}
+
+pub fn print_batch_summary(summary: &BatchReport, output_dir: &Path, x_pos: i32, y_pos: i32) {
+    say!("\n🎉 Parallel certificate generation complete!");
+    say!("⚡ Used {} CPU cores", summary.threads);
+    say!("🎯 All text was centered around ({}, {})", x_pos, y_pos);
+    if let Some(plan) = &summary.normalization {
+        say!("📏 Template normalized: {}", plan.describe());
+        if let Some(warning) = plan.warning() {
+            println!("⚠️ {}", warning);
+        }
+    }
+    match &summary.deep_color {
+        Some(DeepColor::Kept) => say!("🎨 16-bit template: certificates keep 16 bits per channel"),
+        Some(DeepColor::Reduced { reason }) => say!("⚠️ 16-bit template reduced to 8 bits per channel since {}", reason),
+        None => {}
+    }
+    if let Some(watermark) = &summary.watermark {
+        say!("💧 Watermarked \"{}\": drafts only; run again without the flag for the final certificates", watermark);
+    }
+    if let Some(fingerprint) = &summary.fingerprint {
+        say!("🔏 Fingerprint {}: the settings behind it are in {}", fingerprint.label(), MANIFEST_FILENAME);
+    }
+    if let Some(check) = &summary.font_size {
+        print_font_size_check(check);
+    }
+    say!("✅ Successfully generated: {} certificates", summary.generated);
+    if let Some(sheets) = &summary.sheets {
+        print_sheets(sheets);
+    }
+    say!("⏭️ Skipped (unchanged): {} certificates", summary.skipped_unchanged);
+    if summary.skipped_existing > 0 {
+        say!("⏭️ Skipped (file already existed): {} certificates", summary.skipped_existing);
+    }
+    if summary.renamed > 0 {
+        say!("🔀 Renamed (file already existed): {} certificates", summary.renamed);
+    }
+    if summary.failed > 0 {
+        say!("❌ Failed to generate: {} certificates", summary.failed);
+    }
+    say!("📁 Certificates saved in: {}", output_dir.display());
+    print_groups(&summary.entries);
+    if let Some(line) = summary.estimate.as_ref().and_then(compression_comparison) {
+        println!("{}", line);
+    }
+    if let Some(performance) = summary.performance.summary() {
+        say!("⏱️ {}. Stage times are summed over the worker threads, so together they can exceed the {} s the batch took.",
+             performance, format!("{:.1}", summary.duration.as_secs_f64()));
+    }
+}
+
+// Certificates per group of a grouped batch, in distribution order; nothing for other batches
+fn print_groups(entries: &[ManifestEntry]) {
+    if entries.iter().all(|entry| entry.group.is_none()) {
+        return;
+    }
+    say!("🗂️ Groups:");
+    for (group, members) in entries_by_group(entries) {
+        match group {
+            Some(group) => say!("   • {}: {} certificates", group, members.len()),
+            None => say!("   • {} (empty group column): {} certificates", UNGROUPED_DIR, members.len()),
+        }
+    }
+}
+
+// Extra advice for library errors the user can fix themselves
+pub fn print_error_hint(error: &anyhow::Error) {
+    let Some(cert_error) = error.chain().find_map(|cause| cause.downcast_ref::<CertError>()) else {
+        return;
+    };
+
+    let hint = match cert_error {
+        CertError::FontNotFound(_) | CertError::FontParse(_) =>
+            "Put a valid .ttf or .otf file in the assets/ directory and select it by number",
+        CertError::MissingColumn { .. } =>
+            "Rename the header of the column holding the names to 'Name', or pass --name-column <header> (debug it with option 5)",
+        CertError::DuplicateNames(_) =>
+            "Fix the repeated rows in the CSV, or pass --duplicates keep-all or --duplicates dedupe",
+        CertError::NoNames(_) => "Check that the 'Name' column has values below the header row",
+        CertError::MalformedJson { .. } | CertError::UnexpectedJson { .. } =>
+            "A JSON name list must be an array like [{\"name\": \"Ada\"}] or [\"Ada\", \"Grace\"]",
+        CertError::InvalidColor { .. } => "Use a hex color like #FF0000 or a named color like 'red'",
+        CertError::UnknownPaletteColor { .. } | CertError::OffPalette { .. } =>
+            "Pick a color from palette.toml, next to certmaker.json, by its name as palette:<name>",
+        CertError::PaletteParse { .. } =>
+            "palette.toml lists colors under [colors] as name = \"#RRGGBB\", and may set strict = true",
+        CertError::ImageDecode { .. } => "Re-export the template as PNG or JPG (debug it with option 6)",
+        CertError::DirectoryNotFound { .. } | CertError::NoFilesFound { .. } =>
+            "Run from the project directory so excelcsvs/, Template/ and assets/ are found",
+        _ => return,
+    };
+    println!("💡 {}", hint);
+}
@@ -0,0 +1,90 @@
+// src/dpi.rs
+//! The resolution a template image says it has, so a layout measured in millimetres or
+//! points lands in the same place on a 300 DPI print template and a 96 DPI web one.
+//!
+//! PNGs store it in their `pHYs` chunk and JPEGs in their JFIF header. Many files have
+//! neither; callers fall back to [`ASSUMED_DPI`] and say so.
+//!
+//! ```
+//! use certificate_maker::dpi::{image_dpi, mm_to_pixels, points_to_pixels};
+//! use std::io::BufWriter;
+//!
+//! let path = std::env::temp_dir().join(format!("certmaker-dpi-{}.png", std::process::id()));
+//! let mut encoder = png::Encoder::new(BufWriter::new(std::fs::File::create(&path)?), 4, 4);
+//! encoder.set_color(png::ColorType::Rgba);
+//! // 300 DPI is 11811 pixels per metre
+//! encoder.set_pixel_dims(Some(png::PixelDimensions { xppu: 11811, yppu: 11811, unit: png::Unit::Meter }));
+//! encoder.write_header()?.write_image_data(&[255; 64])?;
+//!
+//! let dpi = image_dpi(&path)?.unwrap();
+//! assert_eq!(dpi.round(), 300.0);
+//! assert_eq!(mm_to_pixels(25.4, dpi).round(), 300.0);
+//! assert_eq!(points_to_pixels(72.0, 96.0), 96.0);
+//! std::fs::remove_file(&path)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use crate::error::{CertError, IoContext, Result};
+use crate::pdf::DEFAULT_PDF_DPI;
+
+/// Resolution taken for a template that doesn't record one: 300 DPI, as for PDFs, since
+/// print templates are the ones laid out in physical units.
+pub const ASSUMED_DPI: f32 = DEFAULT_PDF_DPI;
+
+const MM_PER_INCH: f64 = 25.4;
+const POINTS_PER_INCH: f64 = 72.0;
+
+/// The horizontal resolution recorded in a PNG or JPEG, in dots per inch; `None` when the file
+/// has none, records only an aspect ratio, or is in another format.
+pub fn image_dpi(path: impl AsRef<Path>) -> Result<Option<f32>> {
+    let path = path.as_ref();
+    let open = || File::open(path).io_context(|| format!("Failed to open {}", path.display()));
+    // Enough for the PNG signature or a JPEG's JFIF header
+    let mut header = Vec::new();
+    open()?.take(18).read_to_end(&mut header).io_context(|| format!("Failed to read {}", path.display()))?;
+    if header.starts_with(b"\x89PNG\r\n\x1a\n") {
+        // Only the chunks before the image data are read
+        let reader = png::Decoder::new(BufReader::new(open()?)).read_info()
+            .map_err(|source| CertError::PngDecode { path: path.to_path_buf(), source })?;
+        return Ok(reader.info().pixel_dims
+            .filter(|dims| dims.unit == png::Unit::Meter && dims.xppu > 0)
+            .map(|dims| (dims.xppu as f64 * MM_PER_INCH / 1000.0) as f32));
+    }
+    if header.starts_with(&[0xFF, 0xD8]) {
+        return Ok(jfif_dpi(&header));
+    }
+    Ok(None)
+}
+
+// The density in a JFIF APP0 segment right after the start-of-image marker:
+// FF D8 FF E0 <length> "JFIF\0" <version> <units> <x density> <y density>
+fn jfif_dpi(bytes: &[u8]) -> Option<f32> {
+    if bytes.len() < 16 || bytes[2..4] != [0xFF, 0xE0] || &bytes[6..11] != b"JFIF\0" {
+        return None;
+    }
+    let density = u16::from_be_bytes([bytes[14], bytes[15]]) as f32;
+    match bytes[13] {
+        _ if density == 0.0 => None,
+        1 => Some(density),
+        2 => Some(density * MM_PER_INCH as f32 / 10.0),
+        _ => None,
+    }
+}
+
+/// `mm` millimetres in pixels at `dpi`.
+pub fn mm_to_pixels(mm: f64, dpi: f32) -> f64 {
+    mm / MM_PER_INCH * dpi as f64
+}
+
+/// `pixels` at `dpi` in millimetres.
+pub fn pixels_to_mm(pixels: f64, dpi: f32) -> f64 {
+    pixels / dpi as f64 * MM_PER_INCH
+}
+
+/// `points` typographic points (1/72 inch) in pixels at `dpi`.
+pub fn points_to_pixels(points: f64, dpi: f32) -> f64 {
+    points / POINTS_PER_INCH * dpi as f64
+}
@@ -0,0 +1,181 @@
+// src/duplicates.rs
+//! Repeated names in a name list, and how each group of them was resolved.
+//!
+//! ```
+//! use certificate_maker::duplicates::{DuplicatePolicy, find_duplicates};
+//!
+//! let names = vec!["Alice Smith".to_string(), "Bob".to_string(), "alice  smith".to_string()];
+//! assert_eq!(find_duplicates(&names)[0].indices, vec![0, 2]);
+//!
+//! let resolved = DuplicatePolicy::Dedupe.apply(&names, &[2, 3, 4], &[])?;
+//! assert_eq!(resolved.names, vec!["Alice Smith".to_string(), "Bob".to_string()]);
+//! assert_eq!(resolved.rows, vec![2, 3]);
+//! assert_eq!(resolved.decisions[0].rows, vec![2, 4]);
+//! # Ok::<(), certificate_maker::error::CertError>(())
+//! ```
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use crate::error::{CertError, Result};
+
+/// Names that are equal once case and whitespace are ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    /// Positions in the name list, in list order.
+    pub indices: Vec<usize>,
+}
+
+/// What two names have in common when they count as duplicates: lowercase, single spaces.
+pub fn duplicate_key(name: &str) -> String {
+    name.split_whitespace().map(str::to_lowercase).collect::<Vec<_>>().join(" ")
+}
+
+/// Groups of two or more duplicate names, ordered by their first occurrence.
+pub fn find_duplicates(names: &[String]) -> Vec<DuplicateGroup> {
+    let mut groups: Vec<DuplicateGroup> = Vec::new();
+    let mut group_of_key = HashMap::new();
+    for (index, name) in names.iter().enumerate() {
+        let group = *group_of_key.entry(duplicate_key(name)).or_insert_with(|| {
+            groups.push(DuplicateGroup { indices: Vec::new() });
+            groups.len() - 1
+        });
+        groups[group].indices.push(index);
+    }
+    groups.retain(|group| group.indices.len() > 1);
+    groups
+}
+
+/// How duplicates are resolved without asking.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DuplicatePolicy {
+    /// Refuse to run while any name is repeated.
+    Fail,
+    /// Give every row its own certificate; later ones get `_1`, `_2`, ... suffixes.
+    KeepAll,
+    /// Keep only the first row of each group.
+    #[default]
+    Dedupe,
+}
+
+/// What was done about one group of duplicates.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum DuplicateResolution {
+    KeepAll,
+    KeepFirst,
+    /// The name on `row` (of `source`, when names from several files were merged) was
+    /// corrected to `name`; the other rows are kept as they are.
+    Edited {
+        row: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        source: Option<PathBuf>,
+        name: String,
+    },
+}
+
+/// A duplicate group and its resolution, as recorded in the manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DuplicateDecision {
+    /// Line numbers of the group's rows (see [`ParsedCsv::rows`](crate::csvexcelparser::ParsedCsv::rows)).
+    pub rows: Vec<usize>,
+    /// The file of each row, when names from several files were merged.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<PathBuf>,
+    /// The names as they appeared in the file, in row order.
+    pub names: Vec<String>,
+    #[serde(flatten)]
+    pub resolution: DuplicateResolution,
+}
+
+/// The names left once duplicates are resolved, with the decisions made.
+#[derive(Debug, Clone, Default)]
+pub struct ResolvedNames {
+    pub names: Vec<String>,
+    /// Line number of each remaining name, in the same order as `names`.
+    pub rows: Vec<usize>,
+    /// File of each remaining name; empty when no sources were given.
+    pub sources: Vec<PathBuf>,
+    pub decisions: Vec<DuplicateDecision>,
+}
+
+impl DuplicatePolicy {
+    /// Resolve every duplicate group by this policy; see [`resolve_duplicates`] for `rows` and
+    /// `sources`.
+    pub fn apply(self, names: &[String], rows: &[usize], sources: &[PathBuf]) -> Result<ResolvedNames> {
+        let groups = find_duplicates(names);
+        match self {
+            DuplicatePolicy::Fail if !groups.is_empty() => Err(CertError::DuplicateNames(
+                groups.iter().map(|group| describe_group(group, names, rows, sources)).collect(),
+            )),
+            DuplicatePolicy::Fail | DuplicatePolicy::KeepAll => {
+                resolve_duplicates(names, rows, sources, |_| Ok(DuplicateResolution::KeepAll))
+            }
+            DuplicatePolicy::Dedupe => resolve_duplicates(names, rows, sources, |_| Ok(DuplicateResolution::KeepFirst)),
+        }
+    }
+}
+
+// "row 4" or, with sources, "walk-ins.csv row 4"
+fn describe_row(index: usize, rows: &[usize], sources: &[PathBuf]) -> String {
+    match sources.get(index) {
+        Some(source) => format!("{} row {}", source.file_name().unwrap_or(source.as_os_str()).to_string_lossy(), rows[index]),
+        None => format!("row {}", rows[index]),
+    }
+}
+
+// "'Alice Smith' (row 2, row 4)"
+fn describe_group(group: &DuplicateGroup, names: &[String], rows: &[usize], sources: &[PathBuf]) -> String {
+    let rows: Vec<String> = group.indices.iter().map(|&index| describe_row(index, rows, sources)).collect();
+    format!("'{}' ({})", names[group.indices[0]], rows.join(", "))
+}
+
+/// Ask `decide` about every duplicate group and apply the answers, returning the names to
+/// render and the decisions to record. `rows` holds the line number of each name; `sources`
+/// holds the file of each name when several files were merged, and is empty otherwise.
+pub fn resolve_duplicates<E>(
+    names: &[String],
+    rows: &[usize],
+    sources: &[PathBuf],
+    mut decide: impl FnMut(&DuplicateGroup) -> std::result::Result<DuplicateResolution, E>,
+) -> std::result::Result<ResolvedNames, E> {
+    let mut names = names.to_vec();
+    let mut dropped = HashSet::new();
+    let mut decisions = Vec::new();
+
+    for group in find_duplicates(&names) {
+        let resolution = decide(&group)?;
+        let decision = DuplicateDecision {
+            rows: group.indices.iter().map(|&index| rows[index]).collect(),
+            sources: group.indices.iter().filter_map(|&index| sources.get(index).cloned()).collect(),
+            names: group.indices.iter().map(|&index| names[index].clone()).collect(),
+            resolution,
+        };
+        match &decision.resolution {
+            DuplicateResolution::KeepAll => {}
+            DuplicateResolution::KeepFirst => dropped.extend(group.indices[1..].iter().copied()),
+            DuplicateResolution::Edited { row, source, name } => {
+                let edited = group.indices.iter()
+                    .find(|&&index| rows[index] == *row && sources.get(index) == source.as_ref());
+                if let Some(&index) = edited {
+                    names[index] = name.clone();
+                }
+            }
+        }
+        decisions.push(decision);
+    }
+
+    let kept = |index: &usize| !dropped.contains(index);
+    let sources = if sources.is_empty() {
+        Vec::new()
+    } else {
+        sources.iter().enumerate().filter(|(index, _)| kept(index)).map(|(_, source)| source.clone()).collect()
+    };
+    let (names, rows) = names.into_iter()
+        .zip(rows.iter().copied())
+        .enumerate()
+        .filter(|(index, _)| kept(index))
+        .map(|(_, kept)| kept)
+        .unzip();
+    Ok(ResolvedNames { names, rows, sources, decisions })
+}
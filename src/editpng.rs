@@ -1,258 +1,1279 @@
 // src/editpng.rs
-use anyhow::{Context, Result};
-use image::{Rgba, open, ImageFormat};
+//! Text rendering onto template images.
+use crate::error::{CertError, Result};
+use crate::effects::{TextEffect, paint_effect};
+use crate::files::list_files_with_extensions;
+use crate::fontnames::family_name;
+use crate::measure::measure_scaled;
+use crate::orientation::open_oriented;
+use crate::palette::{self, PALETTE_PREFIX};
+use crate::variation::FontInstance;
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use image::{ColorType, ImageEncoder, ImageFormat, ImageResult, Rgba, RgbaImage};
 use imageproc::drawing::draw_text_mut;
 use rusttype::{Font, Scale, point};
-use std::fs;
-use std::io::{self, Write};
-use std::path::Path;
-
-// Function to list all font files in assets directory
-pub fn list_available_fonts() -> Result<Vec<String>> {
-    let assets_dir = "assets";
-    let mut font_files = Vec::new();
-    
-    if Path::new(assets_dir).exists() {
-        let entries = fs::read_dir(assets_dir)
-            .with_context(|| "Failed to read assets directory")?;
-        
-        for entry in entries {
-            let entry = entry?;
-            let path = entry.path();
-            if let Some(extension) = path.extension() {
-                let ext = extension.to_string_lossy().to_lowercase();
-                if ext == "ttf" || ext == "otf" {
-                    if let Some(filename) = path.file_name() {
-                        font_files.push(filename.to_string_lossy().to_string());
-                    }
-                }
-            }
-        }
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Default fonts directory; bare font filenames that aren't paths are looked up here.
+pub const FONTS_DIR: &str = "assets";
+
+/// Font name that selects the DejaVu Sans copy compiled into the binary.
+pub const BUILTIN_FONT: &str = "Built-in DejaVu Sans";
+
+#[cfg(feature = "embedded-font")]
+const EMBEDDED_FONT: &[u8] = include_bytes!("../assets/DejaVuSans.ttf");
+
+/// List all font files (.ttf/.otf) in `fonts_dir` and its subdirectories, as paths relative to it.
+///
+/// When the directory is missing or has no fonts, the built-in font is offered instead
+/// (if the `embedded-font` feature is enabled).
+pub fn list_available_fonts(fonts_dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+    match list_font_files(fonts_dir.as_ref()) {
+        #[cfg(feature = "embedded-font")]
+        Err(CertError::DirectoryNotFound { .. } | CertError::NoFilesFound { .. }) => Ok(vec![PathBuf::from(BUILTIN_FONT)]),
+        result => result,
+    }
+}
+
+fn list_font_files(fonts_dir: &Path) -> Result<Vec<PathBuf>> {
+    if !fonts_dir.exists() {
+        return Err(CertError::DirectoryNotFound { dir: fonts_dir.to_path_buf(), contents: "font files" });
     }
-    
+
+    let font_files = list_files_with_extensions(fonts_dir, &["ttf", "otf"])?;
     if font_files.is_empty() {
-        return Err(anyhow::anyhow!("No font files found in assets directory"));
+        return Err(CertError::NoFilesFound { dir: fonts_dir.to_path_buf(), contents: ".ttf or .otf files" });
     }
-    
-    font_files.sort();
     Ok(font_files)
 }
 
-// Function to load font data from filename
-fn load_font_data(font_filename: &str) -> Result<Vec<u8>> {
-    let font_path = format!("assets/{}", font_filename);
-    fs::read(&font_path)
-        .with_context(|| format!("Failed to read font file: {}", font_path))
+/// File a font name refers to: the name itself when it is an existing path, otherwise
+/// the file of that name in [`FONTS_DIR`].
+pub fn font_path(font: impl AsRef<Path>) -> PathBuf {
+    let path = font.as_ref();
+    if path.exists() {
+        path.to_path_buf()
+    } else {
+        Path::new(FONTS_DIR).join(path)
+    }
+}
+
+/// Read the raw bytes of a font file (see [`font_path`]), or of the built-in font. A name
+/// that is no file, like "DejaVu Sans Bold", is looked up by family and style in
+/// [`FONTS_DIR`] with [`find_font`](crate::fontnames::find_font). Files are read through the
+/// [font cache](crate::fontcache::shared), again only once they change.
+pub fn load_font_data(font: impl AsRef<Path>) -> Result<Vec<u8>> {
+    match font_file(font.as_ref())? {
+        Some(path) => crate::fontcache::shared().data(path),
+        #[cfg(feature = "embedded-font")]
+        None => Ok(EMBEDDED_FONT.to_vec()),
+        #[cfg(not(feature = "embedded-font"))]
+        None => unreachable!("no built-in font without the embedded-font feature"),
+    }
+}
+
+/// Load and parse a font (see [`load_font_data`]). Every job using the same file shares one
+/// parsed copy from the [font cache](crate::fontcache::shared).
+pub fn load_font(font: impl AsRef<Path>) -> Result<Font<'static>> {
+    let font = font.as_ref();
+    match font_file(font)? {
+        Some(path) => crate::fontcache::shared().font(path),
+        #[cfg(feature = "embedded-font")]
+        None => Font::try_from_bytes(EMBEDDED_FONT).ok_or_else(|| CertError::FontParse(font.to_path_buf())),
+        #[cfg(not(feature = "embedded-font"))]
+        None => unreachable!("no built-in font without the embedded-font feature"),
+    }
 }
 
-// Function to convert hex color to RGBA
-pub fn hex_to_rgba(hex: &str) -> Result<Rgba<u8>> {
-    let hex = hex.trim_start_matches('#');
-    
+// The file a font name stands for, or None for the built-in font
+fn font_file(font: &Path) -> Result<Option<PathBuf>> {
+    #[cfg(feature = "embedded-font")]
+    if font == Path::new(BUILTIN_FONT) {
+        return Ok(None);
+    }
+
+    let font_path = font_path(font);
+    if font_path.exists() {
+        return Ok(Some(font_path));
+    }
+    // Names with an extension or a directory were meant as files
+    match font.extension().or(font.parent().and_then(Path::file_name)) {
+        Some(_) => Err(CertError::FontNotFound(font_path)),
+        None => match crate::fontnames::find_font(FONTS_DIR, &font.to_string_lossy())? {
+            found if found == font => Err(CertError::FontNotFound(font_path)),
+            found => font_file(&found),
+        },
+    }
+}
+
+/// Convert a `#RRGGBB` or `#RRGGBBAA` hex color to RGBA; the `#` is optional and surrounding
+/// spaces are ignored. `palette:<name>` is that entry of the [installed](crate::palette::install)
+/// palette. Anything else, from a CSV cell or a prompt, is an error naming the problem, never
+/// a panic.
+///
+/// ```
+/// use certificate_maker::editpng::hex_to_rgba;
+/// use image::Rgba;
+///
+/// assert_eq!(hex_to_rgba("#1A1A40")?, Rgba([26, 26, 64, 255]));
+/// assert_eq!(hex_to_rgba(" c0102080 ")?, Rgba([192, 16, 32, 128]));
+/// assert_eq!(hex_to_rgba("#ffée0").unwrap_err().to_string(),
+///            "Invalid hex color '#ffée0': 'é' is not a hex digit. Use #RRGGBB or #RRGGBBAA, like #1A1A40 or #1A1A4080");
+/// assert!(hex_to_rgba("#+f+f+f").is_err());
+/// assert!(hex_to_rgba("#12345").is_err());
+/// # Ok::<(), certificate_maker::error::CertError>(())
+/// ```
+pub fn hex_to_rgba(color: &str) -> Result<Rgba<u8>> {
+    let invalid = |reason: String| CertError::InvalidColor { color: color.to_string(), reason };
+    let trimmed = color.trim();
+    if let Some(name) = trimmed.strip_prefix(PALETTE_PREFIX) {
+        return match palette::installed() {
+            Some(palette) => palette.resolve(trimmed),
+            None => Err(CertError::UnknownPaletteColor { name: name.trim().to_string(), available: Vec::new() }),
+        };
+    }
+    let hex = trimmed.strip_prefix('#').unwrap_or(trimmed);
+
+    // Checked before slicing: every digit is then one byte
+    if let Some(c) = hex.chars().find(|c| !c.is_ascii_hexdigit()) {
+        return Err(invalid(format!("'{}' is not a hex digit", c.escape_debug())));
+    }
     if hex.len() != 6 && hex.len() != 8 {
-        return Err(anyhow::anyhow!("Invalid hex color format. Use #RRGGBB or #RRGGBBAA"));
-    }
-    
-    let r = u8::from_str_radix(&hex[0..2], 16)
-        .with_context(|| "Invalid red component in hex color")?;
-    let g = u8::from_str_radix(&hex[2..4], 16)
-        .with_context(|| "Invalid green component in hex color")?;
-    let b = u8::from_str_radix(&hex[4..6], 16)
-        .with_context(|| "Invalid blue component in hex color")?;
-    
-    let a = if hex.len() == 8 {
-        u8::from_str_radix(&hex[6..8], 16)
-            .with_context(|| "Invalid alpha component in hex color")?
+        return Err(invalid(format!("it has {} hex digits, not 6 or 8", hex.len())));
+    }
+
+    let channel = |index: usize| u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).unwrap_or_default();
+    let alpha = if hex.len() == 8 { channel(3) } else { 255 };
+    Ok(Rgba([channel(0), channel(1), channel(2), alpha]))
+}
+
+/// Measure the rendered width and line height of `text`, in pixels; see [`measure_text`](crate::measure::measure_text)
+/// for the rest of its metrics.
+pub fn calculate_text_size(font: &Font, scale: Scale, text: &str) -> (i32, i32) {
+    let metrics = measure_scaled(font, scale, text, 0.0, None);
+    (metrics.ink_bbox.width, metrics.height())
+}
+
+/// Top-left drawing position that centers `text` around `(x, y)`.
+pub fn centered_position(font: &Font, scale: Scale, text: &str, x: i32, y: i32) -> (i32, i32) {
+    let (text_width, text_height) = calculate_text_size(font, scale, text);
+    Anchor::Center.resolve(x, y, text_width, text_height)
+}
+
+/// Which point of the text box a position refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Anchor {
+    /// The text is centered on the position (the interactive default).
+    #[default]
+    Center,
+    /// The position is the top-left corner of the text.
+    TopLeft,
+    /// The text starts at the position and is vertically centered on it.
+    CenterLeft,
+    /// The text ends at the position and is vertically centered on it.
+    CenterRight,
+}
+
+impl Anchor {
+    /// Every anchor, in the order help text lists them.
+    pub const ALL: [Anchor; 4] = [Anchor::Center, Anchor::TopLeft, Anchor::CenterLeft, Anchor::CenterRight];
+
+    /// The name layout files use, such as `top-left`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Anchor::Center => "center",
+            Anchor::TopLeft => "top-left",
+            Anchor::CenterLeft => "center-left",
+            Anchor::CenterRight => "center-right",
+        }
+    }
+
+    /// Top-left drawing position for a text box of `width` x `height` anchored at `(x, y)`.
+    pub fn resolve(self, x: i32, y: i32, width: i32, height: i32) -> (i32, i32) {
+        match self {
+            Anchor::Center => (x - width / 2, y - height / 2),
+            Anchor::TopLeft => (x, y),
+            Anchor::CenterLeft => (x, y - height / 2),
+            Anchor::CenterRight => (x - width, y - height / 2),
+        }
+    }
+}
+
+/// How and where [`draw_text`] places a piece of text.
+#[derive(Clone, Copy)]
+pub struct TextOptions<'a> {
+    pub font: &'a Font<'a>,
+    pub size: f32,
+    pub color: Rgba<u8>,
+    pub x: i32,
+    pub y: i32,
+    pub anchor: Anchor,
+    /// Lines drawn across the text after its glyphs, in order.
+    pub decorations: &'a [Decoration],
+    /// Faux bold and italic made from these glyphs; none by default.
+    pub synthetic: SyntheticStyle,
+    /// A variable font set to chosen axis values, drawn instead of `font` when given.
+    pub variation: Option<&'a FontInstance>,
+    /// Gradient, outline and shadows drawn with the glyphs. The returned bounds are the
+    /// letters' own, which the outline and shadows may reach past.
+    pub effect: Option<&'a TextEffect>,
+}
+
+/// Faux bold and italic, synthesized from the regular glyphs for a family without a real bold
+/// or italic face. A real face looks better whenever there is one; see [`real_style_for`].
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct SyntheticStyle {
+    /// Pixels every stroke is thickened by on each side.
+    pub embolden: f32,
+    /// Degrees the glyphs lean to the right; negative leans them left.
+    pub slant: f32,
+}
+
+/// Steepest slant a [`SyntheticStyle`] accepts, in degrees either way.
+pub const MAX_SLANT: f32 = 45.0;
+
+impl SyntheticStyle {
+    /// Whether the text is drawn as the font has it.
+    pub fn is_none(&self) -> bool {
+        self.embolden == 0.0 && self.slant == 0.0
+    }
+
+    /// Embolden must be 0 or more, and the slant under [`MAX_SLANT`] either way.
+    pub fn validate(&self) -> Result<()> {
+        if !self.embolden.is_finite() || self.embolden < 0.0 {
+            return Err(CertError::InvalidStyle(format!("embolden must be 0 or more pixels, got {}", self.embolden)));
+        }
+        if !self.slant.is_finite() || self.slant.abs() >= MAX_SLANT {
+            return Err(CertError::InvalidStyle(format!("slant must be less than {} degrees either way, got {}", MAX_SLANT, self.slant)));
+        }
+        Ok(())
+    }
+
+    fn shear(&self) -> f32 {
+        self.slant.to_radians().tan()
+    }
+}
+
+/// A font in `fonts_dir` of the same family and width as `font` that really is bold and/or
+/// italic (or oblique) as asked, to suggest instead of a [`SyntheticStyle`].
+pub fn real_style_for(font: impl AsRef<Path>, fonts_dir: impl AsRef<Path>, bold: bool, italic: bool) -> Option<PathBuf> {
+    let data = load_font_data(font.as_ref()).ok()?;
+    let face = ttf_parser::Face::from_slice(&data, 0).ok()?;
+    let family = family_name(&face)?;
+    let width = face.width();
+
+    let fonts_dir = fonts_dir.as_ref();
+    list_available_fonts(fonts_dir).ok()?.into_iter()
+        .map(|candidate| fonts_dir.join(candidate))
+        .find(|candidate| {
+            let Ok(data) = crate::fontcache::shared().data(candidate) else {
+                return false;
+            };
+            let Ok(face) = ttf_parser::Face::from_slice(&data, 0) else {
+                return false;
+            };
+            family_name(&face).as_ref() == Some(&family)
+                && face.width() == width
+                && (face.is_bold() || face.weight().to_number() >= 600) == bold
+                && (face.is_italic() || face.is_oblique()) == italic
+        })
+}
+
+/// Which line a [`Decoration`] draws.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecorationKind {
+    Underline,
+    Strikethrough,
+}
+
+/// Where a font puts a line, in ems: `offset` above the baseline (negative below it) and
+/// `thickness`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineMetrics {
+    pub offset: f32,
+    pub thickness: f32,
+}
+
+/// A line [`draw_text`] draws across the whole measured width of the text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decoration {
+    pub kind: DecorationKind,
+    /// The text color when unset.
+    pub color: Option<Rgba<u8>>,
+    /// The font's own line from [`font_line_metrics`]; when unset, or the font has none, an
+    /// underline sits 0.1 em below the baseline and a strikethrough 0.25 em above it, both
+    /// 0.05 em thick.
+    pub metrics: Option<LineMetrics>,
+}
+
+impl Decoration {
+    /// A line of the given kind, in the text color and where the font puts it.
+    pub fn for_font(kind: DecorationKind, font_data: &[u8]) -> Self {
+        Decoration { kind, color: None, metrics: font_line_metrics(font_data, kind) }
+    }
+
+    fn resolved_metrics(&self) -> LineMetrics {
+        self.metrics.unwrap_or(match self.kind {
+            DecorationKind::Underline => LineMetrics { offset: -0.1, thickness: 0.05 },
+            DecorationKind::Strikethrough => LineMetrics { offset: 0.25, thickness: 0.05 },
+        })
+    }
+}
+
+/// The underline (from the `post` table) or strikethrough (from `OS/2`) a font specifies, if
+/// it specifies one. rusttype doesn't expose these, so the raw font data is read.
+pub fn font_line_metrics(font_data: &[u8], kind: DecorationKind) -> Option<LineMetrics> {
+    let face = ttf_parser::Face::from_slice(font_data, 0).ok()?;
+    let metrics = match kind {
+        DecorationKind::Underline => face.underline_metrics(),
+        DecorationKind::Strikethrough => face.strikeout_metrics(),
+    }?;
+    let units = f32::from(face.units_per_em());
+    (metrics.thickness > 0).then(|| LineMetrics {
+        offset: f32::from(metrics.position) / units,
+        thickness: f32::from(metrics.thickness) / units,
+    })
+}
+
+/// The box a piece of text was drawn into, in image pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextBounds {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl TextBounds {
+    /// First row below the text, handy for stacking another line under it.
+    pub fn bottom(&self) -> i32 {
+        self.y + self.height
+    }
+
+    pub fn center_x(&self) -> i32 {
+        self.x + self.width / 2
+    }
+
+    /// Whether the whole box lies on an image of `width` x `height`.
+    pub fn fits_within(&self, width: u32, height: u32) -> bool {
+        self.x >= 0 && self.y >= 0
+            && self.x as i64 + self.width as i64 <= width as i64
+            && self.y as i64 + self.height as i64 <= height as i64
+    }
+}
+
+/// Characters of `text` the font has no glyph for; they would be drawn as its placeholder box.
+pub fn missing_glyphs(font: &Font, text: &str) -> Vec<char> {
+    let mut missing = Vec::new();
+    for c in text.chars().filter(|c| !c.is_whitespace() && !c.is_control()) {
+        if font.glyph(c).id().0 == 0 && !missing.contains(&c) {
+            missing.push(c);
+        }
+    }
+    missing
+}
+
+/// Decode a template into an RGBA buffer, turned upright by its EXIF orientation (see
+/// [`orientation`](crate::orientation)). An RGBA8 image is used as decoded, not copied.
+pub fn load_template(path: impl AsRef<Path>) -> Result<RgbaImage> {
+    let path = path.as_ref();
+    Ok(open_oriented(path)
+        .map_err(|source| CertError::ImageDecode { path: path.to_path_buf(), source })?
+        .into_rgba8())
+}
+
+/// Encode an image to `path` in the given format.
+pub fn save_image(img: &RgbaImage, path: impl AsRef<Path>, format: ImageFormat) -> Result<()> {
+    let path = path.as_ref();
+    img.save_with_format(path, format)
+        .map_err(|source| CertError::ImageEncode { path: path.to_path_buf(), source })
+}
+
+/// How hard to compress a certificate PNG. Every level writes a standard PNG any viewer reads;
+/// they trade encoding time against file size.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PngCompression {
+    /// Fastest deflate with one fixed filter (Paeth) instead of trying each on every row:
+    /// quicker to encode, files about the same size on typical templates.
+    Fast,
+    /// Fastest deflate with adaptive filtering, which is how `image` writes PNGs.
+    #[default]
+    Balanced,
+    /// Strongest deflate with adaptive filtering: the smallest files, many times slower.
+    Small,
+}
+
+impl PngCompression {
+    pub const ALL: [PngCompression; 3] = [PngCompression::Fast, PngCompression::Balanced, PngCompression::Small];
+
+    /// Name used on the command line and in layout files.
+    pub fn name(self) -> &'static str {
+        match self {
+            PngCompression::Fast => "fast",
+            PngCompression::Balanced => "balanced",
+            PngCompression::Small => "small",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<PngCompression> {
+        PngCompression::ALL.into_iter().find(|level| level.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+/// Encode an image as PNG in memory at the given compression level.
+///
+/// ```
+/// use certificate_maker::editpng::{PngCompression, encode_png};
+/// use image::{ImageOutputFormat, Rgba, RgbaImage};
+///
+/// let img = RgbaImage::from_fn(64, 64, |x, y| Rgba([(x * 4) as u8, (y * 4) as u8, 128, 255]));
+/// let mut default = std::io::Cursor::new(Vec::new());
+/// img.write_to(&mut default, ImageOutputFormat::Png)?;
+/// // Balanced writes exactly what `image` does by default
+/// assert_eq!(encode_png(&img, PngCompression::Balanced)?, default.into_inner());
+/// for level in PngCompression::ALL {
+///     let decoded = image::load_from_memory(&encode_png(&img, level)?)?.to_rgba8();
+///     assert_eq!(decoded, img);
+/// }
+/// # Ok::<(), image::ImageError>(())
+/// ```
+pub fn encode_png(img: &RgbaImage, compression: PngCompression) -> ImageResult<Vec<u8>> {
+    let (compression_type, filter) = png_encoder_settings(compression);
+    let mut encoded = Vec::new();
+    PngEncoder::new_with_quality(&mut encoded, compression_type, filter)
+        .write_image(img.as_raw(), img.width(), img.height(), ColorType::Rgba8)?;
+    Ok(encoded)
+}
+
+// The encoder's compression and filter for a level; 16-bit certificates use the same
+pub(crate) fn png_encoder_settings(compression: PngCompression) -> (CompressionType, FilterType) {
+    match compression {
+        PngCompression::Fast => (CompressionType::Fast, FilterType::Paeth),
+        PngCompression::Balanced => (CompressionType::Fast, FilterType::Adaptive),
+        PngCompression::Small => (CompressionType::Best, FilterType::Adaptive),
+    }
+}
+
+/// Where [`draw_text`] would put `text`, without drawing anything.
+pub fn layout_text(text: &str, opts: &TextOptions) -> Result<TextBounds> {
+    if !opts.size.is_finite() || opts.size <= 0.0 {
+        return Err(CertError::InvalidFontSize(opts.size));
+    }
+
+    opts.synthetic.validate()?;
+
+    let (width, height) = if opts.synthetic.is_none() && opts.variation.is_none() {
+        calculate_text_size(opts.font, Scale::uniform(opts.size), text)
     } else {
-        255 // Default to full opacity
+        styled_text_size(text, opts)
     };
-    
-    Ok(Rgba([r, g, b, a]))
-}
-
-// Function to get user input
-fn get_user_input(prompt: &str) -> String {
-    print!("{}", prompt);
-    io::stdout().flush().unwrap();
-    
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
-    input.trim().to_string()
-}
-
-// Function to select font interactively
-pub fn select_font() -> Result<String> {
-    println!("\n🔤 Available Fonts:");
-    let fonts = list_available_fonts()?;
-    
-    for (i, font) in fonts.iter().enumerate() {
-        println!("  {}. {}", i + 1, font);
-    }
-    
-    loop {
-        let input = get_user_input("\nEnter font name or number: ");
-        
-        // Try to parse as number first
-        if let Ok(num) = input.parse::<usize>() {
-            if num > 0 && num <= fonts.len() {
-                return Ok(fonts[num - 1].clone());
+    let (x, y) = opts.anchor.resolve(opts.x, opts.y, width, height);
+    Ok(TextBounds { x, y, width, height })
+}
+
+/// `opts` at the largest size up to its own at which `text` is at most `max_width` pixels wide,
+/// for names that would otherwise run out of their space.
+pub fn fit_to_width<'a>(text: &str, opts: &TextOptions<'a>, max_width: f32) -> Result<TextOptions<'a>> {
+    if !max_width.is_finite() || max_width <= 0.0 {
+        return Err(CertError::InvalidMeasurement(format!("the maximum width must be a positive number, got {}", max_width)));
+    }
+    let width = layout_text(text, opts)?.width as f32;
+    if width <= max_width {
+        return Ok(*opts);
+    }
+    // Widths grow about linearly with the size; the steps absorb rounding and kerning
+    let mut fitted = TextOptions { size: opts.size * max_width / width, ..*opts };
+    let step = (opts.size / 200.0).max(0.25);
+    while fitted.size > step && layout_text(text, &fitted)?.width as f32 > max_width {
+        fitted.size -= step;
+    }
+    Ok(fitted)
+}
+
+/// What happens to text wider than the space it is given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextOverflow {
+    /// Set smaller until it fits; see [`fit_to_width`].
+    #[default]
+    Shrink,
+    /// Cut short with a suffix, at its own size; see [`ellipsize`].
+    Ellipsis,
+}
+
+impl TextOverflow {
+    /// Every overflow setting, in the order help text lists them.
+    pub const ALL: [TextOverflow; 2] = [TextOverflow::Shrink, TextOverflow::Ellipsis];
+
+    /// The name layout files use.
+    pub fn name(self) -> &'static str {
+        match self {
+            TextOverflow::Shrink => "shrink",
+            TextOverflow::Ellipsis => "ellipsis",
+        }
+    }
+
+    pub fn is_shrink(&self) -> bool {
+        *self == TextOverflow::Shrink
+    }
+}
+
+/// Suffix of ellipsized text when nothing else is set.
+pub const ELLIPSIS: &str = "…";
+
+/// `text` cut short and ended with `suffix` so it is at most `max_width` pixels wide at the
+/// size of `opts`, or `text` itself when it already fits. The cut falls between words when
+/// that keeps at least two thirds of the characters that would fit, otherwise between
+/// characters; when not even one character fits, only the suffix is left.
+///
+/// ```
+/// use certificate_maker::editpng::{Anchor, SyntheticStyle, TextOptions, ellipsize, layout_text, load_font};
+/// use image::Rgba;
+///
+/// let font = load_font("DejaVuSans.ttf")?;
+/// let opts = TextOptions { font: &font, size: 24.0, color: Rgba([0, 0, 0, 255]), x: 0, y: 0, anchor: Anchor::TopLeft,
+///                          decorations: &[], synthetic: SyntheticStyle::default(), variation: None, effect: None };
+/// let title = "Introduction to the Analytical Engine and Its Programming";
+/// let cut = ellipsize(title, &opts, 300.0, "…")?;
+/// assert_eq!(cut, "Introduction to the…");
+/// assert!(layout_text(&cut, &opts)?.width <= 300);
+/// assert_eq!(ellipsize("Algebra", &opts, 300.0, "…")?, "Algebra");
+/// # Ok::<(), certificate_maker::error::CertError>(())
+/// ```
+pub fn ellipsize(text: &str, opts: &TextOptions, max_width: f32, suffix: &str) -> Result<String> {
+    if !max_width.is_finite() || max_width <= 0.0 {
+        return Err(CertError::InvalidMeasurement(format!("the maximum width must be a positive number, got {}", max_width)));
+    }
+    if layout_text(text, opts)?.width as f32 <= max_width {
+        return Ok(text.to_string());
+    }
+    let ends: Vec<usize> = text.char_indices().map(|(index, _)| index).skip(1).chain([text.len()]).collect();
+    let cut = |length: usize| format!("{}{}", text[..ends.get(length.wrapping_sub(1)).copied().unwrap_or(0)].trim_end(), suffix);
+    // The most characters that fit with the suffix; widths grow with the length
+    let (mut low, mut high) = (0, ends.len() - 1);
+    while low < high {
+        let middle = (low + high).div_ceil(2);
+        if layout_text(&cut(middle), opts)?.width as f32 <= max_width {
+            low = middle;
+        } else {
+            high = middle - 1;
+        }
+    }
+    let kept = &text[..ends.get(low.wrapping_sub(1)).copied().unwrap_or(0)];
+    let at_word_end = text[kept.len()..].starts_with(char::is_whitespace);
+    if !at_word_end
+        && let Some(space) = kept.rfind(char::is_whitespace)
+        && kept[..space].trim_end().chars().count() * 3 >= low * 2 {
+        return Ok(format!("{}{}", kept[..space].trim_end(), suffix));
+    }
+    Ok(cut(low))
+}
+
+/// The text and options to draw `text` at most `max_width` pixels wide, set smaller or cut
+/// short with `suffix` as `overflow` says.
+pub fn fit_text<'a>(text: &str, opts: &TextOptions<'a>, max_width: f32, overflow: TextOverflow, suffix: &str) -> Result<(String, TextOptions<'a>)> {
+    match overflow {
+        TextOverflow::Shrink => Ok((text.to_string(), fit_to_width(text, opts, max_width)?)),
+        TextOverflow::Ellipsis => Ok((ellipsize(text, opts, max_width, suffix)?, *opts)),
+    }
+}
+
+// A glyph rasterized with the pen starting at (0, ascent): its pixel box and its coverage,
+// row by row
+pub(crate) struct RasterGlyph {
+    pub(crate) min_x: i32,
+    pub(crate) min_y: i32,
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) coverage: Vec<f32>,
+}
+
+// Every glyph of the text, from the variable font instance when there is one
+fn raster_glyphs(text: &str, opts: &TextOptions) -> Vec<RasterGlyph> {
+    if let Some(instance) = opts.variation {
+        return instance.raster_glyphs(text, opts.size);
+    }
+    let scale = Scale::uniform(opts.size);
+    opts.font.layout(text, scale, point(0.0, opts.font.v_metrics(scale).ascent))
+        .filter_map(|glyph| {
+            let bb = glyph.pixel_bounding_box()?;
+            let (width, height) = (bb.width() as usize, bb.height() as usize);
+            let mut coverage = vec![0.0; width * height];
+            glyph.draw(|x, y, value| coverage[y as usize * width + x as usize] = value);
+            Some(RasterGlyph { min_x: bb.min.x, min_y: bb.min.y, width, height, coverage })
+        })
+        .collect()
+}
+
+// Ascent and descent in pixels, from the variable font instance when there is one
+fn text_v_metrics(opts: &TextOptions) -> (f32, f32) {
+    match opts.variation {
+        Some(instance) => instance.v_metrics(opts.size),
+        None => {
+            let v_metrics = opts.font.v_metrics(Scale::uniform(opts.size));
+            (v_metrics.ascent, v_metrics.descent)
+        }
+    }
+}
+
+// Leftmost and rightmost ink after shearing, from the corners of each glyph box; rows are
+// pushed right by their height above the baseline
+fn sheared_extent(glyphs: &[RasterGlyph], ascent: f32, shear: f32) -> Option<(f32, f32)> {
+    glyphs.iter()
+        .map(|glyph| {
+            let low = (ascent - (glyph.min_y + glyph.height as i32) as f32) * shear;
+            let high = (ascent - glyph.min_y as f32) * shear;
+            (glyph.min_x as f32 + low.min(high), (glyph.min_x + glyph.width as i32) as f32 + low.max(high))
+        })
+        .reduce(|(left, right), (glyph_left, glyph_right)| (left.min(glyph_left), right.max(glyph_right)))
+}
+
+// Like calculate_text_size, widened by the slant and by the emboldening on every side
+fn styled_text_size(text: &str, opts: &TextOptions) -> (i32, i32) {
+    let (ascent, descent) = text_v_metrics(opts);
+    let Some((left, right)) = sheared_extent(&raster_glyphs(text, opts), ascent, opts.synthetic.shear()) else {
+        return (0, 0);
+    };
+    let grow = (2.0 * opts.synthetic.embolden).ceil() as i32;
+    ((right - left).ceil() as i32 + grow, (ascent - descent).ceil() as i32 + grow)
+}
+
+/// Draw `text` onto `img` in place, then its decorations, and return where the text ended up.
+pub fn draw_text(img: &mut RgbaImage, text: &str, opts: &TextOptions) -> Result<TextBounds> {
+    let bounds = layout_text(text, opts)?;
+    let scale = Scale::uniform(opts.size);
+    if opts.synthetic.is_none() && opts.variation.is_none() && opts.effect.is_none() {
+        draw_text_mut(img, opts.color, bounds.x, bounds.y, scale, opts.font, text);
+    } else {
+        draw_styled_text(img, text, opts, &bounds);
+    }
+
+    // draw_text_mut puts the baseline one ascent below the top of the box
+    let baseline = bounds.y as f32 + text_v_metrics(opts).0 + opts.synthetic.embolden.ceil();
+    for decoration in opts.decorations {
+        let metrics = decoration.resolved_metrics();
+        let thickness = (metrics.thickness * opts.size).max(1.0);
+        let middle = baseline - metrics.offset * opts.size;
+        fill_band(img, bounds.x, bounds.x + bounds.width, middle - thickness / 2.0, middle + thickness / 2.0,
+                  decoration.color.unwrap_or(opts.color));
+    }
+    Ok(bounds)
+}
+
+// Faux bold and italic, variable font instances and effects: every glyph's coverage is copied
+// into one canvas with its rows shifted by the shear, the canvas is dilated by the emboldening,
+// then blended in like draw_text_mut does, or painted with the effect. The unsheared ink keeps
+// its place, so plain and styled text line up
+fn draw_styled_text(img: &mut RgbaImage, text: &str, opts: &TextOptions, bounds: &TextBounds) {
+    let shear = opts.synthetic.shear();
+    let ascent = text_v_metrics(opts).0;
+    let glyphs = raster_glyphs(text, opts);
+    let Some((sheared_left, _)) = sheared_extent(&glyphs, ascent, shear) else {
+        return;
+    };
+    let plain_left = glyphs.iter().map(|glyph| glyph.min_x).min().unwrap_or(0);
+    // An effect's outline and blur spread past the letters, so they get a margin to grow into
+    let margin = opts.effect.map_or(0, |effect| effect.reach(opts.size));
+    let pad = opts.synthetic.embolden.ceil() as i32 + margin;
+    let (width, height) = ((bounds.width + 2 + 2 * margin) as usize, (bounds.height + 2 + 2 * margin) as usize);
+    let mut canvas = vec![0.0f32; width * height];
+
+    for glyph in &glyphs {
+        for (index, &value) in glyph.coverage.iter().enumerate() {
+            if value <= 0.0 {
+                continue;
+            }
+            let (x, y) = (glyph.min_x + (index % glyph.width) as i32, glyph.min_y + (index / glyph.width) as i32);
+            // Split the sheared pixel between the two canvas columns it straddles
+            let column = x as f32 + (ascent - (y as f32 + 0.5)) * shear - sheared_left + pad as f32;
+            let row = y + pad;
+            let (first, fraction) = (column.floor(), column - column.floor());
+            for (cx, weight) in [(first as i32, 1.0 - fraction), (first as i32 + 1, fraction)] {
+                if cx >= 0 && row >= 0 && (cx as usize) < width && (row as usize) < height {
+                    let cell = &mut canvas[row as usize * width + cx as usize];
+                    *cell = (*cell + value * weight).min(1.0);
+                }
             }
         }
-        
-        // Try to find by name (case insensitive)
-        for font in &fonts {
-            if font.to_lowercase() == input.to_lowercase() {
-                return Ok(font.clone());
+    }
+
+    let canvas = if opts.synthetic.embolden > 0.0 { dilate(&canvas, width, height, opts.synthetic.embolden) } else { canvas };
+    let (origin_x, origin_y) = (bounds.x + plain_left - margin, bounds.y - margin);
+    if let Some(effect) = opts.effect {
+        paint_effect(img, &canvas, width, (origin_x, origin_y), effect, opts, bounds);
+        return;
+    }
+    for (index, &value) in canvas.iter().enumerate() {
+        let (x, y) = (origin_x + (index % width) as i32, origin_y + (index / width) as i32);
+        if value > 0.0 && x >= 0 && y >= 0 && (x as u32) < img.width() && (y as u32) < img.height() {
+            let pixel = img.get_pixel_mut(x as u32, y as u32);
+            for channel in 0..4 {
+                pixel[channel] = (pixel[channel] as f32 * (1.0 - value) + opts.color[channel] as f32 * value).round() as u8;
             }
         }
-        
-        println!("❌ Invalid selection. Please try again.");
-    }
-}
-
-// Function to get color from user
-pub fn get_color_from_user() -> Result<Rgba<u8>> {
-    println!("\n🎨 Color Options:");
-    println!("  • Enter hex color code only (e.g., #FF0000 for red, #00FF00 for green)");
-    
-    loop {
-        let input = get_user_input("Enter color: ");
-        
-        // Check for common color names
-        let color = match input.to_lowercase().as_str() {
-            "white" => Rgba([255, 255, 255, 255]),
-            "black" => Rgba([0, 0, 0, 255]),
-            "red" => Rgba([255, 0, 0, 255]),
-            "green" => Rgba([0, 255, 0, 255]),
-            "blue" => Rgba([0, 0, 255, 255]),
-            "yellow" => Rgba([255, 255, 0, 255]),
-            "orange" => Rgba([255, 165, 0, 255]),
-            "purple" => Rgba([128, 0, 128, 255]),
-            _ => {
-                // Try to parse as hex
-                match hex_to_rgba(&input) {
-                    Ok(color) => color,
-                    Err(_) => {
-                        println!("❌ Invalid color. Try a hex code like #FF0000 or a color name like 'red'");
+    }
+}
+
+// Grow coverage by `radius` pixels: each cell takes the strongest neighbor within the radius,
+// faded over the last pixel so fractional radii stay smooth
+pub(crate) fn dilate(canvas: &[f32], width: usize, height: usize, radius: f32) -> Vec<f32> {
+    let reach = radius.ceil() as i32;
+    let mut grown = vec![0.0f32; canvas.len()];
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            let mut strongest = 0.0f32;
+            for dy in -reach..=reach {
+                for dx in -reach..=reach {
+                    let (nx, ny) = (x + dx, y + dy);
+                    if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
                         continue;
                     }
+                    let weight = (radius + 1.0 - ((dx * dx + dy * dy) as f32).sqrt()).clamp(0.0, 1.0);
+                    strongest = strongest.max(canvas[ny as usize * width + nx as usize] * weight);
                 }
             }
-        };
-        
-        return Ok(color);
+            grown[y as usize * width + x as usize] = strongest;
+        }
     }
+    grown
 }
 
-// Helper function to calculate text size
-fn calculate_text_size(font: &Font, scale: Scale, text: &str) -> (i32, i32) {
-    let v_metrics = font.v_metrics(scale);
-    let glyphs: Vec<_> = font.layout(text, scale, point(0.0, 0.0 + v_metrics.ascent)).collect();
+// Fill columns x0..x1 between two fractional rows, blending edge rows by how much of them the
+// band covers, the same way draw_text_mut blends glyph coverage
+fn fill_band(img: &mut RgbaImage, x0: i32, x1: i32, top: f32, bottom: f32, color: Rgba<u8>) {
+    let rows = (top.floor() as i32).max(0)..(bottom.ceil() as i32).min(img.height() as i32);
+    let columns = x0.max(0)..x1.min(img.width() as i32);
+    for y in rows {
+        let coverage = (bottom.min(y as f32 + 1.0) - top.max(y as f32)).clamp(0.0, 1.0);
+        for x in columns.clone() {
+            let pixel = img.get_pixel_mut(x as u32, y as u32);
+            for channel in 0..4 {
+                pixel[channel] = (pixel[channel] as f32 * (1.0 - coverage) + color[channel] as f32 * coverage).round() as u8;
+            }
+        }
+    }
+}
 
-    if glyphs.is_empty() {
-        return (0, 0);
+/// How each side of a [`BorderStyle`] is ruled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BorderLine {
+    /// One line the full thickness.
+    #[default]
+    Solid,
+    /// Two lines, each a third of the thickness, with a third between them.
+    Double,
+}
+
+impl BorderLine {
+    pub const ALL: [BorderLine; 2] = [BorderLine::Solid, BorderLine::Double];
+
+    /// Name used in layout files and at the prompts.
+    pub fn name(self) -> &'static str {
+        match self {
+            BorderLine::Solid => "solid",
+            BorderLine::Double => "double",
+        }
     }
 
-    let min_x = glyphs
-        .iter()
-        .filter_map(|g| g.pixel_bounding_box().map(|b| b.min.x))
-        .min()
-        .unwrap_or(0);
-    
-    let max_x = glyphs
-        .iter()
-        .filter_map(|g| g.pixel_bounding_box().map(|b| b.max.x))
-        .max()
-        .unwrap_or(0);
+    pub fn from_name(name: &str) -> Option<BorderLine> {
+        BorderLine::ALL.into_iter().find(|line| line.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+/// The thickness of each side of a border, in pixels; 0 leaves that side out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BorderSides {
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub left: f32,
+}
+
+impl BorderSides {
+    /// The same thickness on every side.
+    pub fn all(thickness: f32) -> Self {
+        BorderSides { top: thickness, right: thickness, bottom: thickness, left: thickness }
+    }
 
-    let width = max_x - min_x;
-    let height = (v_metrics.ascent - v_metrics.descent).ceil() as i32;
+    fn scaled(self, factor: f32) -> Self {
+        BorderSides { top: self.top * factor, right: self.right * factor, bottom: self.bottom * factor, left: self.left * factor }
+    }
 
-    (width, height)
+    fn as_array(self) -> [f32; 4] {
+        [self.top, self.right, self.bottom, self.left]
+    }
 }
 
-pub fn add_text_to_png_interactive(
-    input_path: &str,
-    output_path: &str,
-    text: &str,
-    x: i32,
-    y: i32,
-) -> Result<()> {
-    let mut img = open(input_path)
-        .with_context(|| format!("Failed to open image: {}", input_path))?
-        .to_rgba8();
-
-    // Select font
-    let font_filename = select_font()?;
-    let font_data = load_font_data(&font_filename)?;
-    let font = Font::try_from_bytes(&font_data)
-        .ok_or_else(|| anyhow::anyhow!("Failed to load font: {}", font_filename))?;
-
-    // Get font size
-    let font_size_input = get_user_input("Enter font size (default 40): ");
-    let font_size = if font_size_input.is_empty() {
-        40.0
-    } else {
-        font_size_input.parse().unwrap_or(40.0)
+/// A frame around the template, drawn by [`draw_border`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BorderStyle {
+    pub line: BorderLine,
+    /// Blended over the template by its alpha, so a translucent border lets the template show.
+    pub color: Rgba<u8>,
+    pub thickness: BorderSides,
+    /// Gap between the template's edges and the outside of the border, in pixels.
+    pub inset: f32,
+    /// Radius of the outside corners in pixels; 0 for square corners. The inside corners
+    /// follow, less the thickness.
+    pub radius: f32,
+}
+
+impl BorderStyle {
+    /// The style scaled by `factor`, for a template that was resized.
+    pub fn scaled(&self, factor: f32) -> Self {
+        BorderStyle { thickness: self.thickness.scaled(factor), inset: self.inset * factor, radius: self.radius * factor, ..*self }
+    }
+
+    /// Check the numbers, and that a border of this style fits on a `width` x `height` template.
+    pub fn validate(&self, width: u32, height: u32) -> Result<()> {
+        let invalid = |reason: String| Err(CertError::InvalidBorder(reason));
+        let sides = self.thickness.as_array();
+        if sides.iter().any(|side| !side.is_finite() || *side < 0.0) {
+            return invalid(format!("thicknesses must be numbers of pixels, at least 0, got {:?}", sides));
+        }
+        if sides.iter().all(|side| *side == 0.0) {
+            return invalid("at least one side needs a thickness".to_string());
+        }
+        for (name, value) in [("inset", self.inset), ("radius", self.radius)] {
+            if !value.is_finite() || value < 0.0 {
+                return invalid(format!("the {} must be a number of pixels, at least 0, got {}", name, value));
+            }
+        }
+        let across = self.inset * 2.0 + self.thickness.left + self.thickness.right;
+        let down = self.inset * 2.0 + self.thickness.top + self.thickness.bottom;
+        if across >= width as f32 || down >= height as f32 {
+            return invalid(format!("with its inset and thickness it needs more than the {}x{} template", width, height));
+        }
+        Ok(())
+    }
+}
+
+/// Draw a border around `img`, anti-aliased along its rounded corners and blended by the
+/// color's alpha. Each side takes its own thickness; a double line splits it into two rules
+/// and the gap between them, as in CSS.
+///
+/// ```
+/// use certificate_maker::editpng::{BorderLine, BorderSides, BorderStyle, draw_border};
+/// use image::{Rgba, RgbaImage};
+///
+/// let mut img = RgbaImage::from_pixel(200, 100, Rgba([255, 255, 255, 255]));
+/// let style = BorderStyle {
+///     line: BorderLine::Double,
+///     color: Rgba([20, 40, 120, 255]),
+///     thickness: BorderSides::all(9.0),
+///     inset: 10.0,
+///     radius: 0.0,
+/// };
+/// draw_border(&mut img, &style)?;
+/// assert_eq!(img.get_pixel(5, 50), &Rgba([255, 255, 255, 255]));   // the inset
+/// assert_eq!(img.get_pixel(11, 50), &Rgba([20, 40, 120, 255]));    // the outer rule
+/// assert_eq!(img.get_pixel(14, 50), &Rgba([255, 255, 255, 255]));  // the gap
+/// assert_eq!(img.get_pixel(17, 50), &Rgba([20, 40, 120, 255]));    // the inner rule
+/// assert_eq!(img.get_pixel(100, 50), &Rgba([255, 255, 255, 255])); // the middle
+///
+/// // Rounded, the corner is cut off and its curve blends into the template
+/// let mut img = RgbaImage::from_pixel(200, 100, Rgba([255, 255, 255, 255]));
+/// draw_border(&mut img, &BorderStyle { line: BorderLine::Solid, radius: 20.0, ..style })?;
+/// assert_eq!(img.get_pixel(12, 12), &Rgba([255, 255, 255, 255]));
+/// assert!(img.pixels().any(|pixel| pixel[0] > 20 && pixel[0] < 255));
+/// # Ok::<(), certificate_maker::error::CertError>(())
+/// ```
+pub fn draw_border(img: &mut RgbaImage, style: &BorderStyle) -> Result<()> {
+    let (width, height) = img.dimensions();
+    style.validate(width, height)?;
+    let outer = [style.inset, style.inset, width as f32 - style.inset, height as f32 - style.inset];
+    // (fraction of the thickness in from the outside, whether coverage starts or stops there)
+    let edges: &[(f32, f32)] = match style.line {
+        BorderLine::Solid => &[(0.0, 1.0), (1.0, -1.0)],
+        BorderLine::Double => &[(0.0, 1.0), (1.0 / 3.0, -1.0), (2.0 / 3.0, 1.0), (1.0, -1.0)],
     };
+    let shapes: Vec<(RoundedRect, f32)> = edges.iter()
+        .map(|&(fraction, sign)| (RoundedRect::inside(outer, style.radius, style.thickness.scaled(fraction)), sign))
+        .collect();
 
-    // Get color
-    let color = get_color_from_user()?;
+    // Pixels well inside the border's inner edge aren't covered, so only the frame is visited
+    let inner = &shapes[shapes.len() - 1].0;
+    let corner = inner.radius.into_iter().fold(0.0, f32::max);
+    let hole_x = (inner.left + 1.0).ceil() as u32..(inner.right - 1.0).max(0.0) as u32;
+    let hole_y = (inner.top + corner + 1.0).ceil() as u32..(inner.bottom - corner - 1.0).max(0.0) as u32;
+    for y in 0..height {
+        for x in 0..width {
+            if hole_y.contains(&y) && hole_x.contains(&x) {
+                continue;
+            }
+            let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+            let coverage: f32 = shapes.iter().map(|(shape, sign)| shape.coverage(px, py) * sign).sum();
+            if coverage > 0.0 {
+                blend_over(img.get_pixel_mut(x, y), style.color, coverage.min(1.0));
+            }
+        }
+    }
+    Ok(())
+}
 
-    let scale = Scale::uniform(font_size);
+// A rectangle with a radius per corner (top-left, top-right, bottom-right, bottom-left)
+struct RoundedRect {
+    left: f32,
+    top: f32,
+    right: f32,
+    bottom: f32,
+    radius: [f32; 4],
+}
+
+impl RoundedRect {
+    // `outer` moved in by each side's `inset`; its corners lose the larger of their two sides'
+    // insets from the radius, down to square
+    fn inside([left, top, right, bottom]: [f32; 4], radius: f32, inset: BorderSides) -> Self {
+        let corner = |a: f32, b: f32| (radius - a.max(b)).max(0.0);
+        RoundedRect {
+            left: left + inset.left,
+            top: top + inset.top,
+            right: right - inset.right,
+            bottom: bottom - inset.bottom,
+            radius: [
+                corner(inset.top, inset.left),
+                corner(inset.top, inset.right),
+                corner(inset.bottom, inset.right),
+                corner(inset.bottom, inset.left),
+            ],
+        }
+    }
+
+    // How much of the pixel centered on (x, y) is inside, from its signed distance to the edge
+    fn coverage(&self, x: f32, y: f32) -> f32 {
+        let (half_width, half_height) = ((self.right - self.left) / 2.0, (self.bottom - self.top) / 2.0);
+        if half_width <= 0.0 || half_height <= 0.0 {
+            return 0.0;
+        }
+        let (dx, dy) = (x - (self.left + half_width), y - (self.top + half_height));
+        let radius = match (dx < 0.0, dy < 0.0) {
+            (true, true) => self.radius[0],
+            (false, true) => self.radius[1],
+            (false, false) => self.radius[2],
+            (true, false) => self.radius[3],
+        }
+        .min(half_width)
+        .min(half_height);
+        let (qx, qy) = (dx.abs() - half_width + radius, dy.abs() - half_height + radius);
+        let distance = qx.max(qy).min(0.0) + (qx.max(0.0).powi(2) + qy.max(0.0).powi(2)).sqrt() - radius;
+        (0.5 - distance).clamp(0.0, 1.0)
+    }
+}
 
-    // Calculate text size for centering
-    let (text_width, text_height) = calculate_text_size(&font, scale, text);
-    
-    // Calculate centered position
-    let centered_x = x - text_width / 2;
-    let centered_y = y - text_height / 2;
-    
-    println!("🎯 Centering text '{}' around ({}, {})", text, x, y);
-    println!("📐 Text dimensions: {}x{} pixels", text_width, text_height);
-    println!("📍 Drawing at adjusted position: ({}, {})", centered_x, centered_y);
+// Blend `color` over `pixel` at `coverage`, weighting by both alphas so a translucent color
+// over a transparent template stays translucent
+pub(crate) fn blend_over(pixel: &mut Rgba<u8>, color: Rgba<u8>, coverage: f32) {
+    let source = color[3] as f32 / 255.0 * coverage;
+    let destination = pixel[3] as f32 / 255.0;
+    let alpha = source + destination * (1.0 - source);
+    if alpha <= 0.0 {
+        return;
+    }
+    for channel in 0..3 {
+        let blended = (color[channel] as f32 * source + pixel[channel] as f32 * destination * (1.0 - source)) / alpha;
+        pixel[channel] = blended.round() as u8;
+    }
+    pixel[3] = (alpha * 255.0).round() as u8;
+}
 
-    // Draw text at centered position
-    draw_text_mut(&mut img, color, centered_x, centered_y, scale, &font, text);
+/// Which side of the circle [`draw_text_on_arc`] sets text on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArcSide {
+    /// Running clockwise over the top, letters pointing away from the center.
+    #[default]
+    Top,
+    /// Running counterclockwise under the bottom, letters upright and pointing at the center.
+    Bottom,
+}
 
-    img.save_with_format(output_path, ImageFormat::Png)
-        .with_context(|| format!("Failed to save image: {}", output_path))?;
+/// How much of the circle arc text takes up. Angles are in degrees, clockwise from the top
+/// of the circle (90 is the right, 180 the bottom).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArcSweep {
+    /// The font's own letter spacing, centered on the top or the bottom.
+    Natural,
+    /// Spread evenly over this many degrees, centered on the top or the bottom.
+    Centered(f32),
+    /// Spread evenly from the first letter at `start` to the last at `end`. The text runs
+    /// clockwise on the top, so `end` > `start`, and counterclockwise on the bottom.
+    Between { start: f32, end: f32 },
+}
 
-    println!("✅ Text added successfully with font '{}' and size {}!", font_filename, font_size);
-    println!("🎯 Text centered around coordinates ({}, {})", x, y);
-    println!("📁 Saved to: {}", output_path);
-    Ok(())
+/// Where and how [`draw_text_on_arc`] sets a piece of text.
+#[derive(Clone, Copy)]
+pub struct ArcOptions<'a> {
+    pub font: &'a Font<'a>,
+    pub size: f32,
+    pub color: Rgba<u8>,
+    pub center_x: i32,
+    pub center_y: i32,
+    /// Distance from the center to the baseline, in pixels.
+    pub radius: f32,
+    pub side: ArcSide,
+    pub sweep: ArcSweep,
 }
 
-pub fn add_text_with_custom_options(
-    input_path: &str,
-    output_path: &str,
+/// Draw `text` along a circle, each glyph rotated so its baseline is tangent to the arc, and
+/// return the box around everything drawn. Glyphs are spaced by their advances and kerning,
+/// plus an equal extra gap when the sweep is wider than the text; a sweep too narrow for the
+/// text is an error rather than overlapping letters. Straight text is unaffected: this is a
+/// separate, per-glyph rasterizer that blends the same way [`draw_text`] does.
+///
+/// ```
+/// use certificate_maker::editpng::{ArcOptions, ArcSide, ArcSweep, draw_text_on_arc, load_font};
+/// use image::{Rgba, RgbaImage};
+///
+/// let font = load_font("assets/DejaVuSans.ttf")?;
+/// let mut img = RgbaImage::from_pixel(400, 400, Rgba([255, 255, 255, 255]));
+/// let options = ArcOptions {
+///     font: &font, size: 24.0, color: Rgba([0, 0, 0, 255]), center_x: 200, center_y: 200,
+///     radius: 150.0, side: ArcSide::Top, sweep: ArcSweep::Centered(120.0),
+/// };
+/// let bounds = draw_text_on_arc(&mut img, "CERTIFICATE OF EXCELLENCE", &options)?;
+/// // Over the top of the circle, not below its center
+/// assert!(bounds.bottom() < 200 && bounds.y < 60);
+/// # Ok::<(), certificate_maker::error::CertError>(())
+/// ```
+pub fn draw_text_on_arc(img: &mut RgbaImage, text: &str, opts: &ArcOptions) -> Result<TextBounds> {
+    if !opts.size.is_finite() || opts.size <= 0.0 {
+        return Err(CertError::InvalidFontSize(opts.size));
+    }
+    let invalid = |reason: String| CertError::InvalidArc { text: text.to_string(), reason };
+    if !opts.radius.is_finite() || opts.radius <= 0.0 {
+        return Err(invalid(format!("the radius must be a positive number, got {}", opts.radius)));
+    }
+
+    // Lay the glyphs out on a straight baseline first: (glyph, distance of its middle from the
+    // start of the text), with kerning included in the advances
+    let scale = Scale::uniform(opts.size);
+    let mut glyphs = Vec::new();
+    let mut pen = 0.0;
+    let mut previous = None;
+    for c in text.chars().filter(|c| !c.is_control()) {
+        let glyph = opts.font.glyph(c).scaled(scale);
+        if let Some(previous) = previous {
+            pen += opts.font.pair_kerning(scale, previous, glyph.id());
+        }
+        previous = Some(glyph.id());
+        let advance = glyph.h_metrics().advance_width;
+        glyphs.push((glyph, pen + advance / 2.0));
+        pen += advance;
+    }
+    if glyphs.is_empty() {
+        return Ok(TextBounds { x: opts.center_x, y: opts.center_y, width: 0, height: 0 });
+    }
+
+    // Arc length the text needs, and the first glyph's angle, direction and extra gap
+    let natural = (pen / opts.radius).to_degrees();
+    let middle = match opts.side {
+        ArcSide::Top => 0.0,
+        ArcSide::Bottom => 180.0,
+    };
+    let direction = match opts.side {
+        ArcSide::Top => 1.0,
+        ArcSide::Bottom => -1.0,
+    };
+    let (start, sweep) = match opts.sweep {
+        ArcSweep::Natural => (middle - direction * natural / 2.0, natural),
+        ArcSweep::Centered(sweep) => (middle - direction * sweep / 2.0, sweep),
+        ArcSweep::Between { start, end } => (start, (end - start) * direction),
+    };
+    if !sweep.is_finite() || sweep <= 0.0 {
+        return Err(invalid(match opts.side {
+            ArcSide::Top => "text on the top runs clockwise, so end must be greater than start".to_string(),
+            ArcSide::Bottom => "text on the bottom runs counterclockwise, so end must be less than start".to_string(),
+        }));
+    }
+    if sweep < natural - 0.01 {
+        return Err(invalid(format!("it needs {:.1} degrees at radius {}, but only has {:.1}", natural, opts.radius, sweep)));
+    }
+    let extra_gap = if glyphs.len() > 1 { (sweep - natural).to_radians() * opts.radius / (glyphs.len() - 1) as f32 } else { 0.0 };
+    // A single glyph spread over a sweep sits in its middle
+    let start = if glyphs.len() == 1 { start + direction * (sweep - natural) / 2.0 } else { start };
+
+    let mut union: Option<(i32, i32, i32, i32)> = None;
+    for (index, (glyph, middle_offset)) in glyphs.into_iter().enumerate() {
+        let distance = middle_offset + extra_gap * index as f32;
+        let angle = start.to_radians() + direction * distance / opts.radius;
+        // On the bottom the letters are turned half a circle further, so they stand upright
+        let rotation = match opts.side {
+            ArcSide::Top => angle,
+            ArcSide::Bottom => angle - std::f32::consts::PI,
+        };
+        let anchor = (
+            opts.center_x as f32 + opts.radius * angle.sin(),
+            opts.center_y as f32 - opts.radius * angle.cos(),
+        );
+        let glyph = glyph.positioned(point(0.0, 0.0));
+        let Some(glyph_box) = glyph.pixel_bounding_box() else {
+            continue;
+        };
+        let advance = glyph.unpositioned().h_metrics().advance_width;
+        let (x0, y0, x1, y1) = draw_rotated_glyph(img, &glyph, glyph_box, advance, anchor, rotation, opts.color);
+        union = Some(match union {
+            Some((ux0, uy0, ux1, uy1)) => (ux0.min(x0), uy0.min(y0), ux1.max(x1), uy1.max(y1)),
+            None => (x0, y0, x1, y1),
+        });
+    }
+
+    Ok(match union {
+        Some((x0, y0, x1, y1)) => TextBounds { x: x0, y: y0, width: x1 - x0, height: y1 - y0 },
+        None => TextBounds { x: opts.center_x, y: opts.center_y, width: 0, height: 0 },
+    })
+}
+
+// Rasterize one glyph upright, then blend it into the image turned `rotation` radians
+// clockwise about the middle of its baseline, which lands on `anchor`. Returns the image box
+// it covers
+fn draw_rotated_glyph(
+    img: &mut RgbaImage,
+    glyph: &rusttype::PositionedGlyph,
+    glyph_box: rusttype::Rect<i32>,
+    advance: f32,
+    anchor: (f32, f32),
+    rotation: f32,
+    color: Rgba<u8>,
+) -> (i32, i32, i32, i32) {
+    let (width, height) = (glyph_box.width() as usize, glyph_box.height() as usize);
+    let mut coverage = vec![0.0f32; width * height];
+    glyph.draw(|x, y, value| coverage[y as usize * width + x as usize] = value);
+    let sample = |x: i32, y: i32| -> f32 {
+        if x < 0 || y < 0 || x as usize >= width || y as usize >= height {
+            0.0
+        } else {
+            coverage[y as usize * width + x as usize]
+        }
+    };
+
+    // Glyph box corners relative to the middle of the baseline, turned into image space
+    let (sin, cos) = rotation.sin_cos();
+    let left = glyph_box.min.x as f32 - advance / 2.0;
+    let corners = [
+        (left, glyph_box.min.y as f32),
+        (left + width as f32, glyph_box.min.y as f32),
+        (left, glyph_box.max.y as f32),
+        (left + width as f32, glyph_box.max.y as f32),
+    ].map(|(u, v)| (anchor.0 + u * cos - v * sin, anchor.1 + u * sin + v * cos));
+    let min_x = corners.iter().map(|c| c.0).fold(f32::INFINITY, f32::min).floor() as i32;
+    let max_x = corners.iter().map(|c| c.0).fold(f32::NEG_INFINITY, f32::max).ceil() as i32;
+    let min_y = corners.iter().map(|c| c.1).fold(f32::INFINITY, f32::min).floor() as i32;
+    let max_y = corners.iter().map(|c| c.1).fold(f32::NEG_INFINITY, f32::max).ceil() as i32;
+
+    // Every image pixel in that box is turned back into the glyph's own space and sampled
+    // bilinearly there
+    for y in min_y.max(0)..max_y.min(img.height() as i32) {
+        for x in min_x.max(0)..max_x.min(img.width() as i32) {
+            let (dx, dy) = (x as f32 + 0.5 - anchor.0, y as f32 + 0.5 - anchor.1);
+            let u = dx * cos + dy * sin - left - 0.5;
+            let v = -dx * sin + dy * cos - glyph_box.min.y as f32 - 0.5;
+            let (u0, v0) = (u.floor(), v.floor());
+            let (fu, fv) = (u - u0, v - v0);
+            let (u0, v0) = (u0 as i32, v0 as i32);
+            let value = sample(u0, v0) * (1.0 - fu) * (1.0 - fv)
+                + sample(u0 + 1, v0) * fu * (1.0 - fv)
+                + sample(u0, v0 + 1) * (1.0 - fu) * fv
+                + sample(u0 + 1, v0 + 1) * fu * fv;
+            if value > 0.0 {
+                // Same blend as draw_text_mut, so arc and straight text look alike
+                let pixel = img.get_pixel_mut(x as u32, y as u32);
+                for channel in 0..4 {
+                    pixel[channel] = (pixel[channel] as f32 * (1.0 - value) + color[channel] as f32 * value).round() as u8;
+                }
+            }
+        }
+    }
+    (min_x, min_y, max_x, max_y)
+}
+
+/// Draw text with its top-left corner at `(x, y)` onto a copy of the template, without saving it.
+pub fn render_text_with_custom_options(
+    input_path: impl AsRef<Path>,
     text: &str,
     x: i32,
     y: i32,
-    font_filename: &str,
+    font_filename: impl AsRef<Path>,
     font_size: f32,
     hex_color: &str,
-) -> Result<()> {
-    let mut img = open(input_path)
-        .with_context(|| format!("Failed to open image: {}", input_path))?
-        .to_rgba8();
-
-    // Load selected font
-    let font_data = load_font_data(font_filename)?;
-    let font = Font::try_from_bytes(&font_data)
-        .ok_or_else(|| anyhow::anyhow!("Failed to load font: {}", font_filename))?;
-
-    // Convert hex color to RGBA
-    let text_color = hex_to_rgba(hex_color)?;
+) -> Result<RgbaImage> {
+    render_text_on(load_template(input_path)?, text, x, y, font_filename, font_size, hex_color)
+}
 
-    let scale = Scale::uniform(font_size);
-    draw_text_mut(&mut img, text_color, x, y, scale, &font, text);
+/// [`render_text_with_custom_options`] on a template that is already decoded.
+pub fn render_text_on(
+    mut img: RgbaImage,
+    text: &str,
+    x: i32,
+    y: i32,
+    font_filename: impl AsRef<Path>,
+    font_size: f32,
+    hex_color: &str,
+) -> Result<RgbaImage> {
+    let font = load_font(font_filename)?;
+    let options = TextOptions {
+        font: &font,
+        size: font_size,
+        color: hex_to_rgba(hex_color)?,
+        x,
+        y,
+        anchor: Anchor::TopLeft,
+        decorations: &[],
+        synthetic: SyntheticStyle::default(),
+        variation: None,
+        effect: None,
+    };
+    draw_text(&mut img, text, &options)?;
+    Ok(img)
+}
 
-    img.save_with_format(output_path, ImageFormat::Png)
-        .with_context(|| format!("Failed to save image: {}", output_path))?;
+/// Draw text with its top-left corner at `(x, y)` and save the result as PNG.
+#[allow(clippy::too_many_arguments)]
+pub fn add_text_with_custom_options(
+    input_path: impl AsRef<Path>,
+    output_path: impl AsRef<Path>,
+    text: &str,
+    x: i32,
+    y: i32,
+    font_filename: impl AsRef<Path>,
+    font_size: f32,
+    hex_color: &str,
+) -> Result<()> {
+    let img = render_text_with_custom_options(input_path, text, x, y, font_filename, font_size, hex_color)?;
+    save_image(&img, output_path, ImageFormat::Png)
+}
 
-    println!("✅ Custom text added successfully!");
-    println!("📁 Saved to: {}", output_path);
-    Ok(())
+/// Render one certificate with `text` centered around `(center_x, center_y)` and save it as PNG.
+///
+/// Returns the top-left position the text was drawn at.
+#[allow(clippy::too_many_arguments)]
+pub fn render_certificate(
+    template_path: impl AsRef<Path>,
+    output_path: impl AsRef<Path>,
+    text: &str,
+    center_x: i32,
+    center_y: i32,
+    font_filename: impl AsRef<Path>,
+    font_size: f32,
+    hex_color: &str,
+) -> Result<(i32, i32)> {
+    let mut img = load_template(template_path)?;
+    let font = load_font(font_filename)?;
+    let options = TextOptions {
+        font: &font,
+        size: font_size,
+        color: hex_to_rgba(hex_color)?,
+        x: center_x,
+        y: center_y,
+        anchor: Anchor::Center,
+        decorations: &[],
+        synthetic: SyntheticStyle::default(),
+        variation: None,
+        effect: None,
+    };
+    let bounds = draw_text(&mut img, text, &options)?;
+    save_image(&img, output_path, ImageFormat::Png)?;
+    Ok((bounds.x, bounds.y))
 }
@@ -0,0 +1,380 @@
+// src/effects.rs
+//! Named text effects such as gold foil or engraved lettering, so a look is picked by one word
+//! instead of tuned.
+//!
+//! A [`TextEffect`] bundles what [`draw_text`](crate::editpng::draw_text) can add to plain
+//! glyphs: a vertical [`Gradient`] in place of the text color, a [`Stroke`] around the letters
+//! and [`Shadow`]s under them. Its lengths are fractions of the font size, so an effect looks
+//! the same on a 20 px line and a 90 px name, and survives [`fit_to_width`] shrinking the text.
+//! Because it is drawn by `draw_text` itself, it goes wherever text does: every anchor, faux
+//! bold and italic, variable fonts and underlines.
+//!
+//! [`BUILTIN_EFFECTS`] are always there. A palette file adds its own, or redefines a built-in,
+//! in `[effects.<name>]` tables whose colors may name palette entries:
+//!
+//! ```toml
+//! [effects.award]
+//! gradient = ["palette:gold", "#7A5C12"]                    # top, bottom
+//! stroke = { width = 0.02, color = "#3D2E08" }
+//! shadow = { x = 0.03, y = 0.03, blur = 0.03, color = "#00000080" }
+//! ```
+//!
+//! `shadow` may also be a list of them, drawn in order.
+//!
+//! ```
+//! use certificate_maker::editpng::{Anchor, SyntheticStyle, TextOptions, draw_text, load_font};
+//! use certificate_maker::effects::{Gradient, TextEffect, find_effect};
+//! use image::{Rgba, RgbaImage};
+//!
+//! let font = load_font("assets/DejaVuSans.ttf")?;
+//! let fade = TextEffect {
+//!     gradient: Some(Gradient { top: Rgba([255, 0, 0, 255]), bottom: Rgba([0, 0, 255, 255]) }),
+//!     ..TextEffect::named("fade")
+//! };
+//! let mut img = RgbaImage::from_pixel(300, 160, Rgba([255, 255, 255, 255]));
+//! let options = TextOptions {
+//!     font: &font, size: 100.0, color: Rgba([0, 0, 0, 255]), x: 150, y: 80, anchor: Anchor::Center,
+//!     decorations: &[], synthetic: SyntheticStyle::default(), variation: None, effect: Some(&fade),
+//! };
+//! let bounds = draw_text(&mut img, "I", &options)?;
+//! // Red at the top of the stem, blue at its foot
+//! let middle = (bounds.y + bounds.height / 2) as u32;
+//! let stem = (0..300).find(|&x| img.get_pixel(x, middle)[1] < 100).expect("the stem is drawn") + 3;
+//! let (top, foot) = (img.get_pixel(stem, (bounds.y + bounds.height / 5) as u32), img.get_pixel(stem, (bounds.y + bounds.height * 3 / 4) as u32));
+//! assert!(top[0] > top[2] && foot[2] > foot[0]);
+//!
+//! assert!(find_effect("Gold-Foil")?.stroke.is_some());
+//! assert!(find_effect("glitter").is_err());
+//! # Ok::<(), certificate_maker::error::CertError>(())
+//! ```
+//!
+//! [`fit_to_width`]: crate::editpng::fit_to_width
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::editpng::{TextBounds, TextOptions, hex_to_rgba};
+use crate::error::{CertError, Result};
+use crate::palette::installed;
+
+/// Effects every installation has, in the order they are offered.
+pub const BUILTIN_EFFECTS: [&str; 4] = ["shadow", "engraved", "embossed", "gold-foil"];
+
+/// Widest [`Stroke`] an effect takes, as a fraction of the font size.
+pub const MAX_STROKE: f32 = 0.25;
+
+/// Farthest a [`Shadow`] is offset or blurred, as a fraction of the font size.
+pub const MAX_SHADOW: f32 = 1.0;
+
+/// A vertical fill from the top of the text box to its bottom, drawn instead of the text color.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Gradient {
+    #[serde(with = "hex_color")]
+    pub top: Rgba<u8>,
+    #[serde(with = "hex_color")]
+    pub bottom: Rgba<u8>,
+}
+
+impl Gradient {
+    /// The color `t` of the way down, 0 at the top and 1 at the bottom.
+    pub fn at(&self, t: f32) -> Rgba<u8> {
+        let t = t.clamp(0.0, 1.0);
+        Rgba(std::array::from_fn(|channel| {
+            (self.top[channel] as f32 + (self.bottom[channel] as f32 - self.top[channel] as f32) * t).round() as u8
+        }))
+    }
+}
+
+/// An outline around the letters, under the fill.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Stroke {
+    /// Fraction of the font size the outline reaches past the letters.
+    pub width: f32,
+    #[serde(with = "hex_color")]
+    pub color: Rgba<u8>,
+}
+
+/// A copy of the letters, outline included, drawn under them and offset.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Shadow {
+    /// Offset to the right, as a fraction of the font size; negative is to the left.
+    pub x: f32,
+    /// Offset downwards, as a fraction of the font size; negative is upwards.
+    pub y: f32,
+    /// How far the edges are softened, as a fraction of the font size.
+    #[serde(default)]
+    pub blur: f32,
+    #[serde(with = "hex_color")]
+    pub color: Rgba<u8>,
+}
+
+/// A named set of drawing parameters for text. Its colors are resolved, so one recorded in
+/// a manifest redraws the same pixels even after the palette changes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextEffect {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gradient: Option<Gradient>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stroke: Option<Stroke>,
+    /// Drawn first to last, all under the stroke and the fill.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shadows: Vec<Shadow>,
+}
+
+impl TextEffect {
+    /// An effect that draws nothing extra yet, to fill in.
+    pub fn named(name: impl Into<String>) -> Self {
+        TextEffect { name: name.into(), gradient: None, stroke: None, shadows: Vec::new() }
+    }
+
+    /// Check every length is a number within [`MAX_STROKE`] or [`MAX_SHADOW`].
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason: String| Err(CertError::InvalidEffect(format!("{}: {}", self.name, reason)));
+        if let Some(stroke) = &self.stroke
+            && !(stroke.width.is_finite() && (0.0..=MAX_STROKE).contains(&stroke.width)) {
+            return invalid(format!("the stroke width must be between 0 and {} of the font size, got {}", MAX_STROKE, stroke.width));
+        }
+        for shadow in &self.shadows {
+            if ![shadow.x, shadow.y].iter().all(|offset| offset.is_finite() && offset.abs() <= MAX_SHADOW) {
+                return invalid(format!("a shadow is offset at most {} of the font size either way, got x {} and y {}", MAX_SHADOW, shadow.x, shadow.y));
+            }
+            if !(shadow.blur.is_finite() && (0.0..=MAX_SHADOW).contains(&shadow.blur)) {
+                return invalid(format!("a shadow's blur must be between 0 and {} of the font size, got {}", MAX_SHADOW, shadow.blur));
+            }
+        }
+        Ok(())
+    }
+
+    /// How far past the letters the effect draws at `size`, in pixels.
+    pub fn reach(&self, size: f32) -> i32 {
+        let stroke = self.stroke.map_or(0, |stroke| stroke_pixels(stroke.width, size).ceil() as i32);
+        let shadow = self.shadows.iter()
+            .map(|shadow| pixels(shadow.x, size).abs().max(pixels(shadow.y, size).abs()) + 2 * blur_pixels(shadow.blur, size) as i32)
+            .max()
+            .unwrap_or(0);
+        stroke + shadow
+    }
+}
+
+/// The built-in effect called `name`, ignoring case.
+pub fn builtin_effect(name: &str) -> Option<TextEffect> {
+    let color = |hex: &str| hex_to_rgba(hex).expect("built-in effect colors are valid");
+    let name = BUILTIN_EFFECTS.into_iter().find(|builtin| builtin.eq_ignore_ascii_case(name.trim()))?;
+    let mut effect = TextEffect::named(name);
+    match name {
+        // A soft drop shadow, down and to the right
+        "shadow" => effect.shadows = vec![Shadow { x: 0.04, y: 0.04, blur: 0.03, color: color("#00000066") }],
+        // Cut into the paper: lit along the lower edges, shaded along the upper ones
+        "engraved" => effect.shadows = vec![
+            Shadow { x: 0.0, y: 0.025, blur: 0.0, color: color("#FFFFFFB3") },
+            Shadow { x: 0.0, y: -0.02, blur: 0.0, color: color("#00000059") },
+        ],
+        // Raised off the paper: lit from the top left, shading the bottom right
+        "embossed" => effect.shadows = vec![
+            Shadow { x: -0.02, y: -0.02, blur: 0.01, color: color("#FFFFFFCC") },
+            Shadow { x: 0.025, y: 0.025, blur: 0.02, color: color("#00000080") },
+        ],
+        // Light gold shading to dark, with a fine dark edge and a small shadow
+        "gold-foil" => {
+            effect.gradient = Some(Gradient { top: color("#F7E08A"), bottom: color("#A47A22") });
+            effect.stroke = Some(Stroke { width: 0.015, color: color("#5E4510") });
+            effect.shadows = vec![Shadow { x: 0.02, y: 0.025, blur: 0.02, color: color("#00000059") }];
+        }
+        _ => unreachable!("every built-in effect is listed"),
+    }
+    Some(effect)
+}
+
+/// Names of every effect [`find_effect`] knows: the installed palette's, then the built-ins it
+/// doesn't redefine.
+pub fn effect_names() -> Vec<String> {
+    let own: Vec<String> = installed().map(|palette| palette.effects.iter().map(|effect| effect.name.clone()).collect()).unwrap_or_default();
+    let builtins = BUILTIN_EFFECTS.into_iter()
+        .filter(|builtin| !own.iter().any(|name| name.eq_ignore_ascii_case(builtin)))
+        .map(str::to_string);
+    own.iter().cloned().chain(builtins).collect()
+}
+
+/// The effect called `name`, ignoring case, from the installed palette before the built-ins.
+pub fn find_effect(name: &str) -> Result<TextEffect> {
+    installed().and_then(|palette| palette.effect(name)).cloned()
+        .or_else(|| builtin_effect(name))
+        .ok_or_else(|| CertError::UnknownEffect { name: name.trim().to_string(), available: effect_names() })
+}
+
+// One [effects.<name>] table of a palette file, its colors read with `color`
+pub(crate) fn parse_effect(name: &str, table: &Value, color: &dyn Fn(&str) -> Result<Rgba<u8>>) -> std::result::Result<TextEffect, String> {
+    let Some(table) = table.as_object() else {
+        return Err(format!("effects.{}: expected a table of gradient, stroke and shadow", name));
+    };
+    let color_at = |key: &str, value: Option<&Value>| {
+        value.and_then(Value::as_str)
+            .ok_or_else(|| format!("effects.{}.{}: expected a color in quotes such as \"#1A1A40\"", name, key))
+            .and_then(|text| color(text).map_err(|e| format!("effects.{}.{}: {}", name, key, e)))
+    };
+    let number_at = |key: &str, value: Option<&Value>| {
+        value.and_then(Value::as_f64).map(|number| number as f32)
+            .ok_or_else(|| format!("effects.{}.{}: expected a number, a fraction of the font size such as 0.03", name, key))
+    };
+    let check_keys = |key: &str, part: &serde_json::Map<String, Value>, allowed: &[&str]| {
+        match part.keys().find(|found| !allowed.contains(&found.as_str())) {
+            Some(unknown) => Err(format!("effects.{}.{}.{}: unknown key; use {}", name, key, unknown, allowed.join(", "))),
+            None => Ok(()),
+        }
+    };
+
+    let mut effect = TextEffect::named(name);
+    for (key, value) in table {
+        match key.as_str() {
+            "gradient" => match value.as_array().map(Vec::as_slice) {
+                Some([top, bottom]) => effect.gradient = Some(Gradient {
+                    top: color_at("gradient", Some(top))?,
+                    bottom: color_at("gradient", Some(bottom))?,
+                }),
+                _ => return Err(format!("effects.{}.gradient: expected [top, bottom] colors, like [\"#F7E08A\", \"#A47A22\"]", name)),
+            },
+            "stroke" => {
+                let stroke = value.as_object().ok_or_else(|| format!("effects.{}.stroke: expected {{ width = 0.02, color = \"#3D2E08\" }}", name))?;
+                check_keys("stroke", stroke, &["width", "color"])?;
+                effect.stroke = Some(Stroke {
+                    width: number_at("stroke.width", stroke.get("width"))?,
+                    color: color_at("stroke.color", stroke.get("color"))?,
+                });
+            }
+            "shadow" => {
+                let shadows = match value {
+                    Value::Array(shadows) => shadows.iter().collect(),
+                    single => vec![single],
+                };
+                for shadow in shadows {
+                    let shadow = shadow.as_object()
+                        .ok_or_else(|| format!("effects.{}.shadow: expected {{ x = 0.03, y = 0.03, blur = 0.03, color = \"#00000080\" }}", name))?;
+                    check_keys("shadow", shadow, &["x", "y", "blur", "color"])?;
+                    effect.shadows.push(Shadow {
+                        x: number_at("shadow.x", shadow.get("x"))?,
+                        y: number_at("shadow.y", shadow.get("y"))?,
+                        blur: shadow.get("blur").map_or(Ok(0.0), |blur| number_at("shadow.blur", Some(blur)))?,
+                        color: color_at("shadow.color", shadow.get("color"))?,
+                    });
+                }
+            }
+            other => return Err(format!("effects.{}.{}: unknown key; an effect has gradient, stroke and shadow", name, other)),
+        }
+    }
+    effect.validate().map_err(|e| format!("effects.{}: {}", name, e))?;
+    Ok(effect)
+}
+
+// Paint `coverage`, the letters rasterized on a `width`-wide canvas whose top-left corner lands
+// on `origin`, with `effect`: the shadows, then the stroke, then the fill, the text color or
+// the gradient over the rows of the text box
+pub(crate) fn paint_effect(
+    img: &mut RgbaImage,
+    coverage: &[f32],
+    width: usize,
+    origin: (i32, i32),
+    effect: &TextEffect,
+    opts: &TextOptions,
+    bounds: &TextBounds,
+) {
+    let size = opts.size;
+    let height = coverage.len() / width.max(1);
+    let outline = match effect.stroke {
+        Some(stroke) if stroke.width > 0.0 => crate::editpng::dilate(coverage, width, height, stroke_pixels(stroke.width, size)),
+        _ => coverage.to_vec(),
+    };
+    for shadow in &effect.shadows {
+        let radius = blur_pixels(shadow.blur, size);
+        let blurred = if radius > 0 { box_blur(&outline, width, height, radius) } else { outline.clone() };
+        let shifted = (origin.0 + pixels(shadow.x, size), origin.1 + pixels(shadow.y, size));
+        blend_layer(img, &blurred, width, shifted, |_| shadow.color);
+    }
+    if let Some(stroke) = effect.stroke.filter(|stroke| stroke.width > 0.0) {
+        blend_layer(img, &outline, width, origin, |_| stroke.color);
+    }
+    blend_layer(img, coverage, width, origin, |y| match effect.gradient {
+        Some(gradient) => gradient.at((y - bounds.y) as f32 / bounds.height.max(1) as f32),
+        None => opts.color,
+    });
+}
+
+// A fraction of the font size in whole pixels, at least one either way when it isn't 0
+fn pixels(fraction: f32, size: f32) -> i32 {
+    let rounded = (fraction * size).round() as i32;
+    if rounded == 0 && fraction != 0.0 { fraction.signum() as i32 } else { rounded }
+}
+
+// Strokes stay at least a pixel wide, or small text would lose them
+fn stroke_pixels(fraction: f32, size: f32) -> f32 {
+    (fraction * size).max(1.0)
+}
+
+fn blur_pixels(fraction: f32, size: f32) -> usize {
+    (fraction * size).round().max(0.0) as usize
+}
+
+// Two passes of a box blur each way, close to a gaussian of about `radius`
+fn box_blur(coverage: &[f32], width: usize, height: usize, radius: usize) -> Vec<f32> {
+    let mut blurred = coverage.to_vec();
+    for _ in 0..2 {
+        blurred = blur_pass(&blurred, width, height, radius, true);
+        blurred = blur_pass(&blurred, width, height, radius, false);
+    }
+    blurred
+}
+
+// Each cell becomes the mean of the 2 * radius + 1 cells around it along rows or columns, with
+// nothing beyond the canvas edges
+fn blur_pass(coverage: &[f32], width: usize, height: usize, radius: usize, along_rows: bool) -> Vec<f32> {
+    let (lines, length) = if along_rows { (height, width) } else { (width, height) };
+    let at = |line: usize, step: usize| if along_rows { line * width + step } else { step * width + line };
+    let window = (2 * radius + 1) as f32;
+    let mut blurred = vec![0.0f32; coverage.len()];
+    for line in 0..lines {
+        let mut sum: f32 = (0..=radius.min(length.saturating_sub(1))).map(|step| coverage[at(line, step)]).sum();
+        for step in 0..length {
+            blurred[at(line, step)] = sum / window;
+            if step + radius + 1 < length {
+                sum += coverage[at(line, step + radius + 1)];
+            }
+            if step >= radius {
+                sum -= coverage[at(line, step - radius)];
+            }
+        }
+    }
+    blurred
+}
+
+// Composite `color(row)` over the image wherever the canvas has coverage, the color's alpha
+// scaling it
+fn blend_layer(img: &mut RgbaImage, coverage: &[f32], width: usize, (origin_x, origin_y): (i32, i32), color: impl Fn(i32) -> Rgba<u8>) {
+    for (index, &value) in coverage.iter().enumerate() {
+        let (x, y) = (origin_x + (index % width) as i32, origin_y + (index / width) as i32);
+        if value <= 0.0 || x < 0 || y < 0 || x as u32 >= img.width() || y as u32 >= img.height() {
+            continue;
+        }
+        let color = color(y);
+        let weight = value.min(1.0) * color[3] as f32 / 255.0;
+        let pixel = img.get_pixel_mut(x as u32, y as u32);
+        for channel in 0..3 {
+            pixel[channel] = (pixel[channel] as f32 * (1.0 - weight) + color[channel] as f32 * weight).round() as u8;
+        }
+        pixel[3] = (pixel[3] as f32 + (255.0 - pixel[3] as f32) * weight).round() as u8;
+    }
+}
+
+// Colors as #RRGGBB or #RRGGBBAA in manifests and saved settings
+mod hex_color {
+    use image::Rgba;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(color: &Rgba<u8>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&crate::palette::to_hex(*color))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Rgba<u8>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        crate::editpng::hex_to_rgba(&text).map_err(serde::de::Error::custom)
+    }
+}
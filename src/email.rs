@@ -0,0 +1,351 @@
+// src/email.rs
+//! Emailing each certificate to its recipient over SMTP once a batch is done.
+//!
+//! Only compiled with the `email` feature. Recipients come from the list's email column (see
+//! [`Record::email`](crate::source::Record::email)) by way of the manifest, which also records
+//! every message sent, so an interrupted run resumes without mailing anyone twice.
+//!
+//! Settings are the `"email"` section of `certmaker.json`, with the SMTP server, credentials
+//! and sender overridable from `CERTMAKER_SMTP_*` and `CERTMAKER_EMAIL_FROM`:
+//!
+//! ```json
+//! "email": {
+//!     "from": "Events Team <events@example.org>",
+//!     "subject": "Your certificate, {name}",
+//!     "body": "Hello {name},\n\nyour certificate is attached.",
+//!     "smtp_host": "smtp.example.org",
+//!     "username": "events@example.org",
+//!     "per_minute": 20
+//! }
+//! ```
+//!
+//! ```
+//! use certificate_maker::email::{fill_template, valid_address};
+//!
+//! assert_eq!(fill_template("Well done, {name}!", "Ada Lovelace"), "Well done, Ada Lovelace!");
+//! assert!(valid_address("ada@example.org"));
+//! assert!(!valid_address("ada at example.org"));
+//! ```
+use lettre::message::header::ContentType;
+use lettre::message::{Attachment, Mailbox, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Address, Message, SmtpTransport, Transport};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::error::{CertError, IoContext, Result};
+use crate::manifest::{Manifest, ManifestEntry, SentEmail};
+
+/// Subject used when the config file sets none.
+pub const DEFAULT_SUBJECT: &str = "Your certificate, {name}";
+/// Body used when the config file sets none.
+pub const DEFAULT_BODY: &str = "Hello {name},\n\nplease find your certificate attached.\n";
+/// How long connecting to and talking with the SMTP server may take.
+pub const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+/// Sending stops after this many failures in a row; the rest is left for the next run.
+pub const MAX_FAILURES_IN_A_ROW: usize = 3;
+
+/// How the connection to the SMTP server is secured.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Upgrade a plain connection with STARTTLS, usually on port 587.
+    #[default]
+    Starttls,
+    /// TLS from the first byte, usually on port 465.
+    Tls,
+    /// No encryption at all; only for a relay on the same machine or network.
+    None,
+}
+
+/// The `"email"` section of the config file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmailSettings {
+    /// Sender, either `events@example.org` or `Events Team <events@example.org>`.
+    pub from: Option<String>,
+    /// Subject line; `{name}` is replaced by the recipient's name.
+    pub subject: String,
+    /// Plain-text body; `{name}` is replaced by the recipient's name.
+    pub body: String,
+    pub smtp_host: Option<String>,
+    /// Defaults to 587, 465 or 25 depending on `security`.
+    pub smtp_port: Option<u16>,
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Messages sent per minute at most; 0 sends as fast as the server accepts them.
+    pub per_minute: u32,
+}
+
+impl Default for EmailSettings {
+    fn default() -> Self {
+        EmailSettings {
+            from: None,
+            subject: DEFAULT_SUBJECT.to_string(),
+            body: DEFAULT_BODY.to_string(),
+            smtp_host: None,
+            smtp_port: None,
+            security: SmtpSecurity::default(),
+            username: None,
+            password: None,
+            per_minute: 20,
+        }
+    }
+}
+
+impl EmailSettings {
+    /// Read the `"email"` section of a config file (a missing file or section gives the
+    /// defaults), then apply `CERTMAKER_SMTP_HOST`, `CERTMAKER_SMTP_PORT`,
+    /// `CERTMAKER_SMTP_USERNAME`, `CERTMAKER_SMTP_PASSWORD` and `CERTMAKER_EMAIL_FROM`.
+    pub fn load(config_file: impl AsRef<Path>) -> Result<Self> {
+        let path = config_file.as_ref();
+        let mut settings = if path.exists() {
+            let content = std::fs::read_to_string(path)
+                .io_context(|| format!("Failed to read config file: {}", path.display()))?;
+            let parse_error = |source| CertError::Json { context: format!("Failed to parse the email settings in {}", path.display()), source };
+            let mut config: serde_json::Value = serde_json::from_str(&content).map_err(parse_error)?;
+            match config.get_mut("email").map(serde_json::Value::take) {
+                Some(section) => serde_json::from_value(section).map_err(parse_error)?,
+                None => EmailSettings::default(),
+            }
+        } else {
+            EmailSettings::default()
+        };
+
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        settings.smtp_host = var("CERTMAKER_SMTP_HOST").or(settings.smtp_host);
+        settings.username = var("CERTMAKER_SMTP_USERNAME").or(settings.username);
+        settings.password = var("CERTMAKER_SMTP_PASSWORD").or(settings.password);
+        settings.from = var("CERTMAKER_EMAIL_FROM").or(settings.from);
+        if let Some(port) = var("CERTMAKER_SMTP_PORT") {
+            let port = port.parse().map_err(|_| CertError::EmailConfig(format!("CERTMAKER_SMTP_PORT '{}' is not a port number", port)))?;
+            settings.smtp_port = Some(port);
+        }
+        Ok(settings)
+    }
+
+    /// The port to connect to, explicit or the usual one for `security`.
+    pub fn port(&self) -> u16 {
+        self.smtp_port.unwrap_or(match self.security {
+            SmtpSecurity::Starttls => 587,
+            SmtpSecurity::Tls => 465,
+            SmtpSecurity::None => 25,
+        })
+    }
+
+    /// `host:port` of the SMTP server, for messages; `None` until a host is set.
+    pub fn server(&self) -> Option<String> {
+        self.smtp_host.as_ref().map(|host| format!("{}:{}", host, self.port()))
+    }
+
+    /// The pause between two messages that keeps under `per_minute`.
+    pub fn interval(&self) -> Duration {
+        match self.per_minute {
+            0 => Duration::ZERO,
+            per_minute => Duration::from_secs(60) / per_minute,
+        }
+    }
+}
+
+/// Replace every `{name}` in a subject or body template.
+pub fn fill_template(template: &str, name: &str) -> String {
+    template.replace("{name}", name)
+}
+
+/// Whether an address is something an SMTP server would accept as a recipient.
+pub fn valid_address(address: &str) -> bool {
+    address.parse::<Address>().is_ok()
+}
+
+/// One message to send.
+#[derive(Debug, Clone)]
+pub struct Outgoing {
+    pub name: String,
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+    /// The certificate to attach.
+    pub attachment: PathBuf,
+}
+
+/// A certificate that can't be emailed because its row has no usable address.
+#[derive(Debug, Clone)]
+pub struct Unaddressed {
+    pub name: String,
+    pub row: Option<usize>,
+    /// What the email column held, if anything.
+    pub address: Option<String>,
+}
+
+/// What an email run will do with the certificates of a batch.
+#[derive(Debug, Clone, Default)]
+pub struct EmailPlan {
+    pub outgoing: Vec<Outgoing>,
+    /// Certificates the manifest records as already sent to the same address.
+    pub already_sent: usize,
+    pub unaddressed: Vec<Unaddressed>,
+}
+
+/// Sort a batch's manifest entries (see
+/// [`BatchReport::entries`](crate::csvexcelparser::BatchReport::entries)) into messages to
+/// send, messages sent before, and rows without a valid address.
+pub fn plan_emails(settings: &EmailSettings, entries: &[ManifestEntry]) -> EmailPlan {
+    let mut plan = EmailPlan::default();
+    for entry in entries {
+        match &entry.email {
+            Some(to) if valid_address(to) => {
+                if entry.emailed.as_ref().is_some_and(|sent| &sent.to == to) {
+                    plan.already_sent += 1;
+                    continue;
+                }
+                plan.outgoing.push(Outgoing {
+                    name: entry.name.clone(),
+                    to: to.clone(),
+                    subject: fill_template(&settings.subject, &entry.name),
+                    body: fill_template(&settings.body, &entry.name),
+                    attachment: entry.output_file.clone(),
+                });
+            }
+            address => plan.unaddressed.push(Unaddressed { name: entry.name.clone(), row: entry.row, address: address.clone() }),
+        }
+    }
+    plan
+}
+
+/// A configured connection to the SMTP server.
+pub struct Mailer {
+    transport: SmtpTransport,
+    from: Mailbox,
+    server: String,
+}
+
+impl Mailer {
+    /// Check the settings and log in to the server, so bad credentials fail before anything
+    /// is sent.
+    pub fn connect(settings: &EmailSettings) -> Result<Mailer> {
+        let (Some(host), Some(server)) = (settings.smtp_host.as_deref(), settings.server()) else {
+            return Err(CertError::EmailConfig("no SMTP server; set \"smtp_host\" or CERTMAKER_SMTP_HOST".to_string()));
+        };
+        let Some(from) = settings.from.as_deref() else {
+            return Err(CertError::EmailConfig("no sender; set \"from\" or CERTMAKER_EMAIL_FROM".to_string()));
+        };
+        let from: Mailbox = from.parse()
+            .map_err(|source| CertError::EmailSender { address: from.to_string(), source })?;
+
+        let smtp_error = |source| CertError::Smtp { server: server.clone(), source };
+        let mut builder = match settings.security {
+            SmtpSecurity::Starttls => SmtpTransport::starttls_relay(host).map_err(smtp_error)?,
+            SmtpSecurity::Tls => SmtpTransport::relay(host).map_err(smtp_error)?,
+            SmtpSecurity::None => SmtpTransport::builder_dangerous(host),
+        }
+        .port(settings.port())
+        .timeout(Some(SMTP_TIMEOUT));
+        match (&settings.username, &settings.password) {
+            (Some(username), Some(password)) => builder = builder.credentials(Credentials::new(username.clone(), password.clone())),
+            (Some(_), None) => {
+                return Err(CertError::EmailConfig("a username but no password; set CERTMAKER_SMTP_PASSWORD".to_string()));
+            }
+            _ => {}
+        }
+
+        let transport = builder.build();
+        transport.test_connection().map_err(smtp_error)?;
+        Ok(Mailer { transport, from, server })
+    }
+
+    /// Send one certificate.
+    pub fn send(&self, outgoing: &Outgoing) -> Result<()> {
+        let to: Mailbox = outgoing.to.parse()
+            .map_err(|_| CertError::EmailConfig(format!("'{}' is not a valid recipient", outgoing.to)))?;
+        let bytes = std::fs::read(&outgoing.attachment)
+            .io_context(|| format!("Failed to read certificate: {}", outgoing.attachment.display()))?;
+        let filename = outgoing.attachment.file_name().unwrap_or_default().to_string_lossy().to_string();
+        // Certificates saved as PDFs are attached as PDFs
+        let is_pdf = outgoing.attachment.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("pdf"));
+        let content_type = if is_pdf { "application/pdf" } else { "image/png" };
+        let content_type = ContentType::parse(content_type).expect("image/png and application/pdf are valid content types");
+
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(&outgoing.subject)
+            .multipart(MultiPart::mixed()
+                .singlepart(SinglePart::plain(outgoing.body.clone()))
+                .singlepart(Attachment::new(filename).body(bytes, content_type)))
+            .map_err(|source| CertError::EmailMessage { to: outgoing.to.clone(), source })?;
+        self.transport.send(&message)
+            .map_err(|source| CertError::Smtp { server: self.server.clone(), source })?;
+        Ok(())
+    }
+}
+
+/// How an email run went.
+#[derive(Debug, Clone, Default)]
+pub struct EmailReport {
+    pub sent: usize,
+    /// Recipient and error of every message that failed.
+    pub failed: Vec<(String, String)>,
+    /// Messages not attempted because too many failed in a row.
+    pub not_attempted: usize,
+}
+
+/// Send every planned message, pausing between them to stay under the rate limit, and record
+/// each one in the manifest of `output_dir` as soon as the server accepted it.
+///
+/// `on_message` is called after each attempt with the message and its outcome.
+pub fn send_emails(
+    mailer: &Mailer,
+    plan: &EmailPlan,
+    output_dir: impl AsRef<Path>,
+    interval: Duration,
+    mut on_message: impl FnMut(&Outgoing, &Result<()>),
+) -> Result<EmailReport> {
+    let output_dir = output_dir.as_ref();
+    let mut report = EmailReport::default();
+    let mut failures_in_a_row = 0;
+    // Loaded once for the whole run; each sent message is marked in memory and saved
+    let mut manifest = Manifest::load(output_dir)?;
+    let positions: HashMap<PathBuf, usize> = manifest.entries.iter()
+        .enumerate()
+        .map(|(position, entry)| (entry.output_file.clone(), position))
+        .collect();
+
+    for (index, outgoing) in plan.outgoing.iter().enumerate() {
+        if index > 0 {
+            std::thread::sleep(interval);
+        }
+        let result = mailer.send(outgoing);
+        on_message(outgoing, &result);
+        match result {
+            Ok(()) => {
+                if let Some(&position) = positions.get(&outgoing.attachment) {
+                    mark_sent(&mut manifest.entries[position], outgoing);
+                    // Saved after every message, and atomically, so a crash or Ctrl+C loses at most the one being sent
+                    manifest.save(output_dir)?;
+                }
+                report.sent += 1;
+                failures_in_a_row = 0;
+            }
+            Err(e) => {
+                report.failed.push((outgoing.to.clone(), e.to_string()));
+                failures_in_a_row += 1;
+                if failures_in_a_row == MAX_FAILURES_IN_A_ROW {
+                    report.not_attempted = plan.outgoing.len() - index - 1;
+                    break;
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+// Record who the certificate went to and when
+fn mark_sent(entry: &mut ManifestEntry, outgoing: &Outgoing) {
+    let at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    entry.emailed = Some(SentEmail { to: outgoing.to.clone(), at });
+}
@@ -0,0 +1,271 @@
+// src/error.rs
+//! Error type returned by the library.
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Everything the library can fail with, so embedders can match on the cause.
+#[derive(Debug, Error)]
+pub enum CertError {
+    #[error("Font file not found: {}", .0.display())]
+    FontNotFound(PathBuf),
+
+    #[error("Failed to load font: {}", .0.display())]
+    FontParse(PathBuf),
+
+    #[error("No font file or family is called '{query}'{}", if .suggestions.is_empty() { String::new() } else { format!(". Did you mean {}?", .suggestions.join(" or ")) })]
+    UnknownFont { query: String, suggestions: Vec<String> },
+
+    #[error("'{query}' matches several fonts; use a fuller name or the filename:\n  - {}", .candidates.join("\n  - "))]
+    AmbiguousFont { query: String, candidates: Vec<String> },
+
+    #[error("No '{wanted}' column found. Make sure your CSV has a column named '{wanted}' (available columns: {available:?})")]
+    MissingColumn { wanted: String, available: Vec<String> },
+
+    #[error("No valid names found in CSV file: {}", .0.display())]
+    NoNames(PathBuf),
+
+    #[error("Duplicate names found: {}. Resolve them in the file or choose a duplicate policy", .0.join(", "))]
+    DuplicateNames(Vec<String>),
+
+    #[error("Invalid row selection '{input}': {reason}")]
+    InvalidSelection { input: String, reason: String },
+
+    #[error("Unsupported file type: {}. Please use .csv, .json or .txt files{}", .0.display(), if cfg!(feature = "xlsx") { " (or .xlsx)" } else { "" })]
+    UnsupportedFileType(PathBuf),
+
+    #[error("Malformed JSON in {} at byte {offset}: {source}\n  near: {snippet}", path.display())]
+    MalformedJson { path: PathBuf, offset: usize, snippet: String, #[source] source: serde_json::Error },
+
+    #[error("Expected a JSON array of names or objects in {}, found {found}", path.display())]
+    UnexpectedJson { path: PathBuf, found: &'static str },
+
+    #[error("Reading name lists from URLs needs the 'remote' feature (cargo build --features remote): {0}")]
+    RemoteDisabled(String),
+
+    #[error("Notifying {0} when a batch finishes needs the 'remote' feature (cargo build --features remote)")]
+    WebhookDisabled(String),
+
+    #[cfg(feature = "remote")]
+    #[error("Could not look up the server of {url}; check the address and your internet connection")]
+    RemoteDns { url: String },
+
+    #[cfg(feature = "remote")]
+    #[error("Could not connect to the server of {url}: {reason}")]
+    RemoteConnect { url: String, reason: String },
+
+    #[cfg(feature = "remote")]
+    #[error("Timed out after {seconds} s downloading {url}")]
+    RemoteTimeout { url: String, seconds: u64 },
+
+    #[cfg(feature = "remote")]
+    #[error("Downloading {url} failed: HTTP {status}")]
+    RemoteStatus { url: String, status: reqwest::StatusCode },
+
+    #[cfg(feature = "remote")]
+    #[error("{url} is larger than the {} MB download limit", limit / 1_000_000)]
+    RemoteTooLarge { url: String, limit: u64 },
+
+    #[cfg(feature = "remote")]
+    #[error("{url} returned a web page instead of a name list; a Google Sheet must be shared with 'Anyone with the link'")]
+    RemoteHtml { url: String },
+
+    #[cfg(feature = "remote")]
+    #[error("Failed to download {url}: {source}")]
+    Remote { url: String, #[source] source: reqwest::Error },
+
+    #[cfg(feature = "remote")]
+    #[error("Google Fonts has no family called '{0}'; check the spelling on fonts.google.com")]
+    UnknownFontFamily(String),
+
+    #[cfg(feature = "remote")]
+    #[error("Unreadable Google Fonts metadata: {0}")]
+    FontMetadata(String),
+
+    #[cfg(feature = "remote")]
+    #[error("Notifying {url} failed: {reason}")]
+    Webhook { url: String, reason: String },
+
+    #[cfg(feature = "email")]
+    #[error("Email settings: {0}")]
+    EmailConfig(String),
+
+    #[cfg(feature = "email")]
+    #[error("'{address}' is not a valid sender address: {source}")]
+    EmailSender { address: String, #[source] source: lettre::address::AddressError },
+
+    #[cfg(feature = "email")]
+    #[error("Could not build the email to {to}: {source}")]
+    EmailMessage { to: String, #[source] source: lettre::error::Error },
+
+    #[cfg(feature = "email")]
+    #[error("SMTP server {server}: {source}")]
+    Smtp { server: String, #[source] source: lettre::transport::smtp::Error },
+
+    #[cfg(feature = "server")]
+    #[error("Could not listen on {address}: {reason}")]
+    ServerBind { address: String, reason: String },
+
+    #[error("Failed to read CSV {}: {source}", path.display())]
+    Csv { path: PathBuf, #[source] source: csv::Error },
+
+    #[cfg(feature = "xlsx")]
+    #[error("Failed to read spreadsheet {}: {source}", path.display())]
+    Spreadsheet { path: PathBuf, #[source] source: calamine::Error },
+
+    #[error("Failed to decode image {}: {source}", path.display())]
+    ImageDecode { path: PathBuf, #[source] source: image::ImageError },
+
+    #[error("Failed to save image {}: {source}", path.display())]
+    ImageEncode { path: PathBuf, #[source] source: image::ImageError },
+
+    #[error("Failed to read PNG info of {}: {source}", path.display())]
+    PngDecode { path: PathBuf, #[source] source: png::DecodingError },
+
+    #[error("{} can't be used as a template: {reason}", path.display())]
+    UnsuitableTemplate { path: PathBuf, reason: String },
+
+    #[error("{0} has no alpha channel")]
+    NoAlphaChannel(String),
+
+    #[error("Invalid hex color '{color}': {reason}. Use #RRGGBB or #RRGGBBAA, like #1A1A40 or #1A1A4080")]
+    InvalidColor { color: String, reason: String },
+
+    #[error("No palette color is called '{name}'{}", if .available.is_empty() { "; there is no palette.toml to take it from".to_string() } else { format!("; the palette has {}", .available.join(", ")) })]
+    UnknownPaletteColor { name: String, available: Vec<String> },
+
+    #[error("{color} isn't one of the palette's colors; the nearest is {nearest} ({hex}, ΔE {delta_e:.1})")]
+    OffPalette { color: String, nearest: String, hex: String, delta_e: f32 },
+
+    #[error("Can't read palette {}: {reason}", path.display())]
+    PaletteParse { path: PathBuf, reason: String },
+
+    #[error("Invalid font variation: {0}")]
+    InvalidVariation(String),
+
+    #[error("Invalid synthetic style: {0}")]
+    InvalidStyle(String),
+
+    #[error("No text effect is called '{name}'; there are {}", .available.join(", "))]
+    UnknownEffect { name: String, available: Vec<String> },
+
+    #[error("Invalid text effect: {0}")]
+    InvalidEffect(String),
+
+    #[error("Font size must be a positive number, got {0}")]
+    InvalidFontSize(f32),
+
+    #[error("Can't measure text: {0}")]
+    InvalidMeasurement(String),
+
+    #[error("Can't set '{text}' on the arc: {reason}")]
+    InvalidArc { text: String, reason: String },
+
+    #[error("Invalid barcode: {0}")]
+    InvalidBarcode(String),
+
+    #[error("Invalid graphic field: {0}")]
+    InvalidGraphic(String),
+
+    #[error("Invalid table: {0}")]
+    InvalidTable(String),
+
+    #[error("Invalid border: {0}")]
+    InvalidBorder(String),
+
+    #[error("Invalid patch: {0}")]
+    InvalidPatch(String),
+
+    #[error("Invalid typography: {0}")]
+    InvalidTypography(String),
+
+    #[error("Invalid stamp: {0}")]
+    InvalidStamp(String),
+
+    #[error("Invalid watermark: {0}")]
+    InvalidWatermark(String),
+
+    #[error("Can't record the settings fingerprint: {0}")]
+    Fingerprint(String),
+
+    #[error("Can't put the certificates on sheets: {0}")]
+    InvalidImposition(String),
+
+    #[error("Can't set the duplicates aside: {0}")]
+    InvalidSetAside(String),
+
+    #[error("The {kind} ({width}x{height} at {x}, {y}) runs off the {template_width}x{template_height} template")]
+    GraphicOffTemplate { kind: &'static str, x: i32, y: i32, width: u32, height: u32, template_width: u32, template_height: u32 },
+
+    #[error("The position ({x}, {y}) is outside the {width}x{height} template: x must be 0 to {}, y 0 to {}. Allow bleed to place text past the edge on purpose", .width.saturating_sub(1), .height.saturating_sub(1))]
+    PositionOffTemplate { x: i32, y: i32, width: u32, height: u32 },
+
+    #[error("Can't write certificates to {}: {reason}. {hint}", path.display())]
+    OutputDirUnusable { path: PathBuf, reason: String, hint: String },
+
+    #[error("{} is a version {version} plan, and this Certificate Maker runs version {} plans; make the plan again", path.display(), crate::plan::PLAN_VERSION)]
+    PlanVersion { path: PathBuf, version: u64 },
+
+    #[error("{} has changed since the plan was made; make the plan again so the names are measured on what will be drawn", .0.display())]
+    PlanOutdated(PathBuf),
+
+    #[error("{} is a CSV plan, which is only for reading; execute the plan saved as .json", .0.display())]
+    PlanNotRunnable(PathBuf),
+
+    #[error("Invalid shard '{input}': {reason}")]
+    InvalidShard { input: String, reason: String },
+
+    #[error("The shard manifests can't be merged:\n  - {}", .0.join("\n  - "))]
+    ShardsDontAddUp(Vec<String>),
+
+    #[error("Could not build PDF: {0}")]
+    InvalidPdf(String),
+
+    #[error("Directory '{}' not found. Please create it and add {contents}.", dir.display())]
+    DirectoryNotFound { dir: PathBuf, contents: &'static str },
+
+    #[error("No {contents} found in '{}' directory. Please add {contents} first.", dir.display())]
+    NoFilesFound { dir: PathBuf, contents: &'static str },
+
+    #[error("No {} in '{}', so there is no record of which files a batch wrote there; nothing was deleted", crate::manifest::MANIFEST_FILENAME, .0.display())]
+    NoManifest(PathBuf),
+
+    #[error("The manifest in '{}' records no batch to undo (it was already undone, or was written by an older version); nothing was deleted", .0.display())]
+    NothingToUndo(PathBuf),
+
+    #[error("Can't read layout {}: {reason}", path.display())]
+    LayoutParse { path: PathBuf, reason: String },
+
+    #[error("Layout {} is version {version}, but this version of Certificate Maker reads version {}", path.display(), crate::layout::LAYOUT_VERSION)]
+    UnsupportedLayoutVersion { path: PathBuf, version: u32 },
+
+    #[error("Layout {} isn't valid:\n  - {}", path.display(), problems.join("\n  - "))]
+    InvalidLayoutFile { path: PathBuf, problems: Vec<String> },
+
+    #[error("Invalid layout:\n  - {}", .0.join("\n  - "))]
+    InvalidLayout(Vec<String>),
+
+    #[error("Invalid certificate job:\n  - {}", .0.join("\n  - "))]
+    InvalidJob(Vec<String>),
+
+    #[error("{context}: {source}")]
+    Json { context: String, #[source] source: serde_json::Error },
+
+    #[error("Failed to create worker thread pool: {0}")]
+    ThreadPool(#[from] rayon::ThreadPoolBuildError),
+
+    #[error("{context}: {source}")]
+    Io { context: String, #[source] source: std::io::Error },
+}
+
+pub type Result<T, E = CertError> = std::result::Result<T, E>;
+
+/// `with_context` for I/O results, producing [`CertError::Io`].
+pub(crate) trait IoContext<T> {
+    fn io_context<F: FnOnce() -> String>(self, context: F) -> Result<T>;
+}
+
+impl<T> IoContext<T> for std::result::Result<T, std::io::Error> {
+    fn io_context<F: FnOnce() -> String>(self, context: F) -> Result<T> {
+        self.map_err(|source| CertError::Io { context: context(), source })
+    }
+}
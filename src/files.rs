@@ -0,0 +1,284 @@
+// src/files.rs
+//! Directory scanning shared by the template, font, CSV and image listings, and
+//! checks on the directories outputs are written to.
+use crate::error::{CertError, IoContext, Result};
+use std::cmp::Ordering;
+use std::ffi::OsString;
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use walkdir::{DirEntry, WalkDir};
+
+/// How many levels of subdirectories are searched, e.g. `Template/2025/gala/`.
+pub const MAX_SCAN_DEPTH: usize = 3;
+
+/// Files under `dir` (and its subdirectories) whose extension is one of `extensions`.
+///
+/// Paths are returned relative to `dir`, so `dir.join(path)` gives the full path; names
+/// that aren't valid UTF-8 are kept as they are. Hidden directories are skipped, and files
+/// sort by directory first, then by filename.
+pub fn list_files_with_extensions(dir: impl AsRef<Path>, extensions: &[&str]) -> Result<Vec<PathBuf>> {
+    let dir = dir.as_ref();
+    let mut files = Vec::new();
+
+    let walker = WalkDir::new(dir)
+        .min_depth(1)
+        .max_depth(MAX_SCAN_DEPTH + 1)
+        .into_iter()
+        .filter_entry(|entry| !is_hidden_dir(entry));
+
+    for entry in walker {
+        let entry = entry
+            .map_err(std::io::Error::from)
+            .io_context(|| format!("Failed to read {} directory", dir.display()))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let path = entry.path();
+        if let Some(extension) = path.extension() {
+            let ext = extension.to_string_lossy().to_lowercase();
+            if extensions.contains(&ext.as_str())
+                && let Ok(relative) = path.strip_prefix(dir) {
+                files.push(relative.to_path_buf());
+            }
+        }
+    }
+
+    files.sort_by(|a, b| by_directory_then_name(a, b));
+    Ok(files)
+}
+
+fn is_hidden_dir(entry: &DirEntry) -> bool {
+    entry.depth() > 0
+        && entry.file_type().is_dir()
+        && entry.file_name().to_string_lossy().starts_with('.')
+}
+
+// Top-level files first, then each subdirectory's files together
+fn by_directory_then_name(a: &Path, b: &Path) -> Ordering {
+    a.parent().cmp(&b.parent()).then_with(|| a.file_name().cmp(&b.file_name()))
+}
+
+/// Check that new files can be created in `dir` by creating and removing a probe file.
+///
+/// Catches read-only locations before a render is wasted on them, with the cause and what to
+/// do about it in a [`CertError::OutputDirUnusable`].
+pub fn check_writable(dir: impl AsRef<Path>) -> Result<()> {
+    let dir = dir.as_ref();
+    let probe = dir.join(format!(".certmaker_write_test_{}", std::process::id()));
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&probe)
+        .map_err(|e| unusable_output_dir(dir, &e))?;
+    std::fs::remove_file(&probe)
+        .io_context(|| format!("Failed to remove {}", probe.display()))
+}
+
+/// What [`check_output_dir`] found out about the directory certificates go to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputDirCheck {
+    /// Bytes free on its volume; `None` when the system doesn't say.
+    pub free_space: Option<u64>,
+    /// The network file system it is on, such as `cifs` or `nfs4`, where writing many small
+    /// files one at a time is slow. `None` on a local disk, or where this can't be told.
+    pub network: Option<String>,
+}
+
+/// Get `dir` ready for certificates: create it, write and delete a probe file, and find how
+/// much space is free and whether it is on a network share. Permission problems, a read-only
+/// volume, a full one, a share that isn't mounted and a path that is a file each fail with a
+/// [`CertError::OutputDirUnusable`] naming the cause and what to do about it.
+///
+/// ```
+/// use certificate_maker::error::CertError;
+/// use certificate_maker::files::check_output_dir;
+///
+/// let dir = std::env::temp_dir().join(format!("certmaker check output {}", std::process::id()));
+/// let check = check_output_dir(dir.join("2025 gala"))?;
+/// assert!(dir.join("2025 gala").is_dir());
+/// assert_eq!(check.network, None);
+///
+/// std::fs::write(dir.join("notes.txt"), "")?;
+/// match check_output_dir(dir.join("notes.txt")) {
+///     Err(CertError::OutputDirUnusable { reason, .. }) => assert!(reason.contains("is a file")),
+///     other => panic!("{:?}", other),
+/// }
+/// std::fs::remove_dir_all(&dir)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn check_output_dir(dir: impl AsRef<Path>) -> Result<OutputDirCheck> {
+    let dir = dir.as_ref();
+    if dir.exists() && !dir.is_dir() {
+        return Err(unusable_output_dir(dir, &std::io::Error::from(std::io::ErrorKind::NotADirectory)));
+    }
+    std::fs::create_dir_all(dir).map_err(|e| unusable_output_dir(dir, &e))?;
+    check_writable(dir)?;
+    Ok(OutputDirCheck {
+        free_space: fs2::available_space(dir).ok(),
+        network: network_file_system(dir),
+    })
+}
+
+// The cause of an output directory failing to be created or written, and what to do about it
+fn unusable_output_dir(dir: &Path, e: &std::io::Error) -> CertError {
+    use std::io::ErrorKind;
+    let (reason, hint) = match e.kind() {
+        ErrorKind::NotADirectory | ErrorKind::AlreadyExists => {
+            let file = dir.ancestors().find(|path| path.is_file()).unwrap_or(dir);
+            (format!("{} is a file, not a directory", file.display()), "Pick another directory, or move the file out of the way")
+        }
+        ErrorKind::PermissionDenied => ("permission denied".to_string(), "Pick a directory you can write to, or ask whoever manages it for write access"),
+        ErrorKind::ReadOnlyFilesystem => ("its volume is mounted read-only".to_string(), "Remount it read-write, or pick a directory on another volume"),
+        ErrorKind::StorageFull | ErrorKind::QuotaExceeded => ("its volume is full".to_string(), "Free up space there, or pick a directory on another volume"),
+        ErrorKind::NotFound => ("the path can't be reached".to_string(), "Check that the drive or network share is connected and mounted"),
+        _ => (e.to_string(), "Pick another directory"),
+    };
+    CertError::OutputDirUnusable { path: dir.to_path_buf(), reason, hint: hint.to_string() }
+}
+
+// File systems whose files live on another machine
+#[cfg(target_os = "linux")]
+const NETWORK_FILE_SYSTEMS: &[&str] = &["nfs", "nfs4", "cifs", "smb3", "smbfs", "9p", "afs", "ceph", "glusterfs", "fuse.sshfs", "fuse.rclone", "davfs", "fuse.davfs2"];
+
+/// The network file system `path` is on, from the longest mount point in `/proc/mounts`
+/// containing it on Linux, or a `\\server\share` path on Windows.
+pub fn network_file_system(path: impl AsRef<Path>) -> Option<String> {
+    let path = std::fs::canonicalize(path.as_ref()).unwrap_or_else(|_| path.as_ref().to_path_buf());
+    network_file_system_of(&path)
+}
+
+#[cfg(target_os = "linux")]
+fn network_file_system_of(path: &Path) -> Option<String> {
+    let mounts = std::fs::read_to_string("/proc/mounts").ok()?;
+    mounts.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let (_, mount_point, fs_type) = (fields.next()?, fields.next()?, fields.next()?);
+            Some((unescape_mount_point(mount_point), fs_type))
+        })
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.as_os_str().len())
+        .map(|(_, fs_type)| fs_type)
+        .filter(|fs_type| NETWORK_FILE_SYSTEMS.contains(fs_type))
+        .map(str::to_string)
+}
+
+#[cfg(windows)]
+fn network_file_system_of(path: &Path) -> Option<String> {
+    use std::path::{Component, Prefix};
+    match path.components().next() {
+        Some(Component::Prefix(prefix)) if matches!(prefix.kind(), Prefix::UNC(..) | Prefix::VerbatimUNC(..)) => Some("smb".to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn network_file_system_of(_path: &Path) -> Option<String> {
+    None
+}
+
+// /proc/mounts writes a space in a mount point as \040, a tab as \011 and so on
+#[cfg(target_os = "linux")]
+fn unescape_mount_point(field: &str) -> PathBuf {
+    let mut bytes = Vec::with_capacity(field.len());
+    let mut rest = field.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let octal = tail.get(..3).filter(|digits| digits.iter().all(|digit| (b'0'..=b'7').contains(digit)));
+        match octal {
+            Some(digits) if byte == b'\\' => {
+                bytes.push(digits.iter().fold(0u8, |value, digit| value.wrapping_mul(8) + (digit - b'0')));
+                rest = &tail[3..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// A directory for [`CertificateJob::stage_locally`](crate::job::CertificateJob::stage_locally)
+/// under the system's temporary directory, one for each process.
+pub fn local_staging_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("certmaker-staging-{}", std::process::id()))
+}
+
+/// Move a file, copying it and removing the original when `to` is on another volume.
+pub fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    std::fs::copy(from, to)?;
+    std::fs::remove_file(from)
+}
+
+/// Longest [`portable_file_name`] in bytes. File names can be 255 bytes on every common file
+/// system, which leaves room for a prefix like `certificate_`, a `_12` and an extension.
+pub const MAX_FILE_NAME_PART: usize = 200;
+
+/// `text` made safe as (part of) a file or directory name on Windows, macOS and Linux. Path
+/// separators, control characters and the characters Windows forbids (`: * ? " < > |`) become
+/// `_`; a device name Windows reserves, such as `CON` or `com1.txt`, gets a `_` after it; and
+/// the result is cut at a character boundary to [`MAX_FILE_NAME_PART`] bytes.
+///
+/// ```
+/// use certificate_maker::files::{MAX_FILE_NAME_PART, portable_file_name};
+///
+/// assert_eq!(portable_file_name("Ana: \"Ní\" <Dubh>?"), "Ana_ _Ní_ _Dubh__");
+/// assert_eq!(portable_file_name("../etc/passwd"), ".._etc_passwd");
+/// assert_eq!(portable_file_name("Con"), "Con_");
+/// assert_eq!(portable_file_name("nul.txt"), "nul_.txt");
+/// assert_eq!(portable_file_name(&"é".repeat(150)).len(), MAX_FILE_NAME_PART);
+/// ```
+pub fn portable_file_name(text: &str) -> String {
+    let mut name = String::with_capacity(text.len().min(MAX_FILE_NAME_PART));
+    for c in text.chars() {
+        let c = if c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') { '_' } else { c };
+        if name.len() + c.len_utf8() > MAX_FILE_NAME_PART {
+            break;
+        }
+        name.push(c);
+    }
+    if is_reserved_on_windows(&name) {
+        name.insert(name.find('.').unwrap_or(name.len()), '_');
+    }
+    name
+}
+
+// Device names Windows reserves whatever follows the first dot: CON, PRN, AUX, NUL, COM1-9, LPT1-9
+fn is_reserved_on_windows(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or_default().trim_end_matches(' ').to_ascii_uppercase();
+    match stem.as_str() {
+        "CON" | "PRN" | "AUX" | "NUL" => true,
+        _ => {
+            let mut chars = stem.chars();
+            let device: String = chars.by_ref().take(3).collect();
+            matches!(device.as_str(), "COM" | "LPT")
+                && matches!((chars.next(), chars.next()), (Some('1'..='9' | '¹' | '²' | '³'), None))
+        }
+    }
+}
+
+/// `path` with `_1`, `_2`, ... appended to its file stem, the first one `is_taken` rejects.
+///
+/// `certificates/certificate_Alice.png` becomes `certificates/certificate_Alice_1.png`.
+pub fn next_free_path(path: &Path, is_taken: impl Fn(&Path) -> bool) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default();
+    let mut counter = 1;
+    loop {
+        let mut file_name = OsString::from(stem);
+        file_name.push(format!("_{}", counter));
+        if let Some(extension) = path.extension() {
+            file_name.push(".");
+            file_name.push(extension);
+        }
+        let candidate = path.with_file_name(file_name);
+        if !is_taken(&candidate) {
+            return candidate;
+        }
+        counter += 1;
+    }
+}
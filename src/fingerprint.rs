@@ -0,0 +1,179 @@
+// src/fingerprint.rs
+//! A record of exactly how a certificate was made, for audits: the crate version and every
+//! resolved setting it was drawn with, hashed into a short label like `CM 0.1.0 · a1b2c3`.
+//!
+//! The settings are the ones the batch actually drew with, after normalizing, with the
+//! template and font named by file name and content hash. Nothing about the machine, the time
+//! or where the files lie goes in, so the same settings always give the same fingerprint, and
+//! a [deterministic](crate::job::CertificateJob::deterministic) run writes the same bytes.
+//!
+//! With [`FingerprintMode::Visible`] the label is drawn small in the bottom-right corner;
+//! [`FingerprintMode::Hidden`] leaves the certificate as it looks. Either way the whole
+//! [`Fingerprint`] goes into an iTXt chunk of each PNG ([`embed_fingerprint`]) and into the
+//! manifest, which notes the fingerprint of each certificate.
+//!
+//! ```
+//! use certificate_maker::fingerprint::{Fingerprint, embed_fingerprint, read_fingerprint};
+//! use serde_json::json;
+//!
+//! let fingerprint = Fingerprint::new(json!({ "font_size": 48.0, "color": "#000000" }));
+//! assert_eq!(fingerprint, Fingerprint::new(json!({ "font_size": 48.0, "color": "#000000" })));
+//! assert_ne!(fingerprint.hash, Fingerprint::new(json!({ "font_size": 50.0, "color": "#000000" })).hash);
+//! assert!(fingerprint.label().starts_with(&format!("CM {} · ", env!("CARGO_PKG_VERSION"))));
+//! assert_eq!(fingerprint.short_hash().len(), 6);
+//!
+//! let mut png = Vec::new();
+//! image::RgbaImage::new(4, 4).write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+//! let path = std::env::temp_dir().join(format!("certmaker-fingerprint-{}.png", std::process::id()));
+//! std::fs::write(&path, embed_fingerprint(&png, &fingerprint)?)?;
+//! assert_eq!(read_fingerprint(&path)?, Some(fingerprint));
+//! // Still an image like any other
+//! assert_eq!(image::open(&path)?.width(), 4);
+//! std::fs::remove_file(&path)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use std::io::BufReader;
+use std::path::Path;
+
+use image::{Rgba, RgbaImage};
+use png::text_metadata::{EncodableTextChunk, ITXtChunk};
+use rusttype::Font;
+use serde::{Deserialize, Serialize};
+
+use crate::editpng::{Anchor, SyntheticStyle, TextOptions, draw_text, missing_glyphs};
+use crate::error::{CertError, IoContext, Result};
+use crate::manifest::hash_bytes;
+
+/// Keyword of the PNG iTXt chunk holding the fingerprint as JSON.
+pub const PNG_KEYWORD: &str = "CertificateMaker fingerprint";
+
+/// Hex digits of the hash shown in the label.
+pub const SHORT_HASH_LEN: usize = 6;
+
+/// Smallest size the visible label is drawn at, in pixels.
+pub const MIN_LABEL_SIZE: f32 = 10.0;
+
+// PNG signature, then the IHDR chunk: length, type, 13 bytes of data and the CRC
+const PNG_HEADER_LEN: usize = 8 + 4 + 4 + 13 + 4;
+
+/// Whether certificates carry a fingerprint, and whether it shows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FingerprintMode {
+    /// No fingerprint anywhere.
+    #[default]
+    Off,
+    /// In the PNG metadata and the manifest only.
+    Hidden,
+    /// Also drawn small in the bottom-right corner.
+    Visible,
+}
+
+impl FingerprintMode {
+    pub const ALL: [FingerprintMode; 3] = [FingerprintMode::Off, FingerprintMode::Hidden, FingerprintMode::Visible];
+
+    /// Name used on the command line and in saved settings.
+    pub fn name(self) -> &'static str {
+        match self {
+            FingerprintMode::Off => "off",
+            FingerprintMode::Hidden => "hidden",
+            FingerprintMode::Visible => "visible",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<FingerprintMode> {
+        FingerprintMode::ALL.into_iter().find(|mode| mode.name().eq_ignore_ascii_case(name.trim()))
+    }
+
+    pub fn is_off(self) -> bool {
+        self == FingerprintMode::Off
+    }
+}
+
+/// The crate version and resolved settings a certificate was made with, and their hash.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    pub version: String,
+    /// SHA-256 of the version and the settings, as hex.
+    pub hash: String,
+    pub settings: serde_json::Value,
+}
+
+impl Fingerprint {
+    /// The fingerprint of `settings` made with this version of the crate.
+    pub fn new(settings: serde_json::Value) -> Self {
+        let version = env!("CARGO_PKG_VERSION").to_string();
+        // Objects keep their keys in order, so the same settings always serialize the same
+        let hashed = serde_json::json!({ "version": version, "settings": settings });
+        Fingerprint { hash: hash_bytes(hashed.to_string().as_bytes()), version, settings }
+    }
+
+    /// The first [`SHORT_HASH_LEN`] hex digits of the hash.
+    pub fn short_hash(&self) -> &str {
+        &self.hash[..SHORT_HASH_LEN.min(self.hash.len())]
+    }
+
+    /// What is drawn on the certificate, like `CM 0.1.0 · a1b2c3`.
+    pub fn label(&self) -> String {
+        format!("CM {} · {}", self.version, self.short_hash())
+    }
+}
+
+/// Draw `label` small in the bottom-right corner of `img`, in grey: a 80th of the
+/// certificate's height, and at least [`MIN_LABEL_SIZE`]. A font without the middle dot
+/// gets a hyphen instead.
+pub fn draw_fingerprint(img: &mut RgbaImage, font: &Font, label: &str) -> Result<()> {
+    let label = if missing_glyphs(font, label).is_empty() { label.to_string() } else { label.replace('·', "-") };
+    let (width, height) = img.dimensions();
+    let size = (height as f32 / 80.0).max(MIN_LABEL_SIZE);
+    let margin = size.round() as i32;
+    let options = TextOptions {
+        font,
+        size,
+        color: Rgba([128, 128, 128, 255]),
+        x: width as i32 - margin,
+        y: height as i32 - margin - (size / 2.0).round() as i32,
+        anchor: Anchor::CenterRight,
+        decorations: &[],
+        synthetic: SyntheticStyle::default(),
+        variation: None,
+        effect: None,
+    };
+    draw_text(img, &label, &options)?;
+    Ok(())
+}
+
+/// `png` with `fingerprint` added as an iTXt chunk right after the header, where readers
+/// find it before the pixels.
+pub fn embed_fingerprint(png: &[u8], fingerprint: &Fingerprint) -> Result<Vec<u8>> {
+    if png.len() < PNG_HEADER_LEN || &png[12..16] != b"IHDR" {
+        return Err(CertError::Fingerprint("the image isn't a PNG".to_string()));
+    }
+    let text = serde_json::to_string(fingerprint)
+        .map_err(|e| CertError::Fingerprint(e.to_string()))?;
+    let mut chunk = Vec::new();
+    ITXtChunk::new(PNG_KEYWORD, text).encode(&mut chunk)
+        .map_err(|e| CertError::Fingerprint(e.to_string()))?;
+    let mut embedded = Vec::with_capacity(png.len() + chunk.len());
+    embedded.extend_from_slice(&png[..PNG_HEADER_LEN]);
+    embedded.extend_from_slice(&chunk);
+    embedded.extend_from_slice(&png[PNG_HEADER_LEN..]);
+    Ok(embedded)
+}
+
+/// The fingerprint embedded in the PNG at `path`; `None` when it has none.
+pub fn read_fingerprint(path: impl AsRef<Path>) -> Result<Option<Fingerprint>> {
+    let path = path.as_ref();
+    let file = std::fs::File::open(path)
+        .io_context(|| format!("Failed to open {}", path.display()))?;
+    let reader = png::Decoder::new(BufReader::new(file)).read_info()
+        .map_err(|source| CertError::PngDecode { path: path.to_path_buf(), source })?;
+    let Some(chunk) = reader.info().utf8_text.iter().find(|chunk| chunk.keyword == PNG_KEYWORD) else {
+        return Ok(None);
+    };
+    let text = chunk.get_text()
+        .map_err(|source| CertError::PngDecode { path: path.to_path_buf(), source })?;
+    serde_json::from_str(&text)
+        .map(Some)
+        .map_err(|source| CertError::Json { context: format!("Unreadable fingerprint in {}", path.display()), source })
+}
@@ -0,0 +1,233 @@
+// src/fontcache.rs
+//! Font files read and parsed once, shared by every job that draws with them.
+//!
+//! [`load_font`](crate::editpng::load_font) and [`load_font_data`](crate::editpng::load_font_data)
+//! go through the [`shared`] cache, so a server, a watch loop or successive interactive runs
+//! don't reread a large CJK font for every batch. Fonts are keyed by their canonical path and
+//! remember the file's size and modification time: a file replaced since it was read is read
+//! again. The cache holds at most its capacity in bytes and drops the least recently used
+//! fonts beyond it; a font larger than the whole capacity is loaded but not kept.
+//!
+//! One lock guards the cache, and a font is read while holding it, so jobs on several threads
+//! asking for the same font read the file once.
+//!
+//! ```
+//! use certificate_maker::fontcache::FontCache;
+//!
+//! let path = std::env::temp_dir().join(format!("certmaker-fontcache-{}.ttf", std::process::id()));
+//! std::fs::copy("tests/fixtures/DejaVuSans-Latin.ttf", &path)?;
+//! let cache = FontCache::new(64 * 1024 * 1024);
+//!
+//! std::thread::scope(|scope| {
+//!     for _ in 0..4 {
+//!         scope.spawn(|| cache.font(&path).unwrap());
+//!     }
+//! });
+//! assert_eq!((cache.stats().hits, cache.stats().misses), (3, 1));
+//!
+//! // A replaced file is read again
+//! std::fs::write(&path, std::fs::read("assets/DejaVuSans.ttf")?)?;
+//! let glyphs = cache.font(&path)?.glyph_count();
+//! assert!(glyphs > 1000);
+//! assert_eq!(cache.stats().misses, 2);
+//!
+//! cache.clear();
+//! assert_eq!(cache.stats().fonts, 0);
+//! std::fs::remove_file(&path)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use rusttype::Font;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use crate::error::{CertError, IoContext, Result};
+
+/// Bytes the [`shared`] cache keeps before it drops fonts: room for a few large CJK fonts.
+pub const DEFAULT_CAPACITY: u64 = 256 * 1024 * 1024;
+
+static SHARED: LazyLock<FontCache> = LazyLock::new(|| FontCache::new(DEFAULT_CAPACITY));
+
+/// The cache every font load of this process goes through.
+pub fn shared() -> &'static FontCache {
+    &SHARED
+}
+
+/// How full a [`FontCache`] is and how often it answered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FontCacheStats {
+    /// Fonts held.
+    pub fonts: usize,
+    /// Bytes they take, file contents and parsed fonts together.
+    pub bytes: u64,
+    pub capacity: u64,
+    /// Loads answered without reading the file.
+    pub hits: u64,
+    /// Loads that read the file: the first of each font, and again after it changed or was
+    /// dropped.
+    pub misses: u64,
+}
+
+/// Font files by path, read once and parsed once; safe to share between threads.
+pub struct FontCache {
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    fonts: HashMap<PathBuf, CachedFont>,
+    capacity: u64,
+    bytes: u64,
+    // Advances on every load; the font used longest ago is dropped first
+    clock: u64,
+    hits: u64,
+    misses: u64,
+}
+
+struct CachedFont {
+    stamp: FileStamp,
+    data: Vec<u8>,
+    // Parsed on the first load_font, not by load_font_data
+    font: Option<Font<'static>>,
+    last_used: u64,
+}
+
+// What tells a replaced file apart without reading it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl CachedFont {
+    fn bytes(&self) -> u64 {
+        let parsed = if self.font.is_some() { self.data.len() } else { 0 };
+        (self.data.len() + parsed) as u64
+    }
+}
+
+impl FontCache {
+    /// An empty cache holding at most `capacity` bytes.
+    pub fn new(capacity: u64) -> FontCache {
+        FontCache {
+            inner: Mutex::new(Inner { fonts: HashMap::new(), capacity, bytes: 0, clock: 0, hits: 0, misses: 0 }),
+        }
+    }
+
+    /// The contents of the font file at `path`, like `fs::read`.
+    pub fn data(&self, path: impl AsRef<Path>) -> Result<Vec<u8>> {
+        let mut inner = self.lock();
+        let key = inner.load(path.as_ref())?;
+        let data = inner.fonts.get(&key).map(|cached| cached.data.clone());
+        inner.evict(&key);
+        Ok(data.unwrap_or_default())
+    }
+
+    /// The font file at `path`, parsed. Cloning a `Font` only shares it, so every job gets
+    /// the same parsed font.
+    pub fn font(&self, path: impl AsRef<Path>) -> Result<Font<'static>> {
+        let path = path.as_ref();
+        let mut inner = self.lock();
+        let key = inner.load(path)?;
+        let cached = inner.fonts.get_mut(&key).expect("font was just loaded");
+        let (font, parsed_bytes) = match &cached.font {
+            Some(font) => (font.clone(), 0),
+            None => {
+                let font = Font::try_from_vec(cached.data.clone()).ok_or_else(|| CertError::FontParse(path.to_path_buf()))?;
+                cached.font = Some(font.clone());
+                (font, cached.data.len() as u64)
+            }
+        };
+        inner.bytes += parsed_bytes;
+        inner.evict(&key);
+        Ok(font)
+    }
+
+    /// Forget the font at `path`, so the next load reads it again.
+    pub fn invalidate(&self, path: impl AsRef<Path>) {
+        let mut inner = self.lock();
+        let key = canonical(path.as_ref());
+        inner.remove(&key);
+    }
+
+    /// Forget every font.
+    pub fn clear(&self) {
+        let mut inner = self.lock();
+        inner.fonts.clear();
+        inner.bytes = 0;
+    }
+
+    /// Hold at most `capacity` bytes from now on, dropping fonts beyond it right away.
+    pub fn set_capacity(&self, capacity: u64) {
+        let mut inner = self.lock();
+        inner.capacity = capacity;
+        inner.evict(Path::new(""));
+    }
+
+    pub fn stats(&self) -> FontCacheStats {
+        let inner = self.lock();
+        FontCacheStats { fonts: inner.fonts.len(), bytes: inner.bytes, capacity: inner.capacity, hits: inner.hits, misses: inner.misses }
+    }
+
+    // A panic elsewhere while holding the lock leaves the cache consistent: every change to
+    // it is made after the file was read
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Inner {
+    // Make sure `path` is cached and current, and return its key
+    fn load(&mut self, path: &Path) -> Result<PathBuf> {
+        let key = canonical(path);
+        let context = || format!("Failed to read font file: {}", path.display());
+        let stamp = FileStamp::of(&key).io_context(context)?;
+        self.clock += 1;
+        if let Some(cached) = self.fonts.get_mut(&key)
+            && cached.stamp == stamp {
+            cached.last_used = self.clock;
+            self.hits += 1;
+            return Ok(key);
+        }
+
+        let data = fs::read(&key).io_context(context)?;
+        self.misses += 1;
+        self.remove(&key);
+        let cached = CachedFont { stamp, data, font: None, last_used: self.clock };
+        self.bytes += cached.bytes();
+        self.fonts.insert(key.clone(), cached);
+        Ok(key)
+    }
+
+    fn remove(&mut self, key: &Path) {
+        if let Some(cached) = self.fonts.remove(key) {
+            self.bytes -= cached.bytes();
+        }
+    }
+
+    // Drop the least recently used fonts until the cache fits, `just_used` last of all
+    fn evict(&mut self, just_used: &Path) {
+        while self.bytes > self.capacity {
+            let oldest = self.fonts.iter()
+                .min_by_key(|(key, cached)| (key.as_path() == just_used, cached.last_used))
+                .map(|(key, _)| key.clone());
+            match oldest {
+                Some(key) => self.remove(&key),
+                None => break,
+            }
+        }
+    }
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> std::io::Result<FileStamp> {
+        let metadata = fs::metadata(path)?;
+        Ok(FileStamp { len: metadata.len(), modified: metadata.modified().ok() })
+    }
+}
+
+// The same file by any relative path or symlink shares one entry
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
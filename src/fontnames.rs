@@ -0,0 +1,164 @@
+// src/fontnames.rs
+//! Fonts by the family and style names in their `name` table, so "Playfair Display Bold"
+//! finds `PlayfairDisplay-Bold.ttf`. Filenames keep working and are always tried first.
+//!
+//! ```no_run
+//! use certificate_maker::fontnames::{find_font, list_font_faces};
+//!
+//! for face in list_font_faces("assets")? {
+//!     println!("{}", face.label());
+//! }
+//! let bold = find_font("assets", "dejavu sans bold")?;
+//! assert_eq!(bold, std::path::Path::new("assets/DejaVuSans-Bold.ttf"));
+//! # Ok::<(), certificate_maker::error::CertError>(())
+//! ```
+use std::path::{Path, PathBuf};
+use ttf_parser::{Face, name_id};
+
+use crate::editpng::{BUILTIN_FONT, list_available_fonts, load_font_data};
+use crate::error::{CertError, Result};
+
+// Styles a bare family name stands for
+const REGULAR_STYLES: [&str; 4] = ["regular", "book", "normal", "roman"];
+const MAX_SUGGESTIONS: usize = 3;
+
+/// A font file and the names it gives itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontFace {
+    /// Relative to the fonts directory, or [`BUILTIN_FONT`].
+    pub file: PathBuf,
+    /// Empty when the file has no readable `name` table.
+    pub family: String,
+    pub style: String,
+}
+
+impl FontFace {
+    /// `Playfair Display — Bold (PlayfairDisplay-Bold.ttf)`, or just the filename for a font
+    /// without names.
+    pub fn label(&self) -> String {
+        if self.family.is_empty() {
+            self.file.display().to_string()
+        } else {
+            format!("{} — {} ({})", self.family, self.style, self.file.display())
+        }
+    }
+
+    /// `Playfair Display Bold`.
+    pub fn full_name(&self) -> String {
+        format!("{} {}", self.family, self.style)
+    }
+}
+
+/// Family and style of a font, preferring the typographic names that group every weight of a
+/// family under one name.
+pub fn face_names(font_data: &[u8]) -> Option<(String, String)> {
+    let face = Face::from_slice(font_data, 0).ok()?;
+    let style = name(&face, name_id::TYPOGRAPHIC_SUBFAMILY)
+        .or_else(|| name(&face, name_id::SUBFAMILY))
+        .unwrap_or_else(|| "Regular".to_string());
+    Some((family_name(&face)?, style))
+}
+
+// The typographic family when the font names one, otherwise the legacy family
+pub(crate) fn family_name(face: &Face) -> Option<String> {
+    name(face, name_id::TYPOGRAPHIC_FAMILY).or_else(|| name(face, name_id::FAMILY))
+}
+
+fn name(face: &Face, id: u16) -> Option<String> {
+    face.names().into_iter()
+        .filter(|name| name.name_id == id)
+        .find_map(|name| name.to_string())
+}
+
+/// Every font [`list_available_fonts`] finds, with its names.
+pub fn list_font_faces(fonts_dir: impl AsRef<Path>) -> Result<Vec<FontFace>> {
+    let fonts_dir = fonts_dir.as_ref();
+    Ok(list_available_fonts(fonts_dir)?.into_iter()
+        .map(|file| {
+            let path = if file == Path::new(BUILTIN_FONT) { file.clone() } else { fonts_dir.join(&file) };
+            let (family, style) = load_font_data(&path).ok()
+                .and_then(|data| face_names(&data))
+                .unwrap_or_default();
+            FontFace { file, family, style }
+        })
+        .collect())
+}
+
+/// Resolve a font the way a person names it. A path that exists, a file in `fonts_dir` or the
+/// built-in font's name is returned as it is; otherwise `query` is matched, ignoring case and
+/// spacing, against "family style" and then against a family alone, which means its regular
+/// style. Several fonts with the same names are an [`CertError::AmbiguousFont`] listing them
+/// all, and no match is a [`CertError::UnknownFont`] with the closest names.
+pub fn find_font(fonts_dir: impl AsRef<Path>, query: &str) -> Result<PathBuf> {
+    let fonts_dir = fonts_dir.as_ref();
+    if query == BUILTIN_FONT || Path::new(query).exists() {
+        return Ok(PathBuf::from(query));
+    }
+    if fonts_dir.join(query).exists() {
+        return Ok(fonts_dir.join(query));
+    }
+
+    let faces = list_font_faces(fonts_dir)?;
+    let wanted = normalized(query);
+    let mut candidates: Vec<&FontFace> = faces.iter()
+        .filter(|face| !face.family.is_empty() && normalized(&face.full_name()) == wanted)
+        .collect();
+    if candidates.is_empty() {
+        let family: Vec<&FontFace> = faces.iter().filter(|face| normalized(&face.family) == wanted).collect();
+        let regular: Vec<&FontFace> = family.iter().copied()
+            .filter(|face| REGULAR_STYLES.contains(&face.style.to_lowercase().as_str()))
+            .collect();
+        candidates = if regular.is_empty() { family } else { regular };
+    }
+
+    match candidates.as_slice() {
+        [only] if only.file == Path::new(BUILTIN_FONT) => Ok(only.file.clone()),
+        [only] => Ok(fonts_dir.join(&only.file)),
+        [] => Err(CertError::UnknownFont { query: query.to_string(), suggestions: suggestions(&faces, &wanted) }),
+        several => Err(CertError::AmbiguousFont {
+            query: query.to_string(),
+            candidates: several.iter().map(|face| face.label()).collect(),
+        }),
+    }
+}
+
+// Lowercase words separated by single spaces, so "PLAYFAIR  display-bold" and
+// "Playfair Display Bold" compare equal
+fn normalized(name: &str) -> String {
+    name.split(|c: char| c.is_whitespace() || c == '-' || c == '_')
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Full names that contain the query or are a few typos away from it, closest first
+fn suggestions(faces: &[FontFace], wanted: &str) -> Vec<String> {
+    let mut scored: Vec<(usize, String)> = faces.iter()
+        .filter(|face| !face.family.is_empty())
+        .filter_map(|face| {
+            let full_name = normalized(&face.full_name());
+            let distance = edit_distance(&full_name, wanted).min(edit_distance(&normalized(&face.family), wanted));
+            let close = distance <= (wanted.chars().count() / 4).max(2);
+            (close || full_name.contains(wanted)).then(|| (distance, face.full_name()))
+        })
+        .collect();
+    scored.sort();
+    scored.dedup_by(|a, b| a.1 == b.1);
+    scored.into_iter().take(MAX_SUGGESTIONS).map(|(_, name)| format!("'{}'", name)).collect()
+}
+
+// Levenshtein distance over characters
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
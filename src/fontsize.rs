@@ -0,0 +1,207 @@
+// src/fontsize.rs
+//! Whether a font size suits the template. A name taller than a good part of the template, or
+//! too small to read once printed, makes a useless certificate that nothing else warns about.
+//!
+//! The text is measured with [`measure_text`], as it is drawn. The smallest legible size is
+//! [`FontSizeLimits::min_points`] typographic points at the template's DPI when the file
+//! records one (see [`image_dpi`]), and [`FontSizeLimits::min_pixels`] when it doesn't, as for
+//! most web templates.
+//!
+//! ```
+//! use certificate_maker::editpng::load_font;
+//! use certificate_maker::fontsize::{FontSizeIssue, FontSizeLimits, check_font_size};
+//!
+//! let font = load_font("tests/fixtures/DejaVuSans-Latin.ttf")?;
+//! let limits = FontSizeLimits::default();
+//! // 200 pixels on a 400-pixel-high template is half of it
+//! let check = check_font_size(&font, "Ada Lovelace", 200.0, (600, 400), None, &limits)?;
+//! assert_eq!(check.issue, Some(FontSizeIssue::TooLarge));
+//! // The size suggested instead is within the limits
+//! let suggested = check.suggested_size.unwrap();
+//! assert!(check_font_size(&font, "Ada Lovelace", suggested, (600, 400), None, &limits)?.is_ok());
+//!
+//! // 12 pixels is under 3 points at 300 DPI
+//! let check = check_font_size(&font, "Ada Lovelace", 12.0, (2480, 3508), Some(300.0), &limits)?;
+//! assert_eq!(check.issue, Some(FontSizeIssue::TooSmall));
+//! assert_eq!(check.points.map(f32::round), Some(3.0));
+//! assert!(check_font_size(&font, "Ada Lovelace", 40.0, (600, 400), None, &limits)?.is_ok());
+//! # Ok::<(), certificate_maker::error::CertError>(())
+//! ```
+use std::path::Path;
+
+use rusttype::Font;
+use serde::Serialize;
+
+use crate::dpi::{image_dpi, points_to_pixels};
+use crate::error::{CertError, Result};
+use crate::measure::measure_text;
+use crate::orientation::oriented_dimensions;
+
+/// Largest share of the template's height the text may take: 40%.
+pub const MAX_HEIGHT_FRACTION: f32 = 0.4;
+
+/// Smallest legible size in print, in points, for templates that record their DPI.
+pub const MIN_POINTS: f32 = 6.0;
+
+/// Smallest legible size in pixels, for templates that don't record a DPI.
+pub const MIN_PIXELS: f32 = 10.0;
+
+const POINTS_PER_INCH: f32 = 72.0;
+
+/// What [`check_font_size`] counts as too large and too small.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct FontSizeLimits {
+    /// Share of the template's height, between 0 and 1.
+    pub max_height_fraction: f32,
+    pub min_points: f32,
+    pub min_pixels: f32,
+}
+
+impl Default for FontSizeLimits {
+    fn default() -> Self {
+        FontSizeLimits { max_height_fraction: MAX_HEIGHT_FRACTION, min_points: MIN_POINTS, min_pixels: MIN_PIXELS }
+    }
+}
+
+impl FontSizeLimits {
+    /// A share of the template's height, as a percentage like `40%` or a fraction like `0.4`.
+    pub fn parse_height_fraction(text: &str) -> Option<f32> {
+        let text = text.trim();
+        let fraction = match text.strip_suffix('%') {
+            Some(percent) => percent.trim().parse::<f32>().ok()? / 100.0,
+            None => text.parse::<f32>().ok()?,
+        };
+        (fraction > 0.0 && fraction <= 1.0).then_some(fraction)
+    }
+
+    /// The problem with these limits, if any.
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if !(self.max_height_fraction > 0.0 && self.max_height_fraction <= 1.0) {
+            return Err(format!("The largest text height must be more than 0% and at most 100% of the template, got {}%", self.max_height_fraction * 100.0));
+        }
+        if !self.min_points.is_finite() || self.min_points < 0.0 || !self.min_pixels.is_finite() || self.min_pixels < 0.0 {
+            return Err(format!("The smallest legible size must be zero or more, got {} pt and {} px", self.min_points, self.min_pixels));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FontSizeIssue {
+    /// Taller than [`FontSizeLimits::max_height_fraction`] of the template.
+    TooLarge,
+    /// Below the legibility floor.
+    TooSmall,
+}
+
+/// What [`check_font_size`] measured, and the size to use instead when it found a problem.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FontSizeCheck {
+    pub font_size: f32,
+    /// Height of the lines the text is set in, in pixels.
+    pub text_height: i32,
+    pub template_height: u32,
+    /// The template's resolution; `None` when the file records none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dpi: Option<f32>,
+    /// `text_height` in points at `dpi`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub points: Option<f32>,
+    /// The legibility floor, in pixels.
+    pub min_height: f32,
+    /// The largest height allowed, in pixels.
+    pub max_height: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issue: Option<FontSizeIssue>,
+    /// A font size inside the limits, rounded to a whole pixel; `None` when there's no problem.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggested_size: Option<f32>,
+}
+
+impl FontSizeCheck {
+    pub fn is_ok(&self) -> bool {
+        self.issue.is_none()
+    }
+
+    /// The measured height, in points too when the DPI is known.
+    pub fn describe_height(&self) -> String {
+        match (self.points, self.dpi) {
+            (Some(points), Some(dpi)) => format!("{} px tall ({:.1} pt at {:.0} DPI)", self.text_height, points, dpi),
+            _ => format!("{} px tall (the template records no DPI)", self.text_height),
+        }
+    }
+
+    /// What is wrong, with the size to use instead, for reports and logs; `None` when nothing is.
+    pub fn message(&self) -> Option<String> {
+        let suggested = self.suggested_size.unwrap_or(self.font_size);
+        let share = self.text_height as f32 / self.template_height.max(1) as f32 * 100.0;
+        match self.issue? {
+            FontSizeIssue::TooLarge => Some(format!(
+                "font size {} makes the text {}, {:.0}% of the {} px high template; size {} keeps it within {:.0}%",
+                self.font_size, self.describe_height(), share, self.template_height, suggested,
+                self.max_height / self.template_height.max(1) as f32 * 100.0)),
+            FontSizeIssue::TooSmall => Some(format!(
+                "font size {} makes the text {}, below the legibility floor of {:.0} px; size {} or more reads",
+                self.font_size, self.describe_height(), self.min_height, suggested)),
+        }
+    }
+}
+
+/// Measure `text` set at `font_size` and check it against `limits` on a template of
+/// `template_size` pixels recording `dpi`.
+pub fn check_font_size(font: &Font, text: &str, font_size: f32, template_size: (u32, u32), dpi: Option<f32>, limits: &FontSizeLimits) -> Result<FontSizeCheck> {
+    limits.validate().map_err(|problem| CertError::InvalidJob(vec![problem]))?;
+    // Empty text still takes a line's height
+    let text = if text.trim().is_empty() { "Hg" } else { text };
+    let metrics = measure_text(font, text, font_size, 0.0, None)?;
+    let text_height = metrics.height();
+    let template_height = template_size.1;
+    let dpi = dpi.filter(|dpi| dpi.is_finite() && *dpi > 0.0);
+    let min_height = match dpi {
+        Some(dpi) => points_to_pixels(limits.min_points as f64, dpi) as f32,
+        None => limits.min_pixels,
+    };
+    let max_height = template_height as f32 * limits.max_height_fraction;
+
+    // The height scales with the size, so the size that gives the height aimed at is about in
+    // proportion; heights are rounded up to whole pixels, so it is measured again to be sure
+    let height = text_height.max(1) as f32;
+    let measured = |size: f32| measure_text(font, text, size, 0.0, None).map(|metrics| metrics.height() as f32);
+    let (issue, suggested_size) = if height > max_height {
+        let mut size = (font_size * max_height / height).floor().max(1.0);
+        while size > 1.0 && measured(size)? > max_height {
+            size -= 1.0;
+        }
+        (Some(FontSizeIssue::TooLarge), Some(size))
+    } else if height < min_height {
+        let mut size = (font_size * min_height / height).ceil();
+        while measured(size)? < min_height {
+            size += 1.0;
+        }
+        (Some(FontSizeIssue::TooSmall), Some(size))
+    } else {
+        (None, None)
+    };
+    Ok(FontSizeCheck {
+        font_size,
+        text_height,
+        template_height,
+        dpi,
+        points: dpi.map(|dpi| text_height as f32 / dpi * POINTS_PER_INCH),
+        min_height,
+        max_height,
+        issue,
+        suggested_size,
+    })
+}
+
+/// [`check_font_size`] on the template at `template`, with its size and recorded DPI.
+pub fn check_template_font_size(template: impl AsRef<Path>, font: &Font, text: &str, font_size: f32, limits: &FontSizeLimits) -> Result<FontSizeCheck> {
+    let template = template.as_ref();
+    let dimensions = oriented_dimensions(template)
+        .map_err(|source| CertError::ImageDecode { path: template.to_path_buf(), source })?;
+    // A DPI that can't be read is treated as none recorded
+    let dpi = image_dpi(template).ok().flatten();
+    check_font_size(font, text, font_size, dimensions, dpi, limits)
+}
@@ -0,0 +1,261 @@
+// src/googlefonts.rs
+//! Downloading font families from Google Fonts into the fonts directory.
+//!
+//! Only compiled with the `remote` feature. A family's files, weights, axes and license are
+//! read from its `METADATA.pb` in the [google/fonts](https://github.com/google/fonts)
+//! repository, the source Google Fonts itself is built from.
+//!
+//! ```no_run
+//! use certificate_maker::googlefonts::{download_fonts, fetch_family};
+//!
+//! let family = fetch_family("Playfair Display")?;
+//! println!("{} is licensed under {}", family.name, family.license_name());
+//! let bold: Vec<_> = family.fonts.iter().filter(|font| font.weight == 700).collect();
+//! for download in download_fonts(&family, &bold, "assets")? {
+//!     println!("{}", download.path.display());
+//! }
+//! # Ok::<(), certificate_maker::error::CertError>(())
+//! ```
+use std::path::{Path, PathBuf};
+
+use crate::error::{CertError, IoContext, Result};
+use crate::remote::fetch;
+
+const REPOSITORY: &str = "https://raw.githubusercontent.com/google/fonts/main";
+// Where google/fonts keeps families, by license, and each directory's license file
+const LICENSE_DIRECTORIES: [(&str, &str); 3] = [("ofl", "OFL.txt"), ("apache", "LICENSE.txt"), ("ufl", "UFL.txt")];
+/// Largest font file that will be downloaded; CJK families run to tens of MB.
+pub const MAX_FONT_BYTES: u64 = 60_000_000;
+const MAX_METADATA_BYTES: u64 = 1_000_000;
+
+/// One file of a family, at one weight and style. Variable families list their file once per
+/// named weight.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FamilyFont {
+    /// `normal` or `italic`.
+    pub style: String,
+    pub weight: u16,
+    pub filename: String,
+}
+
+impl FamilyFont {
+    /// Variable font files carry their axes in the name: `Inter[opsz,wght].ttf`.
+    pub fn is_variable(&self) -> bool {
+        self.filename.contains('[')
+    }
+}
+
+/// A variable family's axis range.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FamilyAxis {
+    pub tag: String,
+    pub min: f32,
+    pub max: f32,
+}
+
+/// What [`fetch_family`] learned about a family.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FamilyMetadata {
+    pub name: String,
+    /// As `METADATA.pb` spells it: `OFL`, `APACHE2` or `UFL`.
+    pub license: String,
+    pub fonts: Vec<FamilyFont>,
+    /// Empty for families of static fonts.
+    pub axes: Vec<FamilyAxis>,
+    // Directory in the repository, such as `ofl/playfairdisplay`
+    directory: String,
+    license_file: &'static str,
+}
+
+impl FamilyMetadata {
+    /// The license's full name.
+    pub fn license_name(&self) -> &str {
+        match self.license.as_str() {
+            "OFL" => "SIL Open Font License 1.1",
+            "APACHE2" => "Apache License 2.0",
+            "UFL" => "Ubuntu Font License 1.0",
+            other => other,
+        }
+    }
+
+    /// Where to read the license before downloading.
+    pub fn license_url(&self) -> String {
+        format!("{}/{}/{}", REPOSITORY, self.directory, self.license_file)
+    }
+
+    /// Whether the family only ships variable fonts, one file covering every weight.
+    pub fn is_variable_only(&self) -> bool {
+        !self.fonts.is_empty() && self.fonts.iter().all(FamilyFont::is_variable)
+    }
+
+    fn file_url(&self, filename: &str) -> String {
+        format!("{}/{}/{}", REPOSITORY, self.directory, filename)
+    }
+}
+
+/// Directory name google/fonts files a family under: lowercase letters and digits only.
+///
+/// ```
+/// use certificate_maker::googlefonts::family_slug;
+///
+/// assert_eq!(family_slug("Playfair Display"), "playfairdisplay");
+/// assert_eq!(family_slug("M PLUS 1p"), "mplus1p");
+/// ```
+pub fn family_slug(family: &str) -> String {
+    family.chars().filter(char::is_ascii_alphanumeric).map(|c| c.to_ascii_lowercase()).collect()
+}
+
+/// Parse the name, license, fonts and axes out of a family's `METADATA.pb` (protobuf text
+/// format); everything else in it is skipped.
+///
+/// ```
+/// use certificate_maker::googlefonts::parse_metadata;
+///
+/// let metadata = parse_metadata(r#"
+/// name: "Inter"
+/// license: "OFL"
+/// fonts {
+///   name: "Inter"
+///   style: "normal"
+///   weight: 400
+///   filename: "Inter[opsz,wght].ttf"
+/// }
+/// axes {
+///   tag: "wght"
+///   min_value: 100.0
+///   max_value: 900.0
+/// }
+/// "#)?;
+/// assert_eq!(metadata.name, "Inter");
+/// assert_eq!(metadata.fonts[0].weight, 400);
+/// assert!(metadata.is_variable_only());
+/// assert_eq!(metadata.axes[0].max, 900.0);
+/// # Ok::<(), certificate_maker::error::CertError>(())
+/// ```
+pub fn parse_metadata(text: &str) -> Result<FamilyMetadata> {
+    let mut metadata = FamilyMetadata {
+        name: String::new(),
+        license: String::new(),
+        fonts: Vec::new(),
+        axes: Vec::new(),
+        directory: String::new(),
+        license_file: LICENSE_DIRECTORIES[0].1,
+    };
+    // Blocks being read, innermost last, with their fields; top-level fields are read as
+    // they come, and blocks nested in other blocks are skipped
+    let mut blocks: Vec<(&str, Vec<(&str, &str)>)> = Vec::new();
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+        if let Some(name) = line.strip_suffix('{') {
+            blocks.push((name.trim(), Vec::new()));
+            continue;
+        }
+        if line == "}" {
+            let Some((name, fields)) = blocks.pop() else {
+                continue;
+            };
+            if !blocks.is_empty() {
+                continue;
+            }
+            let field = |key: &str| fields.iter().find(|(field, _)| *field == key).map(|(_, value)| *value);
+            match name {
+                "fonts" => {
+                    if let (Some(filename), Some(weight)) = (field("filename"), field("weight").and_then(|w| w.parse().ok())) {
+                        metadata.fonts.push(FamilyFont {
+                            style: field("style").unwrap_or("normal").to_string(),
+                            weight,
+                            filename: filename.to_string(),
+                        });
+                    }
+                }
+                "axes" => {
+                    let number = |key: &str| field(key).and_then(|value| value.parse().ok());
+                    if let (Some(tag), Some(min), Some(max)) = (field("tag"), number("min_value"), number("max_value")) {
+                        metadata.axes.push(FamilyAxis { tag: tag.to_string(), min, max });
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim().trim_matches('"');
+        match blocks.last_mut() {
+            Some((_, fields)) => fields.push((key.trim(), value)),
+            None if key == "name" => metadata.name = value.to_string(),
+            None if key == "license" => metadata.license = value.to_string(),
+            None => {}
+        }
+    }
+
+    if metadata.name.is_empty() || metadata.fonts.is_empty() {
+        return Err(CertError::FontMetadata("it names no family or no font files".to_string()));
+    }
+    Ok(metadata)
+}
+
+/// Look a family up by name, trying each license directory of google/fonts in turn.
+pub fn fetch_family(family: &str) -> Result<FamilyMetadata> {
+    let slug = family_slug(family);
+    if slug.is_empty() {
+        return Err(CertError::UnknownFontFamily(family.to_string()));
+    }
+    for (directory, license_file) in LICENSE_DIRECTORIES {
+        let url = format!("{}/{}/{}/METADATA.pb", REPOSITORY, directory, slug);
+        match fetch(&url, &url, MAX_METADATA_BYTES) {
+            Ok(body) => {
+                let mut metadata = parse_metadata(&String::from_utf8_lossy(&body))?;
+                metadata.directory = format!("{}/{}", directory, slug);
+                metadata.license_file = license_file;
+                return Ok(metadata);
+            }
+            Err(CertError::RemoteStatus { status, .. }) if status == reqwest::StatusCode::NOT_FOUND => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(CertError::UnknownFontFamily(family.to_string()))
+}
+
+/// A font file [`download_fonts`] put in the fonts directory.
+#[derive(Debug, Clone)]
+pub struct FontDownload {
+    pub path: PathBuf,
+    /// Already there and loading, so it wasn't downloaded again.
+    pub cached: bool,
+}
+
+/// Download each distinct file of `fonts` into `fonts_dir`. A file that already exists there
+/// and parses is kept; every download must parse as a font before it is saved.
+pub fn download_fonts(family: &FamilyMetadata, fonts: &[&FamilyFont], fonts_dir: impl AsRef<Path>) -> Result<Vec<FontDownload>> {
+    let fonts_dir = fonts_dir.as_ref();
+    std::fs::create_dir_all(fonts_dir)
+        .io_context(|| format!("Failed to create directory: {}", fonts_dir.display()))?;
+
+    let mut downloads: Vec<FontDownload> = Vec::new();
+    for font in fonts {
+        let path = fonts_dir.join(&font.filename);
+        if downloads.iter().any(|download| download.path == path) {
+            continue;
+        }
+        let loads = |path: &Path| std::fs::read(path).is_ok_and(|data| ttf_parser::Face::from_slice(&data, 0).is_ok());
+        if loads(&path) {
+            downloads.push(FontDownload { path, cached: true });
+            continue;
+        }
+
+        let url = family.file_url(&font.filename);
+        let data = fetch(&url, &url, MAX_FONT_BYTES)?;
+        if ttf_parser::Face::from_slice(&data, 0).is_err() {
+            return Err(CertError::FontParse(PathBuf::from(url)));
+        }
+        // Written next to the destination first, so an interrupted download leaves no half font
+        let partial = path.with_extension("part");
+        std::fs::write(&partial, &data)
+            .io_context(|| format!("Failed to write {}", partial.display()))?;
+        std::fs::rename(&partial, &path)
+            .io_context(|| format!("Failed to move {} to {}", partial.display(), path.display()))?;
+        downloads.push(FontDownload { path, cached: false });
+    }
+    Ok(downloads)
+}
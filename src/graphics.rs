@@ -0,0 +1,410 @@
+// src/graphics.rs
+//! Graphic fields: text, images, barcodes and tables drawn on every certificate besides the name,
+//! each from a data template filled in per row, such as a barcode of the recipient's ticket
+//! number or their photo.
+//!
+//! ```
+//! use certificate_maker::barcode::BarcodeStyle;
+//! use certificate_maker::graphics::{GraphicField, GraphicKind};
+//! use certificate_maker::job::Anchor;
+//! use certificate_maker::source::Record;
+//!
+//! let field = GraphicField {
+//!     kind: GraphicKind::Code128(BarcodeStyle::default()),
+//!     data: "TICKET-{Ticket}".to_string(),
+//!     x: 300,
+//!     y: 250,
+//!     anchor: Anchor::Center,
+//! };
+//! let record = Record {
+//!     name: "Ada Lovelace".to_string(),
+//!     fields: vec![("ticket".to_string(), "0042".to_string())],
+//! };
+//! assert_eq!(field.fill("Ada Lovelace", Some(&record))?, "TICKET-0042");
+//! assert!(field.fill("Ada Lovelace", None).is_err());
+//! # Ok::<(), certificate_maker::error::CertError>(())
+//! ```
+use image::imageops::{self, FilterType};
+use image::RgbaImage;
+use rusttype::Font;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::barcode::{BarcodeStyle, encode_code128, render_code128};
+use crate::editpng::{Anchor, ELLIPSIS, SyntheticStyle, TextBounds, TextOptions, TextOverflow, draw_text, fit_text, hex_to_rgba, layout_text, load_font, load_template};
+use crate::effects::TextEffect;
+use crate::manifest::EllipsizedText;
+use crate::palette::check_color;
+use crate::error::{CertError, Result};
+use crate::source::Record;
+use crate::table::{TableLayout, TableStyle, draw_table, fill_rows, layout_table, parse_cells};
+use crate::typography::{RetouchedText, Typography};
+
+/// What a graphic field draws.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum GraphicKind {
+    /// The data as a line of text.
+    Text(TextStyle),
+    /// The image file the data names, such as `photos/{Id}.png`.
+    Image(ImageStyle),
+    /// A Code128 barcode, the 1D code badge printers scan.
+    Code128(BarcodeStyle),
+    /// Rows of cells in columns, from the style's cell templates or, when it has none, from
+    /// the data as JSON.
+    Table(TableStyle),
+}
+
+impl GraphicKind {
+    /// Lowercase description for messages.
+    pub fn label(&self) -> &'static str {
+        match self {
+            GraphicKind::Text(_) => "text",
+            GraphicKind::Image(_) => "image",
+            GraphicKind::Code128(_) => "barcode",
+            GraphicKind::Table(_) => "table",
+        }
+    }
+}
+
+/// How a text field is set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextStyle {
+    /// Font file or family and style, as for [`CertificateJob::font`](crate::job::CertificateJob::font).
+    pub font: PathBuf,
+    pub size: f32,
+    pub color: String,
+    /// Text wider than this is set smaller, or cut short, until it fits.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_width: Option<f32>,
+    /// What happens to text wider than `max_width`: set smaller, or cut short.
+    #[serde(default, skip_serializing_if = "TextOverflow::is_shrink")]
+    pub overflow: TextOverflow,
+    /// What cut-short text ends with; [`ELLIPSIS`] when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ellipsis: Option<String>,
+    /// Gradient, outline and shadows, such as [`find_effect`](crate::effects::find_effect)`("gold-foil")`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effect: Option<TextEffect>,
+}
+
+/// How an image field is sized; with one dimension given the other keeps the aspect ratio,
+/// and with none the image is drawn at its own size.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct ImageStyle {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+}
+
+/// One graphic drawn on every certificate, its `anchor` point at `(x, y)`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphicField {
+    pub kind: GraphicKind,
+    /// What to draw; `{name}` is replaced by the recipient's name and `{Column}` by that
+    /// column of the row, matched case-insensitively.
+    pub data: String,
+    pub x: i32,
+    pub y: i32,
+    #[serde(default)]
+    pub anchor: Anchor,
+}
+
+impl GraphicField {
+    /// Check the field's settings. Data with placeholders is checked per row when it is filled
+    /// in; data without any is the same on every row, so a missing image or a code that can't
+    /// be encoded is reported here once instead of failing every row.
+    pub fn validate(&self) -> Result<()> {
+        match &self.kind {
+            GraphicKind::Text(style) => {
+                load_font(&style.font)?;
+                check_color(&style.color)?;
+                if !style.size.is_finite() || style.size <= 0.0 {
+                    return Err(CertError::InvalidFontSize(style.size));
+                }
+                if let Some(max_width) = style.max_width
+                    && (!max_width.is_finite() || max_width <= 0.0) {
+                    return Err(CertError::InvalidMeasurement(format!("the maximum width must be a positive number, got {}", max_width)));
+                }
+                if style.overflow == TextOverflow::Ellipsis && style.max_width.is_none() {
+                    return Err(CertError::InvalidMeasurement("text is only cut short past a maximum width, and none is set".to_string()));
+                }
+                if let Some(effect) = &style.effect {
+                    effect.validate()?;
+                }
+            }
+            GraphicKind::Image(style) => {
+                if style.width == Some(0) || style.height == Some(0) {
+                    return Err(CertError::InvalidGraphic("an image can't be drawn 0 pixels wide or tall".to_string()));
+                }
+            }
+            GraphicKind::Code128(style) => style.validate()?,
+            GraphicKind::Table(style) => {
+                style.validate()?;
+                if !style.rows.is_empty() {
+                    if !self.data.trim().is_empty() {
+                        return Err(CertError::InvalidGraphic("a table takes its cells from its rows or from its data, not both".to_string()));
+                    }
+                    return Ok(());
+                }
+            }
+        }
+        if self.data.trim().is_empty() {
+            return Err(CertError::InvalidGraphic(format!("the {}'s data template is empty", self.kind.label())));
+        }
+        if placeholders(&self.data).is_empty() {
+            match &self.kind {
+                GraphicKind::Image(_) => {
+                    load_template(&self.data)?;
+                }
+                GraphicKind::Code128(_) => {
+                    encode_code128(&self.data)?;
+                }
+                GraphicKind::Table(style) => {
+                    parse_cells(&self.data, style.columns.len())?;
+                }
+                GraphicKind::Text(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// The data of one row: the template with `{name}` and every `{Column}` filled in (see
+    /// [`fill_placeholders`]). For a table with cell templates, their filled-in cells as JSON.
+    pub fn fill(&self, name: &str, record: Option<&Record>) -> Result<String> {
+        match &self.kind {
+            GraphicKind::Table(style) if !style.rows.is_empty() => fill_rows(&style.rows, name, record),
+            _ => fill_placeholders(&self.data, name, record),
+        }
+    }
+
+    /// `data` of one row with `typography` applied to what is drawn as text: a text field's
+    /// text and each cell of a table. Codes and image paths are left alone. Every value it
+    /// changes is added to `retouched`.
+    pub fn retouch(&self, data: String, typography: &Typography, retouched: &mut Vec<RetouchedText>) -> Result<String> {
+        if typography.is_none() {
+            return Ok(data);
+        }
+        let mut clean = |field: String, before: &str| {
+            let after = typography.apply(before);
+            if after != before {
+                retouched.push(RetouchedText { field, before: before.to_string(), after: after.clone() });
+            }
+            after
+        };
+        match &self.kind {
+            GraphicKind::Text(_) => Ok(clean(self.data.clone(), &data)),
+            GraphicKind::Table(style) => {
+                let field = if self.data.is_empty() { "table".to_string() } else { format!("table {}", self.data) };
+                let cells = parse_cells(&data, style.columns.len())?.into_iter()
+                    .map(|row| row.iter().map(|cell| clean(field.clone(), cell)).collect::<Vec<_>>())
+                    .collect::<Vec<_>>();
+                serde_json::to_string(&cells).map_err(|e| CertError::InvalidTable(e.to_string()))
+            }
+            GraphicKind::Image(_) | GraphicKind::Code128(_) => Ok(data),
+        }
+    }
+
+    // Where a graphic of `width` x `height` goes; an error when any of it would fall off, since
+    // a cropped code doesn't scan and a cropped photo or line looks like a misprint
+    fn place(&self, width: u32, height: u32, (template_width, template_height): (u32, u32)) -> Result<TextBounds> {
+        let (x, y) = self.anchor.resolve(self.x, self.y, width as i32, height as i32);
+        let bounds = TextBounds { x, y, width: width as i32, height: height as i32 };
+        if !bounds.fits_within(template_width, template_height) {
+            return Err(CertError::GraphicOffTemplate {
+                kind: self.kind.label(),
+                x,
+                y,
+                width,
+                height,
+                template_width,
+                template_height,
+            });
+        }
+        Ok(bounds)
+    }
+}
+
+/// Names of the `{Column}` placeholders in a data template, in order; `{name}` is one too.
+pub fn placeholders(template: &str) -> Vec<&str> {
+    let mut found = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}').map(|end| start + end) else { break };
+        found.push(&rest[start + 1..end]);
+        rest = &rest[end + 1..];
+    }
+    found
+}
+
+/// `template` with `{name}` replaced by `name` and every other `{Column}` by that column of
+/// `record`. A column the row doesn't have is an error, so a typo can't print the placeholder.
+pub fn fill_placeholders(template: &str, name: &str, record: Option<&Record>) -> Result<String> {
+    let mut filled = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}').map(|end| start + end) else { break };
+        filled.push_str(&rest[..start]);
+        let column = &rest[start + 1..end];
+        if column.eq_ignore_ascii_case("name") {
+            filled.push_str(name);
+        } else {
+            let value = record.and_then(|record| record.get(column)).ok_or_else(|| CertError::MissingColumn {
+                wanted: column.to_string(),
+                available: record.map(|record| record.fields.iter().map(|(header, _)| header.clone()).collect()).unwrap_or_default(),
+            })?;
+            filled.push_str(value);
+        }
+        rest = &rest[end + 1..];
+    }
+    filled.push_str(rest);
+    Ok(filled)
+}
+
+/// The fonts of a set of text fields, loaded once for a whole batch.
+#[derive(Default)]
+pub struct GraphicFonts {
+    fonts: HashMap<PathBuf, Font<'static>>,
+}
+
+impl GraphicFonts {
+    pub fn load(fields: &[GraphicField]) -> Result<Self> {
+        let mut fonts = HashMap::new();
+        let used = fields.iter().flat_map(|field| match &field.kind {
+            GraphicKind::Text(style) => vec![style.font.as_path()],
+            GraphicKind::Table(style) => vec![style.font.as_path(), style.header_font()],
+            _ => Vec::new(),
+        });
+        for font in used {
+            if !fonts.contains_key(font) {
+                fonts.insert(font.to_path_buf(), load_font(font)?);
+            }
+        }
+        Ok(GraphicFonts { fonts })
+    }
+
+    fn get(&self, font: &Path) -> Result<&Font<'static>> {
+        self.fonts.get(font).ok_or_else(|| CertError::FontNotFound(font.to_path_buf()))
+    }
+}
+
+/// One field of one row, ready to draw.
+#[derive(Debug, Clone)]
+pub enum PlacedGraphic {
+    /// Text at the size it is drawn at, which [`TextStyle::max_width`] may have reduced, and
+    /// the whole of it when it was cut short instead.
+    Text { text: String, full_text: Option<String>, size: f32, bounds: TextBounds },
+    Image { image: RgbaImage, bounds: TextBounds },
+    /// A table's cells and rules, placed relative to the corner of `bounds`.
+    Table { table: TableLayout, bounds: TextBounds },
+}
+
+/// Render and place every field of one row on a template of `dimensions`, in field order.
+/// `font` sets the human-readable line of barcodes. A dry run stops here, so it catches the
+/// same per-row errors as a real run.
+pub fn layout_graphic_fields(
+    fields: &[GraphicField],
+    data: &[String],
+    font: &Font,
+    fonts: &GraphicFonts,
+    dimensions: (u32, u32),
+) -> Result<Vec<PlacedGraphic>> {
+    fields.iter().zip(data)
+        .map(|(field, data)| match &field.kind {
+            GraphicKind::Text(style) => {
+                let options = text_options(field, style, fonts)?;
+                let (text, options) = match style.max_width {
+                    Some(max_width) => fit_text(data, &options, max_width, style.overflow, style.ellipsis.as_deref().unwrap_or(ELLIPSIS))?,
+                    None => (data.clone(), options),
+                };
+                let measured = layout_text(&text, &options)?;
+                let bounds = field.place(measured.width as u32, measured.height as u32, dimensions)?;
+                let full_text = (text != *data).then(|| data.clone());
+                Ok(PlacedGraphic::Text { text, full_text, size: options.size, bounds })
+            }
+            GraphicKind::Image(style) => {
+                let image = load_template(data)?;
+                let image = match (style.width, style.height) {
+                    (None, None) => image,
+                    (width, height) => {
+                        let (own_width, own_height) = image.dimensions();
+                        let width = width.unwrap_or_else(|| (own_width as u64 * height.unwrap_or(own_height) as u64 / own_height.max(1) as u64).max(1) as u32);
+                        let height = height.unwrap_or_else(|| (own_height as u64 * width as u64 / own_width.max(1) as u64).max(1) as u32);
+                        imageops::resize(&image, width, height, FilterType::Lanczos3)
+                    }
+                };
+                let bounds = field.place(image.width(), image.height(), dimensions)?;
+                Ok(PlacedGraphic::Image { image, bounds })
+            }
+            GraphicKind::Code128(style) => {
+                let image = render_code128(data, style, Some(font))?;
+                let bounds = field.place(image.width(), image.height(), dimensions)?;
+                Ok(PlacedGraphic::Image { image, bounds })
+            }
+            GraphicKind::Table(style) => {
+                let rows = parse_cells(data, style.columns.len())?;
+                let table = layout_table(style, &rows, fonts.get(&style.font)?, fonts.get(style.header_font())?)?;
+                let bounds = field.place(table.width, table.height, dimensions)?;
+                Ok(PlacedGraphic::Table { table, bounds })
+            }
+        })
+        .collect()
+}
+
+/// Draw graphics placed by [`layout_graphic_fields`] for `fields` onto `image`.
+pub fn draw_graphic_fields(image: &mut RgbaImage, fields: &[GraphicField], placed: &[PlacedGraphic], fonts: &GraphicFonts) -> Result<()> {
+    for (field, placed) in fields.iter().zip(placed) {
+        match (placed, &field.kind) {
+            (PlacedGraphic::Text { text, size, bounds, .. }, GraphicKind::Text(style)) => {
+                let options = TextOptions { size: *size, x: bounds.x, y: bounds.y, anchor: Anchor::TopLeft, ..text_options(field, style, fonts)? };
+                draw_text(image, text, &options)?;
+            }
+            (PlacedGraphic::Image { image: graphic, bounds }, _) => {
+                imageops::overlay(image, graphic, bounds.x as i64, bounds.y as i64);
+            }
+            (PlacedGraphic::Table { table, bounds }, GraphicKind::Table(style)) => {
+                draw_table(image, table, (bounds.x, bounds.y), style, fonts.get(&style.font)?, fonts.get(style.header_font())?)?;
+            }
+            (PlacedGraphic::Text { .. } | PlacedGraphic::Table { .. }, _) => {}
+        }
+    }
+    Ok(())
+}
+
+/// The values of `placed` that were cut short to fit, text fields by their template and table
+/// cells as the table's.
+pub fn ellipsized_values(fields: &[GraphicField], placed: &[PlacedGraphic]) -> Vec<EllipsizedText> {
+    fields.iter().zip(placed)
+        .flat_map(|(field, placed)| match placed {
+            PlacedGraphic::Text { text, full_text: Some(full), .. } => {
+                vec![EllipsizedText { field: field.data.clone(), full: full.clone(), drawn: text.clone() }]
+            }
+            PlacedGraphic::Table { table, .. } => table.cells.iter()
+                .filter_map(|cell| {
+                    let full = cell.full_text.clone()?;
+                    let field = if field.data.is_empty() { "table".to_string() } else { format!("table {}", field.data) };
+                    Some(EllipsizedText { field, full, drawn: cell.text.clone() })
+                })
+                .collect(),
+            _ => Vec::new(),
+        })
+        .collect()
+}
+
+// Options of a text field at its anchor point and full size
+fn text_options<'a>(field: &GraphicField, style: &'a TextStyle, fonts: &'a GraphicFonts) -> Result<TextOptions<'a>> {
+    Ok(TextOptions {
+        font: fonts.get(&style.font)?,
+        size: style.size,
+        color: hex_to_rgba(&style.color)?,
+        x: field.x,
+        y: field.y,
+        anchor: field.anchor,
+        decorations: &[],
+        synthetic: SyntheticStyle::default(),
+        variation: None,
+        effect: style.effect.as_ref(),
+    })
+}
@@ -0,0 +1,80 @@
+// src/grouping.rs
+//! Grouping certificates for distribution: one subdirectory per value of a CSV column, such
+//! as a department, and the order to hand them out in: by group, then by surname.
+//!
+//! Names are compared with the Unicode Collation Algorithm (the CLDR root order), so accented
+//! letters sort next to their base letter instead of after `z`.
+//!
+//! ```
+//! use certificate_maker::grouping::{collate_names, group_dir_name};
+//! use std::cmp::Ordering;
+//!
+//! assert_eq!(group_dir_name("Sales / EMEA"), Some("Sales _ EMEA".to_string()));
+//! assert_eq!(group_dir_name("  "), None);
+//!
+//! let mut names = vec!["Zoe Ångström", "Ada Zuse", "Emil Adams", "Ida Ångström"];
+//! names.sort_by(|a, b| collate_names(a, b));
+//! assert_eq!(names, ["Emil Adams", "Ida Ångström", "Zoe Ångström", "Ada Zuse"]);
+//! assert_eq!(collate_names("Ida Ångström", "Ida Angstrom"), Ordering::Greater);
+//! ```
+use feruca::Collator;
+use std::cmp::Ordering;
+
+use crate::files::portable_file_name;
+use crate::manifest::ManifestEntry;
+
+/// Subdirectory of the certificates whose group column is empty.
+pub const UNGROUPED_DIR: &str = "_ungrouped";
+
+/// Directory name for a group value: a [`portable_file_name`] without surrounding spaces and
+/// dots, which Windows drops. `None` when nothing is left.
+pub fn group_dir_name(value: &str) -> Option<String> {
+    let trim = |name: &str| name.trim_matches(|c: char| c.is_whitespace() || c == '.').to_string();
+    // Trimmed again in case the cut left a space or dot at the end
+    let name = trim(&portable_file_name(&trim(value)));
+    (!name.is_empty()).then_some(name)
+}
+
+/// The last word of a name, which is sorted on first.
+pub fn surname(name: &str) -> &str {
+    name.split_whitespace().next_back().unwrap_or(name)
+}
+
+/// Compare two names by surname, then by the whole name, with Unicode collation.
+pub fn collate_names(a: &str, b: &str) -> Ordering {
+    let mut collator = Collator::default();
+    collate_names_with(&mut collator, a, b)
+}
+
+fn collate_names_with(collator: &mut Collator, a: &str, b: &str) -> Ordering {
+    collator.collate(surname(a), surname(b))
+        .then_with(|| collator.collate(a, b))
+}
+
+/// Manifest entries in distribution order: by group (ungrouped last), then by surname and
+/// name. The order a combined PDF or contact sheet should use.
+pub fn distribution_order(entries: &[ManifestEntry]) -> Vec<&ManifestEntry> {
+    let mut collator = Collator::default();
+    let mut ordered: Vec<&ManifestEntry> = entries.iter().collect();
+    ordered.sort_by(|a, b| {
+        let groups = match (&a.group, &b.group) {
+            (Some(a), Some(b)) => collator.collate(a, b),
+            (a, b) => a.is_none().cmp(&b.is_none()),
+        };
+        groups.then_with(|| collate_names_with(&mut collator, &a.name, &b.name))
+    });
+    ordered
+}
+
+/// Entries split by group in [`distribution_order`], for anything produced once per group;
+/// the ungrouped ones come last under `None`.
+pub fn entries_by_group(entries: &[ManifestEntry]) -> Vec<(Option<&str>, Vec<&ManifestEntry>)> {
+    let mut groups: Vec<(Option<&str>, Vec<&ManifestEntry>)> = Vec::new();
+    for entry in distribution_order(entries) {
+        match groups.last_mut() {
+            Some((group, members)) if *group == entry.group.as_deref() => members.push(entry),
+            _ => groups.push((entry.group.as_deref(), vec![entry])),
+        }
+    }
+    groups
+}
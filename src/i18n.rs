@@ -0,0 +1,262 @@
+// src/i18n.rs
+//! Translations of what the interactive tool says to the person at the keyboard.
+//!
+//! A message is looked up by its English text, gettext style, in a catalog compiled in from
+//! `locales/<language>.po`. A message a catalog doesn't have, or has with an empty `msgstr`,
+//! stays in English, so a half-done translation is still usable. English needs no catalog.
+//!
+//! Only prompts and messages for people go through [`tr!`](crate::tr) and [`say!`](crate::say).
+//! Diagnostics stay in English so whoever supports a run can read them: run logs, reports,
+//! manifests and every other file the tool writes, JSON on stdout, and this library's
+//! [`CertError`](crate::error::CertError)s.
+//!
+//! In a message, `{}` takes the next argument and `{0}`, `{1}`… take one by position, so a
+//! translation can put them in another order; `{{` and `}}` are braces. Arguments are
+//! formatted with `Display` by the caller beforehand when they need a width or precision.
+//!
+//! ```
+//! use certificate_maker::i18n::{Catalog, fill};
+//!
+//! let catalog = Catalog::from_po("es", "msgid \"Saved {} of {}\"\nmsgstr \"Guardados {0} de {1}\"\n")?;
+//! assert_eq!(fill(catalog.translate("Saved {} of {}"), &[&3, &5]), "Guardados 3 de 5");
+//! // Not in the catalog: the English text
+//! assert_eq!(catalog.translate("Done"), "Done");
+//! assert_eq!(fill("{{{}}}", &[&"braces"]), "{braces}");
+//! # Ok::<(), String>(())
+//! ```
+use std::collections::HashMap;
+use std::fmt::{Display, Write};
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Environment variable naming the language, ahead of the config file and the system locale.
+pub const LANGUAGE_ENV: &str = "CERTMAKER_LANG";
+
+/// Catalogs compiled into the binary, by language code.
+const CATALOGS: [(&str, &str); 1] = [("es", include_str!("../locales/es.po"))];
+
+static INSTALLED: OnceLock<Catalog> = OnceLock::new();
+
+/// The translations of one language, keyed by the English message.
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    pub language: String,
+    messages: HashMap<String, String>,
+}
+
+impl Catalog {
+    /// Parse a PO file: `msgid`/`msgstr` pairs of quoted strings, continued on following
+    /// quoted lines, with `#` comments. Errors name the line.
+    pub fn from_po(language: &str, text: &str) -> Result<Catalog, String> {
+        let mut messages = HashMap::new();
+        let mut id: Option<String> = None;
+        let mut current: Option<(bool, String)> = None;
+        let mut finish = |current: &mut Option<(bool, String)>, id: &mut Option<String>, line: usize| -> Result<(), String> {
+            match current.take() {
+                Some((false, text)) => *id = Some(text),
+                Some((true, text)) => {
+                    let key = id.take().ok_or_else(|| format!("line {}: msgstr without a msgid", line))?;
+                    if !key.is_empty() && !text.is_empty() {
+                        messages.insert(key, text);
+                    }
+                }
+                None => {}
+            }
+            Ok(())
+        };
+        for (number, line) in text.lines().enumerate().map(|(index, line)| (index + 1, line.trim())) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (is_str, rest) = if let Some(rest) = line.strip_prefix("msgid ") {
+                finish(&mut current, &mut id, number)?;
+                (Some(false), rest)
+            } else if let Some(rest) = line.strip_prefix("msgstr ") {
+                finish(&mut current, &mut id, number)?;
+                (Some(true), rest)
+            } else {
+                (None, line)
+            };
+            let text = unquote(rest.trim()).ok_or_else(|| format!("line {}: expected a quoted string", number))?;
+            match (is_str, &mut current) {
+                (Some(is_str), current) => *current = Some((is_str, text)),
+                (None, Some((_, continued))) => continued.push_str(&text),
+                (None, None) => return Err(format!("line {}: text outside a msgid or msgstr", number)),
+            }
+        }
+        finish(&mut current, &mut id, text.lines().count())?;
+        if let Some(id) = id {
+            return Err(format!("msgid {:?} has no msgstr", id));
+        }
+        Ok(Catalog { language: language.to_string(), messages })
+    }
+
+    /// The compiled-in catalog of `language` (`es`, `es_MX.UTF-8`…), if there is one.
+    pub fn builtin(language: &str) -> Option<Catalog> {
+        let code = language_code(language)?;
+        let (code, text) = CATALOGS.iter().find(|(code_, _)| *code_ == code)?;
+        // Checked by tests/i18n.rs, so a broken catalog never ships
+        Catalog::from_po(code, text).ok()
+    }
+
+    /// The translation of `message`, or `message` itself.
+    pub fn translate<'a>(&'a self, message: &'a str) -> &'a str {
+        self.messages.get(message).map_or(message, String::as_str)
+    }
+
+    /// Every English message this catalog translates.
+    pub fn messages(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.messages.iter().map(|(id, text)| (id.as_str(), text.as_str()))
+    }
+}
+
+/// Languages with a compiled-in catalog, besides English.
+pub fn languages() -> impl Iterator<Item = &'static str> {
+    CATALOGS.iter().map(|(code, _)| *code)
+}
+
+/// The language asked for: [`LANGUAGE_ENV`], then the `"language"` entry of the config file,
+/// then `LC_ALL`, `LC_MESSAGES` and `LANG`. `None` is English.
+pub fn requested_language(config_file: &Path) -> Option<String> {
+    let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+    var(LANGUAGE_ENV)
+        .or_else(|| configured_language(config_file))
+        .or_else(|| ["LC_ALL", "LC_MESSAGES", "LANG"].into_iter().find_map(var))
+}
+
+// The "language" entry of certmaker.json; a missing or broken file is reported where the
+// paths are read, not here
+fn configured_language(config_file: &Path) -> Option<String> {
+    let content = std::fs::read_to_string(config_file).ok()?;
+    let config: serde_json::Value = serde_json::from_str(&content).ok()?;
+    config.get("language")?.as_str().map(str::to_string)
+}
+
+// "es_ES.UTF-8" → "es"; "C" and "POSIX" are no language
+fn language_code(language: &str) -> Option<String> {
+    let code = language.split(['_', '-', '.', '@']).next()?.to_ascii_lowercase();
+    (!code.is_empty() && code != "c" && code != "posix").then_some(code)
+}
+
+/// Make `catalog` the one [`tr!`](crate::tr) uses; only the first call has any effect.
+pub fn install(catalog: Catalog) -> bool {
+    INSTALLED.set(catalog).is_ok()
+}
+
+/// The catalog [`install`] made current, if any.
+pub fn installed() -> Option<&'static Catalog> {
+    INSTALLED.get()
+}
+
+/// `message` in the installed language.
+pub fn translate(message: &'static str) -> &'static str {
+    installed().map_or(message, |catalog| catalog.translate(message))
+}
+
+/// Put `args` into `template`: `{}` in turn, `{N}` by position, `{{` and `}}` as braces.
+/// A placeholder without an argument is left as it is.
+pub fn fill(template: &str, args: &[&dyn Display]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut next = 0;
+    let mut rest = template;
+    while let Some(at) = rest.find(['{', '}']) {
+        out.push_str(&rest[..at]);
+        rest = &rest[at..];
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            out.push_str(&rest[..1]);
+            rest = &rest[2..];
+            continue;
+        }
+        let placeholder = rest.strip_prefix('{').and_then(|inner| inner.find('}').map(|end| &inner[..end]));
+        let index = match placeholder {
+            Some("") => {
+                next += 1;
+                Some(next - 1)
+            }
+            Some(digits) => digits.parse::<usize>().ok(),
+            None => None,
+        };
+        match (placeholder, index.and_then(|index| args.get(index))) {
+            (Some(placeholder), Some(arg)) => {
+                let _ = write!(out, "{}", arg);
+                rest = &rest[placeholder.len() + 2..];
+            }
+            _ => {
+                out.push_str(&rest[..1]);
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The placeholders of a message, for checking a translation keeps all of them: `{}` counted
+/// in turn, `{N}` as written.
+pub fn placeholders(template: &str) -> Vec<usize> {
+    let mut found = Vec::new();
+    let mut next = 0;
+    let mut rest = template;
+    while let Some(at) = rest.find('{') {
+        rest = &rest[at + 1..];
+        if let Some(escaped) = rest.strip_prefix('{') {
+            rest = escaped;
+            continue;
+        }
+        let Some(end) = rest.find('}') else { break };
+        match &rest[..end] {
+            "" => {
+                found.push(next);
+                next += 1;
+            }
+            digits => found.extend(digits.parse::<usize>().ok()),
+        }
+        rest = &rest[end + 1..];
+    }
+    found.sort_unstable();
+    found
+}
+
+// A PO string: quoted, with \" \\ \n \t escapes
+fn unquote(text: &str) -> Option<String> {
+    let inner = text.strip_prefix('"')?.strip_suffix('"')?;
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.push(match chars.next()? {
+                'n' => '\n',
+                't' => '\t',
+                other => other,
+            }),
+            '"' => return None,
+            c => out.push(c),
+        }
+    }
+    Some(out)
+}
+
+/// A message for people, translated into the installed language, with its arguments filled in.
+///
+/// The message must be a string literal, so it can be found in the source when translating.
+#[macro_export]
+macro_rules! tr {
+    ($message:literal) => {
+        $crate::i18n::fill($crate::i18n::translate($message), &[])
+    };
+    ($message:literal, $($arg:expr),+ $(,)?) => {
+        $crate::i18n::fill($crate::i18n::translate($message), &[$(&$arg as &dyn ::std::fmt::Display),+])
+    };
+}
+
+/// `println!` of a translated [`tr!`](crate::tr) message; plain `println!` is for output meant
+/// for programs, which stays in English.
+#[macro_export]
+macro_rules! say {
+    () => {
+        ::std::println!()
+    };
+    ($($message:tt)+) => {
+        ::std::println!("{}", $crate::tr!($($message)+))
+    };
+}
@@ -0,0 +1,496 @@
+// src/imposition.rs
+//! Print imposition: successive certificates laid out in a grid on larger sheets, such as A5
+//! certificates two-up on A4, with gutters between them and optional crop marks to cut along.
+//!
+//! Certificates keep their physical size. Each is scaled from the DPI it was rendered at to
+//! the sheet's, and one that doesn't fit its cell is an error rather than being shrunk. The
+//! grid is centered on the sheet, and cells fill left to right, then top to bottom.
+//!
+//! [`impose_batch`] reads a batch's PNG certificates in distribution order, each group
+//! starting a new sheet, so the stacks come off the printer collated. The last sheet of a
+//! group may have empty cells. The sheets are recorded in the manifest, so undo removes them
+//! with the batch.
+//!
+//! ```
+//! use certificate_maker::imposition::{Grid, Imposition, SheetSize};
+//! use certificate_maker::layout::Length;
+//! use image::{Rgba, RgbaImage};
+//!
+//! // A4 at 300 DPI
+//! let a4 = SheetSize::parse("A4@300").unwrap();
+//! assert_eq!(a4.pixels(96.0), (2480, 3508));
+//! assert_eq!(SheetSize::parse("a4-landscape").unwrap().pixels(300.0), (3508, 2480));
+//!
+//! // Three 100x50 certificates, two to a 120x130 sheet with 10 pixels between them
+//! let certificate = RgbaImage::from_pixel(100, 50, Rgba([200, 0, 0, 255]));
+//! let imposition = Imposition::new(SheetSize::parse("120x130").unwrap(), Grid::parse("1x2").unwrap())
+//!     .gutter(Length::Pixels(10.0));
+//! let sheets = imposition.impose(&vec![certificate; 3], 300.0)?;
+//! assert_eq!(sheets.len(), 2);
+//! assert_eq!(sheets[0].dimensions(), (120, 130));
+//! // The 100x110 grid is centered: a margin, the first certificate, the gutter, the second
+//! assert_eq!(sheets[0].get_pixel(9, 9), &Rgba([255, 255, 255, 255]));
+//! assert_eq!(sheets[0].get_pixel(10, 10), &Rgba([200, 0, 0, 255]));
+//! assert_eq!(sheets[0].get_pixel(60, 65), &Rgba([255, 255, 255, 255]));
+//! assert_eq!(sheets[0].get_pixel(60, 70), &Rgba([200, 0, 0, 255]));
+//! // The last sheet has only its first cell filled
+//! assert_eq!(sheets[1].get_pixel(60, 100), &Rgba([255, 255, 255, 255]));
+//!
+//! // A 2x2 grid of them doesn't fit
+//! let too_many = Imposition::new(SheetSize::parse("120x130").unwrap(), Grid::parse("2x2").unwrap());
+//! assert!(too_many.impose(&[RgbaImage::new(100, 50)], 300.0).is_err());
+//! # Ok::<(), certificate_maker::error::CertError>(())
+//! ```
+use image::imageops::{self, FilterType};
+use image::{Rgba, RgbaImage};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::fmt;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use crate::dpi::{ASSUMED_DPI, mm_to_pixels, pixels_to_mm, points_to_pixels};
+use crate::editpng::load_template;
+use crate::error::{CertError, IoContext, Result};
+use crate::grouping::entries_by_group;
+use crate::job::CertificateFormat;
+use crate::layout::{Length, TemplateScale};
+use crate::manifest::{ManifestEntry, record_artifacts};
+use crate::pdf::pages_to_pdf;
+
+/// Start of the name of each sheet saved as PNG, followed by its number: `sheet_0001.png`.
+pub const SHEET_PREFIX: &str = "sheet_";
+
+/// Where the sheets go when they are saved as one PDF, a page each.
+pub const SHEETS_PDF_FILENAME: &str = "sheets.pdf";
+
+/// Paper sizes a sheet can be given by, with their portrait width and height in millimetres.
+pub const PAPER_SIZES: [(&str, f64, f64); 7] = [
+    ("A3", 297.0, 420.0),
+    ("A4", 210.0, 297.0),
+    ("A5", 148.0, 210.0),
+    ("A6", 105.0, 148.0),
+    ("Letter", 215.9, 279.4),
+    ("Legal", 215.9, 355.6),
+    ("Tabloid", 279.4, 431.8),
+];
+
+/// Gap between a certificate's edge and the start of its crop marks, in millimetres.
+pub const CROP_MARK_OFFSET_MM: f64 = 3.0;
+
+/// Length of each crop mark, in millimetres.
+pub const CROP_MARK_LENGTH_MM: f64 = 5.0;
+
+// Line weight of the crop marks, in points
+const CROP_MARK_WEIGHT_PT: f64 = 0.5;
+
+const PAPER: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const MARK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+/// The size of a sheet: a paper size, or pixels. Either can be followed by `@<dpi>`; without
+/// it the sheet is at the certificates' DPI.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SheetSize {
+    Pixels { width: u32, height: u32, dpi: Option<f32> },
+    /// One of [`PAPER_SIZES`], already turned for landscape.
+    Paper { name: &'static str, width_mm: f64, height_mm: f64, dpi: Option<f32> },
+}
+
+impl Default for SheetSize {
+    /// Portrait A4.
+    fn default() -> Self {
+        let (name, width_mm, height_mm) = PAPER_SIZES[1];
+        SheetSize::Paper { name, width_mm, height_mm, dpi: None }
+    }
+}
+
+impl SheetSize {
+    /// Parse `<width>x<height>` in pixels, or a paper size such as `A4` or `A4-landscape`
+    /// (case doesn't matter), each optionally followed by `@<dpi>`, like `A4@300`.
+    pub fn parse(text: &str) -> Option<SheetSize> {
+        let (size, dpi) = match text.trim().split_once('@') {
+            Some((size, dpi)) => {
+                let dpi = dpi.trim().trim_end_matches("dpi").trim().parse::<f32>().ok()
+                    .filter(|dpi| dpi.is_finite() && *dpi > 0.0)?;
+                (size.trim(), Some(dpi))
+            }
+            None => (text.trim(), None),
+        };
+        if let Some((width, height)) = size.split_once(['x', 'X']) {
+            let (width, height) = (width.trim().parse::<u32>().ok()?, height.trim().parse::<u32>().ok()?);
+            return (width > 0 && height > 0).then_some(SheetSize::Pixels { width, height, dpi });
+        }
+        let lower = size.to_ascii_lowercase();
+        let (paper, landscape) = match lower.strip_suffix("-landscape") {
+            Some(paper) => (paper, true),
+            None => (lower.strip_suffix("-portrait").unwrap_or(&lower), false),
+        };
+        let &(name, width_mm, height_mm) = PAPER_SIZES.iter().find(|(name, _, _)| name.eq_ignore_ascii_case(paper))?;
+        let (width_mm, height_mm) = if landscape { (height_mm, width_mm) } else { (width_mm, height_mm) };
+        Some(SheetSize::Paper { name, width_mm, height_mm, dpi })
+    }
+
+    /// The sheet's resolution: its own, or `certificate_dpi` when it has none.
+    pub fn dpi(&self, certificate_dpi: f32) -> f32 {
+        match *self {
+            SheetSize::Pixels { dpi, .. } | SheetSize::Paper { dpi, .. } => dpi.unwrap_or(certificate_dpi),
+        }
+    }
+
+    /// Width and height in pixels, for certificates rendered at `certificate_dpi`.
+    pub fn pixels(&self, certificate_dpi: f32) -> (u32, u32) {
+        match *self {
+            SheetSize::Pixels { width, height, .. } => (width, height),
+            SheetSize::Paper { width_mm, height_mm, .. } => {
+                let dpi = self.dpi(certificate_dpi);
+                (mm_to_pixels(width_mm, dpi).round() as u32, mm_to_pixels(height_mm, dpi).round() as u32)
+            }
+        }
+    }
+}
+
+impl fmt::Display for SheetSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let dpi = match *self {
+            SheetSize::Pixels { width, height, dpi } => {
+                write!(f, "{}x{} px", width, height)?;
+                dpi
+            }
+            SheetSize::Paper { name, width_mm, height_mm, dpi } => {
+                write!(f, "{}{}", name, if width_mm > height_mm { " landscape" } else { "" })?;
+                dpi
+            }
+        };
+        match dpi {
+            Some(dpi) => write!(f, " at {} DPI", dpi),
+            None => Ok(()),
+        }
+    }
+}
+
+/// How many certificates go across and down a sheet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Grid {
+    pub columns: u32,
+    pub rows: u32,
+}
+
+impl Grid {
+    /// Parse `<columns>x<rows>`, like `1x2` for two certificates one above the other.
+    pub fn parse(text: &str) -> Option<Grid> {
+        let (columns, rows) = text.trim().split_once(['x', 'X'])?;
+        let (columns, rows) = (columns.trim().parse::<u32>().ok()?, rows.trim().parse::<u32>().ok()?);
+        (columns > 0 && rows > 0).then_some(Grid { columns, rows })
+    }
+
+    /// How many certificates a sheet holds.
+    pub fn cells(self) -> usize {
+        self.columns as usize * self.rows as usize
+    }
+
+    /// Width and height of the whole grid of `cell`-sized cells with `gutter` pixels between
+    /// neighbours.
+    pub fn size(self, cell: (u32, u32), gutter: u32) -> (u64, u64) {
+        let span = |count: u32, length: u32| count as u64 * length as u64 + (count as u64 - 1) * gutter as u64;
+        (span(self.columns, cell.0), span(self.rows, cell.1))
+    }
+
+    /// Top-left corner of the `index`th cell from the grid's own corner, filling left to
+    /// right, then top to bottom.
+    pub fn cell_origin(self, index: usize, cell: (u32, u32), gutter: u32) -> (u64, u64) {
+        let (column, row) = (index as u64 % self.columns as u64, index as u64 / self.columns as u64);
+        (column * (cell.0 + gutter) as u64, row * (cell.1 + gutter) as u64)
+    }
+}
+
+impl fmt::Display for Grid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}x{}", self.columns, self.rows)
+    }
+}
+
+/// How certificates are put on sheets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Imposition {
+    pub sheet: SheetSize,
+    pub grid: Grid,
+    /// Space between neighbouring certificates: pixels, or a length such as `"5mm"` at the
+    /// sheet's DPI.
+    pub gutter: Length,
+    /// Short lines in the margins around the grid continuing each certificate's edges, from
+    /// [`CROP_MARK_OFFSET_MM`] away from it, to cut along. Marks that would reach into the grid
+    /// are left out.
+    pub crop_marks: bool,
+    /// PNG saves a `sheet_0001.png` per sheet; PDF saves them as the pages of one
+    /// [`SHEETS_PDF_FILENAME`].
+    pub format: CertificateFormat,
+}
+
+impl Imposition {
+    /// `grid` on `sheet`, with no gutters or crop marks, saved as PNG.
+    pub fn new(sheet: SheetSize, grid: Grid) -> Self {
+        Imposition { sheet, grid, gutter: Length::Pixels(0.0), crop_marks: false, format: CertificateFormat::Png }
+    }
+
+    pub fn gutter(mut self, gutter: Length) -> Self {
+        self.gutter = gutter;
+        self
+    }
+
+    pub fn crop_marks(mut self, crop_marks: bool) -> Self {
+        self.crop_marks = crop_marks;
+        self
+    }
+
+    pub fn format(mut self, format: CertificateFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Check the gutter; the fit is only known once the certificates' size is.
+    pub fn validate(&self) -> Result<()> {
+        let scale = TemplateScale::pixels(self.sheet.pixels(ASSUMED_DPI));
+        self.gutter.resolve_or_zero(&scale).map_err(|e| CertError::InvalidImposition(format!("gutter: {}", e)))?;
+        Ok(())
+    }
+
+    /// One line for plans and summaries, like "1x2 on A4 at 300 DPI, 5mm gutters, crop marks".
+    pub fn describe(&self) -> String {
+        let mut description = format!("{} on {}", self.grid, self.sheet);
+        match &self.gutter {
+            Length::Pixels(pixels) if *pixels == 0.0 => {}
+            Length::Pixels(pixels) => description.push_str(&format!(", {} px gutters", pixels)),
+            Length::Physical(text) => description.push_str(&format!(", {} gutters", text.trim())),
+        }
+        if self.crop_marks {
+            description.push_str(", crop marks");
+        }
+        description
+    }
+
+    /// Check that a grid of certificates of `certificate` pixels, rendered at
+    /// `certificate_dpi`, fits on the sheet with its gutters.
+    pub fn check_fit(&self, certificate: (u32, u32), certificate_dpi: f32) -> Result<()> {
+        self.geometry(certificate, certificate_dpi).map(|_| ())
+    }
+
+    /// Lay `certificates`, rendered at `certificate_dpi`, out on as many sheets as they need.
+    /// They must all be the same size.
+    pub fn impose(&self, certificates: &[RgbaImage], certificate_dpi: f32) -> Result<Vec<RgbaImage>> {
+        let Some(first) = certificates.first() else {
+            return Ok(Vec::new());
+        };
+        let geometry = self.geometry(first.dimensions(), certificate_dpi)?;
+        certificates.chunks(self.grid.cells())
+            .map(|sheet| {
+                let placed: Vec<_> = sheet.iter().enumerate().map(|(index, certificate)| (format!("certificate {}", index + 1), certificate)).collect();
+                geometry.draw(&placed)
+            })
+            .collect()
+    }
+
+    // Where everything goes on a sheet, for certificates of `certificate` pixels at `certificate_dpi`
+    fn geometry(&self, certificate: (u32, u32), certificate_dpi: f32) -> Result<SheetGeometry> {
+        let dpi = self.sheet.dpi(certificate_dpi);
+        let sheet = self.sheet.pixels(certificate_dpi);
+        let scale = dpi as f64 / certificate_dpi as f64;
+        let cell = ((certificate.0 as f64 * scale).round() as u32, (certificate.1 as f64 * scale).round() as u32);
+        let gutter = self.gutter.resolve_or_zero(&TemplateScale::new(sheet, Some(dpi), None))
+            .map_err(|e| CertError::InvalidImposition(format!("gutter: {}", e)))?
+            .round() as u32;
+
+        let grid = self.grid.size(cell, gutter);
+        if grid.0 > sheet.0 as u64 || grid.1 > sheet.1 as u64 {
+            let mm = |pixels: u64| pixels_to_mm(pixels as f64, dpi);
+            return Err(CertError::InvalidImposition(format!(
+                "a {} grid of {:.0}x{:.0} mm certificates{} is {:.0}x{:.0} mm, larger than the {:.0}x{:.0} mm sheet ({})",
+                self.grid, mm(cell.0 as u64), mm(cell.1 as u64),
+                if gutter > 0 { format!(" with {:.1} mm gutters", mm(gutter as u64)) } else { String::new() },
+                mm(grid.0), mm(grid.1), mm(sheet.0 as u64), mm(sheet.1 as u64), self.sheet
+            )));
+        }
+
+        let crop_marks = self.crop_marks.then(|| CropMarks {
+            offset: mm_to_pixels(CROP_MARK_OFFSET_MM, dpi).round() as i64,
+            length: mm_to_pixels(CROP_MARK_LENGTH_MM, dpi).round() as i64,
+            weight: (points_to_pixels(CROP_MARK_WEIGHT_PT, dpi).round() as i64).max(1),
+        });
+        Ok(SheetGeometry {
+            grid: self.grid,
+            sheet,
+            dpi,
+            certificate,
+            cell,
+            gutter,
+            origin: ((sheet.0 as u64 - grid.0) / 2, (sheet.1 as u64 - grid.1) / 2),
+            crop_marks,
+        })
+    }
+}
+
+/// What [`impose_batch`] wrote.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImposedSheets {
+    /// The sheet PNGs, or the one PDF holding them all.
+    pub files: Vec<PathBuf>,
+    pub sheets: usize,
+    pub certificates: usize,
+    /// Cells left empty on the last sheet of each group.
+    pub empty_cells: usize,
+    /// Width and height of a sheet in pixels.
+    pub sheet_size: (u32, u32),
+    pub dpi: f32,
+}
+
+/// Put the certificates of `entries`, PNGs rendered at `certificate_dpi`, on sheets saved in
+/// `output_dir` and recorded in its manifest. Sheets follow the distribution order, each group
+/// starting a new one.
+pub fn impose_batch(entries: &[ManifestEntry], output_dir: impl AsRef<Path>, imposition: &Imposition, certificate_dpi: f32) -> Result<ImposedSheets> {
+    let output_dir = output_dir.as_ref();
+    if let Some(entry) = entries.iter().find(|entry| !entry.output_file.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("png"))) {
+        return Err(CertError::InvalidImposition(format!(
+            "{} isn't a PNG; sheets are made from PNG certificates, so generate them with --format png (the sheets can still be a PDF)",
+            entry.output_file.display()
+        )));
+    }
+    let groups = entries_by_group(entries);
+    let sheets: Vec<&[&ManifestEntry]> = groups.iter().flat_map(|(_, members)| members.chunks(imposition.grid.cells())).collect();
+    let Some(first) = sheets.first().and_then(|sheet| sheet.first()) else {
+        return Err(CertError::InvalidImposition("the batch has no certificates to put on sheets".to_string()));
+    };
+    let certificate = image::image_dimensions(&first.output_file)
+        .map_err(|source| CertError::ImageDecode { path: first.output_file.clone(), source })?;
+    let geometry = imposition.geometry(certificate, certificate_dpi)?;
+
+    let draw = |members: &[&ManifestEntry]| -> Result<RgbaImage> {
+        let certificates = members.iter()
+            .map(|entry| Ok((entry.output_file.display().to_string(), load_template(&entry.output_file)?)))
+            .collect::<Result<Vec<_>>>()?;
+        let placed: Vec<_> = certificates.iter().map(|(label, certificate)| (label.clone(), certificate)).collect();
+        geometry.draw(&placed)
+    };
+    let files = match imposition.format {
+        CertificateFormat::Png => sheets.par_iter().enumerate()
+            .map(|(index, members)| {
+                let path = output_dir.join(format!("{}{:04}.png", SHEET_PREFIX, index + 1));
+                write_sheet_png(&draw(members)?, geometry.dpi, &path)?;
+                Ok(path)
+            })
+            .collect::<Result<Vec<_>>>()?,
+        CertificateFormat::Pdf => {
+            let path = output_dir.join(SHEETS_PDF_FILENAME);
+            let pdf = pages_to_pdf(sheets.len(), sheets.iter().map(|members| draw(members)), geometry.dpi)?;
+            std::fs::write(&path, pdf).io_context(|| format!("Failed to write {}", path.display()))?;
+            vec![path]
+        }
+    };
+    record_artifacts(output_dir, &files)?;
+
+    Ok(ImposedSheets {
+        files,
+        sheets: sheets.len(),
+        certificates: entries.len(),
+        empty_cells: sheets.iter().map(|members| imposition.grid.cells() - members.len()).sum(),
+        sheet_size: geometry.sheet,
+        dpi: geometry.dpi,
+    })
+}
+
+// Sizes in sheet pixels
+struct SheetGeometry {
+    grid: Grid,
+    sheet: (u32, u32),
+    dpi: f32,
+    // A certificate as rendered, and the cell it is scaled to
+    certificate: (u32, u32),
+    cell: (u32, u32),
+    gutter: u32,
+    // The grid's top-left corner
+    origin: (u64, u64),
+    crop_marks: Option<CropMarks>,
+}
+
+struct CropMarks {
+    offset: i64,
+    length: i64,
+    weight: i64,
+}
+
+impl SheetGeometry {
+    // A sheet with `certificates` in its first cells; each comes with a label for errors
+    fn draw(&self, certificates: &[(String, &RgbaImage)]) -> Result<RgbaImage> {
+        let mut sheet = RgbaImage::from_pixel(self.sheet.0, self.sheet.1, PAPER);
+        let corners: Vec<(i64, i64)> = (0..certificates.len())
+            .map(|index| {
+                let (x, y) = self.grid.cell_origin(index, self.cell, self.gutter);
+                ((self.origin.0 + x) as i64, (self.origin.1 + y) as i64)
+            })
+            .collect();
+        if let Some(marks) = &self.crop_marks {
+            for &(x, y) in &corners {
+                self.draw_crop_marks(&mut sheet, marks, x, y);
+            }
+        }
+        for ((label, certificate), (x, y)) in certificates.iter().zip(corners) {
+            if certificate.dimensions() != self.certificate {
+                return Err(CertError::InvalidImposition(format!(
+                    "{} is {}x{}, but the batch's certificates are {}x{}; a sheet only holds certificates of one size",
+                    label, certificate.width(), certificate.height(), self.certificate.0, self.certificate.1
+                )));
+            }
+            let scaled = if self.cell == self.certificate {
+                Cow::Borrowed(*certificate)
+            } else {
+                Cow::Owned(imageops::resize(*certificate, self.cell.0, self.cell.1, FilterType::Lanczos3))
+            };
+            imageops::overlay(&mut sheet, scaled.as_ref(), x, y);
+        }
+        Ok(sheet)
+    }
+
+    // Two marks at each corner of the cell at (x, y), continuing its edges outwards: the
+    // horizontal ones only left and right of the grid, the vertical ones only above and below
+    fn draw_crop_marks(&self, sheet: &mut RgbaImage, marks: &CropMarks, x: i64, y: i64) {
+        let (width, height) = (self.cell.0 as i64, self.cell.1 as i64);
+        let half = marks.weight / 2;
+        let (grid_width, grid_height) = self.grid.size(self.cell, self.gutter);
+        let (left, top) = (self.origin.0 as i64, self.origin.1 as i64);
+        let grid = (left, top, left + grid_width as i64, top + grid_height as i64);
+        let mut fill = |x0: i64, y0: i64, x1: i64, y1: i64, horizontal: bool| {
+            for y in y0.max(0)..y1.min(sheet.height() as i64) {
+                for x in x0.max(0)..x1.min(sheet.width() as i64) {
+                    let outside = if horizontal { !(grid.0..grid.2).contains(&x) } else { !(grid.1..grid.3).contains(&y) };
+                    if outside {
+                        sheet.put_pixel(x as u32, y as u32, MARK);
+                    }
+                }
+            }
+        };
+        for (edge_x, outward_x) in [(x, -1), (x + width, 1)] {
+            for (edge_y, outward_y) in [(y, -1), (y + height, 1)] {
+                let (near_x, far_x) = (edge_x + outward_x * marks.offset, edge_x + outward_x * (marks.offset + marks.length));
+                let (near_y, far_y) = (edge_y + outward_y * marks.offset, edge_y + outward_y * (marks.offset + marks.length));
+                fill(near_x.min(far_x), edge_y - half, near_x.max(far_x), edge_y - half + marks.weight, true);
+                fill(edge_x - half, near_y.min(far_y), edge_x - half + marks.weight, near_y.max(far_y), false);
+            }
+        }
+    }
+}
+
+// Save a sheet as PNG with its DPI recorded, so it prints at its physical size
+fn write_sheet_png(sheet: &RgbaImage, dpi: f32, path: &Path) -> Result<()> {
+    let encode_error = |e: png::EncodingError| CertError::ImageEncode {
+        path: path.to_path_buf(),
+        source: image::ImageError::Encoding(image::error::EncodingError::new(image::ImageFormat::Png.into(), e)),
+    };
+    let file = std::fs::File::create(path).io_context(|| format!("Failed to create {}", path.display()))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), sheet.width(), sheet.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let pixels_per_metre = mm_to_pixels(1000.0, dpi).round() as u32;
+    encoder.set_pixel_dims(Some(png::PixelDimensions { xppu: pixels_per_metre, yppu: pixels_per_metre, unit: png::Unit::Meter }));
+    let mut writer = encoder.write_header().map_err(encode_error)?;
+    writer.write_image_data(sheet.as_raw()).map_err(encode_error)?;
+    writer.finish().map_err(encode_error)
+}
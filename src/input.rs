@@ -0,0 +1,86 @@
+// src/input.rs
+// Reading answers from stdin, shared by every interactive flow
+use certificate_maker::say;
+use anyhow::Result;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+// Ways a prompt can end without an answer; flows propagate them with `?`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interrupt {
+    // "back" or "b": return to the previous step
+    Back,
+    // "cancel": abort the flow and return to the main menu
+    Cancel,
+    // stdin is closed (Ctrl+D, end of a pipe)
+    Closed,
+}
+
+impl fmt::Display for Interrupt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Interrupt::Back => write!(f, "went back"),
+            Interrupt::Cancel => write!(f, "cancelled"),
+            Interrupt::Closed => write!(f, "input closed"),
+        }
+    }
+}
+
+impl std::error::Error for Interrupt {}
+
+pub fn interrupt_of(error: &anyhow::Error) -> Option<Interrupt> {
+    error.downcast_ref::<Interrupt>().copied()
+}
+
+pub fn is_interrupt(error: &anyhow::Error) -> bool {
+    interrupt_of(error).is_some()
+}
+
+// Print a prompt and read one line; Ok(None) means stdin reached EOF
+pub fn get_user_input(prompt: &str) -> io::Result<Option<String>> {
+    print!("{}", prompt);
+    io::stdout().flush()?;
+
+    let mut bytes = Vec::new();
+    if io::stdin().lock().read_until(b'\n', &mut bytes)? == 0 {
+        return Ok(None);
+    }
+
+    let input = match String::from_utf8(bytes) {
+        Ok(input) => input,
+        Err(e) => {
+            say!("⚠️ Input was not valid UTF-8; invalid characters were replaced");
+            String::from_utf8_lossy(e.as_bytes()).into_owned()
+        }
+    };
+    Ok(Some(input.trim().to_string()))
+}
+
+// Like get_user_input, but EOF, "back" and "cancel" become Interrupt errors so flows can use `?`
+pub fn ask(prompt: &str) -> Result<String> {
+    let input = get_user_input(prompt)?.ok_or(Interrupt::Closed)?;
+    match input.to_lowercase().as_str() {
+        "back" | "b" => Err(Interrupt::Back.into()),
+        "cancel" => Err(Interrupt::Cancel.into()),
+        _ => Ok(input),
+    }
+}
+
+// Run steps in order from `start`; Back re-runs the previous step, Back on the first step cancels
+pub fn run_steps<S: Copy>(steps: &[S], start: usize, mut run: impl FnMut(S) -> Result<()>) -> Result<()> {
+    let mut index = start;
+    while index < steps.len() {
+        match run(steps[index]) {
+            Ok(()) => index += 1,
+            Err(e) if interrupt_of(&e) == Some(Interrupt::Back) => {
+                if index == 0 {
+                    return Err(Interrupt::Cancel.into());
+                }
+                say!("↩️ Going back...");
+                index -= 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
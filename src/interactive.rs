@@ -0,0 +1,2875 @@
+// src/interactive.rs
+// Prompt-driven flows for the menu, built on the certificate_maker library
+use anyhow::Result;
+use image::{ImageFormat, Rgba, RgbaImage};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use certificate_maker::audit::{DUPLICATES_DIR, audit_directory, set_aside};
+use certificate_maker::analysis::{ImageAnalysis, PrintTarget, analyze_image_file, analyze_image_files, check_print_readiness, decode_and_analyze};
+use certificate_maker::calibration::render_calibration_sheet;
+use certificate_maker::depth::{DepthPolicy, is_deep};
+use certificate_maker::csvexcelparser::{
+    BatchEstimate, BatchReport, BatchSettings, CsvFormat, DRY_RUN_REPORT_FILENAME, MergedCsv, ParsedCsv, RecentColors, check_text_region, check_text_region_on, estimate_batch_for_template, largest_text_box,
+    list_csv_files, list_template_files, name_list_url, parse_multi_selection, parse_name_list_with, preview_csv,
+    read_csv_text,
+    run_batch,
+};
+use certificate_maker::duplicates::{
+    DuplicateGroup, DuplicatePolicy, DuplicateResolution, ResolvedNames, resolve_duplicates,
+};
+use certificate_maker::error::CertError;
+use certificate_maker::fontnames::{find_font, list_font_faces};
+use certificate_maker::fontsize::{FontSizeLimits, check_template_font_size};
+use certificate_maker::graphics::GraphicField;
+use certificate_maker::files::{OutputDirCheck, check_output_dir, list_files_with_extensions, local_staging_dir, network_file_system, next_free_path};
+use certificate_maker::job::{Anchor, CertificateFormat, CertificateJob, OverwritePolicy, PngCompression, check_position};
+use certificate_maker::editpng::{
+    BUILTIN_FONT, BorderLine, BorderSides, BorderStyle, SyntheticStyle, TextOptions, TextOverflow, draw_text, real_style_for, hex_to_rgba,
+    list_available_fonts, load_font, load_font_data, render_certificate, save_image,
+};
+use certificate_maker::layout::{LAYOUT_EXTENSIONS, Layout, check_layout_file, load_layout, save_mapping};
+use certificate_maker::manifest::{MANIFEST_FILENAME, Manifest, plan_undo, remove_stale_outputs};
+use certificate_maker::mapping::{ColumnMapping, MAPPINGS_FILE, MappingStore, suggest_column};
+use certificate_maker::memory::format_size;
+use certificate_maker::namecase::NameCase;
+use certificate_maker::typography::Typography;
+use certificate_maker::normalize::Normalization;
+use certificate_maker::fingerprint::FingerprintMode;
+use certificate_maker::orientation::oriented_dimensions;
+use certificate_maker::imposition::Imposition;
+use certificate_maker::watermark::WatermarkStyle;
+use certificate_maker::effects::{effect_names, find_effect};
+use certificate_maker::palette::{PALETTE_PREFIX, check_color, installed as installed_palette, to_hex};
+use certificate_maker::patch::{Patch, PatchFill, apply_patch, apply_patches};
+use certificate_maker::paths::AppPaths;
+use certificate_maker::perftest::{perf_test, sample_names};
+use certificate_maker::plan::{BatchPlan, execute_plan, plan_batch};
+use certificate_maker::progress::ProgressSink;
+use certificate_maker::placeholder::{DEFAULT_PLACEHOLDER, erase_placeholder, find_placeholder, mark_placeholder};
+use certificate_maker::runlog::{LoggedProgress, RunLog, utc_timestamp};
+use certificate_maker::selection::{RowSelection, Selected};
+use certificate_maker::shard::{SHARD_MANIFEST_PREFIX, Shard, execute_shard, merge_shard_manifests, shard_manifests_in};
+use certificate_maker::variation::{
+    AxisValue, VariationAxis, check_axis_values, format_axis_values, load_font_instance, named_instances, parse_axis_values, variation_axes,
+};
+use certificate_maker::source::NameColumn;
+use certificate_maker::stamp::{StampJob, StampStyle};
+use certificate_maker::templates::{check_template, import_template, remove_template};
+use certificate_maker::verify::{verify_directory, verify_file};
+use certificate_maker::webhook::{BatchNotification, WebhookSettings, content_run_id, new_run_id, notify_batch};
+use certificate_maker::{say, tr};
+
+use crate::input::{Interrupt, ask, interrupt_of, is_interrupt, run_steps};
+use crate::mailing::email_certificates;
+use crate::display::{
+    ConsoleProgress, NAMES_SHOWN, print_stamp_plan, print_stamp_summary, print_analysis, print_batch_plan, print_calibration_sheet, print_batch_summary, print_csv_format, print_dry_run_summary, print_font_size_check, print_plan_summary, print_print_readiness,
+    print_comparison_table, print_duplicate_groups, print_malformed_rows, print_name_case_preview, print_name_overview, print_names_from, print_parsed_csv,
+    print_audit_report, print_directory_verification, print_duplicate_set, print_effect_choices, print_layout, print_palette, print_perf_test, print_placeholder, print_verification,
+};
+
+// Entries matching typed text: an exact name, else names starting with it, else names containing it
+// Non-UTF-8 names are matched by their display form but returned untouched
+fn match_items<'a>(items: &'a [PathBuf], input: &str) -> Vec<&'a PathBuf> {
+    // Spacing and the dash between a font's family and style don't have to be typed
+    let simplify = |text: &str| text.to_lowercase().split_whitespace().filter(|word| *word != "—").collect::<Vec<_>>().join(" ");
+    let needle = simplify(input);
+    let lowercase = |item: &PathBuf| simplify(&item.to_string_lossy());
+    if let Some(exact) = items.iter().find(|item| lowercase(item) == needle) {
+        return vec![exact];
+    }
+
+    let prefixed: Vec<&PathBuf> = items.iter().filter(|item| lowercase(item).starts_with(&needle)).collect();
+    if !prefixed.is_empty() {
+        return prefixed;
+    }
+    items.iter().filter(|item| lowercase(item).contains(&needle)).collect()
+}
+
+#[cfg(feature = "remote")]
+fn print_numbered<S: AsRef<str>>(items: &[S]) {
+    for (i, item) in items.iter().enumerate() {
+        println!("  {}. {}", i + 1, item.as_ref());
+    }
+}
+
+fn print_numbered_paths<P: AsRef<Path>>(items: &[P]) {
+    for (i, item) in items.iter().enumerate() {
+        println!("  {}. {}", i + 1, item.as_ref().display());
+    }
+}
+
+// List the items and keep asking until one is picked by number, name or a unique part of a name
+// Several partial matches narrow the list; an empty answer shows the full list again
+pub fn select_from_list(label: &str, items: &[PathBuf]) -> Result<PathBuf> {
+    let mut selected = pick_from_list(label, items, false)?;
+    Ok(selected.remove(0))
+}
+
+// Like select_from_list, also accepting "1,3,4", "all" or an https:// URL when `many` is set
+fn pick_from_list(label: &str, items: &[PathBuf], many: bool) -> Result<Vec<PathBuf>> {
+    print_numbered_paths(items);
+
+    let mut shown: Vec<&PathBuf> = items.iter().collect();
+    let hint = match many {
+        true if cfg!(feature = "remote") => tr!("number, name, part of a name, '1,3,4', 'all' or an https:// URL"),
+        true => tr!("number, name, part of a name, '1,3,4' or 'all'"),
+        false => tr!("enter number, name or part of a name"),
+    };
+    loop {
+        let input = ask(&tr!("\nSelect {} ({}): ", label, hint))?;
+
+        if input.is_empty() {
+            shown = items.iter().collect();
+            print_numbered_paths(&shown);
+            continue;
+        }
+
+        if many && let Some(url) = name_list_url(Path::new(&input)) {
+            if cfg!(feature = "remote") {
+                say!("✅ Selected {}: {}", label, url);
+                return Ok(vec![PathBuf::from(url)]);
+            }
+            println!("❌ {}", CertError::RemoteDisabled(url.to_string()));
+            continue;
+        }
+
+        // Several at once, numbered like the list currently on screen
+        if many && (input.contains(',') || input.eq_ignore_ascii_case("all")) {
+            match parse_multi_selection(&input, shown.len()) {
+                Some(picked) => {
+                    let selected: Vec<PathBuf> = picked.iter().map(|&index| shown[index].clone()).collect();
+                    say!("✅ Selected {} files:", selected.len());
+                    print_numbered_paths(&selected);
+                    return Ok(selected);
+                }
+                None => say!("❌ Enter numbers from the list separated by commas, like 1,3"),
+            }
+            continue;
+        }
+
+        // Numbers refer to the list currently on screen
+        if let Ok(num) = input.parse::<usize>()
+            && num > 0 && num <= shown.len() {
+            let selected = shown[num - 1].clone();
+            say!("✅ Selected {}: {}", label, selected.display());
+            return Ok(vec![selected]);
+        }
+
+        match match_items(items, &input).as_slice() {
+            [] => say!("❌ Nothing matches '{}'. Please try again.", input),
+            [only] => {
+                say!("✅ Selected {}: {}", label, only.display());
+                return Ok(vec![only.to_path_buf()]);
+            }
+            matches => {
+                say!("🔎 {} entries match '{}':", matches.len(), input);
+                shown = matches.to_vec();
+                print_numbered_paths(&shown);
+            }
+        }
+    }
+}
+
+// Function to select CSV file interactively
+pub fn select_csv_file(paths: &AppPaths) -> Result<PathBuf> {
+    say!("\n📄 Available CSV Files in '{}' directory:", paths.csvs.display());
+    let csv_files = list_csv_files(&paths.csvs)?;
+    let selected_file = select_from_list(&tr!("CSV file"), &csv_files)?;
+    Ok(paths.csvs.join(selected_file))
+}
+
+// Pick one or more CSV files to merge into a batch
+pub fn select_csv_files(paths: &AppPaths) -> Result<Vec<PathBuf>> {
+    say!("\n📄 Available CSV Files in '{}' directory:", paths.csvs.display());
+    let csv_files = match list_csv_files(&paths.csvs) {
+        // A URL can still be entered
+        Err(CertError::DirectoryNotFound { .. } | CertError::NoFilesFound { .. }) if cfg!(feature = "remote") => {
+            say!("  (none, but an https:// URL works)");
+            Vec::new()
+        }
+        result => result?,
+    };
+    let selected = pick_from_list(&tr!("CSV file"), &csv_files, true)?;
+    Ok(selected.into_iter()
+        .map(|file| if name_list_url(&file).is_some() { file } else { paths.csvs.join(file) })
+        .collect())
+}
+
+// Function to select template file interactively
+pub fn select_template_file(paths: &AppPaths) -> Result<PathBuf> {
+    say!("\n🖼️ Available Template Files in '{}' directory:", paths.templates.display());
+    let template_files = list_template_files(&paths.templates)?;
+    let selected_file = select_from_list(&tr!("template"), &template_files)?;
+    Ok(paths.templates.join(selected_file))
+}
+
+// Function to select font file interactively; returns the font's full path
+// Fonts are listed by family and style, with the filename after them, and can be typed as either
+pub fn select_font_file(paths: &AppPaths) -> Result<PathBuf> {
+    let faces = list_font_faces(&paths.fonts)?;
+    if faces.len() == 1 && faces[0].file == Path::new(BUILTIN_FONT) {
+        say!("\n⚠️ No font files in '{}' directory, only the built-in font is available:", paths.fonts.display());
+    } else {
+        say!("\n🔤 Available Font Files in '{}' directory:", paths.fonts.display());
+    }
+    let labels: Vec<PathBuf> = faces.iter().map(|face| PathBuf::from(face.label())).collect();
+    let selected = select_from_list(&tr!("font"), &labels)?;
+    let index = labels.iter().position(|label| *label == selected).unwrap_or_default();
+    Ok(paths.font(&faces[index].file))
+}
+
+// The font named by --font, when it names exactly one
+fn font_from_flag(draft: &BatchDraft, paths: &AppPaths) -> Option<PathBuf> {
+    let query = draft.flags.font.as_deref()?;
+    match find_font(&paths.fonts, query) {
+        Ok(font) => {
+            say!("✅ Font: {}", font.display());
+            Some(font)
+        }
+        Err(e) => {
+            say!("❌ --font: {}", e);
+            None
+        }
+    }
+}
+
+// A variable font asks for a named instance, a weight or axis values; static fonts ask nothing
+pub fn select_font_variation(font: &Path) -> Result<Vec<AxisValue>> {
+    let Ok(data) = load_font_data(font) else {
+        return Ok(Vec::new());
+    };
+    let axes: Vec<VariationAxis> = variation_axes(&data).into_iter().filter(|axis| !axis.hidden).collect();
+    if axes.is_empty() {
+        return Ok(Vec::new());
+    }
+    say!("\n🎚️ {} is a variable font:", font.display());
+    for axis in &axes {
+        say!("  • {} ({}): {} to {}, default {}", axis.name, axis.tag, axis.min, axis.max, axis.default);
+    }
+    let instances = named_instances(&data);
+    if !instances.is_empty() {
+        say!("Named instances:");
+        for (i, instance) in instances.iter().enumerate() {
+            println!("  {}. {} ({})", i + 1, instance.name, format_axis_values(&instance.coordinates));
+        }
+    }
+    let has_weight = axes.iter().any(|axis| axis.tag == "wght");
+
+    loop {
+        let input = ask(&tr!("Instance number, a weight like 650, values like wght=700 wdth=85, or Enter for the default: "))?;
+        if input.is_empty() {
+            return Ok(Vec::new());
+        }
+        // A bare number picks an instance when there is one that far down, else sets the weight
+        let values = match input.parse::<f32>() {
+            Ok(number) if number.fract() == 0.0 && number >= 1.0 && (number as usize) <= instances.len() => {
+                Ok(instances[number as usize - 1].coordinates.clone())
+            }
+            Ok(number) if has_weight => Ok(vec![AxisValue { tag: "wght".to_string(), value: number }]),
+            _ => parse_axis_values(&input),
+        };
+        match values.and_then(|values| check_axis_values(&data, &values).map(|()| values)) {
+            Ok(values) => return Ok(values),
+            Err(e) => println!("❌ {}", e),
+        }
+    }
+}
+
+// A palette entry picked by its number or name at a color prompt, as its palette: reference
+fn palette_pick(input: &str) -> Option<String> {
+    let palette = installed_palette()?;
+    let entry = match input.trim().parse::<usize>() {
+        Ok(number) => palette.colors.get(number.checked_sub(1)?)?,
+        Err(_) => palette.get(input)?,
+    };
+    Some(entry.reference())
+}
+
+// Check a font size against the template and, when the text comes out too large or too small
+// for it, offer a size that suits it. The size to use; unchanged when the template or the font
+// can't be read, which the later steps report
+fn offer_font_size(template: &Path, font_file: &Path, text: &str, font_size: f32, limits: &FontSizeLimits) -> Result<f32> {
+    let Ok(font) = load_font(font_file) else { return Ok(font_size) };
+    let Ok(check) = check_template_font_size(template, &font, text, font_size, limits) else { return Ok(font_size) };
+    let Some(suggested) = check.suggested_size else { return Ok(font_size) };
+    print_font_size_check(&check);
+    match ask(&tr!("Use size {} instead? (Y/n): ", suggested))?.eq_ignore_ascii_case("n") {
+        true => Ok(font_size),
+        false => Ok(suggested),
+    }
+}
+
+// Colors offered by name at the color prompt, besides the palette's
+const NAMED_COLORS: [(&str, &str); 8] = [
+    ("white", "#FFFFFF"),
+    ("black", "#000000"),
+    ("red", "#FF0000"),
+    ("green", "#00FF00"),
+    ("blue", "#0000FF"),
+    ("yellow", "#FFFF00"),
+    ("orange", "#FFA500"),
+    ("purple", "#800080"),
+];
+
+// Where an entry of the color menu comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColorSource {
+    Recent,
+    Palette,
+    Named,
+}
+
+// One numbered entry of the color menu: what it's called and the color as it is recorded
+struct ColorChoice {
+    source: ColorSource,
+    name: String,
+    color: String,
+}
+
+// Recent custom colors first, then the palette's, then the named colors; a strict palette
+// leaves out everything it doesn't allow
+fn color_menu(recent: &RecentColors) -> Vec<ColorChoice> {
+    let palette = installed_palette();
+    let mut choices: Vec<ColorChoice> = recent.colors.iter()
+        .filter(|color| check_color(color).is_ok())
+        .map(|color| ColorChoice { source: ColorSource::Recent, name: String::new(), color: color.clone() })
+        .collect();
+    if let Some(palette) = palette {
+        choices.extend(palette.colors.iter().map(|entry| ColorChoice { source: ColorSource::Palette, name: entry.name.clone(), color: entry.reference() }));
+    }
+    if !palette.is_some_and(|palette| palette.strict) {
+        choices.extend(NAMED_COLORS.iter().map(|(name, hex)| ColorChoice { source: ColorSource::Named, name: name.to_string(), color: hex.to_string() }));
+    }
+    choices
+}
+
+fn print_color_menu(choices: &[ColorChoice]) {
+    let strict = installed_palette().is_some_and(|palette| palette.strict);
+    let mut shown = None;
+    for (i, choice) in choices.iter().enumerate() {
+        if shown != Some(choice.source) {
+            match choice.source {
+                ColorSource::Recent => say!("  Recent:"),
+                ColorSource::Palette if strict => say!("  Palette (only these colors are allowed):"),
+                ColorSource::Palette => say!("  Palette:"),
+                ColorSource::Named => say!("  Named colors:"),
+            }
+            shown = Some(choice.source);
+        }
+        let hex = hex_to_rgba(&choice.color).map(to_hex).unwrap_or_default();
+        println!("  {:>2}. {:<16} {}", i + 1, choice.name, hex);
+    }
+}
+
+// The color an answer names, as it is recorded, and whether it was a custom color; None when
+// it names none. Numbers pick from the menu, then palette and named colors go by name, then hex
+fn parse_color_choice(input: &str, choices: &[ColorChoice]) -> Option<(String, bool)> {
+    let input = input.trim();
+    if let Some(choice) = input.parse::<usize>().ok().and_then(|number| choices.get(number.checked_sub(1)?)) {
+        return Some((choice.color.clone(), choice.source == ColorSource::Recent));
+    }
+    if input.starts_with(PALETTE_PREFIX) {
+        return Some((input.to_string(), false));
+    }
+    if let Some(entry) = installed_palette().and_then(|palette| palette.get(input)) {
+        return Some((entry.reference(), false));
+    }
+    if let Some((_, hex)) = NAMED_COLORS.iter().find(|(name, _)| name.eq_ignore_ascii_case(input)) {
+        return Some((hex.to_string(), false));
+    }
+    hex_to_rgba(input).ok().map(|color| (to_hex(color), true))
+}
+
+// Ask for a color from the menu, by number, name or hex. Returns the color and how it is
+// recorded: `palette:<name>` for palette colors, upper-case hex otherwise. Custom colors are
+// remembered across runs and offered first next time
+pub fn get_color_from_user() -> Result<(Rgba<u8>, String)> {
+    let mut recent = RecentColors::load().unwrap_or_default();
+    let choices = color_menu(&recent);
+    say!("\n🎨 Color Options:");
+    print_color_menu(&choices);
+    let strict = installed_palette().is_some_and(|palette| palette.strict);
+    let example_name = installed_palette().and_then(|palette| palette.colors.first()).map_or("red".to_string(), |entry| entry.name.clone());
+    match strict {
+        true => say!("  • Enter a number from the list or a palette color's name"),
+        false => say!("  • Enter a number from the list, a color's name or a hex code"),
+    }
+
+    loop {
+        let input = ask(&tr!("Enter color: "))?;
+        let Some((color, custom)) = parse_color_choice(&input, &choices) else {
+            match strict {
+                true => say!("❌ '{}' isn't a color here. Enter a number from 1 to {} (e.g. 1) or a palette color's name (e.g. {})",
+                             input, choices.len(), example_name),
+                false => say!("❌ '{}' isn't a color. Enter a number from 1 to {} (e.g. 1), a color's name (e.g. {}) or a hex code, with or without # (e.g. #1A2B3C)",
+                              input, choices.len(), example_name),
+            }
+            continue;
+        };
+        match check_color(&color) {
+            Ok(rgba) => {
+                if custom {
+                    recent.remember(&color);
+                    if let Err(e) = recent.save() {
+                        say!("⚠️ Could not remember {} for next time: {}", color, e);
+                    }
+                }
+                return Ok((rgba, color));
+            }
+            Err(e) => println!("❌ {}", e),
+        }
+    }
+}
+
+// Function to list image files in a specific directory
+pub fn list_image_files_in_dir(dir_path: &Path) -> Result<Vec<PathBuf>, String> {
+    if !dir_path.exists() {
+        return Err(format!("Directory '{}' not found", dir_path.display()));
+    }
+
+    let image_files = list_files_with_extensions(dir_path, &["png", "jpg", "jpeg", "bmp", "gif"])
+        .map_err(|_| format!("Failed to read directory '{}'", dir_path.display()))?;
+
+    if image_files.is_empty() {
+        return Err(format!("No image files found in directory '{}'", dir_path.display()));
+    }
+    Ok(image_files)
+}
+
+// Typed path with surrounding quotes removed, as pasted from Explorer's "Copy as path"
+pub fn typed_path(input: &str) -> PathBuf {
+    let trimmed = input.trim();
+    let unquoted = ['"', '\''].iter()
+        .find_map(|quote| trimmed.strip_prefix(*quote).and_then(|rest| rest.strip_suffix(*quote)))
+        .unwrap_or(trimmed);
+    PathBuf::from(unquoted)
+}
+
+// Function to select input image file
+pub fn select_input_image(paths: &AppPaths) -> Result<PathBuf> {
+    select_image_in_dir(&paths.templates)
+}
+
+// Function to select an image file from any directory
+fn select_image_in_dir(base_path: &Path) -> Result<PathBuf> {
+    let image_files = list_image_files_in_dir(base_path).map_err(anyhow::Error::msg)?;
+
+    say!("\n🖼️ Available Image Files in '{}' directory:", base_path.display());
+    let selected_file = select_from_list(&tr!("image"), &image_files)?;
+    Ok(base_path.join(selected_file))
+}
+
+// Ask for a directory, then pick an image inside it
+pub fn select_image_with_dir(paths: &AppPaths, label: &str) -> Result<PathBuf> {
+    let dir = ask(&tr!("\nDirectory for the {} image (default '{}'): ", label, paths.certificates.display()))?;
+    let dir = if dir.is_empty() { paths.certificates.clone() } else { typed_path(&dir) };
+    select_image_in_dir(&dir)
+}
+
+// Function to select output file path
+fn select_output_path(paths: &AppPaths, default_name: Option<&str>) -> Result<PathBuf> {
+    say!("\n📁 Output File Options:");
+    say!("1. Save in current directory");
+    say!("2. Save in '{}' directory", paths.output.display());
+    say!("3. Custom path");
+    
+    let choice = ask(&tr!("Select option (1-3): "))?;
+    
+    let default_filename = default_name.unwrap_or("output.png");
+    
+    Ok(match choice.as_str() {
+        "1" => {
+            let filename = ask(&tr!("Enter filename (default '{}'): ", default_filename))?;
+            if filename.is_empty() {
+                PathBuf::from(default_filename)
+            } else {
+                typed_path(&filename)
+            }
+        }
+        "2" => {
+            // Create output directory if it doesn't exist
+            let _ = std::fs::create_dir_all(&paths.output);
+            let filename = ask(&tr!("Enter filename (default '{}'): ", default_filename))?;
+            let filename = if filename.is_empty() { PathBuf::from(default_filename) } else { typed_path(&filename) };
+            paths.output.join(filename)
+        }
+        "3" => loop {
+            let output_path = ask(&tr!("Enter full output path: "))?;
+            if output_path.is_empty() {
+                say!("❌ Please enter a path.");
+                continue;
+            }
+            let output_path = typed_path(&output_path);
+            if confirm_output_file(&output_path)? {
+                break output_path;
+            }
+        },
+        _ => {
+            say!("Invalid option, using default");
+            PathBuf::from(default_filename)
+        }
+    })
+}
+
+// Create `dir` if it's missing (after asking) and check files can be written there, saying how
+// much space is free; None means the user should be asked for another location
+fn prepare_output_dir(dir: &Path) -> Result<Option<OutputDirCheck>> {
+    let existed = dir.exists();
+    if !existed && ask(&tr!("📁 Directory '{}' does not exist. Create it? (Y/n): ", dir.display()))?.eq_ignore_ascii_case("n") {
+        return Ok(None);
+    }
+    let check = match check_output_dir(dir) {
+        Ok(check) => check,
+        Err(e) => {
+            println!("❌ {}", e);
+            return Ok(None);
+        }
+    };
+    let action = if existed { tr!("Writable") } else { tr!("Created") };
+    match check.free_space {
+        Some(free) => say!("✅ {}: {} ({} free)", action, dir.display(), format_size(free)),
+        None => println!("✅ {}: {}", action, dir.display()),
+    }
+    Ok(Some(check))
+}
+
+// Validate a custom output file before anything is rendered; Ok(false) means ask again
+fn confirm_output_file(path: &Path) -> Result<bool> {
+    if path.is_dir() {
+        say!("❌ '{}' is a directory; include a file name like certificate.png", path.display());
+        return Ok(false);
+    }
+
+    let parent = path.parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    if prepare_output_dir(parent)?.is_none() {
+        return Ok(false);
+    }
+
+    // Renders are always encoded as PNG, whatever the extension says
+    let is_png = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+    if !is_png {
+        say!("⚠️ '{}' does not end in .png, but the image will be saved as PNG", path.display());
+        let keep = ask(&tr!("Use this path anyway? (y/N): "))?;
+        return Ok(keep.eq_ignore_ascii_case("y"));
+    }
+    Ok(true)
+}
+
+// Ask what to do when the chosen output file is already there
+fn resolve_existing_output(path: PathBuf) -> Result<PathBuf> {
+    if !path.exists() {
+        return Ok(path);
+    }
+
+    let renamed = next_free_path(&path, Path::exists);
+    say!("\n⚠️ '{}' already exists.", path.display());
+    say!("1. Overwrite it");
+    say!("2. Save as '{}'", renamed.display());
+    say!("3. Cancel");
+    loop {
+        match ask(&tr!("Select option (1-3): "))?.as_str() {
+            "1" => return Ok(path),
+            "2" => return Ok(renamed),
+            "3" => return Err(Interrupt::Cancel.into()),
+            _ => say!("❌ Invalid option. Enter 1, 2 or 3."),
+        }
+    }
+}
+
+// One prompt (or group of prompts) of the single-image flow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SingleImageStep {
+    Image,
+    Output,
+    Text,
+    Position,
+    Font,
+    FontSize,
+    Color,
+    Border,
+    Advanced,
+}
+
+const SINGLE_IMAGE_STEPS: [SingleImageStep; 9] = [
+    SingleImageStep::Image,
+    SingleImageStep::Output,
+    SingleImageStep::Text,
+    SingleImageStep::Position,
+    SingleImageStep::Font,
+    SingleImageStep::FontSize,
+    SingleImageStep::Color,
+    SingleImageStep::Border,
+    SingleImageStep::Advanced,
+];
+
+// The answers of the single-image flow; the advanced ones keep the batch defaults unless asked
+#[derive(Debug, Default)]
+struct SingleImageDraft {
+    input_file: PathBuf,
+    output_file: PathBuf,
+    text: String,
+    x: i32,
+    y: i32,
+    font_filename: PathBuf,
+    font_size: f32,
+    hex_color: String,
+    border: Option<BorderStyle>,
+    anchor: Anchor,
+    max_width: Option<f32>,
+    overflow: TextOverflow,
+    effect: Option<String>,
+    synthetic: SyntheticStyle,
+    typography: Typography,
+}
+
+impl SingleImageDraft {
+    // The one-row job the batch would run for these answers, so the image is drawn by the
+    // same renderer with every option the batch has
+    fn job(&self, watermark: Option<&WatermarkStyle>) -> Result<CertificateJob> {
+        let mut job = CertificateJob::new(&self.input_file, vec![self.text.clone()])
+            .font(&self.font_filename)
+            .font_size(self.font_size)
+            .color(&self.hex_color)
+            .position(self.anchor, self.x, self.y)
+            .borders(self.border.iter().cloned().collect())
+            .overflow(self.overflow)
+            .synthetic(self.synthetic)
+            .typography(self.typography);
+        if let Some(max_width) = self.max_width {
+            job = job.max_width(max_width);
+        }
+        if let Some(name) = &self.effect {
+            job = job.effect(find_effect(name)?);
+        }
+        if let Some(watermark) = watermark {
+            job = job.watermark(watermark.clone());
+        }
+        Ok(job)
+    }
+}
+
+fn prompt_single_image_step(step: SingleImageStep, draft: &mut SingleImageDraft, paths: &AppPaths) -> Result<()> {
+    match step {
+        SingleImageStep::Image => {
+            draft.input_file = select_input_image(paths)?;
+            if !draft.input_file.exists() {
+                return Err(anyhow::anyhow!("Selected file not found: {}", draft.input_file.display()));
+            }
+        }
+        SingleImageStep::Output => {
+            // Generate default output name based on input
+            let input_stem = draft.input_file
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("output");
+            let default_output = format!("{}_with_text.png", input_stem);
+            let output_file = select_output_path(paths, Some(&default_output))?;
+            draft.output_file = resolve_existing_output(output_file)?;
+        }
+        SingleImageStep::Text => {
+            draft.text = ask(&tr!("Enter text to add: "))?;
+            if draft.text.is_empty() {
+                say!("No text entered.");
+                return Err(Interrupt::Cancel.into());
+            }
+        }
+        SingleImageStep::Position => {
+            match analyze_image_file(&draft.input_file) {
+                Ok(analysis) => print_analysis(&analysis),
+                Err(e) => say!("❌ Error analyzing file: {}", e),
+            }
+
+            // The batch's default too: the text centered on the image
+            let (width, height) = oriented_dimensions(&draft.input_file).unwrap_or((100, 100));
+            let (center_x, center_y) = (width as i32 / 2, height as i32 / 2);
+            let x_input = ask(&tr!("Enter X position (or press Enter for the center, {}): ", center_x))?;
+            draft.x = x_input.parse().unwrap_or(center_x);
+
+            let y_input = ask(&tr!("Enter Y position (or press Enter for the center, {}): ", center_y))?;
+            draft.y = y_input.parse().unwrap_or(center_y);
+        }
+        SingleImageStep::Font => {
+            draft.font_filename = select_font_file(paths)?;
+        }
+        SingleImageStep::FontSize => {
+            let font_size_input = ask(&tr!("Enter font size (default 40): "))?;
+            let font_size = if font_size_input.is_empty() {
+                40.0
+            } else {
+                font_size_input.parse().unwrap_or(40.0)
+            };
+            draft.font_size = offer_font_size(&draft.input_file, &draft.font_filename, &draft.text, font_size, &FontSizeLimits::default())?;
+        }
+        SingleImageStep::Color => {
+            let (_, color) = get_color_from_user()?;
+            draft.hex_color = color;
+        }
+        SingleImageStep::Border => {
+            draft.border = ask_border(&draft.input_file)?;
+        }
+        SingleImageStep::Advanced => {
+            if ask(&tr!("Advanced settings (alignment, width, effect, faux bold/italic, typography)? (y/N): "))?.eq_ignore_ascii_case("y") {
+                ask_advanced_text(draft)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// The text options a one-off image rarely needs, asked in turn; Enter keeps each as it is
+fn ask_advanced_text(draft: &mut SingleImageDraft) -> Result<()> {
+    say!("\n📌 Which point of the text goes at ({}, {})?", draft.x, draft.y);
+    for (i, anchor) in Anchor::ALL.iter().enumerate() {
+        println!("  {}. {}", i + 1, anchor.name());
+    }
+    draft.anchor = loop {
+        let input = ask(&tr!("Select anchor (default {}): ", draft.anchor.name()))?;
+        if input.is_empty() {
+            break draft.anchor;
+        }
+        let picked = match input.parse::<usize>() {
+            Ok(number) => number.checked_sub(1).and_then(|index| Anchor::ALL.get(index)).copied(),
+            Err(_) => Anchor::ALL.into_iter().find(|anchor| anchor.name().eq_ignore_ascii_case(input.trim())),
+        };
+        match picked {
+            Some(anchor) => break anchor,
+            None => say!("❌ Pick a number from 1 to {} or one of the names above", Anchor::ALL.len()),
+        }
+    };
+
+    draft.max_width = loop {
+        let input = ask(&tr!("Maximum text width in pixels (Enter for none): "))?;
+        if input.is_empty() {
+            break None;
+        }
+        match input.parse::<f32>() {
+            Ok(width) if width.is_finite() && width > 0.0 => break Some(width),
+            _ => say!("❌ Enter a positive number of pixels, or nothing for no limit"),
+        }
+    };
+    if draft.max_width.is_some() {
+        draft.overflow = loop {
+            let input = ask(&tr!("Text wider than that: 'shrink' it to fit or cut it short with an 'ellipsis' (default shrink): "))?;
+            if input.is_empty() {
+                break TextOverflow::Shrink;
+            }
+            match TextOverflow::ALL.into_iter().find(|overflow| overflow.name().eq_ignore_ascii_case(input.trim())) {
+                Some(overflow) => break overflow,
+                None => say!("❌ Enter 'shrink' or 'ellipsis'"),
+            }
+        };
+    }
+
+    draft.effect = ask_effect(draft.effect.clone())?;
+
+    draft.synthetic = loop {
+        let number = |input: String| if input.is_empty() { Ok(0.0) } else { input.parse::<f32>() };
+        let embolden = number(ask(&tr!("Faux bold: pixels to thicken each stroke by (Enter for none): "))?);
+        let slant = number(ask(&tr!("Faux italic: degrees to lean the letters (Enter for none): "))?);
+        let (Ok(embolden), Ok(slant)) = (embolden, slant) else {
+            say!("❌ Enter numbers, or nothing for none");
+            continue;
+        };
+        let synthetic = SyntheticStyle { embolden, slant };
+        match synthetic.validate() {
+            Ok(()) => break synthetic,
+            Err(e) => println!("❌ {}", e),
+        }
+    };
+
+    draft.typography = loop {
+        let input = ask(&tr!("Typographic cleanup: quotes, dashes, ellipses, spaces, trim or all, separated by commas (Enter for none): "))?;
+        match input.parse::<Typography>() {
+            Ok(typography) => break typography,
+            Err(e) => println!("❌ {}", e),
+        }
+    };
+    Ok(())
+}
+
+// A text effect, built in or from the palette, by number or name; Enter keeps `current`
+fn ask_effect(current: Option<String>) -> Result<Option<String>> {
+    let names = effect_names();
+    say!("\n✨ Text effects:");
+    print_effect_choices(&names);
+    let label = current.clone().unwrap_or_else(|| "none".to_string());
+    loop {
+        let input = ask(&tr!("Select effect (number or name, 0 for none, default {}): ", label))?;
+        if input.is_empty() {
+            return Ok(current);
+        }
+        if input == "0" || input.eq_ignore_ascii_case("none") {
+            return Ok(None);
+        }
+        let picked = match input.parse::<usize>() {
+            Ok(number) => number.checked_sub(1).and_then(|index| names.get(index)),
+            Err(_) => names.iter().find(|name| name.eq_ignore_ascii_case(input.trim())),
+        };
+        match picked {
+            Some(name) => return Ok(Some(name.clone())),
+            None => say!("❌ Pick a number from 0 to {} or one of the names above", names.len()),
+        }
+    }
+}
+
+// An optional frame drawn on the image before the text; asked again until it fits the image
+fn ask_border(input_file: &Path) -> Result<Option<BorderStyle>> {
+    let dimensions = oriented_dimensions(input_file).ok();
+    loop {
+        let input = ask(&tr!("Add a border? Enter 'solid' or 'double', or press Enter for none: "))?;
+        if input.is_empty() {
+            return Ok(None);
+        }
+        let Some(line) = BorderLine::from_name(&input) else {
+            say!("❌ Enter 'solid' or 'double', or nothing for no border");
+            continue;
+        };
+        let number = |input: String, default: f32| input.parse::<f32>().ok().filter(|number| *number >= 0.0).unwrap_or(default);
+        let thickness = number(ask(&tr!("Border thickness in pixels (default 12): "))?, 12.0);
+        let inset = number(ask(&tr!("Gap between the image's edges and the border in pixels (default 20): "))?, 20.0);
+        let radius = number(ask(&tr!("Corner radius in pixels (default 0, square corners): "))?, 0.0);
+        say!("Border color:");
+        let (color, _) = get_color_from_user()?;
+        let border = BorderStyle { line, color, thickness: BorderSides::all(thickness), inset, radius };
+        match dimensions.map(|(width, height)| border.validate(width, height)) {
+            Some(Err(e)) => println!("❌ {}", e),
+            _ => return Ok(Some(border)),
+        }
+    }
+}
+
+// Pick an image, text, position, font and color, then render one image, with the watermark
+// of --draft or --watermark over it
+pub fn add_text_to_single_image_interactive(paths: &AppPaths, watermark: Option<&WatermarkStyle>) -> Result<()> {
+    let mut draft = SingleImageDraft::default();
+    run_steps(&SINGLE_IMAGE_STEPS, 0, |step| prompt_single_image_step(step, &mut draft, paths))?;
+
+    say!("🎯 Placing text '{}' with its {} at ({}, {})", draft.text, draft.anchor.name(), draft.x, draft.y);
+    // The border goes under the text and the watermark over it, as in a batch
+    let img = draft.job(watermark)?.render_image(0)?;
+    save_image(&img, &draft.output_file, ImageFormat::Png)?;
+    if let Some(border) = &draft.border {
+        say!("🖼️ Added a {} border", border.line.name());
+    }
+    if let Some(watermark) = watermark {
+        say!("💧 Watermarked: {}", watermark.describe());
+    }
+
+    say!("✅ Text added with font '{}' and size {}!", draft.font_filename.display(), draft.font_size);
+    say!("📁 Output saved to: {}", draft.output_file.display());
+    Ok(())
+}
+
+// Stamp one text and logo on every image of a directory, with the batch's progress lines,
+// generation log and summary; false when any image failed
+pub fn stamp_directory(paths: &AppPaths, options: &BatchOptions, mut style: StampStyle, input_dir: PathBuf, output_dir: PathBuf, keep_format: bool) -> Result<bool> {
+    say!("🖋️ === Stamp Images ===");
+    style.font = match &options.font {
+        Some(query) => find_font(&paths.fonts, query)?,
+        None => {
+            let fonts = list_available_fonts(&paths.fonts)?;
+            match fonts.iter().find(|font| *font == Path::new("DejaVuSans.ttf")).or(fonts.first()) {
+                Some(font) => paths.font(font),
+                None => PathBuf::from(BUILTIN_FONT),
+            }
+        }
+    };
+    style.synthetic = options.synthetic.unwrap_or_default();
+    style.typography = options.typography.unwrap_or_default();
+    let job = StampJob::new(&input_dir, &output_dir, style)
+        .keep_format(keep_format)
+        .overwrite(options.overwrite)
+        .dry_run(options.dry_run);
+    if let Err(problems) = job.validate() {
+        return Err(CertError::InvalidJob(problems).into());
+    }
+    let images = job.images()?;
+    print_stamp_plan(job.style(), images.len(), &input_dir, &output_dir);
+
+    let console = ConsoleProgress::new(options.dry_run).verbose(options.verbose);
+    let log = start_run_log(paths, options);
+    let summary = match &log {
+        Some(log) => {
+            log.line(&format!("STAMP \"{}\" on {} images from {} into {}", job.style().text, images.len(), input_dir.display(), output_dir.display()));
+            job.run_with_progress(&LoggedProgress::new(log, &console))
+        }
+        None => job.run_with_progress(&console),
+    };
+    let summary = match summary {
+        Ok(summary) => summary,
+        Err(e) => {
+            if let Some(log) = &log {
+                log.failure(&e);
+            }
+            return Err(e.into());
+        }
+    };
+    print_stamp_summary(&summary, &output_dir);
+    if options.dry_run {
+        std::fs::create_dir_all(&output_dir)?;
+        let report_path = output_dir.join(DRY_RUN_REPORT_FILENAME);
+        summary.save_json(&report_path)?;
+        say!("📄 Report: {}", report_path.display());
+    }
+    if let Some(log) = &log {
+        say!("📝 Generation log: {}", log.path().display());
+        if let Some(e) = log.error() {
+            say!("⚠️ The log may be incomplete: {}", e);
+        }
+    }
+    Ok(summary.failed == 0)
+}
+
+// Function to debug CSV file contents
+pub fn debug_csv_file(file_path: &Path, format: &CsvFormat) -> Result<()> {
+    say!("\n🔍 === CSV File Debug Info ===");
+
+    // Read raw file content first, decoded the same way the parser will see it
+    let size = std::fs::metadata(file_path)
+        .map_err(|e| anyhow::anyhow!("Failed to read file: {} ({})", file_path.display(), e))?
+        .len();
+    let (content, detected) = read_csv_text(file_path, format)?;
+
+    say!("📄 File size: {} bytes", size);
+    // JSON and text lists have no delimiter to detect
+    let is_csv = file_path.extension().is_none_or(|ext| !["json", "txt"].iter().any(|other| ext.eq_ignore_ascii_case(other)));
+    if is_csv {
+        print_csv_format(&detected, format);
+    }
+    say!("📄 First 200 characters:");
+    println!("{}", content.chars().take(200).collect::<String>());
+
+    if content.chars().count() > 200 {
+        say!("... (truncated)");
+    }
+
+    // Count lines
+    let lines: Vec<&str> = content.lines().collect();
+    say!("📄 Total lines: {}", lines.len());
+
+    if !lines.is_empty() {
+        say!("📄 First line (header): '{}'", lines[0]);
+        if lines.len() > 1 {
+            say!("📄 Second line (first data): '{}'", lines[1]);
+        }
+    }
+
+    // Try to parse with CSV reader
+    match parse_name_list_with(file_path, &NameColumn::from_env(), format) {
+        Ok(parsed) => {
+            say!("📋 Parsed headers: {}", format!("{:?}", parsed.headers));
+            say!("📋 Number of columns: {}", parsed.headers.len());
+            print_parsed_csv(&parsed, true);
+            print_malformed_rows(&parsed);
+            print_duplicate_groups(&parsed);
+        }
+        Err(e) => {
+            say!("❌ Failed to parse: {}", e);
+            say!("💡 If the columns or characters look wrong, force them with --delimiter and --encoding");
+        }
+    }
+
+    Ok(())
+}
+
+// Function to debug template file
+pub fn debug_template_file(path: &Path) -> Result<()> {
+    say!("\n🔍 === Template File Debug Info ===");
+
+    if !path.exists() {
+        return Err(anyhow::anyhow!("Template file not found: {}", path.display()));
+    }
+
+    // Get file size
+    let metadata = std::fs::metadata(path)?;
+    say!("📄 File size: {} bytes ({} KB)", metadata.len(), format!("{:.2}", metadata.len() as f64 / 1024.0));
+
+    // Try to analyze with our existing image analysis
+    match analyze_image_file(path) {
+        Ok(analysis) => {
+            say!("✅ Template analysis:");
+            say!("  📐 Dimensions: {}x{} pixels", analysis.width, analysis.height);
+            say!("  🎨 Color type: {}", format!("{:?}", analysis.color_type));
+            say!("  📊 Suggested center coordinates: ({}, {})",
+                    analysis.center_x, analysis.center_y);
+        }
+        Err(e) => {
+            say!("❌ Failed to analyze template: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+// Check a template against the paper and resolution it will be printed at
+pub fn check_print_readiness_interactive(paths: &AppPaths, options: &BatchOptions) -> Result<()> {
+    say!("\n🖨️ === Print Readiness ===");
+    let template = select_template_file(paths)?;
+    let target = options.print.unwrap_or_default();
+    if options.print.is_none() {
+        say!("💡 Checking for {} at {} DPI; choose with --paper and --print-dpi", target.paper, format!("{:.0}", target.dpi));
+    }
+    let report = check_print_readiness(&template, &target)?;
+    print_print_readiness(&template, &report);
+    Ok(())
+}
+
+// Choices of the name case prompt, in menu order
+const NAME_CASES: [NameCase; 4] = [NameCase::Preserve, NameCase::Title, NameCase::Upper, NameCase::Lower];
+
+// One prompt (or group of prompts) of the batch setup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchStep {
+    Csv,
+    Template,
+    Position,
+    Font,
+    FontSize,
+    Color,
+    Effect,
+    OutputDir,
+    NameCase,
+}
+
+pub const BATCH_STEPS: [BatchStep; 9] = [
+    BatchStep::Csv,
+    BatchStep::Template,
+    BatchStep::Position,
+    BatchStep::Font,
+    BatchStep::FontSize,
+    BatchStep::Color,
+    BatchStep::Effect,
+    BatchStep::OutputDir,
+    BatchStep::NameCase,
+];
+
+// What happens to the certificates once a batch is done
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmailMode {
+    // Email each one to its recipient
+    Send,
+    // Only print what would be sent
+    DryRun,
+}
+
+// Batch choices made on the command line instead of at the prompts
+#[derive(Debug, Clone, Default)]
+pub struct BatchOptions {
+    // --overwrite, --rename or --skip-existing
+    pub overwrite: OverwritePolicy,
+    // --name-column <header>
+    pub name_column: Option<String>,
+    // --delimiter <char> and --encoding <label>
+    pub csv_format: CsvFormat,
+    // --duplicates <fail|keep-all|dedupe>; None asks about each group of repeated names
+    pub duplicates: Option<DuplicatePolicy>,
+    // --name-case <preserve|title|upper|lower>, offered as the default at the prompt
+    pub name_case: Option<NameCase>,
+    // --typography <quotes,dashes,ellipses,spaces,trim|all|none>
+    pub typography: Option<Typography>,
+    // --case-filenames
+    pub case_filenames: bool,
+    // --rows <range>, --head <n> or --sample <n> [--seed <s>]; None asks after the CSV is parsed
+    pub selection: Option<RowSelection>,
+    // --email or --dry-run-email (needs the `email` feature)
+    pub email: Option<EmailMode>,
+    // --dry-run: check and plan the whole batch but write no certificates
+    pub dry_run: bool,
+    // --deterministic: byte-identical output for the same inputs; rejects unseeded samples
+    pub deterministic: bool,
+    // --no-log: skip the output/<timestamp>_generation.log of each batch
+    pub no_log: bool,
+    // --verbose: list every skipped or recovered row of the list, and every value the typography
+    // pass changed as rows finish
+    pub verbose: bool,
+    // --allow-bleed: accept a name position off the template, for text placed past the edge
+    pub allow_bleed: bool,
+    // --max-text-height <percent>, --min-text-points <pt> and --min-text-pixels <px>: what counts as
+    // too large or too small a font size for the template
+    pub font_size_limits: FontSizeLimits,
+    // --strict-font-size: refuse a batch whose font size is outside those limits instead of warning
+    pub strict_font_size: bool,
+    // --stage-locally: write the certificates to a local directory and move them to the output
+    // directory at the end; offered anyway when the output directory is on a network share
+    pub stage_locally: bool,
+    // --ignore-exif-orientation: use templates and pictures as stored, for rotations already baked in
+    pub ignore_orientation: bool,
+    // --embolden <px> and --slant <deg>: faux bold and italic
+    pub synthetic: Option<SyntheticStyle>,
+    // --font <file or "Family Style">; None asks
+    pub font: Option<String>,
+    // --barcode <data> --barcode-at <x,y> [--barcode-module <px>] [--barcode-height <px>] [--barcode-text <px>]
+    pub graphics: Vec<GraphicField>,
+    // --group-by <column>: one subdirectory per value of the column
+    pub group_by: Option<String>,
+    // --compression <fast|balanced|small>; None keeps the remembered or layout's level
+    pub compression: Option<PngCompression>,
+    // --bit-depth <16|8>; None keeps the remembered depth, 16 bits for a 16-bit template
+    pub depth: Option<DepthPolicy>,
+    // --format <png|pdf>; None keeps the remembered or layout's format
+    pub format: Option<CertificateFormat>,
+    // --preview [<px>]: a JPEG preview of each certificate, 600 pixels on its longer side by default
+    pub preview: Option<u32>,
+    // --normalize <W>x<H>[:<mode>]: bring the template to that size before drawing
+    pub normalize: Option<Normalization>,
+    // --fingerprint [visible|hidden|off]: record how each certificate was made, drawn in the corner
+    // unless hidden; None keeps the remembered mode
+    pub fingerprint: Option<FingerprintMode>,
+    // --draft or --watermark <text>, adjusted by --watermark-angle/-opacity/-size/-tile/-color:
+    // drawn over every certificate of this run only
+    pub watermark: Option<WatermarkStyle>,
+    // --impose <C>x<R>, adjusted by --sheet, --gutter, --crop-marks and --sheet-format: the
+    // certificates laid out on print sheets after this run
+    pub imposition: Option<Imposition>,
+    // --memory-budget <size>: fewer worker threads so the batch fits in that much memory
+    pub memory_budget: Option<u64>,
+    // --plan-out <file>: save the batch's plan (JSON, or CSV to read) instead of generating
+    pub plan_out: Option<PathBuf>,
+    // --execute-plan <file>: render a saved JSON plan exactly as written, without the menu
+    pub execute_plan: Option<PathBuf>,
+    // --shard <i/n>: with --execute-plan, render only shard i of n for merge-manifests to combine
+    pub shard: Option<Shard>,
+    // --paper <size> and --print-dpi <dpi>: what templates are checked against for print; either
+    // checks the batch's template as it is picked, as a PDF batch always does
+    pub print: Option<PrintTarget>,
+}
+
+// Answers collected so far; earlier answers survive going back
+#[derive(Debug, Default)]
+pub struct BatchDraft {
+    pub settings: BatchSettings,
+    pub names: Vec<String>,
+    // --name-column, --delimiter, --encoding and --duplicates, applied whenever another CSV is picked
+    pub flags: BatchOptions,
+    // The template as last decoded and analyzed; see `BatchDraft::template`
+    template: Option<Rc<DecodedTemplate>>,
+    // Size of the placeholder the position was taken from, offered as the font size
+    pub placeholder_size: Option<f32>,
+}
+
+// A template decoded once for the position step, the summary's estimate, the ink check and
+// the preview, instead of each reading a possibly huge file again; patched as the batch will be
+pub struct DecodedTemplate {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    patches: Vec<Patch>,
+    pub analysis: ImageAnalysis,
+    pub image: RgbaImage,
+}
+
+impl std::fmt::Debug for DecodedTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DecodedTemplate({}, {}x{})", self.path.display(), self.image.width(), self.image.height())
+    }
+}
+
+impl BatchDraft {
+    // Nothing answered yet, apart from what the command line decided
+    pub fn new(options: &BatchOptions) -> Self {
+        BatchDraft {
+            settings: BatchSettings {
+                overwrite: options.overwrite,
+                name_column: options.name_column.clone(),
+                csv_format: options.csv_format,
+                name_case: options.name_case.unwrap_or_default(),
+                typography: options.typography.unwrap_or_default(),
+                case_filenames: options.case_filenames,
+                synthetic: options.synthetic.unwrap_or_default(),
+                graphics: options.graphics.clone(),
+                group_by: options.group_by.clone(),
+                compression: options.compression.unwrap_or_default(),
+                depth: options.depth.unwrap_or_default(),
+                format: options.format.unwrap_or_default(),
+                preview: options.preview,
+                normalize: options.normalize,
+                fingerprint: options.fingerprint.unwrap_or_default(),
+                watermark: options.watermark.clone(),
+                imposition: options.imposition.clone(),
+                dry_run: options.dry_run,
+                allow_bleed: options.allow_bleed,
+                font_size_limits: options.font_size_limits,
+                strict_font_size: options.strict_font_size,
+                stage_locally: options.stage_locally,
+                verbose: options.verbose,
+                deterministic: options.deterministic,
+                memory_budget: options.memory_budget,
+                ..BatchSettings::default()
+            },
+            names: Vec::new(),
+            flags: options.clone(),
+            template: None,
+            placeholder_size: None,
+        }
+    }
+
+    // The chosen template with its patches painted over, decoded on first use; decoded again
+    // only when another one is picked, the file changed since or the patches did
+    pub fn template(&mut self) -> Result<Rc<DecodedTemplate>> {
+        let path = &self.settings.template_file;
+        let patches = &self.settings.patches;
+        let modified = std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+        if let Some(template) = &self.template
+            && template.path == *path && template.modified == modified && template.patches == *patches {
+            return Ok(Rc::clone(template));
+        }
+        let (analysis, image) = decode_and_analyze(path)?;
+        let mut image = image.into_rgba8();
+        apply_patches(&mut image, patches)?;
+        let template = Rc::new(DecodedTemplate { path: path.clone(), modified, patches: patches.clone(), analysis, image });
+        self.template = Some(Rc::clone(&template));
+        Ok(template)
+    }
+}
+
+// Look for a placeholder the designer left on the template, in the batch's font once one is
+// chosen, else in the --font or built-in one. The match is shown on the preview image
+// before its position is used; its size becomes the suggested font size, and the placeholder
+// can be painted out on a copy of the template that the batch then uses
+fn locate_placeholder(draft: &mut BatchDraft, paths: &AppPaths) -> Result<Option<(i32, i32)>> {
+    let text = ask(&tr!("Placeholder text on the template (Enter for {}): ", DEFAULT_PLACEHOLDER))?;
+    let text = if text.is_empty() { DEFAULT_PLACEHOLDER.to_string() } else { text };
+    let font_file = if !draft.settings.font_filename.as_os_str().is_empty() {
+        draft.settings.font_filename.clone()
+    } else if let Some(font) = font_from_flag(draft, paths) {
+        font
+    } else if cfg!(feature = "embedded-font") {
+        PathBuf::from(BUILTIN_FONT)
+    } else {
+        let first = list_available_fonts(&paths.fonts)?.into_iter().next()
+            .ok_or_else(|| anyhow::anyhow!("No fonts in '{}' to look for the placeholder in", paths.fonts.display()))?;
+        paths.fonts.join(first)
+    };
+    let font = load_font(&font_file)?;
+    let template = draft.template()?;
+    say!("🔎 Looking for '{}' set in about {}...", text, font_file.display());
+    let Some(found) = find_placeholder(&template.image, &text, &font)? else {
+        say!("❌ '{}' isn't on the template, or looks too unlike it in this font; enter the position by hand", text);
+        return Ok(None);
+    };
+
+    let mut marked = template.image.clone();
+    mark_placeholder(&mut marked, &found);
+    std::fs::create_dir_all(&paths.output)?;
+    let preview_path = paths.output.join("preview.png");
+    save_image(&marked, &preview_path, ImageFormat::Png)?;
+    print_placeholder(&text, &found, &preview_path);
+    if ask(&tr!("Center the names on ({}, {})? (Y/n): ", found.x, found.y))?.eq_ignore_ascii_case("n") {
+        return Ok(None);
+    }
+    draft.placeholder_size = Some(found.font_size);
+
+    let [r, g, b, _] = found.background.0;
+    let answer = ask(&tr!("Paint the placeholder over with the background color #{} on a copy of the template? (y/N): ", format!("{:02X}{:02X}{:02X}", r, g, b)))?;
+    if answer.eq_ignore_ascii_case("y") {
+        let mut clean = template.image.clone();
+        erase_placeholder(&mut clean, &found);
+        let template_file = &draft.settings.template_file;
+        let stem = template_file.file_stem().unwrap_or_default().to_string_lossy();
+        let clean_file = template_file.with_file_name(format!("{}_clean.png", stem));
+        let clean_file = if clean_file.exists() { next_free_path(&clean_file, Path::exists) } else { clean_file };
+        save_image(&clean, &clean_file, ImageFormat::Png)?;
+        say!("🧽 Saved {} without the placeholder; this batch uses it", clean_file.display());
+        draft.settings.template_file = clean_file;
+        // The copy was made from the patched template, so it needs no patching of its own
+        draft.settings.patches.clear();
+    }
+    Ok(Some((found.x, found.y)))
+}
+
+// Offer to paint over parts of the chosen template, such as last year's date, showing each
+// patch on the preview image. The patches are painted at the start of every batch, unless
+// the patched template is saved into the templates directory as a new file for the batch
+// to use instead
+fn ask_patches(draft: &mut BatchDraft, paths: &AppPaths) -> Result<()> {
+    draft.settings.patches.clear();
+    if !ask(&tr!("Paint over part of the template first, such as last year's date? (y/N): "))?.eq_ignore_ascii_case("y") {
+        return Ok(());
+    }
+    let template = draft.template()?;
+    let (width, height) = template.image.dimensions();
+    let mut patched = template.image.clone();
+    let mut patches = Vec::new();
+    std::fs::create_dir_all(&paths.output)?;
+    let preview_path = paths.output.join("preview.png");
+    loop {
+        let input = ask(&tr!("Rectangle to paint over as x,y,width,height in pixels of the {}x{} template: ", width, height))?;
+        let numbers: Vec<&str> = input.split(',').map(str::trim).collect();
+        let rectangle = match numbers[..] {
+            [x, y, w, h] => x.parse::<i32>().ok().zip(y.parse::<i32>().ok()).zip(w.parse::<u32>().ok().zip(h.parse::<u32>().ok())),
+            _ => None,
+        };
+        let Some(((x, y), (w, h))) = rectangle else {
+            say!("❌ Enter four whole numbers, the top-left corner then the size, like 980,610,160,60");
+            continue;
+        };
+        let fill = match ask(&tr!("Fill with s (the color around it, default), c (a copy of the strip beside it) or a color like #FFFFFF: "))?.to_lowercase().as_str() {
+            "" | "s" => PatchFill::Sample,
+            "c" => PatchFill::Clone,
+            color => match color.parse() {
+                Ok(fill) => fill,
+                Err(e) => {
+                    println!("❌ {}", e);
+                    continue;
+                }
+            },
+        };
+        let patch = Patch { x, y, width: w, height: h, fill };
+        if let Err(e) = apply_patch(&mut patched, &patch) {
+            println!("❌ {}", e);
+            continue;
+        }
+        if let Some((x, y, w, h)) = patch.clamp(width, height)
+            && (x as i32, y as i32, w, h) != (patch.x, patch.y, patch.width, patch.height) {
+            say!("⚠️ Only the part on the template was painted: {}x{} at ({}, {})", w, h, x, y);
+        }
+        patches.push(patch);
+        save_image(&patched, &preview_path, ImageFormat::Png)?;
+        say!("🩹 Painted over; see {}", preview_path.display());
+        if !ask(&tr!("Paint over another part? (y/N): "))?.eq_ignore_ascii_case("y") {
+            break;
+        }
+    }
+
+    let prompt = format!("Save the patched template into '{}' as a new file, so later batches needn't patch it? (y/N): ", paths.templates.display());
+    if ask(&prompt)?.eq_ignore_ascii_case("y") {
+        let stem = draft.settings.template_file.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        let patched_file = paths.templates.join(format!("{}_patched.png", stem));
+        let patched_file = if patched_file.exists() { next_free_path(&patched_file, Path::exists) } else { patched_file };
+        std::fs::create_dir_all(&paths.templates)?;
+        save_image(&patched, &patched_file, ImageFormat::Png)?;
+        say!("💾 Saved {}; this batch uses it", patched_file.display());
+        draft.settings.template_file = patched_file;
+    } else {
+        say!("✅ {} patch(es) will be painted over the template at the start of the batch", patches.len());
+        draft.settings.patches = patches;
+    }
+    Ok(())
+}
+
+// How to find the names in a CSV: the picked header, else the aliases
+pub fn name_column_for(header: Option<&str>) -> NameColumn {
+    match header {
+        Some(header) => NameColumn::header(header),
+        None => NameColumn::from_env(),
+    }
+}
+
+// Show every column with its first values and let the user say which one holds the names
+fn pick_name_column(csv_file: &Path, format: &CsvFormat) -> Result<String> {
+    let preview = preview_csv(csv_file, 2, format)?;
+    say!("\n❓ Could not tell which column of {} holds the names:", csv_file.display());
+    for (index, header) in preview.headers.iter().enumerate() {
+        println!("  {}. {} (e.g. {})", index + 1, header, preview.column(index).join(", "));
+    }
+
+    loop {
+        let input = ask(&tr!("Select the name column (enter number): "))?;
+        match input.parse::<usize>() {
+            Ok(num) if num > 0 && num <= preview.headers.len() => {
+                let header = preview.headers[num - 1].clone();
+                say!("✅ Using column '{}' for names (remembered for repeat runs)", header);
+                return Ok(header);
+            }
+            _ => say!("❌ Please enter a number between 1 and {}", preview.headers.len()),
+        }
+    }
+}
+
+// Show one group of repeated names and ask whether to keep them all, keep the first or fix one
+// With names merged from several files, rows are numbered per file, so members are picked by position
+fn ask_duplicate_resolution(group: &DuplicateGroup, names: &[String], rows: &[usize], sources: &[PathBuf]) -> Result<DuplicateResolution> {
+    let place = |index: usize| match sources.get(index) {
+        Some(source) => format!("row {} of {}", rows[index], source.file_name().unwrap_or(source.as_os_str()).to_string_lossy()),
+        None => format!("row {}", rows[index]),
+    };
+    say!("\n⚠️ These names look like duplicates:");
+    for (position, &index) in group.indices.iter().enumerate() {
+        if sources.is_empty() {
+            say!("  Row {}: {}", rows[index], names[index]);
+        } else {
+            println!("  {}) {}: {}", position + 1, place(index), names[index]);
+        }
+    }
+    say!("  1. Keep all (later certificates get _1, _2, ... suffixes)");
+    say!("  2. Keep only the first ({})", place(group.indices[0]));
+    say!("  3. Edit one of them");
+
+    loop {
+        match ask(&tr!("Choose (1-3): "))?.as_str() {
+            "1" => return Ok(DuplicateResolution::KeepAll),
+            "2" => return Ok(DuplicateResolution::KeepFirst),
+            "3" => break,
+            _ => say!("❌ Please enter 1, 2 or 3"),
+        }
+    }
+
+    let index = if sources.is_empty() {
+        let listed: Vec<String> = group.indices.iter().map(|&index| rows[index].to_string()).collect();
+        loop {
+            let input = ask(&tr!("Row to edit ({}): ", listed.join(", ")))?;
+            match group.indices.iter().find(|&&index| rows[index].to_string() == input) {
+                Some(&index) => break index,
+                None => say!("❌ Please enter one of the row numbers above"),
+            }
+        }
+    } else {
+        loop {
+            match ask(&tr!("Name to edit (1-{}): ", group.indices.len()))?.parse::<usize>() {
+                Ok(num) if num > 0 && num <= group.indices.len() => break group.indices[num - 1],
+                _ => say!("❌ Please enter a number between 1 and {}", group.indices.len()),
+            }
+        }
+    };
+    loop {
+        let name = ask(&tr!("New name for {}: ", place(index)))?;
+        if !name.is_empty() {
+            return Ok(DuplicateResolution::Edited { row: rows[index], source: sources.get(index).cloned(), name });
+        }
+        say!("❌ The name cannot be empty");
+    }
+}
+
+// Apply --duplicates, or ask about each group of repeated names when it wasn't given
+fn resolve_batch_duplicates(names: &[String], rows: &[usize], sources: &[PathBuf], policy: Option<DuplicatePolicy>) -> Result<ResolvedNames> {
+    match policy {
+        Some(policy) => {
+            let resolved = policy.apply(names, rows, sources)?;
+            if !resolved.decisions.is_empty() {
+                say!("⚠️ {} group(s) of duplicate names resolved by --duplicates: {} of {} names kept",
+                     resolved.decisions.len(), resolved.names.len(), names.len());
+            }
+            Ok(resolved)
+        }
+        None => resolve_duplicates(names, rows, sources, |group| ask_duplicate_resolution(group, names, rows, sources)),
+    }
+}
+
+// Offer to render only part of a long list; Enter keeps every row
+// A deterministic run only takes samples with a seed, so the same rows are picked every time
+// Names shown per page when the whole list is paged through
+const NAMES_PER_PAGE: usize = 20;
+
+// Offers 'l' to page through the names when the overview left some out
+fn ask_row_selection(names: &[String], deterministic: bool) -> Result<RowSelection> {
+    let total = names.len();
+    let list = if total > NAMES_SHOWN * 2 { ", l to list them" } else { "" };
+    loop {
+        let input = ask(&tr!("Rows to generate (Enter for all {}{}, or e.g. 1..100, head 25, sample 10): ", total, list))?;
+        if input.is_empty() {
+            return Ok(RowSelection::All);
+        }
+        if !list.is_empty() && input.eq_ignore_ascii_case("l") {
+            page_names(names)?;
+            continue;
+        }
+        match input.parse() {
+            Ok(RowSelection::Sample { count, .. }) if deterministic && !input.contains("seed") => {
+                say!("❌ A deterministic run needs a seed for its sample, e.g. 'sample {} seed 42'", count);
+            }
+            Ok(selection) => return Ok(selection),
+            Err(e) => println!("❌ {}", e),
+        }
+    }
+}
+
+// Every name, a page at a time: Enter for the next page, q to stop
+fn page_names(names: &[String]) -> Result<()> {
+    let pages = names.len().div_ceil(NAMES_PER_PAGE);
+    for (page, chunk) in names.chunks(NAMES_PER_PAGE).enumerate() {
+        print_names_from(chunk, page * NAMES_PER_PAGE);
+        if page + 1 < pages && ask(&tr!("Page {} of {}; Enter for the next, q to stop: ", page + 1, pages))?.eq_ignore_ascii_case("q") {
+            break;
+        }
+    }
+    Ok(())
+}
+
+// Apply a row selection, saying what was picked and warning when it had to be clamped
+pub fn select_rows(selection: RowSelection, names: &[String]) -> Selected {
+    let selected = selection.select(names.len());
+    if let Some(warning) = &selected.warning {
+        println!("⚠️ {}", warning);
+    }
+    if selection.is_partial() {
+        say!("🎯 Selected {} of {} rows ({}); other certificates in the output directory are left alone",
+             selected.indices.len(), names.len(), selection);
+    }
+    selected
+}
+
+// Parse one CSV of the batch, asking for the name column if none is recognized
+// Only the first file's pick is remembered; a later file falls back to the usual aliases
+fn parse_batch_csv(input_file: &Path, first: bool, draft: &mut BatchDraft) -> Result<ParsedCsv> {
+    let format = draft.settings.csv_format;
+    let header = if first { draft.settings.name_column.as_deref() } else { None };
+    let parsed = match parse_name_list_with(input_file, &name_column_for(header), &format) {
+        Err(CertError::MissingColumn { .. }) => {
+            let header = pick_name_column(input_file, &format)?;
+            let parsed = parse_name_list_with(input_file, &NameColumn::header(&header), &format)?;
+            if first {
+                draft.settings.name_column = Some(header);
+            }
+            parsed
+        }
+        result => result?,
+    };
+    if let Some(detected) = &parsed.format {
+        print_csv_format(detected, &format);
+    }
+    print_parsed_csv(&parsed, draft.flags.verbose);
+    Ok(parsed)
+}
+
+// Parse the names of one or more CSVs into the draft, merged in the order given
+// Returns false when the files don't match and the user chose not to merge them
+fn load_batch_csv(input_files: Vec<PathBuf>, draft: &mut BatchDraft) -> Result<bool> {
+    let mut files = Vec::new();
+    for (index, input_file) in input_files.iter().enumerate() {
+        say!("\n📄 Parsing names from {}...", input_file.display());
+        files.push(parse_batch_csv(input_file, index == 0, draft)?);
+    }
+
+    let merged = MergedCsv { files };
+    let differences = merged.header_differences();
+    if !differences.is_empty() {
+        say!("\n⚠️ The selected CSV files don't match:");
+        for difference in &differences {
+            println!("  • {}", difference);
+        }
+        if ask(&tr!("Merge them anyway? (y/N): "))?.to_lowercase() != "y" {
+            return Ok(false);
+        }
+    }
+
+    let (names, rows) = (merged.names(), merged.rows());
+    let sources = if merged.files.len() > 1 { merged.sources() } else { Vec::new() };
+    let resolved = resolve_batch_duplicates(&names, &rows, &sources, draft.flags.duplicates)?;
+
+    print_name_overview(&resolved.names);
+    let selection = match draft.flags.selection {
+        Some(selection) => selection,
+        None => ask_row_selection(&resolved.names, draft.settings.deterministic)?,
+    };
+    let selected = select_rows(selection, &resolved.names);
+
+    draft.settings.csv_file = input_files[0].clone();
+    draft.settings.extra_csv_files = input_files[1..].to_vec();
+    draft.settings.duplicates = resolved.decisions;
+    draft.settings.rows = selected.pick(&resolved.rows);
+    draft.settings.sources = if sources.is_empty() { Vec::new() } else { selected.pick(&resolved.sources) };
+    draft.settings.emails = merged.emails(&draft.settings.rows, &draft.settings.sources);
+    draft.settings.records = merged.records(&draft.settings.rows, &draft.settings.sources);
+    draft.settings.selection = selection;
+    draft.names = selected.pick(&resolved.names);
+    Ok(true)
+}
+
+// Ask the prompts of one step, storing the answers in the draft
+pub fn prompt_batch_step(step: BatchStep, draft: &mut BatchDraft, paths: &AppPaths) -> Result<()> {
+    let settings = &mut draft.settings;
+    match step {
+        BatchStep::Csv => {
+            // Automatically look in the CSV directory and let user select one or more
+            let input_files = match select_csv_files(paths) {
+                Ok(files) => files,
+                Err(e) if is_interrupt(&e) => return Err(e),
+                Err(e) => {
+                    println!("❌ {}", e);
+                    say!("\n💡 Tips:");
+                    say!("  • Create the '{}' directory, or point CERTMAKER_CSV_DIR at your CSV folder", paths.csvs.display());
+                    say!("  • Add CSV files with a 'Name' column");
+                    say!("  • Example CSV format:");
+                    say!("    Name");
+                    say!("    Alice Johnson");
+                    say!("    Bob Smith");
+                    return Err(e);
+                }
+            };
+
+            draft.settings.name_column = draft.flags.name_column.clone();
+            draft.settings.csv_format = draft.flags.csv_format;
+            if !load_batch_csv(input_files, draft)? {
+                say!("↩️ Pick the CSV files again");
+                return prompt_batch_step(step, draft, paths);
+            }
+        }
+        BatchStep::Template => {
+            // Automatically look in the template directory and let user select
+            settings.template_file = match select_template_file(paths) {
+                Ok(file) => file,
+                Err(e) if is_interrupt(&e) => return Err(e),
+                Err(e) => {
+                    println!("❌ {}", e);
+                    say!("\n💡 Tips:");
+                    say!("  • Create the '{}' directory, or point CERTMAKER_TEMPLATE_DIR at your templates", paths.templates.display());
+                    say!("  • Add PNG/JPG template files for certificates");
+                    say!("  • Supported formats: .png, .jpg, .jpeg");
+                    return Err(e);
+                }
+            };
+            if draft.settings.format == CertificateFormat::Pdf || draft.flags.print.is_some() {
+                let target = draft.flags.print.unwrap_or_default();
+                match check_print_readiness(&draft.settings.template_file, &target) {
+                    Ok(report) => print_print_readiness(&draft.settings.template_file, &report),
+                    Err(e) => say!("⚠️ Could not check the template for print: {}", e),
+                }
+            }
+            ask_patches(draft, paths)?;
+        }
+        BatchStep::Position => {
+            // Analyze template
+            say!("\n📊 Analyzing template...");
+            let template = draft.template().inspect_err(|e| say!("⚠️ Could not analyze the template: {}", e)).ok();
+            let analysis = template.as_ref().map(|template| &template.analysis);
+            if let Some(analysis) = analysis {
+                say!("Template dimensions: {}x{} pixels", analysis.width, analysis.height);
+                say!("Suggested coordinates for centering: ({}, {})",
+                        analysis.center_x, analysis.center_y);
+            }
+
+            // Get positioning
+            let mut x_input = ask(&tr!("\nEnter X position for name (Enter for center, f to find a placeholder such as {}): ", DEFAULT_PLACEHOLDER))?;
+            if x_input.eq_ignore_ascii_case("f") {
+                if let Some((x, y)) = locate_placeholder(draft, paths)? {
+                    draft.settings.x_pos = x;
+                    draft.settings.y_pos = y;
+                    return Ok(());
+                }
+                x_input = ask(&tr!("\nEnter X position for name (or press Enter for center): "))?;
+            }
+            let mut y_input = ask(&tr!("Enter Y position for name (or press Enter for center): "))?;
+
+            // Default to center if no input
+            let (default_x, default_y) = match analysis {
+                Some(analysis) => (analysis.center_x as i32, analysis.center_y as i32),
+                None => (400, 300),
+            };
+
+            // A point off the template is almost always a typo (9600 for 960), so ask again with
+            // the ranges that fit, unless it is meant to bleed past the edge
+            let dimensions = analysis.map(|analysis| (analysis.width, analysis.height));
+            let settings = &mut draft.settings;
+            loop {
+                settings.x_pos = if x_input.is_empty() { default_x } else { x_input.parse().unwrap_or(default_x) };
+                settings.y_pos = if y_input.is_empty() { default_y } else { y_input.parse().unwrap_or(default_y) };
+                settings.allow_bleed = draft.flags.allow_bleed;
+                let Some((width, height)) = dimensions else { break };
+                match check_position(settings.x_pos, settings.y_pos, (width, height)) {
+                    Ok(()) => break,
+                    Err(_) if settings.allow_bleed => {
+                        say!("💡 ({}, {}) is off the {}x{} template; placed there anyway (--allow-bleed)", settings.x_pos, settings.y_pos, width, height);
+                        break;
+                    }
+                    Err(e) => println!("⚠️ {}", e),
+                }
+                if ask(&tr!("Place the name there anyway, bleeding past the edge on purpose? (y/N): "))?.eq_ignore_ascii_case("y") {
+                    settings.allow_bleed = true;
+                    break;
+                }
+                x_input = ask(&tr!("Enter X position for name (0 to {}, or press Enter for center): ", width.saturating_sub(1)))?;
+                y_input = ask(&tr!("Enter Y position for name (0 to {}, or press Enter for center): ", height.saturating_sub(1)))?;
+            }
+        }
+        BatchStep::Font => {
+            // --font decides the first time; editing the field from the summary asks again
+            let from_flag = if settings.font_filename.as_os_str().is_empty() { font_from_flag(draft, paths) } else { None };
+            let settings = &mut draft.settings;
+            // Font selection from the fonts directory
+            settings.font_filename = match from_flag.map_or_else(|| select_font_file(paths), Ok) {
+                Ok(font) => font,
+                Err(e) if is_interrupt(&e) => return Err(e),
+                Err(e) => {
+                    println!("❌ {}", e);
+                    say!("\n💡 Tips:");
+                    say!("  • Create the '{}' directory, or point CERTMAKER_FONTS_DIR at your fonts", paths.fonts.display());
+                    say!("  • Add font files (.ttf, .otf)");
+                    say!("  • You can download fonts from Google Fonts");
+
+                    // Fallback to manual input
+                    let manual_font = ask(&tr!("\nOr enter the path of a font file manually: "))?;
+                    if manual_font.is_empty() {
+                        return Err(anyhow::anyhow!("No font selected"));
+                    }
+                    typed_path(&manual_font)
+                }
+            };
+            settings.font_variation = select_font_variation(&settings.font_filename)?;
+        }
+        BatchStep::FontSize => {
+            // A placeholder found on the template suggests its own size
+            let (default_size, prompt) = match draft.placeholder_size {
+                Some(size) => (size, format!("Enter font size (default {}, the placeholder's): ", size)),
+                None => (40.0, "Enter font size (default 40): ".to_string()),
+            };
+            let font_size_input = ask(&prompt)?;
+            let font_size = if font_size_input.is_empty() { default_size } else { font_size_input.parse().unwrap_or(default_size) };
+            let sample = draft.names.first().map_or("", String::as_str);
+            settings.font_size = offer_font_size(&settings.template_file, &settings.font_filename, sample, font_size, &settings.font_size_limits)?;
+        }
+        BatchStep::Color => {
+            // A strict palette can't default to black unless black is one of its colors
+            let (hint, default) = match installed_palette() {
+                Some(palette) => {
+                    say!("\n🎨 Text color:");
+                    print_palette(palette);
+                    let default = match palette.strict {
+                        true => palette.colors.iter().find(|entry| entry.color == Rgba([0, 0, 0, 255])).unwrap_or(&palette.colors[0]).reference(),
+                        false => "#000000".to_string(),
+                    };
+                    (if palette.strict { tr!("a number or name from the palette") } else { tr!("a number, a name or hex like #000000") }, default)
+                }
+                None => (tr!("only hex like #000000"), "#000000".to_string()),
+            };
+            settings.hex_color = loop {
+                let color_input = ask(&tr!("Enter text color ({}, default {}): ", hint, default.trim_start_matches(PALETTE_PREFIX)))?;
+                let color = if color_input.is_empty() { default.clone() } else { palette_pick(&color_input).unwrap_or(color_input) };
+                match check_color(&color) {
+                    Ok(_) => break color,
+                    Err(e) => println!("❌ {}", e),
+                }
+            };
+        }
+        BatchStep::Effect => {
+            settings.effect = ask_effect(settings.effect.clone())?;
+        }
+        BatchStep::OutputDir => {
+            // Get output directory
+            let (output_dir, check) = loop {
+                let output_dir = ask(&tr!("\nEnter output directory (default '{}'): ", paths.certificates.display()))?;
+                let output_dir = if output_dir.is_empty() { paths.certificates.clone() } else { typed_path(&output_dir) };
+                if let Some(check) = prepare_output_dir(&output_dir)? {
+                    break (output_dir, check);
+                }
+            };
+            settings.output_dir = output_dir;
+            // Saving file after file over the network is the slow part of a batch to a share
+            settings.stage_locally = match &check.network {
+                Some(file_system) if !draft.flags.stage_locally => {
+                    say!("🌐 '{}' is on a network share ({}), where saving certificates one by one is slow",
+                         settings.output_dir.display(), file_system);
+                    !ask(&tr!("Write them to a local directory first and move them there at the end? (Y/n): "))?.eq_ignore_ascii_case("n")
+                }
+                _ => draft.flags.stage_locally,
+            };
+        }
+        BatchStep::NameCase => {
+            say!("\n🔤 How should names be cased on the certificates?");
+            for (i, case) in NAME_CASES.iter().enumerate() {
+                println!("  {}. {}", i + 1, case.label());
+            }
+            let default = NAME_CASES.iter().position(|&case| case == settings.name_case).unwrap_or(0) + 1;
+            settings.name_case = loop {
+                let input = ask(&tr!("Select name case (default {}): ", default))?;
+                match if input.is_empty() { Ok(default) } else { input.parse::<usize>() } {
+                    Ok(num) if num > 0 && num <= NAME_CASES.len() => break NAME_CASES[num - 1],
+                    _ => say!("❌ Please enter a number between 1 and {}", NAME_CASES.len()),
+                }
+            };
+            print_name_case_preview(settings.name_case, &draft.names);
+        }
+    }
+    Ok(())
+}
+
+// Walk the user through CSV, template, position, font, color and output selection
+pub fn prompt_batch_settings(paths: &AppPaths, options: &BatchOptions) -> Result<(BatchSettings, Vec<String>)> {
+    let mut draft = BatchDraft::new(options);
+    run_steps(&BATCH_STEPS, 0, |step| prompt_batch_step(step, &mut draft, paths))?;
+    Ok((draft.settings, draft.names))
+}
+
+// Interactive certificate generation with template and font selection
+pub fn generate_certificates_interactive(paths: &AppPaths, options: &BatchOptions) -> Result<()> {
+    say!("🎓 === Certificate Generator (CSV Files Only) ===");
+    say!("💡 Type 'back' at any prompt to change the previous answer, or 'cancel' to return to the menu");
+
+    let mut draft = BatchDraft::new(options);
+    run_steps(&BATCH_STEPS, 0, |step| prompt_batch_step(step, &mut draft, paths))?;
+    review_and_generate(&mut draft, paths)
+}
+
+// List, import and remove the templates in the templates directory until the user goes back
+pub fn manage_templates(paths: &AppPaths) -> Result<()> {
+    loop {
+        say!("\n🖼️ === Manage Templates ('{}') ===", paths.templates.display());
+        say!("  1. List templates");
+        say!("  2. Import a template");
+        say!("  3. Remove a template");
+        say!("  4. Back to the menu");
+        let result = match ask(&tr!("Choose an option (1-4): "))?.as_str() {
+            "1" => list_templates(paths),
+            "2" => import_template_interactive(paths),
+            "3" => remove_template_interactive(paths),
+            "4" => return Ok(()),
+            _ => {
+                say!("❌ Please enter a number between 1 and 4");
+                continue;
+            }
+        };
+        match result {
+            Ok(()) => {}
+            Err(e) if is_interrupt(&e) => return Err(e),
+            Err(e) => println!("❌ {}", e),
+        }
+    }
+}
+
+fn list_templates(paths: &AppPaths) -> Result<()> {
+    let template_paths: Vec<PathBuf> = list_template_files(&paths.templates)?.iter()
+        .map(|file| paths.templates.join(file))
+        .collect();
+    print_comparison_table(&analyze_image_files(&template_paths));
+    Ok(())
+}
+
+// Check an image, copy (or convert) it into the templates directory and analyze the result
+fn import_template_interactive(paths: &AppPaths) -> Result<()> {
+    let source = typed_path(&ask(&tr!("Path of the image to import: "))?);
+    let check = check_template(&source)?;
+    let format = check.format.map_or("unknown format".to_string(), |format| format!("{:?}", format));
+    println!("✅ {}x{} {} ({:?})", check.width, check.height, format, check.color_type);
+    for warning in &check.warnings {
+        println!("  ⚠️ {}", warning);
+    }
+
+    let bits = if is_deep(check.color_type) { 16 } else { 8 };
+    let convert = check.convertible
+        && !ask(&tr!("Convert it to a {}-bit RGBA PNG, as certificates are drawn? (Y/n): ", bits))?.eq_ignore_ascii_case("n");
+    let imported = import_template(&source, &paths.templates, convert)?;
+    say!("📥 Imported as {}", imported.display());
+
+    let analysis = analyze_image_file(&imported)?;
+    print_analysis(&analysis);
+    say!("🎯 Names are centered on ({}, {}) unless you choose another position", analysis.center_x, analysis.center_y);
+    Ok(())
+}
+
+fn remove_template_interactive(paths: &AppPaths) -> Result<()> {
+    let template = select_template_file(paths)?;
+    if !ask(&tr!("Delete {}? (y/N): ", template.display()))?.eq_ignore_ascii_case("y") {
+        say!("↩️ Nothing was deleted.");
+        return Ok(());
+    }
+    for removed in remove_template(&template)? {
+        say!("🗑️ Removed {}", removed.display());
+    }
+    Ok(())
+}
+
+// Look a family up on Google Fonts, show its license and download the chosen weights into the
+// fonts directory, where the font picker finds them
+#[cfg(feature = "remote")]
+pub fn download_font(paths: &AppPaths) -> Result<()> {
+    use certificate_maker::googlefonts::{FamilyFont, download_fonts, fetch_family};
+
+    say!("\n🔤 === Download a Font from Google Fonts ===");
+    let query = ask(&tr!("Family name (e.g. Playfair Display): "))?;
+    if query.is_empty() {
+        say!("↩️ Nothing was downloaded.");
+        return Ok(());
+    }
+    let family = match fetch_family(&query) {
+        Ok(family) => family,
+        Err(e @ (CertError::RemoteDns { .. } | CertError::RemoteConnect { .. } | CertError::RemoteTimeout { .. })) => {
+            println!("❌ {}", e);
+            say!("💡 Offline? Download it from https://fonts.google.com/specimen/{}, unzip it and copy the .ttf files into '{}'",
+                 query.split_whitespace().collect::<Vec<_>>().join("+"), paths.fonts.display());
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    say!("📜 {} is licensed under the {}", family.name, family.license_name());
+    println!("   {}", family.license_url());
+    // Variable files are listed once per named weight; offer each file once with its range
+    let mut files: Vec<(&FamilyFont, String)> = Vec::new();
+    for font in &family.fonts {
+        if files.iter().any(|(file, _)| file.filename == font.filename) {
+            continue;
+        }
+        let weights = match family.axes.iter().find(|axis| axis.tag == "wght") {
+            Some(axis) if font.is_variable() => format!("weights {}-{}", axis.min, axis.max),
+            _ => format!("weight {}", font.weight),
+        };
+        files.push((font, format!("{} ({}, {})", font.filename, weights, font.style)));
+    }
+    let labels: Vec<&str> = files.iter().map(|(_, label)| label.as_str()).collect();
+    print_numbered(&labels);
+    if family.is_variable_only() {
+        say!("💡 Variable fonts hold every weight in one file; pick the weight when you choose the font");
+    }
+
+    let regular: Vec<usize> = (0..files.len())
+        .filter(|&i| files[i].0.weight == 400 && files[i].0.style == "normal")
+        .collect();
+    let picked = loop {
+        let input = ask(&tr!("Files to download (e.g. 1,3 or 'all'; Enter for the regular weight): "))?;
+        if input.is_empty() && !regular.is_empty() {
+            break regular.clone();
+        }
+        match parse_multi_selection(&input, files.len()) {
+            Some(picked) if !picked.is_empty() => break picked,
+            _ => say!("❌ Please enter numbers between 1 and {}, separated by commas", files.len()),
+        }
+    };
+
+    if ask(&tr!("Accept the {} and download {} file(s)? (Y/n): ", family.license_name(), picked.len()))?.eq_ignore_ascii_case("n") {
+        say!("↩️ Nothing was downloaded.");
+        return Ok(());
+    }
+    let fonts: Vec<&FamilyFont> = picked.iter().map(|&i| files[i].0).collect();
+    for download in download_fonts(&family, &fonts, &paths.fonts)? {
+        if download.cached {
+            say!("⏭️ {} is already there", download.path.display());
+        } else {
+            say!("✅ Downloaded {}", download.path.display());
+        }
+    }
+    say!("🎯 {} is in the font picker now", family.name);
+    Ok(())
+}
+
+#[cfg(not(feature = "remote"))]
+pub fn download_font(paths: &AppPaths) -> Result<()> {
+    anyhow::bail!("This build can't download fonts; rebuild with `cargo run --features remote`, or copy .ttf files into '{}' yourself",
+                  paths.fonts.display())
+}
+
+// Check a certificate, or every file of an output directory, against the manifest it was
+// issued from. The manifest defaults to the one next to the file or in the directory, then to
+// the certificates directory's. Returns whether everything checked out
+pub fn verify_certificates(paths: &AppPaths, target: Option<PathBuf>, manifest: Option<PathBuf>) -> Result<bool> {
+    say!("🔏 === Verify Certificates ===");
+    let target = match target {
+        Some(target) => target,
+        None => {
+            let input = ask(&tr!("File or directory to verify (Enter for '{}'): ", paths.certificates.display()))?;
+            if input.is_empty() { paths.certificates.clone() } else { typed_path(&input) }
+        }
+    };
+    if !target.exists() {
+        anyhow::bail!("{} does not exist", target.display());
+    }
+    let manifest_path = manifest.unwrap_or_else(|| {
+        let beside = if target.is_dir() { target.clone() } else { target.parent().unwrap_or(Path::new(".")).to_path_buf() };
+        let beside = beside.join(MANIFEST_FILENAME);
+        if beside.exists() { beside } else { paths.certificates.join(MANIFEST_FILENAME) }
+    });
+    if !manifest_path.exists() {
+        anyhow::bail!("No {} at {}; pass the one the certificates were issued from with --manifest <path>", MANIFEST_FILENAME, manifest_path.display());
+    }
+    let manifest = Manifest::load_file(&manifest_path)?;
+    say!("📜 Checking against {} ({} certificates recorded)", manifest_path.display(), manifest.entries.len());
+
+    if target.is_dir() {
+        let report = verify_directory(&manifest, &target)?;
+        print_directory_verification(&report);
+        Ok(report.is_clean())
+    } else {
+        let verification = verify_file(&manifest, &target)?;
+        print_verification(&verification);
+        say!("   SHA-256 {}", verification.file_hash);
+        Ok(verification.is_verified())
+    }
+}
+
+// Report certificates under `dir` (the certificates directory by default) whose names only
+// differ in accents, case or spacing. With `fix`, ask which file of each set to keep and, once
+// confirmed, move the others into the _duplicates folder next to them; nothing is deleted
+pub fn audit_certificates(paths: &AppPaths, dir: Option<PathBuf>, fix: bool) -> Result<()> {
+    say!("👯 === Audit Near-Duplicate Certificates ===");
+    let dir = dir.unwrap_or_else(|| paths.certificates.clone());
+    let report = audit_directory(&dir)?;
+    print_audit_report(&report);
+    if report.sets.is_empty() {
+        return Ok(());
+    }
+    if !fix {
+        say!("\n💡 Run `audit {} --fix` to pick the file to keep in each set and move the others to {}/",
+             dir.display(), DUPLICATES_DIR);
+        return Ok(());
+    }
+
+    let (mut moved, mut resolved) = (0, 0);
+    for (index, set) in report.sets.iter().enumerate() {
+        say!("\n👯 Set {} of {}: '{}'", index + 1, report.sets.len(), set.key);
+        print_duplicate_set(set);
+        let count = set.files.len();
+        let keep = loop {
+            let answer = ask(&tr!("Keep which file? (1-{}, Enter for the suggested 1, s to skip): ", count))?;
+            if answer.is_empty() {
+                break Some(0);
+            }
+            if answer.eq_ignore_ascii_case("s") {
+                break None;
+            }
+            match answer.parse::<usize>() {
+                Ok(number) if (1..=count).contains(&number) => break Some(number - 1),
+                _ => say!("❌ Enter a number from 1 to {}, or s to skip this set", count),
+            }
+        };
+        let Some(keep) = keep else {
+            say!("⏭️ Left as it is.");
+            continue;
+        };
+        let duplicates_dir = set.files[keep].path.parent().unwrap_or(Path::new("")).join(DUPLICATES_DIR);
+        let answer = ask(&tr!("Keep {} and move the other {} to {}? (y/N): ",
+                              set.files[keep].path.display(), if count == 2 { "file".to_string() } else { format!("{} files", count - 1) },
+                              duplicates_dir.display()))?;
+        if !answer.eq_ignore_ascii_case("y") {
+            say!("↩️ Nothing was moved.");
+            continue;
+        }
+        for file in set_aside(set, keep)? {
+            println!("  📦 {} → {}", file.from.display(), file.to.display());
+            moved += 1;
+        }
+        resolved += 1;
+    }
+    say!("\n✅ Resolved {} of {} sets, moving {} files; nothing was deleted", resolved, report.sets.len(), moved);
+    Ok(())
+}
+
+// Pick a layout file from the templates directory
+fn select_layout_file(paths: &AppPaths) -> Result<PathBuf> {
+    say!("\n📐 Layout files in '{}' directory:", paths.templates.display());
+    let layouts = list_files_with_extensions(&paths.templates, &LAYOUT_EXTENSIONS)?;
+    if layouts.is_empty() {
+        anyhow::bail!("No .toml or .json layout files in '{}'; the README's Layout Files section shows the format",
+                      paths.templates.display());
+    }
+    Ok(paths.templates.join(select_from_list(&tr!("layout file"), &layouts)?))
+}
+
+// Check a layout file (by default picked from the templates directory) the way a batch loads
+// it, and the columns it uses against `list` when given, without drawing anything. Returns
+// whether it is valid, printing every problem when it isn't
+pub fn validate_layout_interactive(paths: &AppPaths, options: &BatchOptions, layout: Option<PathBuf>, list: Option<PathBuf>) -> Result<bool> {
+    say!("🔎 === Validate Layout File ===");
+    let (layout_path, list) = match layout {
+        Some(layout) => (layout, list),
+        None => {
+            let layout = select_layout_file(paths)?;
+            let answer = ask(&tr!("Check the columns it uses against a CSV file too? (y/N): "))?;
+            let list = if answer.eq_ignore_ascii_case("y") { Some(select_csv_file(paths)?) } else { None };
+            (layout, list)
+        }
+    };
+    match check_layout_file(&layout_path, list.as_deref(), &options.csv_format) {
+        Ok(layout) => {
+            print_layout(&layout);
+            match &list {
+                Some(list) => say!("\n✅ {} is valid, and {} has every column it uses", layout_path.display(), list.display()),
+                None => say!("\n✅ {} is valid", layout_path.display()),
+            }
+            Ok(true)
+        }
+        Err(e @ (CertError::InvalidLayoutFile { .. } | CertError::LayoutParse { .. } | CertError::UnsupportedLayoutVersion { .. })) => {
+            println!("\n❌ {}", e);
+            Ok(false)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Generate a batch from a layout file (by default picked from the templates directory) for a
+// list (by default picked from the CSV directory), into `output_dir` or the certificates directory
+pub fn generate_from_layout_interactive(
+    paths: &AppPaths,
+    options: &BatchOptions,
+    layout: Option<PathBuf>,
+    list: Option<PathBuf>,
+    output_dir: Option<PathBuf>,
+) -> Result<()> {
+    say!("📐 === Generate from Layout File ===");
+    if options.plan_out.is_some() {
+        anyhow::bail!("--plan-out plans batches from a CSV (option 2 or Repeat last generation); a layout batch can't be planned yet");
+    }
+    let layout_path = match layout {
+        Some(layout) => layout,
+        None => select_layout_file(paths)?,
+    };
+    let mut layout = load_layout(&layout_path)?;
+    if options.name_column.is_some() {
+        layout.name_column = options.name_column.clone();
+    }
+    layout.validate().map_err(|problems| CertError::InvalidLayoutFile { path: layout_path.clone(), problems })?;
+    print_layout(&layout);
+
+    let list = match list {
+        Some(list) => list,
+        None => select_csv_file(paths)?,
+    };
+    map_layout_columns(&layout_path, &mut layout, &list, &options.csv_format)?;
+    let output_dir = match output_dir {
+        Some(dir) => dir,
+        None => {
+            let input = ask(&tr!("Output directory (Enter for '{}'): ", paths.certificates.display()))?;
+            if input.is_empty() { paths.certificates.clone() } else { typed_path(&input) }
+        }
+    };
+
+    let mut job = layout.job_from_list(&list, &options.csv_format)?
+        .output_dir(&output_dir)
+        .overwrite(options.overwrite)
+        .name_case(options.name_case.unwrap_or_default())
+        .typography(options.typography.unwrap_or_default())
+        .case_filenames(options.case_filenames)
+        .allow_bleed(options.allow_bleed)
+        .dry_run(options.dry_run)
+        .deterministic(options.deterministic);
+    if let Some(compression) = options.compression {
+        job = job.compression(compression);
+    }
+    if let Some(depth) = options.depth {
+        job = job.depth(depth);
+    }
+    if let Some(format) = options.format {
+        job = job.format(format);
+    }
+    if let Some(budget) = options.memory_budget {
+        job = job.memory_budget(budget);
+    }
+    if let Some(preview) = options.preview {
+        job = job.preview(preview);
+    }
+    if let Some(normalization) = options.normalize {
+        job = job.normalize(normalization);
+    }
+    if let Some(mode) = options.fingerprint {
+        job = job.fingerprint(mode);
+    }
+    if let Some(watermark) = &options.watermark {
+        job = job.watermark(watermark.clone());
+    }
+    if let Some(imposition) = &options.imposition {
+        job = job.impose(imposition.clone());
+    }
+    if options.stage_locally {
+        job = job.stage_locally(local_staging_dir());
+    } else if let Some(file_system) = network_file_system(&output_dir) {
+        say!("💡 {} is on a network share ({}); --stage-locally writes the certificates locally and moves them there at the end, which is usually faster",
+             output_dir.display(), file_system);
+    }
+    let summary = job.run_with_progress(&ConsoleProgress::new(options.dry_run).verbose(options.verbose));
+    if options.stage_locally {
+        let _ = std::fs::remove_dir(local_staging_dir());
+    }
+    let summary = summary?;
+    if options.dry_run {
+        print_dry_run_summary(&summary, &output_dir);
+    } else {
+        let (x, y) = layout.name_position().unwrap_or_default();
+        print_batch_summary(&summary, &output_dir, x, y);
+    }
+    Ok(())
+}
+
+// Give every placeholder of the layout a column of `list`: by its own name, the layout's
+// [mapping], the mapping remembered for lists with the same header row, or else by asking, with
+// a guess for each. What was asked is remembered, and can be saved into the layout file
+fn map_layout_columns(layout_path: &Path, layout: &mut Layout, list: &Path, format: &CsvFormat) -> Result<()> {
+    let preview = preview_csv(list, 2, format)?;
+    let headers = &preview.headers;
+    if layout.unmapped(headers).is_empty() {
+        return Ok(());
+    }
+    let mut store = MappingStore::load(MAPPINGS_FILE)?;
+    if let Some(remembered) = store.get(headers) {
+        say!("🔗 Using the columns remembered for lists with these headers: {}", remembered);
+        use_mapping(layout, remembered);
+    }
+    let unmapped = layout.unmapped(headers);
+    if unmapped.is_empty() {
+        return Ok(());
+    }
+
+    say!("\n🔗 {} has no column for some of the layout's placeholders. Its columns:", list.display());
+    for (index, header) in headers.iter().enumerate() {
+        println!("  {}. {} (e.g. {})", index + 1, header, preview.column(index).join(", "));
+    }
+    let mut asked = ColumnMapping::default();
+    for placeholder in &unmapped {
+        let suggestion = suggest_column(placeholder, headers);
+        let prompt = match suggestion {
+            Some(index) => format!("Column for {{{}}} (number, Enter for '{}'): ", placeholder, headers[index]),
+            None => format!("Column for {{{}}} (number, Enter to leave it unmapped): ", placeholder),
+        };
+        loop {
+            let input = ask(&prompt)?;
+            let index = match input.parse::<usize>() {
+                Ok(num) if num > 0 && num <= headers.len() => Some(num - 1),
+                _ if input.is_empty() => suggestion,
+                _ => {
+                    say!("❌ Please enter a number between 1 and {}", headers.len());
+                    continue;
+                }
+            };
+            if let Some(index) = index {
+                asked.insert(placeholder.as_str(), headers[index].as_str());
+            }
+            break;
+        }
+    }
+    use_mapping(layout, &asked);
+
+    let unmapped = layout.unmapped(headers);
+    if !unmapped.is_empty() {
+        let unmapped: Vec<String> = unmapped.iter().map(|placeholder| format!("{{{}}}", placeholder)).collect();
+        anyhow::bail!("No certificates were generated: {} has no column for {}. Pick one for each when asked, or map them in the layout's [mapping] table",
+                      list.display(), unmapped.join(", "));
+    }
+    if asked.is_empty() {
+        return Ok(());
+    }
+    store.remember(headers, &asked);
+    store.save(MAPPINGS_FILE)?;
+    say!("✅ Mapped {} (remembered for lists with the same columns)", asked);
+    let answer = ask(&tr!("Also save this mapping into {}, so it runs without asking anywhere? (y/N): ", layout_path.display()))?;
+    if answer.eq_ignore_ascii_case("y") {
+        save_mapping(layout_path, &asked)?;
+        say!("💾 Saved into the [mapping] table of {}", layout_path.display());
+    }
+    Ok(())
+}
+
+// Add `mapping` to the layout's own; a mapped name column replaces the layout's name_column
+fn use_mapping(layout: &mut Layout, mapping: &ColumnMapping) {
+    if mapping.get("name").is_some() {
+        layout.name_column = None;
+    }
+    layout.mapping.merge(mapping);
+}
+
+// Delete what the last batch wrote to `output_dir` (by default where the last remembered batch
+// went), leaving anything edited since. Asks first unless `assume_yes`
+pub fn undo_last_batch(paths: &AppPaths, output_dir: Option<PathBuf>, assume_yes: bool) -> Result<()> {
+    say!("↩️ === Undo Last Batch ===");
+    let output_dir = match output_dir {
+        Some(dir) => dir,
+        None => BatchSettings::load_last_run()?
+            .map_or_else(|| paths.certificates.clone(), |settings| settings.output_dir),
+    };
+    let plan = plan_undo(&output_dir)?;
+    let finished = utc_timestamp(UNIX_EPOCH + Duration::from_secs(plan.record.at));
+    say!("📁 Batch finished {} in {}", &finished[..19].replace('T', " "), output_dir.display());
+    for kept in &plan.kept {
+        say!("  ⏭️ Keeping {}: {}", kept.path.display(), kept.reason);
+    }
+    if plan.delete.is_empty() {
+        say!("✅ Nothing left to delete.");
+        return Ok(());
+    }
+    say!("🗑️ {} files are exactly as the batch wrote them and will be deleted", plan.delete.len());
+    if !assume_yes {
+        let answer = ask(&tr!("Delete {} files? (y/N): ", plan.delete.len()))?;
+        if !answer.eq_ignore_ascii_case("y") {
+            say!("↩️ Nothing was deleted.");
+            return Ok(());
+        }
+    }
+
+    // Files changed between the question and the deletion are kept as well
+    let already_kept = plan.kept.len();
+    let report = plan.apply()?;
+    for kept in &report.kept[already_kept..] {
+        say!("  ⏭️ Keeping {}: {}", kept.path.display(), kept.reason);
+    }
+    say!("✅ Deleted {} files, kept {}", report.removed.len(), report.kept.len());
+    Ok(())
+}
+
+// Replay the settings of the last successful batch, only re-asking what changed or went missing
+pub fn repeat_last_generation(paths: &AppPaths, options: &BatchOptions) -> Result<()> {
+    say!("🔁 === Repeat Last Generation ===");
+    let Some(settings) = BatchSettings::load_last_run()? else {
+        say!("❌ No previous generation found. Run option 2 once and its settings will be remembered.");
+        return Ok(());
+    };
+    say!("💡 Type 'back' at any prompt to change the previous answer, or 'cancel' to return to the menu");
+
+    let mut draft = BatchDraft::new(options);
+    draft.settings = BatchSettings {
+        overwrite: options.overwrite,
+        name_column: options.name_column.clone().or(settings.name_column.clone()),
+        csv_format: options.csv_format.or(settings.csv_format),
+        name_case: options.name_case.unwrap_or(settings.name_case),
+        typography: options.typography.unwrap_or(settings.typography),
+        case_filenames: options.case_filenames || settings.case_filenames,
+        synthetic: options.synthetic.unwrap_or(settings.synthetic),
+        graphics: if options.graphics.is_empty() { settings.graphics.clone() } else { options.graphics.clone() },
+        group_by: options.group_by.clone().or(settings.group_by.clone()),
+        compression: options.compression.unwrap_or(settings.compression),
+        depth: options.depth.unwrap_or(settings.depth),
+        format: options.format.unwrap_or(settings.format),
+        preview: options.preview.or(settings.preview),
+        normalize: options.normalize.or(settings.normalize),
+        fingerprint: options.fingerprint.unwrap_or(settings.fingerprint),
+        watermark: options.watermark.clone(),
+        imposition: options.imposition.clone(),
+        dry_run: options.dry_run,
+        allow_bleed: options.allow_bleed || settings.allow_bleed,
+        font_size_limits: options.font_size_limits,
+        strict_font_size: options.strict_font_size,
+        stage_locally: options.stage_locally || settings.stage_locally,
+        verbose: options.verbose,
+        deterministic: options.deterministic,
+        memory_budget: options.memory_budget,
+        ..settings
+    };
+
+    // The CSV is usually what changed, so always confirm it
+    let csv_files = draft.settings.csv_files();
+    let listed: Vec<String> = csv_files.iter().map(|file| format!("'{}'", file.display())).collect();
+    let missing: Vec<PathBuf> = csv_files.iter()
+        .filter(|file| name_list_url(file).is_none() && !file.exists())
+        .cloned()
+        .collect();
+    let noun = if csv_files.len() == 1 { tr!("file") } else { tr!("files") };
+    let reuse_csv = missing.is_empty()
+        && !ask(&tr!("Use CSV {} {} again? (Y/n): ", noun, listed.join(", ")))?.eq_ignore_ascii_case("n");
+    if !reuse_csv || !load_batch_csv(csv_files, &mut draft)? {
+        for file in missing {
+            say!("⚠️ CSV file '{}' no longer exists", file.display());
+        }
+        prompt_batch_step(BatchStep::Csv, &mut draft, paths)?;
+    }
+
+    // Re-ask only the settings whose files have since been deleted
+    if !draft.settings.template_file.exists() {
+        say!("⚠️ Template '{}' no longer exists", draft.settings.template_file.display());
+        prompt_batch_step(BatchStep::Template, &mut draft, paths)?;
+    }
+    // The saved font may be a family name, and --font replaces it
+    let saved_font = options.font.clone().unwrap_or_else(|| draft.settings.font_filename.to_string_lossy().into_owned());
+    match find_font(&paths.fonts, &saved_font) {
+        Ok(font) if load_font_data(&font).is_ok() => draft.settings.font_filename = font,
+        Ok(_) => {
+            say!("⚠️ Font '{}' is no longer available", saved_font);
+            prompt_batch_step(BatchStep::Font, &mut draft, paths)?;
+        }
+        Err(e) => {
+            println!("⚠️ {}", e);
+            prompt_batch_step(BatchStep::Font, &mut draft, paths)?;
+        }
+    }
+
+    review_and_generate(&mut draft, paths)
+}
+
+// Summary screen, then the batch itself; Back from the summary re-asks the last setting
+fn review_and_generate(draft: &mut BatchDraft, paths: &AppPaths) -> Result<()> {
+    loop {
+        match review_batch(draft, paths) {
+            Ok(()) => break,
+            Err(e) if interrupt_of(&e) == Some(Interrupt::Back) => {
+                run_steps(&BATCH_STEPS, BATCH_STEPS.len() - 1, |step| prompt_batch_step(step, draft, paths))?;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    if let Some(plan_file) = &draft.flags.plan_out {
+        return save_batch_plan(&draft.settings, &draft.names, plan_file);
+    }
+    let log = start_run_log(paths, &draft.flags);
+    let summary = run_batch_with_output(&draft.settings, &draft.names, log.as_ref())?;
+    // Nothing was written, so there is nothing to announce or email
+    if summary.dry_run {
+        return Ok(());
+    }
+    notify_batch_finished(&draft.settings, &summary, paths);
+    match draft.flags.email {
+        Some(mode) => email_certificates(&draft.settings, &summary, mode, paths),
+        None => Ok(()),
+    }
+}
+
+// Show the whole plan and loop until the user proceeds; Abort cancels the flow
+fn review_batch(draft: &mut BatchDraft, paths: &AppPaths) -> Result<()> {
+    loop {
+        let template = draft.template();
+        let settings = &draft.settings;
+        let estimate = match (draft.names.first(), &template) {
+            (Some(sample_name), Ok(template)) => estimate_batch_for_template(&template.image, &settings.output_dir, sample_name,
+                                                                             draft.names.len(), &settings.font_filename,
+                                                                             settings.font_size, &settings.hex_color, settings.compression)
+                .inspect_err(|e| say!("⚠️ Could not estimate batch size: {}", e))
+                .ok()
+                .and_then(|estimate| match settings.memory_budget {
+                    Some(budget) => estimate.with_memory_budget(budget).inspect_err(|e| println!("❌ {}", e)).ok(),
+                    None => Some(estimate),
+                }),
+            (Some(_), Err(e)) => {
+                say!("⚠️ Could not estimate batch size: {}", e);
+                None
+            }
+            (None, _) => None,
+        };
+        print_batch_plan(settings, &draft.names, estimate.as_ref());
+        print_name_case_preview(settings.name_case, &draft.names);
+        let synthetic = settings.synthetic;
+        if !synthetic.is_none()
+            && let Some(real) = real_style_for(&settings.font_filename, &paths.fonts, synthetic.embolden > 0.0, synthetic.slant != 0.0) {
+            say!("  💡 {} has the real style; pick it as the font (and drop --embolden/--slant) for better-looking text", real.display());
+        }
+
+        say!("\n  p. Proceed   d. Dry run   v. Preview and adjust   c. Calibration sheet   1-{}. Edit that field   a. Abort",
+             BATCH_STEPS.len());
+        let choice = ask(&tr!("Choose an option: "))?.to_lowercase();
+        match choice.as_str() {
+            "d" | "dry run" => {
+                let dry_run = BatchSettings { dry_run: true, ..settings.clone() };
+                let log = start_run_log(paths, &draft.flags);
+                if let Err(e) = run_batch_with_output(&dry_run, &draft.names, log.as_ref()) {
+                    say!("❌ Dry run failed: {}", e);
+                }
+            }
+            "v" | "preview" => match refine_with_preview(draft, paths) {
+                Err(e) if interrupt_of(&e).is_none() => say!("❌ Preview failed: {}", e),
+                Err(e) if interrupt_of(&e) != Some(Interrupt::Back) => return Err(e),
+                _ => {}
+            },
+            "c" | "calibrate" => match render_calibration_sheet(settings, &draft.names, &paths.output) {
+                Ok(sheet) => print_calibration_sheet(&sheet),
+                Err(e) => say!("❌ Calibration sheet failed: {}", e),
+            },
+            "p" | "proceed" => {
+                if confirm_batch(settings, &draft.names, estimate.as_ref(), template.as_deref().ok())? {
+                    return Ok(());
+                }
+            }
+            "a" | "abort" => return Err(Interrupt::Cancel.into()),
+            _ => match choice.parse::<usize>() {
+                Ok(field) if (1..=BATCH_STEPS.len()).contains(&field) => {
+                    // Back while editing a single field just returns to the summary
+                    match prompt_batch_step(BATCH_STEPS[field - 1], draft, paths) {
+                        Err(e) if interrupt_of(&e) != Some(Interrupt::Back) => return Err(e),
+                        _ => {}
+                    }
+                }
+                _ => say!("❌ Invalid choice. Enter p, d, v, c, a or a field number."),
+            },
+        }
+    }
+}
+
+// One adjustment typed in the preview loop
+enum Nudge {
+    Move(i32, i32),
+    Size(f32),
+    ResizeBy(f32),
+    Color(String),
+    Reset,
+    Accept,
+}
+
+// "u 10", "d 5", "l 20", "r 3" (1 pixel without a number), "size +4", "size 48", "color #aa0000",
+// "reset" or "ok"
+fn parse_nudge(input: &str) -> Option<Nudge> {
+    let mut words = input.split_whitespace();
+    let command = words.next()?.to_lowercase();
+    let argument = words.next();
+    if words.next().is_some() {
+        return None;
+    }
+    let pixels = || argument.map_or(Some(1), |value| value.parse::<i32>().ok());
+    match command.as_str() {
+        "u" | "up" => pixels().map(|n| Nudge::Move(0, -n)),
+        "d" | "down" => pixels().map(|n| Nudge::Move(0, n)),
+        "l" | "left" => pixels().map(|n| Nudge::Move(-n, 0)),
+        "r" | "right" => pixels().map(|n| Nudge::Move(n, 0)),
+        "size" => {
+            let value = argument?;
+            let amount = value.parse::<f32>().ok()?;
+            Some(if value.starts_with(['+', '-']) { Nudge::ResizeBy(amount) } else { Nudge::Size(amount) })
+        }
+        "color" => argument.map(|color| Nudge::Color(color.to_string())),
+        "reset" if argument.is_none() => Some(Nudge::Reset),
+        "ok" if argument.is_none() => Some(Nudge::Accept),
+        _ => None,
+    }
+}
+
+// Render the first name with the pending position, size and color, then re-render after each
+// nudge until the user accepts. The template and font are decoded once, so each round only
+// draws and encodes; `ok` hands the values to the batch, `back` drops them
+fn refine_with_preview(draft: &mut BatchDraft, paths: &AppPaths) -> Result<()> {
+    let Some(name) = draft.names.first() else {
+        say!("❌ There are no names to preview.");
+        return Ok(());
+    };
+    let text = draft.settings.typography.apply(&draft.settings.name_case.apply(name));
+    let template = draft.template()?;
+    let template = &template.image;
+    let font = load_font(&draft.settings.font_filename)?;
+    let instance = load_font_instance(&draft.settings.font_filename, &draft.settings.font_variation)?;
+    let effect = draft.settings.effect.as_deref().map(find_effect).transpose()?;
+    std::fs::create_dir_all(&paths.output)?;
+    let preview_path = paths.output.join("preview.png");
+
+    let start = (draft.settings.x_pos, draft.settings.y_pos, draft.settings.font_size, draft.settings.hex_color.clone());
+    let (mut x, mut y, mut size, mut color) = start.clone();
+    say!("\n🔍 Previewing '{}' in {}", text, preview_path.display());
+    say!("💡 u/d/l/r <pixels> move, size +4 / size 48 resize, color #aa0000 recolors, reset, ok to accept, back to discard");
+    loop {
+        let options = TextOptions { font: &font, size, color: hex_to_rgba(&color)?, x, y, anchor: Anchor::Center, decorations: &[],
+                                     synthetic: draft.settings.synthetic, variation: instance.as_ref(), effect: effect.as_ref() };
+        let mut img = template.clone();
+        let bounds = draw_text(&mut img, &text, &options)?;
+        save_image(&img, &preview_path, ImageFormat::Png)?;
+        say!("🖼️ Centered on ({}, {}), {}px, {} - text box ({}, {}) {}x{}{}", x, y, size, color,
+             bounds.x, bounds.y, bounds.width, bounds.height,
+             if bounds.fits_within(img.width(), img.height()) { "" } else { " ⚠️ runs off the template" });
+
+        // Invalid values are rejected before they replace the ones that rendered fine
+        loop {
+            let input = ask(&tr!("Adjust: "))?;
+            match parse_nudge(&input) {
+                Some(Nudge::Move(dx, dy)) => {
+                    x = x.saturating_add(dx);
+                    y = y.saturating_add(dy);
+                }
+                Some(Nudge::Size(new_size)) if new_size > 0.0 => size = new_size,
+                Some(Nudge::ResizeBy(delta)) if size + delta > 0.0 => size += delta,
+                Some(Nudge::Size(_) | Nudge::ResizeBy(_)) => {
+                    say!("❌ The font size must stay above 0");
+                    continue;
+                }
+                Some(Nudge::Color(new_color)) => match palette_pick(&new_color).map_or_else(|| check_color(&new_color).map(|_| new_color), Ok) {
+                    Ok(new_color) => color = new_color,
+                    Err(e) => {
+                        println!("❌ {}", e);
+                        continue;
+                    }
+                },
+                Some(Nudge::Reset) => (x, y, size, color) = start.clone(),
+                Some(Nudge::Accept) => {
+                    draft.settings.x_pos = x;
+                    draft.settings.y_pos = y;
+                    draft.settings.font_size = size;
+                    draft.settings.hex_color = color;
+                    say!("✅ The batch will use ({}, {}), {}px, {}", x, y, size, draft.settings.hex_color);
+                    return Ok(());
+                }
+                None => {
+                    say!("❌ Try u 10, d 5, l 20, r 3, size +4, size 48, color #aa0000, reset or ok");
+                    continue;
+                }
+            }
+            break;
+        }
+    }
+}
+
+// Disk space and ink checks; Ok(false) sends the user back to the summary
+fn confirm_batch(settings: &BatchSettings, names: &[String], estimate: Option<&BatchEstimate>, template: Option<&DecodedTemplate>) -> Result<bool> {
+    // Projected output must fit before committing to a long run
+    if let Some(estimate) = estimate
+        && !estimate.fits_on_disk() {
+        say!("⚠️ The projected output is larger than the free space on the output volume!");
+        let proceed = ask(&tr!("Generate anyway? (y/N): "))?;
+        if !proceed.eq_ignore_ascii_case("y") {
+            say!("↩️ Free up space or edit the output directory (field 7).");
+            return Ok(false);
+        }
+    }
+
+    // The longest name running off the edge is as much a typo as a point off it
+    if !settings.allow_bleed
+        && let Some(template) = template
+        && let Ok(text) = largest_text_box(settings, names)
+        && !text.fits_within(template.image.width(), template.image.height()) {
+        say!("\n⚠️ The longest name ({}x{} at {}, {}) runs off the {}x{} template",
+             text.width, text.height, text.x, text.y, template.image.width(), template.image.height());
+        let (width, height) = (template.image.width() as i32, template.image.height() as i32);
+        if text.width > width || text.height > height {
+            say!("  📐 It is larger than the template at {}px; only a smaller font size fits", settings.font_size);
+        } else {
+            say!("  📐 It fits centered with x from {} to {} and y from {} to {}",
+                 text.width / 2, width - (text.width - text.width / 2), text.height / 2, height - (text.height - text.height / 2));
+        }
+        if !ask(&tr!("Generate anyway, bleeding past the edge on purpose? (y/N): "))?.eq_ignore_ascii_case("y") {
+            say!("↩️ Edit the position (field 3) or the font size (field 5) and try again.");
+            return Ok(false);
+        }
+    }
+
+    // Catch stale coordinates before the name lands on pre-printed artwork
+    let region = match template {
+        Some(template) => check_text_region_on(&template.image, settings, names),
+        None => check_text_region(settings, names),
+    };
+    match region {
+        Ok(region) if region.exceeds_threshold() => {
+            say!("\n⚠️ The text area already contains {}% ink on the template!", format!("{:.1}", region.coverage * 100.0));
+            say!("  📐 Region: ({}, {}) to ({}, {}) - {}x{} pixels",
+                 region.x, region.y, region.x + region.width, region.y + region.height,
+                 region.width, region.height);
+            say!("  🎨 Background color: #{}",
+                 format!("{:02X}{:02X}{:02X}", region.background[0], region.background[1], region.background[2]));
+            let proceed = ask(&tr!("Generate anyway? (y/N): "))?;
+            if !proceed.eq_ignore_ascii_case("y") {
+                say!("↩️ Edit the position (field 3) and try again.");
+                return Ok(false);
+            }
+        }
+        Ok(region) => say!("✅ Text area is clear ({}% ink)", format!("{:.1}", region.coverage * 100.0)),
+        Err(e) => say!("⚠️ Could not check the text area: {}", e),
+    }
+
+    Ok(true)
+}
+
+// Run a batch with live progress lines and the usual summary
+pub fn run_batch_with_output(settings: &BatchSettings, names: &[String], log: Option<&RunLog>) -> Result<BatchReport> {
+    if settings.dry_run {
+        say!("\n🧪 Dry run: checking {} names without writing any certificates...", names.len());
+    } else {
+        say!("\n🎓 Generating certificates in parallel using {} cores...", rayon::current_num_threads());
+    }
+    say!("🎯 Text will be centered around coordinates ({}, {})", settings.x_pos, settings.y_pos);
+
+    let console = ConsoleProgress::new(settings.dry_run).verbose(settings.verbose);
+    let result = match log {
+        Some(log) => {
+            log.settings(settings, names.len());
+            run_batch(settings, names, &LoggedProgress::new(log, &console))
+        }
+        None => run_batch(settings, names, &console),
+    };
+    let summary = result.inspect_err(|e| {
+        if let Some(log) = log {
+            log.failure(e);
+        }
+    })?;
+    report_batch(settings, &summary, log)?;
+    Ok(summary)
+}
+
+// --plan-out: lay the batch out and save the plan instead of generating
+fn save_batch_plan(settings: &BatchSettings, names: &[String], plan_file: &Path) -> Result<()> {
+    say!("\n📋 Planning {} names without writing any certificates...", names.len());
+    let plan = plan_batch(settings, names)?;
+    plan.save(plan_file)?;
+    print_plan_summary(&plan);
+    say!("📄 Plan: {}", plan_file.display());
+    if plan_file.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("csv")) {
+        say!("💡 A CSV plan is for reading; save it as .json to render it with --execute-plan");
+    } else {
+        say!("▶️ Render exactly this plan with: --execute-plan {}", plan_file.display());
+    }
+    Ok(())
+}
+
+// --execute-plan: render a saved plan as written, with the usual progress and summary; false
+// when some rows failed
+pub fn execute_plan_interactive(paths: &AppPaths, options: &BatchOptions, plan_file: &Path) -> Result<bool> {
+    let plan = BatchPlan::load(plan_file)?;
+    say!("📋 Plan {}: {} certificates into {}", plan_file.display(), plan.renderable(), plan.settings.output_dir.display());
+    if let Some(shard) = options.shard {
+        say!("🧩 Shard {}: {} of the plan's {} rows", shard, shard.rows(plan.rows.len()).len(), plan.rows.len());
+    }
+    say!("\n🎓 Rendering the plan in parallel using {} cores...", rayon::current_num_threads());
+    let execute = |progress: &dyn ProgressSink| match options.shard {
+        Some(shard) => execute_shard(&plan, shard, progress),
+        None => execute_plan(&plan, progress),
+    };
+
+    let log = start_run_log(paths, options);
+    let console = ConsoleProgress::new(false).verbose(options.verbose);
+    let result = match &log {
+        Some(log) => {
+            log.settings(&plan.settings, plan.rows.len());
+            execute(&LoggedProgress::new(log, &console))
+        }
+        None => execute(&console),
+    };
+    let summary = result.inspect_err(|e| {
+        if let Some(log) = &log {
+            log.failure(e);
+        }
+    })?;
+    report_batch(&plan.settings, &summary, log.as_ref())?;
+    notify_batch_finished(&plan.settings, &summary, paths);
+    if let Some(shard) = options.shard {
+        say!("🧩 Shard {} done. Once every shard's certificates and {}* files are in {}, combine them with", shard, SHARD_MANIFEST_PREFIX, plan.settings.output_dir.display());
+        say!("   CertificateMaker merge-manifests {}", plan.settings.output_dir.display());
+    }
+    Ok(summary.failed == 0)
+}
+
+// Check that the shards of a plan add up and combine their manifests into manifest.json
+pub fn merge_manifests_interactive(paths: &AppPaths, output_dir: Option<PathBuf>, files: Vec<PathBuf>) -> Result<bool> {
+    say!("🧩 === Merge Shard Manifests ===");
+    let output_dir = output_dir.unwrap_or_else(|| paths.certificates.clone());
+    let files = if files.is_empty() { shard_manifests_in(&output_dir)? } else { files };
+    say!("📁 {} shard manifests in {}", files.len(), output_dir.display());
+    let merged = merge_shard_manifests(&output_dir, &files)?;
+    say!("✅ Merged {} shards: {} certificates recorded in {}", merged.shards, merged.certificates, merged.manifest.display());
+    if !merged.failed.is_empty() {
+        say!("⚠️ {} rows failed in their shard and have no certificate:", merged.failed.len());
+        for row in &merged.failed {
+            say!("  ❌ Row {}: {}", row.index + 1, row.output_file.display());
+        }
+        say!("💡 Run their shard again after fixing them; remove its old manifest first");
+    }
+    Ok(merged.failed.is_empty())
+}
+
+// The generation log of a batch in the output directory, unless --no-log; a log that can't be
+// created is only a warning
+pub fn start_run_log(paths: &AppPaths, options: &BatchOptions) -> Option<RunLog> {
+    if options.no_log {
+        return None;
+    }
+    RunLog::create(&paths.output)
+        .inspect_err(|e| say!("⚠️ Could not start the generation log: {}", e))
+        .ok()
+}
+
+// Summary, remembered settings and stale-output cleanup after a batch finished
+pub fn report_batch(settings: &BatchSettings, summary: &BatchReport, log: Option<&RunLog>) -> Result<()> {
+    if let Some(log) = log {
+        say!("📝 Generation log: {}", log.path().display());
+        if let Some(e) = log.error() {
+            say!("⚠️ The log may be incomplete: {}", e);
+        }
+    }
+    if summary.dry_run {
+        return report_dry_run(settings, summary);
+    }
+    print_batch_summary(summary, &settings.output_dir, settings.x_pos, settings.y_pos);
+    if let Err(e) = settings.save_last_run() {
+        say!("⚠️ Could not remember these settings for next time: {}", e);
+    }
+
+    // Offer to clean up certificates for rows that were removed from the CSV
+    let mut removed = 0;
+    if !summary.stale.is_empty() {
+        say!("\n🧹 {} certificates belong to names no longer in the CSV:", summary.stale.len());
+        for entry in &summary.stale {
+            println!("  • {} ({})", entry.name, entry.output_file.display());
+        }
+        let confirm = ask(&tr!("Delete these certificates? (y/N): "))?;
+        if confirm.eq_ignore_ascii_case("y") {
+            let removed_files = remove_stale_outputs(&settings.output_dir, &summary.stale)?;
+            for file in &removed_files {
+                say!("🗑️ Removed: {}", file.display());
+                if let Some(log) = log {
+                    log.line(&format!("REMOVED {}", file.display()));
+                }
+            }
+            // Previews go with their certificates but aren't counted separately
+            removed = removed_files.iter().filter(|file| summary.stale.iter().any(|entry| &entry.output_file == *file)).count();
+        }
+    }
+
+    say!("\n📊 Generated: {} | Skipped (unchanged): {} | Skipped (existing): {} | Renamed: {} | Removed: {} | Failed: {}",
+         summary.generated, summary.skipped_unchanged, summary.skipped_existing, summary.renamed, removed, summary.failed);
+    if let Some(estimate) = &summary.estimate {
+        say!("💾 Estimated output of this run: {} MB", format!("{:.1}", estimate.total_bytes as f64 / (1024.0 * 1024.0)));
+    }
+
+    Ok(())
+}
+
+// What a real run would do, and the JSON report for CI; a dry run is never remembered as the
+// last generation and never deletes anything
+fn report_dry_run(settings: &BatchSettings, summary: &BatchReport) -> Result<()> {
+    print_dry_run_summary(summary, &settings.output_dir);
+    let report_path = settings.output_dir.join(DRY_RUN_REPORT_FILENAME);
+    summary.save_json(&report_path)?;
+    say!("📄 Report: {}", report_path.display());
+    Ok(())
+}
+
+// POST the batch summary to the configured notify_url, if any; a notification that can't be
+// sent is only a warning and never fails the batch
+pub fn notify_batch_finished(settings: &BatchSettings, summary: &BatchReport, paths: &AppPaths) {
+    let webhook = match WebhookSettings::load(&paths.config) {
+        Ok(webhook) => webhook,
+        Err(e) => {
+            say!("⚠️ Could not read the notify settings: {}", e);
+            return;
+        }
+    };
+    let Some(url) = webhook.url.as_deref() else {
+        return;
+    };
+    let run_id = if settings.deterministic { content_run_id(summary) } else { new_run_id() };
+    let notification = BatchNotification::new(run_id, settings, summary);
+    match notify_batch(&webhook, &notification) {
+        Ok(()) => say!("🔔 Notified {} (run {})", url, notification.run_id),
+        Err(e) => say!("⚠️ Could not send the batch notification: {}", e),
+    }
+}
+
+// Time `count` sample certificates through the whole pipeline on the user's own template and
+// font: the last generation's settings when there is one, otherwise the first template's center
+pub fn run_perf_test(paths: &AppPaths, options: &BatchOptions, template: Option<PathBuf>, count: usize) -> Result<()> {
+    let template = template.map(|template| if template.exists() { template } else { paths.templates.join(template) });
+    let last = BatchSettings::load_last_run().ok().flatten()
+        .filter(|last| last.template_file.exists() && template.as_ref().is_none_or(|template| *template == last.template_file));
+    let template = match (template, &last) {
+        (Some(template), _) => template,
+        (None, Some(last)) => last.template_file.clone(),
+        (None, None) => paths.templates.join(&list_template_files(&paths.templates)?[0]),
+    };
+    let font = match (&options.font, &last) {
+        (Some(query), _) => find_font(&paths.fonts, query)?,
+        (None, Some(last)) => last.font_filename.clone(),
+        (None, None) => {
+            let fonts = list_available_fonts(&paths.fonts)?;
+            paths.font(fonts.iter().find(|f| *f == Path::new("DejaVuSans.ttf")).unwrap_or(&fonts[0]))
+        }
+    };
+
+    let mut job = CertificateJob::new(&template, sample_names(count)).font(&font);
+    if let Some(last) = &last {
+        job = job.font_size(last.font_size)
+            .color(&last.hex_color)
+            .position(Anchor::Center, last.x_pos, last.y_pos)
+            .name_case(last.name_case)
+            .synthetic(last.synthetic)
+            .font_variation(last.font_variation.clone())
+            .compression(last.compression);
+    }
+    if let Some(synthetic) = options.synthetic {
+        job = job.synthetic(synthetic);
+    }
+    if let Some(compression) = options.compression {
+        job = job.compression(compression);
+    }
+    if let Some(depth) = options.depth {
+        job = job.depth(depth);
+    }
+    if let Some(budget) = options.memory_budget {
+        job = job.memory_budget(budget);
+    }
+
+    say!("⏱️ Perf test: {} sample certificates on {} with {}{}", count, template.display(), font.display(),
+         if last.is_some() { ", as the last generation" } else { "" });
+    print_perf_test(&perf_test(job)?);
+    Ok(())
+}
+
+// Render one sample certificate from the first template and font, no prompts
+pub fn run_demo(paths: &AppPaths, text: &str) -> Result<()> {
+    let template = paths.templates.join(&list_template_files(&paths.templates)?[0]);
+    let fonts = list_available_fonts(&paths.fonts)?;
+    let font = paths.font(fonts.iter().find(|f| *f == Path::new("DejaVuSans.ttf")).unwrap_or(&fonts[0]));
+    let (width, height) = oriented_dimensions(&template)?;
+    let output_file = paths.output.join("demo_certificate.png");
+    std::fs::create_dir_all(&paths.output)?;
+
+    say!("🎬 Demo: \"{}\" on {} with {}", text, template.display(), font.display());
+    let (x, y) = render_certificate(&template, &output_file, text, width as i32 / 2, height as i32 / 2, &font, 48.0, "#000000")?;
+    say!("✅ Text drawn at ({}, {})", x, y);
+    say!("📁 Output saved to: {}", output_file.display());
+    Ok(())
+}
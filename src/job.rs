@@ -0,0 +1,2069 @@
+// src/job.rs
+//! Builder-style batch generation API.
+//!
+//! ```no_run
+//! use certificate_maker::job::{Anchor, CertificateJob};
+//!
+//! let report = CertificateJob::new("Template/certificate.png", vec!["Alice Johnson".to_string()])
+//!     .font("DejaVuSans.ttf")
+//!     .font_size(48.0)
+//!     .color("#000")
+//!     .position(Anchor::Center, 960, 620)
+//!     .output_dir("certs")
+//!     .threads(4)
+//!     .run()?;
+//! println!("{} generated", report.generated);
+//! # Ok::<(), certificate_maker::error::CertError>(())
+//! ```
+use rayon::prelude::*;
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use walkdir::WalkDir;
+
+use crate::backside::{BackSide, back_path, front_path};
+use crate::csvexcelparser::{BatchItem, BatchReport, DrawnText, certificate_output_path, estimate_batch_for_template};
+use crate::duplicates::DuplicateDecision;
+use crate::error::{CertError, IoContext, Result};
+use crate::effects::TextEffect;
+use crate::editpng::{
+    BorderStyle, ELLIPSIS, TextBounds, TextOptions, TextOverflow, draw_border, draw_text, encode_png, fit_text, font_line_metrics, hex_to_rgba, layout_text, load_font, load_font_data, load_template, missing_glyphs,
+};
+use crate::files::{check_output_dir, move_file, next_free_path};
+use crate::fingerprint::{Fingerprint, FingerprintMode, draw_fingerprint, embed_fingerprint};
+use crate::fontsize::{FontSizeCheck, FontSizeLimits, check_font_size};
+use crate::graphics::{GraphicField, GraphicFonts, GraphicKind, PlacedGraphic, draw_graphic_fields, ellipsized_values, layout_graphic_fields};
+use crate::grouping::{UNGROUPED_DIR, group_dir_name};
+use crate::namecase::NameCase;
+use crate::normalize::{NormalizePlan, Normalization};
+use crate::orientation::oriented_dimensions;
+use crate::depth::{DeepColor, DeepTemplate, DepthPolicy, deep_color, encode_png16, load_deep_template};
+use crate::imposition::{Imposition, impose_batch};
+use crate::memory::MemoryPlan;
+use crate::dpi::image_dpi;
+use crate::manifest::{Artifact, BatchRecord, DRY_RUN_MANIFEST_FILENAME, EllipsizedText, Manifest, ManifestEntry, hash_bytes, hash_file, row_hash};
+use crate::palette::{PALETTE_PREFIX, check_color, installed as installed_palette};
+use crate::patch::{Patch, apply_patches};
+use crate::pdf::{DEFAULT_PDF_DPI, images_to_pdf};
+use crate::preview::{encode_preview, preview_path, render_preview};
+use crate::plan::PlannedRow;
+use crate::sanitize::strip_control;
+use crate::shard::ShardRecord;
+use crate::watermark::{WatermarkLayout, WatermarkMask, WatermarkStyle};
+use crate::progress::{NoProgress, ProgressSink, call_sink};
+use crate::source::{NameSource, Record};
+use crate::timing::{PerformanceBreakdown, Stage, StageTimings};
+use crate::typography::{RetouchedText, Typography};
+use crate::variation::{AxisValue, FontInstance, check_axis_values, format_axis_values};
+
+pub use crate::editpng::{Anchor, Decoration, DecorationKind, PngCompression, SyntheticStyle};
+
+/// Rows the workers render at a time. A row's text and graphic data are filled in only when
+/// its chunk starts, and the manifest is saved after each chunk, so a batch stopped partway
+/// still records the certificates it wrote.
+pub const RENDER_CHUNK_ROWS: usize = 256;
+
+/// Rows a batch under a [`memory_budget`](CertificateJob::memory_budget) keeps in its
+/// report's items: the first this many that failed or drew a warning. Every row still goes
+/// to the progress sink, and so to the run log, and the report's counts cover them all.
+pub const BUDGET_REPORTED_ROWS: usize = 1000;
+
+// What a row wrote: the hash of the certificate as written, and its preview and its back
+// when the batch makes them
+struct WrittenRow {
+    file_hash: String,
+    preview: Option<Artifact>,
+    back: Option<Artifact>,
+}
+
+// What a row draws, filled in from its name and record: once to hash the row, and again when
+// its chunk is rendered
+struct RowContent {
+    // Cased and cleaned up, or the plan's
+    text: String,
+    graphic_data: Result<Vec<String>, String>,
+    back_data: Option<Result<Vec<String>, String>>,
+    retouched: Vec<RetouchedText>,
+}
+
+// A row to render, holding only its place until its chunk starts; `entry` is its position
+// among the batch's manifest entries
+struct WaitingRow {
+    index: usize,
+    entry: usize,
+    // Saved under another name because its own was taken
+    renamed: bool,
+    // Grouped, but with nothing in the group column
+    ungrouped: bool,
+}
+
+// A row of the chunk being rendered, with everything decided about it before the workers start
+struct PendingRow<'a> {
+    index: usize,
+    entry: usize,
+    name: String,
+    output_file: PathBuf,
+    renamed: bool,
+    ungrouped: bool,
+    content: RowContent,
+    planned: Option<&'a PlannedRow>,
+}
+
+// Where each of the batch's manifest entries stands, so a save between chunks records only
+// what is on disk
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RowState {
+    Unchanged,
+    Waiting,
+    Written,
+    Failed,
+}
+
+// The back's template, decoded once, and what every row draws on it besides its own data
+struct BackCanvas {
+    template: RgbaImage,
+    // Its 16-bit pixels when certificates keep them
+    deep: Option<DeepTemplate>,
+    fonts: GraphicFonts,
+    watermark: Option<WatermarkMask>,
+}
+
+/// What to do when a certificate's file already exists but wasn't written by an earlier
+/// run into the same directory (those are always updated).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OverwritePolicy {
+    /// Replace the existing file.
+    Overwrite,
+    /// Write `certificate_<name>_1.png`, `_2`, ... instead.
+    Rename,
+    /// Leave the file alone and don't render that row.
+    #[default]
+    SkipExisting,
+}
+
+/// What each certificate is saved as.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CertificateFormat {
+    /// A PNG per side: `certificate_<name>.png`, or `_front.png` and `_back.png` with a back.
+    #[default]
+    Png,
+    /// One PDF per recipient with a page per side, each page sized to its image at the
+    /// template's DPI (or [`DEFAULT_PDF_DPI`] when it records none).
+    Pdf,
+}
+
+impl CertificateFormat {
+    pub const ALL: [CertificateFormat; 2] = [CertificateFormat::Png, CertificateFormat::Pdf];
+
+    /// Name used on the command line and in layout files, also the file extension.
+    pub fn name(self) -> &'static str {
+        match self {
+            CertificateFormat::Png => "png",
+            CertificateFormat::Pdf => "pdf",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<CertificateFormat> {
+        CertificateFormat::ALL.into_iter().find(|format| format.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+/// A batch of certificates rendered from one template.
+#[derive(Debug, Clone)]
+pub struct CertificateJob {
+    template: PathBuf,
+    names: Vec<String>,
+    texts: Vec<String>,
+    planned: Vec<PlannedRow>,
+    shard: Option<ShardRecord>,
+    font: PathBuf,
+    font_size: f32,
+    font_size_limits: FontSizeLimits,
+    strict_font_size: bool,
+    color: String,
+    anchor: Anchor,
+    // None means the center of the template
+    position: Option<(i32, i32)>,
+    bleed: bool,
+    max_width: Option<f32>,
+    overflow: TextOverflow,
+    ellipsis: Option<String>,
+    decorations: Vec<Decoration>,
+    synthetic: SyntheticStyle,
+    font_variation: Vec<AxisValue>,
+    effect: Option<TextEffect>,
+    graphics: Vec<GraphicField>,
+    patches: Vec<Patch>,
+    borders: Vec<BorderStyle>,
+    back: Option<BackSide>,
+    format: CertificateFormat,
+    output_dir: PathBuf,
+    staging: Option<PathBuf>,
+    overwrite: OverwritePolicy,
+    duplicates: Vec<DuplicateDecision>,
+    name_case: NameCase,
+    typography: Typography,
+    case_filenames: bool,
+    rows: Vec<usize>,
+    sources: Vec<PathBuf>,
+    emails: Vec<String>,
+    records: Vec<Record>,
+    group_by: Option<String>,
+    compression: PngCompression,
+    depth: DepthPolicy,
+    preview: Option<u32>,
+    watermark: Option<WatermarkStyle>,
+    fingerprint: FingerprintMode,
+    imposition: Option<Imposition>,
+    normalize: Option<Normalization>,
+    // Set on the copy `normalized` makes, whose numbers are already moved onto the new size
+    template_plan: Option<NormalizePlan>,
+    back_plan: Option<NormalizePlan>,
+    partial: bool,
+    dry_run: bool,
+    deterministic: bool,
+    threads: Option<usize>,
+    memory_budget: Option<u64>,
+}
+
+impl CertificateJob {
+    /// Start a job with the interactive defaults: DejaVuSans 40px, black, centered, into `certificates/`.
+    pub fn new(template: impl Into<PathBuf>, names: Vec<String>) -> Self {
+        CertificateJob {
+            template: template.into(),
+            names,
+            texts: Vec::new(),
+            planned: Vec::new(),
+            shard: None,
+            font: PathBuf::from("DejaVuSans.ttf"),
+            font_size: 40.0,
+            font_size_limits: FontSizeLimits::default(),
+            strict_font_size: false,
+            color: "#000000".to_string(),
+            anchor: Anchor::Center,
+            position: None,
+            bleed: false,
+            max_width: None,
+            overflow: TextOverflow::Shrink,
+            ellipsis: None,
+            decorations: Vec::new(),
+            synthetic: SyntheticStyle::default(),
+            font_variation: Vec::new(),
+            effect: None,
+            graphics: Vec::new(),
+            patches: Vec::new(),
+            borders: Vec::new(),
+            back: None,
+            format: CertificateFormat::default(),
+            output_dir: PathBuf::from("certificates"),
+            staging: None,
+            overwrite: OverwritePolicy::default(),
+            duplicates: Vec::new(),
+            name_case: NameCase::default(),
+            typography: Typography::default(),
+            case_filenames: false,
+            rows: Vec::new(),
+            sources: Vec::new(),
+            emails: Vec::new(),
+            records: Vec::new(),
+            group_by: None,
+            compression: PngCompression::default(),
+            depth: DepthPolicy::default(),
+            preview: None,
+            watermark: None,
+            fingerprint: FingerprintMode::Off,
+            imposition: None,
+            normalize: None,
+            template_plan: None,
+            back_plan: None,
+            partial: false,
+            dry_run: false,
+            deterministic: false,
+            threads: None,
+            memory_budget: None,
+        }
+    }
+
+    /// Start a job with the names read from any [`NameSource`].
+    pub fn from_source(template: impl Into<PathBuf>, mut source: impl NameSource) -> Result<Self> {
+        Ok(CertificateJob::new(template, source.names()?))
+    }
+
+    /// Font filename inside the assets directory, or [`BUILTIN_FONT`](crate::editpng::BUILTIN_FONT).
+    pub fn font(mut self, font: impl Into<PathBuf>) -> Self {
+        self.font = font.into();
+        self
+    }
+
+    pub fn font_size(mut self, font_size: f32) -> Self {
+        self.font_size = font_size;
+        self
+    }
+
+    /// What counts as too large or too small a font size for the template; see
+    /// [`check_font_size`](Self::check_font_size).
+    pub fn font_size_limits(mut self, limits: FontSizeLimits) -> Self {
+        self.font_size_limits = limits;
+        self
+    }
+
+    /// Refuse to run with a font size outside the [limits](Self::font_size_limits) instead of
+    /// only reporting it in [`BatchReport::font_size`]. Off by default.
+    ///
+    /// ```
+    /// use certificate_maker::job::CertificateJob;
+    ///
+    /// let job = CertificateJob::new("tests/fixtures/template.png", vec!["Ada Lovelace".to_string()])
+    ///     .font("tests/fixtures/DejaVuSans-Latin.ttf")
+    ///     .font_size(300.0);
+    /// assert!(job.check_font_size()?.suggested_size.is_some());
+    /// assert!(job.validate().is_ok());
+    /// let problems = job.strict_font_size(true).validate().unwrap_err();
+    /// assert!(problems[0].contains("of the 400 px high template"), "{:?}", problems);
+    /// # Ok::<(), certificate_maker::error::CertError>(())
+    /// ```
+    pub fn strict_font_size(mut self, strict: bool) -> Self {
+        self.strict_font_size = strict;
+        self
+    }
+
+    /// Hex color, `#RGB`, `#RRGGBB` or `#RRGGBBAA`.
+    pub fn color(mut self, color: impl Into<String>) -> Self {
+        self.color = expand_short_hex(&color.into());
+        self
+    }
+
+    pub fn position(mut self, anchor: Anchor, x: i32, y: i32) -> Self {
+        self.anchor = anchor;
+        self.position = Some((x, y));
+        self
+    }
+
+    /// Accept a position outside the template, or a negative one, for text placed past the
+    /// edge on purpose. Without it [`validate`](Self::validate) takes such a position for a typo.
+    ///
+    /// ```
+    /// use certificate_maker::job::{Anchor, CertificateJob};
+    ///
+    /// // 3000 for 300 on a 600x400 template
+    /// let job = CertificateJob::new("tests/fixtures/template.png", vec!["Ada Lovelace".to_string()])
+    ///     .font("tests/fixtures/DejaVuSans-Latin.ttf")
+    ///     .position(Anchor::Center, 3000, 200);
+    /// let problems = job.validate().unwrap_err();
+    /// assert!(problems[0].contains("x must be 0 to 599, y 0 to 399"));
+    ///
+    /// // A name half off the left edge, meant to be cut by it
+    /// assert!(job.position(Anchor::Center, -20, 200).allow_bleed(true).validate().is_ok());
+    /// ```
+    pub fn allow_bleed(mut self, bleed: bool) -> Self {
+        self.bleed = bleed;
+        self
+    }
+
+    /// Set names wider than this many pixels smaller, each just small enough to fit, or cut
+    /// them short with [`overflow`](Self::overflow).
+    pub fn max_width(mut self, max_width: f32) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+
+    /// What happens to names wider than [`max_width`](Self::max_width): set smaller (the
+    /// default), or cut short at their size. Cut names are listed in their row's warnings and
+    /// manifest entry with their whole text.
+    pub fn overflow(mut self, overflow: TextOverflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// What cut-short names end with, [`ELLIPSIS`] by default.
+    pub fn ellipsis(mut self, suffix: impl Into<String>) -> Self {
+        self.ellipsis = Some(suffix.into());
+        self
+    }
+
+    /// Text to draw for each name, in the same order as the names, such as "Dr. {Name}"
+    /// filled in per row; the names still give the filenames and manifest entries.
+    pub fn texts(mut self, texts: Vec<String>) -> Self {
+        self.texts = texts;
+        self
+    }
+
+    /// Render each name exactly as a [`BatchPlan`](crate::plan::BatchPlan) laid it out, in the
+    /// same order as the names: its drawn text, output file, box and font size are taken from
+    /// the plan, not worked out again. Every planned row is rendered, unchanged or not; see
+    /// [`execute_plan`](crate::plan::execute_plan).
+    pub fn planned(mut self, rows: Vec<PlannedRow>) -> Self {
+        self.planned = rows;
+        self
+    }
+
+    /// Render one shard of a plan: the manifest is saved as the shard's own file,
+    /// [`ShardRecord::file_name`], recording the shard and only the certificates it wrote, for
+    /// [`merge_shard_manifests`](crate::shard::merge_shard_manifests) to combine. See
+    /// [`execute_shard`](crate::shard::execute_shard).
+    pub fn shard(mut self, record: ShardRecord) -> Self {
+        self.shard = Some(record);
+        self
+    }
+
+    /// Underline or strike through every name. Decorations without metrics use the job
+    /// font's own underline and strikethrough lines.
+    pub fn decorations(mut self, decorations: Vec<Decoration>) -> Self {
+        self.decorations = decorations;
+        self
+    }
+
+    /// Faux bold and italic for a font without a real bold or italic face.
+    pub fn synthetic(mut self, synthetic: SyntheticStyle) -> Self {
+        self.synthetic = synthetic;
+        self
+    }
+
+    /// Axis values for a variable font, such as `wght=700`. Axes left out keep their defaults.
+    pub fn font_variation(mut self, font_variation: Vec<AxisValue>) -> Self {
+        self.font_variation = font_variation;
+        self
+    }
+
+    /// Draw every name with a gradient, outline or shadows, such as a built-in from
+    /// [`find_effect`](crate::effects::find_effect). The effect is recorded in the manifest.
+    pub fn effect(mut self, effect: TextEffect) -> Self {
+        self.effect = Some(effect);
+        self
+    }
+
+    /// Graphics drawn on every certificate besides the name, such as a barcode of a column.
+    pub fn graphics(mut self, graphics: Vec<GraphicField>) -> Self {
+        self.graphics = graphics;
+        self
+    }
+
+    /// Rectangles of the template painted over once, before the borders and everything else,
+    /// such as last year's date; see [`crate::patch`]. Their pixels are the template's own,
+    /// before any [`normalize`](Self::normalize).
+    ///
+    /// ```
+    /// use certificate_maker::job::CertificateJob;
+    /// use certificate_maker::patch::{Patch, PatchFill};
+    ///
+    /// let patch = Patch { x: 0, y: 0, width: 40, height: 30, fill: "#FF0000".parse::<PatchFill>()? };
+    /// let job = CertificateJob::new("tests/fixtures/template.png", vec!["Ada Lovelace".to_string()])
+    ///     .font("tests/fixtures/DejaVuSans-Latin.ttf")
+    ///     .patches(vec![patch]);
+    /// assert_eq!(job.render_image(0)?.get_pixel(10, 10).0, [255, 0, 0, 255]);
+    /// # Ok::<(), certificate_maker::error::CertError>(())
+    /// ```
+    pub fn patches(mut self, patches: Vec<Patch>) -> Self {
+        self.patches = patches;
+        self
+    }
+
+    /// Borders drawn on the template once, before anything else, so the name and the graphics
+    /// are always over them. Later borders cover earlier ones.
+    ///
+    /// ```
+    /// use certificate_maker::editpng::{BorderLine, BorderSides, BorderStyle};
+    /// use certificate_maker::job::CertificateJob;
+    /// use image::Rgba;
+    ///
+    /// let gold = Rgba([176, 141, 60, 255]);
+    /// let border = BorderStyle { line: BorderLine::Solid, color: gold, thickness: BorderSides::all(8.0), inset: 4.0, radius: 0.0 };
+    /// let job = CertificateJob::new("tests/fixtures/template.png", vec!["Ada Lovelace".to_string()])
+    ///     .font("tests/fixtures/DejaVuSans-Latin.ttf")
+    ///     .borders(vec![border]);
+    /// assert_eq!(job.render_image(0)?.get_pixel(6, 100), &gold);
+    /// # Ok::<(), certificate_maker::error::CertError>(())
+    /// ```
+    pub fn borders(mut self, borders: Vec<BorderStyle>) -> Self {
+        self.borders = borders;
+        self
+    }
+
+    /// Make the certificates double-sided, with `back` as the other side (see
+    /// [`crate::backside`]). Its fields are filled in from the same row as the front. As PNGs
+    /// each recipient gets a `_front` and a `_back` file; as a PDF, one file of two pages.
+    pub fn back(mut self, back: BackSide) -> Self {
+        self.back = Some(back);
+        self
+    }
+
+    /// Save each certificate as a PNG (the default) or a PDF; see [`CertificateFormat`].
+    pub fn format(mut self, format: CertificateFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn output_dir(mut self, output_dir: impl Into<PathBuf>) -> Self {
+        self.output_dir = output_dir.into();
+        self
+    }
+
+    /// Write the files under `dir` first and move them into the output directory once the
+    /// batch has rendered, for an output directory on a network share, where writing file
+    /// after file is slow. Everything else, such as which files already exist and the
+    /// manifest, is about the output directory as usual.
+    ///
+    /// ```
+    /// use certificate_maker::job::CertificateJob;
+    ///
+    /// let dir = std::env::temp_dir().join(format!("certmaker-stage-{}", std::process::id()));
+    /// let report = CertificateJob::new("tests/fixtures/template.png", vec!["Ada Lovelace".to_string()])
+    ///     .font("tests/fixtures/DejaVuSans-Latin.ttf")
+    ///     .output_dir(dir.join("share"))
+    ///     .stage_locally(dir.join("local"))
+    ///     .run()?;
+    /// assert!(report.items[0].output_file.starts_with(dir.join("share")));
+    /// assert!(report.items[0].output_file.exists());
+    /// assert_eq!(std::fs::read_dir(dir.join("local"))?.count(), 0);
+    /// std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn stage_locally(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.staging = Some(dir.into());
+        self
+    }
+
+    /// How to treat files that are already in the output directory; defaults to skipping them.
+    pub fn overwrite(mut self, overwrite: OverwritePolicy) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// How repeated names in the list were resolved, recorded in the manifest. Rows whose
+    /// names are still repeated each get a certificate, with `_1`, `_2`, ... suffixes.
+    pub fn duplicates(mut self, duplicates: Vec<DuplicateDecision>) -> Self {
+        self.duplicates = duplicates;
+        self
+    }
+
+    /// Case transform for the drawn text; filenames and the manifest keep the names as given.
+    pub fn name_case(mut self, name_case: NameCase) -> Self {
+        self.name_case = name_case;
+        self
+    }
+
+    /// Typographic cleanup of the drawn text: the name and every text field and table cell,
+    /// after the case transform. Filenames and the manifest keep the values as given.
+    pub fn typography(mut self, typography: Typography) -> Self {
+        self.typography = typography;
+        self
+    }
+
+    /// Also use the transformed names for filenames and manifest entries.
+    pub fn case_filenames(mut self, case_filenames: bool) -> Self {
+        self.case_filenames = case_filenames;
+        self
+    }
+
+    /// Line number of each name in its source file, in the same order as the names; recorded
+    /// in the manifest.
+    pub fn rows(mut self, rows: Vec<usize>) -> Self {
+        self.rows = rows;
+        self
+    }
+
+    /// File of each name, in the same order as the names, when names from several files were
+    /// merged; recorded in the manifest.
+    pub fn sources(mut self, sources: Vec<PathBuf>) -> Self {
+        self.sources = sources;
+        self
+    }
+
+    /// Recipient address of each name, in the same order as the names; empty strings where
+    /// there is none. Recorded in the manifest, along with whether the certificate was
+    /// already emailed to that address.
+    pub fn emails(mut self, emails: Vec<String>) -> Self {
+        self.emails = emails;
+        self
+    }
+
+    /// Every column of each name's row, in the same order as the names; graphic fields fill
+    /// their `{Column}` placeholders from it.
+    pub fn records(mut self, records: Vec<Record>) -> Self {
+        self.records = records;
+        self
+    }
+
+    /// Save each certificate in a subdirectory named after its row's value of `column` (see
+    /// [`group_dir_name`]), recorded as the manifest entry's group; rows where it is empty go
+    /// into [`UNGROUPED_DIR`] with a warning. Needs [`records`](Self::records).
+    pub fn group_by(mut self, column: impl Into<String>) -> Self {
+        self.group_by = Some(column.into());
+        self
+    }
+
+    /// The names are only part of a list: certificates of the other rows are kept in the
+    /// manifest and never reported as stale.
+    pub fn partial(mut self, partial: bool) -> Self {
+        self.partial = partial;
+        self
+    }
+
+    /// Do everything but draw and save: every name is measured and checked, output files are
+    /// planned and the would-be manifest is written to [`DRY_RUN_MANIFEST_FILENAME`]. The
+    /// report is marked [`BatchReport::dry_run`] and counts what a real run would generate.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Make the batch reproducible: the same template, font, settings and names write
+    /// byte-identical certificates and `manifest.json`, on any number of threads. Certificates
+    /// carry no timestamps either way; this pins the batch time in the manifest to
+    /// [`SOURCE_DATE_EPOCH`], or the Unix epoch when that isn't set.
+    ///
+    /// ```
+    /// use certificate_maker::editpng::save_image;
+    /// use certificate_maker::job::CertificateJob;
+    /// use certificate_maker::manifest::hash_file;
+    /// use image::{ImageFormat, Rgba, RgbaImage};
+    ///
+    /// let dir = std::env::temp_dir().join(format!("certmaker-deterministic-{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir)?;
+    /// let template = dir.join("template.png");
+    /// save_image(&RgbaImage::from_pixel(600, 300, Rgba([250, 245, 230, 255])), &template, ImageFormat::Png)?;
+    /// let names = vec!["Ada Lovelace".to_string(), "Grace Hopper".to_string(), "Édith Piaf".to_string()];
+    ///
+    /// let mut runs = Vec::new();
+    /// for threads in [1, 3] {
+    ///     let output = dir.join("certificates");
+    ///     let report = CertificateJob::new(&template, names.clone())
+    ///         .output_dir(&output)
+    ///         .threads(threads)
+    ///         .deterministic(true)
+    ///         .run()?;
+    ///     let mut files: Vec<_> = report.items.iter().map(|item| item.output_file.clone()).collect();
+    ///     files.push(output.join("manifest.json"));
+    ///     runs.push(files.iter().map(hash_file).collect::<Result<Vec<_>, _>>()?);
+    ///     std::fs::remove_dir_all(&output)?;
+    /// }
+    /// assert_eq!(runs[0].len(), 4);
+    /// assert_eq!(runs[0], runs[1]);
+    /// std::fs::remove_dir_all(&dir)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    ///
+    /// [`SOURCE_DATE_EPOCH`]: https://reproducible-builds.org/specs/source-date-epoch/
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// How hard to compress the PNGs; [`PngCompression::Balanced`] by default. `Fast` encodes
+    /// several times quicker for noticeably larger files, `Small` the other way round.
+    pub fn compression(mut self, compression: PngCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Whether a 16-bit PNG template keeps its depth: with [`DepthPolicy::Keep`], the default,
+    /// PNG certificates drawn on one are saved with 16 bits per channel (see [`crate::depth`]).
+    /// [`DepthPolicy::Reduce`] draws and saves them at 8 bits, as before.
+    pub fn depth(mut self, depth: DepthPolicy) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// What the batch does with the template's depth; `None` for an 8-bit template.
+    pub fn deep_color(&self) -> Result<Option<DeepColor>> {
+        let normalized = self.template_plan.is_some() || self.normalize_plan()?.is_some_and(|plan| !plan.is_identity());
+        deep_color(&self.template, self.depth, self.format, normalized)
+    }
+
+    /// Also save a JPEG preview of each certificate, at most `max_dimension` pixels on its
+    /// longer side, as `<certificate>_preview.jpg` next to it (see [`crate::preview`]). It is
+    /// made from the image just drawn, and recorded with its hash in the certificate's manifest
+    /// entry. Off by default.
+    pub fn preview(mut self, max_dimension: u32) -> Self {
+        self.preview = Some(max_dimension);
+        self
+    }
+
+    /// Draw a watermark over every certificate, after the name and the graphics (see
+    /// [`crate::watermark`]), such as [`WatermarkStyle::draft`] on copies sent for approval.
+    /// Each certificate's manifest entry records the watermark's text, and the same batch
+    /// run without it renders every certificate again, clean.
+    pub fn watermark(mut self, style: WatermarkStyle) -> Self {
+        self.watermark = Some(style);
+        self
+    }
+
+    /// Record how each certificate was made (see [`crate::fingerprint`]): the crate version and
+    /// the resolved settings go into each PNG's metadata and the manifest, and with
+    /// [`FingerprintMode::Visible`] a label like `CM 0.1.0 · a1b2c3` is drawn small in the
+    /// bottom-right corner. PDFs record it in the manifest only. Off by default.
+    ///
+    /// ```
+    /// use certificate_maker::fingerprint::FingerprintMode;
+    /// use certificate_maker::job::CertificateJob;
+    ///
+    /// let job = CertificateJob::new("tests/fixtures/template.png", vec!["Ada Lovelace".to_string()])
+    ///     .font("tests/fixtures/DejaVuSans-Latin.ttf");
+    /// let fingerprint = job.settings_fingerprint()?;
+    /// assert_eq!(fingerprint, job.clone().fingerprint(FingerprintMode::Visible).settings_fingerprint()?);
+    /// assert_ne!(fingerprint, job.clone().font_size(41.0).settings_fingerprint()?);
+    /// assert_eq!(fingerprint.settings["font_size"], 40.0);
+    ///
+    /// // Drawn only when visible
+    /// let plain = job.render_image(0)?;
+    /// assert_eq!(plain, job.clone().fingerprint(FingerprintMode::Hidden).render_image(0)?);
+    /// assert_ne!(plain, job.clone().fingerprint(FingerprintMode::Visible).render_image(0)?);
+    /// # Ok::<(), certificate_maker::error::CertError>(())
+    /// ```
+    pub fn fingerprint(mut self, mode: FingerprintMode) -> Self {
+        self.fingerprint = mode;
+        self
+    }
+
+    /// The fingerprint of the settings the batch draws with, whether or not it
+    /// [carries one](Self::fingerprint).
+    pub fn settings_fingerprint(&self) -> Result<Fingerprint> {
+        let job = self.normalized()?;
+        let font_hash = hash_bytes(&load_font_data(&job.font)?);
+        job.fingerprint_of(&hash_file(&job.template)?, &font_hash)
+    }
+
+    /// After the batch, also lay its certificates out on print sheets (see
+    /// [`crate::imposition`]), unchanged ones included, in distribution order. The
+    /// certificates must be PNGs. The sheets are recorded with the batch, so undo removes them.
+    pub fn impose(mut self, imposition: Imposition) -> Self {
+        self.imposition = Some(imposition);
+        self
+    }
+
+    /// Bring the template to an exact size before drawing (see [`crate::normalize`]). The
+    /// position, font size, maximum width and graphics are still given against the template
+    /// as it is on disk, and are moved onto the new size with it.
+    ///
+    /// ```
+    /// use certificate_maker::job::CertificateJob;
+    /// use certificate_maker::normalize::Normalization;
+    ///
+    /// let job = CertificateJob::new("tests/fixtures/template.png", vec!["Ada Lovelace".to_string()])
+    ///     .font("tests/fixtures/DejaVuSans-Latin.ttf")
+    ///     .normalize(Normalization::parse("1200x800").unwrap());
+    /// assert_eq!(job.normalize_plan()?.unwrap().scale_x, 2.0);
+    /// assert_eq!(job.render_image(0)?.dimensions(), (1200, 800));
+    /// # Ok::<(), certificate_maker::error::CertError>(())
+    /// ```
+    pub fn normalize(mut self, normalization: Normalization) -> Self {
+        self.normalize = Some(normalization);
+        self
+    }
+
+    /// How the template will be normalized; `None` without [`normalize`](Self::normalize).
+    pub fn normalize_plan(&self) -> Result<Option<NormalizePlan>> {
+        let Some(normalization) = self.normalize else { return Ok(None) };
+        let dimensions = oriented_dimensions(&self.template)
+            .map_err(|source| CertError::ImageDecode { path: self.template.clone(), source })?;
+        Ok(Some(normalization.plan(dimensions)))
+    }
+
+    /// Number of worker threads; defaults to rayon's global pool.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Run only as many worker threads as fit in `bytes` of memory, from the template's size;
+    /// see [`memory_plan`](Self::memory_plan). Without a budget every thread runs. A real run
+    /// under a budget also keeps only its problem rows in the report, at most
+    /// [`BUDGET_REPORTED_ROWS`]; the progress sink still sees every row.
+    pub fn memory_budget(mut self, bytes: u64) -> Self {
+        self.memory_budget = Some(bytes);
+        self
+    }
+
+    /// How many threads the batch will run and the memory they will hold at the peak.
+    pub fn memory_plan(&self) -> Result<MemoryPlan> {
+        let dimensions = self.template_dimensions()?;
+        let available = self.threads.unwrap_or_else(rayon::current_num_threads);
+        let plan = match self.deep_color()? {
+            Some(DeepColor::Kept) => MemoryPlan::for_deep_template(dimensions, available, self.memory_budget),
+            _ => MemoryPlan::for_template(dimensions, available, self.memory_budget),
+        };
+        plan.map_err(|problem| CertError::InvalidJob(vec![problem]))
+    }
+
+    pub fn template(&self) -> &Path {
+        &self.template
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn output_directory(&self) -> &Path {
+        &self.output_dir
+    }
+
+    /// Measure the first name at the font size against the template and the
+    /// [limits](Self::font_size_limits), at the DPI the template records.
+    pub fn check_font_size(&self) -> Result<FontSizeCheck> {
+        let font = load_font(&self.font)?;
+        let text = self.names.first().map_or("", String::as_str);
+        let dpi = image_dpi(&self.template).ok().flatten();
+        check_font_size(&font, text, self.font_size, self.template_dimensions()?, dpi, &self.font_size_limits)
+    }
+
+    /// Check every setting and return all problems at once.
+    pub fn validate(&self) -> std::result::Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+
+        if self.names.is_empty() {
+            problems.push("No names to generate certificates for".to_string());
+        }
+        if !self.template.exists() {
+            problems.push(format!("Template file not found: {}", self.template.display()));
+        } else if let Err(e) = oriented_dimensions(&self.template) {
+            problems.push(format!("Template is not a readable image: {} ({})", self.template.display(), e));
+        }
+        if let Err(e) = load_font(&self.font) {
+            problems.push(e.to_string());
+        }
+        if !self.font_size.is_finite() || self.font_size <= 0.0 {
+            problems.push(format!("Font size must be a positive number, got {}", self.font_size));
+        }
+        if let Err(problem) = self.font_size_limits.validate() {
+            problems.push(problem);
+        } else if self.strict_font_size
+            && let Ok(check) = self.check_font_size()
+            && let Some(message) = check.message() {
+            problems.push(format!("The font size doesn't suit the template: {}", message));
+        }
+        if let Some(max_width) = self.max_width
+            && (!max_width.is_finite() || max_width <= 0.0) {
+            problems.push(format!("Maximum text width must be a positive number, got {}", max_width));
+        }
+        if self.overflow == TextOverflow::Ellipsis && self.max_width.is_none() {
+            problems.push("Names are only cut short past a maximum text width, and none is set".to_string());
+        }
+        if !self.texts.is_empty() && self.texts.len() != self.names.len() {
+            problems.push(format!("{} texts were given for {} names", self.texts.len(), self.names.len()));
+        }
+        if !self.planned.is_empty() && self.planned.len() != self.names.len() {
+            problems.push(format!("{} planned rows were given for {} names", self.planned.len(), self.names.len()));
+        }
+        match check_color(&self.color) {
+            Ok(_) => {}
+            Err(e @ (CertError::OffPalette { .. } | CertError::UnknownPaletteColor { .. })) => problems.push(e.to_string()),
+            Err(e) => problems.push(format!("Invalid color '{}': {}", self.color, e)),
+        }
+        if !self.font_variation.is_empty()
+            && let Err(e) = load_font_data(&self.font).and_then(|data| check_axis_values(&data, &self.font_variation)) {
+            problems.push(e.to_string());
+        }
+        if let Some(effect) = &self.effect
+            && let Err(e) = effect.validate() {
+            problems.push(e.to_string());
+        }
+        if let Err(e) = self.synthetic.validate() {
+            problems.push(e.to_string());
+        }
+        for graphic in &self.graphics {
+            if let Err(e) = graphic.validate() {
+                problems.push(e.to_string());
+            }
+        }
+        if let Some((x, y)) = self.position
+            && !self.bleed
+            && let Ok(dimensions) = self.template_dimensions()
+            && let Err(e) = check_position(x, y, dimensions) {
+            problems.push(e.to_string());
+        }
+        if let Ok((width, height)) = oriented_dimensions(&self.template) {
+            for patch in &self.patches {
+                if let Err(e) = patch.validate(width, height) {
+                    problems.push(e.to_string());
+                }
+            }
+            for border in &self.borders {
+                if let Err(e) = border.validate(width, height) {
+                    problems.push(e.to_string());
+                }
+            }
+        }
+        if let Some(Err(back_problems)) = self.back.as_ref().map(BackSide::validate) {
+            problems.extend(back_problems);
+        }
+        if let Some(column) = &self.group_by {
+            if self.records.is_empty() {
+                problems.push(format!("Grouping by '{}' needs each row's columns (CertificateJob::records)", column));
+            } else if self.records.iter().all(|record| record.get(column).is_none()) {
+                let headers: Vec<&str> = self.records[0].fields.iter().map(|(header, _)| header.as_str()).collect();
+                problems.push(format!("There is no column '{}' to group by; the list's columns are {}", column, headers.join(", ")));
+            }
+        }
+        if self.deterministic
+            && let Ok(value) = std::env::var("SOURCE_DATE_EPOCH")
+            && value.trim().parse::<u64>().is_err() {
+            problems.push(format!("SOURCE_DATE_EPOCH must be a whole number of seconds, got '{}'", value));
+        }
+        if let Some(normalization) = self.normalize
+            && (normalization.width == 0 || normalization.height == 0) {
+            problems.push(format!("Normalized template size must be at least 1x1 pixels, got {}x{}", normalization.width, normalization.height));
+        }
+        if let Some(Err(e)) = self.watermark.as_ref().map(WatermarkStyle::validate) {
+            problems.push(e.to_string());
+        }
+        if let Some(imposition) = &self.imposition {
+            if self.format != CertificateFormat::Png {
+                problems.push("Sheets are made from PNG certificates; save the certificates as PNG (the sheets can still be a PDF)".to_string());
+            }
+            let certificate = match self.normalize {
+                Some(normalization) => Ok((normalization.width, normalization.height)),
+                None => oriented_dimensions(&self.template),
+            };
+            if let Ok(certificate) = certificate
+                && let Err(e) = imposition.validate().and_then(|()| imposition.check_fit(certificate, self.certificate_dpi())) {
+                problems.push(e.to_string());
+            }
+        }
+        if self.preview == Some(0) {
+            problems.push("Preview size must be at least 1 pixel".to_string());
+        }
+        if self.threads == Some(0) {
+            problems.push("Thread count must be at least 1".to_string());
+        }
+        if let Some(budget) = self.memory_budget
+            && let Ok(dimensions) = oriented_dimensions(&self.template)
+            && let Err(problem) = match self.deep_color() {
+                Ok(Some(DeepColor::Kept)) => MemoryPlan::for_deep_template(dimensions, 1, Some(budget)),
+                _ => MemoryPlan::for_template(dimensions, 1, Some(budget)),
+            } {
+            problems.push(problem);
+        }
+
+        if problems.is_empty() { Ok(()) } else { Err(problems) }
+    }
+
+    /// Validate, then render every certificate.
+    pub fn run(&self) -> Result<BatchReport> {
+        self.run_with_progress(&NoProgress)
+    }
+
+    /// Like [`run`](Self::run), reporting progress to `progress`.
+    pub fn run_with_progress(&self, progress: &dyn ProgressSink) -> Result<BatchReport> {
+        if let Err(problems) = self.validate() {
+            return Err(CertError::InvalidJob(problems));
+        }
+
+        let job = self.normalized()?;
+        // A budget only ever lowers the thread count; without one nothing changes
+        let threads = match job.memory_budget {
+            Some(_) => Some(job.memory_plan()?.threads),
+            None => job.threads,
+        };
+        match threads {
+            Some(threads) => {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()?;
+                pool.install(|| job.render_all(progress))
+            }
+            None => job.render_all(progress),
+        }
+    }
+
+    /// Draw the certificate of row `index` in memory, pixel for pixel as [`run`](Self::run)
+    /// would before encoding it, without writing anything.
+    ///
+    /// ```
+    /// use certificate_maker::job::CertificateJob;
+    ///
+    /// let job = CertificateJob::new("tests/fixtures/template.png", vec!["Ada Lovelace".to_string()])
+    ///     .font("tests/fixtures/DejaVuSans-Latin.ttf");
+    /// let img = job.render_image(0)?;
+    /// assert_eq!(img.dimensions(), (600, 400));
+    /// assert_eq!(img.get_pixel(300, 200).0[..3], [0, 0, 0]);
+    /// assert!(job.render_image(1).is_err());
+    /// # Ok::<(), certificate_maker::error::CertError>(())
+    /// ```
+    pub fn render_image(&self, index: usize) -> Result<RgbaImage> {
+        if let Err(problems) = self.validate() {
+            return Err(CertError::InvalidJob(problems));
+        }
+        let job = self.normalized()?;
+        job.render_normalized_image(index)
+    }
+
+    /// Draw the back of row `index` in memory, as [`run`](Self::run) would; `None` for a
+    /// single-sided job.
+    pub fn render_back_image(&self, index: usize) -> Result<Option<RgbaImage>> {
+        if let Err(problems) = self.validate() {
+            return Err(CertError::InvalidJob(problems));
+        }
+        let job = self.normalized()?;
+        let Some(back) = &job.back else { return Ok(None) };
+        let Some(name) = job.names.get(index) else {
+            return Err(CertError::InvalidJob(vec![format!("There is no row {} in a job of {} names", index, job.names.len())]));
+        };
+        let font = load_font(&job.font)?;
+        let canvas = job.back_canvas(back, &font)?;
+        let data = job.fill_graphics(&back.graphics, &job.name_case.apply(name), index, &mut Vec::new())?;
+        let placed = layout_graphic_fields(&back.graphics, &data, &font, &canvas.fonts, canvas.template.dimensions())?;
+        job.draw_back(&canvas, &placed).map(Some)
+    }
+
+    fn render_normalized_image(&self, index: usize) -> Result<RgbaImage> {
+        self.render_image_on(index, load_template(&self.template)?)
+    }
+
+    /// Draw the certificate of row `index` on `template`, decoded already but not yet patched
+    /// or bordered, the way [`render_image`](Self::render_image) draws it on the template
+    /// file. The job is neither validated nor normalized; callers that keep templates in
+    /// memory, like the HTTP service, check their own input.
+    pub(crate) fn render_image_on(&self, index: usize, template: RgbaImage) -> Result<RgbaImage> {
+        let Some(name) = self.names.get(index) else {
+            return Err(CertError::InvalidJob(vec![format!("There is no row {} in a job of {} names", index, self.names.len())]));
+        };
+        let drawn_name = self.name_case.apply(name);
+        let text = match self.texts.get(index) {
+            Some(text) => self.name_case.apply(text),
+            None => drawn_name.clone(),
+        };
+        let text = self.typography.apply(&strip_control(&text));
+
+        let mut img = self.prepare_template(template)?;
+        let (x, y) = self.resolved_position()?;
+        let font = load_font(&self.font)?;
+        let font_data = load_font_data(&self.font)?;
+        let decorations = self.resolved_decorations(&font_data);
+        let fingerprint = match self.fingerprint {
+            FingerprintMode::Visible => Some(self.fingerprint_of(&hash_file(&self.template)?, &hash_bytes(&font_data))?),
+            _ => None,
+        };
+        let instance = if self.font_variation.is_empty() {
+            None
+        } else {
+            Some(FontInstance::new(font_data, self.font_variation.clone())?)
+        };
+        let options = TextOptions {
+            font: &font,
+            size: self.font_size,
+            color: hex_to_rgba(&self.color)?,
+            x,
+            y,
+            anchor: self.anchor,
+            decorations: &decorations,
+            synthetic: self.synthetic,
+            variation: instance.as_ref(),
+            effect: self.effect.as_ref(),
+        };
+        let (text, options) = match self.max_width {
+            Some(max_width) => fit_text(&text, &options, max_width, self.overflow, self.ellipsis_suffix())?,
+            None => (text, options),
+        };
+        let graphic_fonts = GraphicFonts::load(&self.graphics)?;
+        let data = self.fill_graphics(&self.graphics, &drawn_name, index, &mut Vec::new())?;
+        let placed = layout_graphic_fields(&self.graphics, &data, &font, &graphic_fonts, img.dimensions())?;
+        let watermark = self.watermark.as_ref().map(|style| WatermarkMask::new(style, &font, img.dimensions())).transpose()?;
+        self.draw_row(&mut img, &text, &options, &placed, &graphic_fonts, watermark.as_ref())?;
+        self.draw_fingerprint_label(&mut img, &font, fingerprint.as_ref())?;
+        Ok(img)
+    }
+
+    // What row `index` draws: its text, cased and cleaned up unless it was planned, and the
+    // data of the graphics on both sides
+    fn row_content(&self, index: usize, planned: Option<&PlannedRow>) -> RowContent {
+        let drawn_name = self.name_case.apply(&self.names[index]);
+        let mut retouched = Vec::new();
+        let text = match planned {
+            // Already cased, cleaned up and cut short when it was planned
+            Some(row) => row.text.clone(),
+            None => {
+                let cased = match self.texts.get(index) {
+                    Some(text) => self.name_case.apply(text),
+                    None => drawn_name.clone(),
+                };
+                // Control characters would only be drawn as boxes
+                let cased = strip_control(&cased).into_owned();
+                // Cleaned up after the case transform, for drawing only, and hashed like the case
+                let text = self.typography.apply(&cased);
+                if text != cased {
+                    retouched.push(RetouchedText { field: "name".to_string(), before: cased, after: text.clone() });
+                }
+                text
+            }
+        };
+        let graphic_data = self.fill_graphics(&self.graphics, &drawn_name, index, &mut retouched)
+            .map_err(|e| e.to_string());
+        let mut back_retouched = Vec::new();
+        let back_data = self.back.as_ref().map(|back| {
+            self.fill_graphics(&back.graphics, &drawn_name, index, &mut back_retouched)
+                .map_err(|e| format!("on the back: {}", e))
+        });
+        retouched.extend(back_retouched.into_iter()
+            .map(|value| RetouchedText { field: format!("back: {}", value.field), ..value }));
+        RowContent { text, graphic_data, back_data, retouched }
+    }
+
+    // Each graphic's data for row `index`, cleaned up by the typography pass; the values it
+    // changed are added to `retouched`
+    fn fill_graphics(&self, graphics: &[GraphicField], drawn_name: &str, index: usize, retouched: &mut Vec<RetouchedText>) -> Result<Vec<String>> {
+        graphics.iter()
+            .map(|graphic| {
+                graphic.fill(drawn_name, self.records.get(index))
+                    // What is drawn as text loses its control characters; barcodes and image paths are left alone
+                    .map(|data| match graphic.kind {
+                        GraphicKind::Text(_) | GraphicKind::Table(_) => strip_control(&data).into_owned(),
+                        GraphicKind::Image(_) | GraphicKind::Code128(_) => data,
+                    })
+                    .and_then(|data| graphic.retouch(data, &self.typography, retouched))
+            })
+            .collect()
+    }
+
+    // Resolved anchor position (defaults to the template center)
+    fn resolved_position(&self) -> Result<(i32, i32)> {
+        match self.position {
+            Some(position) => Ok(position),
+            None => {
+                let (width, height) = self.template_dimensions()?;
+                Ok((width as i32 / 2, height as i32 / 2))
+            }
+        }
+    }
+
+    // The resolution the certificates are rendered at: the template's, or the PDF default
+    fn certificate_dpi(&self) -> f32 {
+        image_dpi(&self.template).ok().flatten().unwrap_or(DEFAULT_PDF_DPI)
+    }
+
+    // Size of the template as drawn on, after any normalization
+    fn template_dimensions(&self) -> Result<(u32, u32)> {
+        if let Some(plan) = self.template_plan {
+            return Ok(plan.target);
+        }
+        oriented_dimensions(&self.template)
+            .map_err(|source| CertError::ImageDecode { path: self.template.clone(), source })
+    }
+
+    // What cut-short names end with
+    fn ellipsis_suffix(&self) -> &str {
+        self.ellipsis.as_deref().unwrap_or(ELLIPSIS)
+    }
+
+    // Whether the name, a text field or a table is colored with a palette:<name> reference
+    fn uses_palette(&self) -> bool {
+        let back_graphics = self.back.iter().flat_map(|back| &back.graphics);
+        let text_colors = self.graphics.iter().chain(back_graphics).flat_map(|graphic| match &graphic.kind {
+            GraphicKind::Text(style) => vec![&style.color],
+            GraphicKind::Table(style) => std::iter::once(&style.color).chain(&style.rule_color).collect(),
+            _ => Vec::new(),
+        });
+        std::iter::once(&self.color).chain(text_colors).any(|color| color.trim().starts_with(PALETTE_PREFIX))
+    }
+
+    // Every effect the name and the text fields are drawn with, each once, for the manifest
+    fn effects_used(&self) -> Vec<TextEffect> {
+        let back_graphics = self.back.iter().flat_map(|back| &back.graphics);
+        let field_effects = self.graphics.iter().chain(back_graphics).filter_map(|graphic| match &graphic.kind {
+            GraphicKind::Text(style) => style.effect.as_ref(),
+            _ => None,
+        });
+        let mut effects: Vec<TextEffect> = Vec::new();
+        for effect in self.effect.iter().chain(field_effects) {
+            if !effects.contains(effect) {
+                effects.push(effect.clone());
+            }
+        }
+        effects
+    }
+
+    // Every setting that decides how a certificate looks, as resolved, for its fingerprint. Files
+    // are named by file name and content, so moving them doesn't change it; settings that aren't
+    // serializable are written as they debug-print, as in the row key
+    fn fingerprint_of(&self, template_hash: &str, font_hash: &str) -> Result<Fingerprint> {
+        let file_name = |path: &Path| path.file_name().map(|name| name.to_string_lossy().into_owned());
+        let debug = |value: &dyn std::fmt::Debug| format!("{:?}", value);
+        let palette = installed_palette().filter(|_| self.uses_palette()).map(|palette| debug(&palette.colors));
+        let settings = serde_json::json!({
+            "template": { "file": file_name(&self.template), "hash": template_hash },
+            "template_size": self.template_dimensions()?,
+            "normalization": self.template_plan,
+            "patches": self.patches,
+            "borders": self.borders.iter().map(|border| debug(border)).collect::<Vec<_>>(),
+            "font": { "file": file_name(&self.font), "hash": font_hash },
+            "font_size": self.font_size,
+            "font_variation": self.font_variation,
+            "color": self.color,
+            "palette": palette,
+            "anchor": self.anchor,
+            "position": self.resolved_position()?,
+            "max_width": self.max_width,
+            "overflow": self.overflow,
+            "ellipsis": (self.overflow == TextOverflow::Ellipsis).then(|| self.ellipsis_suffix()),
+            "decorations": self.decorations.iter().map(|decoration| debug(decoration)).collect::<Vec<_>>(),
+            "synthetic": self.synthetic,
+            "effect": self.effect,
+            "name_case": self.name_case,
+            "typography": self.typography,
+            "graphics": self.graphics,
+            "back": self.back.as_ref().map(|back| debug(back)),
+            "watermark": self.watermark.as_ref().map(|style| debug(style)),
+            "format": self.format,
+            "compression": self.compression,
+            "depth": self.deep_color()?,
+        });
+        Ok(Fingerprint::new(settings))
+    }
+
+    // The decoded template, patched, normalized when the job says so, and with the borders
+    // drawn on it
+    fn load_template(&self) -> Result<RgbaImage> {
+        self.prepare_template(load_template(&self.template)?)
+    }
+
+    // `template` as decoded, patched, normalized and bordered like the template file
+    fn prepare_template(&self, mut template: RgbaImage) -> Result<RgbaImage> {
+        apply_patches(&mut template, &self.patches)?;
+        let mut template = match &self.template_plan {
+            Some(plan) => plan.apply(&template),
+            None => template,
+        };
+        for border in &self.borders {
+            draw_border(&mut template, border)?;
+        }
+        Ok(template)
+    }
+
+    // A copy with the position and sizes moved onto the normalized template, which it then
+    // loads; the job itself when there is nothing to normalize
+    fn normalized(&self) -> Result<Cow<'_, CertificateJob>> {
+        let back_plan = match (&self.back, self.normalize) {
+            (Some(back), Some(normalization)) => {
+                let dimensions = oriented_dimensions(&back.template)
+                    .map_err(|source| CertError::ImageDecode { path: back.template.clone(), source })?;
+                Some(normalization.plan(dimensions)).filter(|plan| !plan.is_identity())
+            }
+            _ => None,
+        };
+        let plan = self.normalize_plan()?.filter(|plan| !plan.is_identity());
+        if plan.is_none() && back_plan.is_none() {
+            return Ok(Cow::Borrowed(self));
+        }
+        let mut job = self.clone();
+        // The back is moved onto its own size, which needn't be the front's
+        if let (Some(back), Some(back_plan)) = (&mut job.back, back_plan) {
+            back.graphics = back.graphics.iter().map(|graphic| back_plan.map_graphic(graphic)).collect();
+            back.borders = back.borders.iter().map(|border| border.scaled(back_plan.factor() as f32)).collect();
+            job.back_plan = Some(back_plan);
+        }
+        let Some(plan) = plan else {
+            job.normalize = None;
+            return Ok(Cow::Owned(job));
+        };
+        let (x, y) = self.resolved_position()?;
+        job.position = Some(plan.map_point(x, y));
+        job.font_size = plan.map_length(self.font_size);
+        job.max_width = self.max_width.map(|max_width| plan.map_length(max_width));
+        job.synthetic.embolden = plan.map_length(self.synthetic.embolden);
+        job.graphics = self.graphics.iter().map(|graphic| plan.map_graphic(graphic)).collect();
+        job.borders = self.borders.iter().map(|border| border.scaled(plan.factor() as f32)).collect();
+        if let Some(watermark) = &mut job.watermark {
+            watermark.size = watermark.size.map(|size| plan.map_length(size));
+            if let WatermarkLayout::Tiled { spacing } = &mut watermark.layout {
+                *spacing = plan.map_length(*spacing);
+            }
+        }
+        job.normalize = None;
+        job.template_plan = Some(plan);
+        Ok(Cow::Owned(job))
+    }
+
+    // Where a row's certificate goes under the overwrite policy, and whether it was renamed;
+    // None skips the row. Files the previous manifest records for this name are our own output
+    fn plan_output(&self, default_output: PathBuf, name: &str, previous: &HashMap<&Path, &ManifestEntry>, taken: &HashSet<PathBuf>) -> Option<(PathBuf, bool)> {
+        let ours = |path: &Path| previous.get(path).is_some_and(|entry| entry.name == name);
+        let free = |path: &Path| !taken.contains(path) && (ours(path) || !path.exists());
+
+        if free(&default_output) {
+            return Some((default_output, false));
+        }
+        match self.overwrite {
+            OverwritePolicy::Overwrite if !taken.contains(&default_output) => Some((default_output, false)),
+            OverwritePolicy::SkipExisting if !taken.contains(&default_output) => None,
+            // Already claimed by another row of this batch, so it must be renamed either way
+            _ => Some((next_free_path(&default_output, |candidate| !free(candidate)), true)),
+        }
+    }
+
+    fn render_all(&self, progress: &dyn ProgressSink) -> Result<BatchReport> {
+        let started = Instant::now();
+        let output_dir = self.output_dir.as_path();
+        // Everything the rows share is checked before the workers start: a broken one fails the
+        // batch once here rather than every row the same way
+        check_output_dir(output_dir)?;
+        if let Some(staging) = &self.staging {
+            check_output_dir(staging)?;
+        }
+
+        let (x_pos, y_pos) = self.resolved_position()?;
+        let font_size = self.check_font_size()?;
+
+        // Load font once for text size calculations
+        let font = load_font(&self.font)?;
+        // Decode the template once; every worker draws on its own copy
+        let template = self.load_template()?;
+        // A 16-bit template is still drawn on at 8 bits; its own pixels go back in when saving
+        let template_depth = self.deep_color()?;
+        let deep = match template_depth {
+            Some(DeepColor::Kept) if !self.dry_run => load_deep_template(&self.template, &template)?,
+            _ => None,
+        };
+
+        // Hash shared inputs once so each row hash is cheap
+        let template_hash = hash_file(&self.template)?;
+        let font_data = load_font_data(&self.font)?;
+        let font_hash = hash_bytes(&font_data);
+        let decorations = self.resolved_decorations(&font_data);
+        let mut anchor_key = match self.anchor {
+            Anchor::Center => String::new(),
+            other => format!("{:?}", other),
+        };
+        // Decorations change the pixels too; added the same way so undecorated hashes stay put
+        for decoration in &decorations {
+            anchor_key.push_str(&format!("{:?}", decoration));
+        }
+        if !self.synthetic.is_none() {
+            anchor_key.push_str(&format!("{:?}", self.synthetic));
+        }
+        if !self.font_variation.is_empty() {
+            anchor_key.push_str(&format_axis_values(&self.font_variation));
+        }
+        if let Some(effect) = &self.effect {
+            anchor_key.push_str(&format!("effect={:?}", effect));
+        }
+        if let Some(max_width) = self.max_width {
+            anchor_key.push_str(&format!("max_width={}", max_width));
+        }
+        if self.overflow == TextOverflow::Ellipsis {
+            anchor_key.push_str(&format!("ellipsis={}", self.ellipsis_suffix()));
+        }
+        // Same pixels, different file; switching level re-encodes everything once
+        if self.compression != PngCompression::Balanced {
+            anchor_key.push_str(&format!("compression={}", self.compression.name()));
+        }
+        // Same drawing, saved at another depth
+        if template_depth == Some(DeepColor::Kept) {
+            anchor_key.push_str("depth=16");
+        }
+        // The normalized template is what is drawn on, so its size is part of the key
+        if let Some(plan) = &self.template_plan {
+            anchor_key.push_str(&format!("normalize={:?}", plan));
+        }
+        // A palette:<name> color follows the palette, so an edited palette re-renders the rows
+        if let Some(palette) = installed_palette().filter(|_| self.uses_palette()) {
+            anchor_key.push_str(&format!("palette={:?}", palette.colors));
+        }
+        if !self.patches.is_empty() {
+            anchor_key.push_str(&format!("patches={:?}", self.patches));
+        }
+        if !self.borders.is_empty() {
+            anchor_key.push_str(&format!("borders={:?}", self.borders));
+        }
+        if let Some(watermark) = &self.watermark {
+            anchor_key.push_str(&format!("watermark={:?}", watermark));
+        }
+        if let Some(back) = &self.back {
+            anchor_key.push_str(&format!("back={}{:?}{:?}{:?}{:?}", hash_file(&back.template)?, back.graphics, back.patches, back.borders, self.back_plan));
+        }
+        // Turning previews on renders every row once more, so each gets its preview
+        if let Some(preview) = self.preview {
+            anchor_key.push_str(&format!("preview={}", preview));
+        }
+        // Only the settings go into the fingerprint, so one serves every row
+        let fingerprint = match self.fingerprint {
+            FingerprintMode::Off => None,
+            mode => {
+                anchor_key.push_str(&format!("fingerprint={}", mode.name()));
+                Some(self.fingerprint_of(&template_hash, &font_hash)?)
+            }
+        };
+        let previous = Manifest::load(output_dir)?;
+        // Looked up for every row, so indexed once instead of scanned each time
+        let previous_entries = previous.by_output_file();
+
+        // Split rows into unchanged (skip) and new/changed (render). Every output filename is
+        // decided here, before the workers start, so renamed files can't collide
+        let mut manifest = Manifest {
+            duplicates: self.duplicates.clone(),
+            font_variation: self.font_variation.clone(),
+            effects: self.effects_used(),
+            ..Manifest::default()
+        };
+        let mut waiting = Vec::new();
+        let mut taken = HashSet::new();
+        let mut renamed = 0;
+        let mut skipped_existing = 0;
+        for (index, name) in self.names.iter().enumerate() {
+            let planned = self.planned.get(index);
+            // The drawn text is hashed, so changing the case re-renders; the name keeps the
+            // filename and manifest entry unless those were asked to follow the case too
+            let drawn_name = self.name_case.apply(name);
+            let content = self.row_content(index, planned);
+            let name = if self.case_filenames { drawn_name.clone() } else { name.clone() };
+            // Grouped rows go into their group's subdirectory, rows with no group into _ungrouped
+            let group = self.group_by.as_ref().map(|column| {
+                self.records.get(index)
+                    .and_then(|record| record.get(column))
+                    .map(str::trim)
+                    .filter(|value| group_dir_name(value).is_some())
+                    .map(str::to_string)
+            });
+            let row_dir = match &group {
+                None => output_dir.to_path_buf(),
+                Some(value) => output_dir.join(value.as_deref().and_then(group_dir_name).as_deref().unwrap_or(UNGROUPED_DIR)),
+            };
+            // A planned row's warnings already say so
+            let ungrouped = planned.is_none() && matches!(group, Some(None));
+            // A repeated name finds its default filename taken and is renamed
+            let (output_filename, was_renamed) = match planned {
+                Some(row) => (row.output_file.clone(), false),
+                None => {
+                    let default_output = self.output_path(&row_dir, &name);
+                    let Some(output) = self.plan_output(default_output, &name, &previous_entries, &taken) else {
+                        skipped_existing += 1;
+                        continue;
+                    };
+                    output
+                }
+            };
+            taken.insert(output_filename.clone());
+            let before = previous_entries.get(output_filename.as_path()).copied();
+
+            // Each row draws its own graphic data, so it joins the row's key along with the
+            // content of the images it names; a row whose data can't be filled in always fails again
+            let RowContent { text, graphic_data, back_data, .. } = &content;
+            let row_key = match graphic_data {
+                Ok(data) if !self.graphics.is_empty() => format!("{}{}", anchor_key, graphics_key(&self.graphics, data)),
+                _ => anchor_key.clone(),
+            };
+            // The back is filled in from the same row, so its data joins the key the same way
+            let row_key = match (back_data, &self.back) {
+                (Some(Ok(data)), Some(back)) if !back.graphics.is_empty() => format!("{}back{}", row_key, graphics_key(&back.graphics, data)),
+                _ => row_key,
+            };
+            // Hashed with its whole text, as a run that cuts it short itself would
+            let hashed_text = planned.map_or(text.as_str(), PlannedRow::full_text);
+            let content_hash = row_hash(hashed_text, &template_hash, &font_hash, self.font_size, &self.color, x_pos, y_pos, &row_key);
+            let unchanged = planned.is_none()
+                && before.is_some_and(|entry| entry.content_hash == content_hash)
+                && output_filename.exists()
+                && self.preview.is_none_or(|_| preview_path(&output_filename).exists())
+                && self.back_file(&output_filename).is_none_or(|back| back.exists())
+                && graphic_data.is_ok()
+                && back_data.as_ref().is_none_or(Result::is_ok);
+
+            // A certificate already emailed to this address isn't sent again, even if re-rendered
+            let email = self.emails.get(index).filter(|email| !email.is_empty()).cloned();
+            let emailed = before
+                .and_then(|entry| entry.emailed.clone())
+                .filter(|sent| email.as_ref() == Some(&sent.to));
+            manifest.entries.push(ManifestEntry {
+                name: name.clone(),
+                output_file: output_filename.clone(),
+                content_hash,
+                row: self.rows.get(index).copied(),
+                source: self.sources.get(index).cloned(),
+                email,
+                group: group.flatten(),
+                emailed,
+                // Kept for unchanged rows, filled in below for the ones rendered now
+                file_hash: before.and_then(|entry| entry.file_hash.clone()),
+                preview: before.and_then(|entry| entry.preview.clone()),
+                back: before.and_then(|entry| entry.back.clone()),
+                watermark: self.watermark.as_ref().map(|style| style.text.clone()),
+                fingerprint: before.and_then(|entry| entry.fingerprint.clone()),
+                issued: before.and_then(|entry| entry.issued),
+                ellipsized: before.map(|entry| entry.ellipsized.clone()).unwrap_or_default(),
+            });
+            if !unchanged {
+                if was_renamed {
+                    renamed += 1;
+                }
+                // Filled in again when its chunk starts, so nothing of it is held till then
+                waiting.push(WaitingRow { index, entry: manifest.entries.len() - 1, renamed: was_renamed, ungrouped });
+            }
+        }
+
+        let skipped_unchanged = manifest.entries.len() - waiting.len();
+        let current: HashSet<&Path> = manifest.entries.iter().map(|entry| entry.output_file.as_path()).collect();
+        let stale: Vec<ManifestEntry> = previous.entries.iter()
+            .filter(|entry| !current.contains(entry.output_file.as_path()))
+            .cloned()
+            .collect();
+
+        // Group subdirectories are made up front, so the workers only write files
+        if !self.dry_run {
+            let dirs: HashSet<&Path> = waiting.iter().filter_map(|row| manifest.entries[row.entry].output_file.parent()).collect();
+            for dir in dirs.into_iter().flat_map(|dir| [dir.to_path_buf(), self.staged(dir)]) {
+                std::fs::create_dir_all(&dir)
+                    .io_context(|| format!("Failed to create output directory: {}", dir.display()))?;
+            }
+        }
+
+        let instance = if self.font_variation.is_empty() {
+            None
+        } else {
+            Some(FontInstance::new(font_data.clone(), self.font_variation.clone())?)
+        };
+        let text_options = TextOptions {
+            font: &font,
+            size: self.font_size,
+            color: hex_to_rgba(&self.color)?,
+            x: x_pos,
+            y: y_pos,
+            anchor: self.anchor,
+            decorations: &decorations,
+            synthetic: self.synthetic,
+            variation: instance.as_ref(),
+            effect: self.effect.as_ref(),
+        };
+        let graphic_fonts = GraphicFonts::load(&self.graphics)?;
+        // Rasterized once; every row only blends it in
+        let watermark = match &self.watermark {
+            Some(style) if !self.dry_run => Some(WatermarkMask::new(style, &font, template.dimensions())?),
+            _ => None,
+        };
+        let mut back_canvas = self.back.as_ref().map(|back| self.back_canvas(back, &font)).transpose()?;
+        if let (Some(back), Some(canvas)) = (&self.back, &mut back_canvas)
+            && !self.dry_run
+            && deep_color(&back.template, self.depth, self.format, self.back_plan.is_some())? == Some(DeepColor::Kept) {
+            canvas.deep = load_deep_template(&back.template, &canvas.template)?;
+        }
+        let pdf_dpi = self.certificate_dpi();
+        let total = waiting.len();
+        let completed = AtomicUsize::new(0);
+
+        let estimate = match waiting.first() {
+            Some(row) => {
+                let sample = self.row_content(row.index, self.planned.get(row.index)).text;
+                estimate_batch_for_template(&template, output_dir, &sample, total, &self.font, self.font_size, &self.color, self.compression).ok()
+            }
+            None => None,
+        };
+        let estimate = match (estimate, self.memory_budget) {
+            (Some(estimate), Some(budget)) => estimate.with_memory_budget(budget).ok(),
+            (estimate, _) => estimate,
+        };
+
+        // Sink panics are collected instead of unwinding through the workers
+        let progress_errors = Mutex::new(Vec::new());
+        if let Some(error) = call_sink(|| progress.on_start(total)) {
+            progress_errors.lock().unwrap_or_else(|e| e.into_inner()).push(error);
+        }
+
+        let render = |pending_row: &PendingRow| {
+            let PendingRow { index, name, output_file: output_filename, renamed: was_renamed, ungrouped, content, planned, .. } = pending_row;
+            let RowContent { text, graphic_data, back_data, retouched } = content;
+            let mut timings = StageTimings::default();
+            // Laid out and checked the same way in a dry run, so a clean one predicts a clean run
+            let laid_out = timings.time(Stage::Layout, || {
+                let (drawn, options) = match (planned, self.max_width) {
+                    // The plan's box and size, so nothing is centered or fitted again
+                    (Some(row), _) => (text.clone(), TextOptions { size: row.font_size, x: row.x, y: row.y, anchor: Anchor::TopLeft, ..text_options }),
+                    (None, Some(max_width)) => fit_text(text, &text_options, max_width, self.overflow, self.ellipsis_suffix())?,
+                    (None, None) => (text.clone(), text_options),
+                };
+                let bounds = layout_text(&drawn, &options)?;
+                let warnings = match planned {
+                    Some(row) => row.warnings.clone(),
+                    None => text_warnings(&font, &drawn, &bounds, template.dimensions()),
+                };
+                let graphics = graphic_data.clone().and_then(|data| {
+                    layout_graphic_fields(&self.graphics, &data, &font, &graphic_fonts, template.dimensions()).map_err(|e| e.to_string())
+                });
+                let back = match (back_data, &self.back, &back_canvas) {
+                    (Some(data), Some(back), Some(canvas)) => data.clone().and_then(|data| {
+                        layout_graphic_fields(&back.graphics, &data, &font, &canvas.fonts, canvas.template.dimensions())
+                            .map_err(|e| format!("on the back: {}", e))
+                    }).map(Some),
+                    _ => Ok(None),
+                };
+                Ok::<_, CertError>((drawn, bounds, options, warnings, graphics, back))
+            });
+            let mut ellipsized = Vec::new();
+            let (position, drawn_text, result, mut warnings) = match laid_out {
+                Ok((drawn, bounds, options, warnings, graphics, back)) => {
+                    if let Some(row) = planned {
+                        ellipsized.clone_from(&row.ellipsized);
+                    } else {
+                        if drawn != *text {
+                            ellipsized.push(EllipsizedText { field: "name".to_string(), full: text.clone(), drawn: drawn.clone() });
+                        }
+                        if let Ok(placed) = &graphics {
+                            ellipsized.extend(ellipsized_values(&self.graphics, placed));
+                        }
+                        if let (Ok(Some(placed)), Some(side)) = (&back, &self.back) {
+                            ellipsized.extend(ellipsized_values(&side.graphics, placed).into_iter()
+                                .map(|value| EllipsizedText { field: format!("back: {}", value.field), ..value }));
+                        }
+                    }
+                    let drawn_text = DrawnText { text: drawn.clone(), font_size: options.size, width: bounds.width, height: bounds.height };
+                    let result = match (graphics, back) {
+                        (Err(e), _) | (_, Err(e)) => Err(e),
+                        _ if self.dry_run => Ok(None),
+                        (Ok(placed), Ok(back_placed)) => {
+                            let back = back_canvas.as_ref().zip(back_placed.as_deref());
+                            self.render_row(&template, deep.as_ref(), &drawn, &options, &placed, &graphic_fonts, watermark.as_ref(), back, fingerprint.as_ref(), pdf_dpi, output_filename, &mut timings)
+                                .map(Some)
+                                .map_err(|e| e.to_string())
+                        }
+                    };
+                    ((bounds.x, bounds.y), Some(drawn_text), result, warnings)
+                }
+                Err(e) => ((x_pos, y_pos), None, Err(e.to_string()), Vec::new()),
+            };
+            if *ungrouped && let Some(column) = &self.group_by {
+                warnings.push(format!("its '{}' is empty, so it is saved in {}", column, UNGROUPED_DIR));
+            }
+            if *was_renamed {
+                let file_name = output_filename.file_name().unwrap_or_default().to_string_lossy();
+                warnings.push(format!("its file name was taken, so it is saved as {}", file_name));
+            }
+            for value in ellipsized.iter().filter(|_| planned.is_none()) {
+                warnings.push(format!("{} was cut short to \"{}\" to fit; the whole text is \"{}\"", value.field, value.drawn, value.full));
+            }
+
+            let (written, error) = match result {
+                Ok(written) => (written, None),
+                Err(e) => (None, Some(e)),
+            };
+            let item = BatchItem {
+                index: *index,
+                name: name.clone(),
+                output_file: output_filename.clone(),
+                position,
+                error,
+                warnings,
+                ellipsized,
+                retouched: retouched.clone(),
+                timings,
+                drawn: drawn_text,
+            };
+
+            let current_completed = completed.fetch_add(1, Ordering::Relaxed) + 1;
+            if let Some(error) = call_sink(|| progress.on_item(current_completed, &item)) {
+                progress_errors.lock().unwrap_or_else(|e| e.into_inner()).push(error);
+            }
+            (item, written)
+        };
+
+        let at = if self.deterministic {
+            source_date_epoch()
+        } else {
+            SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
+        };
+        // Under a memory budget only the counts, the timings and the first problem rows are
+        // kept; every row has been to the progress sink by then
+        // A dry run's report is its list of rows, so it keeps them all
+        let streaming = self.memory_budget.is_some() && !self.dry_run;
+        let mut items = Vec::new();
+        let (mut generated, mut failed) = (0, 0);
+        let mut row_timings = Vec::with_capacity(total);
+        let mut outputs = Vec::new();
+        let mut artifacts = Vec::new();
+        let mut states = vec![RowState::Unchanged; manifest.entries.len()];
+        for row in &waiting {
+            states[row.entry] = RowState::Waiting;
+        }
+        // Rendered RENDER_CHUNK_ROWS rows at a time: a chunk's rows are filled in when it
+        // starts, and what it wrote is saved before the next, so a batch stopped partway keeps
+        // its record. A dry run has nothing to keep, and a shard's manifest is only whole once
+        // every row of the shard is in it
+        let save_chunks = !self.dry_run && self.shard.is_none();
+        let mut waiting = waiting.into_iter();
+        loop {
+            let chunk: Vec<PendingRow> = waiting.by_ref()
+                .take(RENDER_CHUNK_ROWS)
+                .map(|row| PendingRow {
+                    index: row.index,
+                    entry: row.entry,
+                    name: manifest.entries[row.entry].name.clone(),
+                    output_file: manifest.entries[row.entry].output_file.clone(),
+                    renamed: row.renamed,
+                    ungrouped: row.ungrouped,
+                    content: self.row_content(row.index, self.planned.get(row.index)),
+                    planned: self.planned.get(row.index),
+                })
+                .collect();
+            if chunk.is_empty() {
+                break;
+            }
+            let (mut chunk_items, mut written): (Vec<BatchItem>, Vec<Option<WrittenRow>>) = chunk.par_iter().map(&render).unzip();
+            if self.staging.is_some() {
+                self.move_staged(&mut chunk_items, &mut written);
+            }
+            for ((item, written), row) in chunk_items.into_iter().zip(written).zip(&chunk) {
+                row_timings.push(item.timings);
+                if item.error.is_some() {
+                    // Failed rows must not be recorded, or the next run would skip them
+                    failed += 1;
+                    states[row.entry] = RowState::Failed;
+                } else {
+                    generated += 1;
+                    states[row.entry] = RowState::Written;
+                    let entry = &mut manifest.entries[row.entry];
+                    entry.issued = written.is_some().then_some(at);
+                    entry.file_hash = written.as_ref().map(|written| written.file_hash.clone());
+                    entry.preview = written.as_ref().and_then(|written| written.preview.clone());
+                    entry.fingerprint = written.as_ref().and(fingerprint.as_ref()).map(|fingerprint| fingerprint.hash.clone());
+                    entry.back = written.and_then(|written| written.back);
+                    entry.ellipsized = item.ellipsized.clone();
+                    artifacts.extend(entry.preview.clone());
+                    artifacts.extend(entry.back.clone());
+                    outputs.push(item.output_file.clone());
+                }
+                let problem = item.error.is_some() || !item.warnings.is_empty();
+                if !streaming || (problem && items.len() < BUDGET_REPORTED_ROWS) {
+                    items.push(item);
+                }
+            }
+            if save_chunks && waiting.len() > 0 {
+                // Rows still waiting keep what the last run recorded, so their old files
+                // aren't taken for new ones
+                let entries = manifest.entries.iter().zip(&states)
+                    .filter_map(|(entry, state)| match state {
+                        RowState::Unchanged | RowState::Written => Some(entry.clone()),
+                        RowState::Waiting => previous_entries.get(entry.output_file.as_path()).map(|&entry| entry.clone()),
+                        RowState::Failed => None,
+                    })
+                    .chain(stale.iter().cloned())
+                    .collect();
+                let mut so_far = Manifest {
+                    entries,
+                    duplicates: manifest.duplicates.clone(),
+                    font_variation: manifest.font_variation.clone(),
+                    effects: manifest.effects.clone(),
+                    last_batch: if outputs.is_empty() {
+                        previous.last_batch.clone()
+                    } else {
+                        Some(BatchRecord { at, outputs: outputs.clone(), artifacts: artifacts.clone() })
+                    },
+                    ..Manifest::default()
+                };
+                so_far.keep_fingerprints(fingerprint.iter().chain(&previous.fingerprints).cloned());
+                so_far.save(output_dir)?;
+            }
+        }
+        if self.staging.is_some() {
+            self.remove_staging_dirs();
+        }
+        // Only now are the failed rows known to all be out
+        let mut state = states.into_iter();
+        manifest.entries.retain(|_| state.next() != Some(RowState::Failed));
+        // A batch that rendered nothing (or a dry run) leaves the previous one as the batch to undo
+        manifest.last_batch = if self.shard.is_some() && outputs.is_empty() {
+            None
+        } else if outputs.is_empty() || self.dry_run {
+            previous.last_batch.clone()
+        } else {
+            // The previews and backs are undone along with their certificates
+            Some(BatchRecord { at, outputs, artifacts })
+        };
+        // Keep stale entries until their outputs are actually removed; a shard's manifest
+        // holds only its own, and the merge keeps the rest
+        let current_rows = manifest.entries.len();
+        if self.shard.is_none() {
+            manifest.entries.extend(stale.iter().cloned());
+        }
+        manifest.keep_fingerprints(fingerprint.iter().chain(&previous.fingerprints).cloned());
+        if self.dry_run {
+            manifest.save_as(output_dir.join(DRY_RUN_MANIFEST_FILENAME))?;
+        } else if let Some(record) = &self.shard {
+            manifest.shard = Some(record.clone());
+            manifest.save_as(output_dir.join(record.file_name()))?;
+        } else {
+            manifest.save(output_dir)?;
+        }
+        manifest.entries.truncate(current_rows);
+        let entries = manifest.entries;
+        let sheets = match &self.imposition {
+            Some(imposition) if !self.dry_run && !entries.is_empty() => Some(Box::new(impose_batch(&entries, output_dir, imposition, pdf_dpi)?)),
+            _ => None,
+        };
+
+        let performance = PerformanceBreakdown::from_timings(&row_timings);
+
+        let mut report = BatchReport {
+            dry_run: self.dry_run,
+            generated,
+            skipped_unchanged,
+            skipped_existing,
+            renamed,
+            failed,
+            items,
+            // Rows outside a partial run aren't gone from the list, just not selected
+            stale: if self.partial { Vec::new() } else { stale },
+            entries,
+            estimate,
+            threads: rayon::current_num_threads(),
+            duration: started.elapsed(),
+            performance,
+            normalization: self.template_plan,
+            deep_color: template_depth,
+            watermark: self.watermark.as_ref().map(|style| style.text.clone()),
+            fingerprint: fingerprint.map(Box::new),
+            font_size: Some(font_size).filter(|check| !check.is_ok()),
+            sheets,
+            progress_errors: progress_errors.into_inner().unwrap_or_else(|e| e.into_inner()),
+        };
+        if let Some(error) = call_sink(|| progress.on_finish(&report)) {
+            report.progress_errors.push(error);
+        }
+        Ok(report)
+    }
+}
+
+impl CertificateJob {
+    // The name, then the graphics over it, then the watermark over everything
+    fn draw_row(
+        &self,
+        img: &mut RgbaImage,
+        text: &str,
+        options: &TextOptions,
+        placed: &[PlacedGraphic],
+        graphic_fonts: &GraphicFonts,
+        watermark: Option<&WatermarkMask>,
+    ) -> Result<()> {
+        draw_text(img, text, options)?;
+        draw_graphic_fields(img, &self.graphics, placed, graphic_fonts)?;
+        if let Some(watermark) = watermark {
+            watermark.draw(img);
+        }
+        Ok(())
+    }
+
+    // The fingerprint's label in the corner, when it is visible
+    fn draw_fingerprint_label(&self, img: &mut RgbaImage, font: &rusttype::Font, fingerprint: Option<&Fingerprint>) -> Result<()> {
+        match fingerprint {
+            Some(fingerprint) if self.fingerprint == FingerprintMode::Visible => draw_fingerprint(img, font, &fingerprint.label()),
+            _ => Ok(()),
+        }
+    }
+
+    // The back's template and graphic fonts, and its own watermark mask since its size can differ
+    fn back_canvas(&self, back: &BackSide, font: &rusttype::Font<'static>) -> Result<BackCanvas> {
+        let template = back.load_template(self.back_plan.as_ref())?;
+        let watermark = match &self.watermark {
+            Some(style) if !self.dry_run => Some(WatermarkMask::new(style, font, template.dimensions())?),
+            _ => None,
+        };
+        Ok(BackCanvas { template, deep: None, fonts: GraphicFonts::load(&back.graphics)?, watermark })
+    }
+
+    // The back's graphics on a copy of its template, then the watermark over them
+    fn draw_back(&self, canvas: &BackCanvas, placed: &[PlacedGraphic]) -> Result<RgbaImage> {
+        let mut img = canvas.template.clone();
+        if let Some(back) = &self.back {
+            draw_graphic_fields(&mut img, &back.graphics, placed, &canvas.fonts)?;
+        }
+        if let Some(watermark) = &canvas.watermark {
+            watermark.draw(&mut img);
+        }
+        Ok(img)
+    }
+
+    // Where a row's certificate goes in `dir` before any renaming: a PDF, a PNG, or the front
+    // PNG of a double-sided one
+    fn output_path(&self, dir: &Path, name: &str) -> PathBuf {
+        let certificate = certificate_output_path(dir, name);
+        match (self.format, &self.back) {
+            (CertificateFormat::Pdf, _) => certificate.with_extension(CertificateFormat::Pdf.name()),
+            (CertificateFormat::Png, Some(_)) => front_path(&certificate),
+            (CertificateFormat::Png, None) => certificate,
+        }
+    }
+
+    // The separate back file that goes with a certificate; None when single-sided or in a PDF
+    fn back_file(&self, output_filename: &Path) -> Option<PathBuf> {
+        match (self.format, &self.back) {
+            (CertificateFormat::Png, Some(_)) => Some(back_path(output_filename)),
+            _ => None,
+        }
+    }
+
+    // Decorations without metrics of their own take the font's
+    fn resolved_decorations(&self, font_data: &[u8]) -> Vec<Decoration> {
+        self.decorations.iter()
+            .map(|decoration| Decoration {
+                metrics: decoration.metrics.or_else(|| font_line_metrics(font_data, decoration.kind)),
+                ..*decoration
+            })
+            .collect()
+    }
+
+    // Draw a laid-out row on a copy of the template, and its back when there is one, and save
+    // them with the preview if asked for, timing each stage; returns what was written
+    #[allow(clippy::too_many_arguments)]
+    fn render_row(
+        &self,
+        template: &RgbaImage,
+        deep: Option<&DeepTemplate>,
+        text: &str,
+        options: &TextOptions,
+        placed: &[PlacedGraphic],
+        graphic_fonts: &GraphicFonts,
+        watermark: Option<&WatermarkMask>,
+        back: Option<(&BackCanvas, &[PlacedGraphic])>,
+        fingerprint: Option<&Fingerprint>,
+        pdf_dpi: f32,
+        output_filename: &Path,
+        timings: &mut StageTimings,
+    ) -> Result<WrittenRow> {
+        let mut img = timings.time(Stage::TemplateClone, || template.clone());
+        timings.time(Stage::Drawing, || {
+            self.draw_row(&mut img, text, options, placed, graphic_fonts, watermark)?;
+            self.draw_fingerprint_label(&mut img, options.font, fingerprint)
+        })?;
+        let back_img = timings.time(Stage::Drawing, || back.map(|(canvas, placed)| self.draw_back(canvas, placed)).transpose())?;
+        let preview_file = preview_path(output_filename);
+        let back_file = self.back_file(output_filename);
+        // The images are dropped once encoded
+        let (encoded, encoded_back, preview) = timings.time(Stage::Encoding, || {
+            // Scaled from the image in memory, before it is dropped
+            let preview = self.preview
+                .map(|max_dimension| encode_preview(&render_preview(&img, max_dimension)))
+                .transpose()
+                .map_err(|source| CertError::ImageEncode { path: preview_file.clone(), source })?;
+            // Drawn on `base`, and saved with its 16 bits when `deep` has them; every PNG, the
+            // back's too, records the fingerprint
+            let encode = |img: &RgbaImage, base: &RgbaImage, deep: Option<&DeepTemplate>, path: &Path| {
+                let png = match deep {
+                    Some(deep) => encode_png16(&deep.restore(base, img), deep.has_alpha(), self.compression),
+                    None => encode_png(img, self.compression),
+                }.map_err(|source| CertError::ImageEncode { path: path.to_path_buf(), source })?;
+                match fingerprint {
+                    Some(fingerprint) => embed_fingerprint(&png, fingerprint),
+                    None => Ok(png),
+                }
+            };
+            let (encoded, encoded_back) = match (self.format, back_img, &back_file, back) {
+                (CertificateFormat::Pdf, back_img, ..) => {
+                    let pages: Vec<RgbaImage> = std::iter::once(img).chain(back_img).collect();
+                    (images_to_pdf(&pages, pdf_dpi)?, None)
+                }
+                (CertificateFormat::Png, Some(back_img), Some(back_file), Some((canvas, _))) => (
+                    encode(&img, template, deep, output_filename)?,
+                    Some(encode(&back_img, &canvas.template, canvas.deep.as_ref(), back_file)?),
+                ),
+                (CertificateFormat::Png, ..) => (encode(&img, template, deep, output_filename)?, None),
+            };
+            Ok::<_, CertError>((encoded, encoded_back, preview))
+        })?;
+        // Hashed from memory rather than read back, so each file is only touched once
+        timings.time(Stage::Writing, || {
+            let write = |path: PathBuf, bytes: &[u8]| {
+                let target = self.staged(&path);
+                std::fs::write(&target, bytes)
+                    .io_context(|| format!("Failed to write {}", target.display()))?;
+                Ok::<_, CertError>(Artifact { path, file_hash: hash_bytes(bytes) })
+            };
+            let file_hash = write(output_filename.to_path_buf(), &encoded)?.file_hash;
+            let back = match (back_file, encoded_back) {
+                (Some(back_file), Some(png)) => Some(write(back_file, &png)?),
+                _ => None,
+            };
+            let preview = preview.map(|jpeg| write(preview_file, &jpeg)).transpose()?;
+            Ok(WrittenRow { file_hash, preview, back })
+        })
+    }
+}
+
+impl CertificateJob {
+    // Where a file of the output directory is first written: under the staging directory
+    // when there is one
+    fn staged(&self, path: &Path) -> PathBuf {
+        match (&self.staging, path.strip_prefix(&self.output_dir)) {
+            (Some(staging), Ok(relative)) => staging.join(relative),
+            _ => path.to_path_buf(),
+        }
+    }
+
+    // Move the staged files of every rendered row into the output directory. A row whose
+    // files can't be moved fails, so the manifest doesn't record it
+    fn move_staged(&self, items: &mut [BatchItem], written: &mut [Option<WrittenRow>]) {
+        for (item, written) in items.iter_mut().zip(written.iter_mut()) {
+            let Some(row) = written.as_ref() else { continue };
+            let files = std::iter::once(item.output_file.clone())
+                .chain(row.preview.iter().chain(&row.back).map(|artifact| artifact.path.clone()));
+            for file in files {
+                let staged = self.staged(&file);
+                if let Err(e) = move_file(&staged, &file) {
+                    item.error = Some(format!("written to {} but not moved to the output directory: {}", staged.display(), e));
+                    *written = None;
+                    break;
+                }
+            }
+        }
+    }
+
+    // Once every chunk is moved: only the directories the batch made are left, and only empty
+    // ones are removed
+    fn remove_staging_dirs(&self) {
+        if let Some(staging) = &self.staging {
+            for entry in WalkDir::new(staging).min_depth(1).contents_first(true).into_iter().flatten() {
+                if entry.file_type().is_dir() {
+                    let _ = std::fs::remove_dir(entry.path());
+                }
+            }
+        }
+    }
+}
+
+// A row's graphic data for its key, along with the content of the images it names
+fn graphics_key(graphics: &[GraphicField], data: &[String]) -> String {
+    let images: Vec<Option<String>> = graphics.iter().zip(data)
+        .filter(|(graphic, _)| matches!(graphic.kind, GraphicKind::Image(_)))
+        .map(|(_, path)| hash_file(path).ok())
+        .collect();
+    format!("{:?}{:?}{:?}", graphics, data, images)
+}
+
+/// Whether the point (`x`, `y`) lies on a template of `width`x`height` pixels, the error
+/// giving the ranges that do.
+pub fn check_position(x: i32, y: i32, (width, height): (u32, u32)) -> Result<()> {
+    if x < 0 || y < 0 || x as i64 >= width as i64 || y as i64 >= height as i64 {
+        return Err(CertError::PositionOffTemplate { x, y, width, height });
+    }
+    Ok(())
+}
+
+// Text running off the template or drawn with the font's placeholder box
+fn text_warnings(font: &rusttype::Font, text: &str, bounds: &TextBounds, (width, height): (u32, u32)) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if !bounds.fits_within(width, height) {
+        warnings.push(format!("the text ({}x{} at {}, {}) runs off the {}x{} template",
+                              bounds.width, bounds.height, bounds.x, bounds.y, width, height));
+    }
+    let missing = missing_glyphs(font, text);
+    if !missing.is_empty() {
+        let missing: String = missing.into_iter().collect();
+        warnings.push(format!("the font has no glyph for '{}'", missing));
+    }
+    warnings
+}
+
+// "#abc" -> "#aabbcc", anything else unchanged
+fn expand_short_hex(color: &str) -> String {
+    let hex = color.trim_start_matches('#');
+    if hex.len() == 3 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        let doubled: String = hex.chars().flat_map(|c| [c, c]).collect();
+        format!("#{}", doubled)
+    } else {
+        color.to_string()
+    }
+}
+
+// Batch time of a deterministic run: SOURCE_DATE_EPOCH when it is set, else the Unix epoch
+fn source_date_epoch() -> u64 {
+    std::env::var("SOURCE_DATE_EPOCH").ok().and_then(|value| value.trim().parse().ok()).unwrap_or(0)
+}
@@ -0,0 +1,1516 @@
+// src/layout.rs
+//! Layout files: a whole certificate described in one TOML or JSON document instead of
+//! answered prompts. A layout names its template and lists its fields: text, image overlays,
+//! barcodes and tables, each with a position and a value filled in from the CSV's columns. A `[back]`
+//! table makes the certificate double-sided, with a template and fields of its own.
+//!
+//! ```toml
+//! version = 1
+//! template = "certificate.png"   # relative to the layout file
+//! compression = "fast"           # optional: fast, balanced (the default) or small
+//! format = "pdf"                 # optional: png (the default) or pdf, a page per side
+//! dpi = 300                      # optional: plain pixel numbers were measured at 300 DPI
+//!
+//! [[fields]]                     # the first text field is the name
+//! type = "text"
+//! value = "{Name}"
+//! x = "50%"                      # pixels, a percentage of the template, or "30mm", "1.5in", "72pt"
+//! y = 400
+//! font = "DejaVu Sans Bold"
+//! size = 60                      # pixels, or "24pt", "8mm"
+//! max_width = 900
+//!
+//! [[fields]]
+//! type = "barcode"
+//! value = "T-{Ticket}"
+//! x = "50%"
+//! y = "88%"
+//!
+//! [[fields]]
+//! type = "table"
+//! x = "50%"
+//! y = "70%"
+//! columns = [                    # width in pixels, a percentage of the template or a length
+//!     { header = "Module", width = "40%", overflow = "ellipsis" },
+//!     { header = "Score", width = 120, align = "right" },
+//! ]
+//! rows = [["{Module1}", "{Score1}"], ["{Module2}", "{Score2}"]]   # or cells = "{Scores}", as JSON
+//! rule = 1                       # between rows, 0 (the default) for none
+//!
+//! [[fields]]                     # painted over the template first, wherever it is listed
+//! type = "patch"
+//! x = 980                        # the top-left corner, like the other fields' positions
+//! y = 610
+//! width = 160                    # pixels, or a length like "20mm"
+//! height = 60
+//! fill = "clone"                 # sample (the default), clone or a color like "#FFFFFF"
+//!
+//! [[fields]]                     # drawn under every other field, wherever it is listed
+//! type = "border"
+//! line = "double"                # solid (the default) or double
+//! thickness = "3mm"              # or [top, right, bottom, left], 0 to leave a side out
+//! inset = "8mm"
+//! radius = "5mm"
+//! color = "#B08D3C"
+//!
+//! [back]                         # optional: the other side, filled in from the same row
+//! template = "back.png"
+//!
+//! [[back.fields]]
+//! type = "barcode"
+//! value = "T-{Ticket}"           # the same code as on the front
+//! x = "50%"
+//! y = "80%"
+//! ```
+//!
+//! ```
+//! use certificate_maker::editpng::save_image;
+//! use certificate_maker::layout::{generate_from_layout, load_layout};
+//! use image::{ImageFormat, Rgba, RgbaImage};
+//!
+//! let dir = std::env::temp_dir().join(format!("certmaker-layout-{}", std::process::id()));
+//! std::fs::create_dir_all(&dir)?;
+//! save_image(&RgbaImage::from_pixel(800, 500, Rgba([250, 245, 230, 255])), dir.join("template.png"), ImageFormat::Png)?;
+//! std::fs::write(dir.join("names.csv"), "Name,Course,Ticket\nAda Lovelace,Analysis,0042\nGrace Hopper,Compilers,1337\n")?;
+//! std::fs::write(dir.join("layout.toml"), r##"
+//!     version = 1
+//!     template = "template.png"
+//!
+//!     [[fields]]
+//!     type = "text"
+//!     value = "{Name}"
+//!     x = "50%"
+//!     y = 200
+//!     size = 48
+//!
+//!     [[fields]]
+//!     type = "text"
+//!     value = "for completing {Course}"
+//!     x = "50%"
+//!     y = 280
+//!     size = 24
+//!     color = "#444444"
+//!
+//!     [[fields]]
+//!     type = "barcode"
+//!     value = "T-{Ticket}"
+//!     x = "50%"
+//!     y = "85%"
+//! "##)?;
+//!
+//! let layout = load_layout(dir.join("layout.toml"))?;
+//! let report = generate_from_layout(&layout, dir.join("names.csv"), dir.join("certificates"))?;
+//! assert_eq!((report.generated, report.failed), (2, 0));
+//! std::fs::remove_dir_all(&dir)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! Physical lengths are converted at the template's DPI, read from the file or set with
+//! `template_dpi`, so the same layout fits a 300 DPI print template and a 96 DPI web one. See
+//! [`TemplateScale`].
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+
+use crate::backside::BackSide;
+use crate::barcode::BarcodeStyle;
+use crate::csvexcelparser::{BatchReport, CsvFormat, MergedCsv, parse_name_list_with};
+use crate::dpi::{ASSUMED_DPI, image_dpi, mm_to_pixels, points_to_pixels};
+use crate::editpng::{Anchor, BorderLine, BorderSides, BorderStyle, PngCompression, TextOverflow, hex_to_rgba};
+use crate::error::{CertError, IoContext, Result};
+use crate::fontnames::edit_distance;
+use crate::orientation::oriented_dimensions;
+use crate::graphics::{GraphicField, GraphicKind, ImageStyle, TextStyle, fill_placeholders, placeholders};
+use crate::job::{CertificateFormat, CertificateJob};
+use crate::mapping::ColumnMapping;
+use crate::effects::{effect_names, find_effect};
+use crate::palette::{check_color, installed as installed_palette};
+use crate::patch::{Patch, PatchFill};
+use crate::source::{NameColumn, Record};
+use crate::table::{CellAlign, TableColumn, TableStyle};
+
+/// Schema version this build reads and writes.
+pub const LAYOUT_VERSION: u32 = 1;
+
+/// Extensions of layout files: TOML, or the same document as JSON.
+pub const LAYOUT_EXTENSIONS: [&str; 2] = ["toml", "json"];
+
+/// A certificate design: the template and every field drawn on it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Layout {
+    /// Always [`LAYOUT_VERSION`] for layouts this build writes.
+    pub version: u32,
+    pub template: PathBuf,
+    /// Header of the column holding the names; the usual aliases when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name_column: Option<String>,
+    /// Columns filling the fields' placeholders in lists whose headers name them otherwise,
+    /// such as `course = "Programme"`; `name` maps the name column.
+    #[serde(default, skip_serializing_if = "ColumnMapping::is_empty")]
+    pub mapping: ColumnMapping,
+    /// PNG compression of the certificates; balanced when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<PngCompression>,
+    /// What the certificates are saved as; PNG when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<CertificateFormat>,
+    /// Resolution the plain pixel numbers were measured at. On a template of another DPI they
+    /// are scaled to match; unset, they are the template's own pixels.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dpi: Option<f32>,
+    /// The template's resolution, instead of what its file records (or [`ASSUMED_DPI`] when
+    /// it records none).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_dpi: Option<f32>,
+    /// Drawn in order, so later fields cover earlier ones, except patches and then borders,
+    /// which are drawn first. The first text field is the name: it is centered and checked like the name of a
+    /// prompted batch.
+    #[serde(default)]
+    pub fields: Vec<LayoutField>,
+    /// The other side of a double-sided certificate; single-sided when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub back: Option<BackLayout>,
+}
+
+/// The back of a double-sided certificate: its own template and fields, placed with the
+/// layout's `dpi` on its own template. There is no name field on the back; every field, text
+/// included, is filled in from the same row as the front.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BackLayout {
+    pub template: PathBuf,
+    /// The back template's resolution, instead of what its file records.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_dpi: Option<f32>,
+    /// Drawn in order, except patches and borders, which are drawn first, as on the front.
+    #[serde(default)]
+    pub fields: Vec<LayoutField>,
+}
+
+/// How a layout's numbers become pixels on one template.
+///
+/// ```
+/// use certificate_maker::layout::{Coordinate, Length, TemplateScale};
+///
+/// // A layout measured at 300 DPI, on a 96 DPI web template
+/// let scale = TemplateScale::new((794, 1123), Some(96.0), Some(300.0));
+/// assert_eq!(Coordinate::Pixels(600).resolve(794, &scale), Ok(192));
+/// assert_eq!(Coordinate::Relative("50%".to_string()).resolve(794, &scale), Ok(397));
+/// assert_eq!(Coordinate::Relative("1in".to_string()).resolve(794, &scale), Ok(96));
+/// assert_eq!(Length::Physical("12pt".to_string()).resolve(&scale), Ok(16.0));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TemplateScale {
+    pub width: u32,
+    pub height: u32,
+    /// Pixels per inch that physical lengths are converted at.
+    pub dpi: f32,
+    /// Whether `dpi` is [`ASSUMED_DPI`] because nothing said otherwise.
+    pub dpi_assumed: bool,
+    /// What plain pixel numbers are multiplied by: the template's DPI over the layout's `dpi`,
+    /// or 1 when the layout doesn't set one.
+    pub pixel_scale: f32,
+}
+
+impl TemplateScale {
+    /// The scale for a template of `dimensions` at `template_dpi` (assumed when `None`), for a
+    /// layout whose pixel numbers were measured at `reference_dpi`.
+    pub fn new(dimensions: (u32, u32), template_dpi: Option<f32>, reference_dpi: Option<f32>) -> Self {
+        let dpi = template_dpi.unwrap_or(ASSUMED_DPI);
+        TemplateScale {
+            width: dimensions.0,
+            height: dimensions.1,
+            dpi,
+            dpi_assumed: template_dpi.is_none(),
+            pixel_scale: reference_dpi.map_or(1.0, |reference| dpi / reference),
+        }
+    }
+
+    /// Plain pixel numbers taken as they are, for a template of `dimensions`.
+    pub fn pixels(dimensions: (u32, u32)) -> Self {
+        TemplateScale::new(dimensions, None, None)
+    }
+
+    // "30mm", "1.5in", "72pt" or "400px" in pixels; None without one of those units
+    fn length(&self, text: &str) -> Option<f64> {
+        let text = text.trim();
+        let (value, unit) = ["mm", "cm", "in", "pt", "px"].into_iter().find_map(|unit| {
+            let value = text.strip_suffix(unit)?.trim().parse::<f64>().ok().filter(|value| value.is_finite())?;
+            Some((value, unit))
+        })?;
+        Some(match unit {
+            "mm" => mm_to_pixels(value, self.dpi),
+            "cm" => mm_to_pixels(value * 10.0, self.dpi),
+            "in" => value * self.dpi as f64,
+            "pt" => points_to_pixels(value, self.dpi),
+            _ => value * self.pixel_scale as f64,
+        })
+    }
+}
+
+/// A position on the template: pixels from the top-left corner, or text with a unit, either
+/// a percentage of the template's width or height such as `"50%"` or a physical length from
+/// the edge such as `"30mm"`, `"1.5in"` or `"72pt"`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Coordinate {
+    Pixels(i32),
+    Relative(String),
+}
+
+impl Coordinate {
+    /// Pixels along a template side `extent` pixels long.
+    pub fn resolve(&self, extent: u32, scale: &TemplateScale) -> std::result::Result<i32, String> {
+        match self {
+            Coordinate::Pixels(pixels) => Ok((*pixels as f64 * scale.pixel_scale as f64).round() as i32),
+            Coordinate::Relative(text) => text.trim().strip_suffix('%')
+                .and_then(|percent| percent.trim().parse::<f64>().ok())
+                .filter(|percent| percent.is_finite())
+                .map(|percent| extent as f64 * percent / 100.0)
+                .or_else(|| scale.length(text))
+                .map(|pixels| pixels.round() as i32)
+                .ok_or_else(|| format!("'{}' is not a position; use pixels like 400, a percentage like \"50%\" or a length like \"30mm\", \"1.5in\" or \"72pt\"", text)),
+        }
+    }
+
+    /// Whether the position depends on the template's DPI.
+    pub fn is_physical(&self) -> bool {
+        matches!(self, Coordinate::Relative(text) if !text.trim().ends_with(['%', 'x']))
+    }
+}
+
+/// A position typed on the command line: `"400"` is pixels, anything else such as `"50%"` or
+/// `"30mm"` is resolved against the template.
+impl From<&str> for Coordinate {
+    fn from(text: &str) -> Self {
+        match text.trim().parse() {
+            Ok(pixels) => Coordinate::Pixels(pixels),
+            Err(_) => Coordinate::Relative(text.trim().to_string()),
+        }
+    }
+}
+
+/// A size such as a font size or a maximum width: pixels, or a physical length such as
+/// `"24pt"` or `"8mm"`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Length {
+    Pixels(f32),
+    Physical(String),
+}
+
+impl Length {
+    /// The size in pixels on the template; an error for text without a unit or for a size that
+    /// isn't positive.
+    pub fn resolve(&self, scale: &TemplateScale) -> std::result::Result<f32, String> {
+        self.pixels(scale).filter(|pixels| *pixels > 0.0).ok_or_else(|| self.problem("positive"))
+    }
+
+    /// Like [`resolve`](Self::resolve), but 0 is allowed, for gaps and thicknesses that can be left out.
+    pub fn resolve_or_zero(&self, scale: &TemplateScale) -> std::result::Result<f32, String> {
+        self.pixels(scale).filter(|pixels| *pixels >= 0.0).ok_or_else(|| self.problem("0 or a larger"))
+    }
+
+    fn pixels(&self, scale: &TemplateScale) -> Option<f32> {
+        match self {
+            Length::Pixels(pixels) => Some(*pixels as f64 * scale.pixel_scale as f64),
+            Length::Physical(text) => scale.length(text),
+        }
+        .map(|pixels| pixels as f32)
+    }
+
+    fn problem(&self, wanted: &str) -> String {
+        match self {
+            Length::Pixels(pixels) => format!("{} is not a {} size", pixels, wanted),
+            Length::Physical(text) => format!("'{}' is not a size; use pixels like 40 or a length like \"24pt\" or \"8mm\"", text),
+        }
+    }
+
+    /// Whether the size depends on the template's DPI.
+    pub fn is_physical(&self) -> bool {
+        matches!(self, Length::Physical(text) if !text.trim().ends_with("px"))
+    }
+}
+
+impl From<f32> for Length {
+    fn from(pixels: f32) -> Self {
+        Length::Pixels(pixels)
+    }
+}
+
+/// The thickness of a border: one length for every side, or four for the top, right, bottom
+/// and left, as in CSS. A side of 0 is left out.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum BorderThickness {
+    All(Length),
+    Sides([Length; 4]),
+}
+
+impl BorderThickness {
+    /// Each side in pixels on the template.
+    pub fn resolve(&self, scale: &TemplateScale) -> std::result::Result<BorderSides, String> {
+        match self {
+            BorderThickness::All(length) => length.resolve_or_zero(scale).map(BorderSides::all),
+            BorderThickness::Sides([top, right, bottom, left]) => Ok(BorderSides {
+                top: top.resolve_or_zero(scale)?,
+                right: right.resolve_or_zero(scale)?,
+                bottom: bottom.resolve_or_zero(scale)?,
+                left: left.resolve_or_zero(scale)?,
+            }),
+        }
+    }
+
+    fn lengths(&self) -> &[Length] {
+        match self {
+            BorderThickness::All(length) => std::slice::from_ref(length),
+            BorderThickness::Sides(sides) => sides,
+        }
+    }
+}
+
+/// One field of a layout.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum LayoutField {
+    /// A line of text, set smaller or cut short when it is wider than `max_width`.
+    Text {
+        value: String,
+        x: Coordinate,
+        y: Coordinate,
+        #[serde(default)]
+        anchor: Anchor,
+        /// A font file (relative to the layout, or in the fonts directory) or a family and style.
+        #[serde(default = "default_font")]
+        font: PathBuf,
+        #[serde(default = "default_size")]
+        size: Length,
+        #[serde(default = "default_color")]
+        color: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_width: Option<Length>,
+        /// Whether text wider than `max_width` is set smaller or cut short.
+        #[serde(default, skip_serializing_if = "TextOverflow::is_shrink")]
+        overflow: TextOverflow,
+        /// What cut-short text ends with, "…" by default.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        ellipsis: Option<String>,
+        /// A text effect such as "gold-foil"; see [`find_effect`].
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        effect: Option<String>,
+    },
+    /// An image file, such as a logo or `photos/{Id}.png`, drawn at its own size unless
+    /// `width` or `height` is given.
+    Image {
+        path: String,
+        x: Coordinate,
+        y: Coordinate,
+        #[serde(default)]
+        anchor: Anchor,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        width: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        height: Option<u32>,
+    },
+    /// A Code128 barcode; see [`BarcodeStyle`].
+    Barcode {
+        value: String,
+        x: Coordinate,
+        y: Coordinate,
+        #[serde(default)]
+        anchor: Anchor,
+        #[serde(default = "default_module_width")]
+        module_width: u32,
+        #[serde(default = "default_bar_height")]
+        height: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        text_size: Option<Length>,
+    },
+    /// A frame around the template, `inset` in from its edges; see [`BorderStyle`]. Borders
+    /// are drawn on the template before every other field, wherever they are listed.
+    Border {
+        #[serde(default)]
+        line: BorderLine,
+        thickness: BorderThickness,
+        #[serde(default = "default_color")]
+        color: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        inset: Option<Length>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        radius: Option<Length>,
+    },
+    /// Rows of cells in columns, such as modules and their scores; see [`TableStyle`]. The
+    /// cells are `rows` of templates, or `cells`, a `{Column}` holding them as JSON.
+    Table {
+        x: Coordinate,
+        y: Coordinate,
+        #[serde(default)]
+        anchor: Anchor,
+        #[serde(default = "default_font")]
+        font: PathBuf,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        header_font: Option<PathBuf>,
+        #[serde(default = "default_size")]
+        size: Length,
+        #[serde(default = "default_color")]
+        color: String,
+        columns: Vec<LayoutColumn>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        rows: Vec<Vec<String>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cells: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        row_height: Option<Length>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        rule: Option<Length>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        rule_color: Option<String>,
+    },
+    /// A rectangle of the template painted over before anything is drawn, such as last year's
+    /// date; see [`Patch`]. `x` and `y` are its top-left corner.
+    Patch {
+        x: Coordinate,
+        y: Coordinate,
+        width: Length,
+        height: Length,
+        #[serde(default)]
+        fill: PatchFill,
+    },
+}
+
+/// One column of a table field: its width in pixels, a percentage of the template's width or
+/// a length, and how its cells are set; see [`TableColumn`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LayoutColumn {
+    pub width: Coordinate,
+    #[serde(default)]
+    pub align: CellAlign,
+    #[serde(default, skip_serializing_if = "TextOverflow::is_shrink")]
+    pub overflow: TextOverflow,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ellipsis: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header: Option<String>,
+}
+
+fn default_font() -> PathBuf {
+    PathBuf::from("DejaVuSans.ttf")
+}
+
+fn default_size() -> Length {
+    Length::Pixels(40.0)
+}
+
+fn default_color() -> String {
+    "#000000".to_string()
+}
+
+fn default_module_width() -> u32 {
+    BarcodeStyle::default().module_width
+}
+
+fn default_bar_height() -> u32 {
+    BarcodeStyle::default().height
+}
+
+impl LayoutField {
+    /// The template its value is filled in from: the text, the image path, the barcode data or
+    /// the column of a table's JSON cells; empty for a border, a patch or a table with `rows`.
+    pub fn value(&self) -> &str {
+        match self {
+            LayoutField::Text { value, .. } | LayoutField::Barcode { value, .. } => value,
+            LayoutField::Image { path, .. } => path,
+            LayoutField::Table { cells, .. } => cells.as_deref().unwrap_or_default(),
+            LayoutField::Border { .. } | LayoutField::Patch { .. } => "",
+        }
+    }
+
+    // Every template filled in per row, with its key: the value, and a table's cell templates
+    fn templates(&self) -> Vec<(String, &str)> {
+        let mut templates = vec![(self.value_key().to_string(), self.value())];
+        if let LayoutField::Table { rows, .. } = self {
+            for (row, cells) in rows.iter().enumerate() {
+                templates.extend(cells.iter().enumerate().map(|(column, cell)| (format!("rows[{}][{}]", row, column), cell.as_str())));
+            }
+        }
+        templates
+    }
+
+    /// Lowercase description for messages.
+    pub fn label(&self) -> &'static str {
+        match self {
+            LayoutField::Text { .. } => "text",
+            LayoutField::Image { .. } => "image",
+            LayoutField::Barcode { .. } => "barcode",
+            LayoutField::Border { .. } => "border",
+            LayoutField::Patch { .. } => "patch",
+            LayoutField::Table { .. } => "table",
+        }
+    }
+
+    /// The key holding [`value`](Self::value).
+    pub fn value_key(&self) -> &'static str {
+        match self {
+            LayoutField::Image { .. } => "path",
+            LayoutField::Table { .. } => "cells",
+            _ => "value",
+        }
+    }
+
+    /// The field as drawn on a template, its numbers converted to pixels with `scale`; `None`
+    /// for a border or a patch, which are a [`border`](Self::border) and a
+    /// [`patch`](Self::patch). An error names the key at fault.
+    pub fn graphic(&self, scale: &TemplateScale) -> std::result::Result<Option<GraphicField>, String> {
+        let (x, y, anchor) = match self {
+            LayoutField::Text { x, y, anchor, .. } | LayoutField::Image { x, y, anchor, .. } | LayoutField::Barcode { x, y, anchor, .. }
+            | LayoutField::Table { x, y, anchor, .. } => (x, y, anchor),
+            LayoutField::Border { .. } | LayoutField::Patch { .. } => return Ok(None),
+        };
+        let length = |key: &str, length: &Length| length.resolve(scale).map_err(|e| format!("{}: {}", key, e));
+        // Whole pixels, at least one, however far they are scaled down
+        let pixels = |pixels: u32| ((pixels as f64 * scale.pixel_scale as f64).round() as u32).max(1);
+        let kind = match self {
+            LayoutField::Text { font, size, color, max_width, overflow, ellipsis, effect, .. } => GraphicKind::Text(TextStyle {
+                font: font.clone(),
+                size: length("size", size)?,
+                color: color.clone(),
+                max_width: max_width.as_ref().map(|max_width| length("max_width", max_width)).transpose()?,
+                overflow: *overflow,
+                ellipsis: ellipsis.clone(),
+                effect: effect.as_deref().map(find_effect).transpose().map_err(|e| format!("effect: {}", e))?,
+            }),
+            LayoutField::Image { width, height, .. } => GraphicKind::Image(ImageStyle { width: width.map(pixels), height: height.map(pixels) }),
+            LayoutField::Barcode { module_width, height, text_size, .. } => GraphicKind::Code128(BarcodeStyle {
+                module_width: pixels(*module_width),
+                height: pixels(*height),
+                text_size: text_size.as_ref().map(|text_size| length("text_size", text_size)).transpose()?,
+            }),
+            LayoutField::Table { font, header_font, size, color, columns, rows, cells, row_height, rule, rule_color, .. } => {
+                match (rows.is_empty(), cells) {
+                    (false, Some(_)) => return Err("cells: a table takes its cells from rows or from cells, not both".to_string()),
+                    (true, None) => return Err("rows: missing; a table needs rows of cell templates, or cells naming a column that holds them as JSON".to_string()),
+                    _ => {}
+                }
+                if let Some(index) = rows.iter().position(|row| row.len() > columns.len()) {
+                    return Err(format!("rows[{}]: has {} cells, but the table has {} columns", index, rows[index].len(), columns.len()));
+                }
+                let columns = columns.iter().enumerate()
+                    .map(|(index, column)| {
+                        let width = column.width.resolve(scale.width, scale).map_err(|e| format!("columns[{}].width: {}", index, e))?;
+                        // A percentage of an unreadable template is 0, which validation already reports
+                        if width <= 0 && scale.width > 0 {
+                            return Err(format!("columns[{}].width: must be at least 1 pixel, got {}", index, width));
+                        }
+                        Ok(TableColumn {
+                            width: width.max(1) as u32,
+                            align: column.align,
+                            overflow: column.overflow,
+                            ellipsis: column.ellipsis.clone(),
+                            header: column.header.clone(),
+                        })
+                    })
+                    .collect::<std::result::Result<Vec<_>, String>>()?;
+                GraphicKind::Table(TableStyle {
+                    font: font.clone(),
+                    header_font: header_font.clone(),
+                    size: length("size", size)?,
+                    color: color.clone(),
+                    columns,
+                    rows: rows.clone(),
+                    row_height: row_height.as_ref().map(|row_height| length("row_height", row_height)).transpose()?,
+                    rule: rule.as_ref().map_or(Ok(0.0), |rule| rule.resolve_or_zero(scale)).map_err(|e| format!("rule: {}", e))?,
+                    rule_color: rule_color.clone(),
+                })
+            }
+            LayoutField::Border { .. } | LayoutField::Patch { .. } => unreachable!("returned above"),
+        };
+        let x = x.resolve(scale.width, scale).map_err(|e| format!("x: {}", e))?;
+        let y = y.resolve(scale.height, scale).map_err(|e| format!("y: {}", e))?;
+        Ok(Some(GraphicField { kind, data: self.value().to_string(), x, y, anchor: *anchor }))
+    }
+
+    /// A border field's style, its lengths converted to pixels with `scale`; `None` for other
+    /// fields. An error names the key at fault.
+    pub fn border(&self, scale: &TemplateScale) -> std::result::Result<Option<BorderStyle>, String> {
+        let LayoutField::Border { line, thickness, color, inset, radius } = self else { return Ok(None) };
+        let gap = |key: &str, length: &Option<Length>| {
+            length.as_ref().map_or(Ok(0.0), |length| length.resolve_or_zero(scale)).map_err(|e| format!("{}: {}", key, e))
+        };
+        Ok(Some(BorderStyle {
+            line: *line,
+            color: hex_to_rgba(color).map_err(|e| format!("color: {}", e))?,
+            thickness: thickness.resolve(scale).map_err(|e| format!("thickness: {}", e))?,
+            inset: gap("inset", inset)?,
+            radius: gap("radius", radius)?,
+        }))
+    }
+
+    /// A patch field's rectangle in whole pixels, converted with `scale`; `None` for other
+    /// fields. An error names the key at fault.
+    pub fn patch(&self, scale: &TemplateScale) -> std::result::Result<Option<Patch>, String> {
+        let LayoutField::Patch { x, y, width, height, fill } = self else { return Ok(None) };
+        let length = |key: &str, length: &Length| length.resolve(scale).map(|pixels| pixels.round().max(1.0) as u32).map_err(|e| format!("{}: {}", key, e));
+        Ok(Some(Patch {
+            x: x.resolve(scale.width, scale).map_err(|e| format!("x: {}", e))?,
+            y: y.resolve(scale.height, scale).map_err(|e| format!("y: {}", e))?,
+            width: length("width", width)?,
+            height: length("height", height)?,
+            fill: fill.clone(),
+        }))
+    }
+
+    /// Whether any of its positions or sizes is a physical length, which depends on the
+    /// template's DPI.
+    pub fn is_physical(&self) -> bool {
+        match self {
+            LayoutField::Text { x, y, size, max_width, .. } => {
+                x.is_physical() || y.is_physical() || size.is_physical() || max_width.as_ref().is_some_and(Length::is_physical)
+            }
+            LayoutField::Image { x, y, .. } => x.is_physical() || y.is_physical(),
+            LayoutField::Barcode { x, y, text_size, .. } => x.is_physical() || y.is_physical() || text_size.as_ref().is_some_and(Length::is_physical),
+            LayoutField::Border { thickness, inset, radius, .. } => {
+                thickness.lengths().iter().any(Length::is_physical) || inset.iter().chain(radius).any(Length::is_physical)
+            }
+            LayoutField::Patch { x, y, width, height, .. } => x.is_physical() || y.is_physical() || width.is_physical() || height.is_physical(),
+            LayoutField::Table { x, y, size, columns, row_height, rule, .. } => {
+                x.is_physical() || y.is_physical() || size.is_physical() || row_height.iter().chain(rule).any(Length::is_physical)
+                    || columns.iter().any(|column| column.width.is_physical())
+            }
+        }
+    }
+}
+
+impl BackLayout {
+    /// How the back's numbers become pixels on its template, for a layout whose pixel numbers
+    /// were measured at `reference_dpi`; see [`Layout::scale`].
+    pub fn scale(&self, reference_dpi: Option<f32>) -> Result<TemplateScale> {
+        let dimensions = oriented_dimensions(&self.template)
+            .map_err(|source| CertError::ImageDecode { path: self.template.clone(), source })?;
+        Ok(self.scale_for(dimensions, reference_dpi))
+    }
+
+    fn scale_for(&self, dimensions: (u32, u32), reference_dpi: Option<f32>) -> TemplateScale {
+        let template_dpi = self.template_dpi.or_else(|| image_dpi(&self.template).ok().flatten());
+        TemplateScale::new(dimensions, template_dpi, reference_dpi)
+    }
+
+    /// The back as the job draws it: its fields as graphics, patches and borders, in pixels.
+    pub fn side(&self, reference_dpi: Option<f32>) -> Result<BackSide> {
+        let scale = self.scale(reference_dpi)?;
+        let mut back = BackSide::new(&self.template);
+        for field in &self.fields {
+            back.graphics.extend(field.graphic(&scale).map_err(|e| CertError::InvalidLayout(vec![format!("back.{}", e)]))?);
+            back.patches.extend(field.patch(&scale).map_err(|e| CertError::InvalidLayout(vec![format!("back.{}", e)]))?);
+            back.borders.extend(field.border(&scale).map_err(|e| CertError::InvalidLayout(vec![format!("back.{}", e)]))?);
+        }
+        Ok(back)
+    }
+}
+
+/// Read a layout from a `.toml` or `.json` file. Relative paths in it (the template, image
+/// fields and font files next to it) are taken relative to the layout's directory.
+///
+/// Every mistake in the document's shape is reported at once, each with the path of its key
+/// such as `fields[2].anchor`, the value found and what would be valid. Whether the files it
+/// names exist is checked by [`Layout::validate`], or by [`check_layout_file`] for both.
+///
+/// ```
+/// use certificate_maker::error::CertError;
+/// use certificate_maker::layout::load_layout;
+///
+/// let path = std::env::temp_dir().join(format!("certmaker-typos-{}.toml", std::process::id()));
+/// std::fs::write(&path, r##"
+///     version = 1
+///     template = "template.png"
+///
+///     [[fields]]
+///     type = "text"
+///     value = "{Name}"
+///     x = "50%"
+///     colour = "#1A1A40"
+///     anchor = "middle"
+/// "##)?;
+/// let Err(CertError::InvalidLayoutFile { problems, .. }) = load_layout(&path) else { panic!("the typos were accepted") };
+/// assert_eq!(problems.len(), 3);
+/// assert!(problems[0].starts_with("fields[0].colour: unknown key (did you mean 'color'?)"));
+/// assert!(problems[1].starts_with("fields[0].anchor: 'middle' isn't an anchor"));
+/// assert!(problems[2].starts_with("fields[0].y: missing"));
+/// std::fs::remove_file(&path)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn load_layout(path: impl AsRef<Path>) -> Result<Layout> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path)
+        .io_context(|| format!("Failed to read layout {}", path.display()))?;
+    let parse_error = |reason: String| CertError::LayoutParse { path: path.to_path_buf(), reason };
+    let document: Value = match layout_extension(path).as_str() {
+        "toml" => toml::from_str(&content).map_err(|e| parse_error(e.to_string().trim_end().to_string()))?,
+        "json" => serde_json::from_str(&content).map_err(|e| parse_error(e.to_string()))?,
+        _ => return Err(parse_error("layouts are .toml or .json files".to_string())),
+    };
+    // A newer layout's other problems would only be noise
+    if let Some(version) = document.get("version").and_then(Value::as_u64)
+        && version != LAYOUT_VERSION as u64 {
+        return Err(CertError::UnsupportedLayoutVersion { path: path.to_path_buf(), version: version.min(u32::MAX as u64) as u32 });
+    }
+    let problems = check_schema(&document);
+    if !problems.is_empty() {
+        return Err(CertError::InvalidLayoutFile { path: path.to_path_buf(), problems });
+    }
+    let mut layout: Layout = serde_json::from_value(document).map_err(|e| parse_error(e.to_string()))?;
+
+    let dir = path.parent().unwrap_or(Path::new(""));
+    layout.template = dir.join(&layout.template);
+    resolve_field_paths(dir, &mut layout.fields);
+    if let Some(back) = &mut layout.back {
+        back.template = dir.join(&back.template);
+        resolve_field_paths(dir, &mut back.fields);
+    }
+    Ok(layout)
+}
+
+// Image paths, and font files next to the layout, taken relative to its directory
+fn resolve_field_paths(dir: &Path, fields: &mut [LayoutField]) {
+    for field in fields {
+        match field {
+            LayoutField::Image { path, .. } => *path = dir.join(&*path).to_string_lossy().into_owned(),
+            LayoutField::Text { font, .. } if dir.join(&*font).is_file() => *font = dir.join(&*font),
+            LayoutField::Table { font, header_font, .. } => {
+                for font in std::iter::once(font).chain(header_font) {
+                    if dir.join(&*font).is_file() {
+                        *font = dir.join(&*font);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Load a layout and check everything it names, as a batch does before drawing; with `list`,
+/// also check the columns its fields use are in that CSV, JSON or text list. All problems
+/// come back in one [`CertError::InvalidLayoutFile`].
+pub fn check_layout_file(path: impl AsRef<Path>, list: Option<&Path>, format: &CsvFormat) -> Result<Layout> {
+    let path = path.as_ref();
+    let layout = load_layout(path)?;
+    let mut problems = layout.validate().err().unwrap_or_default();
+    if let Some(list) = list {
+        let parsed = parse_name_list_with(list, &layout.name_column(), format)?;
+        problems.extend(layout.check_columns(&parsed.headers).err().unwrap_or_default());
+    }
+    if problems.is_empty() {
+        Ok(layout)
+    } else {
+        Err(CertError::InvalidLayoutFile { path: path.to_path_buf(), problems })
+    }
+}
+
+/// Write `mapping` into the `[mapping]` table of the layout file at `path`, replacing the
+/// columns of placeholders it already maps. The rest of the file, comments included, is left as
+/// it is, so the layout then runs with these columns without asking.
+///
+/// ```
+/// use certificate_maker::layout::{load_layout, save_mapping};
+/// use certificate_maker::mapping::ColumnMapping;
+///
+/// let path = std::env::temp_dir().join(format!("certmaker-mapping-{}.toml", std::process::id()));
+/// std::fs::write(&path, "version = 1\ntemplate = \"certificate.png\"  # the museum's\n")?;
+/// let mut mapping = ColumnMapping::default();
+/// mapping.insert("course", "Programme");
+/// save_mapping(&path, &mapping)?;
+///
+/// assert!(std::fs::read_to_string(&path)?.contains("# the museum's"));
+/// assert_eq!(load_layout(&path)?.mapping.get("course"), Some("Programme"));
+/// std::fs::remove_file(&path)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn save_mapping(path: impl AsRef<Path>, mapping: &ColumnMapping) -> Result<()> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path)
+        .io_context(|| format!("Failed to read layout {}", path.display()))?;
+    let parse_error = |reason: String| CertError::LayoutParse { path: path.to_path_buf(), reason };
+    let not_a_table = || parse_error("mapping: expected a table of placeholders and columns".to_string());
+    let content = match layout_extension(path).as_str() {
+        "toml" => {
+            let mut document: toml_edit::DocumentMut = content.parse()
+                .map_err(|e: toml_edit::TomlError| parse_error(e.to_string().trim_end().to_string()))?;
+            let table = document.entry("mapping").or_insert(toml_edit::table()).as_table_like_mut().ok_or_else(not_a_table)?;
+            for (placeholder, column) in mapping.iter() {
+                let replaced: Vec<String> = table.iter().map(|(key, _)| key.to_string()).filter(|key| key.eq_ignore_ascii_case(placeholder)).collect();
+                for key in replaced {
+                    table.remove(&key);
+                }
+                table.insert(placeholder, toml_edit::value(column));
+            }
+            document.to_string()
+        }
+        "json" => {
+            let mut document: Value = serde_json::from_str(&content).map_err(|e| parse_error(e.to_string()))?;
+            let table = document.as_object_mut().ok_or_else(not_a_table)?
+                .entry("mapping").or_insert_with(|| Value::Object(Map::new()))
+                .as_object_mut().ok_or_else(not_a_table)?;
+            for (placeholder, column) in mapping.iter() {
+                table.retain(|key, _| !key.eq_ignore_ascii_case(placeholder));
+                table.insert(placeholder.to_string(), Value::String(column.to_string()));
+            }
+            serde_json::to_string_pretty(&document).map_err(|e| parse_error(e.to_string()))?
+        }
+        _ => return Err(parse_error("layouts are .toml or .json files".to_string())),
+    };
+    std::fs::write(path, content).io_context(|| format!("Failed to write layout {}", path.display()))
+}
+
+// What a key of a layout document holds
+#[derive(Clone, Copy)]
+enum KeyKind {
+    Version,
+    Text,
+    Pixels,
+    Number,
+    Length,
+    Gap,
+    Thickness,
+    Coordinate,
+    Anchor,
+    Color,
+    Compression,
+    Format,
+    BorderLine,
+    PatchFill,
+    Effect,
+    Align,
+    Overflow,
+    Columns,
+    Rows,
+    Mapping,
+    Fields,
+    Back,
+}
+
+const FIELD_TYPES: [&str; 6] = ["text", "image", "barcode", "table", "border", "patch"];
+
+// Keys of the top level and of each field type: name, what it holds, whether it is required
+const LAYOUT_KEYS: &[(&str, KeyKind, bool)] = &[
+    ("version", KeyKind::Version, true),
+    ("template", KeyKind::Text, true),
+    ("name_column", KeyKind::Text, false),
+    ("mapping", KeyKind::Mapping, false),
+    ("compression", KeyKind::Compression, false),
+    ("format", KeyKind::Format, false),
+    ("dpi", KeyKind::Number, false),
+    ("template_dpi", KeyKind::Number, false),
+    ("fields", KeyKind::Fields, false),
+    ("back", KeyKind::Back, false),
+];
+const BACK_KEYS: &[(&str, KeyKind, bool)] = &[
+    ("template", KeyKind::Text, true),
+    ("template_dpi", KeyKind::Number, false),
+    ("fields", KeyKind::Fields, false),
+];
+const TEXT_KEYS: &[(&str, KeyKind, bool)] = &[
+    ("type", KeyKind::Text, true),
+    ("value", KeyKind::Text, true),
+    ("x", KeyKind::Coordinate, true),
+    ("y", KeyKind::Coordinate, true),
+    ("anchor", KeyKind::Anchor, false),
+    ("font", KeyKind::Text, false),
+    ("size", KeyKind::Length, false),
+    ("color", KeyKind::Color, false),
+    ("max_width", KeyKind::Length, false),
+    ("overflow", KeyKind::Overflow, false),
+    ("ellipsis", KeyKind::Text, false),
+    ("effect", KeyKind::Effect, false),
+];
+const IMAGE_KEYS: &[(&str, KeyKind, bool)] = &[
+    ("type", KeyKind::Text, true),
+    ("path", KeyKind::Text, true),
+    ("x", KeyKind::Coordinate, true),
+    ("y", KeyKind::Coordinate, true),
+    ("anchor", KeyKind::Anchor, false),
+    ("width", KeyKind::Pixels, false),
+    ("height", KeyKind::Pixels, false),
+];
+const BARCODE_KEYS: &[(&str, KeyKind, bool)] = &[
+    ("type", KeyKind::Text, true),
+    ("value", KeyKind::Text, true),
+    ("x", KeyKind::Coordinate, true),
+    ("y", KeyKind::Coordinate, true),
+    ("anchor", KeyKind::Anchor, false),
+    ("module_width", KeyKind::Pixels, false),
+    ("height", KeyKind::Pixels, false),
+    ("text_size", KeyKind::Length, false),
+];
+const TABLE_KEYS: &[(&str, KeyKind, bool)] = &[
+    ("type", KeyKind::Text, true),
+    ("x", KeyKind::Coordinate, true),
+    ("y", KeyKind::Coordinate, true),
+    ("anchor", KeyKind::Anchor, false),
+    ("font", KeyKind::Text, false),
+    ("header_font", KeyKind::Text, false),
+    ("size", KeyKind::Length, false),
+    ("color", KeyKind::Color, false),
+    ("columns", KeyKind::Columns, true),
+    ("rows", KeyKind::Rows, false),
+    ("cells", KeyKind::Text, false),
+    ("row_height", KeyKind::Length, false),
+    ("rule", KeyKind::Gap, false),
+    ("rule_color", KeyKind::Color, false),
+];
+const COLUMN_KEYS: &[(&str, KeyKind, bool)] = &[
+    ("width", KeyKind::Coordinate, true),
+    ("align", KeyKind::Align, false),
+    ("overflow", KeyKind::Overflow, false),
+    ("ellipsis", KeyKind::Text, false),
+    ("header", KeyKind::Text, false),
+];
+const BORDER_KEYS: &[(&str, KeyKind, bool)] = &[
+    ("type", KeyKind::Text, true),
+    ("line", KeyKind::BorderLine, false),
+    ("thickness", KeyKind::Thickness, true),
+    ("color", KeyKind::Color, false),
+    ("inset", KeyKind::Gap, false),
+    ("radius", KeyKind::Gap, false),
+];
+const PATCH_KEYS: &[(&str, KeyKind, bool)] = &[
+    ("type", KeyKind::Text, true),
+    ("x", KeyKind::Coordinate, true),
+    ("y", KeyKind::Coordinate, true),
+    ("width", KeyKind::Length, true),
+    ("height", KeyKind::Length, true),
+    ("fill", KeyKind::PatchFill, false),
+];
+
+impl KeyKind {
+    fn check(self, value: &Value) -> std::result::Result<(), String> {
+        let ok = match self {
+            KeyKind::Version => value.as_u64().is_some_and(|version| version <= u32::MAX as u64),
+            KeyKind::Text => value.is_string(),
+            KeyKind::Pixels => value.as_u64().is_some_and(|pixels| (1..=u32::MAX as u64).contains(&pixels)),
+            KeyKind::Number => value.as_f64().is_some_and(|number| number.is_finite() && number > 0.0),
+            KeyKind::Length => match value {
+                Value::String(text) => return Length::Physical(text.clone()).resolve(&TemplateScale::pixels((100, 100))).map(|_| ()),
+                _ => value.as_f64().is_some_and(|number| number.is_finite() && number > 0.0),
+            },
+            KeyKind::Gap => match value {
+                Value::String(text) => return Length::Physical(text.clone()).resolve_or_zero(&TemplateScale::pixels((100, 100))).map(|_| ()),
+                _ => value.as_f64().is_some_and(|number| number.is_finite() && number >= 0.0),
+            },
+            KeyKind::Thickness => match value {
+                Value::Array(sides) if sides.len() == 4 => {
+                    for (side, value) in ["top", "right", "bottom", "left"].into_iter().zip(sides) {
+                        KeyKind::Gap.check(value).map_err(|e| format!("{}: {}", side, e))?;
+                    }
+                    return Ok(());
+                }
+                Value::Array(_) => false,
+                _ => return KeyKind::Gap.check(value),
+            },
+            KeyKind::Coordinate => match value {
+                Value::String(text) => return Coordinate::Relative(text.clone()).resolve(100, &TemplateScale::pixels((100, 100))).map(|_| ()),
+                _ => value.as_i64().is_some_and(|pixels| i32::try_from(pixels).is_ok()),
+            },
+            KeyKind::Anchor => {
+                let names = Anchor::ALL.map(Anchor::name);
+                return match value.as_str() {
+                    Some(name) if names.contains(&name) => Ok(()),
+                    Some(name) => Err(format!("'{}' isn't an anchor{}; use one of {}", name, did_you_mean(name, &names), names.join(", "))),
+                    None => Err(format!("expected an anchor name in quotes, one of {}; found {}", names.join(", "), describe(value))),
+                };
+            }
+            KeyKind::Compression => {
+                let names = PngCompression::ALL.map(PngCompression::name);
+                return match value.as_str() {
+                    Some(name) if names.contains(&name) => Ok(()),
+                    Some(name) => Err(format!("'{}' isn't a compression level{}; use one of {}", name, did_you_mean(name, &names), names.join(", "))),
+                    None => Err(format!("expected a compression level in quotes, one of {}; found {}", names.join(", "), describe(value))),
+                };
+            }
+            KeyKind::Format => {
+                let names = CertificateFormat::ALL.map(CertificateFormat::name);
+                return match value.as_str() {
+                    Some(name) if names.contains(&name) => Ok(()),
+                    Some(name) => Err(format!("'{}' isn't an output format{}; use one of {}", name, did_you_mean(name, &names), names.join(", "))),
+                    None => Err(format!("expected an output format in quotes, one of {}; found {}", names.join(", "), describe(value))),
+                };
+            }
+            KeyKind::BorderLine => {
+                let names = BorderLine::ALL.map(BorderLine::name);
+                return match value.as_str() {
+                    Some(name) if names.contains(&name) => Ok(()),
+                    Some(name) => Err(format!("'{}' isn't a border line{}; use one of {}", name, did_you_mean(name, &names), names.join(", "))),
+                    None => Err(format!("expected a border line in quotes, one of {}; found {}", names.join(", "), describe(value))),
+                };
+            }
+            KeyKind::PatchFill => {
+                return match value.as_str() {
+                    Some(fill) => fill.parse::<PatchFill>().map(|_| ())
+                        .map_err(|_| format!("'{}' isn't a fill{}; use sample, clone or a color like \"#FFFFFF\"", fill, did_you_mean(fill, &["sample", "clone"]))),
+                    None => Err(format!("expected sample, clone or a color in quotes, found {}", describe(value))),
+                };
+            }
+            KeyKind::Color => {
+                return match value.as_str() {
+                    Some(color) => match check_color(color) {
+                        Ok(_) => Ok(()),
+                        Err(e @ (CertError::OffPalette { .. } | CertError::UnknownPaletteColor { .. })) => Err(e.to_string()),
+                        Err(_) => Err(format!("'{}' isn't a color; use \"#RRGGBB\" or \"#RRGGBBAA\" such as \"#1A1A40\"{}", color,
+                                              if installed_palette().is_some() { ", or \"palette:<name>\"" } else { "" })),
+                    },
+                    None => Err(format!("expected a color in quotes such as \"#1A1A40\", found {}", describe(value))),
+                };
+            }
+            KeyKind::Effect => {
+                let names = effect_names();
+                return match value.as_str() {
+                    Some(name) if names.iter().any(|known| known.eq_ignore_ascii_case(name.trim())) => Ok(()),
+                    Some(name) => {
+                        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+                        Err(format!("'{}' isn't a text effect{}; use one of {}", name, did_you_mean(name, &names), names.join(", ")))
+                    }
+                    None => Err(format!("expected an effect name in quotes, one of {}; found {}", names.join(", "), describe(value))),
+                };
+            }
+            KeyKind::Align => {
+                let names = CellAlign::ALL.map(CellAlign::name);
+                return match value.as_str() {
+                    Some(name) if names.contains(&name) => Ok(()),
+                    Some(name) => Err(format!("'{}' isn't an alignment{}; use one of {}", name, did_you_mean(name, &names), names.join(", "))),
+                    None => Err(format!("expected an alignment in quotes, one of {}; found {}", names.join(", "), describe(value))),
+                };
+            }
+            KeyKind::Overflow => {
+                let names = TextOverflow::ALL.map(TextOverflow::name);
+                return match value.as_str() {
+                    Some(name) if names.contains(&name) => Ok(()),
+                    Some(name) => Err(format!("'{}' isn't an overflow setting{}; use one of {}", name, did_you_mean(name, &names), names.join(", "))),
+                    None => Err(format!("expected an overflow setting in quotes, one of {}; found {}", names.join(", "), describe(value))),
+                };
+            }
+            // Each column's keys are checked by check_fields
+            KeyKind::Columns => value.as_array().is_some_and(|columns| !columns.is_empty()),
+            KeyKind::Rows => value.as_array().is_some_and(|rows| {
+                rows.iter().all(|row| row.as_array().is_some_and(|cells| cells.iter().all(Value::is_string)))
+            }),
+            KeyKind::Mapping => value.as_object().is_some_and(|mapping| mapping.values().all(Value::is_string)),
+            KeyKind::Fields => value.is_array(),
+            KeyKind::Back => value.is_object(),
+        };
+        if ok {
+            return Ok(());
+        }
+        let expected = match self {
+            KeyKind::Version => format!("the layout version, {}", LAYOUT_VERSION),
+            KeyKind::Text => "text in quotes".to_string(),
+            KeyKind::Pixels => "a whole number of pixels, at least 1".to_string(),
+            KeyKind::Number => "a positive number".to_string(),
+            KeyKind::Length => "pixels like 40 or a length like \"24pt\" or \"8mm\"".to_string(),
+            KeyKind::Gap => "pixels like 20 or a length like \"5mm\", at least 0".to_string(),
+            KeyKind::Thickness => "pixels like 12, a length like \"3mm\", or a list of four for the top, right, bottom and left".to_string(),
+            KeyKind::Coordinate => "pixels like 400, a percentage like \"50%\" or a length like \"30mm\"".to_string(),
+            KeyKind::Columns => "a list of columns such as [{ width = 300, header = \"Module\" }]".to_string(),
+            KeyKind::Rows => "a list of rows, each a list of cell templates in quotes, such as [[\"{Module1}\", \"{Score1}\"]]".to_string(),
+            KeyKind::Mapping => "a [mapping] table of placeholders and the columns that fill them, such as course = \"Programme\"".to_string(),
+            KeyKind::Fields => "a list of [[fields]] tables".to_string(),
+            KeyKind::Back => "a [back] table with the back's template and fields".to_string(),
+            KeyKind::Anchor | KeyKind::Color | KeyKind::Compression | KeyKind::Format | KeyKind::BorderLine | KeyKind::PatchFill
+            | KeyKind::Effect | KeyKind::Align | KeyKind::Overflow => unreachable!("checked above"),
+        };
+        Err(format!("expected {}, found {}", expected, describe(value)))
+    }
+}
+
+// Every problem with the shape of a parsed layout document, each starting with its key's path
+fn check_schema(document: &Value) -> Vec<String> {
+    let Some(table) = document.as_object() else {
+        return vec![format!("expected a table of keys starting with version = {}, found {}", LAYOUT_VERSION, describe(document))];
+    };
+    let mut problems = Vec::new();
+    check_keys("", "layouts", table, LAYOUT_KEYS, &mut problems);
+    check_fields("fields", table, &mut problems);
+    if let Some(back) = table.get("back").and_then(Value::as_object) {
+        check_keys("back", "back sides", back, BACK_KEYS, &mut problems);
+        check_fields("back.fields", back, &mut problems);
+    }
+    problems
+}
+
+// Problems with each field listed under `key` of `table`
+fn check_fields(key: &str, table: &Map<String, Value>, problems: &mut Vec<String>) {
+    let fields = table.get("fields").and_then(Value::as_array).map(Vec::as_slice).unwrap_or_default();
+    for (index, field) in fields.iter().enumerate() {
+        let path = format!("{}[{}]", key, index);
+        let Some(field) = field.as_object() else {
+            problems.push(format!("{}: expected a field table, found {}", path, describe(field)));
+            continue;
+        };
+        let keys = match field.get("type").map(|kind| (kind, kind.as_str())) {
+            Some((_, Some("text"))) => TEXT_KEYS,
+            Some((_, Some("image"))) => IMAGE_KEYS,
+            Some((_, Some("barcode"))) => BARCODE_KEYS,
+            Some((_, Some("table"))) => TABLE_KEYS,
+            Some((_, Some("border"))) => BORDER_KEYS,
+            Some((_, Some("patch"))) => PATCH_KEYS,
+            Some((_, Some(kind))) => {
+                problems.push(format!("{}.type: '{}' isn't a field type{}; use one of {}", path, kind, did_you_mean(kind, &FIELD_TYPES), FIELD_TYPES.join(", ")));
+                continue;
+            }
+            Some((kind, None)) => {
+                problems.push(format!("{}.type: expected one of {} in quotes, found {}", path, FIELD_TYPES.join(", "), describe(kind)));
+                continue;
+            }
+            None => {
+                problems.push(format!("{}.type: missing; every field needs a type, one of {}", path, FIELD_TYPES.join(", ")));
+                continue;
+            }
+        };
+        let kind = field["type"].as_str().unwrap_or_default();
+        check_keys(&path, &format!("{} fields", kind), field, keys, problems);
+        if kind == "table" {
+            let columns = field.get("columns").and_then(Value::as_array).map(Vec::as_slice).unwrap_or_default();
+            for (index, column) in columns.iter().enumerate() {
+                match column.as_object() {
+                    Some(column) => check_keys(&format!("{}.columns[{}]", path, index), "table columns", column, COLUMN_KEYS, problems),
+                    None => problems.push(format!("{}.columns[{}]: expected a column table such as {{ width = 300 }}, found {}", path, index, describe(column))),
+                }
+            }
+        }
+    }
+}
+
+// Problems with the keys of one table: unknown keys, missing required ones and wrong values
+fn check_keys(prefix: &str, what: &str, table: &Map<String, Value>, keys: &[(&str, KeyKind, bool)], problems: &mut Vec<String>) {
+    let path = |key: &str| if prefix.is_empty() { key.to_string() } else { format!("{}.{}", prefix, key) };
+    let names: Vec<&str> = keys.iter().map(|(name, ..)| *name).collect();
+    for (key, value) in table {
+        match keys.iter().find(|(name, ..)| name == key) {
+            Some((_, kind, _)) => {
+                if let Err(e) = kind.check(value) {
+                    problems.push(format!("{}: {}", path(key), e));
+                }
+            }
+            None => problems.push(format!("{}: unknown key{}; {} take {}", path(key), did_you_mean(key, &names), what, names.join(", "))),
+        }
+    }
+    for (name, ..) in keys.iter().filter(|(name, _, required)| *required && !table.contains_key(*name)) {
+        problems.push(format!("{}: missing; {} need it", path(name), what));
+    }
+}
+
+// " (did you mean 'color'?)" when one of `candidates` is a couple of typos from `wanted`
+fn did_you_mean(wanted: &str, candidates: &[&str]) -> String {
+    let wanted_lower = wanted.to_lowercase();
+    candidates.iter()
+        .map(|candidate| (edit_distance(&wanted_lower, &candidate.to_lowercase()), candidate))
+        .filter(|(distance, _)| *distance <= (wanted.chars().count() / 3).max(2))
+        .min()
+        .map(|(_, candidate)| format!(" (did you mean '{}'?)", candidate))
+        .unwrap_or_default()
+}
+
+// A value as it would be written in the file, for messages
+fn describe(value: &Value) -> String {
+    match value {
+        Value::String(text) => format!("\"{}\"", text),
+        Value::Array(_) => "a list".to_string(),
+        Value::Object(_) => "a table".to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn layout_extension(path: &Path) -> String {
+    path.extension().map(|extension| extension.to_string_lossy().to_lowercase()).unwrap_or_default()
+}
+
+// Problems with each of `fields`, listed under `key`, placed with `scale` on a template of
+// `dimensions` (unknown when it can't be read)
+fn validate_fields(key: &str, fields: &[LayoutField], scale: &TemplateScale, dimensions: Option<(u32, u32)>, problems: &mut Vec<String>) {
+    for (index, field) in fields.iter().enumerate() {
+        let graphic = match field.graphic(scale) {
+            Ok(Some(graphic)) => graphic,
+            // A border or a patch: its numbers, and that it fits on the template
+            Ok(None) => {
+                match field.patch(scale) {
+                    Ok(Some(patch)) => {
+                        if let Some((width, height)) = dimensions
+                            && let Err(e) = patch.validate(width, height) {
+                            problems.push(format!("{}[{}]: {}", key, index, e));
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => problems.push(format!("{}[{}].{}", key, index, e)),
+                }
+                match field.border(scale) {
+                    Ok(Some(border)) => {
+                        if let Some((width, height)) = dimensions
+                            && let Err(e) = border.validate(width, height) {
+                            problems.push(format!("{}[{}].thickness: {}", key, index, e));
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => problems.push(format!("{}[{}].{}", key, index, e)),
+                }
+                continue;
+            }
+            Err(e) => {
+                problems.push(format!("{}[{}].{}", key, index, e));
+                continue;
+            }
+        };
+        if let Err(e) = graphic.validate() {
+            let value_key = match &e {
+                CertError::FontNotFound(_) | CertError::FontParse(_) | CertError::UnknownFont { .. } | CertError::AmbiguousFont { .. } => "font",
+                CertError::InvalidColor { .. } => "color",
+                CertError::InvalidFontSize(_) => "size",
+                CertError::InvalidMeasurement(_) => "max_width",
+                CertError::InvalidTable(_) => "columns",
+                _ => field.value_key(),
+            };
+            problems.push(format!("{}[{}].{}: {}", key, index, value_key, e));
+        }
+        if let LayoutField::Image { path, .. } = field
+            && placeholders(path).is_empty()
+            && let Err(e) = oriented_dimensions(path) {
+            problems.push(format!("{}[{}].path: {} can't be read: {}", key, index, path, e));
+        }
+    }
+}
+
+impl Layout {
+    /// Write the layout as TOML or JSON, by the extension of `path`. Paths are written as they
+    /// are, so a loaded layout saved elsewhere keeps pointing at the same files.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let parse_error = |reason: String| CertError::LayoutParse { path: path.to_path_buf(), reason };
+        let content = match layout_extension(path).as_str() {
+            "toml" => toml::to_string_pretty(self).map_err(|e| parse_error(e.to_string()))?,
+            "json" => serde_json::to_string_pretty(self).map_err(|e| parse_error(e.to_string()))?,
+            _ => return Err(parse_error("layouts are .toml or .json files".to_string())),
+        };
+        std::fs::write(path, content).io_context(|| format!("Failed to write layout {}", path.display()))
+    }
+
+    /// The first text field, which is drawn as the name.
+    pub fn name_field(&self) -> Option<&LayoutField> {
+        self.fields.iter().find(|field| matches!(field, LayoutField::Text { .. }))
+    }
+
+    /// Where the name is drawn on the template, in pixels; `None` without a readable template
+    /// or a name field.
+    pub fn name_position(&self) -> Option<(i32, i32)> {
+        let graphic = self.name_field()?.graphic(&self.scale().ok()?).ok()??;
+        Some((graphic.x, graphic.y))
+    }
+
+    /// How the layout's numbers become pixels on its template: the template's size, its DPI
+    /// (`template_dpi`, else what the file records, else [`ASSUMED_DPI`]) and the `dpi` the
+    /// plain pixel numbers were measured at.
+    pub fn scale(&self) -> Result<TemplateScale> {
+        let dimensions = oriented_dimensions(&self.template)
+            .map_err(|source| CertError::ImageDecode { path: self.template.clone(), source })?;
+        Ok(self.scale_for(dimensions))
+    }
+
+    fn scale_for(&self, dimensions: (u32, u32)) -> TemplateScale {
+        let template_dpi = self.template_dpi.or_else(|| image_dpi(&self.template).ok().flatten());
+        TemplateScale::new(dimensions, template_dpi, self.dpi)
+    }
+
+    /// Whether where things land depends on the template's DPI: the layout sets `dpi`, or a
+    /// field uses a physical length.
+    pub fn uses_dpi(&self) -> bool {
+        let back_fields = self.back.iter().flat_map(|back| &back.fields);
+        self.dpi.is_some() || self.fields.iter().chain(back_fields).any(LayoutField::is_physical)
+    }
+
+    /// Why the layout may land in the wrong place: it depends on the template's DPI, but the
+    /// template doesn't record one and `template_dpi` isn't set, so [`ASSUMED_DPI`] is used.
+    pub fn dpi_warning(&self) -> Option<String> {
+        let scale = self.scale().ok()?;
+        (self.uses_dpi() && scale.dpi_assumed).then(|| {
+            format!("{} doesn't record its DPI, so {} DPI is assumed to place the layout; set template_dpi if it is another",
+                    self.template.display(), ASSUMED_DPI)
+        })
+    }
+
+    /// Check the template, every font, color and position, and every image that isn't
+    /// named per row, returning all problems at once, each starting with its key's path such as
+    /// `fields[1].font`. Columns are checked against a CSV by [`check_columns`](Self::check_columns).
+    pub fn validate(&self) -> std::result::Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+        if self.version != LAYOUT_VERSION {
+            problems.push(format!("version: {} isn't supported; this version reads version {}", self.version, LAYOUT_VERSION));
+        }
+        let dimensions = match oriented_dimensions(&self.template) {
+            Ok(dimensions) => Some(dimensions),
+            Err(e) => {
+                problems.push(format!("template: {} can't be read: {}", self.template.display(), e));
+                None
+            }
+        };
+        if self.name_field().is_none() {
+            problems.push("fields: a layout needs a text field; the first one is drawn as the name".to_string());
+        }
+        for (key, dpi) in [("dpi", self.dpi), ("template_dpi", self.template_dpi)] {
+            if let Some(dpi) = dpi
+                && !(dpi.is_finite() && dpi > 0.0) {
+                problems.push(format!("{}: must be a positive number of dots per inch, got {}", key, dpi));
+            }
+        }
+
+        let scale = self.scale_for(dimensions.unwrap_or_default());
+        validate_fields("fields", &self.fields, &scale, dimensions, &mut problems);
+
+        if let Some(back) = &self.back {
+            let dimensions = match oriented_dimensions(&back.template) {
+                Ok(dimensions) => Some(dimensions),
+                Err(e) => {
+                    problems.push(format!("back.template: {} can't be read: {}", back.template.display(), e));
+                    None
+                }
+            };
+            if let Some(dpi) = back.template_dpi
+                && !(dpi.is_finite() && dpi > 0.0) {
+                problems.push(format!("back.template_dpi: must be a positive number of dots per inch, got {}", dpi));
+            }
+            let scale = back.scale_for(dimensions.unwrap_or_default(), self.dpi);
+            validate_fields("back.fields", &back.fields, &scale, dimensions, &mut problems);
+        }
+
+        if problems.is_empty() { Ok(()) } else { Err(problems) }
+    }
+
+    /// Check that every `{Column}` the fields use is one of `headers`, by its own name or the
+    /// column the [`mapping`](Self::mapping) gives it.
+    pub fn check_columns(&self, headers: &[String]) -> std::result::Result<(), Vec<String>> {
+        let known = |column: &str| column.eq_ignore_ascii_case("name") || self.mapping.column_for(column, headers).is_some();
+        let header_names: Vec<&str> = headers.iter().map(|header| header.trim()).collect();
+        let back_fields = self.back.iter().flat_map(|back| back.fields.iter().enumerate().map(|(index, field)| ("back.fields", index, field)));
+        let problems: Vec<String> = self.fields.iter().enumerate()
+            .map(|(index, field)| ("fields", index, field))
+            .chain(back_fields)
+            .flat_map(|(key, index, field)| {
+                let header_names = &header_names;
+                field.templates().into_iter()
+                    .flat_map(|(value_key, template)| placeholders(template).into_iter().map(move |column| (value_key.clone(), column)))
+                    .filter(|(_, column)| !known(column))
+                    .map(move |(value_key, column)| match self.mapping.get(column) {
+                        Some(mapped) => format!("{}[{}].{}: uses the column '{{{}}}', mapped to '{}', which the list doesn't have{}; its columns are {}",
+                                                key, index, value_key, column, mapped, did_you_mean(mapped, header_names), header_names.join(", ")),
+                        None => format!("{}[{}].{}: uses the column '{{{}}}', which the list doesn't have{}; its columns are {}",
+                                        key, index, value_key, column, did_you_mean(column, header_names), header_names.join(", ")),
+                    })
+            })
+            .collect();
+        if problems.is_empty() { Ok(()) } else { Err(problems) }
+    }
+
+    /// Every `{Column}` the fields use, front then back, each once.
+    pub fn placeholders(&self) -> Vec<&str> {
+        let back_fields = self.back.iter().flat_map(|back| &back.fields);
+        let mut found: Vec<&str> = Vec::new();
+        for field in self.fields.iter().chain(back_fields) {
+            for (_, template) in field.templates() {
+                for column in placeholders(template) {
+                    if !found.iter().any(|known| known.trim().eq_ignore_ascii_case(column.trim())) {
+                        found.push(column);
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    /// The placeholders no column of `headers` fills, by its own name or through the mapping:
+    /// `name` first when the name column can't be found, then those of [`placeholders`](Self::placeholders).
+    pub fn unmapped(&self, headers: &[String]) -> Vec<String> {
+        let name = self.name_column().find(headers).is_none().then(|| "name".to_string());
+        let columns = self.placeholders().into_iter().filter(|column| !column.trim().eq_ignore_ascii_case("name"));
+        name.into_iter()
+            .chain(self.mapping.unmapped(columns, headers).into_iter().map(str::to_string))
+            .collect()
+    }
+
+    /// The column holding the names: [`name_column`](Self::name_column) when set, then the
+    /// column the [`mapping`](Self::mapping) gives `name`, otherwise the usual aliases.
+    pub fn name_column(&self) -> NameColumn {
+        match self.name_column.as_deref().or(self.mapping.get("name")) {
+            Some(header) => NameColumn::header(header),
+            None => NameColumn::from_env(),
+        }
+    }
+
+    /// A job drawing this layout for `records`: the first text field is the name, with the
+    /// records' names giving the filenames, and the other fields are graphic fields.
+    pub fn job(&self, records: Vec<Record>) -> Result<CertificateJob> {
+        self.validate().map_err(CertError::InvalidLayout)?;
+        let scale = self.scale()?;
+        let mut graphics = Vec::new();
+        let mut patches = Vec::new();
+        let mut borders = Vec::new();
+        for field in &self.fields {
+            graphics.extend(field.graphic(&scale).map_err(|e| CertError::InvalidLayout(vec![e]))?);
+            patches.extend(field.patch(&scale).map_err(|e| CertError::InvalidLayout(vec![e]))?);
+            borders.extend(field.border(&scale).map_err(|e| CertError::InvalidLayout(vec![e]))?);
+        }
+        let name_index = graphics.iter().position(|graphic| matches!(graphic.kind, GraphicKind::Text(_)))
+            .ok_or_else(|| CertError::InvalidLayout(vec!["fields: a layout needs a text field; the first one is drawn as the name".to_string()]))?;
+        let name = graphics.remove(name_index);
+        let GraphicKind::Text(style) = name.kind else { unreachable!("the name field is a text field") };
+        let texts = records.iter()
+            .map(|record| fill_placeholders(&name.data, &record.name, Some(record)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let names = records.iter().map(|record| record.name.clone()).collect();
+        let mut job = CertificateJob::new(&self.template, names)
+            .texts(texts)
+            .font(&style.font)
+            .font_size(style.size)
+            .color(&style.color)
+            .position(name.anchor, name.x, name.y)
+            .graphics(graphics)
+            .patches(patches)
+            .borders(borders)
+            .records(records);
+        if let Some(max_width) = style.max_width {
+            job = job.max_width(max_width).overflow(style.overflow);
+        }
+        if let Some(ellipsis) = style.ellipsis {
+            job = job.ellipsis(ellipsis);
+        }
+        if let Some(effect) = style.effect {
+            job = job.effect(effect);
+        }
+        if let Some(compression) = self.compression {
+            job = job.compression(compression);
+        }
+        if let Some(format) = self.format {
+            job = job.format(format);
+        }
+        if let Some(back) = &self.back {
+            job = job.back(back.side(self.dpi)?);
+        }
+        Ok(job)
+    }
+
+    /// A job drawing this layout for every row of a CSV, JSON or text list, after checking
+    /// the columns the fields use are in it, directly or through the mapping.
+    pub fn job_from_list(&self, list: impl AsRef<Path>, format: &CsvFormat) -> Result<CertificateJob> {
+        let parsed = parse_name_list_with(list, &self.name_column(), format)?;
+        self.check_columns(&parsed.headers).map_err(CertError::InvalidLayout)?;
+        let rows = parsed.rows.clone();
+        let merged = MergedCsv { files: vec![parsed] };
+        let mut records = merged.records(&rows, &[]);
+        self.mapping.apply(&mut records);
+        let emails = merged.emails(&rows, &[]);
+        Ok(self.job(records)?.rows(rows).emails(emails))
+    }
+}
+
+/// Render a certificate for every row of `csv` with `layout` into `output_dir`.
+pub fn generate_from_layout(layout: &Layout, csv: impl AsRef<Path>, output_dir: impl AsRef<Path>) -> Result<BatchReport> {
+    layout.job_from_list(csv, &CsvFormat::default())?
+        .output_dir(output_dir.as_ref())
+        .run()
+}
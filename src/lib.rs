@@ -0,0 +1,66 @@
+// src/lib.rs
+//! Certificate Maker library: analyze templates, parse name lists and render
+//! certificates without any interactive I/O.
+//!
+//! The `CertificateMaker` binary builds its menu on top of these modules.
+pub mod analysis;
+pub mod audit;
+pub mod backside;
+pub mod barcode;
+pub mod calibration;
+pub mod csvexcelparser;
+pub mod depth;
+pub mod dpi;
+pub mod duplicates;
+pub mod editpng;
+pub mod effects;
+#[cfg(feature = "email")]
+pub mod email;
+pub mod error;
+pub mod files;
+pub mod fingerprint;
+pub mod fontcache;
+pub mod fontnames;
+pub mod fontsize;
+#[cfg(feature = "remote")]
+pub mod googlefonts;
+pub mod graphics;
+pub mod grouping;
+pub mod i18n;
+pub mod imposition;
+pub mod job;
+pub mod layout;
+pub mod manifest;
+pub mod mapping;
+pub mod measure;
+pub mod memory;
+pub mod namecase;
+pub mod normalize;
+pub mod orientation;
+pub mod palette;
+pub mod patch;
+pub mod paths;
+pub mod pdf;
+pub mod plan;
+pub mod perftest;
+pub mod placeholder;
+pub mod preview;
+pub mod progress;
+#[cfg(feature = "remote")]
+pub mod remote;
+pub mod runlog;
+pub mod sanitize;
+pub mod selection;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod shard;
+pub mod source;
+pub mod stamp;
+pub mod table;
+pub mod templates;
+pub mod timing;
+pub mod typography;
+pub mod variation;
+pub mod verify;
+pub mod watermark;
+pub mod webhook;
@@ -0,0 +1,93 @@
+// src/mailing.rs
+// The --email and --dry-run-email step after a batch, built on certificate_maker::email
+use anyhow::Result;
+
+use certificate_maker::csvexcelparser::{BatchReport, BatchSettings};
+use certificate_maker::paths::AppPaths;
+#[cfg(feature = "email")]
+use certificate_maker::{say, tr};
+
+use crate::interactive::EmailMode;
+
+// Email the batch's certificates, or show what would be sent; SMTP credentials only ever come
+// from the environment or the config file, never from a prompt
+#[cfg(feature = "email")]
+pub fn email_certificates(settings: &BatchSettings, summary: &BatchReport, mode: EmailMode, paths: &AppPaths) -> Result<()> {
+    use certificate_maker::email::{EmailSettings, Mailer, plan_emails, send_emails};
+
+    use crate::input::ask;
+
+    let email = EmailSettings::load(&paths.config)?;
+    let plan = plan_emails(&email, &summary.entries);
+
+    say!("\n📧 === Emailing Certificates ===");
+    if !plan.unaddressed.is_empty() {
+        say!("⚠️ {} certificates have no valid email address and are skipped:", plan.unaddressed.len());
+        for skipped in &plan.unaddressed {
+            let row = skipped.row.map(|row| format!(" (row {})", row)).unwrap_or_default();
+            match &skipped.address {
+                Some(address) => println!("  • {}{}: '{}'", skipped.name, row, address),
+                None => say!("  • {}{}: no address", skipped.name, row),
+            }
+        }
+    }
+    if plan.already_sent > 0 {
+        say!("✅ {} certificates were already emailed in an earlier run", plan.already_sent);
+    }
+    if plan.outgoing.is_empty() {
+        say!("📭 Nothing left to email.");
+        return Ok(());
+    }
+
+    let sender = email.from.as_deref().unwrap_or("(no sender set)");
+    let server = email.server().unwrap_or_else(|| "(no SMTP server set)".to_string());
+    if mode == EmailMode::DryRun {
+        let first = &plan.outgoing[0];
+        say!("🧪 Dry run: {} emails from {} via {}, one every {} s", plan.outgoing.len(), sender, server,
+             format!("{:.1}", email.interval().as_secs_f64()));
+        say!("\n--- First message ---");
+        say!("To: {}", first.to);
+        say!("Subject: {}", first.subject);
+        say!("Attachment: {}", first.attachment.display());
+        println!("\n{}", first.body.trim_end());
+        println!("---------------------\n");
+        for outgoing in &plan.outgoing {
+            println!("  • {} <{}>: \"{}\" + {}", outgoing.name, outgoing.to,
+                     outgoing.subject, outgoing.attachment.file_name().unwrap_or_default().to_string_lossy());
+        }
+        say!("🧪 Nothing was sent. Run with --email instead of --dry-run-email to send these.");
+        return Ok(());
+    }
+
+    let confirm = ask(&tr!("Send {} emails from {} via {}? (y/N): ", plan.outgoing.len(), sender, server))?;
+    if !confirm.eq_ignore_ascii_case("y") {
+        say!("↩️ Nothing was sent.");
+        return Ok(());
+    }
+    let mailer = Mailer::connect(&email)?;
+    let total = plan.outgoing.len();
+    let mut attempted = 0;
+    let report = send_emails(&mailer, &plan, &settings.output_dir, email.interval(), |outgoing, result| {
+        attempted += 1;
+        match result {
+            Ok(()) => say!("📨 [{}/{}] Sent to {} <{}>", attempted, total, outgoing.name, outgoing.to),
+            Err(e) => println!("❌ [{}/{}] {} <{}>: {}", attempted, total, outgoing.name, outgoing.to, e),
+        }
+    })?;
+
+    say!("\n📊 Emailed: {} | Failed: {} | Already sent: {} | No address: {}",
+         report.sent, report.failed.len(), plan.already_sent, plan.unaddressed.len());
+    if report.not_attempted > 0 {
+        say!("⚠️ Stopped after several failures in a row; {} emails were not attempted.", report.not_attempted);
+    }
+    if !report.failed.is_empty() || report.not_attempted > 0 {
+        say!("💡 Run the batch again with --email to retry; certificates already sent are skipped.");
+    }
+    Ok(())
+}
+
+// The flags are refused up front without the feature, so this is never reached
+#[cfg(not(feature = "email"))]
+pub fn email_certificates(_settings: &BatchSettings, _summary: &BatchReport, _mode: EmailMode, _paths: &AppPaths) -> Result<()> {
+    anyhow::bail!("This build can't send email; rebuild with `cargo run --features email`")
+}
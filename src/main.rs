@@ -1,356 +1,1253 @@
 // src/main.rs
 use anyhow::Result;
-use std::io::{self, Write};
-use std::path::Path;
+use std::ffi::OsString;
+use std::path::PathBuf;
 
-// Declare modules
-mod analysis;
-mod editpng;
-mod csvexcelparser;
+// Declare binary-only modules (the library lives in lib.rs)
+mod display;
+mod input;
+mod interactive;
+mod mailing;
+#[cfg(feature = "tui")]
+mod tui;
+mod watch;
 
 // Import functions
-use analysis::{analyze_png_file, print_analysis};
-use editpng::add_text_to_png_interactive;
-use csvexcelparser::{generate_certificates_interactive, create_sample_csv, select_csv_file, debug_csv_file, select_template_file, debug_template_file};
+use certificate_maker::analysis::{PrintTarget, analyze_image_file, analyze_image_file_json, analyze_image_files, compare_images, write_alpha_heatmap, write_analysis_json, write_comparison_csv};
+use certificate_maker::csvexcelparser::{CsvEncoding, CsvFormat, create_sample_csv};
+use certificate_maker::depth::DepthPolicy;
+use certificate_maker::barcode::BarcodeStyle;
+use certificate_maker::duplicates::DuplicatePolicy;
+use certificate_maker::graphics::{GraphicField, GraphicKind};
+use certificate_maker::job::{Anchor, CertificateFormat, OverwritePolicy, PngCompression, SyntheticStyle};
+use certificate_maker::imposition::{Grid, Imposition, PAPER_SIZES, SheetSize};
+use certificate_maker::effects::find_effect;
+use certificate_maker::fontsize::FontSizeLimits;
+use certificate_maker::layout::{Coordinate, Length};
+use certificate_maker::memory::parse_size;
+use certificate_maker::preview::DEFAULT_PREVIEW_SIZE;
+use certificate_maker::namecase::NameCase;
+use certificate_maker::normalize::Normalization;
+use certificate_maker::fingerprint::FingerprintMode;
+use certificate_maker::orientation;
+use certificate_maker::i18n::{self, Catalog};
+use certificate_maker::palette::{self, Palette, check_color};
+use certificate_maker::watermark::{DEFAULT_TILE_SPACING, WatermarkLayout, WatermarkStyle};
+use certificate_maker::paths::{AppPaths, CONFIG_FILE, PathOverrides};
+use certificate_maker::selection::{RowSelection, random_seed};
+use certificate_maker::shard::Shard;
+use certificate_maker::stamp::{StampLogo, StampStyle};
+use certificate_maker::{say, tr};
+use display::{print_analysis, print_comparison_table, print_diff_report, print_error_hint};
+use interactive::{
+    BatchOptions, EmailMode, add_text_to_single_image_interactive, audit_certificates, debug_csv_file, debug_template_file, download_font, execute_plan_interactive, merge_manifests_interactive, check_print_readiness_interactive,
+    generate_certificates_interactive,
+    generate_from_layout_interactive, list_image_files_in_dir, manage_templates, repeat_last_generation, run_demo, run_perf_test, select_csv_file, select_image_with_dir, select_input_image,
+    select_template_file, stamp_directory, typed_path, undo_last_batch, validate_layout_interactive, verify_certificates,
+};
+use input::{Interrupt, ask, get_user_input, interrupt_of, is_interrupt};
+use watch::watch_csv_interactive;
 
-fn get_user_input(prompt: &str) -> String {
-    print!("{}", prompt);
-    io::stdout().flush().unwrap();
-    
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
-    input.trim().to_string()
+// Helper function to show path tips
+fn show_path_tips(paths: &AppPaths) {
+    say!("\n💡 File Organization Tips:");
+    say!("  • Put input images in current directory or {}/ folder", paths.templates.display());
+    say!("  • Output files will be saved in current directory or {}/ folder", paths.output.display());
+    say!("  • CSV files should be in {}/ directory", paths.csvs.display());
+    say!("  • Template files should be in {}/ directory", paths.templates.display());
+    say!("  • Font files should be in {}/ directory", paths.fonts.display());
+    say!("  • Move any of these with --template-dir/--fonts-dir/--csv-dir/--certificates-dir/--output-dir,");
+    say!("    CERTMAKER_TEMPLATE_DIR and friends, or a {} config file", CONFIG_FILE);
 }
 
-// Function to list image files in a specific directory
-fn list_image_files_in_dir(dir_path: &str) -> Result<Vec<String>, String> {
-    let mut image_files = Vec::new();
-    
-    if !Path::new(dir_path).exists() {
-        return Err(format!("Directory '{}' not found", dir_path));
-    }
-    
-    let entries = std::fs::read_dir(dir_path)
-        .map_err(|_| format!("Failed to read directory '{}'", dir_path))?;
-    
-    for entry in entries {
-        if let Ok(entry) = entry {
-            let path = entry.path();
-            if let Some(extension) = path.extension() {
-                let ext = extension.to_string_lossy().to_lowercase();
-                if ext == "png" || ext == "jpg" || ext == "jpeg" || ext == "bmp" || ext == "gif" {
-                    if let Some(filename) = path.file_name() {
-                        image_files.push(filename.to_string_lossy().to_string());
+fn show_menu(paths: &AppPaths) {
+    say!("\n🎯 === Certificate Maker ===");
+    say!("1. Add text to single image (interactive)");
+    say!("2. Generate certificates from CSV files in '{}' directory", paths.csvs.display());
+    say!("3. Analyze image file");
+    say!("4. Create sample CSV file");
+    say!("5. Debug CSV file");
+    say!("6. Debug template file");
+    say!("7. Show file organization tips");
+    say!("8. Watch CSV file and generate certificates for new rows");
+    say!("9. Analyze all templates");
+    say!("10. Compare two images");
+    say!("11. Repeat last generation");
+    say!("12. Undo last batch");
+    say!("13. Manage templates");
+    say!("14. Download a font from Google Fonts");
+    say!("15. Verify issued certificates");
+    say!("16. Generate certificates from a layout file");
+    say!("17. Validate a layout file");
+    say!("18. Check a template's print readiness");
+    say!("19. Exit");
+}
+
+// What the menu loop should do after an option finishes
+enum Flow {
+    Pause,
+    Menu,
+    Exit,
+}
+
+fn run_menu_option(choice: &str, paths: &AppPaths, options: &BatchOptions) -> Result<Flow> {
+    match choice {
+        "1" => {
+            // Single image text addition
+            say!("\n📝 Single Image Text Addition");
+            say!("💡 Type 'back' at any prompt to change the previous answer, or 'cancel' to return to the menu");
+            match add_text_to_single_image_interactive(paths, options.watermark.as_ref()) {
+                Ok(()) => say!("✅ Text added successfully!"),
+                Err(e) if is_interrupt(&e) => return Err(e),
+                Err(e) => {
+                    say!("❌ Error: {}", e);
+                    print_error_hint(&e);
+                    show_path_tips(paths);
+                }
+            }
+        }
+        
+        "2" => {
+            // Batch certificate generation
+            say!("\n🎓 Certificate Generator");
+            match generate_certificates_interactive(paths, options) {
+                Ok(()) if options.dry_run => say!("🧪 Dry run completed; no certificates were written."),
+                Ok(()) if options.plan_out.is_some() => say!("📋 Plan saved; no certificates were written."),
+                Ok(()) => say!("🎉 Batch certificate generation completed!"),
+                Err(e) if is_interrupt(&e) => return Err(e),
+                Err(e) => {
+                    say!("❌ Error: {}", e);
+                    print_error_hint(&e);
+                    show_path_tips(paths);
+                }
+            }
+        }
+        
+        "3" => {
+            // Analyze PNG file - UPDATED with menu selection
+            say!("\n📊 Image File Analysis");
+            
+            let file_path = match select_input_image(paths) {
+                Ok(file) => file,
+                Err(e) if is_interrupt(&e) => return Err(e),
+                Err(e) => {
+                    println!("❌ {}", e);
+                    return Ok(Flow::Menu);
+                }
+            };
+            
+            if !file_path.exists() {
+                say!("❌ Selected file not found: {}", file_path.display());
+                return Ok(Flow::Menu);
+            }
+            
+            let analysis = match analyze_image_file(&file_path) {
+                Ok(analysis) => analysis,
+                Err(e) => {
+                    say!("❌ Error analyzing file: {}", e);
+                    show_path_tips(paths);
+                    return Ok(Flow::Menu);
+                }
+            };
+            print_analysis(&analysis);
+            
+            // Visualize where the transparent regions are
+            if analysis.alpha.is_some() {
+                let heatmap = ask(&tr!("\nWrite a transparency heatmap to '{}'? (y/N): ", paths.output.display()))?;
+                if heatmap.eq_ignore_ascii_case("y") {
+                    let stem = file_path
+                        .file_stem()
+                        .map(|s| s.to_string_lossy())
+                        .unwrap_or("image".into());
+                    let heatmap_path = paths.output.join(format!("{}_alpha_heatmap.png", stem));
+                    match write_alpha_heatmap(&analysis, &heatmap_path) {
+                        Ok(()) => say!("✅ Heatmap saved to: {}", heatmap_path.display()),
+                        Err(e) => say!("❌ Error writing heatmap: {}", e),
                     }
                 }
             }
+            
+            // Optional machine-readable export
+            say!("\n💾 Export analysis as JSON?");
+            say!("1. Save next to the image");
+            say!("2. Print to stdout");
+            let export = ask(&tr!("Select option (1-2, or press Enter to skip): "))?;
+            match export.as_str() {
+                "1" => match write_analysis_json(&file_path) {
+                    Ok(json_path) => say!("✅ Analysis saved to: {}", json_path.display()),
+                    Err(e) => say!("❌ Error exporting analysis: {}", e),
+                },
+                "2" => match analyze_image_file_json(&file_path) {
+                    Ok(json) => println!("{}", json),
+                    Err(e) => say!("❌ Error exporting analysis: {}", e),
+                },
+                _ => {}
+            }
         }
-    }
-    
-    if image_files.is_empty() {
-        return Err(format!("No image files found in directory '{}'", dir_path));
-    }
-    
-    image_files.sort();
-    Ok(image_files)
-}
-
-// Function to select input image file
-fn select_input_image() -> Result<String, String> {
-    let base_path = "Template".to_string();
-    let image_files = match list_image_files_in_dir(&base_path) {
-        Ok(files) => files,
-        Err(e) => return Err(e),
-    };
-    
-    println!("\n🖼️ Available Image Files in 'Template' directory:");
-    for (i, file) in image_files.iter().enumerate() {
-        println!("  {}. {}", i + 1, file);
-    }
-    
-    loop {
-        let input = get_user_input("\nSelect image file (enter number or filename): ");
         
-        // Try to parse as number first
-        if let Ok(num) = input.parse::<usize>() {
-            if num > 0 && num <= image_files.len() {
-                let selected_file = &image_files[num - 1];
-                let full_path = format!("{}/{}", base_path, selected_file);
-                println!("✅ Selected: {}", selected_file);
-                return Ok(full_path);
+        "4" => {
+            // Create sample CSV
+            say!("\n📄 Create Sample CSV");
+            
+            let default_file = paths.csvs.join("sample_names.csv");
+            let filename = ask(&tr!("Enter filename for sample CSV (default '{}'): ", default_file.display()))?;
+            let filename = if filename.is_empty() { default_file } else { typed_path(&filename) };
+            
+            match create_sample_csv(&filename) {
+                Ok(()) => {
+                    say!("✅ Sample CSV created: {}", filename.display());
+                    say!("✅ Sample CSV created successfully!");
+                    if let Ok(current_dir) = std::env::current_dir() {
+                        say!("📁 Full path: {}", current_dir.join(&filename).display());
+                    }
+                }
+                Err(e) => say!("❌ Error creating sample CSV: {}", e),
             }
         }
         
-        // Try to find by filename (case insensitive)
-        for file in &image_files {
-            if file.to_lowercase() == input.to_lowercase() {
-                let full_path = format!("{}/{}", base_path, file);
-                println!("✅ Selected: {}", file);
-                return Ok(full_path);
+        "5" => {
+            // Debug CSV file
+            say!("\n🔍 CSV File Debugger");
+            
+            let csv_file = match select_csv_file(paths) {
+                Ok(file) => file,
+                Err(e) if is_interrupt(&e) => return Err(e),
+                Err(e) => {
+                    println!("❌ {}", e);
+                    return Ok(Flow::Menu);
+                }
+            };
+            
+            match debug_csv_file(&csv_file, &options.csv_format) {
+                Ok(()) => say!("✅ CSV debug complete"),
+                Err(e) => say!("❌ Debug error: {}", e),
             }
         }
         
-        println!("❌ Invalid selection. Please try again.");
-    }
-}
+        "6" => {
+            // Debug template file
+            say!("\n🔍 Template File Debugger");
+            
+            let template_file = match select_template_file(paths) {
+                Ok(file) => file,
+                Err(e) if is_interrupt(&e) => return Err(e),
+                Err(e) => {
+                    println!("❌ {}", e);
+                    return Ok(Flow::Menu);
+                }
+            };
+            
+            match debug_template_file(&template_file) {
+                Ok(()) => say!("✅ Template debug complete"),
+                Err(e) => say!("❌ Debug error: {}", e),
+            }
+        }
+        
+        "7" => {
+            // Show file organization tips
+            show_path_tips(paths);
+        }
+        
+        "8" => {
+            // Watch CSV for late registrations
+            say!("\n👀 CSV Watch Mode");
+            match watch_csv_interactive(paths, options) {
+                Ok(()) => say!("✅ Watch mode finished"),
+                Err(e) if is_interrupt(&e) => return Err(e),
+                Err(e) => {
+                    say!("❌ Error: {}", e);
+                    print_error_hint(&e);
+                    show_path_tips(paths);
+                }
+            }
+        }
+        
+        "9" => {
+            // Analyze every template and compare them side by side
+            say!("\n📊 Template Comparison");
+            
+            let template_files = match list_image_files_in_dir(&paths.templates) {
+                Ok(files) => files,
+                Err(e) => {
+                    println!("❌ {}", e);
+                    return Ok(Flow::Menu);
+                }
+            };
+            let template_paths: Vec<PathBuf> = template_files.iter()
+                .map(|file| paths.templates.join(file))
+                .collect();
+            
+            let results = analyze_image_files(&template_paths);
+            print_comparison_table(&results);
+            
+            let report_path = paths.output.join("template_report.csv");
+            let save = ask(&tr!("\nSave table as '{}'? (y/N): ", report_path.display()))?;
+            if save.eq_ignore_ascii_case("y") {
+                match write_comparison_csv(&results, &report_path) {
+                    Ok(()) => say!("✅ Report saved to: {}", report_path.display()),
+                    Err(e) => say!("❌ Error saving report: {}", e),
+                }
+            }
+        }
+        
+        "10" => {
+            // Pixel diff between two renders
+            say!("\n🔍 Compare Two Images");
+            
+            let first = match select_image_with_dir(paths, &tr!("first")) {
+                Ok(file) => file,
+                Err(e) if is_interrupt(&e) => return Err(e),
+                Err(e) => {
+                    println!("❌ {}", e);
+                    return Ok(Flow::Menu);
+                }
+            };
+            let second = match select_image_with_dir(paths, &tr!("second")) {
+                Ok(file) => file,
+                Err(e) if is_interrupt(&e) => return Err(e),
+                Err(e) => {
+                    println!("❌ {}", e);
+                    return Ok(Flow::Menu);
+                }
+            };
+            
+            let diff_path = paths.output.join("diff.png");
+            let write_diff = ask(&tr!("Write a visual diff to '{}'? (y/N): ", diff_path.display()))?;
+            let diff_output = if write_diff.eq_ignore_ascii_case("y") { Some(diff_path.as_path()) } else { None };
+            
+            match compare_images(&first, &second, diff_output) {
+                Ok(report) => {
+                    print_diff_report(&report);
+                    if let Some(path) = diff_output
+                        && report.dimensions_match {
+                        say!("📁 Visual diff saved to: {}", path.display());
+                    }
+                }
+                Err(e) => say!("❌ Error comparing images: {}", e),
+            }
+        }
+        
+        "11" => {
+            match repeat_last_generation(paths, options) {
+                Ok(()) => {}
+                Err(e) if is_interrupt(&e) => return Err(e),
+                Err(e) => {
+                    say!("❌ Error: {}", e);
+                    print_error_hint(&e);
+                }
+            }
+        }
 
+        "12" => {
+            match undo_last_batch(paths, None, false) {
+                Ok(()) => {}
+                Err(e) if is_interrupt(&e) => return Err(e),
+                Err(e) => println!("❌ {}", e),
+            }
+        }
 
-// Function to select output file path
-fn select_output_path(default_name: Option<&str>) -> String {
-    println!("\n📁 Output File Options:");
-    println!("1. Save in current directory");
-    println!("2. Save in 'output' directory");
-    println!("3. Custom path");
-    
-    let choice = get_user_input("Select option (1-3): ");
-    
-    let default_filename = default_name.unwrap_or("output.png");
-    
-    match choice.as_str() {
-        "1" => {
-            let filename = get_user_input(&format!("Enter filename (default '{}'): ", default_filename));
-            if filename.is_empty() {
-                default_filename.to_string()
-            } else {
-                filename
+        "13" => {
+            match manage_templates(paths) {
+                Ok(()) => return Ok(Flow::Menu),
+                Err(e) if is_interrupt(&e) => return Err(e),
+                Err(e) => println!("❌ {}", e),
             }
         }
-        "2" => {
-            // Create output directory if it doesn't exist
-            let _ = std::fs::create_dir_all("output");
-            let filename = get_user_input(&format!("Enter filename (default '{}'): ", default_filename));
-            let filename = if filename.is_empty() { default_filename } else { &filename };
-            format!("output/{}", filename)
+
+        "14" => {
+            match download_font(paths) {
+                Ok(()) => {}
+                Err(e) if is_interrupt(&e) => return Err(e),
+                Err(e) => println!("❌ {}", e),
+            }
         }
-        "3" => {
-            get_user_input("Enter full output path: ")
+
+        "15" => {
+            match verify_certificates(paths, None, None) {
+                Ok(_) => {}
+                Err(e) if is_interrupt(&e) => return Err(e),
+                Err(e) => println!("❌ {}", e),
+            }
+        }
+
+        "16" => {
+            match generate_from_layout_interactive(paths, options, None, None, None) {
+                Ok(()) => {}
+                Err(e) if is_interrupt(&e) => return Err(e),
+                Err(e) => {
+                    println!("❌ {}", e);
+                    print_error_hint(&e);
+                }
+            }
+        }
+
+        "17" => {
+            match validate_layout_interactive(paths, options, None, None) {
+                Ok(_) => {}
+                Err(e) if is_interrupt(&e) => return Err(e),
+                Err(e) => println!("❌ {}", e),
+            }
         }
+
+        "18" => {
+            match check_print_readiness_interactive(paths, options) {
+                Ok(()) => {}
+                Err(e) if is_interrupt(&e) => return Err(e),
+                Err(e) => println!("❌ {}", e),
+            }
+        }
+
+        "19" => {
+            // Exit
+            say!("👋 Goodbye!");
+            return Ok(Flow::Exit);
+        }
+        
         _ => {
-            println!("Invalid option, using default");
-            default_filename.to_string()
+            say!("❌ Invalid option. Please select 1-19.");
         }
     }
+    
+    
+    Ok(Flow::Pause)
 }
 
-// Helper function to show path tips
-fn show_path_tips() {
-    println!("\n💡 File Organization Tips:");
-    println!("  • Put input images in current directory or Template/ folder");
-    println!("  • Output files will be saved in current directory or output/ folder");
-    println!("  • CSV files should be in excelcsvs/ directory");
-    println!("  • Template files should be in Template/ directory");
-    println!("  • Font files should be in assets/ directory");
+// Pull --template-dir/--fonts-dir/--csv-dir/--certificates-dir/--output-dir/--config <path>
+// out of the arguments, leaving the rest for the mode flags below
+// Arguments stay OsStrings so non-UTF-8 paths reach the filesystem intact
+fn split_path_flags(args: Vec<OsString>) -> Result<(Vec<OsString>, PathOverrides, Option<PathBuf>)> {
+    let mut rest = Vec::new();
+    let mut overrides = PathOverrides::default();
+    let mut config_file = None;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let slot = match arg.to_str().unwrap_or_default() {
+            "--template-dir" => &mut overrides.templates,
+            "--fonts-dir" => &mut overrides.fonts,
+            "--csv-dir" => &mut overrides.csvs,
+            "--certificates-dir" => &mut overrides.certificates,
+            "--output-dir" => &mut overrides.output,
+            "--config" => &mut config_file,
+            _ => {
+                rest.push(arg);
+                continue;
+            }
+        };
+        let value = args.next().ok_or_else(|| anyhow::anyhow!("{} needs a path", arg.to_string_lossy()))?;
+        *slot = Some(PathBuf::from(value));
+    }
+    Ok((rest, overrides, config_file))
 }
 
-fn show_menu() {
-    println!("\n🎯 === Certificate Maker ===");
-    println!("1. Add text to single image (interactive)");
-    println!("2. Generate certificates from CSV files in 'excelcsvs' directory");
-    println!("3. Analyze PNG file");
-    println!("4. Create sample CSV file");
-    println!("5. Debug CSV file");
-    println!("6. Debug template file");
-    println!("7. Show file organization tips");
-    println!("8. Exit");
+// serve [--bind <host:port>] [--workers <n>]: load every template and font, then answer requests
+#[cfg(feature = "server")]
+fn run_server(paths: &AppPaths, args: &[OsString]) -> Result<()> {
+    use certificate_maker::server::{Renderer, ServerOptions, serve};
+
+    let mut options = ServerOptions::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = args.next().ok_or_else(|| anyhow::anyhow!("{} needs a value", arg.to_string_lossy()))?.to_string_lossy();
+        match arg.to_str().unwrap_or_default() {
+            "--bind" => options.address = value.to_string(),
+            "--workers" => {
+                options.workers = value.parse().ok().filter(|&workers| workers > 0)
+                    .ok_or_else(|| anyhow::anyhow!("--workers must be a positive whole number"))?;
+            }
+            other => anyhow::bail!("Unknown serve option '{}': use --bind <host:port> or --workers <n>", other),
+        }
+    }
+
+    say!("📦 Loading templates from {} and fonts from {}...", paths.templates.display(), paths.fonts.display());
+    let renderer = Renderer::load(paths)?;
+    say!("🖼️ Templates: {}", renderer.template_names().collect::<Vec<_>>().join(", "));
+    say!("🔤 Fonts: {}", renderer.font_names().count());
+    say!("🌐 Listening on http://{} with {} workers (POST /render, GET /templates); Ctrl+C stops",
+         options.address, options.workers);
+    serve(&renderer, &options, &|line| println!("{}", line))?;
+    Ok(())
 }
 
-fn main() -> Result<()> {
-    // Show current working directory at startup
-    if let Ok(current_dir) = std::env::current_dir() {
-        println!("📁 Starting in directory: {}", current_dir.display());
+// --delimiter accepts the character itself or a name, since a tab or semicolon is awkward in a shell
+fn parse_delimiter(value: &str) -> Result<u8> {
+    match value.to_lowercase().as_str() {
+        "comma" => Ok(b','),
+        "semicolon" => Ok(b';'),
+        "tab" | "\\t" => Ok(b'\t'),
+        "pipe" => Ok(b'|'),
+        _ if value.len() == 1 && value.is_ascii() => Ok(value.as_bytes()[0]),
+        _ => anyhow::bail!("--delimiter must be a single character or one of comma, semicolon, tab, pipe"),
     }
-    
-    loop {
-        show_menu();
-        let choice = get_user_input("\nSelect an option (1-8): ");
-        
-        match choice.as_str() {
-            "1" => {
-                // Single image text addition - UPDATED with menu selection
-                println!("\n📝 Single Image Text Addition");
-                
-                let input_file = match select_input_image() {
-                    Ok(file) => file,
-                    Err(e) => {
-                        println!("❌ {}", e);
-                        continue;
-                    }
-                };
-                
-                // Verify the input file exists
-                if !Path::new(&input_file).exists() {
-                    println!("❌ Selected file not found: {}", input_file);
-                    continue;
-                }
-                
-                // Generate default output name based on input
-                let input_stem = Path::new(&input_file)
-                    .file_stem()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or("output");
-                let default_output = format!("{}_with_text.png", input_stem);
-                
-                let output_file = select_output_path(Some(&default_output));
-                
-                let text = get_user_input("Enter text to add: ");
-                if text.is_empty() {
-                    println!("No text entered. Returning to menu...");
-                    continue;
-                }
-                //DO analysis 
-                // Analyze PNG file - UPDATED with menu selection
-                println!("\n📊 PNG File Analysis");
-                
-                let file_path = match select_input_image() {
-                    Ok(file) => file,
-                    Err(e) => {
-                        println!("❌ {}", e);
-                        continue;
-                    }
+}
+
+// Pull --overwrite/--rename/--skip-existing, --name-column <header>, --delimiter <char>,
+// --encoding <label>, --duplicates <policy>, --name-case <case>, --typography <list>, --case-filenames,
+// --rows <range>/--head <n>/--sample <n> [--seed <s>], --email/--dry-run-email, --dry-run,
+// --deterministic, --no-log, --verbose, --allow-bleed, --stage-locally, --ignore-exif-orientation, --embolden <px>, --slant <deg>, --font <name>, --group-by <column>,
+// --compression <level>, --bit-depth <16|8>, --format <png|pdf>, --preview [<px>], --normalize <size>,
+// --fingerprint [visible|hidden|off],
+// --memory-budget <size>, --plan-out <file>, --execute-plan <file> [--shard <i/n>], --paper <size>,
+// --print-dpi <dpi>, --max-text-height <percent>, --min-text-points <pt>, --min-text-pixels <px>,
+// --strict-font-size, --draft and the --watermark, --impose
+// and --barcode flags out of the arguments;
+// batches skip files they didn't write themselves unless told otherwise
+fn split_batch_flags(args: Vec<OsString>) -> Result<(Vec<OsString>, BatchOptions)> {
+    let mut rest = Vec::new();
+    let mut policy = None;
+    let mut name_column = None;
+    let mut group_by = None;
+    let mut compression = None;
+    let mut depth = None;
+    let mut format = None;
+    let mut preview = None;
+    let mut normalize = None;
+    let mut fingerprint = None;
+    let mut draft = false;
+    let mut watermark_text = None;
+    let mut watermark_style = WatermarkStyle::draft();
+    let mut grid = None;
+    // The grid comes from --impose; the rest is adjusted by the flags that go with it
+    let unimposed = Imposition::new(SheetSize::default(), Grid { columns: 1, rows: 1 });
+    let mut imposition = unimposed.clone();
+    let mut memory_budget = None;
+    let mut plan_out = None;
+    let mut execute_plan = None;
+    let mut shard = None;
+    let mut paper = None;
+    let mut print_dpi = None;
+    let mut font_size_limits = FontSizeLimits::default();
+    let mut strict_font_size = false;
+    let mut csv_format = CsvFormat::default();
+    let mut duplicates = None;
+    let mut name_case = None;
+    let mut typography = None;
+    let mut case_filenames = false;
+    let mut selections = Vec::new();
+    let mut seed = None;
+    let mut email_modes = Vec::new();
+    let mut dry_run = false;
+    let mut deterministic = false;
+    let mut no_log = false;
+    let mut verbose = false;
+    let mut allow_bleed = false;
+    let mut stage_locally = false;
+    let mut ignore_orientation = false;
+    let mut embolden = None;
+    let mut slant = None;
+    let mut font = None;
+    let mut barcode = None;
+    let mut barcode_at = None;
+    let mut barcode_style = BarcodeStyle::default();
+
+    let mut args = args.into_iter().peekable();
+    while let Some(arg) = args.next() {
+        let flag = match arg.to_str().unwrap_or_default() {
+            "--name-column" => {
+                let header = args.next().ok_or_else(|| anyhow::anyhow!("--name-column needs a column header"))?;
+                let header = header.into_string().map_err(|_| anyhow::anyhow!("--name-column must be valid UTF-8"))?;
+                name_column = Some(header);
+                continue;
+            }
+            "--group-by" => {
+                let column = args.next().ok_or_else(|| anyhow::anyhow!("--group-by needs a column header"))?;
+                let column = column.into_string().map_err(|_| anyhow::anyhow!("--group-by must be valid UTF-8"))?;
+                group_by = Some(column);
+                continue;
+            }
+            "--compression" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("--compression needs a level: fast, balanced or small"))?;
+                let value = value.to_string_lossy();
+                compression = Some(PngCompression::from_name(&value)
+                    .ok_or_else(|| anyhow::anyhow!("Unsupported --compression '{}': use fast, balanced or small", value))?);
+                continue;
+            }
+            "--bit-depth" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("--bit-depth needs 16 or 8"))?;
+                let value = value.to_string_lossy();
+                depth = Some(DepthPolicy::from_name(&value)
+                    .ok_or_else(|| anyhow::anyhow!("Unsupported --bit-depth '{}': use 16 or 8", value))?);
+                continue;
+            }
+            "--format" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("--format needs png or pdf"))?;
+                let value = value.to_string_lossy();
+                format = Some(CertificateFormat::from_name(&value)
+                    .ok_or_else(|| anyhow::anyhow!("Unsupported --format '{}': use png or pdf", value))?);
+                continue;
+            }
+            "--preview" => {
+                // The size is optional, so only a number after the flag is taken as one
+                let size = args.next_if(|value| value.to_str().is_some_and(|value| value.parse::<i64>().is_ok()));
+                let size = match size {
+                    Some(value) => value.to_string_lossy().parse::<u32>().ok().filter(|&size| size > 0)
+                        .ok_or_else(|| anyhow::anyhow!("--preview '{}' isn't a size; give the longer side in pixels, like 600", value.to_string_lossy()))?,
+                    None => DEFAULT_PREVIEW_SIZE,
                 };
-                
-                if !Path::new(&file_path).exists() {
-                    println!("❌ Selected file not found: {}", file_path);
-                    continue;
-                }
-                
-                match analyze_png_file(&file_path) {
-                    Ok(analysis) => print_analysis(&analysis),
-                    Err(e) => {
-                        println!("❌ Error analyzing file: {}", e);
-                        show_path_tips();
-                    }
-                }
-                
-                //end analysis
-                let x_input = get_user_input("Enter X position (or press Enter for default 50): ");
-                let x_pos = if x_input.is_empty() { 50 } else { x_input.parse().unwrap_or(50) };
-                
-                let y_input = get_user_input("Enter Y position (or press Enter for default 50): ");
-                let y_pos = if y_input.is_empty() { 50 } else { y_input.parse().unwrap_or(50) };
-                
-                match add_text_to_png_interactive(&input_file, &output_file, &text, x_pos, y_pos) {
-                    Ok(()) => {
-                        println!("✅ Text added successfully!");
-                        println!("📁 Output saved to: {}", output_file);
-                    }
-                    Err(e) => {
-                        println!("❌ Error: {}", e);
-                        show_path_tips();
-                    }
-                }
+                preview = Some(size);
+                continue;
             }
-            
-            "2" => {
-                // Batch certificate generation
-                println!("\n🎓 Certificate Generator");
-                match generate_certificates_interactive() {
-                    Ok(()) => println!("🎉 Batch certificate generation completed!"),
-                    Err(e) => {
-                        println!("❌ Error: {}", e);
-                        show_path_tips();
-                    }
-                }
+            "--normalize" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("--normalize needs a size, like 2480x3508 or 2480x3508:fit"))?;
+                let value = value.to_string_lossy();
+                normalize = Some(Normalization::parse(&value)
+                    .ok_or_else(|| anyhow::anyhow!("--normalize '{}' isn't a size; use <width>x<height>, optionally with :stretch, :fit, :fill or :pad", value))?);
+                continue;
             }
-            
-            "3" => {
-                // Analyze PNG file - UPDATED with menu selection
-                println!("\n📊 PNG File Analysis");
-                
-                let file_path = match select_input_image() {
-                    Ok(file) => file,
-                    Err(e) => {
-                        println!("❌ {}", e);
-                        continue;
-                    }
+            "--fingerprint" => {
+                // The mode is optional, so only a mode's name after the flag is taken as one
+                let mode = args.next_if(|value| value.to_str().is_some_and(|value| FingerprintMode::from_name(value).is_some()));
+                fingerprint = Some(mode.and_then(|value| FingerprintMode::from_name(&value.to_string_lossy())).unwrap_or(FingerprintMode::Visible));
+                continue;
+            }
+            "--draft" => {
+                draft = true;
+                continue;
+            }
+            "--watermark" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("--watermark needs the text to draw, like \"SAMPLE\""))?;
+                watermark_text = Some(value.into_string().map_err(|_| anyhow::anyhow!("--watermark must be valid UTF-8"))?);
+                continue;
+            }
+            "--watermark-angle" | "--watermark-size" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("{} needs a number", arg.to_string_lossy()))?;
+                let value = value.to_string_lossy().parse::<f32>()
+                    .map_err(|_| anyhow::anyhow!("{} must be a number", arg.to_string_lossy()))?;
+                if arg == "--watermark-angle" { watermark_style.angle = value } else { watermark_style.size = Some(value) }
+                continue;
+            }
+            "--watermark-opacity" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("--watermark-opacity needs a percentage, like 25%"))?;
+                watermark_style.opacity = WatermarkStyle::parse_opacity(&value.to_string_lossy())
+                    .ok_or_else(|| anyhow::anyhow!("--watermark-opacity '{}' isn't an opacity; use a percentage like 25% or a fraction like 0.25", value.to_string_lossy()))?;
+                continue;
+            }
+            "--watermark-tile" => {
+                // The spacing is optional, so only a number after the flag is taken as one
+                let spacing = args.next_if(|value| value.to_str().is_some_and(|value| value.parse::<f64>().is_ok()));
+                let spacing = match spacing {
+                    Some(value) => value.to_string_lossy().parse::<f32>().ok().filter(|spacing| *spacing >= 0.0)
+                        .ok_or_else(|| anyhow::anyhow!("--watermark-tile '{}' isn't a spacing; give the gap between tiles in pixels", value.to_string_lossy()))?,
+                    None => DEFAULT_TILE_SPACING,
                 };
-                
-                if !Path::new(&file_path).exists() {
-                    println!("❌ Selected file not found: {}", file_path);
-                    continue;
-                }
-                
-                match analyze_png_file(&file_path) {
-                    Ok(analysis) => print_analysis(&analysis),
-                    Err(e) => {
-                        println!("❌ Error analyzing file: {}", e);
-                        show_path_tips();
-                    }
-                }
+                watermark_style.layout = WatermarkLayout::Tiled { spacing };
+                continue;
             }
-            
-            "4" => {
-                // Create sample CSV
-                println!("\n📄 Create Sample CSV");
-                
-                let filename = get_user_input("Enter filename for sample CSV (default 'excelcsvs/sample_names.csv'): ");
-                let filename = if filename.is_empty() { "excelcsvs/sample_names.csv" } else { &filename };
-                
-                match create_sample_csv(filename) {
-                    Ok(()) => {
-                        println!("✅ Sample CSV created successfully!");
-                        if let Ok(current_dir) = std::env::current_dir() {
-                            println!("📁 Full path: {}", current_dir.join(filename).display());
-                        }
-                    }
-                    Err(e) => println!("❌ Error creating sample CSV: {}", e),
-                }
+            "--watermark-color" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("--watermark-color needs a hex color, like #808080"))?;
+                watermark_style.color = check_color(&value.to_string_lossy())?;
+                continue;
             }
-            
-            "5" => {
-                // Debug CSV file
-                println!("\n🔍 CSV File Debugger");
-                
-                let csv_file = match select_csv_file() {
-                    Ok(file) => file,
-                    Err(e) => {
-                        println!("❌ {}", e);
-                        continue;
-                    }
+            "--impose" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("--impose needs a grid of columns x rows, like 1x2"))?;
+                grid = Some(Grid::parse(&value.to_string_lossy())
+                    .ok_or_else(|| anyhow::anyhow!("--impose '{}' isn't a grid; use <columns>x<rows>, like 1x2 or 2x2", value.to_string_lossy()))?);
+                continue;
+            }
+            "--sheet" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("--sheet needs a size, like A4, A4-landscape@300 or 2480x3508"))?;
+                imposition.sheet = SheetSize::parse(&value.to_string_lossy())
+                    .ok_or_else(|| anyhow::anyhow!("--sheet '{}' isn't a size; use a paper size ({}), optionally -landscape, or <width>x<height> pixels, either with @<dpi>",
+                                                   value.to_string_lossy(), PAPER_SIZES.map(|(name, _, _)| name).join(", ")))?;
+                continue;
+            }
+            "--gutter" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("--gutter needs a length, like 5mm or 40"))?;
+                let value = value.to_string_lossy();
+                imposition.gutter = match value.trim().parse::<f32>() {
+                    Ok(pixels) => Length::Pixels(pixels),
+                    Err(_) => Length::Physical(value.to_string()),
                 };
-                
-                match debug_csv_file(&csv_file) {
-                    Ok(()) => println!("✅ CSV debug complete"),
-                    Err(e) => println!("❌ Debug error: {}", e),
-                }
+                continue;
             }
-            
-            "6" => {
-                // Debug template file
-                println!("\n🔍 Template File Debugger");
-                
-                let template_file = match select_template_file() {
-                    Ok(file) => file,
-                    Err(e) => {
-                        println!("❌ {}", e);
-                        continue;
-                    }
+            "--crop-marks" => {
+                imposition.crop_marks = true;
+                continue;
+            }
+            "--sheet-format" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("--sheet-format needs png or pdf"))?;
+                let value = value.to_string_lossy();
+                imposition.format = CertificateFormat::from_name(&value)
+                    .ok_or_else(|| anyhow::anyhow!("Unsupported --sheet-format '{}': use png or pdf", value))?;
+                continue;
+            }
+            "--memory-budget" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("--memory-budget needs a size, like 3G or 1500M"))?;
+                let value = value.to_string_lossy();
+                memory_budget = Some(parse_size(&value)
+                    .ok_or_else(|| anyhow::anyhow!("--memory-budget '{}' isn't a size; use bytes or a number with K, M or G, like 3G", value))?);
+                continue;
+            }
+            "--delimiter" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("--delimiter needs a character"))?;
+                csv_format.delimiter = Some(parse_delimiter(&value.to_string_lossy())?);
+                continue;
+            }
+            "--duplicates" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("--duplicates needs a policy"))?;
+                duplicates = Some(match value.to_str().unwrap_or_default() {
+                    "fail" => DuplicatePolicy::Fail,
+                    "keep-all" => DuplicatePolicy::KeepAll,
+                    "dedupe" => DuplicatePolicy::Dedupe,
+                    other => anyhow::bail!("Unsupported --duplicates '{}': use fail, keep-all or dedupe", other),
+                });
+                continue;
+            }
+            "--name-case" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("--name-case needs a case"))?;
+                name_case = Some(match value.to_str().unwrap_or_default() {
+                    "preserve" => NameCase::Preserve,
+                    "title" => NameCase::Title,
+                    "upper" => NameCase::Upper,
+                    "lower" => NameCase::Lower,
+                    other => anyhow::bail!("Unsupported --name-case '{}': use preserve, title, upper or lower", other),
+                });
+                continue;
+            }
+            "--rows" | "--head" | "--sample" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("{} needs a value", arg.to_string_lossy()))?;
+                let value = value.to_string_lossy();
+                let selection: RowSelection = match arg.to_str().unwrap_or_default() {
+                    "--rows" => value.parse()?,
+                    "--head" => format!("head {}", value).parse()?,
+                    _ => format!("sample {} seed 0", value).parse()?,
                 };
-                
-                match debug_template_file(&template_file) {
-                    Ok(()) => println!("✅ Template debug complete"),
-                    Err(e) => println!("❌ Debug error: {}", e),
-                }
+                selections.push(selection);
+                continue;
             }
-            
-            "7" => {
-                // Show file organization tips
-                show_path_tips();
+            "--seed" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("--seed needs a number"))?;
+                seed = Some(value.to_string_lossy().parse::<u64>().map_err(|_| anyhow::anyhow!("--seed must be a whole number"))?);
+                continue;
             }
-            
-            "8" => {
-                // Exit
-                println!("👋 Goodbye!");
-                break;
+            "--font" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("--font needs a font file or a family and style, like \"DejaVu Sans Bold\""))?;
+                font = Some(value.to_string_lossy().into_owned());
+                continue;
             }
-            
+            "--barcode" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("--barcode needs the data to encode, like \"{{Ticket}}\""))?;
+                barcode = Some(value.into_string().map_err(|_| anyhow::anyhow!("--barcode must be valid UTF-8"))?);
+                continue;
+            }
+            "--barcode-at" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("--barcode-at needs the barcode's center as x,y"))?;
+                let value = value.to_string_lossy();
+                let position = value.split_once(',')
+                    .and_then(|(x, y)| Some((x.trim().parse::<i32>().ok()?, y.trim().parse::<i32>().ok()?)));
+                barcode_at = Some(position.ok_or_else(|| anyhow::anyhow!("--barcode-at must be two whole numbers, like 960,1200"))?);
+                continue;
+            }
+            "--barcode-module" | "--barcode-height" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("{} needs a size in pixels", arg.to_string_lossy()))?;
+                let value = value.to_string_lossy().parse::<u32>().ok().filter(|value| *value > 0)
+                    .ok_or_else(|| anyhow::anyhow!("{} must be a positive whole number of pixels", arg.to_string_lossy()))?;
+                if arg == "--barcode-module" { barcode_style.module_width = value } else { barcode_style.height = value }
+                continue;
+            }
+            "--barcode-text" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("--barcode-text needs a font size in pixels"))?;
+                barcode_style.text_size = Some(value.to_string_lossy().parse::<f32>()
+                    .map_err(|_| anyhow::anyhow!("--barcode-text must be a number"))?);
+                continue;
+            }
+            "--embolden" | "--slant" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("{} needs a number", arg.to_string_lossy()))?;
+                let value = value.to_string_lossy().parse::<f32>()
+                    .map_err(|_| anyhow::anyhow!("{} must be a number", arg.to_string_lossy()))?;
+                if arg == "--embolden" { embolden = Some(value) } else { slant = Some(value) }
+                continue;
+            }
+            "--typography" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("--typography needs a list such as quotes,dashes or all"))?;
+                typography = Some(value.to_string_lossy().parse()?);
+                continue;
+            }
+            "--plan-out" | "--execute-plan" => {
+                let path = args.next().ok_or_else(|| anyhow::anyhow!("{} needs a plan file", arg.to_string_lossy()))?;
+                if arg == "--plan-out" { plan_out = Some(PathBuf::from(path)) } else { execute_plan = Some(PathBuf::from(path)) }
+                continue;
+            }
+            "--paper" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("--paper needs a paper size, like A4"))?;
+                let names: Vec<&str> = PAPER_SIZES.iter().map(|(name, _, _)| *name).collect();
+                let target = PrintTarget::new(&value.to_string_lossy(), PrintTarget::default().dpi)
+                    .ok_or_else(|| anyhow::anyhow!("--paper '{}' isn't a paper size; use one of {}", value.to_string_lossy(), names.join(", ")))?;
+                paper = Some(target.paper);
+                continue;
+            }
+            "--print-dpi" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("--print-dpi needs a resolution, like 300"))?;
+                print_dpi = Some(value.to_string_lossy().parse::<f32>().ok().filter(|dpi| dpi.is_finite() && *dpi > 0.0)
+                    .ok_or_else(|| anyhow::anyhow!("--print-dpi '{}' isn't a resolution; give dots per inch, like 300", value.to_string_lossy()))?);
+                continue;
+            }
+            "--shard" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("--shard needs the shard and the number of shards, like 2/4"))?;
+                shard = Some(value.to_string_lossy().parse::<Shard>()?);
+                continue;
+            }
+            "--verbose" => {
+                verbose = true;
+                continue;
+            }
+            "--allow-bleed" => {
+                allow_bleed = true;
+                continue;
+            }
+            "--max-text-height" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("--max-text-height needs a share of the template's height, like 40%"))?;
+                font_size_limits.max_height_fraction = FontSizeLimits::parse_height_fraction(&value.to_string_lossy())
+                    .ok_or_else(|| anyhow::anyhow!("--max-text-height '{}' isn't a share of the height; use a percentage like 40% or a fraction like 0.4", value.to_string_lossy()))?;
+                continue;
+            }
+            "--min-text-points" | "--min-text-pixels" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("{} needs a size, like {}", arg.to_string_lossy(), if arg == "--min-text-points" { "6" } else { "10" }))?;
+                let size = value.to_string_lossy().parse::<f32>().ok().filter(|size| size.is_finite() && *size >= 0.0)
+                    .ok_or_else(|| anyhow::anyhow!("{} '{}' isn't a size; give a number of zero or more", arg.to_string_lossy(), value.to_string_lossy()))?;
+                if arg == "--min-text-points" { font_size_limits.min_points = size } else { font_size_limits.min_pixels = size }
+                continue;
+            }
+            "--strict-font-size" => {
+                strict_font_size = true;
+                continue;
+            }
+            "--stage-locally" => {
+                stage_locally = true;
+                continue;
+            }
+            "--ignore-exif-orientation" => {
+                ignore_orientation = true;
+                continue;
+            }
+            "--case-filenames" => {
+                case_filenames = true;
+                continue;
+            }
+            "--dry-run" => {
+                dry_run = true;
+                continue;
+            }
+            "--deterministic" => {
+                deterministic = true;
+                continue;
+            }
+            "--no-log" => {
+                no_log = true;
+                continue;
+            }
+            "--email" | "--dry-run-email" => {
+                email_modes.push(if arg == "--email" { EmailMode::Send } else { EmailMode::DryRun });
+                continue;
+            }
+            "--encoding" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("--encoding needs an encoding name"))?;
+                let label = value.to_string_lossy();
+                csv_format.encoding = Some(CsvEncoding::from_label(&label).ok_or_else(|| {
+                    anyhow::anyhow!("Unsupported --encoding '{}': use utf-8, utf-16le, utf-16be or windows-1252", label)
+                })?);
+                continue;
+            }
+            "--overwrite" => OverwritePolicy::Overwrite,
+            "--rename" => OverwritePolicy::Rename,
+            "--skip-existing" => OverwritePolicy::SkipExisting,
             _ => {
-                println!("❌ Invalid option. Please select 1-8.");
+                rest.push(arg);
+                continue;
+            }
+        };
+        if policy.is_some_and(|policy| policy != flag) {
+            anyhow::bail!("Use only one of --overwrite, --rename and --skip-existing");
+        }
+        policy = Some(flag);
+    }
+    if selections.len() > 1 {
+        anyhow::bail!("Use only one of --rows, --head and --sample");
+    }
+    if deterministic && seed.is_none() && matches!(selections.last(), Some(RowSelection::Sample { .. })) {
+        anyhow::bail!("--deterministic needs a --seed for --sample, or every run would pick different rows");
+    }
+    // Without --seed the sample is drawn once here, so watch mode keeps picking the same rows
+    let selection = match selections.pop() {
+        Some(RowSelection::Sample { count, .. }) => Some(RowSelection::Sample { count, seed: seed.unwrap_or_else(random_seed) }),
+        _ if seed.is_some() => anyhow::bail!("--seed only applies to --sample"),
+        other => other,
+    };
+    if email_modes.len() > 1 {
+        anyhow::bail!("Use only one of --email and --dry-run-email");
+    }
+    if dry_run && !email_modes.is_empty() {
+        anyhow::bail!("A --dry-run writes no certificates, so there is nothing to email; drop the email flag");
+    }
+    if deterministic && email_modes.contains(&EmailMode::Send) {
+        anyhow::bail!("--deterministic can't be combined with --email: send times are recorded in manifest.json; email in a separate run");
+    }
+    if plan_out.is_some() && execute_plan.is_some() {
+        anyhow::bail!("Use only one of --plan-out and --execute-plan: make the plan first, then execute it");
+    }
+    if shard.is_some() && execute_plan.is_none() {
+        anyhow::bail!("--shard splits a plan between machines; use it with --execute-plan <plan.json>");
+    }
+    if plan_out.is_some() && (dry_run || !email_modes.is_empty()) {
+        anyhow::bail!("--plan-out writes no certificates; drop --dry-run and the email flag");
+    }
+    if plan_out.is_some() && (draft || watermark_text.is_some() || grid.is_some()) {
+        anyhow::bail!("Watermarks and print sheets aren't part of a plan; make the plan without --draft, --watermark and --impose");
+    }
+    if !email_modes.is_empty() && !cfg!(feature = "email") {
+        anyhow::bail!("This build can't send email; rebuild with `cargo run --features email`");
+    }
+
+    let synthetic = (embolden.is_some() || slant.is_some()).then(|| SyntheticStyle {
+        embolden: embolden.unwrap_or_default(),
+        slant: slant.unwrap_or_default(),
+    });
+    if let Some(synthetic) = &synthetic {
+        synthetic.validate()?;
+    }
+
+    let graphics = match (barcode, barcode_at) {
+        (Some(data), Some((x, y))) => vec![GraphicField { kind: GraphicKind::Code128(barcode_style), data, x, y, anchor: Anchor::Center }],
+        (Some(_), None) => anyhow::bail!("--barcode needs --barcode-at <x,y> to say where its center goes"),
+        (None, _) if barcode_at.is_some() || barcode_style != BarcodeStyle::default() => {
+            anyhow::bail!("--barcode-at, --barcode-module, --barcode-height and --barcode-text only apply to --barcode")
+        }
+        (None, _) => Vec::new(),
+    };
+    for graphic in &graphics {
+        graphic.validate()?;
+    }
+
+    let watermark = match (draft, watermark_text) {
+        (true, Some(_)) => anyhow::bail!("Use only one of --draft and --watermark; --draft is --watermark DRAFT"),
+        (true, None) => Some(watermark_style),
+        (false, Some(text)) => Some(WatermarkStyle { text, ..watermark_style }),
+        (false, None) if watermark_style != WatermarkStyle::draft() => {
+            anyhow::bail!("--watermark-angle, --watermark-opacity, --watermark-size, --watermark-tile and --watermark-color only apply to --draft or --watermark")
+        }
+        (false, None) => None,
+    };
+    if let Some(watermark) = &watermark {
+        watermark.validate()?;
+    }
+
+    let imposition = match grid {
+        Some(grid) => Some(Imposition { grid, ..imposition }),
+        None if imposition != unimposed => {
+            anyhow::bail!("--sheet, --gutter, --crop-marks and --sheet-format only apply to --impose")
+        }
+        None => None,
+    };
+    if let Some(imposition) = &imposition {
+        imposition.validate()?;
+    }
+
+    Ok((rest, BatchOptions {
+        overwrite: policy.unwrap_or_default(),
+        name_column,
+        csv_format,
+        duplicates,
+        name_case,
+        typography,
+        case_filenames,
+        selection,
+        email: email_modes.pop(),
+        dry_run,
+        deterministic,
+        no_log,
+        verbose,
+        allow_bleed,
+        font_size_limits,
+        strict_font_size,
+        stage_locally,
+        ignore_orientation,
+        synthetic,
+        font,
+        graphics,
+        group_by,
+        compression,
+        depth,
+        format,
+        preview,
+        normalize,
+        fingerprint,
+        watermark,
+        imposition,
+        memory_budget,
+        plan_out,
+        execute_plan,
+        shard,
+        // Either flag alone takes the other from the default, A4 at 300 DPI
+        print: (paper.is_some() || print_dpi.is_some()).then(|| {
+            let default = PrintTarget::default();
+            PrintTarget::new(paper.unwrap_or(default.paper), print_dpi.unwrap_or(default.dpi)).unwrap_or(default)
+        }),
+    }))
+}
+
+const STAMP_USAGE: &str = "use stamp <input dir> <output dir> --text <text> [--at <x>,<y>] [--anchor <anchor>] [--size <size>] \
+    [--color <color>] [--max-width <width>] [--effect <name>] [--logo <image> [--logo-at <x>,<y>] [--logo-anchor <anchor>] \
+    [--logo-width <width>]] [--keep-format]";
+
+// An anchor by the name layout files use
+fn parse_anchor(flag: &str, value: &str) -> Result<Anchor> {
+    Anchor::ALL.into_iter().find(|anchor| anchor.name().eq_ignore_ascii_case(value.trim())).ok_or_else(|| {
+        let names: Vec<&str> = Anchor::ALL.iter().map(|anchor| anchor.name()).collect();
+        anyhow::anyhow!("Unsupported {} '{}': use {}", flag, value, names.join(", "))
+    })
+}
+
+// "<x>,<y>" of pixels, percentages such as "50%,90%" or lengths such as "20mm,15mm"
+fn parse_point(flag: &str, value: &str) -> Result<(Coordinate, Coordinate)> {
+    let (x, y) = value.split_once(',')
+        .ok_or_else(|| anyhow::anyhow!("{} needs <x>,<y>, such as 50%,90% or 400,300; got '{}'", flag, value))?;
+    Ok((Coordinate::from(x), Coordinate::from(y)))
+}
+
+// The directories, stamp and --keep-format of: stamp <input dir> <output dir> --text <text> ...;
+// the font is left for the --font flag or the default
+fn split_stamp_args(args: &[OsString]) -> Result<(PathBuf, PathBuf, StampStyle, bool)> {
+    let mut dirs = Vec::new();
+    let mut style = StampStyle::new("", PathBuf::new());
+    let mut logo: Option<StampLogo> = None;
+    let mut keep_format = false;
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        let flag = arg.to_string_lossy();
+        if flag == "--keep-format" {
+            keep_format = true;
+            continue;
+        }
+        if !flag.starts_with("--") {
+            dirs.push(PathBuf::from(arg));
+            continue;
+        }
+        let value = rest.next()
+            .ok_or_else(|| anyhow::anyhow!("{} needs a value: {}", flag, STAMP_USAGE))?
+            .to_string_lossy()
+            .into_owned();
+        match flag.as_ref() {
+            "--text" => style.text = value,
+            "--at" => (style.x, style.y) = parse_point("--at", &value)?,
+            "--anchor" => style.anchor = parse_anchor("--anchor", &value)?,
+            "--size" => style.size = Coordinate::from(value.as_str()),
+            "--color" => {
+                check_color(&value)?;
+                style.color = value;
+            }
+            "--max-width" => style.max_width = Some(Coordinate::from(value.as_str())),
+            "--effect" => style.effect = Some(find_effect(&value)?),
+            "--logo" => {
+                let (x, y) = (Coordinate::from("50%"), Coordinate::from("50%"));
+                logo = Some(StampLogo { path: PathBuf::from(value), x, y, anchor: Anchor::Center, width: None });
+            }
+            "--logo-at" | "--logo-anchor" | "--logo-width" => {
+                let logo = logo.as_mut().ok_or_else(|| anyhow::anyhow!("{} only applies after --logo <image>", flag))?;
+                match flag.as_ref() {
+                    "--logo-at" => (logo.x, logo.y) = parse_point("--logo-at", &value)?,
+                    "--logo-anchor" => logo.anchor = parse_anchor("--logo-anchor", &value)?,
+                    _ => logo.width = Some(Coordinate::from(value.as_str())),
+                }
             }
+            _ => anyhow::bail!("Unexpected stamp argument '{}': {}", flag, STAMP_USAGE),
+        }
+    }
+    style.logo = logo;
+    let [input_dir, output_dir]: [PathBuf; 2] = dirs.try_into()
+        .map_err(|_| anyhow::anyhow!("stamp needs an input and an output directory: {}", STAMP_USAGE))?;
+    if style.text.is_empty() && style.logo.is_none() {
+        anyhow::bail!("stamp needs --text or --logo: {}", STAMP_USAGE);
+    }
+    Ok((input_dir, output_dir, style, keep_format))
+}
+
+fn main() -> Result<()> {
+    // Non-interactive entry point for scripts: --analyze-json <image>
+    let (args, path_flags, config_file) = split_path_flags(std::env::args_os().collect())?;
+    let paths = AppPaths::resolve(&path_flags, config_file.as_deref())?;
+    // Before anything is said, so every prompt is in one language; without a catalog, English
+    if let Some(catalog) = i18n::requested_language(&paths.config).as_deref().and_then(Catalog::builtin) {
+        i18n::install(catalog);
+    }
+    // Before the batch flags, so --watermark-color can name a palette color
+    if let Some(palette) = Palette::load(paths.palette_file())? {
+        palette::install(palette);
+    }
+    let (args, batch_options) = split_batch_flags(args)?;
+    // Before anything loads a template, so every size and decode agrees
+    orientation::ignore_exif(batch_options.ignore_orientation);
+    if args.len() == 3 && args[1] == "--analyze-json" {
+        println!("{}", analyze_image_file_json(&args[2])?);
+        return Ok(());
+    }
+    // Render a saved plan exactly as written: --execute-plan <plan.json>
+    if let Some(plan) = &batch_options.execute_plan {
+        // A non-zero exit lets scripts notice rows that failed
+        if !execute_plan_interactive(&paths, &batch_options, plan)? {
+            std::process::exit(1);
         }
+        return Ok(());
+    }
+    // Combine the manifests of a sharded plan: merge-manifests [<output dir>] [<shard manifest>...]
+    if args.len() >= 2 && args[1] == "merge-manifests" {
+        let mut rest: Vec<PathBuf> = args[2..].iter().map(PathBuf::from).collect();
+        let output_dir = match rest.first() {
+            Some(first) if first.is_dir() => Some(rest.remove(0)),
+            _ => None,
+        };
+        // A non-zero exit lets scripts notice rows that failed in their shard
+        if !merge_manifests_interactive(&paths, output_dir, rest)? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    // Quick smoke test without the menu: --demo [text]
+    if args.len() >= 2 && args[1] == "--demo" {
+        let text = args.get(2).map(|text| text.to_string_lossy()).unwrap_or("Alice Johnson".into());
+        return run_demo(&paths, &text);
+    }
+    // Certificates per second on your own template and font: perf-test [<template>] [--count <n>]
+    if args.len() >= 2 && args[1] == "perf-test" {
+        let usage = "use perf-test [<template>] [--count <n>]";
+        let mut template = None;
+        let mut count = 50;
+        let mut rest = args[2..].iter();
+        while let Some(arg) = rest.next() {
+            if arg == "--count" {
+                let value = rest.next().ok_or_else(|| anyhow::anyhow!("--count needs a number of certificates: {}", usage))?;
+                count = value.to_string_lossy().parse().ok().filter(|&count| count > 0)
+                    .ok_or_else(|| anyhow::anyhow!("--count needs a number of certificates above 0, got '{}'", value.to_string_lossy()))?;
+            } else if template.is_none() {
+                template = Some(PathBuf::from(arg));
+            } else {
+                anyhow::bail!("Unexpected perf-test argument '{}': {}", arg.to_string_lossy(), usage);
+            }
+        }
+        return run_perf_test(&paths, &batch_options, template, count);
+    }
+    // On-demand rendering over HTTP: serve [--bind <host:port>] [--workers <n>] (needs the `server` feature)
+    if args.len() >= 2 && args[1] == "serve" {
+        #[cfg(feature = "server")]
+        return run_server(&paths, &args[2..]);
+        #[cfg(not(feature = "server"))]
+        anyhow::bail!("This build has no HTTP service; rebuild with `cargo run --features server -- serve`");
+    }
+    // Delete what the last batch wrote: undo [<output dir>] [--yes]
+    if args.len() >= 2 && args[1] == "undo" {
+        let assume_yes = args[2..].iter().any(|arg| arg == "--yes");
+        let mut dirs = args[2..].iter().filter(|arg| *arg != "--yes");
+        let output_dir = dirs.next().map(PathBuf::from);
+        if let Some(extra) = dirs.next() {
+            anyhow::bail!("Unexpected undo argument '{}': use undo [<output dir>] [--yes]", extra.to_string_lossy());
+        }
+        return undo_last_batch(&paths, output_dir, assume_yes);
+    }
+    // Find certificates whose names only differ in accents, case or spacing: audit [<dir>] [--fix]
+    if args.len() >= 2 && args[1] == "audit" {
+        let fix = args[2..].iter().any(|arg| arg == "--fix");
+        let mut dirs = args[2..].iter().filter(|arg| *arg != "--fix");
+        let dir = dirs.next().map(PathBuf::from);
+        if let Some(extra) = dirs.next() {
+            anyhow::bail!("Unexpected audit argument '{}': use audit [<dir>] [--fix]", extra.to_string_lossy());
+        }
+        return audit_certificates(&paths, dir, fix);
+    }
+    // The same text and logo on every image of a directory: stamp <input dir> <output dir> --text <text> ...
+    if args.len() >= 2 && args[1] == "stamp" {
+        let (input_dir, output_dir, style, keep_format) = split_stamp_args(&args[2..])?;
+        // A non-zero exit lets scripts notice images that failed
+        if !stamp_directory(&paths, &batch_options, style, input_dir, output_dir, keep_format)? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    // Check files against the manifest they were issued from: verify <file or dir> [--manifest <path>]
+    if args.len() >= 2 && args[1] == "verify" {
+        let mut target = None;
+        let mut manifest = None;
+        let mut rest = args[2..].iter();
+        while let Some(arg) = rest.next() {
+            if arg == "--manifest" {
+                let path = rest.next().ok_or_else(|| anyhow::anyhow!("--manifest needs a manifest.json path"))?;
+                manifest = Some(PathBuf::from(path));
+            } else if target.is_none() {
+                target = Some(PathBuf::from(arg));
+            } else {
+                anyhow::bail!("Unexpected verify argument '{}': use verify <file or dir> [--manifest <path>]", arg.to_string_lossy());
+            }
+        }
+        let target = target.ok_or_else(|| anyhow::anyhow!("verify needs a file or directory: verify <file or dir> [--manifest <path>]"))?;
+        // A non-zero exit lets scripts act on a failed check
+        if !verify_certificates(&paths, Some(target), manifest)? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    // A whole batch from a layout file: layout <layout file> <csv> [<output dir>]
+    if args.len() >= 2 && args[1] == "layout" {
+        let usage = "use layout <layout.toml or .json> <csv> [<output dir>]";
+        let (layout, list, output_dir) = match &args[2..] {
+            [layout, list] => (layout, list, None),
+            [layout, list, output_dir] => (layout, list, Some(PathBuf::from(output_dir))),
+            _ => anyhow::bail!("layout needs a layout file and a CSV: {}", usage),
+        };
+        let output_dir = output_dir.unwrap_or_else(|| paths.certificates.clone());
+        return generate_from_layout_interactive(&paths, &batch_options, Some(layout.into()), Some(list.into()), Some(output_dir));
+    }
+    // Check a layout file without generating anything: validate <layout file> [<csv>]
+    if args.len() >= 2 && args[1] == "validate" {
+        let (layout, list) = match &args[2..] {
+            [layout] => (layout, None),
+            [layout, list] => (layout, Some(PathBuf::from(list))),
+            _ => anyhow::bail!("validate needs a layout file: use validate <layout.toml or .json> [<csv>]"),
+        };
+        // A non-zero exit lets scripts and CI act on an invalid layout
+        if !validate_layout_interactive(&paths, &batch_options, Some(layout.into()), list)? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    // Full-screen pickers and progress: --tui (needs the `tui` feature)
+    if args.len() >= 2 && args[1] == "--tui" {
+        #[cfg(feature = "tui")]
+        return tui::run_tui(&paths, &batch_options);
+        #[cfg(not(feature = "tui"))]
+        anyhow::bail!("This build has no TUI; rebuild with `cargo run --features tui -- --tui`");
+    }
+    
+    // Show current working directory at startup
+    if let Ok(current_dir) = std::env::current_dir() {
+        say!("📁 Starting in directory: {}", current_dir.display());
+    }
+    
+    loop {
+        show_menu(&paths);
+        let Some(choice) = get_user_input(&tr!("\nSelect an option (1-19): "))? else {
+            say!("\n👋 Goodbye!");
+            break;
+        };
         
-        println!("\nPress Enter to continue...");
-        let _ = get_user_input("");
+        match run_menu_option(&choice, &paths, &batch_options) {
+            Ok(Flow::Pause) => {}
+            Ok(Flow::Menu) => continue,
+            Ok(Flow::Exit) => break,
+            Err(e) => match interrupt_of(&e) {
+                Some(Interrupt::Closed) => {
+                    say!("\n👋 Input closed, exiting.");
+                    break;
+                }
+                Some(_) => {
+                    say!("↩️ Cancelled, returning to the menu.");
+                    continue;
+                }
+                None => say!("❌ Error: {}", e),
+            },
+        }
+        
+        say!("\nPress Enter to continue...");
+        if get_user_input("")?.is_none() {
+            break;
+        }
     }
     
     Ok(())
@@ -0,0 +1,400 @@
+// src/manifest.rs
+//! Per-output-directory record of generated certificates, used for incremental runs.
+use crate::duplicates::DuplicateDecision;
+use crate::effects::TextEffect;
+use crate::fingerprint::Fingerprint;
+use crate::variation::AxisValue;
+use crate::shard::ShardRecord;
+use crate::error::{CertError, IoContext, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub const MANIFEST_FILENAME: &str = "manifest.json";
+/// Where a dry run writes the manifest a real run would, leaving the real one alone.
+pub const DRY_RUN_MANIFEST_FILENAME: &str = "manifest.dry-run.json";
+
+/// One generated certificate and the hash of everything that went into it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub name: String,
+    pub output_file: PathBuf,
+    pub content_hash: String,
+    /// Line of the name in the source file, so runs over parts of a list can be stitched together.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub row: Option<usize>,
+    /// File the name came from, when names from several files were merged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<PathBuf>,
+    /// Recipient address from the list's email column, if it has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    /// Value of the column the batch was grouped by; `None` when it was empty or the batch
+    /// wasn't grouped. See [`CertificateJob::group_by`](crate::job::CertificateJob::group_by).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Set once the certificate was emailed, so an interrupted send picks up where it stopped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emailed: Option<SentEmail>,
+    /// Hash of the certificate file as written, so undo can tell whether it was edited since
+    /// and `verify` can tell whether a file is one that was issued.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_hash: Option<String>,
+    /// Seconds since the Unix epoch when the file was written; kept while the row is unchanged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issued: Option<u64>,
+    /// The JPEG preview saved next to the certificate and its hash, when the batch made
+    /// previews. See [`CertificateJob::preview`](crate::job::CertificateJob::preview).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview: Option<Artifact>,
+    /// The back of a double-sided certificate saved as PNG and its hash; `None` when the
+    /// certificate is single-sided or a PDF holding both sides. See
+    /// [`CertificateJob::back`](crate::job::CertificateJob::back).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub back: Option<Artifact>,
+    /// Text of the watermark drawn over the certificate, such as "DRAFT"; `None` for a clean
+    /// one. See [`CertificateJob::watermark`](crate::job::CertificateJob::watermark).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watermark: Option<String>,
+    /// Hash of the [`Fingerprint`] the certificate was made with, one of the manifest's
+    /// [`fingerprints`](Manifest::fingerprints); `None` when it carries none. See
+    /// [`CertificateJob::fingerprint`](crate::job::CertificateJob::fingerprint).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    /// Values drawn cut short to fit their space, each with its whole text. See
+    /// [`TextOverflow::Ellipsis`](crate::editpng::TextOverflow::Ellipsis).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ellipsized: Vec<EllipsizedText>,
+}
+
+/// A value drawn cut short, so the whole of it isn't lost.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EllipsizedText {
+    /// Which text it was: `name`, a text field's template such as `{Course}`, or a table.
+    pub field: String,
+    /// The value before it was cut.
+    pub full: String,
+    /// What was drawn.
+    pub drawn: String,
+}
+
+/// Where and when a certificate was emailed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SentEmail {
+    pub to: String,
+    /// Seconds since the Unix epoch.
+    pub at: u64,
+}
+
+/// A file written alongside the certificates (preview, zip, contact sheet, PDF, ...).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Artifact {
+    pub path: PathBuf,
+    pub file_hash: String,
+}
+
+/// What the most recent batch that wrote anything wrote, for [`plan_undo`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatchRecord {
+    /// Seconds since the Unix epoch when the batch finished.
+    pub at: u64,
+    /// Certificates rendered by the batch; their hashes are in the entries.
+    pub outputs: Vec<PathBuf>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub artifacts: Vec<Artifact>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub entries: Vec<ManifestEntry>,
+    /// How repeated names in the last run's list were resolved.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub duplicates: Vec<DuplicateDecision>,
+    /// Axis values the last run drew its variable font at; empty for static fonts and defaults.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub font_variation: Vec<AxisValue>,
+    /// Every text effect the last run drew with, its parameters as drawn, so the look can be
+    /// reproduced after the presets change.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub effects: Vec<TextEffect>,
+    /// The settings behind each fingerprint an entry records, with the version that drew them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fingerprints: Vec<Fingerprint>,
+    /// Unset in manifests written before batches were recorded, which can't be undone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_batch: Option<BatchRecord>,
+    /// Set in the manifest of one shard of a plan, which holds only that shard's certificates.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shard: Option<ShardRecord>,
+}
+
+impl Manifest {
+    /// Load the manifest from an output directory, or start an empty one.
+    pub fn load(output_dir: impl AsRef<Path>) -> Result<Manifest> {
+        let manifest_path = output_dir.as_ref().join(MANIFEST_FILENAME);
+        if !manifest_path.exists() {
+            return Ok(Manifest::default());
+        }
+
+        Manifest::load_file(manifest_path)
+    }
+
+    /// Load a manifest file of any name; unlike [`load`](Self::load), a missing file is an error.
+    pub fn load_file(manifest_path: impl AsRef<Path>) -> Result<Manifest> {
+        let manifest_path = manifest_path.as_ref();
+        let content = std::fs::read_to_string(manifest_path)
+            .io_context(|| format!("Failed to read manifest: {}", manifest_path.display()))?;
+        serde_json::from_str(&content)
+            .map_err(|source| CertError::Json { context: format!("Failed to parse manifest: {}", manifest_path.display()), source })
+    }
+
+    pub fn save(&self, output_dir: impl AsRef<Path>) -> Result<()> {
+        self.save_as(output_dir.as_ref().join(MANIFEST_FILENAME))
+    }
+
+    /// Write the manifest to a file of any name. It is written to `<name>.part` beside it and
+    /// moved over the old file, so a crash or Ctrl+C mid-write leaves the old manifest whole.
+    pub fn save_as(&self, manifest_path: impl AsRef<Path>) -> Result<()> {
+        let manifest_path = manifest_path.as_ref();
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|source| CertError::Json { context: "Failed to serialize manifest".to_string(), source })?;
+        let mut partial = manifest_path.as_os_str().to_owned();
+        partial.push(".part");
+        let partial = PathBuf::from(partial);
+        let write = || -> std::io::Result<()> {
+            let mut file = File::create(&partial)?;
+            file.write_all(content.as_bytes())?;
+            // On disk before the rename, or a power cut could still leave an empty manifest
+            file.sync_all()
+        };
+        write().io_context(|| format!("Failed to write manifest: {}", partial.display()))?;
+        std::fs::rename(&partial, manifest_path)
+            .io_context(|| format!("Failed to move {} to {}", partial.display(), manifest_path.display()))
+    }
+
+    /// The entry of `output_file`. This scans the entries; to look up many, index them once
+    /// with [`by_output_file`](Self::by_output_file).
+    pub fn find(&self, output_file: impl AsRef<Path>) -> Option<&ManifestEntry> {
+        self.entries.iter().find(|entry| entry.output_file == output_file.as_ref())
+    }
+
+    /// The entries keyed by their output file.
+    pub fn by_output_file(&self) -> HashMap<&Path, &ManifestEntry> {
+        self.entries.iter().map(|entry| (entry.output_file.as_path(), entry)).collect()
+    }
+
+    /// Keep the fingerprints among `known` that some entry records, once each, as the
+    /// manifest's [`fingerprints`](Self::fingerprints).
+    pub fn keep_fingerprints(&mut self, known: impl IntoIterator<Item = Fingerprint>) {
+        let mut kept: Vec<Fingerprint> = Vec::new();
+        for fingerprint in known {
+            let used = self.entries.iter().any(|entry| entry.fingerprint.as_ref() == Some(&fingerprint.hash));
+            if used && !kept.iter().any(|other| other.hash == fingerprint.hash) {
+                kept.push(fingerprint);
+            }
+        }
+        self.fingerprints = kept;
+    }
+}
+
+/// Add a file made from the last batch's certificates to its record, so undoing the batch
+/// removes it too.
+pub fn record_artifact(output_dir: impl AsRef<Path>, artifact: impl AsRef<Path>) -> Result<()> {
+    record_artifacts(output_dir, &[artifact])
+}
+
+/// [`record_artifact`] for several files at once, saving the manifest once.
+pub fn record_artifacts(output_dir: impl AsRef<Path>, artifacts: &[impl AsRef<Path>]) -> Result<()> {
+    let output_dir = output_dir.as_ref();
+    let mut manifest = Manifest::load(output_dir)?;
+    let record = manifest.last_batch.get_or_insert_with(BatchRecord::default);
+    for artifact in artifacts {
+        let file_hash = hash_file(artifact)?;
+        record.artifacts.retain(|existing| existing.path != artifact.as_ref());
+        record.artifacts.push(Artifact { path: artifact.as_ref().to_path_buf(), file_hash });
+    }
+    manifest.save(output_dir)
+}
+
+/// Hash the raw bytes of a file (template, font, ...), streamed rather than read into memory.
+pub fn hash_file(file_path: impl AsRef<Path>) -> Result<String> {
+    let file_path = file_path.as_ref();
+    let mut hasher = Sha256::new();
+    std::fs::File::open(file_path)
+        .and_then(|file| std::io::copy(&mut std::io::BufReader::new(file), &mut hasher))
+        .io_context(|| format!("Failed to read file for hashing: {}", file_path.display()))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Hash data that is already in memory, e.g. the built-in font.
+pub fn hash_bytes(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Hash every input that affects how a single certificate looks.
+#[allow(clippy::too_many_arguments)]
+pub fn row_hash(
+    name: &str,
+    template_hash: &str,
+    font_hash: &str,
+    font_size: f32,
+    hex_color: &str,
+    x_pos: i32,
+    y_pos: i32,
+    anchor: &str,
+) -> String {
+    let mut hasher = Sha256::new();
+    for part in [
+        name,
+        template_hash,
+        font_hash,
+        &font_size.to_string(),
+        &hex_color.to_lowercase(),
+        &x_pos.to_string(),
+        &y_pos.to_string(),
+    ] {
+        hasher.update(part.as_bytes());
+        // Separator so ("ab", "c") and ("a", "bc") hash differently
+        hasher.update([0u8]);
+    }
+    // Left empty for centered text so manifests from older runs stay valid
+    if !anchor.is_empty() {
+        hasher.update(anchor.as_bytes());
+        hasher.update([0u8]);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Delete the output files of entries that no longer have a matching row.
+///
+/// Returns the paths that were actually removed.
+pub fn remove_stale_outputs(output_dir: impl AsRef<Path>, stale: &[ManifestEntry]) -> Result<Vec<PathBuf>> {
+    let output_dir = output_dir.as_ref();
+    let mut manifest = Manifest::load(output_dir)?;
+    let mut removed = Vec::new();
+
+    for entry in stale {
+        let path = entry.output_file.as_path();
+        if path.exists() {
+            std::fs::remove_file(path)
+                .io_context(|| format!("Failed to remove stale certificate: {}", path.display()))?;
+            removed.push(entry.output_file.clone());
+        }
+        if let Some(preview) = &entry.preview
+            && preview.path.exists() {
+            std::fs::remove_file(&preview.path)
+                .io_context(|| format!("Failed to remove stale preview: {}", preview.path.display()))?;
+            removed.push(preview.path.clone());
+        }
+        if let Some(back) = &entry.back
+            && back.path.exists() {
+            std::fs::remove_file(&back.path)
+                .io_context(|| format!("Failed to remove stale back side: {}", back.path.display()))?;
+            removed.push(back.path.clone());
+        }
+        manifest.entries.retain(|e| e.output_file != entry.output_file);
+    }
+
+    manifest.save(output_dir)?;
+    Ok(removed)
+}
+
+/// A file the undo leaves alone, and why.
+#[derive(Debug, Clone)]
+pub struct KeptFile {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// The files undoing the last batch would delete, checked against their recorded hashes.
+#[derive(Debug, Clone)]
+pub struct UndoPlan {
+    pub output_dir: PathBuf,
+    pub record: BatchRecord,
+    /// Files still exactly as the batch wrote them.
+    pub delete: Vec<PathBuf>,
+    /// Files that are gone, were changed since, or have no hash to compare with.
+    pub kept: Vec<KeptFile>,
+}
+
+/// What [`UndoPlan::apply`] did.
+#[derive(Debug, Clone, Default)]
+pub struct UndoReport {
+    pub removed: Vec<PathBuf>,
+    pub kept: Vec<KeptFile>,
+}
+
+/// Work out how to undo the last batch written to `output_dir`. Fails with
+/// [`CertError::NoManifest`] or [`CertError::NothingToUndo`] rather than guessing which
+/// files are the batch's.
+pub fn plan_undo(output_dir: impl AsRef<Path>) -> Result<UndoPlan> {
+    let output_dir = output_dir.as_ref();
+    if !output_dir.join(MANIFEST_FILENAME).exists() {
+        return Err(CertError::NoManifest(output_dir.to_path_buf()));
+    }
+    let manifest = Manifest::load(output_dir)?;
+    let Some(record) = manifest.last_batch.clone() else {
+        return Err(CertError::NothingToUndo(output_dir.to_path_buf()));
+    };
+
+    let mut plan = UndoPlan { output_dir: output_dir.to_path_buf(), record, delete: Vec::new(), kept: Vec::new() };
+    let entries = manifest.by_output_file();
+    let outputs = plan.record.outputs.iter()
+        .map(|path| (path, entries.get(path.as_path()).and_then(|entry| entry.file_hash.as_deref())));
+    let artifacts = plan.record.artifacts.iter().map(|artifact| (&artifact.path, Some(artifact.file_hash.as_str())));
+    for (path, file_hash) in outputs.chain(artifacts) {
+        match unchanged_since_written(path, file_hash) {
+            Ok(()) => plan.delete.push(path.clone()),
+            Err(reason) => plan.kept.push(KeptFile { path: path.clone(), reason }),
+        }
+    }
+    Ok(plan)
+}
+
+impl UndoPlan {
+    /// Delete the planned files, checking each hash again right before removing it, and drop
+    /// them and the batch record from the manifest.
+    pub fn apply(self) -> Result<UndoReport> {
+        let mut report = UndoReport { removed: Vec::new(), kept: self.kept };
+        let mut manifest = Manifest::load(&self.output_dir)?;
+        let entries = manifest.by_output_file();
+        let artifacts: HashMap<&Path, &str> = self.record.artifacts.iter()
+            .map(|artifact| (artifact.path.as_path(), artifact.file_hash.as_str()))
+            .collect();
+        for path in self.delete {
+            let file_hash = entries.get(path.as_path()).and_then(|entry| entry.file_hash.as_deref())
+                .or_else(|| artifacts.get(path.as_path()).copied());
+            if let Err(reason) = unchanged_since_written(&path, file_hash) {
+                report.kept.push(KeptFile { path, reason });
+                continue;
+            }
+            std::fs::remove_file(&path)
+                .io_context(|| format!("Failed to remove {}", path.display()))?;
+            report.removed.push(path);
+        }
+        // Dropped in one pass once the files are gone
+        let removed: HashSet<&Path> = report.removed.iter().map(PathBuf::as_path).collect();
+        manifest.entries.retain(|entry| !removed.contains(entry.output_file.as_path()));
+        manifest.last_batch = None;
+        manifest.save(&self.output_dir)?;
+        Ok(report)
+    }
+}
+
+// Ok when the file still has the hash recorded when it was written, otherwise why not
+fn unchanged_since_written(path: &Path, file_hash: Option<&str>) -> std::result::Result<(), String> {
+    let Some(file_hash) = file_hash else {
+        return Err("no hash was recorded for it".to_string());
+    };
+    if !path.exists() {
+        return Err("already gone".to_string());
+    }
+    match hash_file(path) {
+        Ok(current) if current == file_hash => Ok(()),
+        Ok(_) => Err("changed since it was generated".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
@@ -0,0 +1,208 @@
+// src/mapping.rs
+//! Column mappings: which column of a name list fills each `{placeholder}` of a layout, so a
+//! layout written for `{course}` also fills in from a list whose header says "Programme".
+//!
+//! A [`ColumnMapping`] comes from a layout's `[mapping]` table or from the mapping step of
+//! "Generate from layout file", which offers [`suggest_column`]'s guess for each placeholder.
+//! The [`MappingStore`] remembers each mapping under the [`schema_key`] of the list's header
+//! row, so the next list with the same columns is mapped without asking.
+//!
+//! ```
+//! use certificate_maker::mapping::{ColumnMapping, MappingStore, suggest_column};
+//! use certificate_maker::source::Record;
+//!
+//! let headers: Vec<String> = ["Participant Full Name", "Programme", "Ticket"].map(String::from).into();
+//! assert_eq!(suggest_column("name", &headers), Some(0));
+//! assert_eq!(suggest_column("ticket_no", &headers), Some(2));
+//! assert_eq!(suggest_column("course", &headers), None);
+//!
+//! let mut mapping = ColumnMapping::default();
+//! mapping.insert("course", "Programme");
+//! assert_eq!(mapping.unmapped(["course", "Ticket", "date"], &headers), vec!["date"]);
+//!
+//! let mut records = vec![Record {
+//!     name: "Ada Lovelace".to_string(),
+//!     fields: vec![("Participant Full Name".to_string(), "Ada Lovelace".to_string()), ("Programme".to_string(), "Analysis".to_string())],
+//! }];
+//! mapping.apply(&mut records);
+//! assert_eq!(records[0].get("course"), Some("Analysis"));
+//!
+//! // The next list with the same header row gets the same mapping
+//! let mut store = MappingStore::default();
+//! store.remember(&headers, &mapping);
+//! let same_schema: Vec<String> = ["participant full name ", "PROGRAMME", "Ticket"].map(String::from).into();
+//! assert_eq!(store.get(&same_schema), Some(&mapping));
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::error::{CertError, IoContext, Result};
+use crate::fontnames::edit_distance;
+use crate::source::Record;
+
+/// File in the working directory that remembers the mapping of each list schema.
+pub const MAPPINGS_FILE: &str = ".certmaker_mappings.json";
+
+/// Placeholders and the header of the column that fills each one, matched ignoring case.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ColumnMapping {
+    columns: BTreeMap<String, String>,
+}
+
+impl ColumnMapping {
+    /// Fill `placeholder` from the column headed `column`, instead of any earlier choice.
+    pub fn insert(&mut self, placeholder: impl Into<String>, column: impl Into<String>) {
+        let placeholder = placeholder.into();
+        self.columns.retain(|known, _| !known.eq_ignore_ascii_case(placeholder.trim()));
+        self.columns.insert(placeholder.trim().to_string(), column.into().trim().to_string());
+    }
+
+    /// Header of the column that fills `placeholder`, if it is mapped.
+    pub fn get(&self, placeholder: &str) -> Option<&str> {
+        self.columns.iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(placeholder.trim()))
+            .map(|(_, column)| column.as_str())
+    }
+
+    /// Every placeholder and its column, in placeholder order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.columns.iter().map(|(placeholder, column)| (placeholder.as_str(), column.as_str()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    /// Add `other`'s placeholders, replacing the columns of those both map.
+    pub fn merge(&mut self, other: &ColumnMapping) {
+        for (placeholder, column) in other.iter() {
+            self.insert(placeholder, column);
+        }
+    }
+
+    /// Index among `headers` of the column filling `placeholder`: the mapped column when it
+    /// is mapped, otherwise a column of the same name.
+    pub fn column_for(&self, placeholder: &str, headers: &[String]) -> Option<usize> {
+        let wanted = self.get(placeholder).unwrap_or(placeholder);
+        headers.iter().position(|header| header.trim().eq_ignore_ascii_case(wanted.trim()))
+    }
+
+    /// The `placeholders` no column of `headers` fills, in the order given.
+    pub fn unmapped<'a>(&self, placeholders: impl IntoIterator<Item = &'a str>, headers: &[String]) -> Vec<&'a str> {
+        placeholders.into_iter()
+            .filter(|placeholder| self.column_for(placeholder, headers).is_none())
+            .collect()
+    }
+
+    /// Give each record its mapped columns under their placeholders' names as well, ahead of a
+    /// column that already has that name. `{name}` is always the name column and is left alone.
+    pub fn apply(&self, records: &mut [Record]) {
+        for (placeholder, column) in self.iter().filter(|(placeholder, _)| !placeholder.eq_ignore_ascii_case("name")) {
+            for record in records.iter_mut() {
+                if let Some(value) = record.get(column).map(str::to_string) {
+                    record.fields.insert(0, (placeholder.to_string(), value));
+                }
+            }
+        }
+    }
+}
+
+/// Each placeholder and its column, as in `{course} ← Programme, {date} ← Issued`.
+impl std::fmt::Display for ColumnMapping {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pairs: Vec<String> = self.iter().map(|(placeholder, column)| format!("{{{}}} ← {}", placeholder, column)).collect();
+        write!(f, "{}", pairs.join(", "))
+    }
+}
+
+/// A remembered mapping and the header row it was made for.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedMapping {
+    pub headers: Vec<String>,
+    pub mapping: ColumnMapping,
+}
+
+/// Mappings remembered by [`schema_key`], as kept in [`MAPPINGS_FILE`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct MappingStore {
+    schemas: BTreeMap<String, SavedMapping>,
+}
+
+impl MappingStore {
+    /// The mappings remembered in `path`; none when the file doesn't exist yet.
+    pub fn load(path: impl AsRef<Path>) -> Result<MappingStore> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(MappingStore::default());
+        }
+        let content = std::fs::read_to_string(path)
+            .io_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&content)
+            .map_err(|source| CertError::Json { context: format!("Failed to parse {}", path.display()), source })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|source| CertError::Json { context: "Failed to serialize column mappings".to_string(), source })?;
+        std::fs::write(path, content)
+            .io_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// The mapping remembered for lists with these headers.
+    pub fn get(&self, headers: &[String]) -> Option<&ColumnMapping> {
+        self.schemas.get(&schema_key(headers)).map(|saved| &saved.mapping)
+    }
+
+    /// Remember `mapping` for lists with these headers, adding to what was remembered before.
+    pub fn remember(&mut self, headers: &[String], mapping: &ColumnMapping) {
+        let saved = self.schemas.entry(schema_key(headers))
+            .or_insert_with(|| SavedMapping { headers: headers.to_vec(), mapping: ColumnMapping::default() });
+        saved.mapping.merge(mapping);
+    }
+}
+
+/// A short hash of a header row, the same for rows that differ only in case or surrounding
+/// spaces.
+pub fn schema_key(headers: &[String]) -> String {
+    let row: Vec<String> = headers.iter().map(|header| header.trim().to_lowercase()).collect();
+    let digest = format!("{:x}", Sha256::digest(row.join("\n").as_bytes()));
+    digest[..16].to_string()
+}
+
+/// The column of `headers` that most likely fills `placeholder`: one of the same name ignoring
+/// case, spaces and punctuation, then one containing it or contained in it ("Participant Full
+/// Name" for `{name}`), then one a few typos away. `None` when nothing is close.
+pub fn suggest_column(placeholder: &str, headers: &[String]) -> Option<usize> {
+    let wanted = simplified(placeholder);
+    if wanted.is_empty() {
+        return None;
+    }
+    headers.iter().enumerate()
+        .filter_map(|(index, header)| {
+            let header = simplified(header);
+            let distance = edit_distance(&wanted, &header);
+            let rank = if header == wanted {
+                0
+            } else if !header.is_empty() && (header.contains(&wanted) || wanted.contains(&header)) {
+                1
+            } else if distance <= (wanted.chars().count() / 3).max(1) {
+                2
+            } else {
+                return None;
+            };
+            Some((rank, distance, index))
+        })
+        .min()
+        .map(|(.., index)| index)
+}
+
+// Lowercase letters and digits only, so "Ticket No." and "ticket_no" compare equal
+fn simplified(text: &str) -> String {
+    text.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
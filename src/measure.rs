@@ -0,0 +1,172 @@
+// src/measure.rs
+//! Text measurement, the one place widths and heights of text come from: [`draw_text`],
+//! [`calculate_text_size`] and the batch's centering all measure through [`measure_text`], so a
+//! layout preview built on it agrees with the render to the pixel.
+//!
+//! [`draw_text`]: crate::editpng::draw_text
+//! [`calculate_text_size`]: crate::editpng::calculate_text_size
+use rusttype::{Font, PositionedGlyph, Scale, point};
+
+use crate::editpng::TextBounds;
+use crate::error::{CertError, Result};
+
+/// What [`measure_text`] found, in pixels. Positions are relative to the top-left corner of the
+/// first line's box, with each line starting at x = 0 and its baseline one ascent below the
+/// top of its box.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextMetrics {
+    /// Box around every pixel the text inks; all zero when nothing is inked, as for spaces.
+    pub ink_bbox: TextBounds,
+    /// Pen advance of the widest line, with kerning and letter spacing.
+    pub advance_width: f32,
+    /// Zero for empty text.
+    pub line_count: usize,
+    /// Ink width of each line, which is what centering uses.
+    pub per_line_widths: Vec<i32>,
+    pub ascent: f32,
+    /// Below the baseline, so negative.
+    pub descent: f32,
+    /// Distance from one line's baseline to the next.
+    pub line_height: f32,
+}
+
+impl TextMetrics {
+    /// Height of the box the lines are set in: every line's height, and the gaps between them.
+    pub fn height(&self) -> i32 {
+        if self.line_count == 0 {
+            return 0;
+        }
+        ((self.line_count - 1) as f32 * self.line_height + self.ascent - self.descent).ceil() as i32
+    }
+}
+
+/// Measure `text` set in `font` at `size` pixels, with `letter_spacing` extra pixels between
+/// glyphs (negative tightens) and, when `max_width` is given, wrapped at spaces so no line's
+/// advance exceeds it. A word wider than `max_width` gets a line of its own.
+///
+/// [`draw_text`](crate::editpng::draw_text) sets one line without extra spacing, which is
+/// `measure_text(font, text, size, 0.0, None)`; the ink width of that line and [`height`]
+/// are the box it centers.
+///
+/// [`height`]: TextMetrics::height
+///
+/// ```
+/// use certificate_maker::editpng::load_font;
+/// use certificate_maker::measure::measure_text;
+///
+/// let font = load_font("assets/DejaVuSans.ttf")?;
+/// let metrics = measure_text(&font, "Ada Lovelace", 40.0, 0.0, None)?;
+/// assert_eq!(metrics.per_line_widths, [227]);
+/// assert_eq!(metrics.height(), 40);
+/// assert_eq!((metrics.ink_bbox.y, metrics.ink_bbox.height), (5, 28));
+///
+/// let metrics = measure_text(&font, "Ada Lovelace", 72.0, 0.0, None)?;
+/// assert_eq!(metrics.per_line_widths, [408]);
+/// assert_eq!(metrics.height(), 72);
+///
+/// let wrapped = measure_text(&font, "Ada Lovelace", 72.0, 2.0, Some(300.0))?;
+/// assert_eq!(wrapped.line_count, 2);
+/// assert_eq!(wrapped.per_line_widths, [117, 278]);
+/// assert_eq!(wrapped.height(), 144);
+/// # Ok::<(), certificate_maker::error::CertError>(())
+/// ```
+pub fn measure_text(font: &Font, text: &str, size: f32, letter_spacing: f32, max_width: Option<f32>) -> Result<TextMetrics> {
+    if !size.is_finite() || size <= 0.0 {
+        return Err(CertError::InvalidFontSize(size));
+    }
+    if !letter_spacing.is_finite() {
+        return Err(CertError::InvalidMeasurement(format!("letter spacing must be a number, got {}", letter_spacing)));
+    }
+    if let Some(max_width) = max_width
+        && (!max_width.is_finite() || max_width <= 0.0) {
+        return Err(CertError::InvalidMeasurement(format!("the maximum width must be a positive number, got {}", max_width)));
+    }
+    Ok(measure_scaled(font, Scale::uniform(size), text, letter_spacing, max_width))
+}
+
+// measure_text without the checks, at any scale
+pub(crate) fn measure_scaled(font: &Font, scale: Scale, text: &str, letter_spacing: f32, max_width: Option<f32>) -> TextMetrics {
+    let v_metrics = font.v_metrics(scale);
+    let line_height = v_metrics.ascent - v_metrics.descent + v_metrics.line_gap;
+    let lines = match max_width {
+        Some(max_width) => wrap(font, scale, text, letter_spacing, max_width),
+        None if text.is_empty() => Vec::new(),
+        None => vec![text.to_string()],
+    };
+
+    let mut metrics = TextMetrics {
+        ink_bbox: TextBounds { x: 0, y: 0, width: 0, height: 0 },
+        advance_width: 0.0,
+        line_count: lines.len(),
+        per_line_widths: Vec::with_capacity(lines.len()),
+        ascent: v_metrics.ascent,
+        descent: v_metrics.descent,
+        line_height,
+    };
+    let mut ink: Option<(i32, i32, i32, i32)> = None;
+    for (index, line) in lines.iter().enumerate() {
+        let baseline = v_metrics.ascent + index as f32 * line_height;
+        let (glyphs, advance) = layout_line(font, scale, line, letter_spacing, baseline);
+        metrics.advance_width = metrics.advance_width.max(advance);
+
+        let mut line_ink: Option<(i32, i32, i32, i32)> = None;
+        for bb in glyphs.iter().filter_map(PositionedGlyph::pixel_bounding_box) {
+            line_ink = Some(match line_ink {
+                Some((x0, y0, x1, y1)) => (x0.min(bb.min.x), y0.min(bb.min.y), x1.max(bb.max.x), y1.max(bb.max.y)),
+                None => (bb.min.x, bb.min.y, bb.max.x, bb.max.y),
+            });
+        }
+        metrics.per_line_widths.push(line_ink.map_or(0, |(x0, _, x1, _)| x1 - x0));
+        if let Some((x0, y0, x1, y1)) = line_ink {
+            ink = Some(match ink {
+                Some((ux0, uy0, ux1, uy1)) => (ux0.min(x0), uy0.min(y0), ux1.max(x1), uy1.max(y1)),
+                None => (x0, y0, x1, y1),
+            });
+        }
+    }
+    if let Some((x0, y0, x1, y1)) = ink {
+        metrics.ink_bbox = TextBounds { x: x0, y: y0, width: x1 - x0, height: y1 - y0 };
+    }
+    metrics
+}
+
+// One line's glyphs with the pen starting at (0, baseline), placed exactly as Font::layout
+// places them when there is no letter spacing, and the pen's advance after the last glyph
+fn layout_line<'f>(font: &Font<'f>, scale: Scale, line: &str, letter_spacing: f32, baseline: f32) -> (Vec<PositionedGlyph<'f>>, f32) {
+    let mut glyphs = Vec::new();
+    let mut caret = 0.0;
+    let mut previous = None;
+    for c in line.chars() {
+        let glyph = font.glyph(c).scaled(scale);
+        if let Some(previous) = previous {
+            caret += font.pair_kerning(scale, previous, glyph.id()) + letter_spacing;
+        }
+        previous = Some(glyph.id());
+        let advance = glyph.h_metrics().advance_width;
+        glyphs.push(glyph.positioned(point(caret, baseline)));
+        caret += advance;
+    }
+    (glyphs, caret)
+}
+
+// Greedy wrapping at whitespace: each line takes words while its advance fits
+fn wrap(font: &Font, scale: Scale, text: &str, letter_spacing: f32, max_width: f32) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        if current.is_empty() {
+            current = word.to_string();
+            continue;
+        }
+        let candidate = format!("{} {}", current, word);
+        if layout_line(font, scale, &candidate, letter_spacing, 0.0).1 > max_width {
+            lines.push(std::mem::replace(&mut current, word.to_string()));
+        } else {
+            current = candidate;
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
@@ -0,0 +1,108 @@
+// src/memory.rs
+//! Memory a batch needs and how many workers fit in a budget.
+//!
+//! All workers share one decoded copy of the template. Each worker also holds its own copy to
+//! draw on and the PNG it is encoding, which is counted at the decoded size as the worst case.
+//! Fonts, names and the per-row results are small next to these and aren't counted.
+//!
+//! ```
+//! use certificate_maker::memory::{MemoryPlan, parse_size};
+//!
+//! assert_eq!(parse_size("1.5G"), Some(1_610_612_736));
+//! assert_eq!(parse_size("512 MB"), Some(536_870_912));
+//! assert_eq!(parse_size("lots"), None);
+//!
+//! // An A4 scan at 300 dpi is about 35 MB decoded
+//! let unlimited = MemoryPlan::for_template((3508, 2480), 8, None)?;
+//! assert_eq!(unlimited.threads, 8);
+//! let capped = MemoryPlan::for_template((3508, 2480), 8, parse_size("300M"))?;
+//! assert_eq!(capped.threads, 4);
+//! assert!(capped.peak_bytes() <= 300 * 1024 * 1024);
+//! assert!(MemoryPlan::for_template((3508, 2480), 8, parse_size("64M")).is_err());
+//! # Ok::<(), String>(())
+//! ```
+use serde::Serialize;
+
+/// How many workers a batch runs and what they hold at the peak.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct MemoryPlan {
+    pub threads: usize,
+    /// The decoded template every worker draws copies of.
+    pub shared_bytes: u64,
+    /// A worker's copy of the template plus its encode buffer.
+    pub per_worker_bytes: u64,
+    /// The budget the plan was made for; `None` runs every available thread.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget: Option<u64>,
+}
+
+impl MemoryPlan {
+    /// Plan for a template of `(width, height)` pixels and `available` threads. With a budget,
+    /// the threads are cut until the peak fits; a budget too small for even one worker is an
+    /// error saying how much is needed.
+    pub fn for_template(dimensions: (u32, u32), available: usize, budget: Option<u64>) -> Result<MemoryPlan, String> {
+        Self::for_pixels(dimensions, 4, 8, available, budget)
+    }
+
+    /// [`for_template`](Self::for_template) for a 16-bit template kept at its depth (see
+    /// [`depth`](crate::depth)): its 16-bit pixels are shared too, and each worker also holds
+    /// the 16-bit certificate and encodes from it.
+    pub fn for_deep_template(dimensions: (u32, u32), available: usize, budget: Option<u64>) -> Result<MemoryPlan, String> {
+        Self::for_pixels(dimensions, 4 + 8, 4 + 8 + 8, available, budget)
+    }
+
+    // Bytes per pixel of the template shared by the workers and held by each of them
+    fn for_pixels((width, height): (u32, u32), shared: u64, per_worker: u64, available: usize, budget: Option<u64>) -> Result<MemoryPlan, String> {
+        let pixels = width as u64 * height as u64;
+        let mut plan = MemoryPlan { threads: available.max(1), shared_bytes: pixels * shared, per_worker_bytes: pixels * per_worker, budget };
+        if let Some(budget) = budget {
+            let needed = plan.shared_bytes + plan.per_worker_bytes;
+            if budget < needed {
+                return Err(format!("A memory budget of {} is too small for a {}x{} template: one worker needs about {}",
+                                   format_size(budget), width, height, format_size(needed)));
+            }
+            let fits = (budget - plan.shared_bytes) / plan.per_worker_bytes.max(1);
+            plan.threads = plan.threads.min(fits as usize);
+        }
+        Ok(plan)
+    }
+
+    /// Memory held at the peak, with every worker mid-render.
+    pub fn peak_bytes(&self) -> u64 {
+        self.shared_bytes + self.per_worker_bytes * self.threads as u64
+    }
+
+    /// Whether the budget took threads away from `available`.
+    pub fn is_capped(&self, available: usize) -> bool {
+        self.threads < available
+    }
+}
+
+/// A size such as `4G`, `512M`, `1.5GB`, `300 MiB` or a plain number of bytes. Units are
+/// binary: `1K` is 1024 bytes.
+pub fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let split = text.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let shift = match unit.trim().to_ascii_lowercase().trim_end_matches("ib").trim_end_matches('b') {
+        "" => 0,
+        "k" => 10,
+        "m" => 20,
+        "g" => 30,
+        "t" => 40,
+        _ => return None,
+    };
+    let bytes = number * (1u64 << shift) as f64;
+    (bytes.is_finite() && bytes >= 1.0).then_some(bytes as u64)
+}
+
+/// `1.5 GB`, `300.0 MB`, for messages.
+pub fn format_size(bytes: u64) -> String {
+    const MB: f64 = 1024.0 * 1024.0;
+    if bytes as f64 >= 1024.0 * MB {
+        format!("{:.1} GB", bytes as f64 / (1024.0 * MB))
+    } else {
+        format!("{:.1} MB", bytes as f64 / MB)
+    }
+}
@@ -0,0 +1,102 @@
+// src/namecase.rs
+//! Case transforms for the text drawn on certificates.
+//!
+//! ```
+//! use certificate_maker::namecase::NameCase;
+//!
+//! assert_eq!(NameCase::Title.apply("ANNA VAN DER BERG"), "Anna van der Berg");
+//! assert_eq!(NameCase::Title.apply("seán o'connor-mcdonald"), "Seán O'Connor-McDonald");
+//! assert_eq!(NameCase::Title.apply("ÉLODIE DURAND"), "Élodie Durand");
+//! assert_eq!(NameCase::Upper.apply("istanbul straße"), "ISTANBUL STRASSE");
+//! assert_eq!(NameCase::Lower.apply("ΟΔΥΣΣΕΑΣ"), "οδυσσεας");
+//! ```
+use serde::{Deserialize, Serialize};
+
+/// Surname particles that stay lowercase unless they start the name.
+pub const NAME_PARTICLES: &[&str] = &[
+    "van", "von", "der", "den", "de", "del", "della", "di", "da", "das", "do", "dos", "du", "la", "le", "ten", "ter", "y",
+];
+
+/// How names are cased when drawn.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NameCase {
+    /// Draw names exactly as they are in the list.
+    #[default]
+    Preserve,
+    /// "john o'connor" becomes "John O'Connor"; particles like "van der" stay lowercase.
+    Title,
+    Upper,
+    Lower,
+}
+
+impl NameCase {
+    /// Transform one name. Case mapping is Unicode-aware but locale-independent.
+    pub fn apply(self, name: &str) -> String {
+        match self {
+            NameCase::Preserve => name.to_string(),
+            NameCase::Title => title_case(name),
+            NameCase::Upper => name.to_uppercase(),
+            NameCase::Lower => name.to_lowercase(),
+        }
+    }
+
+    /// Human-readable name, e.g. for a settings summary.
+    pub fn label(self) -> &'static str {
+        match self {
+            NameCase::Preserve => "as in the list",
+            NameCase::Title => "Title Case",
+            NameCase::Upper => "UPPERCASE",
+            NameCase::Lower => "lowercase",
+        }
+    }
+}
+
+/// Title-case a name, keeping the spacing it had.
+///
+/// Each hyphenated part is capitalized ("Jean-Luc"), as is the letter after a one-letter
+/// apostrophe prefix ("O'Connor", "D'Angelo") and after "Mc" ("McDonald"). "Mac" is left
+/// alone because too many names merely start with it (Mack, Macy).
+pub fn title_case(name: &str) -> String {
+    let mut first_word = true;
+    name.split(' ')
+        .map(|word| {
+            if word.is_empty() {
+                return String::new();
+            }
+            let lower = word.to_lowercase();
+            let cased = if !first_word && NAME_PARTICLES.contains(&lower.as_str()) {
+                lower
+            } else {
+                lower.split('-').map(capitalize_part).collect::<Vec<_>>().join("-")
+            };
+            first_word = false;
+            cased
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// One lowercase hyphen-free part of a name
+fn capitalize_part(part: &str) -> String {
+    for apostrophe in ['\'', '’'] {
+        if let Some((prefix, rest)) = part.split_once(apostrophe)
+            && prefix.chars().count() == 1
+            && !rest.is_empty() {
+            return format!("{}{}{}", capitalize(prefix), apostrophe, capitalize(rest));
+        }
+    }
+    if let Some(rest) = part.strip_prefix("mc")
+        && rest.chars().count() > 1 {
+        return format!("Mc{}", capitalize(rest));
+    }
+    capitalize(part)
+}
+
+// Uppercase the first character; the rest is already lowercase
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,232 @@
+// src/normalize.rs
+//! Templates brought to an exact size before anything is drawn, for tooling that expects,
+//! say, A4 at 300 DPI to the pixel (2480×3508) when the scan came out at 2481×3507.
+//!
+//! Positions and sizes are still entered against the template as it is on disk; a
+//! [`NormalizePlan`] moves them onto the normalized one, so saved presets keep working.
+//!
+//! ```
+//! use certificate_maker::normalize::{FitMode, Normalization};
+//! use image::{Rgba, RgbaImage};
+//!
+//! let normalization = Normalization::parse("2480x3508").unwrap();
+//! assert_eq!(normalization.mode, FitMode::Stretch);
+//! let plan = normalization.plan((2481, 3507));
+//! assert_eq!(plan.map_point(1240, 1753), (1240, 1753));
+//! assert!(plan.warning().is_none());
+//!
+//! // Half the size, fitted into a square: scaled by 0.5 and padded above and below
+//! let plan = Normalization::parse("500x500:fit").unwrap().plan((1000, 600));
+//! assert_eq!((plan.scale_x, plan.offset), (0.5, (0, 100)));
+//! assert_eq!(plan.map_point(500, 300), (250, 250));
+//! assert_eq!(plan.map_length(40.0), 20.0);
+//! let normalized = plan.apply(&RgbaImage::from_pixel(1000, 600, Rgba([0, 0, 80, 255])));
+//! assert_eq!(normalized.dimensions(), (500, 500));
+//! assert_eq!(normalized.get_pixel(250, 50), &Rgba([255, 255, 255, 255]));
+//! assert!(plan.warning().is_none());
+//! assert!(Normalization::parse("4000x2400").unwrap().plan((1000, 600)).warning().is_some());
+//! ```
+use image::imageops::{self, FilterType};
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::graphics::{GraphicField, GraphicKind};
+
+/// Scaling further than this either way is most likely a wrong target size, so it is warned about.
+pub const MAX_NORMALIZE_SCALE: f64 = 2.0;
+
+/// How a template is brought to the target size.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FitMode {
+    /// Scaled to exactly the target size, each side by its own factor. Meant for templates a
+    /// few pixels off; the default.
+    #[default]
+    Stretch,
+    /// Scaled to fit inside the target, keeping the aspect ratio, and padded with white.
+    Fit,
+    /// Scaled to cover the target, keeping the aspect ratio, and cropped.
+    Fill,
+    /// Not scaled; padded with white or cropped.
+    Pad,
+}
+
+impl FitMode {
+    pub const ALL: [FitMode; 4] = [FitMode::Stretch, FitMode::Fit, FitMode::Fill, FitMode::Pad];
+
+    /// The name used by `--normalize` and in messages.
+    pub fn name(self) -> &'static str {
+        match self {
+            FitMode::Stretch => "stretch",
+            FitMode::Fit => "fit",
+            FitMode::Fill => "fill",
+            FitMode::Pad => "pad",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<FitMode> {
+        FitMode::ALL.into_iter().find(|mode| mode.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+/// The size every template is brought to, and how.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Normalization {
+    pub width: u32,
+    pub height: u32,
+    #[serde(default)]
+    pub mode: FitMode,
+}
+
+impl Normalization {
+    /// `"2480x3508"`, optionally with a mode as in `"2480x3508:fit"`; `None` for anything else
+    /// or a zero side.
+    pub fn parse(text: &str) -> Option<Normalization> {
+        let (size, mode) = match text.split_once(':') {
+            Some((size, mode)) => (size, FitMode::from_name(mode)?),
+            None => (text, FitMode::default()),
+        };
+        let (width, height) = size.trim().split_once(['x', 'X', '×'])?;
+        let width = width.trim().parse().ok().filter(|&width| width > 0)?;
+        let height = height.trim().parse().ok().filter(|&height| height > 0)?;
+        Some(Normalization { width, height, mode })
+    }
+
+    /// How a template of `original` size is brought to this one.
+    pub fn plan(&self, original: (u32, u32)) -> NormalizePlan {
+        let (width, height) = (original.0.max(1) as f64, original.1.max(1) as f64);
+        let (scale_x, scale_y) = (self.width as f64 / width, self.height as f64 / height);
+        let (scale_x, scale_y) = match self.mode {
+            FitMode::Stretch => (scale_x, scale_y),
+            FitMode::Fit => (scale_x.min(scale_y), scale_x.min(scale_y)),
+            FitMode::Fill => (scale_x.max(scale_y), scale_x.max(scale_y)),
+            FitMode::Pad => (1.0, 1.0),
+        };
+        let scaled = ((width * scale_x).round() as i64, (height * scale_y).round() as i64);
+        NormalizePlan {
+            original,
+            target: (self.width, self.height),
+            mode: self.mode,
+            scale_x,
+            scale_y,
+            // Centered, so padding or cropping is shared by both sides
+            offset: (((self.width as i64 - scaled.0) / 2) as i32, ((self.height as i64 - scaled.1) / 2) as i32),
+        }
+    }
+}
+
+impl std::fmt::Display for Normalization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{} ({})", self.width, self.height, self.mode.name())
+    }
+}
+
+/// How one template is normalized: each side's scale factor and where the scaled template
+/// lands on the target.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct NormalizePlan {
+    pub original: (u32, u32),
+    pub target: (u32, u32),
+    pub mode: FitMode,
+    pub scale_x: f64,
+    pub scale_y: f64,
+    /// Top-left corner of the scaled template on the target; negative when it is cropped.
+    pub offset: (i32, i32),
+}
+
+impl NormalizePlan {
+    /// Whether the template is already the target size, so nothing changes.
+    pub fn is_identity(&self) -> bool {
+        self.original == self.target
+    }
+
+    /// A point on the original template, on the normalized one.
+    pub fn map_point(&self, x: i32, y: i32) -> (i32, i32) {
+        ((x as f64 * self.scale_x).round() as i32 + self.offset.0, (y as f64 * self.scale_y).round() as i32 + self.offset.1)
+    }
+
+    /// A size on the original template, such as a font size, on the normalized one. When the
+    /// sides scale differently the size follows their geometric mean, so text keeps its area.
+    pub fn map_length(&self, length: f32) -> f32 {
+        (length as f64 * self.factor()) as f32
+    }
+
+    /// The factor sizes are scaled by; see [`map_length`](Self::map_length).
+    pub fn factor(&self) -> f64 {
+        (self.scale_x * self.scale_y).sqrt()
+    }
+
+    /// The field with its position and sizes moved onto the normalized template. Images are
+    /// scaled only when the field gives their size, since their own size isn't known here.
+    pub fn map_graphic(&self, graphic: &GraphicField) -> GraphicField {
+        let (x, y) = self.map_point(graphic.x, graphic.y);
+        let pixels = |pixels: u32| ((pixels as f64 * self.factor()).round() as u32).max(1);
+        let mut kind = graphic.kind.clone();
+        match &mut kind {
+            GraphicKind::Text(style) => {
+                style.size = self.map_length(style.size);
+                style.max_width = style.max_width.map(|max_width| self.map_length(max_width));
+            }
+            GraphicKind::Image(style) => {
+                style.width = style.width.map(|width| ((width as f64 * self.scale_x).round() as u32).max(1));
+                style.height = style.height.map(|height| ((height as f64 * self.scale_y).round() as u32).max(1));
+            }
+            GraphicKind::Code128(style) => {
+                style.module_width = pixels(style.module_width);
+                style.height = pixels(style.height);
+                style.text_size = style.text_size.map(|size| self.map_length(size));
+            }
+            GraphicKind::Table(style) => {
+                style.size = self.map_length(style.size);
+                style.row_height = style.row_height.map(|row_height| self.map_length(row_height));
+                style.rule = self.map_length(style.rule);
+                for column in &mut style.columns {
+                    column.width = ((column.width as f64 * self.scale_x).round() as u32).max(1);
+                }
+            }
+        }
+        GraphicField { kind, x, y, ..graphic.clone() }
+    }
+
+    /// The template at the target size: scaled with Lanczos3, then padded with white or
+    /// cropped around the center.
+    pub fn apply(&self, template: &RgbaImage) -> RgbaImage {
+        if self.is_identity() {
+            return template.clone();
+        }
+        let (width, height) = template.dimensions();
+        let scaled_size = ((width as f64 * self.scale_x).round().max(1.0) as u32, (height as f64 * self.scale_y).round().max(1.0) as u32);
+        let scaled = if scaled_size == (width, height) {
+            template.clone()
+        } else {
+            imageops::resize(template, scaled_size.0, scaled_size.1, FilterType::Lanczos3)
+        };
+        let mut normalized = RgbaImage::from_pixel(self.target.0, self.target.1, Rgba([255, 255, 255, 255]));
+        imageops::replace(&mut normalized, &scaled, self.offset.0 as i64, self.offset.1 as i64);
+        normalized
+    }
+
+    /// "2481x3507 → 2480x3508 (stretch, ×0.9996 wide, ×1.0003 tall)"
+    pub fn describe(&self) -> String {
+        format!("{}x{} → {}x{} ({}, {})", self.original.0, self.original.1, self.target.0, self.target.1, self.mode.name(), self.factor_label(4))
+    }
+
+    /// A warning when either side is scaled by more than [`MAX_NORMALIZE_SCALE`] up or down,
+    /// which usually means the target size is wrong.
+    pub fn warning(&self) -> Option<String> {
+        let extreme = |scale: f64| !(1.0 / MAX_NORMALIZE_SCALE..=MAX_NORMALIZE_SCALE).contains(&scale);
+        (extreme(self.scale_x) || extreme(self.scale_y)).then(|| {
+            format!("normalizing scales the {}x{} template {}, more than {}× either way; check the target size",
+                    self.original.0, self.original.1, self.factor_label(2), MAX_NORMALIZE_SCALE)
+        })
+    }
+
+    // "×0.50", or "×0.9996 wide, ×1.0003 tall" when the sides differ
+    fn factor_label(&self, decimals: usize) -> String {
+        if (self.scale_x - self.scale_y).abs() < 1e-9 {
+            format!("×{:.*}", decimals, self.scale_x)
+        } else {
+            format!("×{:.*} wide, ×{:.*} tall", decimals, self.scale_x, decimals, self.scale_y)
+        }
+    }
+}
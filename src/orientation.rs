@@ -0,0 +1,179 @@
+// src/orientation.rs
+//! The EXIF orientation of templates and pictures, so a JPEG exported from a phone is drawn on
+//! upright rather than lying on its side.
+//!
+//! Cameras store the pixels as the sensor read them and record in EXIF how to turn them. The
+//! image crate decodes the pixels as stored, so every template, overlay and photo is loaded
+//! through [`open_oriented`] and measured with [`oriented_dimensions`], which apply the flag
+//! first. Files whose rotation was already baked in, while the flag stayed behind, can opt out
+//! with [`ignore_exif`].
+//!
+//! ```
+//! use certificate_maker::orientation::{Orientation, exif_orientation, open_oriented, oriented_dimensions};
+//! use image::{Rgb, RgbImage};
+//!
+//! // A 60x20 landscape JPEG whose EXIF says to turn it 90° clockwise
+//! let mut jpeg = Vec::new();
+//! let img = RgbImage::from_fn(60, 20, |x, _| if x < 10 { Rgb([0, 0, 0]) } else { Rgb([255, 255, 255]) });
+//! image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, 95).encode_image(&img)?;
+//! let exif = b"Exif\0\0MM\0\x2a\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01\0\x06\0\0\0\0\0\0";
+//! let mut app1 = vec![0xFF, 0xE1];
+//! app1.extend_from_slice(&(exif.len() as u16 + 2).to_be_bytes());
+//! app1.extend_from_slice(exif);
+//! jpeg.splice(2..2, app1);
+//! let path = std::env::temp_dir().join(format!("certmaker-orientation-{}.jpg", std::process::id()));
+//! std::fs::write(&path, &jpeg)?;
+//!
+//! assert_eq!(exif_orientation(&path), Some(Orientation::Rotate90));
+//! assert_eq!(image::image_dimensions(&path)?, (60, 20));
+//! assert_eq!(oriented_dimensions(&path)?, (20, 60));
+//! // The dark left edge of the stored image is now along the top
+//! let upright = open_oriented(&path)?.to_rgb8();
+//! assert!(upright.get_pixel(10, 2).0[0] < 64 && upright.get_pixel(10, 40).0[0] > 192);
+//! std::fs::remove_file(&path)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use image::{DynamicImage, ImageResult};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Cursor, Seek};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static IGNORED: AtomicBool = AtomicBool::new(false);
+
+/// How the stored pixels are turned to stand upright, by EXIF orientation values 2 to 8.
+/// Value 1, and a file without the flag, need nothing and are `None` wherever one is returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Orientation {
+    FlipHorizontal,
+    Rotate180,
+    FlipVertical,
+    /// Turned 90° clockwise, then mirrored left to right.
+    Rotate90FlipHorizontal,
+    Rotate90,
+    /// Turned 90° counter-clockwise, then mirrored left to right.
+    Rotate270FlipHorizontal,
+    Rotate270,
+}
+
+impl Orientation {
+    /// The orientation an EXIF `Orientation` value asks for.
+    pub fn from_exif(value: u32) -> Option<Self> {
+        match value {
+            2 => Some(Orientation::FlipHorizontal),
+            3 => Some(Orientation::Rotate180),
+            4 => Some(Orientation::FlipVertical),
+            5 => Some(Orientation::Rotate90FlipHorizontal),
+            6 => Some(Orientation::Rotate90),
+            7 => Some(Orientation::Rotate270FlipHorizontal),
+            8 => Some(Orientation::Rotate270),
+            _ => None,
+        }
+    }
+
+    /// Whether the upright image has the stored one's width and height swapped.
+    pub fn swaps_dimensions(self) -> bool {
+        matches!(self, Orientation::Rotate90FlipHorizontal | Orientation::Rotate90
+            | Orientation::Rotate270FlipHorizontal | Orientation::Rotate270)
+    }
+
+    /// The upright size of an image stored as `(width, height)`.
+    pub fn dimensions(self, (width, height): (u32, u32)) -> (u32, u32) {
+        if self.swaps_dimensions() { (height, width) } else { (width, height) }
+    }
+
+    /// Turn the stored pixels upright.
+    pub fn apply(self, img: DynamicImage) -> DynamicImage {
+        match self {
+            Orientation::FlipHorizontal => img.fliph(),
+            Orientation::Rotate180 => img.rotate180(),
+            Orientation::FlipVertical => img.flipv(),
+            Orientation::Rotate90FlipHorizontal => img.rotate90().fliph(),
+            Orientation::Rotate90 => img.rotate90(),
+            Orientation::Rotate270FlipHorizontal => img.rotate270().fliph(),
+            Orientation::Rotate270 => img.rotate270(),
+        }
+    }
+
+    /// What is done to the stored pixels, e.g. "rotated 90° clockwise".
+    pub fn describe(self) -> &'static str {
+        match self {
+            Orientation::FlipHorizontal => "mirrored left to right",
+            Orientation::Rotate180 => "rotated 180°",
+            Orientation::FlipVertical => "mirrored top to bottom",
+            Orientation::Rotate90FlipHorizontal => "rotated 90° clockwise and mirrored",
+            Orientation::Rotate90 => "rotated 90° clockwise",
+            Orientation::Rotate270FlipHorizontal => "rotated 90° counter-clockwise and mirrored",
+            Orientation::Rotate270 => "rotated 90° counter-clockwise",
+        }
+    }
+}
+
+/// Stop (or resume) applying EXIF orientation, for this whole process: for images whose
+/// rotation was already baked into the pixels while the flag was left behind.
+pub fn ignore_exif(ignore: bool) {
+    IGNORED.store(ignore, Ordering::Relaxed);
+}
+
+/// Whether [`ignore_exif`] turned orientation off.
+pub fn exif_ignored() -> bool {
+    IGNORED.load(Ordering::Relaxed)
+}
+
+/// The orientation a file's EXIF asks for, whether or not it is being applied. Only JPEG,
+/// TIFF and WebP files are read: PNG exporters bake the rotation in. Unreadable or missing
+/// EXIF is `None`, like an upright image.
+pub fn exif_orientation(path: impl AsRef<Path>) -> Option<Orientation> {
+    let mut reader = BufReader::new(File::open(path).ok()?);
+    if !carries_exif(reader.fill_buf().ok()?) {
+        return None;
+    }
+    read_orientation(&mut reader)
+}
+
+/// [`exif_orientation`] of a file already in memory.
+pub fn exif_orientation_of(bytes: &[u8]) -> Option<Orientation> {
+    if !carries_exif(bytes) {
+        return None;
+    }
+    read_orientation(&mut Cursor::new(bytes))
+}
+
+/// The orientation to apply to a file: [`exif_orientation`] unless it is being ignored.
+pub fn orientation(path: impl AsRef<Path>) -> Option<Orientation> {
+    if exif_ignored() { None } else { exif_orientation(path) }
+}
+
+/// Width and height of an image as it will be drawn on, read from its header like
+/// `image::image_dimensions`.
+pub fn oriented_dimensions(path: impl AsRef<Path>) -> ImageResult<(u32, u32)> {
+    let path = path.as_ref();
+    let dimensions = image::image_dimensions(path)?;
+    Ok(orientation(path).map_or(dimensions, |orientation| orientation.dimensions(dimensions)))
+}
+
+/// Decode an image and turn it upright, like `image::open`.
+pub fn open_oriented(path: impl AsRef<Path>) -> ImageResult<DynamicImage> {
+    let path = path.as_ref();
+    let img = image::open(path)?;
+    Ok(match orientation(path) {
+        Some(orientation) => orientation.apply(img),
+        None => img,
+    })
+}
+
+// A JPEG is only read up to its EXIF segment
+fn read_orientation(reader: &mut (impl BufRead + Seek)) -> Option<Orientation> {
+    let exif = exif::Reader::new().read_from_container(reader).ok()?;
+    let field = exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)?;
+    Orientation::from_exif(field.value.get_uint(0)?)
+}
+
+// JPEG, big- or little-endian TIFF, or a RIFF WebP
+fn carries_exif(header: &[u8]) -> bool {
+    header.starts_with(&[0xFF, 0xD8])
+        || header.starts_with(b"II*\0")
+        || header.starts_with(b"MM\0*")
+        || (header.starts_with(b"RIFF") && header.get(8..12) == Some(b"WEBP"))
+}
@@ -0,0 +1,242 @@
+// src/palette.rs
+//! A project's approved colors, named in a [`PALETTE_FILE`] next to its config file, so
+//! organizers pick `primary` instead of typing a near-miss hex.
+//!
+//! ```toml
+//! strict = true          # optional: reject colors that aren't in the palette
+//!
+//! [colors]               # listed at the color prompts in this order
+//! primary = "#1A1A40"
+//! gold = "#B08D3C"
+//! muted = "#1A1A4080"    # with alpha
+//! ```
+//!
+//! Wherever a color is read, a layout's `color = "palette:gold"` and the prompts included,
+//! `palette:<name>` stands for that entry once the palette is [`install`]ed;
+//! [`hex_to_rgba`](crate::editpng::hex_to_rgba) resolves it. A strict palette makes
+//! [`check_color`] refuse other colors, naming the nearest entry by ΔE, the distance between
+//! two colors in CIELAB where about 2.3 is the smallest difference people notice.
+//!
+//! ```
+//! use certificate_maker::error::CertError;
+//! use certificate_maker::palette::Palette;
+//! use image::Rgba;
+//!
+//! let palette = Palette::from_toml("strict = true\n[colors]\nprimary = \"#1A1A40\"\ngold = \"#B08D3C\"\n")?;
+//! assert_eq!(palette.resolve("palette:Gold")?, Rgba([176, 141, 60, 255]));
+//! assert_eq!(palette.check("#1a1a40")?, Rgba([26, 26, 64, 255]));
+//! match palette.check("#1B1A42") {
+//!     Err(CertError::OffPalette { nearest, delta_e, .. }) => assert!(nearest == "primary" && delta_e < 2.0),
+//!     other => panic!("expected the nearest color, got {:?}", other),
+//! }
+//! assert!(palette.resolve("palette:teal").is_err());
+//! # Ok::<(), CertError>(())
+//! ```
+use image::Rgba;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::editpng::hex_to_rgba;
+use crate::effects::{TextEffect, parse_effect};
+use crate::error::{CertError, IoContext, Result};
+
+/// File the palette is read from, in the directory of the config file.
+pub const PALETTE_FILE: &str = "palette.toml";
+
+/// Written before an entry's name to use it as a color, as in `palette:primary`.
+pub const PALETTE_PREFIX: &str = "palette:";
+
+static INSTALLED: OnceLock<Palette> = OnceLock::new();
+
+/// One named color of a [`Palette`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaletteColor {
+    pub name: String,
+    pub color: Rgba<u8>,
+}
+
+impl PaletteColor {
+    /// The color as `#RRGGBB`, or `#RRGGBBAA` when it isn't opaque.
+    pub fn hex(&self) -> String {
+        to_hex(self.color)
+    }
+
+    /// How the color is written to refer to this entry, `palette:<name>`.
+    pub fn reference(&self) -> String {
+        format!("{}{}", PALETTE_PREFIX, self.name)
+    }
+}
+
+/// The approved colors, in the order the file lists them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Palette {
+    pub colors: Vec<PaletteColor>,
+    /// Whether [`check`](Self::check) refuses colors that aren't in the palette.
+    pub strict: bool,
+    /// Text effects of the file's own, from its `[effects.<name>]` tables; see
+    /// [`effects`](crate::effects).
+    pub effects: Vec<TextEffect>,
+}
+
+impl Palette {
+    /// Read a palette file; `None` when there is none, which leaves the feature off.
+    pub fn load(path: impl AsRef<Path>) -> Result<Option<Palette>> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(path)
+            .io_context(|| format!("Failed to read palette {}", path.display()))?;
+        Palette::parse(&content).map(Some)
+            .map_err(|reason| CertError::PaletteParse { path: path.to_path_buf(), reason })
+    }
+
+    /// A palette from the text of a palette file.
+    pub fn from_toml(content: &str) -> Result<Palette> {
+        Palette::parse(content).map_err(|reason| CertError::PaletteParse { path: PathBuf::from(PALETTE_FILE), reason })
+    }
+
+    fn parse(content: &str) -> std::result::Result<Palette, String> {
+        let document: Value = toml::from_str(content).map_err(|e| e.to_string())?;
+        let mut palette = Palette::default();
+        let mut effects = None;
+        for (key, value) in document.as_object().into_iter().flatten() {
+            match (key.as_str(), value) {
+                ("strict", Value::Bool(strict)) => palette.strict = *strict,
+                ("strict", _) => return Err("strict: expected true or false".to_string()),
+                ("colors", Value::Object(colors)) => {
+                    for (name, value) in colors {
+                        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+                            return Err(format!("colors.{}: names are letters, digits, - and _", name));
+                        }
+                        if palette.get(name).is_some() {
+                            return Err(format!("colors.{}: listed twice (names ignore case)", name));
+                        }
+                        let color = value.as_str()
+                            .ok_or_else(|| format!("colors.{}: expected a color in quotes such as \"#1A1A40\"", name))
+                            .and_then(|hex| hex_to_rgba(hex).map_err(|e| format!("colors.{}: {}", name, e)))?;
+                        palette.colors.push(PaletteColor { name: name.clone(), color });
+                    }
+                }
+                ("colors", _) => return Err("colors: expected a [colors] table of names and hex colors".to_string()),
+                // Read once the colors are known, as their colors may name them
+                ("effects", Value::Object(tables)) => effects = Some(tables),
+                ("effects", _) => return Err("effects: expected [effects.<name>] tables".to_string()),
+                (key, _) => return Err(format!("{}: unknown key; a palette has strict, [colors] and [effects.<name>]", key)),
+            }
+        }
+        for (name, table) in effects.into_iter().flatten() {
+            if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+                return Err(format!("effects.{}: names are letters, digits, - and _", name));
+            }
+            if palette.effect(name).is_some() {
+                return Err(format!("effects.{}: listed twice (names ignore case)", name));
+            }
+            let effect = parse_effect(name, table, &|color| palette.resolve(color))?;
+            palette.effects.push(effect);
+        }
+        if palette.colors.is_empty() && (palette.strict || palette.effects.is_empty()) {
+            return Err("it lists no colors; add them under [colors], like primary = \"#1A1A40\"".to_string());
+        }
+        Ok(palette)
+    }
+
+    /// The file's own effect called `name`, ignoring case.
+    pub fn effect(&self, name: &str) -> Option<&TextEffect> {
+        self.effects.iter().find(|effect| effect.name.eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// The entry called `name`, ignoring case.
+    pub fn get(&self, name: &str) -> Option<&PaletteColor> {
+        self.colors.iter().find(|entry| entry.name.eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// A `palette:<name>` reference or a hex color as RGBA, in or out of the palette.
+    pub fn resolve(&self, color: &str) -> Result<Rgba<u8>> {
+        match color.trim().strip_prefix(PALETTE_PREFIX) {
+            Some(name) => self.get(name).map(|entry| entry.color).ok_or_else(|| CertError::UnknownPaletteColor {
+                name: name.trim().to_string(),
+                available: self.colors.iter().map(|entry| entry.name.clone()).collect(),
+            }),
+            None => hex_to_rgba(color),
+        }
+    }
+
+    /// [`resolve`](Self::resolve) `color`, and in a strict palette make sure it is one of the
+    /// entries, suggesting the nearest when it isn't.
+    pub fn check(&self, color: &str) -> Result<Rgba<u8>> {
+        let rgba = self.resolve(color)?;
+        if !self.strict || self.colors.iter().any(|entry| entry.color == rgba) {
+            return Ok(rgba);
+        }
+        let (nearest, delta_e) = self.nearest(rgba).expect("a loaded palette has colors");
+        Err(CertError::OffPalette { color: color.trim().to_string(), nearest: nearest.name.clone(), hex: nearest.hex(), delta_e })
+    }
+
+    /// The entry closest to `color` by [`delta_e`], with the distance.
+    pub fn nearest(&self, color: Rgba<u8>) -> Option<(&PaletteColor, f32)> {
+        self.colors.iter()
+            .map(|entry| (entry, delta_e(entry.color, color)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+    }
+
+    /// The entry that is exactly `color`, for showing a hex by its name.
+    pub fn name_of(&self, color: Rgba<u8>) -> Option<&str> {
+        self.colors.iter().find(|entry| entry.color == color).map(|entry| entry.name.as_str())
+    }
+}
+
+/// Make `palette` the one `palette:` references resolve to for the rest of the process.
+/// Returns false, leaving the first one, when a palette was already installed.
+pub fn install(palette: Palette) -> bool {
+    INSTALLED.set(palette).is_ok()
+}
+
+/// The palette [`install`] made current, if any.
+pub fn installed() -> Option<&'static Palette> {
+    INSTALLED.get()
+}
+
+/// A color typed by someone, resolved with the installed palette and checked against it when
+/// it is strict; without one, any hex color.
+pub fn check_color(color: &str) -> Result<Rgba<u8>> {
+    match installed() {
+        Some(palette) => palette.check(color),
+        None => hex_to_rgba(color),
+    }
+}
+
+/// CIE76 ΔE between two colors, their alpha ignored: the distance in CIELAB of their sRGB
+/// values under a D65 white point. Under 1 is invisible; about 2.3 is just noticeable.
+pub fn delta_e(a: Rgba<u8>, b: Rgba<u8>) -> f32 {
+    let (a, b) = (to_lab(a), to_lab(b));
+    a.iter().zip(b).map(|(a, b)| (a - b).powi(2)).sum::<f32>().sqrt()
+}
+
+fn to_lab(color: Rgba<u8>) -> [f32; 3] {
+    let linear = |channel: u8| {
+        let c = channel as f32 / 255.0;
+        if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    };
+    let [r, g, b] = [linear(color[0]), linear(color[1]), linear(color[2])];
+    let x = (0.4124564 * r + 0.3575761 * g + 0.1804375 * b) / 0.95047;
+    let y = 0.2126729 * r + 0.7151522 * g + 0.0721750 * b;
+    let z = (0.0193339 * r + 0.119192 * g + 0.9503041 * b) / 1.08883;
+    let f = |t: f32| {
+        let delta: f32 = 6.0 / 29.0;
+        if t > delta.powi(3) { t.cbrt() } else { t / (3.0 * delta * delta) + 4.0 / 29.0 }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// `color` as `#RRGGBB`, or `#RRGGBBAA` when it isn't opaque.
+pub fn to_hex(color: Rgba<u8>) -> String {
+    let [r, g, b, a] = color.0;
+    if a == 255 {
+        format!("#{:02X}{:02X}{:02X}", r, g, b)
+    } else {
+        format!("#{:02X}{:02X}{:02X}{:02X}", r, g, b, a)
+    }
+}
@@ -0,0 +1,255 @@
+// src/patch.rs
+//! Painting over part of a template before anything is drawn on it, such as last year's
+//! "2024" on a template reused for 2025.
+//!
+//! A [`Patch`] is a rectangle in the template's pixels and a [`PatchFill`]: a solid color, the
+//! average of a ring sampled around the rectangle, or a copy of the strip next to it, which
+//! keeps paper texture and gradients that one color would flatten. A rectangle that runs off
+//! the template is clamped to it.
+//!
+//! ```
+//! use certificate_maker::patch::{Patch, PatchFill, apply_patch};
+//! use image::{Rgba, RgbaImage};
+//!
+//! let paper = Rgba([250, 245, 230, 255]);
+//! let mut img = RgbaImage::from_pixel(300, 200, paper);
+//! for x in 100..140 {
+//!     img.put_pixel(x, 50, Rgba([0, 0, 0, 255])); // "2024"
+//! }
+//! apply_patch(&mut img, &Patch { x: 90, y: 40, width: 60, height: 20, fill: PatchFill::Sample })?;
+//! assert_eq!(img.get_pixel(120, 50), &paper);
+//!
+//! // Clamped: only the part on the template is painted
+//! let fill: PatchFill = "#FF0000".parse()?;
+//! apply_patch(&mut img, &Patch { x: 280, y: -10, width: 50, height: 30, fill })?;
+//! assert_eq!(img.get_pixel(299, 0), &Rgba([255, 0, 0, 255]));
+//! assert_eq!(img.get_pixel(279, 0), &paper);
+//!
+//! // Cloned, a stripe running through the rectangle carries on through it
+//! for x in 0..300 {
+//!     img.put_pixel(x, 150, Rgba([0, 0, 120, 255]));
+//! }
+//! img.put_pixel(150, 145, Rgba([0, 0, 0, 255]));
+//! apply_patch(&mut img, &Patch { x: 130, y: 140, width: 40, height: 20, fill: PatchFill::Clone })?;
+//! assert_eq!(img.get_pixel(150, 150), &Rgba([0, 0, 120, 255]));
+//! assert_eq!(img.get_pixel(150, 145), &paper);
+//! # Ok::<(), certificate_maker::error::CertError>(())
+//! ```
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::editpng::hex_to_rgba;
+use crate::error::{CertError, Result};
+
+/// Width of the ring around a patch that [`PatchFill::Sample`] averages, in pixels.
+pub const SAMPLE_RING: u32 = 3;
+
+/// What a [`Patch`] is filled with. Written as `sample`, `clone` or a color like `#FFFFFF`
+/// in layouts and at the prompts.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum PatchFill {
+    /// The average color of a [`SAMPLE_RING`]-pixel ring around the rectangle.
+    #[default]
+    Sample,
+    /// A copy of the strip of the same size beside the rectangle, from the side whose edges
+    /// match the surroundings best. Without room for a strip on any side, sampled instead.
+    Clone,
+    /// One color, as `#RRGGBB` or `#RRGGBBAA`, replacing the pixels rather than blended
+    /// over them.
+    Color(String),
+}
+
+impl PatchFill {
+    /// Lowercase description for messages.
+    pub fn label(&self) -> &str {
+        match self {
+            PatchFill::Sample => "sample",
+            PatchFill::Clone => "clone",
+            PatchFill::Color(color) => color,
+        }
+    }
+}
+
+impl std::str::FromStr for PatchFill {
+    type Err = CertError;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let text = text.trim();
+        if text.eq_ignore_ascii_case("sample") {
+            return Ok(PatchFill::Sample);
+        }
+        if text.eq_ignore_ascii_case("clone") {
+            return Ok(PatchFill::Clone);
+        }
+        hex_to_rgba(text)
+            .map(|_| PatchFill::Color(text.to_string()))
+            .map_err(|_| CertError::InvalidPatch(format!("'{}' isn't a fill; use sample, clone or a color like \"#FFFFFF\"", text)))
+    }
+}
+
+impl std::fmt::Display for PatchFill {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+impl TryFrom<String> for PatchFill {
+    type Error = CertError;
+
+    fn try_from(text: String) -> Result<Self> {
+        text.parse()
+    }
+}
+
+impl From<PatchFill> for String {
+    fn from(fill: PatchFill) -> Self {
+        fill.to_string()
+    }
+}
+
+/// A rectangle of the template painted over by [`apply_patch`], in the template's own pixels
+/// with `x` and `y` its top-left corner.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Patch {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    #[serde(default)]
+    pub fill: PatchFill,
+}
+
+impl Patch {
+    /// The part of the rectangle on a `width` x `height` template, as (x, y, width, height);
+    /// `None` when none of it is.
+    pub fn clamp(&self, width: u32, height: u32) -> Option<(u32, u32, u32, u32)> {
+        let left = (self.x as i64).clamp(0, width as i64);
+        let top = (self.y as i64).clamp(0, height as i64);
+        let right = (self.x as i64 + self.width as i64).clamp(0, width as i64);
+        let bottom = (self.y as i64 + self.height as i64).clamp(0, height as i64);
+        (right > left && bottom > top).then(|| (left as u32, top as u32, (right - left) as u32, (bottom - top) as u32))
+    }
+
+    /// Check the size and fill, and that the patch lands on a `width` x `height` template with
+    /// something around it to sample or clone from when its fill needs that.
+    pub fn validate(&self, width: u32, height: u32) -> Result<()> {
+        let invalid = |reason: String| Err(CertError::InvalidPatch(reason));
+        if self.width == 0 || self.height == 0 {
+            return invalid(format!("{}x{} is empty; a patch needs a width and a height", self.width, self.height));
+        }
+        if let PatchFill::Color(color) = &self.fill
+            && let Err(e) = hex_to_rgba(color) {
+            return invalid(e.to_string());
+        }
+        let Some(clamped) = self.clamp(width, height) else {
+            return invalid(format!("{}x{} at ({}, {}) is outside the {}x{} template", self.width, self.height, self.x, self.y, width, height));
+        };
+        if !matches!(self.fill, PatchFill::Color(_)) && clamped == (0, 0, width, height) {
+            return invalid(format!("it covers the whole {}x{} template, leaving nothing around it to {}; fill it with a color instead",
+                                   width, height, self.fill.label()));
+        }
+        Ok(())
+    }
+}
+
+/// Paint `patch` over `img`, clamped to it.
+pub fn apply_patch(img: &mut RgbaImage, patch: &Patch) -> Result<()> {
+    let (width, height) = img.dimensions();
+    patch.validate(width, height)?;
+    let Some(region) = patch.clamp(width, height) else {
+        return Ok(());
+    };
+    match &patch.fill {
+        PatchFill::Color(color) => fill(img, region, hex_to_rgba(color)?),
+        PatchFill::Sample => fill(img, region, ring_average(img, region)),
+        PatchFill::Clone => match best_strip(img, region) {
+            Some(offset) => clone_strip(img, region, offset),
+            None => fill(img, region, ring_average(img, region)),
+        },
+    }
+    Ok(())
+}
+
+/// Paint each of `patches` over `img` in order, so later ones cover earlier ones.
+pub fn apply_patches(img: &mut RgbaImage, patches: &[Patch]) -> Result<()> {
+    patches.iter().try_for_each(|patch| apply_patch(img, patch))
+}
+
+fn fill(img: &mut RgbaImage, (x, y, width, height): (u32, u32, u32, u32), color: Rgba<u8>) {
+    for row in y..y + height {
+        for column in x..x + width {
+            img.put_pixel(column, row, color);
+        }
+    }
+}
+
+// The mean of every channel over the ring around `region`, as much of it as is on the image
+fn ring_average(img: &RgbaImage, (x, y, width, height): (u32, u32, u32, u32)) -> Rgba<u8> {
+    let left = x.saturating_sub(SAMPLE_RING);
+    let top = y.saturating_sub(SAMPLE_RING);
+    let right = (x + width + SAMPLE_RING).min(img.width());
+    let bottom = (y + height + SAMPLE_RING).min(img.height());
+    let inside = |column: u32, row: u32| (x..x + width).contains(&column) && (y..y + height).contains(&row);
+    let mut sums = [0u64; 4];
+    let mut count = 0u64;
+    for row in top..bottom {
+        for column in (left..right).filter(|&column| !inside(column, row)) {
+            for (sum, value) in sums.iter_mut().zip(img.get_pixel(column, row).0) {
+                *sum += value as u64;
+            }
+            count += 1;
+        }
+    }
+    // Validation leaves a ring, so count is never 0
+    Rgba(sums.map(|sum| (sum as f64 / count.max(1) as f64).round() as u8))
+}
+
+// Offset of the strip beside `region` to copy: of the strips left, right, above and below that
+// are whole on the image, the one that, copied in, differs least from the pixels just outside
+// the region's edges
+fn best_strip(img: &RgbaImage, (x, y, width, height): (u32, u32, u32, u32)) -> Option<(i64, i64)> {
+    let (w, h) = (width as i64, height as i64);
+    let candidates = [(-w, 0), (w, 0), (0, -h), (0, h)];
+    let fits = |(dx, dy): (i64, i64)| {
+        let (left, top) = (x as i64 + dx, y as i64 + dy);
+        left >= 0 && top >= 0 && left + w <= img.width() as i64 && top + h <= img.height() as i64
+    };
+    candidates.into_iter()
+        .filter(|&offset| fits(offset))
+        .map(|offset| (seam_cost(img, (x, y, width, height), offset), offset))
+        .min_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, offset)| offset)
+}
+
+// Mean difference between each edge pixel of `region`, once copied from `offset`, and its
+// neighbour outside the region
+fn seam_cost(img: &RgbaImage, (x, y, width, height): (u32, u32, u32, u32), (dx, dy): (i64, i64)) -> f64 {
+    let pixel = |column: i64, row: i64| {
+        (column >= 0 && row >= 0 && column < img.width() as i64 && row < img.height() as i64)
+            .then(|| img.get_pixel(column as u32, row as u32))
+    };
+    let (left, top, right, bottom) = (x as i64, y as i64, (x + width) as i64 - 1, (y + height) as i64 - 1);
+    let mut edges = Vec::new();
+    for column in left..=right {
+        edges.push((column, top, column, top - 1));
+        edges.push((column, bottom, column, bottom + 1));
+    }
+    for row in top..=bottom {
+        edges.push((left, row, left - 1, row));
+        edges.push((right, row, right + 1, row));
+    }
+    let (mut total, mut count) = (0.0, 0u32);
+    for (column, row, outside_column, outside_row) in edges {
+        if let (Some(copied), Some(outside)) = (pixel(column + dx, row + dy), pixel(outside_column, outside_row)) {
+            total += copied.0.iter().zip(outside.0).map(|(a, b)| (*a as f64 - b as f64).abs()).sum::<f64>();
+            count += 1;
+        }
+    }
+    if count == 0 { 0.0 } else { total / count as f64 }
+}
+
+fn clone_strip(img: &mut RgbaImage, (x, y, width, height): (u32, u32, u32, u32), (dx, dy): (i64, i64)) {
+    let strip = image::imageops::crop_imm(img, (x as i64 + dx) as u32, (y as i64 + dy) as u32, width, height).to_image();
+    image::imageops::replace(img, &strip, x as i64, y as i64);
+}
@@ -0,0 +1,163 @@
+// src/paths.rs
+//! Where templates, fonts, name lists and outputs live.
+//!
+//! Each directory is resolved from, in priority order: explicit overrides (the binary's
+//! CLI flags), `CERTMAKER_*` environment variables, a `certmaker.json` config file, and
+//! finally the default name next to the current directory or the executable.
+use crate::csvexcelparser::{CSV_DIR, TEMPLATE_DIR};
+use crate::editpng::FONTS_DIR;
+use crate::error::{CertError, IoContext, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// Config file looked up in the current directory when `CERTMAKER_CONFIG` isn't set.
+pub const CONFIG_FILE: &str = "certmaker.json";
+
+/// Default directory for batch output.
+pub const CERTIFICATES_DIR: &str = "certificates";
+
+/// Default directory for single images, reports and diffs.
+pub const OUTPUT_DIR: &str = "output";
+
+/// Resolved directory locations, passed to everything that lists or writes files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppPaths {
+    pub templates: PathBuf,
+    pub fonts: PathBuf,
+    pub csvs: PathBuf,
+    pub certificates: PathBuf,
+    pub output: PathBuf,
+    /// The config file the directories were read from; it need not exist.
+    pub config: PathBuf,
+}
+
+impl Default for AppPaths {
+    /// The historical layout, relative to the current directory.
+    fn default() -> Self {
+        AppPaths {
+            templates: PathBuf::from(TEMPLATE_DIR),
+            fonts: PathBuf::from(FONTS_DIR),
+            csvs: PathBuf::from(CSV_DIR),
+            certificates: PathBuf::from(CERTIFICATES_DIR),
+            output: PathBuf::from(OUTPUT_DIR),
+            config: PathBuf::from(CONFIG_FILE),
+        }
+    }
+}
+
+/// Directories set by one configuration layer; `None` falls through to the next layer.
+///
+/// This is also the format of `certmaker.json`, e.g. `{ "fonts": "/usr/share/fonts/truetype" }`,
+/// apart from its `"email"` section (read by `certificate_maker::email`, see the `email` feature),
+/// `notify_url`/`notify_secret` (read by `certificate_maker::webhook`) and `language` (read by
+/// `certificate_maker::i18n`).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathOverrides {
+    pub templates: Option<PathBuf>,
+    pub fonts: Option<PathBuf>,
+    pub csvs: Option<PathBuf>,
+    pub certificates: Option<PathBuf>,
+    pub output: Option<PathBuf>,
+}
+
+impl PathOverrides {
+    /// Read `CERTMAKER_TEMPLATE_DIR`, `CERTMAKER_FONTS_DIR`, `CERTMAKER_CSV_DIR`,
+    /// `CERTMAKER_CERTIFICATES_DIR` and `CERTMAKER_OUTPUT_DIR`.
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+        PathOverrides {
+            templates: var("CERTMAKER_TEMPLATE_DIR"),
+            fonts: var("CERTMAKER_FONTS_DIR"),
+            csvs: var("CERTMAKER_CSV_DIR"),
+            certificates: var("CERTMAKER_CERTIFICATES_DIR"),
+            output: var("CERTMAKER_OUTPUT_DIR"),
+        }
+    }
+
+    /// Load a config file; a missing file is the same as an empty one.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(PathOverrides::default());
+        }
+
+        let content = std::fs::read_to_string(path)
+            .io_context(|| format!("Failed to read config file: {}", path.display()))?;
+        let parse_error = |source| CertError::Json { context: format!("Failed to parse config file: {}", path.display()), source };
+        let mut config: serde_json::Value = serde_json::from_str(&content).map_err(parse_error)?;
+        // The "email" section, the webhook settings and the language are read where they're used, not here
+        if let Some(sections) = config.as_object_mut() {
+            for key in ["email", "notify_url", "notify_secret", "language"] {
+                sections.remove(key);
+            }
+        }
+        let overrides: PathOverrides = serde_json::from_value(config).map_err(parse_error)?;
+
+        // Relative entries are relative to the config file, not to wherever the tool was started
+        let base = path.parent().unwrap_or(Path::new(""));
+        let anchor = |dir: Option<PathBuf>| dir.map(|dir| base.join(dir));
+        Ok(PathOverrides {
+            templates: anchor(overrides.templates),
+            fonts: anchor(overrides.fonts),
+            csvs: anchor(overrides.csvs),
+            certificates: anchor(overrides.certificates),
+            output: anchor(overrides.output),
+        })
+    }
+}
+
+impl AppPaths {
+    /// Resolve every directory from `cli`, then the environment, then the config file
+    /// (`config_file`, `CERTMAKER_CONFIG` or `certmaker.json`), then the defaults.
+    pub fn resolve(cli: &PathOverrides, config_file: Option<&Path>) -> Result<AppPaths> {
+        let env = PathOverrides::from_env();
+        let config_path = config_file.map(Path::to_path_buf)
+            .or_else(|| std::env::var_os("CERTMAKER_CONFIG").map(PathBuf::from))
+            .unwrap_or_else(|| PathBuf::from(CONFIG_FILE));
+        let config = PathOverrides::from_file(&config_path)?;
+
+        let pick = |layer: fn(&PathOverrides) -> &Option<PathBuf>, default: &str| {
+            [cli, &env, &config].into_iter()
+                .find_map(|overrides| layer(overrides).clone())
+                .unwrap_or_else(|| default_dir(default))
+        };
+
+        Ok(AppPaths {
+            templates: pick(|o| &o.templates, TEMPLATE_DIR),
+            fonts: pick(|o| &o.fonts, FONTS_DIR),
+            csvs: pick(|o| &o.csvs, CSV_DIR),
+            certificates: pick(|o| &o.certificates, CERTIFICATES_DIR),
+            output: pick(|o| &o.output, OUTPUT_DIR),
+            config: config_path,
+        })
+    }
+
+    /// Full path of a font picked from the fonts directory listing.
+    ///
+    /// The built-in font name is passed through unchanged.
+    pub fn font(&self, font_filename: impl AsRef<Path>) -> PathBuf {
+        let font_filename = font_filename.as_ref();
+        if font_filename == Path::new(crate::editpng::BUILTIN_FONT) {
+            return font_filename.to_path_buf();
+        }
+        self.fonts.join(font_filename)
+    }
+
+    /// The [`PALETTE_FILE`](crate::palette::PALETTE_FILE), which sits beside the config file.
+    pub fn palette_file(&self) -> PathBuf {
+        self.config.with_file_name(crate::palette::PALETTE_FILE)
+    }
+}
+
+// `name` in the current directory if it exists there, else next to the executable if it
+// exists there, else `name` in the current directory (to be created on demand)
+fn default_dir(name: &str) -> PathBuf {
+    if Path::new(name).exists() {
+        return PathBuf::from(name);
+    }
+    std::env::current_exe().ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join(name)))
+        .filter(|dir| dir.exists())
+        .unwrap_or_else(|| PathBuf::from(name))
+}
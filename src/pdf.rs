@@ -0,0 +1,132 @@
+// src/pdf.rs
+//! Rendered certificates as PDF documents, one image per page.
+//!
+//! Each page is exactly the size of its image at the given DPI, with the image embedded as a
+//! JPEG. Transparent areas are flattened onto white, as they would be on paper.
+//!
+//! ```
+//! use certificate_maker::pdf::images_to_pdf;
+//! use image::{Rgba, RgbaImage};
+//!
+//! let page = RgbaImage::from_pixel(300, 150, Rgba([255, 255, 255, 255]));
+//! let pdf = images_to_pdf(&[page], 300.0)?;
+//! assert!(pdf.starts_with(b"%PDF-1.4"));
+//! assert!(pdf.ends_with(b"%%EOF\n"));
+//! # Ok::<(), certificate_maker::error::CertError>(())
+//! ```
+use image::codecs::jpeg::JpegEncoder;
+use image::{ColorType, RgbImage, RgbaImage};
+use std::borrow::Borrow;
+use std::io::Write;
+
+use crate::error::{CertError, Result};
+
+/// Resolution assumed for templates when nothing else is known; print templates are usually 300 DPI.
+pub const DEFAULT_PDF_DPI: f32 = 300.0;
+
+/// JPEG quality of the embedded pages.
+pub const PDF_JPEG_QUALITY: u8 = 92;
+
+/// Build a PDF with one page per image, each page sized to its image at `dpi`.
+pub fn images_to_pdf(pages: &[RgbaImage], dpi: f32) -> Result<Vec<u8>> {
+    pages_to_pdf(pages.len(), pages.iter().map(Ok), dpi)
+}
+
+/// Like [`images_to_pdf`], but each of the `count` pages is only made when its turn comes, so
+/// a long document never holds more than one page image in memory.
+pub fn pages_to_pdf<P: Borrow<RgbaImage>>(count: usize, pages: impl IntoIterator<Item = Result<P>>, dpi: f32) -> Result<Vec<u8>> {
+    if !dpi.is_finite() || dpi <= 0.0 {
+        return Err(CertError::InvalidPdf(format!("DPI must be a positive number, got {}", dpi)));
+    }
+    if count == 0 {
+        return Err(CertError::InvalidPdf("a PDF needs at least one page".to_string()));
+    }
+
+    // Objects: 1 catalog, 2 page tree, then page, content stream and image for each page
+    let page_object = |index: usize| 3 + index * 3;
+    let mut pdf = PdfWriter::default();
+    pdf.bytes.extend_from_slice(b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n");
+
+    pdf.object(1, b"<< /Type /Catalog /Pages 2 0 R >>");
+    let kids: Vec<String> = (0..count).map(|index| format!("{} 0 R", page_object(index))).collect();
+    pdf.object(2, format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), count).as_bytes());
+
+    let mut made = 0;
+    for (index, page) in pages.into_iter().enumerate() {
+        if index == count {
+            return Err(CertError::InvalidPdf(format!("more than the {} pages expected", count)));
+        }
+        let page = page?;
+        let page = page.borrow();
+        let id = page_object(index);
+        let (width, height) = (page.width() as f32 * 72.0 / dpi, page.height() as f32 * 72.0 / dpi);
+        pdf.object(id, format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] /Resources << /XObject << /Im0 {} 0 R >> >> /Contents {} 0 R >>",
+            width, height, id + 2, id + 1
+        ).as_bytes());
+
+        let content = format!("q {:.2} 0 0 {:.2} 0 0 cm /Im0 Do Q", width, height);
+        pdf.stream(id + 1, "", content.as_bytes());
+
+        let jpeg = encode_jpeg(page)?;
+        let dictionary = format!(
+            "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB /BitsPerComponent 8 /Filter /DCTDecode ",
+            page.width(), page.height()
+        );
+        pdf.stream(id + 2, &dictionary, &jpeg);
+        made += 1;
+    }
+    if made < count {
+        return Err(CertError::InvalidPdf(format!("only {} of the {} pages expected", made, count)));
+    }
+
+    Ok(pdf.finish())
+}
+
+// Flatten onto white, then JPEG-encode
+fn encode_jpeg(page: &RgbaImage) -> Result<Vec<u8>> {
+    let flattened = RgbImage::from_fn(page.width(), page.height(), |x, y| {
+        let [r, g, b, a] = page.get_pixel(x, y).0;
+        let over_white = |channel: u8| ((channel as u32 * a as u32 + 255 * (255 - a as u32)) / 255) as u8;
+        image::Rgb([over_white(r), over_white(g), over_white(b)])
+    });
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, PDF_JPEG_QUALITY)
+        .encode(&flattened, page.width(), page.height(), ColorType::Rgb8)
+        .map_err(|e| CertError::InvalidPdf(format!("could not encode a page: {}", e)))?;
+    Ok(jpeg)
+}
+
+// Appends numbered objects and remembers where each starts, for the cross-reference table
+#[derive(Default)]
+struct PdfWriter {
+    bytes: Vec<u8>,
+    offsets: Vec<(usize, usize)>,
+}
+
+impl PdfWriter {
+    fn object(&mut self, id: usize, body: &[u8]) {
+        self.offsets.push((id, self.bytes.len()));
+        let _ = writeln!(self.bytes, "{} 0 obj", id);
+        self.bytes.extend_from_slice(body);
+        self.bytes.extend_from_slice(b"\nendobj\n");
+    }
+
+    fn stream(&mut self, id: usize, dictionary: &str, data: &[u8]) {
+        let mut body = format!("<< {}/Length {} >>\nstream\n", dictionary, data.len()).into_bytes();
+        body.extend_from_slice(data);
+        body.extend_from_slice(b"\nendstream");
+        self.object(id, &body);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.offsets.sort_unstable();
+        let xref = self.bytes.len();
+        let _ = writeln!(self.bytes, "xref\n0 {}\n0000000000 65535 f ", self.offsets.len() + 1);
+        for (_, offset) in &self.offsets {
+            let _ = writeln!(self.bytes, "{:010} 00000 n ", offset);
+        }
+        let _ = writeln!(self.bytes, "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF", self.offsets.len() + 1, xref);
+        self.bytes
+    }
+}
@@ -0,0 +1,89 @@
+// src/perftest.rs
+//! A quick end-to-end timing of a real job: sample names rendered with the job's own
+//! template, font and settings into a scratch directory, which is removed afterwards.
+//!
+//! ```
+//! use certificate_maker::job::CertificateJob;
+//! use certificate_maker::perftest::{perf_test, sample_names};
+//!
+//! assert_eq!(sample_names(3), ["Ada Lovelace", "Zoë Okonkwo-Brandt", "Jean-Luc Ångström"]);
+//! assert_eq!(sample_names(100).len(), 100);
+//!
+//! let job = CertificateJob::new("tests/fixtures/template.png", sample_names(4))
+//!     .font("tests/fixtures/DejaVuSans-Latin.ttf");
+//! let result = perf_test(job)?;
+//! assert_eq!(result.certificates, 4);
+//! assert!(result.certificates_per_second() > 0.0);
+//! # Ok::<(), certificate_maker::error::CertError>(())
+//! ```
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::{CertError, IoContext, Result};
+use crate::job::{CertificateJob, OverwritePolicy};
+use crate::timing::PerformanceBreakdown;
+
+const FIRST_NAMES: [&str; 8] = ["Ada", "Zoë", "Jean-Luc", "Mohammed", "Li", "Bartholomew", "Ngozi", "Søren"];
+const SURNAMES: [&str; 8] = ["Lovelace", "Okonkwo-Brandt", "Ångström", "Al-Khwarizmi", "Wei", "Featherstonehaugh", "Adeyemi", "Kierkegaard"];
+
+/// `count` made-up names of mixed lengths, hyphens and accents, like a real list. Every one is
+/// different up to 64; after that they repeat.
+pub fn sample_names(count: usize) -> Vec<String> {
+    (0..count)
+        .map(|index| {
+            let first = FIRST_NAMES[index % FIRST_NAMES.len()];
+            let surname = SURNAMES[(index + index / FIRST_NAMES.len()) % SURNAMES.len()];
+            format!("{} {}", first, surname)
+        })
+        .collect()
+}
+
+/// What a [`perf_test`] measured.
+#[derive(Debug, Clone)]
+pub struct PerfTestResult {
+    pub certificates: usize,
+    pub threads: usize,
+    /// Wall-clock time of the batch, hashing and the manifest included.
+    pub duration: Duration,
+    /// Average size of the certificates written.
+    pub bytes_per_certificate: u64,
+    pub performance: PerformanceBreakdown,
+}
+
+impl PerfTestResult {
+    pub fn certificates_per_second(&self) -> f64 {
+        self.certificates as f64 / self.duration.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// Run `job`, usually with [`sample_names`], into a scratch directory that is deleted
+/// afterwards; its output directory and overwrite policy are replaced. A certificate that
+/// fails fails the test, since the timing would be off.
+pub fn perf_test(job: CertificateJob) -> Result<PerfTestResult> {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.subsec_nanos()).unwrap_or_default();
+    let scratch: PathBuf = std::env::temp_dir().join(format!("certmaker-perf-test-{}-{}", std::process::id(), nanos));
+    let report = job.output_dir(&scratch)
+        .overwrite(OverwritePolicy::Overwrite)
+        .run();
+    // Sizes are read before the directory goes, from the manifest's entries, since a report
+    // under a memory budget keeps only the rows with problems
+    let bytes: u64 = report.iter()
+        .flat_map(|report| &report.entries)
+        .filter_map(|item| std::fs::metadata(&item.output_file).ok())
+        .map(|metadata| metadata.len())
+        .sum();
+    let removed = std::fs::remove_dir_all(&scratch)
+        .io_context(|| format!("Failed to remove the perf test's directory: {}", scratch.display()));
+    let report = report?;
+    removed?;
+    if let Some(error) = report.items.iter().find_map(|item| item.error.as_ref()) {
+        return Err(CertError::InvalidJob(vec![format!("A sample certificate failed: {}", error)]));
+    }
+    Ok(PerfTestResult {
+        certificates: report.generated,
+        threads: report.threads,
+        duration: report.duration,
+        bytes_per_certificate: bytes / report.generated.max(1) as u64,
+        performance: report.performance,
+    })
+}
@@ -0,0 +1,417 @@
+// src/placeholder.rs
+//! Finding a placeholder such as `«NAME»` that a designer left on a template mock-up, so its
+//! position can be used for the names instead of being measured by hand, and painting it out.
+//!
+//! [`find_placeholder`] marks the pixels that stand out from their surroundings, joins them
+//! into text lines and words, and compares every run of words shaped like the placeholder
+//! with the placeholder rendered in the chosen font, by normalized cross-correlation. Sizes
+//! don't need guessing: each candidate is compared at the placeholder's own scale, which also
+//! gives the font size it was set at. The font of the mock-up needn't be the one chosen, but
+//! the closer it is, the higher the [`score`](PlaceholderMatch::score).
+//!
+//! ```
+//! use certificate_maker::editpng::{Anchor, TextOptions, draw_text, load_font, load_template};
+//! use certificate_maker::placeholder::{erase_placeholder, find_placeholder};
+//! use image::Rgba;
+//!
+//! let font = load_font("tests/fixtures/DejaVuSans-Latin.ttf")?;
+//! let mut template = load_template("tests/fixtures/template.png")?;
+//! let blank = template.clone();
+//! let opts = TextOptions {
+//!     font: &font, size: 36.0, color: Rgba([30, 30, 60, 255]), x: 320, y: 210, anchor: Anchor::Center,
+//!     decorations: &[], synthetic: Default::default(), variation: None, effect: None,
+//! };
+//! draw_text(&mut template, "«NAME»", &opts)?;
+//!
+//! let found = find_placeholder(&template, "«NAME»", &font)?.expect("the placeholder is found");
+//! assert!((found.x - 320).abs() <= 2 && (found.y - 210).abs() <= 2, "found at ({}, {})", found.x, found.y);
+//! assert!((found.font_size - 36.0).abs() <= 2.0);
+//! assert!(found.score > 0.9);
+//!
+//! erase_placeholder(&mut template, &found);
+//! let bounds = found.bounds;
+//! assert_eq!(template.get_pixel(bounds.center_x() as u32, (bounds.y + bounds.height / 2) as u32),
+//!            blank.get_pixel(bounds.center_x() as u32, (bounds.y + bounds.height / 2) as u32));
+//! # Ok::<(), certificate_maker::error::CertError>(())
+//! ```
+use image::imageops::{self, FilterType};
+use image::{GrayImage, Luma, Rgba, RgbaImage};
+use imageproc::drawing::{draw_hollow_rect_mut, draw_line_segment_mut};
+use imageproc::rect::Rect;
+use rusttype::Font;
+
+use crate::editpng::{Anchor, TextBounds, TextOptions, draw_text};
+use crate::error::{CertError, Result};
+use crate::measure::measure_text;
+
+/// What designers usually put where the name goes.
+pub const DEFAULT_PLACEHOLDER: &str = "«NAME»";
+
+/// Lowest [`score`](PlaceholderMatch::score) [`find_placeholder`] accepts. Text in another font
+/// than the mock-up's typically scores 0.6 to 0.8, and unrelated words below 0.4.
+pub const MIN_PLACEHOLDER_SCORE: f32 = 0.5;
+
+// What mark_placeholder draws in
+const MARK_COLOR: Rgba<u8> = Rgba([220, 20, 60, 255]);
+
+// Size the placeholder is rendered at to compare candidates with
+const REFERENCE_SIZE: f32 = 64.0;
+// How far a pixel must be darker (or lighter) than its surroundings to count as ink
+const INK_CONTRAST: f32 = 36.0;
+// How far a candidate's width/height may be from the placeholder's, as a factor
+const ASPECT_TOLERANCE: f32 = 1.6;
+// Height candidates are compared at, blurred, so the strokes of another font still line up
+const COMPARE_HEIGHT: u32 = 16;
+const COMPARE_BLUR: f32 = 1.0;
+// Longest run of words tried as one candidate
+const MAX_WORDS: usize = 6;
+
+/// Where [`find_placeholder`] found the placeholder.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaceholderMatch {
+    /// The position to center names on so they sit where the placeholder does, as
+    /// [`Anchor::Center`](crate::editpng::Anchor::Center) centers text: on its ink
+    /// horizontally and on its line box vertically.
+    pub x: i32,
+    pub y: i32,
+    /// Box around the placeholder's ink on the template.
+    pub bounds: TextBounds,
+    /// Size the placeholder appears to be set at, in the chosen font.
+    pub font_size: f32,
+    /// Normalized cross-correlation with the rendered placeholder: 1.0 is a perfect match.
+    pub score: f32,
+    /// The color around the placeholder, which [`erase_placeholder`] paints it over with.
+    pub background: Rgba<u8>,
+}
+
+/// Look for `text` set in about `font` on `template`, returning the best match that scores at
+/// least [`MIN_PLACEHOLDER_SCORE`], or `None`.
+pub fn find_placeholder(template: &RgbaImage, text: &str, font: &Font) -> Result<Option<PlaceholderMatch>> {
+    let pattern = render_pattern(text, font)?;
+    let aspect = pattern.width() as f32 / pattern.height() as f32;
+    let compare_width = ((COMPARE_HEIGHT as f32 * aspect).round() as u32).max(1);
+    let reference = comparable(&pattern, compare_width, COMPARE_HEIGHT);
+    let (luma, dark_ink) = luminance(template);
+    let mask = ink_mask(&luma, dark_ink);
+
+    let mut best: Option<(f32, Bounds)> = None;
+    for line in text_lines(components(&mask, luma.width(), luma.height())) {
+        let words = split_words(&line);
+        for first in 0..words.len() {
+            for last in first..words.len().min(first + MAX_WORDS) {
+                let candidate = words[first].union(&words[last]);
+                let candidate_aspect = candidate.width() as f32 / candidate.height() as f32;
+                if candidate.height() < 6 || candidate_aspect > aspect * ASPECT_TOLERANCE {
+                    break;
+                }
+                if candidate_aspect < aspect / ASPECT_TOLERANCE {
+                    continue;
+                }
+                let score = correlation(&luma, dark_ink, &candidate, &reference);
+                if best.is_none_or(|(best_score, _)| score > best_score) {
+                    best = Some((score, candidate));
+                }
+            }
+        }
+    }
+
+    let Some((score, found)) = best.filter(|(score, _)| *score >= MIN_PLACEHOLDER_SCORE) else {
+        return Ok(None);
+    };
+    // Ink scales with the size, so the reference's ink height gives the size it was set at
+    let font_size = REFERENCE_SIZE * found.height() as f32 / pattern.height() as f32;
+    let metrics = measure_text(font, text, font_size, 0.0, None)?;
+    let bounds = TextBounds { x: found.left, y: found.top, width: found.width(), height: found.height() };
+    Ok(Some(PlaceholderMatch {
+        x: bounds.center_x() - metrics.ink_bbox.x,
+        y: found.top - metrics.ink_bbox.y + metrics.height() / 2,
+        bounds,
+        font_size: (font_size * 10.0).round() / 10.0,
+        score,
+        background: sample_background(template, &erase_box(&bounds, template)),
+    }))
+}
+
+/// Paint over the placeholder `found` on `template` with its [`background`](PlaceholderMatch::background),
+/// a little beyond its ink so anti-aliased edges go too.
+pub fn erase_placeholder(template: &mut RgbaImage, found: &PlaceholderMatch) {
+    let area = erase_box(&found.bounds, template);
+    for y in area.top..area.bottom {
+        for x in area.left..area.right {
+            template.put_pixel(x as u32, y as u32, found.background);
+        }
+    }
+}
+
+/// Draw a box around the placeholder `found` on `image`, and a cross where names will be
+/// centered, so the match can be checked before it is used.
+pub fn mark_placeholder(image: &mut RgbaImage, found: &PlaceholderMatch) {
+    let bounds = found.bounds;
+    for grow in 1..=2 {
+        let rect = Rect::at(bounds.x - grow, bounds.y - grow).of_size((bounds.width + 2 * grow) as u32, (bounds.height + 2 * grow) as u32);
+        draw_hollow_rect_mut(image, rect, MARK_COLOR);
+    }
+    let (x, y, arm) = (found.x as f32, found.y as f32, (bounds.height / 2).max(6) as f32);
+    draw_line_segment_mut(image, (x - arm, y), (x + arm, y), MARK_COLOR);
+    draw_line_segment_mut(image, (x, y - arm), (x, y + arm), MARK_COLOR);
+}
+
+// A box of pixels: left and top included, right and bottom not
+#[derive(Debug, Clone, Copy)]
+struct Bounds {
+    left: i32,
+    top: i32,
+    right: i32,
+    bottom: i32,
+}
+
+impl Bounds {
+    fn width(&self) -> i32 {
+        self.right - self.left
+    }
+
+    fn height(&self) -> i32 {
+        self.bottom - self.top
+    }
+
+    fn union(&self, other: &Bounds) -> Bounds {
+        Bounds {
+            left: self.left.min(other.left),
+            top: self.top.min(other.top),
+            right: self.right.max(other.right),
+            bottom: self.bottom.max(other.bottom),
+        }
+    }
+
+    fn vertical_overlap(&self, other: &Bounds) -> i32 {
+        self.bottom.min(other.bottom) - self.top.max(other.top)
+    }
+}
+
+// The placeholder drawn black on white, cropped to its ink
+fn render_pattern(text: &str, font: &Font) -> Result<GrayImage> {
+    let metrics = measure_text(font, text, REFERENCE_SIZE, 0.0, None)?;
+    let (width, height) = (metrics.ink_bbox.x + metrics.ink_bbox.width + 4, metrics.height() + 4);
+    let mut canvas = RgbaImage::from_pixel(width.max(1) as u32, height.max(1) as u32, Rgba([255, 255, 255, 255]));
+    let opts = TextOptions {
+        font,
+        size: REFERENCE_SIZE,
+        color: Rgba([0, 0, 0, 255]),
+        x: 2,
+        y: 2,
+        anchor: Anchor::TopLeft,
+        decorations: &[],
+        synthetic: Default::default(),
+        variation: None,
+        effect: None,
+    };
+    draw_text(&mut canvas, text, &opts)?;
+    let (luma, _) = luminance(&canvas);
+    let inked = |x: u32, y: u32| luma.get_pixel(x, y).0[0] < 160;
+    let columns: Vec<u32> = (0..luma.width()).filter(|&x| (0..luma.height()).any(|y| inked(x, y))).collect();
+    let rows: Vec<u32> = (0..luma.height()).filter(|&y| (0..luma.width()).any(|x| inked(x, y))).collect();
+    let (Some(&left), Some(&right), Some(&top), Some(&bottom)) = (columns.first(), columns.last(), rows.first(), rows.last()) else {
+        return Err(CertError::InvalidMeasurement(format!("'{}' draws nothing in this font, so it can't be looked for", text)));
+    };
+    Ok(imageops::crop_imm(&luma, left, top, right - left + 1, bottom - top + 1).to_image())
+}
+
+// Gray levels of `image` over white, and whether its ink is darker than its background,
+// judged from whether most of it is light
+fn luminance(image: &RgbaImage) -> (GrayImage, bool) {
+    let mut histogram = [0usize; 256];
+    let luma = GrayImage::from_fn(image.width(), image.height(), |x, y| {
+        let [r, g, b, a] = image.get_pixel(x, y).0;
+        let gray = 0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32;
+        let alpha = a as f32 / 255.0;
+        let level = (gray * alpha + 255.0 * (1.0 - alpha)).round() as u8;
+        histogram[level as usize] += 1;
+        Luma([level])
+    });
+    let half = (image.width() as usize * image.height() as usize) / 2;
+    let mut seen = 0;
+    let median = histogram.iter().position(|&count| {
+        seen += count;
+        seen > half
+    }).unwrap_or(255);
+    (luma, median >= 128)
+}
+
+// Pixels darker (lighter, for light ink) than the average of the square around them by
+// INK_CONTRAST, so faint background patterns and gradients don't count
+fn ink_mask(luma: &GrayImage, dark_ink: bool) -> Vec<bool> {
+    let (width, height) = (luma.width() as usize, luma.height() as usize);
+    let radius = (width.min(height) / 40).clamp(8, 40) as i64;
+    // Summed-area table with a zero row and column in front
+    let mut sums = vec![0u64; (width + 1) * (height + 1)];
+    for y in 0..height {
+        let mut row = 0u64;
+        for x in 0..width {
+            row += luma.as_raw()[y * width + x] as u64;
+            sums[(y + 1) * (width + 1) + x + 1] = sums[y * (width + 1) + x + 1] + row;
+        }
+    }
+    let mut mask = vec![false; width * height];
+    for y in 0..height {
+        let (top, bottom) = ((y as i64 - radius).max(0) as usize, (y as i64 + radius + 1).min(height as i64) as usize);
+        for x in 0..width {
+            let (left, right) = ((x as i64 - radius).max(0) as usize, (x as i64 + radius + 1).min(width as i64) as usize);
+            let total = sums[bottom * (width + 1) + right] + sums[top * (width + 1) + left]
+                - sums[top * (width + 1) + right] - sums[bottom * (width + 1) + left];
+            let mean = total as f32 / ((bottom - top) * (right - left)) as f32;
+            let level = luma.as_raw()[y * width + x] as f32;
+            mask[y * width + x] = if dark_ink { level < mean - INK_CONTRAST } else { level > mean + INK_CONTRAST };
+        }
+    }
+    mask
+}
+
+// Boxes of the 8-connected groups of ink pixels, without specks and without anything as large
+// as a frame or an illustration
+fn components(mask: &[bool], width: u32, height: u32) -> Vec<Bounds> {
+    let (width, height) = (width as usize, height as usize);
+    let mut parent: Vec<usize> = (0..mask.len()).collect();
+    fn root(parent: &mut [usize], mut index: usize) -> usize {
+        while parent[index] != index {
+            parent[index] = parent[parent[index]];
+            index = parent[index];
+        }
+        index
+    }
+    for y in 0..height {
+        for x in 0..width {
+            let index = y * width + x;
+            if !mask[index] {
+                continue;
+            }
+            // Neighbours already visited: left, and the three above
+            let mut neighbours = Vec::with_capacity(4);
+            if x > 0 {
+                neighbours.push(index - 1);
+            }
+            if y > 0 {
+                neighbours.push(index - width);
+                if x > 0 {
+                    neighbours.push(index - width - 1);
+                }
+                if x + 1 < width {
+                    neighbours.push(index - width + 1);
+                }
+            }
+            for neighbour in neighbours.into_iter().filter(|&neighbour| mask[neighbour]) {
+                let (a, b) = (root(&mut parent, index), root(&mut parent, neighbour));
+                if a != b {
+                    parent[a.max(b)] = a.min(b);
+                }
+            }
+        }
+    }
+
+    let mut boxes: std::collections::HashMap<usize, (Bounds, usize)> = std::collections::HashMap::new();
+    for index in (0..mask.len()).filter(|&index| mask[index]) {
+        let (x, y) = ((index % width) as i32, (index / width) as i32);
+        let pixel = Bounds { left: x, top: y, right: x + 1, bottom: y + 1 };
+        boxes.entry(root(&mut parent, index))
+            .and_modify(|(bounds, count)| {
+                *bounds = bounds.union(&pixel);
+                *count += 1;
+            })
+            .or_insert((pixel, 1));
+    }
+    boxes.into_values()
+        .filter(|(bounds, count)| *count >= 3 && bounds.width() < width as i32 / 3 && bounds.height() < height as i32 / 4)
+        .map(|(bounds, _)| bounds)
+        .collect()
+}
+
+// Components joined left to right into lines of text: side by side, overlapping vertically,
+// and no further apart than the line is tall
+fn text_lines(mut components: Vec<Bounds>) -> Vec<Vec<Bounds>> {
+    components.sort_by_key(|bounds| (bounds.left, bounds.top));
+    let mut lines: Vec<(Bounds, Vec<Bounds>)> = Vec::new();
+    for component in components {
+        let joins = lines.iter_mut().find(|(line, _)| {
+            let overlap = line.vertical_overlap(&component);
+            overlap * 2 >= line.height().min(component.height())
+                && component.left - line.right <= line.height().max(component.height())
+        });
+        match joins {
+            Some((line, members)) => {
+                *line = line.union(&component);
+                members.push(component);
+            }
+            None => lines.push((component, vec![component])),
+        }
+    }
+    lines.into_iter().map(|(_, members)| members).collect()
+}
+
+// A line's components grouped into words, at gaps wider than a quarter of the line's height
+fn split_words(line: &[Bounds]) -> Vec<Bounds> {
+    let height = line.iter().map(Bounds::height).max().unwrap_or_default();
+    let mut words: Vec<Bounds> = Vec::new();
+    for component in line {
+        match words.last_mut() {
+            Some(word) if (component.left - word.right) * 4 <= height => *word = word.union(component),
+            _ => words.push(*component),
+        }
+    }
+    words
+}
+
+// Pearson correlation between the candidate, scaled to the pattern's size with its ink made
+// dark, and the pattern
+fn correlation(luma: &GrayImage, dark_ink: bool, candidate: &Bounds, pattern: &GrayImage) -> f32 {
+    let crop = imageops::crop_imm(luma, candidate.left as u32, candidate.top as u32, candidate.width() as u32, candidate.height() as u32);
+    let scaled = comparable(&crop.to_image(), pattern.width(), pattern.height());
+    let level = |value: u8| if dark_ink { value as f32 } else { 255.0 - value as f32 };
+    let count = (pattern.width() * pattern.height()) as f32;
+    let mean_a = scaled.pixels().map(|pixel| level(pixel.0[0])).sum::<f32>() / count;
+    let mean_b = pattern.pixels().map(|pixel| pixel.0[0] as f32).sum::<f32>() / count;
+    let (mut cross, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+    for (a, b) in scaled.pixels().zip(pattern.pixels()) {
+        let (a, b) = (level(a.0[0]) - mean_a, b.0[0] as f32 - mean_b);
+        cross += a * b;
+        var_a += a * a;
+        var_b += b * b;
+    }
+    if var_a == 0.0 || var_b == 0.0 { 0.0 } else { cross / (var_a * var_b).sqrt() }
+}
+
+// `image` at the comparison size, blurred
+fn comparable(image: &GrayImage, width: u32, height: u32) -> GrayImage {
+    imageops::blur(&imageops::resize(image, width, height, FilterType::Triangle), COMPARE_BLUR)
+}
+
+// The ink box grown by a sixth of its height (at least 2 pixels), kept on the image
+fn erase_box(bounds: &TextBounds, image: &RgbaImage) -> Bounds {
+    let pad = (bounds.height / 6).max(2);
+    Bounds {
+        left: (bounds.x - pad).max(0),
+        top: (bounds.y - pad).max(0),
+        right: (bounds.x + bounds.width + pad).min(image.width() as i32),
+        bottom: (bounds.y + bounds.height + pad).min(image.height() as i32),
+    }
+}
+
+// Median of each channel over a 3-pixel ring just outside `area`
+fn sample_background(image: &RgbaImage, area: &Bounds) -> Rgba<u8> {
+    let ring = 3;
+    let mut channels: [Vec<u8>; 4] = Default::default();
+    for y in (area.top - ring).max(0)..(area.bottom + ring).min(image.height() as i32) {
+        for x in (area.left - ring).max(0)..(area.right + ring).min(image.width() as i32) {
+            if x >= area.left && x < area.right && y >= area.top && y < area.bottom {
+                continue;
+            }
+            for (channel, value) in channels.iter_mut().zip(image.get_pixel(x as u32, y as u32).0) {
+                channel.push(value);
+            }
+        }
+    }
+    let median = |values: &mut Vec<u8>| {
+        values.sort_unstable();
+        values.get(values.len() / 2).copied().unwrap_or(255)
+    };
+    let [mut r, mut g, mut b, mut a] = channels;
+    Rgba([median(&mut r), median(&mut g), median(&mut b), median(&mut a)])
+}
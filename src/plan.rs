@@ -0,0 +1,328 @@
+// src/plan.rs
+//! Plans: a batch worked out row by row before anything is drawn, to review, diff or sign off,
+//! then rendered exactly as written down.
+//!
+//! [`plan_batch`] makes a dry run of a batch and keeps, for every certificate it would write,
+//! the text drawn (after the case transform, the typography pass and cutting short), the
+//! output file, the text's box once centered, the font size once shrunk to fit, and the row's
+//! warnings. A [`BatchPlan`] is saved as JSON, or as CSV for a spreadsheet. [`execute_plan`]
+//! renders a JSON plan with each name in the plan's box at the plan's size and into the plan's
+//! file; nothing of them is measured or decided again. Only the graphics are filled in from
+//! the row's columns, which the plan carries.
+//!
+//! A plan records the [`PLAN_VERSION`] it was written in, and the hashes of its template and
+//! font: it is refused once either has changed, since its boxes were measured on them.
+//!
+//! ```
+//! use certificate_maker::csvexcelparser::BatchSettings;
+//! use certificate_maker::plan::{BatchPlan, execute_plan, plan_batch};
+//! use certificate_maker::progress::NoProgress;
+//!
+//! let dir = std::env::temp_dir().join(format!("certmaker-plan-{}", std::process::id()));
+//! let settings = BatchSettings {
+//!     template_file: "tests/fixtures/template.png".into(),
+//!     font_filename: "tests/fixtures/DejaVuSans-Latin.ttf".into(),
+//!     font_size: 32.0,
+//!     hex_color: "#000000".into(),
+//!     x_pos: 300,
+//!     y_pos: 200,
+//!     output_dir: dir.join("certificates"),
+//!     ..Default::default()
+//! };
+//! let plan = plan_batch(&settings, &["Ada Lovelace".to_string(), "Grace Hopper".to_string()])?;
+//! assert!(!dir.join("certificates/Ada_Lovelace.png").exists());
+//! let ada = &plan.rows[0];
+//! assert_eq!((ada.text.as_str(), ada.font_size), ("Ada Lovelace", 32.0));
+//! assert_eq!((ada.x + ada.width / 2, ada.y + ada.height / 2), (300, 200));
+//!
+//! // Saved, reviewed, then rendered as written
+//! plan.save(dir.join("plan.json"))?;
+//! plan.save(dir.join("plan.csv"))?;
+//! assert!(std::fs::read_to_string(dir.join("plan.csv"))?.starts_with("row,name,text,output_file,x,y"));
+//! let report = execute_plan(&BatchPlan::load(dir.join("plan.json"))?, &NoProgress)?;
+//! assert_eq!(report.generated, 2);
+//! assert_eq!(report.items[0].position, (ada.x, ada.y));
+//! assert!(ada.output_file.exists());
+//! std::fs::remove_dir_all(&dir)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::csvexcelparser::{BatchItem, BatchReport, BatchSettings, batch_job, run_job};
+use crate::editpng::load_font_data;
+use crate::error::{CertError, IoContext, Result};
+use crate::manifest::{EllipsizedText, Manifest, hash_bytes, hash_file};
+use crate::progress::ProgressSink;
+use crate::sanitize::csv_safe;
+use crate::shard::ShardRecord;
+use crate::source::Record;
+use crate::timing::StageTimings;
+
+/// Version of the plan format this build writes and executes.
+pub const PLAN_VERSION: u64 = 1;
+
+/// Every certificate of a batch as it will be rendered; see the [module docs](self).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchPlan {
+    /// The [`PLAN_VERSION`] it was written in.
+    pub version: u64,
+    /// Settings of the batch, so a saved plan runs on its own.
+    pub settings: BatchSettings,
+    /// The batch is reproducible; see [`CertificateJob::deterministic`](crate::job::CertificateJob::deterministic).
+    #[serde(default)]
+    pub deterministic: bool,
+    /// Hashes of the template and the font the boxes were measured with.
+    pub template_hash: String,
+    pub font_hash: String,
+    /// Rows of the list the plan leaves alone: their certificates are unchanged since the last
+    /// run, or a file this tool didn't write is in the way.
+    pub skipped_unchanged: usize,
+    pub skipped_existing: usize,
+    pub rows: Vec<PlannedRow>,
+}
+
+/// One certificate as it will be rendered.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlannedRow {
+    /// Line number in its list, when it came from one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub row: Option<usize>,
+    /// The name as the list has it.
+    pub name: String,
+    /// The text drawn, after the case transform, the typography pass and cutting short.
+    pub text: String,
+    pub output_file: PathBuf,
+    /// Top-left corner of the text's box, once centered on the position.
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    /// Font size the text is set at, once shrunk to fit.
+    pub font_size: f32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// Values cut short to fit, each with its whole text.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ellipsized: Vec<EllipsizedText>,
+    /// Why the row can't be rendered; it is reported as failed again without being drawn.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// List the name came from when several were merged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub email: String,
+    /// Every column of the row as `(header, value)`, for the graphics' placeholders.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<(String, String)>,
+}
+
+impl PlannedRow {
+    /// The name's whole text: [`text`](Self::text), unless that was cut short.
+    pub fn full_text(&self) -> &str {
+        self.ellipsized.iter()
+            .find(|value| value.field == "name")
+            .map_or(&self.text, |value| &value.full)
+    }
+}
+
+impl BatchPlan {
+    /// Write the plan to `path`: as CSV, one line per certificate, when it ends in `.csv`,
+    /// otherwise as the JSON [`load`](Self::load) reads back.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let content = if is_csv(path) {
+            self.to_csv(path)?
+        } else {
+            serde_json::to_string_pretty(self)
+                .map_err(|source| CertError::Json { context: "Failed to serialize batch plan".to_string(), source })?
+        };
+        std::fs::write(path, content)
+            .io_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// A plan saved as JSON. Plans of another [`PLAN_VERSION`] are refused rather than half read.
+    pub fn load(path: impl AsRef<Path>) -> Result<BatchPlan> {
+        let path = path.as_ref();
+        if is_csv(path) {
+            return Err(CertError::PlanNotRunnable(path.to_path_buf()));
+        }
+        let content = std::fs::read_to_string(path)
+            .io_context(|| format!("Failed to read {}", path.display()))?;
+        let json_error = |source| CertError::Json { context: format!("Failed to parse {}", path.display()), source };
+        let value: serde_json::Value = serde_json::from_str(&content).map_err(json_error)?;
+        let version = value.get("version").and_then(serde_json::Value::as_u64).unwrap_or_default();
+        if version != PLAN_VERSION {
+            return Err(CertError::PlanVersion { path: path.to_path_buf(), version });
+        }
+        serde_json::from_value(value).map_err(json_error)
+    }
+
+    /// Fail if the template or the font changed since the plan was made.
+    pub fn check_inputs(&self) -> Result<()> {
+        if hash_file(&self.settings.template_file)? != self.template_hash {
+            return Err(CertError::PlanOutdated(self.settings.template_file.clone()));
+        }
+        if hash_bytes(&load_font_data(&self.settings.font_filename)?) != self.font_hash {
+            return Err(CertError::PlanOutdated(self.settings.font_filename.clone()));
+        }
+        Ok(())
+    }
+
+    /// Hash of the plan as it will be executed, the same on every machine given the same file;
+    /// shards record it so that shards of different plans aren't merged.
+    pub fn id(&self) -> Result<String> {
+        let json = serde_json::to_vec(self)
+            .map_err(|source| CertError::Json { context: "Failed to serialize batch plan".to_string(), source })?;
+        Ok(hash_bytes(&json))
+    }
+
+    /// Certificates the plan will write, leaving out the rows that already failed.
+    pub fn renderable(&self) -> usize {
+        self.rows.iter().filter(|row| row.error.is_none()).count()
+    }
+
+    // One line per row; `path` only names the file in errors
+    fn to_csv(&self, path: &Path) -> Result<String> {
+        let csv_error = |source| CertError::Csv { path: path.to_path_buf(), source };
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(["row", "name", "text", "output_file", "x", "y", "width", "height", "font_size", "warnings", "error"])
+            .map_err(csv_error)?;
+        // Text cells hold names from the list, so a spreadsheet opening the plan mustn't run them
+        let text = |text: &str| csv_safe(text).into_owned();
+        for row in &self.rows {
+            writer.write_record([
+                row.row.map(|row| row.to_string()).unwrap_or_default(),
+                text(&row.name),
+                text(&row.text),
+                text(&row.output_file.display().to_string()),
+                row.x.to_string(),
+                row.y.to_string(),
+                row.width.to_string(),
+                row.height.to_string(),
+                row.font_size.to_string(),
+                text(&row.warnings.join("; ")),
+                text(row.error.as_deref().unwrap_or_default()),
+            ]).map_err(csv_error)?;
+        }
+        let content = writer.into_inner().map_err(|e| csv_error(e.into_error().into()))?;
+        Ok(String::from_utf8_lossy(&content).into_owned())
+    }
+}
+
+/// Lay out the batch `run_batch` would run for `settings` and `names`, without drawing or
+/// saving anything; like a dry run, it leaves the would-be manifest in the output directory.
+pub fn plan_batch(settings: &BatchSettings, names: &[String]) -> Result<BatchPlan> {
+    let dry_run = BatchSettings { dry_run: true, ..settings.clone() };
+    let report = batch_job(&dry_run, names)?.run()?;
+    let rows = report.items.iter().map(|item| planned_row(settings, names, item)).collect();
+    Ok(BatchPlan {
+        version: PLAN_VERSION,
+        settings: settings.clone(),
+        deterministic: settings.deterministic,
+        template_hash: hash_file(&settings.template_file)?,
+        font_hash: hash_bytes(&load_font_data(&settings.font_filename)?),
+        skipped_unchanged: report.skipped_unchanged,
+        skipped_existing: report.skipped_existing,
+        rows,
+    })
+}
+
+/// Render every row of `plan` as planned, after checking its template and font are the ones
+/// it was made with. Rows that failed in the plan are reported as failed again. Certificates of
+/// rows the plan left alone stay in the manifest.
+pub fn execute_plan(plan: &BatchPlan, progress: &dyn ProgressSink) -> Result<BatchReport> {
+    let indexes: Vec<usize> = (0..plan.rows.len()).collect();
+    execute_rows(plan, &indexes, None, progress)
+}
+
+// The plan's rows at `indexes`; a shard's manifest is saved as the shard's own file
+pub(crate) fn execute_rows(plan: &BatchPlan, indexes: &[usize], shard: Option<ShardRecord>, progress: &dyn ProgressSink) -> Result<BatchReport> {
+    plan.check_inputs()?;
+    let (renderable, failed): (Vec<_>, Vec<_>) = indexes.iter()
+        .map(|&index| (index, &plan.rows[index]))
+        .partition(|(_, row)| row.error.is_none());
+    let rows: Vec<&PlannedRow> = renderable.iter().map(|(_, row)| *row).collect();
+    let names: Vec<String> = rows.iter().map(|row| row.name.clone()).collect();
+    // The columns the settings don't remember, from the rows themselves
+    let settings = BatchSettings {
+        rows: rows.iter().map(|row| row.row).collect::<Option<_>>().unwrap_or_default(),
+        sources: rows.iter().map(|row| row.source.clone()).collect::<Option<_>>().unwrap_or_default(),
+        emails: rows.iter().map(|row| row.email.clone()).collect(),
+        records: rows.iter().map(|row| Record { name: row.name.clone(), fields: row.fields.clone() }).collect(),
+        deterministic: plan.deterministic,
+        dry_run: false,
+        ..plan.settings.clone()
+    };
+    let mut report = if rows.is_empty() {
+        // Nothing to draw, but a shard still says it ran, so the merge doesn't miss it
+        if let Some(record) = shard {
+            let dir = &plan.settings.output_dir;
+            std::fs::create_dir_all(dir)
+                .io_context(|| format!("Failed to create output directory: {}", dir.display()))?;
+            Manifest { shard: Some(record.clone()), ..Manifest::default() }.save_as(dir.join(record.file_name()))?;
+        }
+        BatchReport::default()
+    } else {
+        let mut job = batch_job(&settings, &names)?
+            .planned(rows.iter().map(|row| (*row).clone()).collect())
+            .partial(true);
+        if let Some(record) = shard {
+            job = job.shard(record);
+        }
+        run_job(&settings, job, progress)?
+    };
+
+    // Indexes among the whole plan, failed rows in their places
+    for item in &mut report.items {
+        item.index = renderable[item.index].0;
+    }
+    for (index, row) in failed {
+        report.items.push(BatchItem {
+            index,
+            name: row.name.clone(),
+            output_file: row.output_file.clone(),
+            position: (row.x, row.y),
+            error: row.error.clone(),
+            warnings: row.warnings.clone(),
+            ellipsized: row.ellipsized.clone(),
+            retouched: Vec::new(),
+            timings: StageTimings::default(),
+            drawn: None,
+        });
+        report.failed += 1;
+    }
+    report.items.sort_by_key(|item| item.index);
+    Ok(report)
+}
+
+// What the dry run decided for one row, with the row's own columns
+fn planned_row(settings: &BatchSettings, names: &[String], item: &BatchItem) -> PlannedRow {
+    let index = item.index;
+    let (text, font_size, width, height) = match &item.drawn {
+        Some(drawn) => (drawn.text.clone(), drawn.font_size, drawn.width, drawn.height),
+        None => (item.name.clone(), settings.font_size, 0, 0),
+    };
+    PlannedRow {
+        row: settings.rows.get(index).copied(),
+        name: names.get(index).cloned().unwrap_or_else(|| item.name.clone()),
+        text,
+        output_file: item.output_file.clone(),
+        x: item.position.0,
+        y: item.position.1,
+        width,
+        height,
+        font_size,
+        warnings: item.warnings.clone(),
+        ellipsized: item.ellipsized.clone(),
+        error: item.error.clone(),
+        source: settings.sources.get(index).cloned(),
+        email: settings.emails.get(index).cloned().unwrap_or_default(),
+        fields: settings.records.get(index).map(|record| record.fields.clone()).unwrap_or_default(),
+    }
+}
+
+fn is_csv(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("csv"))
+}
@@ -0,0 +1,64 @@
+// src/preview.rs
+//! Small JPEG previews of rendered certificates, for pages that show a thumbnail of each one
+//! rather than download the full-size PNG.
+//!
+//! The batch makes a preview from the certificate it just drew, before the image is dropped,
+//! so the PNG is never read back. It is saved next to the certificate as
+//! `<certificate>_preview.jpg` (see [`CertificateJob::preview`](crate::job::CertificateJob::preview)).
+//!
+//! ```
+//! use certificate_maker::preview::{encode_preview, preview_path, render_preview};
+//! use image::{Rgba, RgbaImage};
+//! use std::path::Path;
+//!
+//! let certificate = RgbaImage::from_pixel(3840, 2160, Rgba([250, 245, 230, 255]));
+//! let preview = render_preview(&certificate, 600);
+//! assert_eq!(preview.dimensions(), (600, 338));
+//! assert!(encode_preview(&preview)?.starts_with(&[0xFF, 0xD8]));
+//! assert_eq!(preview_path(Path::new("certs/certificate_Ada.png")), Path::new("certs/certificate_Ada_preview.jpg"));
+//! # Ok::<(), image::ImageError>(())
+//! ```
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::{self, FilterType};
+use image::{ColorType, ImageResult, Rgb, RgbImage, RgbaImage};
+use std::path::{Path, PathBuf};
+
+/// Longest side of a preview when no size is given, in pixels.
+pub const DEFAULT_PREVIEW_SIZE: u32 = 600;
+
+/// JPEG quality of the previews; lower than the PDF pages, since they are only looked at on screen.
+pub const PREVIEW_JPEG_QUALITY: u8 = 85;
+
+/// Where the preview of `certificate` goes: the same directory and name, with `_preview.jpg`
+/// instead of the extension.
+pub fn preview_path(certificate: &Path) -> PathBuf {
+    let stem = certificate.file_stem().unwrap_or_default().to_string_lossy();
+    certificate.with_file_name(format!("{}_preview.jpg", stem))
+}
+
+/// `certificate` scaled down with Lanczos3 so its longer side is `max_dimension`, keeping the
+/// aspect ratio, and flattened onto white since JPEG has no transparency. A certificate that
+/// is already small enough keeps its size.
+pub fn render_preview(certificate: &RgbaImage, max_dimension: u32) -> RgbImage {
+    let (width, height) = certificate.dimensions();
+    let longer = width.max(height);
+    let scaled = if longer > max_dimension {
+        let scale = |side: u32| ((side as u64 * max_dimension as u64 + longer as u64 / 2) / longer as u64).max(1) as u32;
+        imageops::resize(certificate, scale(width), scale(height), FilterType::Lanczos3)
+    } else {
+        certificate.clone()
+    };
+    RgbImage::from_fn(scaled.width(), scaled.height(), |x, y| {
+        let [r, g, b, a] = scaled.get_pixel(x, y).0;
+        let over_white = |channel: u8| ((channel as u32 * a as u32 + 255 * (255 - a as u32)) / 255) as u8;
+        Rgb([over_white(r), over_white(g), over_white(b)])
+    })
+}
+
+/// Encode a preview as a JPEG at [`PREVIEW_JPEG_QUALITY`].
+pub fn encode_preview(preview: &RgbImage) -> ImageResult<Vec<u8>> {
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, PREVIEW_JPEG_QUALITY)
+        .encode(preview.as_raw(), preview.width(), preview.height(), ColorType::Rgb8)?;
+    Ok(jpeg)
+}
@@ -0,0 +1,125 @@
+// src/progress.rs
+//! Progress reporting for batch runs, so other front-ends can show their own progress.
+//!
+//! A GUI can hand a [`ChannelSink`] to the job and drain the receiver on its own thread:
+//!
+//! ```no_run
+//! use certificate_maker::job::CertificateJob;
+//! use certificate_maker::progress::{ChannelSink, ProgressEvent};
+//!
+//! let (sink, events) = ChannelSink::new();
+//! let job = CertificateJob::new("Template/certificate.png", vec!["Alice Johnson".to_string()]);
+//! let worker = std::thread::spawn(move || job.run_with_progress(&sink));
+//!
+//! for event in events {
+//!     match event {
+//!         ProgressEvent::Started { total } => println!("0 / {}", total),
+//!         ProgressEvent::Item { index, item } => println!("{} done: {}", index, item.name),
+//!         ProgressEvent::Finished(report) => println!("{} generated", report.generated),
+//!     }
+//! }
+//! worker.join().unwrap()?;
+//! # Ok::<(), certificate_maker::error::CertError>(())
+//! ```
+use std::any::Any;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, Sender, channel};
+
+use crate::csvexcelparser::{BatchItem, BatchReport};
+
+/// Receives progress from a running batch.
+///
+/// `on_item` is called from the rayon worker threads. A panic in any method is caught
+/// and recorded in [`BatchReport::progress_errors`] instead of stopping the batch.
+pub trait ProgressSink: Send + Sync {
+    /// Called once before rendering, with the number of rows that will be rendered.
+    fn on_start(&self, _total: usize) {}
+
+    /// Called after each row; `index` counts completed rows starting at 1.
+    fn on_item(&self, _index: usize, _item: &BatchItem) {}
+
+    /// Called once after the manifest has been saved.
+    fn on_finish(&self, _report: &BatchReport) {}
+}
+
+/// Sink that ignores all progress.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {}
+
+/// Adapts a `progress(completed, total, item)` closure to a [`ProgressSink`].
+pub struct FnSink<F> {
+    callback: F,
+    total: AtomicUsize,
+}
+
+impl<F: Fn(usize, usize, &BatchItem) + Send + Sync> FnSink<F> {
+    pub fn new(callback: F) -> Self {
+        FnSink { callback, total: AtomicUsize::new(0) }
+    }
+}
+
+impl<F: Fn(usize, usize, &BatchItem) + Send + Sync> ProgressSink for FnSink<F> {
+    fn on_start(&self, total: usize) {
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    fn on_item(&self, index: usize, item: &BatchItem) {
+        (self.callback)(index, self.total.load(Ordering::Relaxed), item);
+    }
+}
+
+/// One progress notification sent by [`ChannelSink`].
+#[derive(Debug, Clone)]
+pub enum ProgressEvent {
+    Started { total: usize },
+    Item { index: usize, item: BatchItem },
+    Finished(BatchReport),
+}
+
+/// Forwards progress to an mpsc channel, for UIs that poll from their own thread.
+///
+/// Events sent after the receiver is dropped are discarded.
+pub struct ChannelSink {
+    sender: Sender<ProgressEvent>,
+}
+
+impl ChannelSink {
+    pub fn new() -> (ChannelSink, Receiver<ProgressEvent>) {
+        let (sender, receiver) = channel();
+        (ChannelSink { sender }, receiver)
+    }
+}
+
+impl ProgressSink for ChannelSink {
+    fn on_start(&self, total: usize) {
+        let _ = self.sender.send(ProgressEvent::Started { total });
+    }
+
+    fn on_item(&self, index: usize, item: &BatchItem) {
+        let _ = self.sender.send(ProgressEvent::Item { index, item: item.clone() });
+    }
+
+    fn on_finish(&self, report: &BatchReport) {
+        let _ = self.sender.send(ProgressEvent::Finished(report.clone()));
+    }
+}
+
+// Run a sink callback, turning a panic into an error message
+pub(crate) fn call_sink(callback: impl FnOnce()) -> Option<String> {
+    catch_unwind(AssertUnwindSafe(callback))
+        .err()
+        .map(|payload| format!("Progress sink panicked: {}", panic_message(payload.as_ref())))
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
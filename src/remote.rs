@@ -0,0 +1,158 @@
+// src/remote.rs
+//! Name lists downloaded from `https://` URLs, such as a Google Sheets CSV export.
+//!
+//! Only compiled with the `remote` feature. Downloads go to a temporary file that is
+//! parsed like any local list, encoding and delimiter detection included.
+//!
+//! ```
+//! use certificate_maker::remote::google_sheets_export_url;
+//!
+//! assert_eq!(
+//!     google_sheets_export_url("https://docs.google.com/spreadsheets/d/abc123/edit#gid=42").as_deref(),
+//!     Some("https://docs.google.com/spreadsheets/d/abc123/export?format=csv&gid=42"),
+//! );
+//! assert_eq!(google_sheets_export_url("https://example.com/roster.csv"), None);
+//! ```
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::error::{CertError, IoContext, Result};
+
+/// Largest name list that will be downloaded.
+pub const MAX_DOWNLOAD_BYTES: u64 = 20_000_000;
+/// How long a download may take from connecting to the last byte.
+pub const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+const SHEETS_PREFIX: &str = "https://docs.google.com/spreadsheets/d/";
+
+/// The CSV export URL of a Google Sheets link, keeping the tab (`gid`) it points at; `None` for
+/// other URLs and for links that already are exports.
+pub fn google_sheets_export_url(url: &str) -> Option<String> {
+    let rest = url.strip_prefix(SHEETS_PREFIX)?;
+    let (published, rest) = match rest.strip_prefix("e/") {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+    let id_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let (id, tail) = rest.split_at(id_end);
+    if id.is_empty() || tail.starts_with("/export") || tail.starts_with("/pub?") {
+        return None;
+    }
+
+    let gid = tail.split(['?', '#', '&'])
+        .find_map(|part| part.strip_prefix("gid="))
+        .filter(|gid| !gid.is_empty() && gid.chars().all(|c| c.is_ascii_digit()));
+    let mut export = if published {
+        format!("{}e/{}/pub?output=csv", SHEETS_PREFIX, id)
+    } else {
+        format!("{}{}/export?format=csv", SHEETS_PREFIX, id)
+    };
+    if let Some(gid) = gid {
+        export.push_str("&gid=");
+        export.push_str(gid);
+    }
+    Some(export)
+}
+
+/// A downloaded name list in a temporary file, deleted when this is dropped.
+#[derive(Debug)]
+pub struct Download {
+    path: PathBuf,
+}
+
+impl Download {
+    /// The temporary file, with the extension of the list it holds.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Download {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Download a name list, rewriting Google Sheets links to their CSV export first.
+pub fn download_name_list(url: &str) -> Result<Download> {
+    let fetch_url = google_sheets_export_url(url).unwrap_or_else(|| url.to_string());
+    let body = fetch(url, &fetch_url, MAX_DOWNLOAD_BYTES)?;
+    let download = Download { path: temp_path(&fetch_url) };
+    std::fs::write(&download.path, body)
+        .io_context(|| format!("Failed to save download to {}", download.path.display()))?;
+    Ok(download)
+}
+
+// GET `fetch_url` into memory, refusing web pages and anything over `limit` bytes; errors name
+// `url`, the address as the user gave it
+pub(crate) fn fetch(url: &str, fetch_url: &str, limit: u64) -> Result<Vec<u8>> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(DOWNLOAD_TIMEOUT)
+        .build()
+        .map_err(|source| CertError::Remote { url: url.to_string(), source })?;
+    let response = client.get(fetch_url).send().map_err(|source| request_error(url, source))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        return Err(CertError::RemoteStatus { url: url.to_string(), status });
+    }
+    let is_html = response.headers().get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"));
+    if is_html {
+        return Err(CertError::RemoteHtml { url: url.to_string() });
+    }
+    if response.content_length().is_some_and(|length| length > limit) {
+        return Err(CertError::RemoteTooLarge { url: url.to_string(), limit });
+    }
+
+    // The declared length can be missing or wrong, so cap what is actually read too
+    let mut body = Vec::new();
+    response.take(limit + 1).read_to_end(&mut body)
+        .map_err(|e| match e.get_ref().and_then(|inner| inner.downcast_ref::<reqwest::Error>()) {
+            Some(source) if source.is_timeout() => CertError::RemoteTimeout { url: url.to_string(), seconds: DOWNLOAD_TIMEOUT.as_secs() },
+            _ => CertError::Io { context: format!("Failed to download {}", url), source: e },
+        })?;
+    if body.len() as u64 > limit {
+        return Err(CertError::RemoteTooLarge { url: url.to_string(), limit });
+    }
+    Ok(body)
+}
+
+// Sort a failed request into DNS, connection, timeout or anything else
+fn request_error(url: &str, source: reqwest::Error) -> CertError {
+    if source.is_timeout() {
+        return CertError::RemoteTimeout { url: url.to_string(), seconds: DOWNLOAD_TIMEOUT.as_secs() };
+    }
+    if source.is_connect() {
+        // reqwest has no DNS flag; the resolver's error is somewhere in the source chain, and
+        // the innermost cause ("Connection refused") says more than reqwest's own message
+        let mut reason = source.to_string();
+        let mut cause: Option<&dyn std::error::Error> = Some(&source);
+        while let Some(error) = cause {
+            let message = error.to_string();
+            if message.contains("dns error") || message.contains("failed to lookup address") {
+                return CertError::RemoteDns { url: url.to_string() };
+            }
+            reason = message;
+            cause = error.source();
+        }
+        return CertError::RemoteConnect { url: url.to_string(), reason };
+    }
+    CertError::Remote { url: url.to_string(), source }
+}
+
+// A fresh file in the temp directory, named so the parser picks the right format
+fn temp_path(fetch_url: &str) -> PathBuf {
+    let url_path = fetch_url.split(['?', '#']).next().unwrap_or(fetch_url);
+    let extension = match Path::new(url_path).extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("json") => "json",
+        Some(ext) if ext.eq_ignore_ascii_case("txt") => "txt",
+        _ => "csv",
+    };
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.subsec_nanos());
+    std::env::temp_dir().join(format!("certmaker-download-{}-{}.{}", std::process::id(), nanos, extension))
+}
@@ -0,0 +1,225 @@
+// src/runlog.rs
+//! A timestamped record of each batch run, independent of what the console shows.
+//!
+//! Every line is written through as soon as it is logged, so a run that panics or is stopped
+//! with Ctrl+C still leaves everything up to that point on disk. Rows are logged from the
+//! worker threads through [`LoggedProgress`], which wraps whatever sink the front-end uses:
+//!
+//! ```no_run
+//! use certificate_maker::job::CertificateJob;
+//! use certificate_maker::progress::NoProgress;
+//! use certificate_maker::runlog::{LoggedProgress, RunLog};
+//!
+//! let log = RunLog::create("output")?;
+//! let job = CertificateJob::new("Template/certificate.png", vec!["Alice Johnson".to_string()]);
+//! job.run_with_progress(&LoggedProgress::new(&log, &NoProgress))?;
+//! println!("Log written to {}", log.path().display());
+//! # Ok::<(), certificate_maker::error::CertError>(())
+//! ```
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::depth::DeepColor;
+use crate::csvexcelparser::{BatchItem, BatchReport, BatchSettings};
+use crate::editpng::BUILTIN_FONT;
+use crate::error::{IoContext, Result};
+use crate::files::next_free_path;
+use crate::fontsize::FontSizeCheck;
+use crate::progress::ProgressSink;
+use crate::sanitize::strip_control;
+
+/// Ending of every log file name; the start is the time the run began.
+pub const LOG_SUFFIX: &str = "_generation.log";
+
+struct LogFile {
+    writer: LineWriter<File>,
+    // The first write that failed; later lines are still attempted
+    error: Option<String>,
+}
+
+/// One run's log file, safe to write to from any thread.
+pub struct RunLog {
+    path: PathBuf,
+    file: Mutex<LogFile>,
+}
+
+impl RunLog {
+    /// Start `<dir>/<timestamp>_generation.log`, creating `dir` if needed. Two runs started in
+    /// the same second get `_1`, `_2`, ... appended instead of sharing a file.
+    pub fn create(dir: impl AsRef<Path>) -> Result<RunLog> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)
+            .io_context(|| format!("Failed to create log directory: {}", dir.display()))?;
+
+        let stamp = utc_timestamp(SystemTime::now()).replace(':', "-");
+        let first_choice = dir.join(format!("{}{}", &stamp[..19], LOG_SUFFIX));
+        let path = if first_choice.exists() { next_free_path(&first_choice, Path::exists) } else { first_choice };
+        let file = OpenOptions::new().write(true).create_new(true).open(&path)
+            .io_context(|| format!("Failed to create log file: {}", path.display()))?;
+        Ok(RunLog { path, file: Mutex::new(LogFile { writer: LineWriter::new(file), error: None }) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append one line, prefixed with the current time. Control characters are taken out, so
+    /// a name with a line break can't forge a line of its own. A failed write never stops the
+    /// batch; see [`error`](Self::error).
+    pub fn line(&self, message: &str) {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = writeln!(file.writer, "[{}] {}", utc_timestamp(SystemTime::now()), strip_control(message)) {
+            file.error.get_or_insert_with(|| format!("Failed to write {}: {}", self.path.display(), e));
+        }
+    }
+
+    /// Why the log may be incomplete, if any line could not be written.
+    pub fn error(&self) -> Option<String> {
+        self.file.lock().unwrap_or_else(|e| e.into_inner()).error.clone()
+    }
+
+    /// Record the settings a batch of `rows` names is about to run with.
+    pub fn settings(&self, settings: &BatchSettings, rows: usize) {
+        self.line(&format!("Batch of {} names{}", rows, if settings.dry_run { " (dry run)" } else { "" }));
+        for csv_file in settings.csv_files() {
+            self.line(&format!("  Name list:  {}", csv_file.display()));
+        }
+        if settings.selection.is_partial() {
+            self.line(&format!("  Rows:       {}", settings.selection));
+        }
+        self.line(&format!("  Template:   {}", settings.template_file.display()));
+        self.line(&format!("  Position:   centered on ({}, {})", settings.x_pos, settings.y_pos));
+        self.line(&format!("  Font:       {} at {}px", settings.font_filename.display(), settings.font_size));
+        if settings.font_filename == Path::new(BUILTIN_FONT) {
+            self.line("  WARNING fonts directory has no fonts; using the built-in fallback font");
+        }
+        self.line(&format!("  Color:      {}", settings.hex_color));
+        self.line(&format!("  Name case:  {}{}", settings.name_case.label(),
+                           if settings.case_filenames { " (filenames too)" } else { "" }));
+        if !settings.typography.is_none() {
+            self.line(&format!("  Typography: {}", settings.typography.label()));
+        }
+        self.line(&format!("  Output:     {}", settings.output_dir.display()));
+        self.line(&format!("  Existing:   {:?}", settings.overwrite));
+        for decision in &settings.duplicates {
+            let name = decision.names.first().map_or("", String::as_str);
+            self.line(&format!("  Duplicates: '{}' on rows {:?}: {:?}", name, decision.rows, decision.resolution));
+        }
+    }
+
+    /// Record a batch that stopped with an error before finishing.
+    pub fn failure(&self, error: &dyn std::fmt::Display) {
+        self.line(&format!("BATCH FAILED {}", error));
+    }
+}
+
+/// A [`ProgressSink`] that logs every row and the summary to a [`RunLog`], then passes each
+/// call on to `inner`.
+pub struct LoggedProgress<'a> {
+    log: &'a RunLog,
+    inner: &'a dyn ProgressSink,
+    // Seen here rather than in the report, which under a memory budget keeps only problem rows
+    rendered: Mutex<HashSet<PathBuf>>,
+}
+
+impl<'a> LoggedProgress<'a> {
+    pub fn new(log: &'a RunLog, inner: &'a dyn ProgressSink) -> Self {
+        LoggedProgress { log, inner, rendered: Mutex::new(HashSet::new()) }
+    }
+}
+
+impl ProgressSink for LoggedProgress<'_> {
+    fn on_start(&self, total: usize) {
+        self.log.line(&format!("Rendering {} certificates", total));
+        self.inner.on_start(total);
+    }
+
+    fn on_item(&self, index: usize, item: &BatchItem) {
+        log_item(self.log, item);
+        self.rendered.lock().unwrap_or_else(|e| e.into_inner()).insert(item.output_file.clone());
+        self.inner.on_item(index, item);
+    }
+
+    fn on_finish(&self, report: &BatchReport) {
+        // Rows that weren't rendered are still part of the record
+        let rendered = self.rendered.lock().unwrap_or_else(|e| e.into_inner());
+        for entry in &report.entries {
+            if !rendered.contains(entry.output_file.as_path()) {
+                self.log.line(&format!("UNCHANGED {} -> {}", entry.name, entry.output_file.display()));
+            }
+        }
+        for entry in &report.stale {
+            self.log.line(&format!("STALE {} -> {} (no longer in the list)", entry.name, entry.output_file.display()));
+        }
+        for error in &report.progress_errors {
+            self.log.line(&format!("WARNING {}", error));
+        }
+        if let Some(DeepColor::Reduced { reason }) = &report.deep_color {
+            self.log.line(&format!("WARNING 16-bit template reduced to 8 bits per channel since {}", reason));
+        }
+        if let Some(message) = report.font_size.as_ref().and_then(FontSizeCheck::message) {
+            self.log.line(&format!("WARNING {}", message));
+        }
+        if let Some(fingerprint) = &report.fingerprint {
+            self.log.line(&format!("Fingerprint: {} (sha256 {})", fingerprint.label(), fingerprint.hash));
+        }
+        self.log.line(&format!(
+            "Finished in {:.1} s: generated {}, skipped (unchanged) {}, skipped (existing) {}, renamed {}, failed {}",
+            report.duration.as_secs_f64(), report.generated, report.skipped_unchanged, report.skipped_existing,
+            report.renamed, report.failed
+        ));
+        if let Some(performance) = report.performance.summary() {
+            self.log.line(&format!("Performance: {}", performance));
+        }
+        self.inner.on_finish(report);
+    }
+}
+
+fn log_item(log: &RunLog, item: &BatchItem) {
+    match &item.error {
+        None => log.line(&format!("OK {} -> {} at ({}, {})", item.name, item.output_file.display(), item.position.0, item.position.1)),
+        Some(e) => log.line(&format!("FAILED {} -> {}: {}", item.name, item.output_file.display(), e)),
+    }
+    for warning in &item.warnings {
+        log.line(&format!("WARNING {}: {}", item.name, warning));
+    }
+    for value in &item.retouched {
+        log.line(&format!("TYPOGRAPHY {}: {} \"{}\" -> \"{}\"", item.name, value.field, value.before, value.after));
+    }
+}
+
+/// `2026-10-17T19:00:12.345Z` for a point in time, in UTC.
+///
+/// ```
+/// use std::time::{Duration, UNIX_EPOCH};
+/// use certificate_maker::runlog::utc_timestamp;
+///
+/// let time = UNIX_EPOCH + Duration::from_millis(1_792_263_612_345);
+/// assert_eq!(utc_timestamp(time), "2026-10-17T19:00:12.345Z");
+/// ```
+pub fn utc_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let seconds_of_day = seconds % 86_400;
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z", year, month, day,
+            seconds_of_day / 3600, seconds_of_day / 60 % 60, seconds_of_day % 60, since_epoch.subsec_millis())
+}
+
+// Days since 1970-01-01 to a proleptic Gregorian date (Howard Hinnant's algorithm)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
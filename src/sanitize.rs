@@ -0,0 +1,118 @@
+// src/sanitize.rs
+//! Name lists from outside can't be trusted. A cell starting with `=`, `+`, `-` or `@` is run
+//! as a formula when a CSV report is opened in a spreadsheet, and control characters corrupt
+//! the generation log and rasterize as boxes.
+//!
+//! Name lists are cleaned as they are parsed: [`clean_value`] removes control characters and
+//! notes what it found in each value, and the parsers report it as
+//! [`FlaggedValue`]s. Every CSV the crate writes goes through [`csv_safe`], which also puts a
+//! `'` before a value a spreadsheet would read as a formula. Numbers, negative ones included,
+//! are left alone.
+//!
+//! ```
+//! use certificate_maker::sanitize::{ValueIssue, clean_value, csv_safe, strip_control};
+//!
+//! assert_eq!(strip_control("Ada\u{7}\tLovelace"), "Ada Lovelace");
+//! assert_eq!(csv_safe("=HYPERLINK(\"http://evil\")"), "'=HYPERLINK(\"http://evil\")");
+//! assert_eq!(csv_safe("@SUM(A1)"), "'@SUM(A1)");
+//! assert_eq!(csv_safe("-12.5"), "-12.5");
+//! assert_eq!(csv_safe("Ada Lovelace"), "Ada Lovelace");
+//!
+//! let (cleaned, issues) = clean_value(" +cmd\u{0}|' /C calc'!A0 ");
+//! assert_eq!(cleaned, "+cmd|' /C calc'!A0");
+//! assert_eq!(issues, vec![ValueIssue::ControlCharacters, ValueIssue::FormulaPrefix]);
+//! assert!(clean_value("Ada Lovelace").1.is_empty());
+//! ```
+use std::borrow::Cow;
+
+use serde::Serialize;
+
+/// Values longer than this many characters are flagged as [`ValueIssue::TooLong`].
+pub const MAX_FIELD_CHARS: usize = 1000;
+
+// What starts a formula in Excel, LibreOffice and Google Sheets
+const FORMULA_PREFIXES: [char; 4] = ['=', '+', '-', '@'];
+
+/// Something wrong with a value of a name list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum ValueIssue {
+    /// It had control characters, which were removed.
+    ControlCharacters,
+    /// It starts like a formula, so CSV reports write it with a leading `'`.
+    FormulaPrefix,
+    /// It is longer than [`MAX_FIELD_CHARS`].
+    TooLong { chars: usize },
+}
+
+/// A value of a name list that [`clean_value`] found something wrong with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FlaggedValue {
+    /// 1-based line number in the file, as in [`SkippedRow`](crate::csvexcelparser::SkippedRow).
+    pub row: usize,
+    pub column: String,
+    pub issue: ValueIssue,
+}
+
+impl FlaggedValue {
+    /// What was found, for the list's warnings.
+    pub fn describe(&self) -> String {
+        match self.issue {
+            ValueIssue::ControlCharacters => format!("'{}' had control characters, which were removed", self.column),
+            ValueIssue::FormulaPrefix => format!("'{}' starts like a spreadsheet formula; CSV reports write it with a leading '", self.column),
+            ValueIssue::TooLong { chars } => format!("'{}' is {} characters long", self.column, chars),
+        }
+    }
+}
+
+// C0 controls, and DEL with them
+fn is_control(c: char) -> bool {
+    c <= '\u{1f}' || c == '\u{7f}'
+}
+
+/// `text` without control characters; tabs and line breaks become spaces, so the words they
+/// separated stay apart.
+pub fn strip_control(text: &str) -> Cow<'_, str> {
+    if !text.chars().any(is_control) {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(text.chars()
+        .filter_map(|c| match c {
+            '\t' | '\n' | '\u{b}' | '\u{c}' | '\r' => Some(' '),
+            c if is_control(c) => None,
+            c => Some(c),
+        })
+        .collect())
+}
+
+/// Whether a spreadsheet would read `text` as a formula. Numbers like `-3` aren't.
+pub fn is_formula_like(text: &str) -> bool {
+    text.starts_with(FORMULA_PREFIXES) && text.parse::<f64>().is_err()
+}
+
+/// `text` as it is safe to write to a CSV: without control characters, and with a `'` before
+/// it when it starts like a formula.
+pub fn csv_safe(text: &str) -> Cow<'_, str> {
+    match strip_control(text) {
+        text if is_formula_like(&text) => Cow::Owned(format!("'{}", text)),
+        text => text,
+    }
+}
+
+/// `value` trimmed and without control characters, and everything found wrong with it.
+pub fn clean_value(value: &str) -> (String, Vec<ValueIssue>) {
+    let mut issues = Vec::new();
+    let stripped = strip_control(value);
+    if matches!(stripped, Cow::Owned(_)) {
+        issues.push(ValueIssue::ControlCharacters);
+    }
+    let cleaned = stripped.trim();
+    if is_formula_like(cleaned) {
+        issues.push(ValueIssue::FormulaPrefix);
+    }
+    let chars = cleaned.chars().count();
+    if chars > MAX_FIELD_CHARS {
+        issues.push(ValueIssue::TooLong { chars });
+    }
+    (cleaned.to_string(), issues)
+}
@@ -0,0 +1,179 @@
+// src/selection.rs
+//! Rendering part of a long name list: a range of rows, the first rows, or a random sample.
+//!
+//! Rows are the names in file order, numbered from 1. Ranges are half-open like Rust's, so
+//! `1..5000` and `5000..10000` split a list between two machines without overlap.
+//!
+//! ```
+//! use certificate_maker::selection::RowSelection;
+//!
+//! let selection: RowSelection = "3..5".parse()?;
+//! assert_eq!(selection.select(10).indices, vec![2, 3]);
+//! assert_eq!("3..=4".parse::<RowSelection>()?, selection);
+//! assert!(format!("1..={}", usize::MAX).parse::<RowSelection>().is_err());
+//!
+//! let head = RowSelection::Head(25).select(10);
+//! assert_eq!(head.indices.len(), 10);
+//! assert!(head.warning.is_some());
+//!
+//! let sample = RowSelection::Sample { count: 3, seed: 7 };
+//! assert_eq!(sample.select(100).indices, sample.select(100).indices);
+//! # Ok::<(), certificate_maker::error::CertError>(())
+//! ```
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::CertError;
+
+/// Which rows of a name list to render.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RowSelection {
+    #[default]
+    All,
+    /// Rows `start` up to, but not including, `end` (to the last row when `None`).
+    Range { start: usize, end: Option<usize> },
+    /// The first rows.
+    Head(usize),
+    /// `count` rows picked at random; the same seed picks the same rows.
+    Sample { count: usize, seed: u64 },
+}
+
+/// The rows picked from a list, and why fewer than asked for were picked, if so.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selected {
+    /// 0-based positions in the list, in list order.
+    pub indices: Vec<usize>,
+    pub warning: Option<String>,
+}
+
+impl Selected {
+    /// The picked items of a list the selection was made on.
+    pub fn pick<T: Clone>(&self, items: &[T]) -> Vec<T> {
+        self.indices.iter().map(|&index| items[index].clone()).collect()
+    }
+}
+
+impl RowSelection {
+    /// Pick rows from a list of `count` names, clamping out-of-range values.
+    pub fn select(&self, count: usize) -> Selected {
+        match *self {
+            RowSelection::All => Selected { indices: (0..count).collect(), warning: None },
+            RowSelection::Range { start, end } => {
+                let requested_end = end.unwrap_or(count + 1);
+                let first = start.min(count + 1);
+                let last = requested_end.min(count + 1).max(first);
+                let warning = (start > count || requested_end > count + 1).then(|| {
+                    format!("The list has {} rows; {} selects rows {}..{}", count, self, first, last)
+                });
+                Selected { indices: (first - 1..last - 1).collect(), warning }
+            }
+            RowSelection::Head(rows) => Selected {
+                indices: (0..rows.min(count)).collect(),
+                warning: (rows > count).then(|| format!("The list has only {} rows; taking all of them", count)),
+            },
+            RowSelection::Sample { count: wanted, seed } => {
+                if wanted >= count {
+                    let warning = (wanted > count)
+                        .then(|| format!("The list has only {} rows; the sample is all of them", count));
+                    return Selected { indices: (0..count).collect(), warning };
+                }
+                // Partial Fisher-Yates shuffle, then back into file order
+                let mut rng = SplitMix64(seed);
+                let mut positions: Vec<usize> = (0..count).collect();
+                for i in 0..wanted {
+                    let j = i + (rng.next() % (count - i) as u64) as usize;
+                    positions.swap(i, j);
+                }
+                let mut indices = positions[..wanted].to_vec();
+                indices.sort_unstable();
+                Selected { indices, warning: None }
+            }
+        }
+    }
+
+    /// Whether rows are left out, so the rest of the output directory must be left alone.
+    pub fn is_partial(&self) -> bool {
+        *self != RowSelection::All
+    }
+}
+
+/// A seed for [`RowSelection::Sample`] when none was given, from the clock.
+pub fn random_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+}
+
+// Small, fast and stable across platforms and versions, which is all a reproducible sample needs
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+impl fmt::Display for RowSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RowSelection::All => write!(f, "all rows"),
+            RowSelection::Range { start, end: Some(end) } => write!(f, "{}..{}", start, end),
+            RowSelection::Range { start, end: None } => write!(f, "{}..", start),
+            RowSelection::Head(rows) => write!(f, "head {}", rows),
+            RowSelection::Sample { count, seed } => write!(f, "sample {} seed {}", count, seed),
+        }
+    }
+}
+
+/// Parses `5000..10000`, `5000..=9999`, `5000..`, `..100`, `head 25`, `sample 10` and
+/// `sample 10 seed 42`; a sample without a seed gets a [`random_seed`].
+impl FromStr for RowSelection {
+    type Err = CertError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| CertError::InvalidSelection { input: input.to_string(), reason: reason.to_string() };
+        let number = |text: &str| text.trim().parse::<usize>().map_err(|_| invalid("expected a whole number"));
+        let words: Vec<&str> = input.split_whitespace().collect();
+
+        match words.as_slice() {
+            ["all"] => Ok(RowSelection::All),
+            ["head", rows] => match number(rows)? {
+                0 => Err(invalid("take at least one row")),
+                rows => Ok(RowSelection::Head(rows)),
+            },
+            ["sample", count, rest @ ..] => {
+                let count = number(count)?;
+                if count == 0 {
+                    return Err(invalid("sample at least one row"));
+                }
+                let seed = match rest {
+                    [] => random_seed(),
+                    ["seed", seed] => seed.parse().map_err(|_| invalid("the seed must be a whole number"))?,
+                    _ => return Err(invalid("write it as 'sample 10' or 'sample 10 seed 42'")),
+                };
+                Ok(RowSelection::Sample { count, seed })
+            }
+            [range] if range.contains("..") => {
+                let (start, end) = range.split_once("..").unwrap_or_default();
+                let start = if start.is_empty() { 1 } else { number(start)? };
+                let end = match end.strip_prefix('=') {
+                    Some(inclusive) => Some(number(inclusive)?.checked_add(1).ok_or_else(|| invalid("the end is too large"))?),
+                    None if end.is_empty() => None,
+                    None => Some(number(end)?),
+                };
+                if start == 0 {
+                    return Err(invalid("rows are numbered from 1"));
+                }
+                if end.is_some_and(|end| end <= start) {
+                    return Err(invalid("the range is empty"));
+                }
+                Ok(RowSelection::Range { start, end })
+            }
+            _ => Err(invalid("use a range like 5000..10000, 'head 25' or 'sample 10'")),
+        }
+    }
+}
@@ -0,0 +1,375 @@
+// src/server.rs
+//! HTTP service rendering single certificates on demand.
+//!
+//! Only compiled with the `server` feature. Every template and font is decoded once when the
+//! [`Renderer`] is loaded; requests draw onto a copy of the cached template. A fixed pool of
+//! worker threads handles requests, so at most that many renders are in memory at once and
+//! a burst waits in the connection queue instead.
+//!
+//! - `GET /templates` lists the templates with their [analysis](crate::analysis::ImageAnalysis).
+//! - `POST /render` takes a [`RenderRequest`] as JSON and answers with the PNG or PDF bytes.
+//!   Any other key is a field of the row, like a CSV column, for `text` to fill in:
+//!
+//! ```json
+//! { "template": "CertificateTemplate.png", "name": "Ada Lovelace", "Course": "Analysis",
+//!   "text": "{Name}, {Course}", "font_size": 48, "format": "pdf" }
+//! ```
+//!
+//! Certificates are drawn by the same renderer as a [`CertificateJob`] of one row, so a
+//! request gets exactly what a batch with the same settings would write.
+//!
+//! Templates and fonts are looked up by their name in the listing only, never as paths, so a
+//! request can't reach files outside the templates and fonts directories. Errors are answered
+//! as `{"error": "..."}` with a 4xx or 5xx status.
+use image::{ImageOutputFormat, RgbaImage};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tiny_http::{Header, Method, Response};
+
+use crate::analysis::{ImageAnalysis, analyze_image_file};
+use crate::csvexcelparser::list_template_files;
+use crate::editpng::{list_available_fonts, load_font, load_template};
+use crate::graphics::fill_placeholders;
+use crate::job::{Anchor, CertificateJob};
+use crate::palette::check_color;
+use crate::error::{CertError, Result};
+use crate::namecase::NameCase;
+use crate::paths::AppPaths;
+use crate::pdf::{DEFAULT_PDF_DPI, images_to_pdf};
+use crate::source::Record;
+
+/// Address the service listens on unless told otherwise; only reachable from this machine.
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:8080";
+/// Largest request body accepted.
+pub const MAX_REQUEST_BYTES: usize = 16 * 1024;
+/// Longest name accepted, in characters.
+pub const MAX_NAME_CHARS: usize = 200;
+/// Largest font size accepted, in pixels.
+pub const MAX_FONT_SIZE: f32 = 1000.0;
+/// Highest DPI accepted for PDF output.
+pub const MAX_DPI: f32 = 2400.0;
+
+/// Output of `POST /render`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Png,
+    Pdf,
+}
+
+/// The JSON body of `POST /render`. Only `template` and `name` are required; the rest
+/// defaults to the interactive defaults (DejaVu Sans 40 px, black, centered on the template).
+#[derive(Debug, Clone, Deserialize)]
+pub struct RenderRequest {
+    /// A template name as listed by `GET /templates`.
+    pub template: String,
+    pub name: String,
+    /// What to draw instead of the name, with `{Name}` and `{Column}` filled in from the
+    /// name and `fields`, like `"Dr. {Name}"`.
+    #[serde(default)]
+    pub text: Option<String>,
+    /// Every other key of the body: the row's other columns, as text.
+    #[serde(flatten)]
+    pub fields: BTreeMap<String, String>,
+    /// A font file name from the fonts directory.
+    #[serde(default)]
+    pub font: Option<String>,
+    #[serde(default)]
+    pub font_size: Option<f32>,
+    /// `#RRGGBB` or `#RRGGBBAA`.
+    #[serde(default)]
+    pub color: Option<String>,
+    /// Center of the text; the template's center when unset.
+    #[serde(default)]
+    pub x: Option<i32>,
+    #[serde(default)]
+    pub y: Option<i32>,
+    #[serde(default)]
+    pub name_case: NameCase,
+    #[serde(default)]
+    pub format: OutputFormat,
+    /// Resolution of the PDF page; the image size is the same either way.
+    #[serde(default)]
+    pub dpi: Option<f32>,
+}
+
+/// A request that can't be served, and the HTTP status to answer it with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestError {
+    pub status: u16,
+    pub message: String,
+}
+
+impl RequestError {
+    fn bad_request(message: impl Into<String>) -> Self {
+        RequestError { status: 400, message: message.into() }
+    }
+}
+
+/// A rendered certificate and its MIME type.
+#[derive(Debug, Clone)]
+pub struct Rendered {
+    pub bytes: Vec<u8>,
+    pub content_type: &'static str,
+}
+
+struct CachedTemplate {
+    path: PathBuf,
+    image: RgbaImage,
+    analysis: ImageAnalysis,
+}
+
+/// Every template and font of an [`AppPaths`], decoded and ready to draw on.
+pub struct Renderer {
+    templates: BTreeMap<String, CachedTemplate>,
+    // Decoded into the shared font cache, where the job renderer finds them
+    fonts: BTreeMap<String, PathBuf>,
+    default_font: String,
+}
+
+impl Renderer {
+    /// Decode every template and font up front; one that fails to load fails the whole
+    /// service, rather than every request that names it.
+    pub fn load(paths: &AppPaths) -> Result<Renderer> {
+        let mut templates = BTreeMap::new();
+        for file in list_template_files(&paths.templates)? {
+            let path = paths.templates.join(&file);
+            let template = CachedTemplate { image: load_template(&path)?, analysis: analyze_image_file(&path)?, path };
+            templates.insert(listing_key(&file), template);
+        }
+
+        let mut fonts = BTreeMap::new();
+        for file in list_available_fonts(&paths.fonts)? {
+            let path = paths.font(&file);
+            load_font(&path)?;
+            fonts.insert(listing_key(&file), path);
+        }
+        // Same default as the interactive flow when the fonts directory has it
+        let default_font = if fonts.contains_key("DejaVuSans.ttf") {
+            "DejaVuSans.ttf".to_string()
+        } else {
+            fonts.keys().next().cloned().unwrap_or_default()
+        };
+
+        Ok(Renderer { templates, fonts, default_font })
+    }
+
+    /// Names a request can use for `template`, in order.
+    pub fn template_names(&self) -> impl Iterator<Item = &str> {
+        self.templates.keys().map(String::as_str)
+    }
+
+    /// Names a request can use for `font`, in order.
+    pub fn font_names(&self) -> impl Iterator<Item = &str> {
+        self.fonts.keys().map(String::as_str)
+    }
+
+    /// The body of `GET /templates`: `[{"name": ..., "analysis": {...}}, ...]`.
+    pub fn templates_json(&self) -> String {
+        let listing: Vec<serde_json::Value> = self.templates.iter()
+            .map(|(name, template)| serde_json::json!({ "name": name, "analysis": template.analysis }))
+            .collect();
+        serde_json::Value::Array(listing).to_string()
+    }
+
+    /// Validate a request and render its certificate.
+    ///
+    /// ```
+    /// use certificate_maker::job::CertificateJob;
+    /// use certificate_maker::paths::AppPaths;
+    /// use certificate_maker::server::{RenderRequest, Renderer};
+    ///
+    /// let paths = AppPaths { templates: "tests/fixtures".into(), fonts: "tests/fixtures".into(), ..AppPaths::default() };
+    /// let renderer = Renderer::load(&paths)?;
+    /// let request: RenderRequest = serde_json::from_str(r#"{
+    ///     "template": "template.png", "name": "Ada Lovelace", "Course": "Analysis", "text": "{Name}, {Course}"
+    /// }"#)?;
+    /// let rendered = renderer.render(&request).unwrap();
+    /// assert_eq!(rendered.content_type, "image/png");
+    ///
+    /// // What a batch of the same row draws
+    /// let job = CertificateJob::new("tests/fixtures/template.png", vec!["Ada Lovelace".to_string()])
+    ///     .font("tests/fixtures/DejaVuSans-Latin.ttf")
+    ///     .texts(vec!["Ada Lovelace, Analysis".to_string()]);
+    /// assert_eq!(image::load_from_memory(&rendered.bytes)?.to_rgba8(), job.render_image(0)?);
+    ///
+    /// let request: RenderRequest = serde_json::from_str(r#"{ "template": "template.png", "name": "Ada", "text": "{Year}" }"#)?;
+    /// assert_eq!(renderer.render(&request).unwrap_err().status, 400);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn render(&self, request: &RenderRequest) -> std::result::Result<Rendered, RequestError> {
+        let template = self.templates.get(checked_name("template", &request.template)?)
+            .ok_or_else(|| RequestError { status: 404, message: format!("Unknown template '{}'; see GET /templates", request.template) })?;
+        let font_name = match &request.font {
+            Some(font) => checked_name("font", font)?,
+            None => self.default_font.as_str(),
+        };
+        let font = self.fonts.get(font_name)
+            .ok_or_else(|| RequestError { status: 404, message: format!("Unknown font '{}'", font_name) })?;
+
+        let name = request.name.trim();
+        if name.is_empty() {
+            return Err(RequestError::bad_request("'name' must not be empty"));
+        }
+        if name.chars().count() > MAX_NAME_CHARS {
+            return Err(RequestError::bad_request(format!("'name' is longer than {} characters", MAX_NAME_CHARS)));
+        }
+        if name.chars().any(char::is_control) {
+            return Err(RequestError::bad_request("'name' must not contain control characters"));
+        }
+        for (column, value) in &request.fields {
+            if value.chars().count() > MAX_NAME_CHARS {
+                return Err(RequestError::bad_request(format!("'{}' is longer than {} characters", column, MAX_NAME_CHARS)));
+            }
+            if value.chars().any(char::is_control) {
+                return Err(RequestError::bad_request(format!("'{}' must not contain control characters", column)));
+            }
+        }
+
+        let size = request.font_size.unwrap_or(40.0);
+        if !size.is_finite() || size <= 0.0 || size > MAX_FONT_SIZE {
+            return Err(RequestError::bad_request(format!("'font_size' must be between 0 and {}", MAX_FONT_SIZE)));
+        }
+        let color = request.color.as_deref().unwrap_or("#000000");
+        check_color(color).map_err(|e| RequestError::bad_request(e.to_string()))?;
+        let (width, height) = template.image.dimensions();
+        let x = request.x.unwrap_or(width as i32 / 2);
+        let y = request.y.unwrap_or(height as i32 / 2);
+        if !(0..=width as i32).contains(&x) || !(0..=height as i32).contains(&y) {
+            return Err(RequestError::bad_request(format!("({}, {}) is outside the {}x{} template", x, y, width, height)));
+        }
+        let dpi = request.dpi.unwrap_or(DEFAULT_PDF_DPI);
+        if !dpi.is_finite() || dpi <= 0.0 || dpi > MAX_DPI {
+            return Err(RequestError::bad_request(format!("'dpi' must be between 0 and {}", MAX_DPI)));
+        }
+
+        let record = Record {
+            name: name.to_string(),
+            fields: request.fields.iter().map(|(column, value)| (column.clone(), value.trim().to_string())).collect(),
+        };
+        let mut job = CertificateJob::new(&template.path, vec![name.to_string()])
+            .font(font)
+            .font_size(size)
+            .color(color)
+            .position(Anchor::Center, x, y)
+            .name_case(request.name_case);
+        if let Some(text) = &request.text {
+            let text = fill_placeholders(text, name, Some(&record)).map_err(|e| RequestError::bad_request(e.to_string()))?;
+            job = job.texts(vec![text]);
+        }
+        let internal = |e: CertError| RequestError { status: 500, message: e.to_string() };
+        let image = job.records(vec![record]).render_image_on(0, template.image.clone()).map_err(internal)?;
+
+        match request.format {
+            OutputFormat::Png => {
+                let mut bytes = Vec::new();
+                image::DynamicImage::ImageRgba8(image)
+                    .write_to(&mut Cursor::new(&mut bytes), ImageOutputFormat::Png)
+                    .map_err(|e| RequestError { status: 500, message: format!("Failed to encode PNG: {}", e) })?;
+                Ok(Rendered { bytes, content_type: "image/png" })
+            }
+            OutputFormat::Pdf => Ok(Rendered { bytes: images_to_pdf(&[image], dpi).map_err(internal)?, content_type: "application/pdf" }),
+        }
+    }
+}
+
+// "sub/Font.ttf" on every platform, as listed to clients
+fn listing_key(file: &Path) -> String {
+    file.to_string_lossy().replace('\\', "/")
+}
+
+// A name from the listing can't be absolute, climb out of its directory or hide control characters
+fn checked_name<'a>(field: &str, name: &'a str) -> std::result::Result<&'a str, RequestError> {
+    let invalid = name.is_empty()
+        || name.len() > 255
+        || name.starts_with('/')
+        || name.contains(['\\', ':'])
+        || name.chars().any(char::is_control)
+        || name.split('/').any(|part| part.is_empty() || part == "." || part == "..");
+    if invalid {
+        return Err(RequestError::bad_request(format!("'{}' must be a file name from the listing", field)));
+    }
+    Ok(name)
+}
+
+/// Where and how the service runs.
+#[derive(Debug, Clone)]
+pub struct ServerOptions {
+    /// `host:port` to listen on.
+    pub address: String,
+    /// Requests handled at once; also the most certificates in memory at once.
+    pub workers: usize,
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        ServerOptions {
+            address: DEFAULT_ADDRESS.to_string(),
+            workers: std::thread::available_parallelism().map_or(2, |workers| workers.get()),
+        }
+    }
+}
+
+/// Serve requests until the process ends. `log` gets one line per request.
+pub fn serve(renderer: &Renderer, options: &ServerOptions, log: &(dyn Fn(&str) + Sync)) -> Result<()> {
+    let server = tiny_http::Server::http(&options.address)
+        .map_err(|e| CertError::ServerBind { address: options.address.clone(), reason: e.to_string() })?;
+
+    std::thread::scope(|scope| {
+        for _ in 0..options.workers.max(1) {
+            scope.spawn(|| {
+                while let Ok(request) = server.recv() {
+                    handle(renderer, request, log);
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+fn handle(renderer: &Renderer, mut request: tiny_http::Request, log: &(dyn Fn(&str) + Sync)) {
+    let started = Instant::now();
+    let method = request.method().clone();
+    let path = request.url().split('?').next().unwrap_or_default().to_string();
+
+    let result = match (&method, path.as_str()) {
+        (Method::Get, "/templates") => Ok(Rendered { bytes: renderer.templates_json().into_bytes(), content_type: "application/json" }),
+        (Method::Post, "/render") => read_render_request(&mut request).and_then(|body| renderer.render(&body)),
+        (_, "/templates" | "/render") => Err(RequestError { status: 405, message: format!("{} is not allowed on {}", method, path) }),
+        _ => Err(RequestError { status: 404, message: format!("No such endpoint: {}", path) }),
+    };
+
+    let (status, response) = match result {
+        Ok(rendered) => (200, Response::from_data(rendered.bytes).with_header(content_type(rendered.content_type))),
+        Err(error) => {
+            let body = serde_json::json!({ "error": error.message }).to_string();
+            (error.status, Response::from_data(body.into_bytes()).with_header(content_type("application/json")))
+        }
+    };
+    // A client that hung up can't be told anything
+    let _ = request.respond(response.with_status_code(status));
+    log(&format!("{} {} -> {} ({} ms)", method, path, status, started.elapsed().as_millis()));
+}
+
+fn read_render_request(request: &mut tiny_http::Request) -> std::result::Result<RenderRequest, RequestError> {
+    let too_large = || RequestError { status: 413, message: format!("The request body is larger than {} bytes", MAX_REQUEST_BYTES) };
+    if request.body_length().is_some_and(|length| length > MAX_REQUEST_BYTES) {
+        return Err(too_large());
+    }
+    // The declared length can be missing, so cap what is actually read too
+    let mut body = Vec::new();
+    request.as_reader().take(MAX_REQUEST_BYTES as u64 + 1).read_to_end(&mut body)
+        .map_err(|e| RequestError::bad_request(format!("Could not read the request body: {}", e)))?;
+    if body.len() > MAX_REQUEST_BYTES {
+        return Err(too_large());
+    }
+    serde_json::from_slice(&body).map_err(|e| RequestError::bad_request(format!("Invalid JSON: {}", e)))
+}
+
+fn content_type(value: &str) -> Header {
+    Header::from_bytes("Content-Type", value).expect("content types are plain ASCII")
+}
@@ -0,0 +1,324 @@
+// src/shard.rs
+//! Shards: one [`BatchPlan`] rendered on several machines at once, then put back together.
+//!
+//! [`execute_shard`] renders only the plan's rows assigned to one [`Shard`]: row `i` (counted
+//! from 0) belongs to shard `i % count + 1`, so every machine given the same plan agrees on
+//! the split without talking to the others. Each run writes its certificates and a manifest of
+//! its own, `manifest.shard-<n>-of-<count>.<run>.json`, which records the plan, the shard and
+//! the rows it was given. Once every shard's certificates and manifest are in the output
+//! directory, [`merge_shard_manifests`] checks that they add up and combines them into
+//! `manifest.json`: a shard missing or run twice, a row rendered by no shard or by two, and a
+//! certificate missing from the directory each stop the merge with every problem listed.
+//!
+//! ```
+//! use certificate_maker::csvexcelparser::BatchSettings;
+//! use certificate_maker::manifest::Manifest;
+//! use certificate_maker::plan::plan_batch;
+//! use certificate_maker::progress::NoProgress;
+//! use certificate_maker::shard::{Shard, execute_shard, merge_shard_manifests, shard_manifests_in};
+//!
+//! let dir = std::env::temp_dir().join(format!("certmaker-shard-{}", std::process::id()));
+//! let settings = BatchSettings {
+//!     template_file: "tests/fixtures/template.png".into(),
+//!     font_filename: "tests/fixtures/DejaVuSans-Latin.ttf".into(),
+//!     font_size: 32.0,
+//!     hex_color: "#000000".into(),
+//!     x_pos: 300,
+//!     y_pos: 200,
+//!     output_dir: dir.clone(),
+//!     ..Default::default()
+//! };
+//! let names: Vec<String> = ["Ada", "Grace", "Mary"].map(String::from).into();
+//! let plan = plan_batch(&settings, &names)?;
+//!
+//! // Shard 1 of 2 gets rows 0 and 2, shard 2 gets row 1
+//! let shard: Shard = "1/2".parse()?;
+//! assert_eq!(shard.rows(plan.rows.len()), vec![0, 2]);
+//! assert_eq!(execute_shard(&plan, shard, &NoProgress)?.generated, 2);
+//! // The other half is missing, so nothing is merged yet
+//! assert!(merge_shard_manifests(&dir, &shard_manifests_in(&dir)?).is_err());
+//!
+//! execute_shard(&plan, "2/2".parse()?, &NoProgress)?;
+//! let merged = merge_shard_manifests(&dir, &shard_manifests_in(&dir)?)?;
+//! assert_eq!((merged.shards, merged.certificates), (2, 3));
+//! assert_eq!(Manifest::load(&dir)?.entries.len(), 3);
+//! std::fs::remove_dir_all(&dir)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::csvexcelparser::BatchReport;
+use crate::error::{CertError, IoContext, Result};
+use crate::manifest::{BatchRecord, MANIFEST_FILENAME, Manifest};
+use crate::plan::{BatchPlan, execute_rows};
+use crate::progress::ProgressSink;
+use crate::webhook::new_run_id;
+
+/// File names of shard manifests start with this; see [`ShardRecord::file_name`].
+pub const SHARD_MANIFEST_PREFIX: &str = "manifest.shard-";
+
+// Rows listed by number before "and N more"
+const ROWS_LISTED: usize = 5;
+
+/// One part of a plan, as `index/count` with `index` counted from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Shard {
+    pub index: usize,
+    pub count: usize,
+}
+
+impl Shard {
+    /// The rows of a plan of `total` rows this shard renders: every `count`-th, starting at
+    /// `index - 1`.
+    pub fn rows(&self, total: usize) -> Vec<usize> {
+        (self.index - 1..total).step_by(self.count).collect()
+    }
+}
+
+/// Parses `2/4`: shard 2 of 4.
+impl FromStr for Shard {
+    type Err = CertError;
+
+    fn from_str(value: &str) -> Result<Shard> {
+        let invalid = |reason: &str| CertError::InvalidShard { input: value.to_string(), reason: reason.to_string() };
+        let (index, count) = value.split_once('/').ok_or_else(|| invalid("write it as <shard>/<shards>, like 2/4"))?;
+        let parse = |number: &str| number.trim().parse::<usize>().ok().filter(|number| *number > 0);
+        let (Some(index), Some(count)) = (parse(index), parse(count)) else {
+            return Err(invalid("both numbers must be whole numbers from 1"));
+        };
+        if index > count {
+            return Err(invalid("the shard can't be past the number of shards"));
+        }
+        Ok(Shard { index, count })
+    }
+}
+
+impl std::fmt::Display for Shard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} of {}", self.index, self.count)
+    }
+}
+
+/// What a shard's manifest says about the run that wrote it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShardRecord {
+    /// [`BatchPlan::id`] of the plan the shard is part of.
+    pub plan: String,
+    pub shard: Shard,
+    /// Different for every run, so a shard run twice is told apart from its copies.
+    pub run: String,
+    /// Rows in the whole plan.
+    pub plan_rows: usize,
+    /// The plan's rows this shard was given, each with the file it writes.
+    pub rows: Vec<ShardRow>,
+}
+
+/// A row of the plan given to a shard.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShardRow {
+    /// Position among the plan's rows, from 0.
+    pub index: usize,
+    pub output_file: PathBuf,
+}
+
+impl ShardRecord {
+    /// The manifest's file name in the output directory.
+    pub fn file_name(&self) -> String {
+        format!("{}{}-of-{}.{}.json", SHARD_MANIFEST_PREFIX, self.shard.index, self.shard.count, self.run)
+    }
+}
+
+/// Render the rows of `plan` assigned to `shard`, as [`execute_plan`](crate::plan::execute_plan)
+/// would, and save their manifest as the shard's own file.
+pub fn execute_shard(plan: &BatchPlan, shard: Shard, progress: &dyn ProgressSink) -> Result<BatchReport> {
+    let indexes = shard.rows(plan.rows.len());
+    let record = ShardRecord {
+        plan: plan.id()?,
+        shard,
+        run: new_run_id(),
+        plan_rows: plan.rows.len(),
+        rows: indexes.iter().map(|&index| ShardRow { index, output_file: plan.rows[index].output_file.clone() }).collect(),
+    };
+    execute_rows(plan, &indexes, Some(record), progress)
+}
+
+/// Every shard manifest directly in `dir`, by name.
+pub fn shard_manifests_in(dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+    let dir = dir.as_ref();
+    let entries = std::fs::read_dir(dir)
+        .io_context(|| format!("Failed to read directory {}", dir.display()))?;
+    let mut files: Vec<PathBuf> = entries.flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.file_name().and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with(SHARD_MANIFEST_PREFIX) && name.ends_with(".json")))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// What merging the shards of a plan found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergedShards {
+    pub shards: usize,
+    /// Certificates the shards issued, now in `manifest.json`.
+    pub certificates: usize,
+    /// Rows a shard was given but couldn't render; render them again with their shard.
+    pub failed: Vec<ShardRow>,
+    pub manifest: PathBuf,
+}
+
+/// Check that the shard manifests `files` make up one whole plan and combine their entries into
+/// `output_dir`'s `manifest.json`, next to the certificates of earlier runs. Nothing is written
+/// when they don't add up: the error lists every problem found.
+pub fn merge_shard_manifests(output_dir: impl AsRef<Path>, files: &[PathBuf]) -> Result<MergedShards> {
+    let output_dir = output_dir.as_ref();
+    if files.is_empty() {
+        return Err(CertError::ShardsDontAddUp(vec![format!("there are no shard manifests ({}*.json) to merge in {}", SHARD_MANIFEST_PREFIX, output_dir.display())]));
+    }
+    let mut problems = Vec::new();
+    // The same run listed twice, or copied, counts once
+    let mut runs: BTreeMap<String, (PathBuf, Manifest, ShardRecord)> = BTreeMap::new();
+    for file in files {
+        let mut manifest = Manifest::load_file(file)?;
+        match manifest.shard.take() {
+            Some(record) => {
+                runs.entry(record.run.clone()).or_insert((file.clone(), manifest, record));
+            }
+            None => problems.push(format!("{} isn't the manifest of a shard", file.display())),
+        }
+    }
+    let Some((first_file, first)) = runs.values().next().map(|(file, _, record)| (file.clone(), record.clone())) else {
+        return Err(CertError::ShardsDontAddUp(problems));
+    };
+
+    // One plan, split the same way
+    for (file, _, record) in runs.values() {
+        if record.plan != first.plan {
+            problems.push(format!("{} is from another plan than {}", file.display(), first_file.display()));
+        } else if record.shard.count != first.shard.count {
+            problems.push(format!("{} splits the plan into {} shards, {} into {}", file.display(), record.shard.count, first_file.display(), first.shard.count));
+        }
+    }
+    if !problems.is_empty() {
+        return Err(CertError::ShardsDontAddUp(problems));
+    }
+
+    // Every shard exactly once
+    let mut by_shard: BTreeMap<usize, Vec<&Path>> = BTreeMap::new();
+    for (file, _, record) in runs.values() {
+        by_shard.entry(record.shard.index).or_default().push(file);
+    }
+    for index in 1..=first.shard.count {
+        match by_shard.get(&index).map(Vec::as_slice) {
+            None | Some([]) => problems.push(format!("shard {} of {} is missing", index, first.shard.count)),
+            Some([_]) => {}
+            Some(files) => {
+                let files: Vec<String> = files.iter().map(|file| file.display().to_string()).collect();
+                problems.push(format!("shard {} of {} was run more than once ({}); keep the manifest of the run that counts", index, first.shard.count, files.join(", ")));
+            }
+        }
+    }
+
+    // Every row by exactly one shard, and every file by exactly one row
+    let mut row_shards: HashMap<usize, BTreeSet<usize>> = HashMap::new();
+    let mut file_shards: HashMap<&Path, BTreeSet<usize>> = HashMap::new();
+    for (_, manifest, record) in runs.values() {
+        for row in &record.rows {
+            row_shards.entry(row.index).or_default().insert(record.shard.index);
+        }
+        for entry in &manifest.entries {
+            file_shards.entry(&entry.output_file).or_default().insert(record.shard.index);
+        }
+    }
+    let unrendered: Vec<usize> = (0..first.plan_rows).filter(|index| !row_shards.contains_key(index)).collect();
+    if !unrendered.is_empty() {
+        problems.push(format!("{} of the plan's rows were rendered by no shard: {}", unrendered.len(), list_rows(&unrendered)));
+    }
+    let mut twice: Vec<(usize, &BTreeSet<usize>)> = row_shards.iter()
+        .filter(|(_, shards)| shards.len() > 1)
+        .map(|(index, shards)| (*index, shards))
+        .collect();
+    twice.sort();
+    for (index, shards) in twice.iter().take(ROWS_LISTED) {
+        problems.push(format!("row {} of the plan was rendered by shards {}", index + 1, join_numbers(shards)));
+    }
+    if twice.len() > ROWS_LISTED {
+        problems.push(format!("… and {} more rows rendered by several shards", twice.len() - ROWS_LISTED));
+    }
+    let mut claimed: Vec<(&Path, &BTreeSet<usize>)> = file_shards.iter()
+        .filter(|(_, shards)| shards.len() > 1)
+        .map(|(file, shards)| (*file, shards))
+        .collect();
+    claimed.sort();
+    for (file, shards) in claimed.iter().take(ROWS_LISTED) {
+        problems.push(format!("{} was written by shards {}", file.display(), join_numbers(shards)));
+    }
+
+    // The certificates themselves must be here, or the manifest would record files that aren't
+    let absent: Vec<&Path> = runs.values()
+        .flat_map(|(_, manifest, _)| &manifest.entries)
+        .map(|entry| entry.output_file.as_path())
+        .filter(|file| !file.exists())
+        .collect();
+    if let Some(file) = absent.first() {
+        problems.push(format!("{} of the shards' certificates aren't on disk, such as {}; copy every shard's certificates into {} before merging",
+                              absent.len(), file.display(), output_dir.display()));
+    }
+    if !problems.is_empty() {
+        return Err(CertError::ShardsDontAddUp(problems));
+    }
+
+    // Into the output directory's manifest, replacing what earlier runs recorded for the same files
+    let mut merged = Manifest::load(output_dir)?;
+    let mut batch = BatchRecord::default();
+    let mut certificates = 0;
+    let mut failed = Vec::new();
+    let mut fingerprints = std::mem::take(&mut merged.fingerprints);
+    let mut incoming = Vec::new();
+    for (position, (_, manifest, record)) in runs.into_values().enumerate() {
+        // Every shard drew with the plan's settings, so any of them can say how
+        if position == 0 {
+            merged.duplicates.clone_from(&manifest.duplicates);
+            merged.font_variation.clone_from(&manifest.font_variation);
+            merged.effects.clone_from(&manifest.effects);
+        }
+        fingerprints.extend(manifest.fingerprints.iter().cloned());
+        let written: HashSet<&Path> = manifest.entries.iter().map(|entry| entry.output_file.as_path()).collect();
+        failed.extend(record.rows.into_iter().filter(|row| !written.contains(row.output_file.as_path())));
+        if let Some(shard_batch) = manifest.last_batch {
+            batch.at = batch.at.max(shard_batch.at);
+            batch.outputs.extend(shard_batch.outputs);
+            batch.artifacts.extend(shard_batch.artifacts);
+        }
+        certificates += manifest.entries.len();
+        incoming.extend(manifest.entries);
+    }
+    // No two shards wrote the same file, so one pass drops every entry the shards replace
+    let replaced: HashSet<&Path> = incoming.iter().map(|entry| entry.output_file.as_path()).collect();
+    merged.entries.retain(|known| !replaced.contains(known.output_file.as_path()));
+    merged.entries.extend(incoming);
+    if !batch.outputs.is_empty() {
+        merged.last_batch = Some(batch);
+    }
+    merged.keep_fingerprints(fingerprints);
+    merged.save(output_dir)?;
+    failed.sort_by_key(|row| row.index);
+    Ok(MergedShards { shards: first.shard.count, certificates, failed, manifest: output_dir.join(MANIFEST_FILENAME) })
+}
+
+// "3, 7, 11 and 40 more", counting rows from 1
+fn list_rows(indexes: &[usize]) -> String {
+    let shown: Vec<String> = indexes.iter().take(ROWS_LISTED).map(|index| (index + 1).to_string()).collect();
+    match indexes.len().saturating_sub(ROWS_LISTED) {
+        0 => shown.join(", "),
+        more => format!("{} and {} more", shown.join(", "), more),
+    }
+}
+
+fn join_numbers(numbers: &BTreeSet<usize>) -> String {
+    let numbers: Vec<String> = numbers.iter().map(usize::to_string).collect();
+    numbers.join(" and ")
+}
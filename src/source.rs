@@ -0,0 +1,313 @@
+// src/source.rs
+//! Where names come from: CSV, JSON and plain text files, in-memory lists or spreadsheets.
+use std::path::{Path, PathBuf};
+
+use crate::csvexcelparser::{CsvFormat, name_list_url, parse_csv_file_with, parse_json_list_with, parse_text_list};
+use crate::error::{CertError, Result};
+
+/// One row of input: the name to print plus every other column by header.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Record {
+    pub name: String,
+    /// `(header, value)` pairs in column order; empty for sources without columns.
+    pub fields: Vec<(String, String)>,
+}
+
+impl Record {
+    /// A record that only has a name.
+    pub fn from_name(name: impl Into<String>) -> Self {
+        Record { name: name.into(), fields: Vec::new() }
+    }
+
+    /// Value of a column, matching the header case-insensitively.
+    pub fn get(&self, column: &str) -> Option<&str> {
+        self.fields.iter()
+            .find(|(header, _)| header.trim().eq_ignore_ascii_case(column.trim()))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The recipient address: the first non-empty column named like one of [`EMAIL_ALIASES`].
+    pub fn email(&self) -> Option<&str> {
+        EMAIL_ALIASES.iter()
+            .filter_map(|alias| self.get(alias))
+            .map(str::trim)
+            .find(|value| !value.is_empty())
+    }
+}
+
+/// Headers accepted as the name column when none is picked explicitly, in priority order.
+pub const DEFAULT_NAME_ALIASES: &[&str] = &[
+    "name", "full name", "fullname", "student name", "participant name", "recipient", "participant", "attendee",
+];
+
+/// Headers of the column holding each recipient's email address, in priority order.
+pub const EMAIL_ALIASES: &[&str] = &["email", "e-mail", "email address", "e-mail address", "mail"];
+
+/// How the column holding the names is found in a CSV or spreadsheet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameColumn {
+    /// Header picked by the user; when set, the aliases are not consulted.
+    pub header: Option<String>,
+    /// Candidate headers, compared case-insensitively.
+    pub aliases: Vec<String>,
+}
+
+impl Default for NameColumn {
+    fn default() -> Self {
+        NameColumn { header: None, aliases: DEFAULT_NAME_ALIASES.iter().map(|alias| alias.to_string()).collect() }
+    }
+}
+
+impl NameColumn {
+    /// Exactly the column with this header.
+    pub fn header(header: impl Into<String>) -> Self {
+        NameColumn { header: Some(header.into()), ..NameColumn::default() }
+    }
+
+    /// The default aliases plus comma-separated extras from `CERTMAKER_NAME_ALIASES`.
+    pub fn from_env() -> Self {
+        let mut column = NameColumn::default();
+        if let Ok(extra) = std::env::var("CERTMAKER_NAME_ALIASES") {
+            column.aliases.extend(extra.split(',').map(str::trim).filter(|alias| !alias.is_empty()).map(str::to_string));
+        }
+        column
+    }
+
+    /// Index of the name column among `headers`.
+    pub fn find(&self, headers: &[String]) -> Option<usize> {
+        let position = |wanted: &str| headers.iter().position(|header| header.trim().eq_ignore_ascii_case(wanted.trim()));
+        match &self.header {
+            Some(header) => position(header),
+            None => self.aliases.iter().find_map(|alias| position(alias)),
+        }
+    }
+
+    /// [`CertError::MissingColumn`] for a file with these headers.
+    pub fn missing(&self, headers: &[String]) -> CertError {
+        CertError::MissingColumn {
+            wanted: self.header.clone().unwrap_or_else(|| "Name".to_string()),
+            available: headers.to_vec(),
+        }
+    }
+}
+
+/// Anything that can produce the rows of a batch.
+pub trait NameSource {
+    fn records(&mut self) -> Result<Vec<Record>>;
+
+    /// Just the names, in row order.
+    fn names(&mut self) -> Result<Vec<String>> {
+        Ok(self.records()?.into_iter().map(|record| record.name).collect())
+    }
+}
+
+impl<S: NameSource + ?Sized> NameSource for Box<S> {
+    fn records(&mut self) -> Result<Vec<Record>> {
+        (**self).records()
+    }
+}
+
+/// In-memory names, e.g. from a database query or a test.
+impl NameSource for Vec<String> {
+    fn records(&mut self) -> Result<Vec<Record>> {
+        Ok(self.iter().map(Record::from_name).collect())
+    }
+}
+
+/// A CSV file with a `Name` column (or one of the [`NameColumn`] aliases).
+#[derive(Debug, Clone)]
+pub struct CsvSource {
+    path: PathBuf,
+    name_column: NameColumn,
+    format: CsvFormat,
+}
+
+impl CsvSource {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        CsvSource { path: path.into(), name_column: NameColumn::default(), format: CsvFormat::default() }
+    }
+
+    /// Force the delimiter or encoding instead of detecting them.
+    pub fn format(mut self, format: CsvFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn name_column(mut self, name_column: NameColumn) -> Self {
+        self.name_column = name_column;
+        self
+    }
+}
+
+impl NameSource for CsvSource {
+    fn records(&mut self) -> Result<Vec<Record>> {
+        Ok(parse_csv_file_with(&self.path, &self.name_column, &self.format)?.records)
+    }
+}
+
+/// A text file with one name per line; blank lines and `#` comments are skipped.
+#[derive(Debug, Clone)]
+pub struct TextSource {
+    path: PathBuf,
+}
+
+impl TextSource {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        TextSource { path: path.into() }
+    }
+}
+
+impl NameSource for TextSource {
+    fn records(&mut self) -> Result<Vec<Record>> {
+        Ok(parse_text_list(&self.path)?.records)
+    }
+}
+
+/// A JSON array of names, or of objects with a `name` key (or one of the [`NameColumn`] aliases).
+#[derive(Debug, Clone)]
+pub struct JsonSource {
+    path: PathBuf,
+    name_column: NameColumn,
+}
+
+impl JsonSource {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        JsonSource { path: path.into(), name_column: NameColumn::default() }
+    }
+
+    pub fn name_column(mut self, name_column: NameColumn) -> Self {
+        self.name_column = name_column;
+        self
+    }
+}
+
+impl NameSource for JsonSource {
+    fn records(&mut self) -> Result<Vec<Record>> {
+        Ok(parse_json_list_with(&self.path, &self.name_column)?.records)
+    }
+}
+
+/// The first worksheet of an .xlsx/.xls/.ods workbook, with a `Name` header cell.
+#[cfg(feature = "xlsx")]
+#[derive(Debug, Clone)]
+pub struct XlsxSource {
+    path: PathBuf,
+    name_column: NameColumn,
+}
+
+#[cfg(feature = "xlsx")]
+impl XlsxSource {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        XlsxSource { path: path.into(), name_column: NameColumn::default() }
+    }
+
+    pub fn name_column(mut self, name_column: NameColumn) -> Self {
+        self.name_column = name_column;
+        self
+    }
+}
+
+#[cfg(feature = "xlsx")]
+impl NameSource for XlsxSource {
+    fn records(&mut self) -> Result<Vec<Record>> {
+        use calamine::Reader;
+        use crate::sanitize::clean_value;
+
+        let spreadsheet_error = |source| CertError::Spreadsheet { path: self.path.clone(), source };
+        let mut workbook = calamine::open_workbook_auto(&self.path).map_err(spreadsheet_error)?;
+        let range = workbook.worksheet_range_at(0)
+            .ok_or_else(|| CertError::NoNames(self.path.clone()))?
+            .map_err(spreadsheet_error)?;
+
+        let mut rows = range.rows();
+        let headers: Vec<String> = rows.next()
+            .map(|row| row.iter().map(|cell| cell.to_string().trim().to_string()).collect())
+            .unwrap_or_default();
+        let name_column = self.name_column.find(&headers)
+            .ok_or_else(|| self.name_column.missing(&headers))?;
+
+        // Cleaned like the values of a CSV; what was found isn't reported for spreadsheets
+        let clean = |cell: &calamine::DataType| clean_value(&cell.to_string()).0;
+        let records: Vec<Record> = rows
+            .filter_map(|row| {
+                let name = clean(row.get(name_column)?);
+                if name.is_empty() {
+                    return None;
+                }
+                let fields = headers.iter().cloned()
+                    .zip(row.iter().map(clean))
+                    .collect();
+                Some(Record { name, fields })
+            })
+            .collect();
+
+        if records.is_empty() {
+            return Err(CertError::NoNames(self.path.clone()));
+        }
+        Ok(records)
+    }
+}
+
+/// A name list downloaded from an `https://` URL; Google Sheets links are read as CSV.
+#[cfg(feature = "remote")]
+#[derive(Debug, Clone)]
+pub struct UrlSource {
+    url: String,
+    name_column: NameColumn,
+    format: CsvFormat,
+}
+
+#[cfg(feature = "remote")]
+impl UrlSource {
+    pub fn new(url: impl Into<String>) -> Self {
+        UrlSource { url: url.into(), name_column: NameColumn::default(), format: CsvFormat::default() }
+    }
+
+    /// Force the delimiter or encoding of a downloaded CSV instead of detecting them.
+    pub fn format(mut self, format: CsvFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn name_column(mut self, name_column: NameColumn) -> Self {
+        self.name_column = name_column;
+        self
+    }
+}
+
+#[cfg(feature = "remote")]
+impl NameSource for UrlSource {
+    fn records(&mut self) -> Result<Vec<Record>> {
+        Ok(crate::csvexcelparser::parse_name_list_with(Path::new(&self.url), &self.name_column, &self.format)?.records)
+    }
+}
+
+/// Pick a source for a file by its extension.
+pub fn source_for_path(file_path: impl AsRef<Path>) -> Result<Box<dyn NameSource>> {
+    source_for_path_with(file_path, &NameColumn::default(), &CsvFormat::default())
+}
+
+/// Like [`source_for_path`], finding the name column of CSVs, JSON and spreadsheets with `name_column`
+/// and reading CSVs with `format`.
+pub fn source_for_path_with(file_path: impl AsRef<Path>, name_column: &NameColumn, format: &CsvFormat) -> Result<Box<dyn NameSource>> {
+    let path = file_path.as_ref();
+    if let Some(url) = name_list_url(path) {
+        #[cfg(feature = "remote")]
+        return Ok(Box::new(UrlSource::new(url).name_column(name_column.clone()).format(*format)));
+        #[cfg(not(feature = "remote"))]
+        return Err(CertError::RemoteDisabled(url.to_string()));
+    }
+    let extension = path.extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+
+    match extension.as_str() {
+        "csv" => Ok(Box::new(CsvSource::new(path).name_column(name_column.clone()).format(*format))),
+        "json" => Ok(Box::new(JsonSource::new(path).name_column(name_column.clone()))),
+        "txt" => Ok(Box::new(TextSource::new(path))),
+        #[cfg(feature = "xlsx")]
+        "xlsx" | "xls" | "ods" => Ok(Box::new(XlsxSource::new(path).name_column(name_column.clone()))),
+        _ => Err(CertError::UnsupportedFileType(path.to_path_buf())),
+    }
+}
@@ -0,0 +1,404 @@
+// src/stamp.rs
+//! Stamping the same text, and optionally a logo, onto every image in a directory: "SAMPLE —
+//! NOT VALID" across a folder of photos, say. Each image is drawn by the certificate renderer
+//! as a one-row [`CertificateJob`], so every text option of a batch works here too.
+//!
+//! Images come in all sizes, so positions and sizes are [`Coordinate`]s: percentages of the
+//! image (`"50%"`), physical lengths converted at its DPI (`"10mm"`), or plain pixels. The font
+//! size and logo width are taken against the image's height and width. Images keep their
+//! path relative to the input directory; an unreadable one fails alone.
+//!
+//! ```
+//! use certificate_maker::editpng::save_image;
+//! use certificate_maker::layout::Coordinate;
+//! use certificate_maker::stamp::{StampJob, StampStyle};
+//! use image::{ImageFormat, Rgba, RgbaImage};
+//!
+//! let dir = std::env::temp_dir().join(format!("certmaker-stamp-{}", std::process::id()));
+//! std::fs::create_dir_all(dir.join("photos/day2"))?;
+//! save_image(&RgbaImage::from_pixel(600, 400, Rgba([255, 255, 255, 255])), dir.join("photos/a.png"), ImageFormat::Png)?;
+//! save_image(&RgbaImage::from_pixel(300, 900, Rgba([255, 255, 255, 255])), dir.join("photos/day2/b.png"), ImageFormat::Png)?;
+//! std::fs::write(dir.join("photos/broken.png"), "not an image")?;
+//!
+//! let mut style = StampStyle::new("SAMPLE", "tests/fixtures/DejaVuSans-Latin.ttf");
+//! style.y = Coordinate::from("90%");
+//! let report = StampJob::new(dir.join("photos"), dir.join("stamped"), style).run()?;
+//! assert_eq!((report.generated, report.failed), (2, 1));
+//! assert!(dir.join("stamped/day2/b.png").exists());
+//!
+//! // Centered across the bottom of each image, whatever its size
+//! let stamped = image::open(dir.join("stamped/day2/b.png"))?.to_rgba8();
+//! assert!((0..300).any(|x| stamped.get_pixel(x, 810).0[0] < 128));
+//! assert!((0..300).all(|x| stamped.get_pixel(x, 450).0[0] == 255));
+//! std::fs::remove_dir_all(&dir)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use crate::csvexcelparser::{BatchItem, BatchReport};
+use crate::dpi::image_dpi;
+use crate::editpng::{Anchor, SyntheticStyle, TextOverflow, load_font, save_image};
+use crate::effects::TextEffect;
+use crate::error::{CertError, IoContext, Result};
+use crate::files::{list_files_with_extensions, next_free_path};
+use crate::graphics::{GraphicField, GraphicKind, ImageStyle};
+use crate::job::{CertificateJob, OverwritePolicy};
+use crate::layout::{Coordinate, TemplateScale};
+use crate::orientation::oriented_dimensions;
+use crate::palette::check_color;
+use crate::progress::{ProgressSink, call_sink};
+use crate::timing::{PerformanceBreakdown, Stage, StageTimings};
+use crate::typography::Typography;
+
+/// Extensions of the images a directory is stamped for.
+pub const STAMP_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "bmp", "gif", "tif", "tiff", "webp"];
+
+/// The text, and optional logo, stamped on every image.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StampStyle {
+    pub text: String,
+    pub font: PathBuf,
+    /// Pixels, a length, or a percentage of the image's height.
+    pub size: Coordinate,
+    pub color: String,
+    pub x: Coordinate,
+    pub y: Coordinate,
+    pub anchor: Anchor,
+    /// Widest the text may be drawn, against the image's width; longer text is shrunk.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_width: Option<Coordinate>,
+    #[serde(default, skip_serializing_if = "SyntheticStyle::is_none")]
+    pub synthetic: SyntheticStyle,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effect: Option<TextEffect>,
+    #[serde(default, skip_serializing_if = "Typography::is_none")]
+    pub typography: Typography,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logo: Option<StampLogo>,
+}
+
+impl StampStyle {
+    /// Black `text` centered on each image, 5% of its height tall.
+    pub fn new(text: impl Into<String>, font: impl Into<PathBuf>) -> Self {
+        StampStyle {
+            text: text.into(),
+            font: font.into(),
+            size: Coordinate::from("5%"),
+            color: "#000000".to_string(),
+            x: Coordinate::from("50%"),
+            y: Coordinate::from("50%"),
+            anchor: Anchor::Center,
+            max_width: None,
+            synthetic: SyntheticStyle::default(),
+            effect: None,
+            typography: Typography::default(),
+            logo: None,
+        }
+    }
+}
+
+/// An image drawn on every stamped image besides the text, such as an event logo.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StampLogo {
+    pub path: PathBuf,
+    pub x: Coordinate,
+    pub y: Coordinate,
+    pub anchor: Anchor,
+    /// Width it is scaled to, against the image's width, keeping its aspect ratio; its own
+    /// size when `None`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<Coordinate>,
+}
+
+/// Every image in `input_dir` stamped with one [`StampStyle`] into `output_dir`.
+#[derive(Debug, Clone)]
+pub struct StampJob {
+    input_dir: PathBuf,
+    output_dir: PathBuf,
+    style: StampStyle,
+    keep_format: bool,
+    overwrite: OverwritePolicy,
+    dry_run: bool,
+}
+
+impl StampJob {
+    /// Stamped images are saved as PNGs, and files already in `output_dir` are left alone.
+    pub fn new(input_dir: impl Into<PathBuf>, output_dir: impl Into<PathBuf>, style: StampStyle) -> Self {
+        StampJob {
+            input_dir: input_dir.into(),
+            output_dir: output_dir.into(),
+            style,
+            keep_format: false,
+            overwrite: OverwritePolicy::SkipExisting,
+            dry_run: false,
+        }
+    }
+
+    /// Save each image in the format it was read in, where that format can be written; PNG
+    /// otherwise, with a warning.
+    pub fn keep_format(mut self, keep_format: bool) -> Self {
+        self.keep_format = keep_format;
+        self
+    }
+
+    /// What to do about a stamped file that already exists.
+    pub fn overwrite(mut self, overwrite: OverwritePolicy) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// Draw every image but save none, so problems show before anything is written.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn style(&self) -> &StampStyle {
+        &self.style
+    }
+
+    /// The images that will be stamped, relative to the input directory.
+    pub fn images(&self) -> Result<Vec<PathBuf>> {
+        list_files_with_extensions(&self.input_dir, STAMP_EXTENSIONS)
+    }
+
+    /// Problems that would stop every image, all of them at once.
+    pub fn validate(&self) -> std::result::Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+        if !self.input_dir.is_dir() {
+            problems.push(format!("Input directory not found: {}", self.input_dir.display()));
+        } else if same_or_inside(&self.output_dir, &self.input_dir) {
+            problems.push(format!("The output directory {} is inside the input directory, so the next run would stamp its own images", self.output_dir.display()));
+        }
+        if self.style.text.trim().is_empty() && self.style.logo.is_none() {
+            problems.push("There is no text or logo to stamp".to_string());
+        }
+        if let Err(e) = load_font(&self.style.font) {
+            problems.push(e.to_string());
+        }
+        if let Err(e) = check_color(&self.style.color) {
+            problems.push(format!("Invalid color '{}': {}", self.style.color, e));
+        }
+        if let Err(e) = self.style.synthetic.validate() {
+            problems.push(e.to_string());
+        }
+        if let Some(Err(e)) = self.style.effect.as_ref().map(TextEffect::validate) {
+            problems.push(e.to_string());
+        }
+        if let Some(logo) = &self.style.logo
+            && let Err(e) = oriented_dimensions(&logo.path) {
+            problems.push(format!("Logo is not a readable image: {} ({})", logo.path.display(), e));
+        }
+        // Percentages and lengths only fail on their syntax, which any size shows
+        if let Err(e) = self.resolve(TemplateScale::pixels((1000, 1000))) {
+            problems.push(e.to_string());
+        }
+        if problems.is_empty() { Ok(()) } else { Err(problems) }
+    }
+
+    /// Validate, then stamp every image.
+    pub fn run(&self) -> Result<BatchReport> {
+        self.run_with_progress(&crate::progress::NoProgress)
+    }
+
+    /// Like [`run`](Self::run), reporting each image to `progress`. Images are stamped in
+    /// parallel, and an image that can't be read, drawn or saved fails by itself.
+    pub fn run_with_progress(&self, progress: &dyn ProgressSink) -> Result<BatchReport> {
+        let started = Instant::now();
+        if let Err(problems) = self.validate() {
+            return Err(CertError::InvalidJob(problems));
+        }
+        let images = self.images()?;
+        if !self.dry_run {
+            std::fs::create_dir_all(&self.output_dir)
+                .io_context(|| format!("Failed to create output directory: {}", self.output_dir.display()))?;
+        }
+
+        // Output names are settled up front, so two images never race for the same file
+        let mut pending = Vec::new();
+        let mut skipped_existing = 0;
+        let mut renamed = 0;
+        let mut taken = std::collections::HashSet::new();
+        for (index, image) in images.into_iter().enumerate() {
+            let (output, format, warning) = self.output_for(&image);
+            let is_taken = |path: &Path| path.exists() || taken.contains(path);
+            let (output, was_renamed) = match self.overwrite {
+                _ if !is_taken(&output) => (output, false),
+                OverwritePolicy::Overwrite => (output, false),
+                OverwritePolicy::SkipExisting => {
+                    skipped_existing += 1;
+                    continue;
+                }
+                OverwritePolicy::Rename => (next_free_path(&output, is_taken), true),
+            };
+            renamed += usize::from(was_renamed);
+            taken.insert(output.clone());
+            pending.push((index, image, output, format, warning));
+        }
+
+        let total = pending.len();
+        let completed = AtomicUsize::new(0);
+        let progress_errors = Mutex::new(Vec::new());
+        if let Some(error) = call_sink(|| progress.on_start(total)) {
+            progress_errors.lock().unwrap_or_else(|e| e.into_inner()).push(error);
+        }
+        let items: Vec<BatchItem> = pending.par_iter()
+            .map(|(index, image, output, format, warning)| {
+                let mut timings = StageTimings::default();
+                let (position, result) = match self.stamp(&self.input_dir.join(image), &mut timings) {
+                    Ok((position, _)) if self.dry_run => (position, Ok(())),
+                    Ok((position, img)) => (position, timings.time(Stage::Writing, || save_stamped(&img, output, *format))),
+                    Err(e) => ((0, 0), Err(e)),
+                };
+                let item = BatchItem {
+                    index: *index,
+                    name: image.display().to_string(),
+                    output_file: output.clone(),
+                    position,
+                    error: result.err().map(|e| e.to_string()),
+                    warnings: warning.iter().cloned().collect(),
+                    ellipsized: Vec::new(),
+                    retouched: Vec::new(),
+                    timings,
+                    drawn: None,
+                };
+                let current_completed = completed.fetch_add(1, Ordering::Relaxed) + 1;
+                if let Some(error) = call_sink(|| progress.on_item(current_completed, &item)) {
+                    progress_errors.lock().unwrap_or_else(|e| e.into_inner()).push(error);
+                }
+                item
+            })
+            .collect();
+
+        let failed = items.iter().filter(|item| item.error.is_some()).count();
+        let mut report = BatchReport {
+            dry_run: self.dry_run,
+            generated: items.len() - failed,
+            skipped_existing,
+            renamed,
+            failed,
+            threads: rayon::current_num_threads(),
+            performance: PerformanceBreakdown::from_timings(items.iter().map(|item| &item.timings)),
+            items,
+            ..BatchReport::default()
+        };
+        if let Some(error) = call_sink(|| progress.on_finish(&report)) {
+            progress_errors.lock().unwrap_or_else(|e| e.into_inner()).push(error);
+        }
+        report.progress_errors = progress_errors.into_inner().unwrap_or_else(|e| e.into_inner());
+        report.duration = started.elapsed();
+        Ok(report)
+    }
+
+    /// Draw one image with the stamp, returning where the text's anchor point went.
+    pub fn stamp(&self, path: &Path, timings: &mut StageTimings) -> Result<((i32, i32), RgbaImage)> {
+        let dimensions = oriented_dimensions(path)
+            .map_err(|source| CertError::ImageDecode { path: path.to_path_buf(), source })?;
+        let dpi = image_dpi(path).ok().flatten();
+        let stamp = timings.time(Stage::Layout, || self.resolve(TemplateScale::new(dimensions, dpi, None)))?;
+        let mut job = CertificateJob::new(path, vec![self.style.text.clone()])
+            .font(&self.style.font)
+            .font_size(stamp.size)
+            .color(&self.style.color)
+            .position(self.style.anchor, stamp.x, stamp.y)
+            .overflow(TextOverflow::Shrink)
+            .synthetic(self.style.synthetic)
+            .typography(self.style.typography)
+            .graphics(stamp.logo.into_iter().collect());
+        if let Some(max_width) = stamp.max_width {
+            job = job.max_width(max_width);
+        }
+        if let Some(effect) = &self.style.effect {
+            job = job.effect(effect.clone());
+        }
+        let img = timings.time(Stage::Drawing, || job.render_image(0))?;
+        Ok(((stamp.x, stamp.y), img))
+    }
+
+    // The style in pixels on an image of `scale`'s size
+    fn resolve(&self, scale: TemplateScale) -> Result<ResolvedStamp> {
+        let resolve = |coordinate: &Coordinate, extent: u32, what: &str| {
+            coordinate.resolve(extent, &scale).map_err(|e| CertError::InvalidStamp(format!("{}: {}", what, e)))
+        };
+        let size = resolve(&self.style.size, scale.height, "size")?;
+        if size <= 0 {
+            return Err(CertError::InvalidStamp(format!("size: the text would be {} pixels tall", size)));
+        }
+        let max_width = self.style.max_width.as_ref().map(|width| resolve(width, scale.width, "max_width")).transpose()?;
+        let logo = match &self.style.logo {
+            Some(logo) => Some(GraphicField {
+                kind: GraphicKind::Image(ImageStyle {
+                    width: logo.width.as_ref().map(|width| resolve(width, scale.width, "logo width")).transpose()?.map(|width| width.max(1) as u32),
+                    height: None,
+                }),
+                data: logo.path.to_string_lossy().into_owned(),
+                x: resolve(&logo.x, scale.width, "logo x")?,
+                y: resolve(&logo.y, scale.height, "logo y")?,
+                anchor: logo.anchor,
+            }),
+            None => None,
+        };
+        Ok(ResolvedStamp {
+            size: size as f32,
+            x: resolve(&self.style.x, scale.width, "x")?,
+            y: resolve(&self.style.y, scale.height, "y")?,
+            max_width: max_width.map(|width| width.max(1) as f32),
+            logo,
+        })
+    }
+
+    // Where an image goes and in what format, with a warning when its own can't be written
+    fn output_for(&self, image: &Path) -> (PathBuf, ImageFormat, Option<String>) {
+        let output = self.output_dir.join(image);
+        let format = ImageFormat::from_path(image).ok();
+        match format {
+            Some(format) if self.keep_format && writable(format) => (output, format, None),
+            Some(format) if self.keep_format => {
+                let warning = format!("{:?} images can't be written, so it is saved as a PNG", format);
+                (output.with_extension("png"), ImageFormat::Png, Some(warning))
+            }
+            _ => (output.with_extension("png"), ImageFormat::Png, None),
+        }
+    }
+}
+
+// The stamp of one image in pixels
+struct ResolvedStamp {
+    size: f32,
+    x: i32,
+    y: i32,
+    max_width: Option<f32>,
+    logo: Option<GraphicField>,
+}
+
+// Formats the image crate can encode as built here
+fn writable(format: ImageFormat) -> bool {
+    matches!(format, ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Bmp | ImageFormat::Gif | ImageFormat::Tiff)
+}
+
+// Save a stamped image, making its directory first; JPEGs have no alpha channel to write
+fn save_stamped(img: &RgbaImage, path: &Path, format: ImageFormat) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).io_context(|| format!("Failed to create output directory: {}", dir.display()))?;
+    }
+    match format {
+        ImageFormat::Jpeg => DynamicImage::ImageRgba8(img.clone()).to_rgb8().save_with_format(path, format)
+            .map_err(|source| CertError::ImageEncode { path: path.to_path_buf(), source }),
+        _ => save_image(img, path, format),
+    }
+}
+
+// Whether `dir` is `parent` or somewhere under it, compared once both exist
+fn same_or_inside(dir: &Path, parent: &Path) -> bool {
+    let canonical = |path: &Path| path.canonicalize().ok();
+    match (canonical(parent), dir.ancestors().find_map(canonical)) {
+        (Some(parent), Some(dir)) => dir.starts_with(&parent),
+        _ => false,
+    }
+}
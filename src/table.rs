@@ -0,0 +1,355 @@
+// src/table.rs
+//! Tables: rows of cells in fixed columns, such as the modules, scores and dates on a
+//! completion certificate. Each column has a width, an alignment and what happens to text too
+//! wide for it, so a long module name never runs into the score next to it. The rows are cell
+//! templates filled in from each CSV row, or one column holding the cells as JSON.
+//!
+//! ```
+//! use certificate_maker::editpng::{TextOverflow, load_font};
+//! use certificate_maker::table::{CellAlign, TableColumn, TableStyle, draw_table, layout_table, parse_cells};
+//! use image::{Rgba, RgbaImage};
+//!
+//! let style = TableStyle {
+//!     columns: vec![
+//!         TableColumn { header: Some("Module".to_string()), overflow: TextOverflow::Ellipsis, ..TableColumn::new(180) },
+//!         TableColumn { header: Some("Score".to_string()), align: CellAlign::Right, ..TableColumn::new(90) },
+//!     ],
+//!     rule: 1.0,
+//!     ..TableStyle::new("DejaVuSans.ttf", 20.0)
+//! };
+//! let rows = parse_cells(r#"[["Algebra", 92], ["Differential Equations and Their Applications", 88]]"#, 2)?;
+//! let font = load_font(&style.font)?;
+//! let table = layout_table(&style, &rows, &font, &font)?;
+//! assert_eq!((table.width, table.height), (270, 3 * 32));
+//!
+//! // The long module name is cut short inside its column
+//! let long = table.cells.iter().find(|cell| cell.text.starts_with("Differential")).unwrap();
+//! assert_eq!(long.full_text.as_deref(), Some("Differential Equations and Their Applications"));
+//! assert!(long.text.ends_with('…'));
+//! assert_eq!(long.size, 20.0);
+//!
+//! let mut img = RgbaImage::from_pixel(300, 120, Rgba([255, 255, 255, 255]));
+//! draw_table(&mut img, &table, (15, 10), &style, &font, &font)?;
+//! assert_eq!(img.get_pixel(150, 10 + 32), &Rgba([0, 0, 0, 255])); // the rule under the header
+//! # Ok::<(), certificate_maker::error::CertError>(())
+//! ```
+use image::{Rgba, RgbaImage};
+use rusttype::Font;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+use crate::editpng::{Anchor, ELLIPSIS, SyntheticStyle, TextBounds, TextOptions, TextOverflow, blend_over, draw_text, fit_text, hex_to_rgba, load_font};
+use crate::error::{CertError, Result};
+use crate::graphics::fill_placeholders;
+use crate::palette::check_color;
+use crate::source::Record;
+
+/// Row height, as a multiple of the font size, when a table doesn't set one.
+pub const ROW_HEIGHT: f32 = 1.6;
+
+/// Space kept between a cell's text and its column's edges, as a fraction of the font size.
+pub const CELL_PADDING: f32 = 0.3;
+
+/// Where text sits across its column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CellAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+impl CellAlign {
+    /// Every alignment, in the order help text lists them.
+    pub const ALL: [CellAlign; 3] = [CellAlign::Left, CellAlign::Center, CellAlign::Right];
+
+    /// The name layout files use.
+    pub fn name(self) -> &'static str {
+        match self {
+            CellAlign::Left => "left",
+            CellAlign::Center => "center",
+            CellAlign::Right => "right",
+        }
+    }
+}
+
+/// One column of a table.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableColumn {
+    /// Width in pixels, padding included.
+    pub width: u32,
+    #[serde(default)]
+    pub align: CellAlign,
+    #[serde(default, skip_serializing_if = "TextOverflow::is_shrink")]
+    pub overflow: TextOverflow,
+    /// What cut-short text ends with; [`ELLIPSIS`] when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ellipsis: Option<String>,
+    /// Text of the header row; without a header on any column there is no header row.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header: Option<String>,
+}
+
+impl TableColumn {
+    /// A left-aligned column of `width` pixels that shrinks text too wide for it.
+    pub fn new(width: u32) -> Self {
+        TableColumn { width, align: CellAlign::default(), overflow: TextOverflow::default(), ellipsis: None, header: None }
+    }
+}
+
+/// How a table is set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableStyle {
+    /// Font file or family and style, as for [`CertificateJob::font`](crate::job::CertificateJob::font).
+    pub font: PathBuf,
+    /// Font of the header row, such as a bold face; `font` when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header_font: Option<PathBuf>,
+    pub size: f32,
+    pub color: String,
+    pub columns: Vec<TableColumn>,
+    /// Cell templates of each row, filled in like other fields' data. When empty, the field's
+    /// data names a column holding the cells as JSON; see [`parse_cells`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rows: Vec<Vec<String>>,
+    /// Height of every row in pixels; [`ROW_HEIGHT`] times the size when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub row_height: Option<f32>,
+    /// Thickness of the rules between rows, header included; 0 draws none.
+    #[serde(default)]
+    pub rule: f32,
+    /// Color of the rules; the text color when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule_color: Option<String>,
+}
+
+impl TableStyle {
+    /// A black table in `font` at `size` pixels, without columns, rows or rules yet.
+    pub fn new(font: impl Into<PathBuf>, size: f32) -> Self {
+        TableStyle {
+            font: font.into(),
+            header_font: None,
+            size,
+            color: "#000000".to_string(),
+            columns: Vec::new(),
+            rows: Vec::new(),
+            row_height: None,
+            rule: 0.0,
+            rule_color: None,
+        }
+    }
+
+    /// The font of the header row.
+    pub fn header_font(&self) -> &Path {
+        self.header_font.as_deref().unwrap_or(&self.font)
+    }
+
+    /// Height of every row in pixels.
+    pub fn row_height(&self) -> f32 {
+        self.row_height.unwrap_or(self.size * ROW_HEIGHT)
+    }
+
+    /// Whether a header row is drawn above the cells.
+    pub fn has_header(&self) -> bool {
+        self.columns.iter().any(|column| column.header.is_some())
+    }
+
+    /// Check the fonts, colors and sizes, and that no row has more cells than there are columns.
+    pub fn validate(&self) -> Result<()> {
+        load_font(&self.font)?;
+        if let Some(header_font) = &self.header_font {
+            load_font(header_font)?;
+        }
+        check_color(&self.color)?;
+        if let Some(rule_color) = &self.rule_color {
+            check_color(rule_color)?;
+        }
+        if !self.size.is_finite() || self.size <= 0.0 {
+            return Err(CertError::InvalidFontSize(self.size));
+        }
+        if self.columns.is_empty() {
+            return Err(CertError::InvalidTable("a table needs at least one column".to_string()));
+        }
+        if let Some(index) = self.columns.iter().position(|column| column.width == 0) {
+            return Err(CertError::InvalidTable(format!("column {} is 0 pixels wide", index + 1)));
+        }
+        if let Some(row_height) = self.row_height
+            && (!row_height.is_finite() || row_height <= 0.0) {
+            return Err(CertError::InvalidTable(format!("the row height must be a positive number, got {}", row_height)));
+        }
+        if !self.rule.is_finite() || self.rule < 0.0 {
+            return Err(CertError::InvalidTable(format!("the rule thickness must be 0 or more pixels, got {}", self.rule)));
+        }
+        check_row_lengths(&self.rows, self.columns.len())
+    }
+}
+
+/// One cell as placed by [`layout_table`], relative to the table's top-left corner.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableCell {
+    /// The text as drawn, cut short when its column ellipsizes.
+    pub text: String,
+    /// The whole text, when it was cut short to fit.
+    pub full_text: Option<String>,
+    /// The size it is drawn at, smaller than the table's when its column shrinks it.
+    pub size: f32,
+    /// Whether it is in the header row, drawn with the header font.
+    pub header: bool,
+    pub x: i32,
+    pub y: i32,
+    pub anchor: Anchor,
+}
+
+/// A table's cells and rules placed for one certificate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableLayout {
+    pub cells: Vec<TableCell>,
+    /// Rectangles of the rules, relative to the table's top-left corner.
+    pub rules: Vec<TextBounds>,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Rows of cells from JSON: a list of rows, each a list of cells, or one flat list of cells
+/// taken `columns` at a time. Numbers and booleans are written as they are and `null` is an
+/// empty cell; a row shorter than the table is padded with empty cells.
+///
+/// ```
+/// use certificate_maker::table::parse_cells;
+///
+/// let nested = parse_cells(r#"[["Algebra", 92], ["Geometry", null]]"#, 2)?;
+/// let flat = parse_cells(r#"["Algebra", "92", "Geometry", ""]"#, 2)?;
+/// assert_eq!(nested, flat);
+/// assert!(parse_cells(r#"[["Algebra", 92, "2026-05-01"]]"#, 2).is_err());
+/// # Ok::<(), certificate_maker::error::CertError>(())
+/// ```
+pub fn parse_cells(data: &str, columns: usize) -> Result<Vec<Vec<String>>> {
+    let value: Value = serde_json::from_str(data.trim())
+        .map_err(|e| CertError::InvalidTable(format!("the cells aren't a JSON list such as [[\"Algebra\", 92]]: {}", e)))?;
+    let Value::Array(items) = value else {
+        return Err(CertError::InvalidTable("the cells aren't a JSON list such as [[\"Algebra\", 92]]".to_string()));
+    };
+    let cell = |value: &Value| match value {
+        Value::String(text) => Ok(text.clone()),
+        Value::Null => Ok(String::new()),
+        Value::Number(_) | Value::Bool(_) => Ok(value.to_string()),
+        other => Err(CertError::InvalidTable(format!("a cell is {}, not text or a number", if other.is_array() { "a list" } else { "an object" }))),
+    };
+    let mut rows = if items.iter().all(Value::is_array) {
+        items.iter()
+            .map(|row| row.as_array().into_iter().flatten().map(cell).collect::<Result<Vec<_>>>())
+            .collect::<Result<Vec<_>>>()?
+    } else {
+        let cells = items.iter().map(cell).collect::<Result<Vec<_>>>()?;
+        cells.chunks(columns.max(1)).map(<[String]>::to_vec).collect()
+    };
+    check_row_lengths(&rows, columns)?;
+    for row in &mut rows {
+        row.resize(columns, String::new());
+    }
+    Ok(rows)
+}
+
+/// Cell templates with `{name}` and every `{Column}` filled in (see
+/// [`fill_placeholders`]), as the JSON [`parse_cells`] reads back.
+pub fn fill_rows(rows: &[Vec<String>], name: &str, record: Option<&Record>) -> Result<String> {
+    let filled = rows.iter()
+        .map(|row| row.iter().map(|cell| fill_placeholders(cell, name, record)).collect::<Result<Vec<_>>>())
+        .collect::<Result<Vec<_>>>()?;
+    serde_json::to_string(&filled).map_err(|e| CertError::InvalidTable(e.to_string()))
+}
+
+fn check_row_lengths(rows: &[Vec<String>], columns: usize) -> Result<()> {
+    match rows.iter().position(|row| row.len() > columns) {
+        Some(index) => Err(CertError::InvalidTable(format!("row {} has {} cells, but the table has {} columns", index + 1, rows[index].len(), columns))),
+        None => Ok(()),
+    }
+}
+
+/// Place the header and `rows` in their columns: each cell's text at its column's alignment,
+/// centered in its row's height, and shrunk or cut short to fit between the column's padding.
+pub fn layout_table(style: &TableStyle, rows: &[Vec<String>], font: &Font, header_font: &Font) -> Result<TableLayout> {
+    let row_height = style.row_height();
+    let header: Option<Vec<String>> = style.has_header()
+        .then(|| style.columns.iter().map(|column| column.header.clone().unwrap_or_default()).collect());
+    let all_rows: Vec<(bool, &Vec<String>)> = header.iter().map(|row| (true, row)).chain(rows.iter().map(|row| (false, row))).collect();
+
+    let mut cells = Vec::new();
+    for (index, (is_header, row)) in all_rows.iter().enumerate() {
+        let middle = (row_height * (index as f32 + 0.5)).round() as i32;
+        let mut left = 0;
+        for (column, text) in style.columns.iter().zip(row.iter()) {
+            let padding = (style.size * CELL_PADDING).min(column.width as f32 / 4.0);
+            let inner = column.width as f32 - 2.0 * padding;
+            let (x, anchor) = match column.align {
+                CellAlign::Left => (left + padding.round() as i32, Anchor::CenterLeft),
+                CellAlign::Center => (left + column.width as i32 / 2, Anchor::Center),
+                CellAlign::Right => (left + column.width as i32 - padding.round() as i32, Anchor::CenterRight),
+            };
+            left += column.width as i32;
+            if text.trim().is_empty() {
+                continue;
+            }
+            let options = TextOptions {
+                font: if *is_header { header_font } else { font },
+                size: style.size,
+                color: Rgba([0, 0, 0, 255]),
+                x,
+                y: middle,
+                anchor,
+                decorations: &[],
+                synthetic: SyntheticStyle::default(),
+                variation: None,
+                effect: None,
+            };
+            let (drawn, fitted) = fit_text(text, &options, inner.max(1.0), column.overflow, column.ellipsis.as_deref().unwrap_or(ELLIPSIS))?;
+            let full_text = (drawn != *text).then(|| text.clone());
+            cells.push(TableCell { text: drawn, full_text, size: fitted.size, header: *is_header, x, y: middle, anchor });
+        }
+    }
+
+    let width = style.columns.iter().map(|column| column.width).sum();
+    let rules = if style.rule > 0.0 {
+        let thickness = style.rule.round().max(1.0) as i32;
+        (1..all_rows.len())
+            .map(|index| TextBounds { x: 0, y: (row_height * index as f32).round() as i32 - thickness / 2, width: width as i32, height: thickness })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let height = (row_height * all_rows.len() as f32).round() as u32;
+    Ok(TableLayout { cells, rules, width, height })
+}
+
+/// Draw a table placed by [`layout_table`] with its top-left corner at `origin`: the rules,
+/// then the cells over them.
+pub fn draw_table(img: &mut RgbaImage, table: &TableLayout, (origin_x, origin_y): (i32, i32), style: &TableStyle, font: &Font, header_font: &Font) -> Result<()> {
+    let color = hex_to_rgba(&style.color)?;
+    let rule_color = style.rule_color.as_deref().map(hex_to_rgba).transpose()?.unwrap_or(color);
+    for rule in &table.rules {
+        let (x, y) = (origin_x + rule.x, origin_y + rule.y);
+        for py in y.max(0)..(y + rule.height).min(img.height() as i32) {
+            for px in x.max(0)..(x + rule.width).min(img.width() as i32) {
+                blend_over(img.get_pixel_mut(px as u32, py as u32), rule_color, 1.0);
+            }
+        }
+    }
+    for cell in &table.cells {
+        let options = TextOptions {
+            font: if cell.header { header_font } else { font },
+            size: cell.size,
+            color,
+            x: origin_x + cell.x,
+            y: origin_y + cell.y,
+            anchor: cell.anchor,
+            decorations: &[],
+            synthetic: SyntheticStyle::default(),
+            variation: None,
+            effect: None,
+        };
+        draw_text(img, &cell.text, &options)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,186 @@
+// src/templates.rs
+//! Importing templates into the templates directory, after checking they will render well.
+//!
+//! ```no_run
+//! use certificate_maker::templates::{check_template, import_template};
+//!
+//! let check = check_template("Downloads/Award Design (final).jpg")?;
+//! for warning in &check.warnings {
+//!     println!("warning: {}", warning);
+//! }
+//! // Saved as Template/award_design_final.png, converted to 8-bit RGBA
+//! let imported = import_template("Downloads/Award Design (final).jpg", "Template", true)?;
+//! # Ok::<(), certificate_maker::error::CertError>(())
+//! ```
+use image::io::Reader as ImageReader;
+use image::{ColorType, DynamicImage, ImageFormat};
+use std::path::{Path, PathBuf};
+
+use crate::depth::is_deep;
+use crate::error::{CertError, IoContext, Result};
+use crate::files::next_free_path;
+use crate::orientation::{exif_ignored, exif_orientation, open_oriented, orientation};
+
+/// Shortest side a template may have; anything smaller can't hold a legible name.
+pub const MIN_TEMPLATE_SIDE: u32 = 200;
+/// Longest side a template may have before every certificate costs hundreds of MB to render.
+pub const MAX_TEMPLATE_SIDE: u32 = 12_000;
+
+/// What [`check_template`] found out about an image.
+#[derive(Debug, Clone)]
+pub struct TemplateCheck {
+    pub width: u32,
+    pub height: u32,
+    pub format: Option<ImageFormat>,
+    pub color_type: ColorType,
+    /// Things that will render differently than the file looks in an image viewer.
+    pub warnings: Vec<String>,
+    /// Whether [`import_template`] with `convert` would change the pixels' format.
+    pub convertible: bool,
+}
+
+/// Decode an image and check it can serve as a template. Images that can't be decoded, or are
+/// smaller than [`MIN_TEMPLATE_SIDE`] or larger than [`MAX_TEMPLATE_SIDE`], are errors;
+/// CMYK JPEGs, 16-bit images and non-sRGB color profiles are warnings.
+pub fn check_template(path: impl AsRef<Path>) -> Result<TemplateCheck> {
+    let path = path.as_ref();
+    let reader = ImageReader::open(path)
+        .io_context(|| format!("Failed to open image file {}", path.display()))?
+        .with_guessed_format()
+        .io_context(|| format!("Failed to detect image format of {}", path.display()))?;
+    let format = reader.format();
+    let img = reader.decode()
+        .map_err(|source| CertError::ImageDecode { path: path.to_path_buf(), source })?;
+    let (width, height) = match orientation(path) {
+        Some(orientation) => orientation.dimensions((img.width(), img.height())),
+        None => (img.width(), img.height()),
+    };
+    let color_type = img.color();
+
+    let unsuitable = |reason: String| CertError::UnsuitableTemplate { path: path.to_path_buf(), reason };
+    if width.min(height) < MIN_TEMPLATE_SIDE {
+        return Err(unsuitable(format!("{}x{} is too small; both sides need at least {} pixels", width, height, MIN_TEMPLATE_SIDE)));
+    }
+    if width.max(height) > MAX_TEMPLATE_SIDE {
+        return Err(unsuitable(format!("{}x{} is too large; neither side may exceed {} pixels", width, height, MAX_TEMPLATE_SIDE)));
+    }
+
+    let mut warnings = Vec::new();
+    if format == Some(ImageFormat::Jpeg) {
+        let bytes = std::fs::read(path)
+            .io_context(|| format!("Failed to read file {}", path.display()))?;
+        if jpeg_components(&bytes) == Some(4) {
+            warnings.push("it is a CMYK JPEG; its colors are converted naively and will look off".to_string());
+        }
+    }
+    if exif_ignored() && let Some(orientation) = exif_orientation(path) {
+        warnings.push(format!("its EXIF asks for it to be {}, which is being ignored", orientation.describe()));
+    }
+    // PNG certificates keep a 16-bit PNG template's depth; see crate::depth
+    if is_deep(color_type) && format != Some(ImageFormat::Png) {
+        warnings.push("it has 16 bits per channel, which only a PNG template keeps; convert it to keep them".to_string());
+    }
+    if format == Some(ImageFormat::Png) && crate::analysis::analyze_image_file(path)?.png
+        .is_some_and(|details| details.has_non_srgb_profile()) {
+        warnings.push("it embeds a non-sRGB color profile, which text drawing ignores".to_string());
+    }
+
+    Ok(TemplateCheck {
+        width,
+        height,
+        format,
+        color_type,
+        warnings,
+        convertible: format != Some(ImageFormat::Png) || !matches!(color_type, ColorType::Rgba8 | ColorType::Rgba16),
+    })
+}
+
+// Number of color components from a JPEG's start-of-frame segment (3 for RGB, 4 for CMYK)
+fn jpeg_components(bytes: &[u8]) -> Option<u8> {
+    // Skip the SOI marker, then walk the segments: FF, marker, 2-byte length, data
+    let mut offset = 2;
+    while offset + 4 <= bytes.len() {
+        if bytes[offset] != 0xFF {
+            return None;
+        }
+        let marker = bytes[offset + 1];
+        let length = u16::from_be_bytes([bytes[offset + 2], bytes[offset + 3]]) as usize;
+        // SOF0-SOF15, except DHT (C4), JPG (C8) and DAC (CC); components follow precision,
+        // height and width
+        if (0xC0..=0xCF).contains(&marker) && ![0xC4, 0xC8, 0xCC].contains(&marker) {
+            return bytes.get(offset + 9).copied();
+        }
+        offset += 2 + length;
+    }
+    None
+}
+
+/// File name an imported template gets: lowercase letters, digits, `-` and `_` only, with
+/// `.png` when converting and the original extension otherwise.
+///
+/// ```
+/// use certificate_maker::templates::sanitized_template_name;
+///
+/// assert_eq!(sanitized_template_name("Downloads/Award Design (final).JPG", false), "award_design_final.jpg");
+/// assert_eq!(sanitized_template_name("Downloads/Award Design (final).JPG", true), "award_design_final.png");
+/// ```
+pub fn sanitized_template_name(source: impl AsRef<Path>, convert: bool) -> String {
+    let source = source.as_ref();
+    let stem = source.file_stem().unwrap_or_default().to_string_lossy().to_lowercase();
+    let mut name = String::new();
+    for c in stem.chars() {
+        if c.is_ascii_alphanumeric() || c == '-' {
+            name.push(c);
+        } else if !name.ends_with('_') {
+            name.push('_');
+        }
+    }
+    let name = name.trim_matches('_');
+    let name = if name.is_empty() { "template" } else { name };
+    let extension = if convert {
+        "png".to_string()
+    } else {
+        source.extension().unwrap_or_default().to_string_lossy().to_lowercase()
+    };
+    format!("{}.{}", name, extension)
+}
+
+/// Check `source` and copy it into `templates_dir` under [`sanitized_template_name`], next to
+/// existing templates rather than over them. With `convert` the image is saved as an RGBA PNG
+/// instead of copied byte for byte, turned upright by its EXIF orientation; 16-bit images keep
+/// their 16 bits per channel, the rest get 8.
+pub fn import_template(source: impl AsRef<Path>, templates_dir: impl AsRef<Path>, convert: bool) -> Result<PathBuf> {
+    let (source, templates_dir) = (source.as_ref(), templates_dir.as_ref());
+    check_template(source)?;
+    std::fs::create_dir_all(templates_dir)
+        .io_context(|| format!("Failed to create directory: {}", templates_dir.display()))?;
+
+    let destination = templates_dir.join(sanitized_template_name(source, convert));
+    let destination = if destination.exists() { next_free_path(&destination, Path::exists) } else { destination };
+    if convert {
+        let img = open_oriented(source)
+            .map_err(|source_error| CertError::ImageDecode { path: source.to_path_buf(), source: source_error })?;
+        let img = if is_deep(img.color()) { DynamicImage::ImageRgba16(img.into_rgba16()) } else { DynamicImage::ImageRgba8(img.into_rgba8()) };
+        img.save_with_format(&destination, ImageFormat::Png)
+            .map_err(|source| CertError::ImageEncode { path: destination.clone(), source })?;
+    } else {
+        std::fs::copy(source, &destination)
+            .io_context(|| format!("Failed to copy {} to {}", source.display(), destination.display()))?;
+    }
+    Ok(destination)
+}
+
+/// Delete a template along with the `.analysis.json` written next to it, if any. Returns every
+/// file removed.
+pub fn remove_template(template: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+    let template = template.as_ref();
+    let mut removed = Vec::new();
+    for path in [template.to_path_buf(), template.with_extension("analysis.json")] {
+        if path.exists() {
+            std::fs::remove_file(&path)
+                .io_context(|| format!("Failed to remove {}", path.display()))?;
+            removed.push(path);
+        }
+    }
+    Ok(removed)
+}
@@ -0,0 +1,232 @@
+// src/timing.rs
+//! Where rendering time goes: how long each certificate spent in each stage of the pipeline,
+//! and the spread of those times over a batch.
+//!
+//! ```
+//! use certificate_maker::timing::{PerformanceBreakdown, Stage, StageTimings};
+//! use std::time::Duration;
+//!
+//! let rows: Vec<StageTimings> = [10, 20, 30].iter()
+//!     .map(|&ms| {
+//!         let mut timings = StageTimings::default();
+//!         timings.set(Stage::Layout, Duration::from_millis(ms / 10));
+//!         timings.set(Stage::Encoding, Duration::from_millis(ms));
+//!         timings
+//!     })
+//!     .collect();
+//! let breakdown = PerformanceBreakdown::from_timings(&rows);
+//! let encoding = breakdown.stage(Stage::Encoding).unwrap();
+//! assert_eq!((encoding.min, encoding.median, encoding.p95), (Duration::from_millis(10), Duration::from_millis(20), Duration::from_millis(30)));
+//! assert_eq!(breakdown.dominant().map(|stats| stats.stage), Some(Stage::Encoding));
+//! assert!((breakdown.share(Stage::Encoding) - 60.0 / 66.0).abs() < 1e-9);
+//! assert!(breakdown.stage(Stage::Writing).is_none());
+//! assert_eq!(breakdown.summary().unwrap(),
+//!            "PNG encoding accounted for 91% of the rendering time (median 20.0 ms per certificate, p95 30.0 ms), then layout 9%");
+//! ```
+use serde::{Serialize, Serializer};
+use std::time::{Duration, Instant};
+
+/// A step of rendering one certificate, in pipeline order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// Copying the decoded template to draw on.
+    TemplateClone,
+    /// Measuring and placing the text and graphics, fitting text to its maximum width.
+    Layout,
+    /// Rasterizing the text and compositing the graphics.
+    Drawing,
+    /// Compressing the image to PNG in memory.
+    Encoding,
+    /// Writing the file and hashing what was written.
+    Writing,
+}
+
+impl Stage {
+    /// Every stage, in pipeline order.
+    pub const ALL: [Stage; 5] = [Stage::TemplateClone, Stage::Layout, Stage::Drawing, Stage::Encoding, Stage::Writing];
+
+    /// Lowercase description for messages.
+    pub fn label(self) -> &'static str {
+        match self {
+            Stage::TemplateClone => "template copying",
+            Stage::Layout => "layout",
+            Stage::Drawing => "drawing",
+            Stage::Encoding => "PNG encoding",
+            Stage::Writing => "writing",
+        }
+    }
+}
+
+/// How long one certificate spent in each stage; `None` for stages it didn't reach, such as
+/// everything after layout in a dry run or after a row failed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct StageTimings {
+    #[serde(rename = "template_clone_seconds", serialize_with = "serialize_optional_seconds", skip_serializing_if = "Option::is_none")]
+    pub template_clone: Option<Duration>,
+    #[serde(rename = "layout_seconds", serialize_with = "serialize_optional_seconds", skip_serializing_if = "Option::is_none")]
+    pub layout: Option<Duration>,
+    #[serde(rename = "drawing_seconds", serialize_with = "serialize_optional_seconds", skip_serializing_if = "Option::is_none")]
+    pub drawing: Option<Duration>,
+    #[serde(rename = "encoding_seconds", serialize_with = "serialize_optional_seconds", skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<Duration>,
+    #[serde(rename = "writing_seconds", serialize_with = "serialize_optional_seconds", skip_serializing_if = "Option::is_none")]
+    pub writing: Option<Duration>,
+}
+
+impl StageTimings {
+    pub fn get(&self, stage: Stage) -> Option<Duration> {
+        *self.slot(stage)
+    }
+
+    pub fn set(&mut self, stage: Stage, duration: Duration) {
+        *self.slot_mut(stage) = Some(duration);
+    }
+
+    /// Run `f`, recording how long it took as `stage`.
+    pub fn time<T>(&mut self, stage: Stage, f: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let result = f();
+        self.set(stage, started.elapsed());
+        result
+    }
+
+    fn slot(&self, stage: Stage) -> &Option<Duration> {
+        match stage {
+            Stage::TemplateClone => &self.template_clone,
+            Stage::Layout => &self.layout,
+            Stage::Drawing => &self.drawing,
+            Stage::Encoding => &self.encoding,
+            Stage::Writing => &self.writing,
+        }
+    }
+
+    fn slot_mut(&mut self, stage: Stage) -> &mut Option<Duration> {
+        match stage {
+            Stage::TemplateClone => &mut self.template_clone,
+            Stage::Layout => &mut self.layout,
+            Stage::Drawing => &mut self.drawing,
+            Stage::Encoding => &mut self.encoding,
+            Stage::Writing => &mut self.writing,
+        }
+    }
+}
+
+/// The spread of one stage's durations over the certificates that reached it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct StageStats {
+    pub stage: Stage,
+    /// Certificates that reached the stage.
+    pub count: usize,
+    #[serde(rename = "min_seconds", serialize_with = "serialize_seconds")]
+    pub min: Duration,
+    #[serde(rename = "median_seconds", serialize_with = "serialize_seconds")]
+    pub median: Duration,
+    #[serde(rename = "p95_seconds", serialize_with = "serialize_seconds")]
+    pub p95: Duration,
+    /// Summed over every certificate and worker thread.
+    #[serde(rename = "total_seconds", serialize_with = "serialize_seconds")]
+    pub total: Duration,
+}
+
+/// Per-stage statistics of a batch, in pipeline order, leaving out stages no certificate reached.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PerformanceBreakdown {
+    pub stages: Vec<StageStats>,
+}
+
+impl PerformanceBreakdown {
+    pub fn from_timings<'a>(timings: impl IntoIterator<Item = &'a StageTimings>) -> Self {
+        let timings: Vec<&StageTimings> = timings.into_iter().collect();
+        let stages = Stage::ALL.iter()
+            .filter_map(|&stage| {
+                let mut durations: Vec<Duration> = timings.iter().filter_map(|timings| timings.get(stage)).collect();
+                durations.sort();
+                let (&min, count) = (durations.first()?, durations.len());
+                Some(StageStats {
+                    stage,
+                    count,
+                    min,
+                    median: nearest_rank(&durations, 0.5),
+                    p95: nearest_rank(&durations, 0.95),
+                    total: durations.iter().sum(),
+                })
+            })
+            .collect();
+        PerformanceBreakdown { stages }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    pub fn stage(&self, stage: Stage) -> Option<&StageStats> {
+        self.stages.iter().find(|stats| stats.stage == stage)
+    }
+
+    /// Time spent in all stages together. With several workers this is more than the wall
+    /// time of the batch, since their stages overlap.
+    pub fn total(&self) -> Duration {
+        self.stages.iter().map(|stats| stats.total).sum()
+    }
+
+    /// Fraction of [`total`](Self::total) spent in `stage`, from 0 to 1.
+    pub fn share(&self, stage: Stage) -> f64 {
+        let total = self.total().as_secs_f64();
+        match self.stage(stage) {
+            Some(stats) if total > 0.0 => stats.total.as_secs_f64() / total,
+            _ => 0.0,
+        }
+    }
+
+    /// The stage that took the most time overall.
+    pub fn dominant(&self) -> Option<&StageStats> {
+        self.stages.iter().max_by_key(|stats| stats.total)
+    }
+
+    /// One sentence on where the time went, largest stage first, such as "PNG encoding
+    /// accounted for 71% of the rendering time (median 41.2 ms per certificate, p95 48.0 ms),
+    /// then drawing 15%, ..."; `None` when nothing was timed.
+    pub fn summary(&self) -> Option<String> {
+        let dominant = self.dominant()?;
+        let mut others: Vec<&StageStats> = self.stages.iter().filter(|stats| stats.stage != dominant.stage).collect();
+        others.sort_by_key(|stats| std::cmp::Reverse(stats.total));
+        let mut summary = format!("{} accounted for {:.0}% of the rendering time (median {} per certificate, p95 {})",
+                                  capitalized(dominant.stage.label()), self.share(dominant.stage) * 100.0,
+                                  milliseconds(dominant.median), milliseconds(dominant.p95));
+        if !others.is_empty() {
+            let shares: Vec<String> = others.iter()
+                .map(|stats| format!("{} {:.0}%", stats.stage.label(), self.share(stats.stage) * 100.0))
+                .collect();
+            summary.push_str(&format!(", then {}", shares.join(", ")));
+        }
+        Some(summary)
+    }
+}
+
+fn capitalized(text: &str) -> String {
+    let mut chars = text.chars();
+    chars.next().map(|first| first.to_uppercase().chain(chars).collect()).unwrap_or_default()
+}
+
+fn milliseconds(duration: Duration) -> String {
+    format!("{:.1} ms", duration.as_secs_f64() * 1000.0)
+}
+
+// The value at percentile `fraction` of sorted, non-empty `durations`
+fn nearest_rank(durations: &[Duration], fraction: f64) -> Duration {
+    let rank = (fraction * durations.len() as f64).ceil() as usize;
+    durations[rank.clamp(1, durations.len()) - 1]
+}
+
+// Durations go into JSON reports as fractional seconds
+fn serialize_seconds<S: Serializer>(duration: &Duration, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+fn serialize_optional_seconds<S: Serializer>(duration: &Option<Duration>, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    match duration {
+        Some(duration) => serializer.serialize_some(&duration.as_secs_f64()),
+        None => serializer.serialize_none(),
+    }
+}
@@ -0,0 +1,368 @@
+// src/tui.rs
+// Full-screen terminal UI (--tui): arrow-key pickers, a settings form and a live progress screen
+// The answers end up in the same BatchSettings the classic prompts build
+use anyhow::{Result, anyhow};
+use std::path::PathBuf;
+
+use certificate_maker::csvexcelparser::{
+    BatchReport, BatchSettings, MergedCsv, list_csv_files, list_template_files, parse_name_list_with, run_batch,
+};
+use certificate_maker::editpng::{hex_to_rgba, list_available_fonts};
+use certificate_maker::files::check_output_dir;
+use certificate_maker::job::check_position;
+use certificate_maker::orientation::oriented_dimensions;
+use certificate_maker::palette::check_color;
+use certificate_maker::error::CertError;
+use certificate_maker::paths::AppPaths;
+use certificate_maker::progress::{ChannelSink, ProgressEvent};
+use certificate_maker::runlog::{LoggedProgress, RunLog};
+use certificate_maker::{say, tr};
+use ratatui::DefaultTerminal;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Gauge, List, ListState, Paragraph};
+
+use crate::interactive::{BatchOptions, name_column_for, notify_batch_finished, report_batch, typed_path};
+use crate::mailing::email_certificates;
+
+// How many finished rows the progress screen keeps on display
+const RECENT_ROWS: usize = 200;
+
+pub fn run_tui(paths: &AppPaths, options: &BatchOptions) -> Result<()> {
+    let mut terminal = ratatui::init();
+    let result = collect_and_run(&mut terminal, paths, options);
+    ratatui::restore();
+
+    match result? {
+        Some((settings, summary, log)) => {
+            let log = log.and_then(|log| log.inspect_err(|e| say!("⚠️ Could not start the generation log: {}", e)).ok());
+            report_batch(&settings, &summary, log.as_ref())?;
+            if summary.dry_run {
+                return Ok(());
+            }
+            notify_batch_finished(&settings, &summary, paths);
+            match options.email {
+                Some(mode) => email_certificates(&settings, &summary, mode, paths),
+                None => Ok(()),
+            }
+        }
+        None => {
+            say!("↩️ Cancelled.");
+            Ok(())
+        }
+    }
+}
+
+// A finished batch; a generation log that couldn't be opened is only reported once the
+// screen is restored
+type Finished = (BatchSettings, BatchReport, Option<Result<RunLog, CertError>>);
+
+// Pick files, fill in the form, then run the batch; None when the user pressed Esc
+// A CSV without a recognizable name column fails here; --name-column picks one up front
+fn collect_and_run(terminal: &mut DefaultTerminal, paths: &AppPaths, options: &BatchOptions) -> Result<Option<Finished>> {
+    let Some(csv) = pick(terminal, "CSV file", &list_csv_files(&paths.csvs)?)? else { return Ok(None) };
+    let csv_file = paths.csvs.join(csv);
+    let parsed = parse_name_list_with(&csv_file, &name_column_for(options.name_column.as_deref()), &options.csv_format)?;
+    // No prompt for repeated names or rows here; --duplicates decides, dedupe by default, and
+    // --rows, --head or --sample pick the rows
+    let resolved = options.duplicates.unwrap_or_default().apply(&parsed.names, &parsed.rows, &[])?;
+    let selection = options.selection.unwrap_or_default();
+    let selected = selection.select(resolved.names.len());
+    let names = selected.pick(&resolved.names);
+    let merged = MergedCsv { files: vec![parsed] };
+    let emails = merged.emails(&selected.pick(&resolved.rows), &[]);
+    let records = merged.records(&selected.pick(&resolved.rows), &[]);
+
+    let Some(template) = pick(terminal, &tr!("Template"), &list_template_files(&paths.templates)?)? else { return Ok(None) };
+    let template_file = paths.templates.join(template);
+    let (width, height) = oriented_dimensions(&template_file)?;
+
+    let Some(font) = pick(terminal, &tr!("Font"), &list_available_fonts(&paths.fonts)?)? else { return Ok(None) };
+    let font_filename = paths.font(&font);
+
+    let mut form = Form::new(vec![
+        (tr!("X position (text center)"), (width / 2).to_string()),
+        (tr!("Y position (text center)"), (height / 2).to_string()),
+        (tr!("Font size"), "40".to_string()),
+        (tr!("Color (hex)"), "#000000".to_string()),
+        (tr!("Output directory"), paths.certificates.display().to_string()),
+    ]);
+    let heading = tr!("{} names · {} ({}x{}) · {}", names.len(), template_file.display(), width, height, font.display());
+    let settings = loop {
+        if !edit_form(terminal, &mut form, &heading)? {
+            return Ok(None);
+        }
+        // An output directory that can't be written to is caught here, not row by row
+        let values = form.to_settings()
+            .and_then(|values| check_output_dir(&values.4).map(|_| values).map_err(|e| e.to_string()));
+        match values {
+            // Off the template is taken for a typo unless --allow-bleed says it's meant
+            Ok((x_pos, y_pos, ..)) if !options.allow_bleed && check_position(x_pos, y_pos, (width, height)).is_err() => {
+                form.error = Some(tr!("X must be 0 to {} and Y 0 to {} on this template (or run with --allow-bleed)",
+                                      width.saturating_sub(1), height.saturating_sub(1)));
+            }
+            Ok((x_pos, y_pos, font_size, hex_color, output_dir)) => break BatchSettings {
+                csv_file: csv_file.clone(),
+                extra_csv_files: Vec::new(),
+                template_file: template_file.clone(),
+                x_pos,
+                y_pos,
+                allow_bleed: options.allow_bleed,
+                font_filename: font_filename.clone(),
+                font_size,
+                font_size_limits: options.font_size_limits,
+                strict_font_size: options.strict_font_size,
+                hex_color,
+                output_dir,
+                stage_locally: options.stage_locally,
+                overwrite: options.overwrite,
+                name_column: options.name_column.clone(),
+                csv_format: options.csv_format,
+                duplicates: resolved.decisions.clone(),
+                rows: selected.pick(&resolved.rows),
+                sources: Vec::new(),
+                emails: emails.clone(),
+                records: records.clone(),
+                selection,
+                name_case: options.name_case.unwrap_or_default(),
+                typography: options.typography.unwrap_or_default(),
+                case_filenames: options.case_filenames,
+                synthetic: options.synthetic.unwrap_or_default(),
+                font_variation: Vec::new(),
+                effect: None,
+                patches: Vec::new(),
+                graphics: options.graphics.clone(),
+                group_by: options.group_by.clone(),
+                compression: options.compression.unwrap_or_default(),
+                depth: options.depth.unwrap_or_default(),
+                format: options.format.unwrap_or_default(),
+                preview: options.preview,
+                normalize: options.normalize,
+                fingerprint: options.fingerprint.unwrap_or_default(),
+                watermark: options.watermark.clone(),
+                imposition: options.imposition.clone(),
+                dry_run: options.dry_run,
+                verbose: options.verbose,
+                deterministic: options.deterministic,
+                memory_budget: options.memory_budget,
+            },
+            Err(message) => form.error = Some(message),
+        }
+    };
+
+    let log = (!options.no_log).then(|| RunLog::create(&paths.output));
+    let summary = run_with_progress_screen(terminal, &settings, &names, log.as_ref().and_then(|log| log.as_ref().ok()))?;
+    Ok(Some((settings, summary, log)))
+}
+
+// Esc and Ctrl+C both back out of a screen
+fn is_cancel(key: &KeyEvent) -> bool {
+    key.code == KeyCode::Esc || (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL))
+}
+
+// Next key press, ignoring releases and non-key events
+fn next_key() -> Result<KeyEvent> {
+    loop {
+        if let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press {
+            return Ok(key);
+        }
+    }
+}
+
+// Arrow keys move, typing filters (case-insensitive substring), Enter picks, Esc cancels
+fn pick(terminal: &mut DefaultTerminal, title: &str, items: &[PathBuf]) -> Result<Option<PathBuf>> {
+    let labels: Vec<String> = items.iter().map(|item| item.display().to_string()).collect();
+    let mut filter = String::new();
+    let mut state = ListState::default().with_selected(Some(0));
+
+    loop {
+        let needle = filter.to_lowercase();
+        let visible: Vec<usize> = (0..items.len()).filter(|&index| labels[index].to_lowercase().contains(&needle)).collect();
+        match state.selected() {
+            _ if visible.is_empty() => state.select(None),
+            Some(index) if index < visible.len() => {}
+            _ => state.select(Some(0)),
+        }
+
+        terminal.draw(|frame| {
+            let [list_area, filter_area, help_area] =
+                Layout::vertical([Constraint::Min(3), Constraint::Length(3), Constraint::Length(1)]).areas(frame.area());
+            let list = List::new(visible.iter().map(|&index| labels[index].as_str()))
+                .block(Block::bordered().title(format!(" {} ({}/{}) ", title, visible.len(), items.len())))
+                .highlight_style(Style::new().reversed())
+                .highlight_symbol("▶ ");
+            frame.render_stateful_widget(list, list_area, &mut state);
+            frame.render_widget(Paragraph::new(filter.as_str()).block(Block::bordered().title(tr!(" Filter "))), filter_area);
+            frame.render_widget(Line::from(tr!("↑/↓ move · type to filter · Enter select · Esc cancel")).dim(), help_area);
+        })?;
+
+        let key = next_key()?;
+        if is_cancel(&key) {
+            return Ok(None);
+        }
+        match key.code {
+            KeyCode::Enter => {
+                if let Some(index) = state.selected() {
+                    return Ok(Some(items[visible[index]].clone()));
+                }
+            }
+            KeyCode::Up => state.select_previous(),
+            KeyCode::Down => state.select_next(),
+            KeyCode::Backspace => {
+                filter.pop();
+            }
+            KeyCode::Char(c) => filter.push(c),
+            _ => {}
+        }
+    }
+}
+
+// Labelled text fields edited one at a time
+struct Form {
+    fields: Vec<(String, String)>,
+    focus: usize,
+    error: Option<String>,
+}
+
+impl Form {
+    fn new(fields: Vec<(String, String)>) -> Self {
+        Form { fields, focus: 0, error: None }
+    }
+
+    fn value(&self, index: usize) -> &str {
+        self.fields[index].1.trim()
+    }
+
+    // x, y, font size, color and output directory, or a message for the first bad field
+    fn to_settings(&self) -> Result<(i32, i32, f32, String, PathBuf), String> {
+        let x = self.value(0).parse().map_err(|_| tr!("X position must be a whole number"))?;
+        let y = self.value(1).parse().map_err(|_| tr!("Y position must be a whole number"))?;
+        let size: f32 = self.value(2).parse().map_err(|_| tr!("Font size must be a number"))?;
+        if !size.is_finite() || size <= 0.0 {
+            return Err(tr!("Font size must be greater than zero"));
+        }
+        let color = self.value(3).to_string();
+        check_color(&color).map_err(|e| e.to_string())?;
+        if self.value(4).is_empty() {
+            return Err(tr!("Output directory can't be empty"));
+        }
+        Ok((x, y, size, color, typed_path(self.value(4))))
+    }
+}
+
+// Tab/arrows move between fields, Enter submits; Ok(false) when cancelled
+fn edit_form(terminal: &mut DefaultTerminal, form: &mut Form, heading: &str) -> Result<bool> {
+    loop {
+        terminal.draw(|frame| {
+            let mut constraints = vec![Constraint::Length(1)];
+            constraints.extend(form.fields.iter().map(|_| Constraint::Length(3)));
+            constraints.extend([Constraint::Length(1), Constraint::Length(1), Constraint::Min(0)]);
+            let areas = Layout::vertical(constraints).split(frame.area());
+
+            frame.render_widget(Line::from(heading).bold(), areas[0]);
+            for (index, (label, value)) in form.fields.iter().enumerate() {
+                let mut block = Block::bordered().title(format!(" {} ", label));
+                if index == form.focus {
+                    block = block.border_style(Style::new().yellow());
+                }
+                // Preview the color on its own value
+                let mut text_style = Style::new();
+                if index == 3
+                    && let Ok(color) = hex_to_rgba(value.trim()) {
+                    text_style = text_style.fg(Color::Rgb(color[0], color[1], color[2]));
+                }
+                frame.render_widget(Paragraph::new(value.as_str()).style(text_style).block(block), areas[index + 1]);
+            }
+
+            let error_area = areas[form.fields.len() + 1];
+            if let Some(error) = &form.error {
+                frame.render_widget(Line::from(format!("❌ {}", error)).red(), error_area);
+            }
+            frame.render_widget(Line::from(tr!("Tab/↑/↓ switch field · Enter generate · Esc cancel")).dim(),
+                                areas[form.fields.len() + 2]);
+        })?;
+
+        let key = next_key()?;
+        if is_cancel(&key) {
+            return Ok(false);
+        }
+        let count = form.fields.len();
+        match key.code {
+            KeyCode::Enter => return Ok(true),
+            KeyCode::Tab | KeyCode::Down => form.focus = (form.focus + 1) % count,
+            KeyCode::BackTab | KeyCode::Up => form.focus = (form.focus + count - 1) % count,
+            KeyCode::Backspace => {
+                form.fields[form.focus].1.pop();
+            }
+            KeyCode::Char(c) => form.fields[form.focus].1.push(c),
+            _ => {}
+        }
+    }
+}
+
+// Run the batch on a worker thread and redraw a gauge for every progress event
+fn run_with_progress_screen(terminal: &mut DefaultTerminal, settings: &BatchSettings, names: &[String], log: Option<&RunLog>) -> Result<BatchReport> {
+    let (sink, events) = ChannelSink::new();
+
+    std::thread::scope(|scope| {
+        let worker = scope.spawn(move || {
+            let result = match log {
+                Some(log) => {
+                    log.settings(settings, names.len());
+                    run_batch(settings, names, &LoggedProgress::new(log, &sink))
+                }
+                None => run_batch(settings, names, &sink),
+            };
+            result.inspect_err(|e| {
+                if let Some(log) = log {
+                    log.failure(e);
+                }
+            })
+        });
+
+        let mut total = 0;
+        let mut done = 0;
+        let mut recent: Vec<String> = Vec::new();
+        // The channel closes when the worker drops the sink
+        for event in &events {
+            match event {
+                ProgressEvent::Started { total: rows } => total = rows,
+                ProgressEvent::Item { index, item } => {
+                    done = index;
+                    recent.push(match (&item.error, item.warnings.first()) {
+                        (Some(error), _) => format!("❌ {}: {}", item.name, error),
+                        (None, Some(warning)) => format!("⚠️ {}: {}", item.name, warning),
+                        (None, None) => format!("✅ {} → {}", item.name, item.output_file.display()),
+                    });
+                    if recent.len() > RECENT_ROWS {
+                        recent.remove(0);
+                    }
+                }
+                ProgressEvent::Finished(_) => {}
+            }
+
+            terminal.draw(|frame| {
+                let [gauge_area, list_area] =
+                    Layout::vertical([Constraint::Length(3), Constraint::Min(3)]).areas(frame.area());
+                let ratio = if total == 0 { 1.0 } else { done as f64 / total as f64 };
+                let title = if settings.dry_run { tr!(" Checking certificates (dry run) ") } else { tr!(" Generating certificates ") };
+                let gauge = Gauge::default()
+                    .block(Block::bordered().title(title))
+                    .gauge_style(Style::new().green())
+                    .ratio(ratio.clamp(0.0, 1.0))
+                    .label(format!("{} / {}", done, total));
+                frame.render_widget(gauge, gauge_area);
+
+                // Newest rows at the bottom, like the console output
+                let rows = list_area.height.saturating_sub(2) as usize;
+                let shown = recent.iter().skip(recent.len().saturating_sub(rows)).map(String::as_str);
+                frame.render_widget(List::new(shown).block(Block::bordered().title(tr!(" Rows "))), list_area);
+            })?;
+        }
+
+        Ok(worker.join().map_err(|_| anyhow!("Batch worker panicked"))??)
+    })
+}
@@ -0,0 +1,166 @@
+// src/typography.rs
+//! Typographic cleanup of the text drawn on certificates.
+//!
+//! Lists typed in a spreadsheet come with straight quotes, `--` for dashes, `...` and stray
+//! spaces or punctuation. Each transform of [`Typography`] can be turned on by itself; it only
+//! changes what is drawn, never filenames or the manifest.
+//!
+//! ```
+//! use certificate_maker::typography::Typography;
+//!
+//! let typography = Typography::all();
+//! assert_eq!(typography.apply("\"Best  Speaker\"  -- Seán O'Brien..."), "“Best Speaker” – Seán O’Brien…");
+//! assert_eq!(typography.apply("  , Ada Lovelace ;"), "Ada Lovelace");
+//! assert_eq!(typography.apply("the '90s 'classic'"), "the ’90s ‘classic’");
+//!
+//! let quotes: Typography = "quotes".parse()?;
+//! assert_eq!(quotes.apply("Don't -- panic..."), "Don’t -- panic...");
+//! assert!("quotes,kerning".parse::<Typography>().is_err());
+//! # Ok::<(), certificate_maker::error::CertError>(())
+//! ```
+use serde::{Deserialize, Serialize};
+
+use crate::error::{CertError, Result};
+
+/// Names of the transforms, as given in a list such as `"quotes,dashes"`.
+pub const TRANSFORMS: &[&str] = &["quotes", "dashes", "ellipses", "spaces", "trim"];
+
+/// Punctuation trimmed from either end of a value by [`Typography::trim`]; a full stop is only
+/// trimmed from the start, since "Jr." ends in one.
+pub const TRIMMED_PUNCTUATION: &[char] = &[',', ';', ':', '-', '_', '*', '/', '\\', '|', '~', '·', '•'];
+
+/// Which typographic transforms are applied to drawn text; all off by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Typography {
+    /// Straight quotes become curly ones, and apostrophes (O’Brien, don’t, ’90s) right quotes.
+    pub quotes: bool,
+    /// `--` becomes an en dash.
+    pub dashes: bool,
+    /// `...` becomes an ellipsis.
+    pub ellipses: bool,
+    /// Runs of spaces and tabs become one space.
+    pub spaces: bool,
+    /// Whitespace and stray punctuation are trimmed from both ends.
+    pub trim: bool,
+}
+
+impl Typography {
+    /// Every transform.
+    pub fn all() -> Self {
+        Typography { quotes: true, dashes: true, ellipses: true, spaces: true, trim: true }
+    }
+
+    /// Whether no transform is on, so text is drawn as it is in the list.
+    pub fn is_none(&self) -> bool {
+        *self == Typography::default()
+    }
+
+    /// Clean up one value. Trimming and spacing go first, so the quotes see the final neighbours.
+    pub fn apply(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        if self.trim {
+            text = trim_noise(&text);
+        }
+        if self.spaces {
+            text = collapse_spaces(&text);
+        }
+        if self.ellipses {
+            text = text.replace("...", "…");
+        }
+        if self.dashes {
+            text = text.replace("--", "–");
+        }
+        if self.quotes {
+            text = smart_quotes(&text);
+        }
+        text
+    }
+
+    /// Names of the transforms that are on, e.g. for a settings summary.
+    pub fn label(&self) -> String {
+        let names: Vec<&str> = TRANSFORMS.iter()
+            .zip([self.quotes, self.dashes, self.ellipses, self.spaces, self.trim])
+            .filter(|(_, on)| *on)
+            .map(|(name, _)| *name)
+            .collect();
+        if names.is_empty() { "off".to_string() } else { names.join(", ") }
+    }
+}
+
+impl std::str::FromStr for Typography {
+    type Err = CertError;
+
+    /// A comma-separated list of [`TRANSFORMS`], `all` or `none`.
+    fn from_str(list: &str) -> Result<Self> {
+        let mut typography = Typography::default();
+        for name in list.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            match name.to_ascii_lowercase().as_str() {
+                "all" => typography = Typography::all(),
+                "none" => typography = Typography::default(),
+                "quotes" => typography.quotes = true,
+                "dashes" => typography.dashes = true,
+                "ellipses" => typography.ellipses = true,
+                "spaces" => typography.spaces = true,
+                "trim" => typography.trim = true,
+                _ => return Err(CertError::InvalidTypography(format!(
+                    "'{}' isn't a transform; use {}, all or none", name, TRANSFORMS.join(", ")))),
+            }
+        }
+        Ok(typography)
+    }
+}
+
+/// Straight quotes made curly. A quote opens at the start of the text or after a space or an
+/// opening bracket, dash or quote, and closes anywhere else; a single quote between two letters
+/// or before a digit ("’90s") is an apostrophe.
+pub fn smart_quotes(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let opens = |index: usize| {
+        index == 0 || matches!(chars[index - 1], c if c.is_whitespace() || "([{<–—-“‘".contains(c))
+    };
+    chars.iter().enumerate()
+        .map(|(index, &c)| match c {
+            '"' if opens(index) => '“',
+            '"' => '”',
+            '\'' if opens(index) && chars.get(index + 1).is_some_and(char::is_ascii_digit) => '’',
+            '\'' if opens(index) => '‘',
+            '\'' => '’',
+            other => other,
+        })
+        .collect()
+}
+
+/// Runs of spaces and tabs collapsed into one space; line breaks are kept.
+pub fn collapse_spaces(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut in_run = false;
+    for c in text.chars() {
+        if c == ' ' || c == '\t' {
+            if !in_run {
+                collapsed.push(' ');
+            }
+            in_run = true;
+        } else {
+            collapsed.push(c);
+            in_run = false;
+        }
+    }
+    collapsed
+}
+
+/// Whitespace and [`TRIMMED_PUNCTUATION`] trimmed from both ends, plus full stops from the start.
+pub fn trim_noise(text: &str) -> String {
+    text.trim_start_matches(|c: char| c.is_whitespace() || c == '.' || TRIMMED_PUNCTUATION.contains(&c))
+        .trim_end_matches(|c: char| c.is_whitespace() || TRIMMED_PUNCTUATION.contains(&c))
+        .to_string()
+}
+
+/// A drawn value the typography pass changed, listed in verbose output and the generation log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetouchedText {
+    /// "name", a text field's template such as "{Course}", or "table" for a table's cells.
+    pub field: String,
+    pub before: String,
+    pub after: String,
+}
@@ -0,0 +1,353 @@
+// src/variation.rs
+//! Variable fonts: the axes and named instances a font offers, and drawing text at chosen axis
+//! values. Static fonts have no axes and keep going through rusttype untouched.
+//!
+//! ```no_run
+//! use certificate_maker::editpng::load_font_data;
+//! use certificate_maker::variation::{format_axis_values, named_instances, variation_axes};
+//!
+//! let data = load_font_data("Inter.ttf")?;
+//! for axis in variation_axes(&data) {
+//!     println!("{} ({}): {} to {}, default {}", axis.name, axis.tag, axis.min, axis.max, axis.default);
+//! }
+//! for instance in named_instances(&data) {
+//!     println!("{}: {}", instance.name, format_axis_values(&instance.coordinates));
+//! }
+//! # Ok::<(), certificate_maker::error::CertError>(())
+//! ```
+use ab_glyph_rasterizer::{Point, Rasterizer, point};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use ttf_parser::{Face, GlyphId, OutlineBuilder, Tag};
+
+use crate::editpng::{RasterGlyph, load_font_data};
+use crate::error::{CertError, Result};
+
+/// One axis of a variable font set to a value, written `wght=700`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AxisValue {
+    /// Four-letter axis tag: `wght`, `wdth`, `opsz`, ...
+    pub tag: String,
+    pub value: f32,
+}
+
+impl fmt::Display for AxisValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={}", self.tag, self.value)
+    }
+}
+
+impl FromStr for AxisValue {
+    type Err = CertError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (tag, value) = s.split_once('=')
+            .ok_or_else(|| CertError::InvalidVariation(format!("'{}' is not tag=value, like wght=700", s.trim())))?;
+        let tag = tag.trim();
+        if tag.len() != 4 || !tag.is_ascii() {
+            return Err(CertError::InvalidVariation(format!("'{}' is not a four-letter axis tag", tag)));
+        }
+        let value = value.trim().parse::<f32>().ok().filter(|value| value.is_finite())
+            .ok_or_else(|| CertError::InvalidVariation(format!("'{}' is not a number", value.trim())))?;
+        Ok(AxisValue { tag: tag.to_string(), value })
+    }
+}
+
+/// Parse axis values separated by commas or spaces.
+///
+/// ```
+/// use certificate_maker::variation::{format_axis_values, parse_axis_values};
+///
+/// let values = parse_axis_values("wght=700, wdth=87.5")?;
+/// assert_eq!(values[0].value, 700.0);
+/// assert_eq!(format_axis_values(&values), "wght=700, wdth=87.5");
+/// # Ok::<(), certificate_maker::error::CertError>(())
+/// ```
+pub fn parse_axis_values(text: &str) -> Result<Vec<AxisValue>> {
+    text.split([',', ' ']).filter(|part| !part.trim().is_empty()).map(str::parse).collect()
+}
+
+/// `wght=700, wdth=87.5`; empty for no values.
+pub fn format_axis_values(values: &[AxisValue]) -> String {
+    values.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+}
+
+/// An axis a variable font can be set along.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariationAxis {
+    pub tag: String,
+    /// What the font calls the axis ("Weight"), or its tag when it doesn't say.
+    pub name: String,
+    pub min: f32,
+    pub default: f32,
+    pub max: f32,
+    /// Meant for the font's own use rather than for users to set.
+    pub hidden: bool,
+}
+
+/// A point on the axes the font designer named, such as "Bold" or "Condensed Light".
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedInstance {
+    pub name: String,
+    /// A value for every axis of the font.
+    pub coordinates: Vec<AxisValue>,
+}
+
+/// Whether the font has variation axes at all.
+pub fn is_variable_font(font_data: &[u8]) -> bool {
+    Face::from_slice(font_data, 0).is_ok_and(|face| face.is_variable())
+}
+
+/// Every axis of a variable font, hidden ones included; empty for a static font.
+pub fn variation_axes(font_data: &[u8]) -> Vec<VariationAxis> {
+    let Ok(face) = Face::from_slice(font_data, 0) else {
+        return Vec::new();
+    };
+    face.variation_axes().into_iter()
+        .map(|axis| VariationAxis {
+            tag: tag_string(axis.tag),
+            name: name_string(&face, axis.name_id).unwrap_or_else(|| tag_string(axis.tag)),
+            min: axis.min_value,
+            default: axis.def_value,
+            max: axis.max_value,
+            hidden: axis.hidden,
+        })
+        .collect()
+}
+
+/// The named instances a variable font defines, in the font's order; empty for a static font.
+pub fn named_instances(font_data: &[u8]) -> Vec<NamedInstance> {
+    let Ok(face) = Face::from_slice(font_data, 0) else {
+        return Vec::new();
+    };
+    let Some(fvar) = face.table_data(Tag::from_bytes(b"fvar")) else {
+        return Vec::new();
+    };
+    let axes = variation_axes(font_data);
+    let read_u16 = |offset: usize| fvar.get(offset..offset + 2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+    let read_fixed = |offset: usize| fvar.get(offset..offset + 4)
+        .map(|bytes| i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32 / 65536.0);
+
+    // Header: version, axes offset, reserved, axis count and size, instance count and size;
+    // instances follow the axes, each a subfamily name ID, flags and one Fixed per axis
+    let (Some(axes_offset), Some(axis_count), Some(axis_size), Some(instance_count), Some(instance_size)) =
+        (read_u16(4), read_u16(8), read_u16(10), read_u16(12), read_u16(14)) else {
+        return Vec::new();
+    };
+    let first = axes_offset as usize + axis_count as usize * axis_size as usize;
+    (0..instance_count as usize)
+        .filter_map(|index| {
+            let offset = first + index * instance_size as usize;
+            let name = name_string(&face, read_u16(offset)?).unwrap_or_else(|| format!("Instance {}", index + 1));
+            let coordinates = axes.iter().enumerate()
+                .map(|(axis, info)| Some(AxisValue { tag: info.tag.clone(), value: read_fixed(offset + 4 + axis * 4)? }))
+                .collect::<Option<Vec<_>>>()?;
+            Some(NamedInstance { name, coordinates })
+        })
+        .collect()
+}
+
+/// Check that the font is variable and that every value names one of its axes, once, within
+/// that axis' range.
+pub fn check_axis_values(font_data: &[u8], values: &[AxisValue]) -> Result<()> {
+    let axes = variation_axes(font_data);
+    if axes.is_empty() {
+        return Err(CertError::InvalidVariation("the font is not a variable font, so it has no axes to set".to_string()));
+    }
+    for (index, value) in values.iter().enumerate() {
+        let Some(axis) = axes.iter().find(|axis| axis.tag == value.tag) else {
+            let tags: Vec<&str> = axes.iter().map(|axis| axis.tag.as_str()).collect();
+            return Err(CertError::InvalidVariation(format!("the font has no '{}' axis; it has {}", value.tag, tags.join(", "))));
+        };
+        if !(axis.min..=axis.max).contains(&value.value) {
+            return Err(CertError::InvalidVariation(format!("{} must be between {} and {}, got {}", value.tag, axis.min, axis.max, value.value)));
+        }
+        if values[..index].iter().any(|earlier| earlier.tag == value.tag) {
+            return Err(CertError::InvalidVariation(format!("{} is set more than once", value.tag)));
+        }
+    }
+    Ok(())
+}
+
+/// A variable font set to chosen axis values, for [`TextOptions::variation`](crate::editpng::TextOptions).
+/// Axes left out stay at their defaults.
+#[derive(Debug, Clone)]
+pub struct FontInstance {
+    data: Vec<u8>,
+    coordinates: Vec<AxisValue>,
+}
+
+impl FontInstance {
+    /// Instantiate `font_data` at `coordinates`, after [`check_axis_values`].
+    pub fn new(font_data: Vec<u8>, coordinates: Vec<AxisValue>) -> Result<Self> {
+        check_axis_values(&font_data, &coordinates)?;
+        Ok(FontInstance { data: font_data, coordinates })
+    }
+
+    pub fn coordinates(&self) -> &[AxisValue] {
+        &self.coordinates
+    }
+
+    fn face(&self) -> Option<Face<'_>> {
+        let mut face = Face::from_slice(&self.data, 0).ok()?;
+        for value in &self.coordinates {
+            let tag: [u8; 4] = value.tag.as_bytes().try_into().ok()?;
+            face.set_variation(Tag::from_bytes(&tag), value.value)?;
+        }
+        Some(face)
+    }
+
+    // Ascent and descent in pixels, scaled like rusttype's v_metrics
+    pub(crate) fn v_metrics(&self, size: f32) -> (f32, f32) {
+        match self.face() {
+            Some(face) => {
+                let scale = pixel_scale(&face, size);
+                (face.ascender() as f32 * scale, face.descender() as f32 * scale)
+            }
+            None => (0.0, 0.0),
+        }
+    }
+
+    // Lay out and rasterize `text` like rusttype's Font::layout: the pen starts at (0, ascent),
+    // advances by each glyph's varied advance and is adjusted by the kern table
+    pub(crate) fn raster_glyphs(&self, text: &str, size: f32) -> Vec<RasterGlyph> {
+        let Some(face) = self.face() else {
+            return Vec::new();
+        };
+        let scale = pixel_scale(&face, size);
+        let ascent = face.ascender() as f32 * scale;
+        let mut caret = 0.0;
+        let mut previous = None;
+        let mut glyphs = Vec::new();
+        for c in text.chars() {
+            let id = face.glyph_index(c).unwrap_or(GlyphId(0));
+            if let Some(previous) = previous {
+                caret += kerning(&face, previous, id) * scale;
+            }
+            previous = Some(id);
+
+            let mut outline = Outline::default();
+            if face.outline_glyph(id, &mut outline).is_some() && !outline.segments.is_empty() {
+                glyphs.push(outline.rasterize(|x, y| point(caret + x * scale, ascent - y * scale)));
+            }
+            caret += face.glyph_hor_advance(id).unwrap_or(0) as f32 * scale;
+        }
+        glyphs
+    }
+}
+
+/// [`FontInstance`] of a font at `coordinates`, or `None` when there are none to apply.
+pub fn load_font_instance(font: impl AsRef<Path>, coordinates: &[AxisValue]) -> Result<Option<FontInstance>> {
+    if coordinates.is_empty() {
+        return Ok(None);
+    }
+    FontInstance::new(load_font_data(font)?, coordinates.to_vec()).map(Some)
+}
+
+fn pixel_scale(face: &Face, size: f32) -> f32 {
+    size / (face.ascender() as f32 - face.descender() as f32)
+}
+
+fn kerning(face: &Face, left: GlyphId, right: GlyphId) -> f32 {
+    face.tables().kern
+        .and_then(|kern| kern.subtables.into_iter()
+            .filter(|subtable| subtable.horizontal && !subtable.variable)
+            .find_map(|subtable| subtable.glyphs_kerning(left, right)))
+        .unwrap_or(0) as f32
+}
+
+fn tag_string(tag: Tag) -> String {
+    String::from_utf8_lossy(&tag.to_bytes()).into_owned()
+}
+
+fn name_string(face: &Face, name_id: u16) -> Option<String> {
+    face.names().into_iter()
+        .filter(|name| name.name_id == name_id)
+        .find_map(|name| name.to_string())
+}
+
+enum Segment {
+    Line(Point, Point),
+    Quad(Point, Point, Point),
+    Cubic(Point, Point, Point, Point),
+}
+
+// A glyph outline in font units, as ttf-parser walks it
+#[derive(Default)]
+struct Outline {
+    segments: Vec<Segment>,
+    start: Point,
+    last: Point,
+}
+
+impl Outline {
+    // Rasterize with every point mapped to pixels by `place`, into the whole-pixel box around it
+    fn rasterize(&self, place: impl Fn(f32, f32) -> Point) -> RasterGlyph {
+        let place = |p: Point| place(p.x, p.y);
+        let placed: Vec<Segment> = self.segments.iter()
+            .map(|segment| match *segment {
+                Segment::Line(a, b) => Segment::Line(place(a), place(b)),
+                Segment::Quad(a, b, c) => Segment::Quad(place(a), place(b), place(c)),
+                Segment::Cubic(a, b, c, d) => Segment::Cubic(place(a), place(b), place(c), place(d)),
+            })
+            .collect();
+        let points = placed.iter().flat_map(|segment| match *segment {
+            Segment::Line(a, b) => vec![a, b],
+            Segment::Quad(a, b, c) => vec![a, b, c],
+            Segment::Cubic(a, b, c, d) => vec![a, b, c, d],
+        });
+        let (mut min, mut max) = (point(f32::MAX, f32::MAX), point(f32::MIN, f32::MIN));
+        for p in points {
+            (min.x, min.y, max.x, max.y) = (min.x.min(p.x), min.y.min(p.y), max.x.max(p.x), max.y.max(p.y));
+        }
+        let (min_x, min_y) = (min.x.floor() as i32, min.y.floor() as i32);
+        let (width, height) = ((max.x.ceil() as i32 - min_x).max(1) as usize, (max.y.ceil() as i32 - min_y).max(1) as usize);
+
+        let shift = |p: Point| point(p.x - min_x as f32, p.y - min_y as f32);
+        let mut rasterizer = Rasterizer::new(width, height);
+        for segment in &placed {
+            match *segment {
+                Segment::Line(a, b) => rasterizer.draw_line(shift(a), shift(b)),
+                Segment::Quad(a, b, c) => rasterizer.draw_quad(shift(a), shift(b), shift(c)),
+                Segment::Cubic(a, b, c, d) => rasterizer.draw_cubic(shift(a), shift(b), shift(c), shift(d)),
+            }
+        }
+        let mut coverage = vec![0.0; width * height];
+        rasterizer.for_each_pixel(|index, value| coverage[index] = value.min(1.0));
+        RasterGlyph { min_x, min_y, width, height, coverage }
+    }
+}
+
+impl OutlineBuilder for Outline {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.start = point(x, y);
+        self.last = self.start;
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let to = point(x, y);
+        self.segments.push(Segment::Line(self.last, to));
+        self.last = to;
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let to = point(x, y);
+        self.segments.push(Segment::Quad(self.last, point(x1, y1), to));
+        self.last = to;
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let to = point(x, y);
+        self.segments.push(Segment::Cubic(self.last, point(x1, y1), point(x2, y2), to));
+        self.last = to;
+    }
+
+    fn close(&mut self) {
+        if self.last != self.start {
+            self.segments.push(Segment::Line(self.last, self.start));
+        }
+        self.last = self.start;
+    }
+}
@@ -0,0 +1,174 @@
+// src/verify.rs
+//! Proving a certificate is one that was issued: a file's SHA-256 is looked up among the
+//! hashes the manifest recorded when each certificate was written.
+//!
+//! ```no_run
+//! use certificate_maker::manifest::Manifest;
+//! use certificate_maker::verify::{VerifyStatus, verify_directory, verify_file};
+//!
+//! let manifest = Manifest::load("certificates")?;
+//! match verify_file(&manifest, "Downloads/certificate_Ada_Lovelace.png")?.status {
+//!     VerifyStatus::Issued(entry) => println!("issued to {}", entry.name),
+//!     other => println!("not ours: {:?}", other),
+//! }
+//!
+//! let report = verify_directory(&manifest, "certificates")?;
+//! println!("{} issued, {} modified, {} missing", report.issued(), report.modified(), report.missing.len());
+//! # Ok::<(), certificate_maker::error::CertError>(())
+//! ```
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+use crate::files::list_files_with_extensions;
+use crate::manifest::{Artifact, Manifest, ManifestEntry, hash_file};
+
+/// Files [`verify_directory`] checks: certificates and the zips and PDFs made from them.
+pub const VERIFIED_EXTENSIONS: [&str; 3] = ["png", "pdf", "zip"];
+
+/// What the manifest says about one file.
+#[derive(Debug, Clone)]
+pub enum VerifyStatus {
+    /// Byte for byte the certificate issued for this entry, or its back, wherever the file
+    /// now lives.
+    Issued(ManifestEntry),
+    /// A file made from the certificates, such as a preview, or a zip or PDF of the last batch.
+    Artifact(Artifact),
+    /// Named like this entry's certificate, but its content differs from what was issued.
+    Modified(ManifestEntry),
+    /// Named like this entry's certificate, which was written before hashes were recorded,
+    /// so it can't be checked.
+    Unrecorded(ManifestEntry),
+    /// Neither its content nor its name is in the manifest.
+    NotFound,
+}
+
+/// One checked file.
+#[derive(Debug, Clone)]
+pub struct Verification {
+    pub file: PathBuf,
+    pub file_hash: String,
+    pub status: VerifyStatus,
+}
+
+impl Verification {
+    /// Whether the file is exactly one that was issued.
+    pub fn is_verified(&self) -> bool {
+        matches!(self.status, VerifyStatus::Issued(_) | VerifyStatus::Artifact(_))
+    }
+}
+
+/// Every file of a directory checked against its manifest.
+#[derive(Debug, Clone, Default)]
+pub struct DirectoryVerification {
+    /// In the directory's listing order.
+    pub files: Vec<Verification>,
+    /// Certificates the manifest records whose file is gone.
+    pub missing: Vec<ManifestEntry>,
+}
+
+impl DirectoryVerification {
+    pub fn issued(&self) -> usize {
+        self.files.iter().filter(|file| file.is_verified()).count()
+    }
+
+    pub fn modified(&self) -> usize {
+        self.files.iter().filter(|file| matches!(file.status, VerifyStatus::Modified(_))).count()
+    }
+
+    /// Whether every file was issued and none is missing.
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty() && self.files.iter().all(Verification::is_verified)
+    }
+
+    /// Files that weren't issued as they are: modified, unrecorded or unknown.
+    pub fn discrepancies(&self) -> impl Iterator<Item = &Verification> {
+        self.files.iter().filter(|file| !file.is_verified())
+    }
+}
+
+/// Check one file against `manifest`. Content is matched first, so a renamed or emailed copy
+/// of a certificate is still found; a file that only matches by name was changed since.
+pub fn verify_file(manifest: &Manifest, file: impl AsRef<Path>) -> Result<Verification> {
+    verify_with(&ManifestIndex::new(manifest), file.as_ref())
+}
+
+/// Check every certificate, zip and PDF in `dir` against `manifest`, hashing them in
+/// parallel, and list the certificates the manifest records that aren't there any more.
+pub fn verify_directory(manifest: &Manifest, dir: impl AsRef<Path>) -> Result<DirectoryVerification> {
+    let dir = dir.as_ref();
+    let files: Vec<PathBuf> = list_files_with_extensions(dir, &VERIFIED_EXTENSIONS)?.into_iter()
+        .map(|file| dir.join(file))
+        .collect();
+    let index = ManifestIndex::new(manifest);
+    let files = files.par_iter()
+        .map(|file| verify_with(&index, file))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Looked for by file name, and in its group's subdirectory, so a directory that was moved
+    // since is still checked
+    let missing = manifest.entries.iter()
+        .filter(|entry| {
+            let file_name = entry.output_file.file_name().unwrap_or_default();
+            let in_group = entry.output_file.parent().and_then(Path::file_name).map(|group| dir.join(group).join(file_name));
+            !dir.join(file_name).exists() && !in_group.is_some_and(|path| path.exists())
+        })
+        .cloned()
+        .collect();
+    Ok(DirectoryVerification { files, missing })
+}
+
+fn verify_with(index: &ManifestIndex, file: &Path) -> Result<Verification> {
+    let file_hash = hash_file(file)?;
+    let status = index.status_of(file, &file_hash);
+    Ok(Verification { file: file.to_path_buf(), file_hash, status })
+}
+
+// The manifest keyed the three ways a file is looked up, built once for a whole directory.
+// Where two entries share a key the first one wins, as a scan in order would find it.
+struct ManifestIndex<'a> {
+    // Certificates and, since they were issued along with their fronts, backs by content
+    issued: HashMap<&'a str, &'a ManifestEntry>,
+    // The last batch's zips and PDFs, and previews, which stay recorded after a later batch
+    artifacts: HashMap<&'a str, &'a Artifact>,
+    // Certificates and backs by file name
+    by_name: HashMap<&'a OsStr, &'a ManifestEntry>,
+}
+
+impl<'a> ManifestIndex<'a> {
+    fn new(manifest: &'a Manifest) -> Self {
+        let mut issued = HashMap::new();
+        let mut by_name = HashMap::new();
+        for entry in &manifest.entries {
+            let back = entry.back.as_ref();
+            for hash in entry.file_hash.as_deref().into_iter().chain(back.map(|back| back.file_hash.as_str())) {
+                issued.entry(hash).or_insert(entry);
+            }
+            for name in entry.output_file.file_name().into_iter().chain(back.and_then(|back| back.path.file_name())) {
+                by_name.entry(name).or_insert(entry);
+            }
+        }
+        let mut artifacts = HashMap::new();
+        let previews = manifest.entries.iter().filter_map(|entry| entry.preview.as_ref());
+        for artifact in manifest.last_batch.iter().flat_map(|record| &record.artifacts).chain(previews) {
+            artifacts.entry(artifact.file_hash.as_str()).or_insert(artifact);
+        }
+        ManifestIndex { issued, artifacts, by_name }
+    }
+
+    fn status_of(&self, file: &Path, file_hash: &str) -> VerifyStatus {
+        if let Some(entry) = self.issued.get(file_hash) {
+            return VerifyStatus::Issued((*entry).clone());
+        }
+        if let Some(artifact) = self.artifacts.get(file_hash) {
+            return VerifyStatus::Artifact((*artifact).clone());
+        }
+        match file.file_name().and_then(|name| self.by_name.get(name)) {
+            Some(entry) if entry.file_hash.is_some() => VerifyStatus::Modified((*entry).clone()),
+            Some(entry) => VerifyStatus::Unrecorded((*entry).clone()),
+            None => VerifyStatus::NotFound,
+        }
+    }
+}
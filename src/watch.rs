@@ -0,0 +1,261 @@
+// src/watch.rs
+use anyhow::{Context, Result};
+use notify::{Event, RecursiveMode, Watcher};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Once;
+use std::time::Duration;
+
+use certificate_maker::csvexcelparser::{BatchReport, BatchSettings, MergedCsv, name_list_url, parse_name_list_with, run_batch};
+use certificate_maker::duplicates::{DuplicatePolicy, DuplicateResolution, ResolvedNames, resolve_duplicates};
+use certificate_maker::error::CertError;
+use certificate_maker::paths::AppPaths;
+use certificate_maker::runlog::{LoggedProgress, RunLog};
+use certificate_maker::say;
+
+use crate::display::ConsoleProgress;
+use crate::interactive::{BatchOptions, name_column_for, notify_batch_finished, prompt_batch_settings, start_run_log};
+
+// Quiet period after the last write before we re-read the CSV
+const DEBOUNCE: Duration = Duration::from_millis(750);
+// How often the loop wakes up to check for Ctrl+C
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+// Excel on Windows can hold the file locked for a moment after saving
+const PARSE_RETRIES: u32 = 5;
+const PARSE_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+static WATCHING: AtomicBool = AtomicBool::new(false);
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+static INSTALL_HANDLER: Once = Once::new();
+
+// Ctrl+C stops watch mode; outside of it the program exits as usual
+fn install_ctrlc_handler() -> Result<()> {
+    let mut result = Ok(());
+    INSTALL_HANDLER.call_once(|| {
+        result = ctrlc::set_handler(|| {
+            if WATCHING.load(Ordering::SeqCst) {
+                STOP_REQUESTED.store(true, Ordering::SeqCst);
+            } else {
+                std::process::exit(130);
+            }
+        })
+        .with_context(|| "Failed to install Ctrl+C handler");
+    });
+    result
+}
+
+// Does this event touch a watched CSV (including Excel's save-via-rename)?
+fn event_touches_file(event: &Event, file_names: &[OsString]) -> bool {
+    event.paths.iter().any(|path| path.file_name().is_some_and(|name| file_names.iter().any(|file_name| file_name == name)))
+}
+
+// Every CSV of the batch; as at the prompt, only the first file uses the picked name column
+fn parse_csv_with_retry(settings: &BatchSettings) -> Result<MergedCsv> {
+    let mut attempt = 1;
+    loop {
+        let parsed = settings.csv_files().iter().enumerate()
+            .map(|(index, csv_file)| {
+                let header = if index == 0 { settings.name_column.as_deref() } else { None };
+                parse_name_list_with(csv_file, &name_column_for(header), &settings.csv_format)
+            })
+            .collect::<Result<Vec<_>, _>>();
+        match parsed {
+            Ok(files) => return Ok(MergedCsv { files }),
+            Err(e) if attempt < PARSE_RETRIES => {
+                say!("⏳ CSV not readable yet ({}), retrying...", e);
+                std::thread::sleep(PARSE_RETRY_DELAY);
+                attempt += 1;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+// Duplicates decided earlier keep their answer; groups that are new since then follow the policy
+fn resolve_cycle_duplicates(settings: &mut BatchSettings, parsed: &MergedCsv, policy: DuplicatePolicy) -> Result<ResolvedNames> {
+    let (all_names, all_rows) = (parsed.names(), parsed.rows());
+    let all_sources = if parsed.files.len() > 1 { parsed.sources() } else { Vec::new() };
+    let resolved = resolve_duplicates(&all_names, &all_rows, &all_sources, |group| {
+        let rows: Vec<usize> = group.indices.iter().map(|&index| all_rows[index]).collect();
+        let sources: Vec<PathBuf> = group.indices.iter().filter_map(|&index| all_sources.get(index).cloned()).collect();
+        let names: Vec<&String> = group.indices.iter().map(|&index| &all_names[index]).collect();
+        let earlier = settings.duplicates.iter().find(|decision| {
+            decision.rows == rows && decision.sources == sources && decision.names.iter().eq(names.iter().copied())
+        });
+        if let Some(earlier) = earlier {
+            return Ok(earlier.resolution.clone());
+        }
+        match policy {
+            DuplicatePolicy::Fail => anyhow::bail!("New duplicate name '{}' on rows {:?}", names[0], rows),
+            DuplicatePolicy::KeepAll => Ok(DuplicateResolution::KeepAll),
+            DuplicatePolicy::Dedupe => Ok(DuplicateResolution::KeepFirst),
+        }
+    })?;
+    settings.duplicates = resolved.decisions.clone();
+    Ok(resolved)
+}
+
+// One batch of watch mode, logged to the session's generation log if there is one
+fn run_logged(settings: &BatchSettings, names: &[String], log: Option<&RunLog>) -> Result<BatchReport, CertError> {
+    let console = ConsoleProgress::default();
+    let result = match log {
+        Some(log) => run_batch(settings, names, &LoggedProgress::new(log, &console)),
+        None => run_batch(settings, names, &console),
+    };
+    result.inspect_err(|e| {
+        if let Some(log) = log {
+            log.failure(e);
+        }
+    })
+}
+
+// Re-parse the CSV and render only the names not generated yet
+fn run_cycle(settings: &mut BatchSettings, paths: &AppPaths, log: Option<&RunLog>, policy: DuplicatePolicy, cycle: usize, watched: &str) {
+    say!("\n🔄 [cycle {}] Change detected in {}", cycle, watched);
+    if let Some(log) = log {
+        log.line(&format!("Cycle {}: change detected in {}", cycle, watched));
+    }
+    let parsed = match parse_csv_with_retry(settings) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            say!("❌ [cycle {}] Could not read CSV, waiting for the next change: {}", cycle, e);
+            if let Some(log) = log {
+                log.failure(&e);
+            }
+            return;
+        }
+    };
+    let resolved = match resolve_cycle_duplicates(settings, &parsed, policy) {
+        Ok(resolved) => resolved,
+        Err(e) => {
+            say!("❌ [cycle {}] {}, waiting for the next change", cycle, e);
+            if let Some(log) = log {
+                log.failure(&e);
+            }
+            return;
+        }
+    };
+    // The same rows as the first run; a sample keeps its seed
+    let selected = settings.selection.select(resolved.names.len());
+    let names = selected.pick(&resolved.names);
+    settings.rows = selected.pick(&resolved.rows);
+    settings.sources = if resolved.sources.is_empty() { Vec::new() } else { selected.pick(&resolved.sources) };
+    settings.emails = parsed.emails(&settings.rows, &settings.sources);
+    settings.records = parsed.records(&settings.rows, &settings.sources);
+
+    match run_logged(settings, &names, log) {
+        Ok(summary) => {
+            say!(
+                "📊 [cycle {}] Generated: {} | Skipped (unchanged): {} | Skipped (existing): {} | Renamed: {} | Failed: {}",
+                cycle, summary.generated, summary.skipped_unchanged, summary.skipped_existing, summary.renamed, summary.failed
+            );
+            // Saving the list without adding anyone isn't worth a notification
+            if summary.generated + summary.failed > 0 {
+                notify_batch_finished(settings, &summary, paths);
+            }
+        }
+        Err(e) => say!("❌ [cycle {}] Batch failed: {}", cycle, e),
+    }
+}
+
+// Watch the selected CSV and generate certificates for newly added rows
+pub fn watch_csv_interactive(paths: &AppPaths, options: &BatchOptions) -> Result<()> {
+    say!("👀 === Watch Mode ===");
+    install_ctrlc_handler()?;
+    if options.dry_run {
+        anyhow::bail!("Watch mode writes certificates as rows arrive; use --dry-run with option 2 instead");
+    }
+    if options.email.is_some() {
+        say!("⚠️ Watch mode doesn't email certificates; generate with --email once the list is complete");
+    }
+
+    let (mut settings, names) = prompt_batch_settings(paths, options)?;
+    let csv_files = settings.csv_files();
+    if let Some(url) = csv_files.iter().find_map(|file| name_list_url(file)) {
+        anyhow::bail!("Watch mode needs local files; download {} and pick the file instead", url);
+    }
+
+    // Initial pass, so everything already in the CSV exists before we start watching. The whole
+    // session shares one generation log
+    let log = start_run_log(paths, options);
+    if let Some(log) = &log {
+        log.settings(&settings, names.len());
+        say!("📝 Generation log: {}", log.path().display());
+    }
+    let summary = run_logged(&settings, &names, log.as_ref())?;
+    say!("📊 Initial run - Generated: {} | Skipped (unchanged): {} | Skipped (existing): {} | Renamed: {} | Failed: {}",
+         summary.generated, summary.skipped_unchanged, summary.skipped_existing, summary.renamed, summary.failed);
+    notify_batch_finished(&settings, &summary, paths);
+
+    let mut file_names = Vec::new();
+    let mut watch_dirs: Vec<&Path> = Vec::new();
+    for csv_path in &csv_files {
+        let file_name = csv_path.file_name()
+            .ok_or_else(|| anyhow::anyhow!("Invalid CSV path: {}", csv_path.display()))?;
+        file_names.push(file_name.to_os_string());
+        // Watch the directory rather than the file so atomic replace-on-save is seen
+        let watch_dir = csv_path.parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        if !watch_dirs.contains(&watch_dir) {
+            watch_dirs.push(watch_dir);
+        }
+    }
+
+    let (tx, rx) = mpsc::channel::<notify::Result<Event>>();
+    let mut watcher = notify::recommended_watcher(tx)
+        .with_context(|| "Failed to create file watcher")?;
+    for watch_dir in watch_dirs {
+        watcher.watch(watch_dir, RecursiveMode::NonRecursive)
+            .with_context(|| format!("Failed to watch directory: {}", watch_dir.display()))?;
+    }
+
+    let watched: Vec<String> = csv_files.iter().map(|file| file.display().to_string()).collect();
+    let watched = watched.join(", ");
+    say!("\n👀 Watching {} for changes. Press Ctrl+C to stop.", watched);
+    STOP_REQUESTED.store(false, Ordering::SeqCst);
+    WATCHING.store(true, Ordering::SeqCst);
+
+    let mut cycle = 0;
+    while !STOP_REQUESTED.load(Ordering::SeqCst) {
+        let event = match rx.recv_timeout(POLL_INTERVAL) {
+            Ok(Ok(event)) => event,
+            Ok(Err(e)) => {
+                say!("⚠️ Watcher error: {}", e);
+                continue;
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => continue,
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        };
+
+        if !event_touches_file(&event, &file_names) || event.kind.is_access() {
+            continue;
+        }
+
+        // Debounce: keep draining until the file has been quiet for a while
+        while rx.recv_timeout(DEBOUNCE).is_ok() {
+            if STOP_REQUESTED.load(Ordering::SeqCst) {
+                break;
+            }
+        }
+        if STOP_REQUESTED.load(Ordering::SeqCst) {
+            break;
+        }
+
+        cycle += 1;
+        run_cycle(&mut settings, paths, log.as_ref(), options.duplicates.unwrap_or_default(), cycle, &watched);
+        say!("\n👀 Watching {} for changes. Press Ctrl+C to stop.", watched);
+    }
+
+    WATCHING.store(false, Ordering::SeqCst);
+    say!("\n🛑 Watch mode stopped after {} update cycles", cycle);
+    if let Some(log) = &log {
+        log.line(&format!("Watch mode stopped after {} update cycles", cycle));
+        if let Some(e) = log.error() {
+            say!("⚠️ The generation log may be incomplete: {}", e);
+        }
+    }
+    Ok(())
+}
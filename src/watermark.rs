@@ -0,0 +1,274 @@
+// src/watermark.rs
+//! Semi-transparent text across a certificate, such as a big diagonal "DRAFT" on copies sent
+//! for approval, so nobody prints them by mistake.
+//!
+//! The text is rasterized once and rotated into a coverage mask the size of the template
+//! ([`WatermarkMask`]); each certificate then only blends the color through the mask, after
+//! everything else is drawn. Blending weighs both alphas, so a watermark over a photograph or
+//! a transparent template comes out as it would in an image editor.
+//!
+//! ```
+//! use certificate_maker::editpng::load_font;
+//! use certificate_maker::watermark::{WatermarkLayout, WatermarkStyle, draw_watermark};
+//! use image::{Rgba, RgbaImage};
+//!
+//! let font = load_font("tests/fixtures/DejaVuSans-Latin.ttf")?;
+//! let mut img = RgbaImage::from_pixel(600, 400, Rgba([255, 255, 255, 255]));
+//! draw_watermark(&mut img, &font, &WatermarkStyle::draft())?;
+//! // Tinted across the middle, untouched in the corners
+//! assert!(img.pixels().any(|pixel| pixel[1] < 255));
+//! assert_eq!(img.get_pixel(0, 0), &Rgba([255, 255, 255, 255]));
+//! assert!(img.pixels().all(|pixel| pixel[3] == 255 && pixel[1] <= pixel[0]));
+//!
+//! // Tiled, it covers the corners too
+//! let mut img = RgbaImage::from_pixel(600, 400, Rgba([255, 255, 255, 255]));
+//! let tiled = WatermarkStyle { layout: WatermarkLayout::Tiled { spacing: 20.0 }, ..WatermarkStyle::draft() };
+//! draw_watermark(&mut img, &font, &tiled)?;
+//! let tinted = |x0: u32, y0: u32| (x0..x0 + 150).any(|x| (y0..y0 + 100).any(|y| img.get_pixel(x, y)[1] < 255));
+//! assert!(tinted(0, 0) && tinted(450, 300));
+//! # Ok::<(), certificate_maker::error::CertError>(())
+//! ```
+use image::{GrayImage, Luma, Rgba, RgbaImage};
+use rusttype::{Font, Scale, point};
+
+use crate::editpng::blend_over;
+use crate::error::{CertError, Result};
+
+/// Text of the `--draft` watermark.
+pub const DRAFT_TEXT: &str = "DRAFT";
+
+/// Gap between tiles when none is given, in pixels.
+pub const DEFAULT_TILE_SPACING: f32 = 80.0;
+
+/// How often the text appears.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WatermarkLayout {
+    /// Once, across the center.
+    Center,
+    /// Repeated over the whole certificate, `spacing` pixels apart, every other row shifted by
+    /// half a tile.
+    Tiled { spacing: f32 },
+}
+
+/// What a watermark says and how it is drawn.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatermarkStyle {
+    pub text: String,
+    /// Font size in pixels. Unset, a centered watermark is as large as fits on the template
+    /// once rotated, and tiles are a twelfth of its shorter side.
+    pub size: Option<f32>,
+    /// Degrees counterclockwise from horizontal, so 45 rises to the right.
+    pub angle: f32,
+    /// From 0 (invisible) to 1 (the color as it is); multiplies the color's own alpha.
+    pub opacity: f32,
+    pub color: Rgba<u8>,
+    pub layout: WatermarkLayout,
+}
+
+impl WatermarkStyle {
+    /// "DRAFT" across the center at 45°, in red at 25% opacity: what `--draft` draws.
+    pub fn draft() -> Self {
+        WatermarkStyle {
+            text: DRAFT_TEXT.to_string(),
+            size: None,
+            angle: 45.0,
+            opacity: 0.25,
+            color: Rgba([200, 30, 30, 255]),
+            layout: WatermarkLayout::Center,
+        }
+    }
+
+    /// An opacity written as a percentage such as `"25%"` or a fraction such as `"0.25"`;
+    /// `None` for anything else, or outside 0 (exclusive) to 1.
+    pub fn parse_opacity(text: &str) -> Option<f32> {
+        let text = text.trim();
+        let opacity = match text.strip_suffix('%') {
+            Some(percent) => percent.trim().parse::<f32>().ok()? / 100.0,
+            None => text.parse::<f32>().ok()?,
+        };
+        (opacity > 0.0 && opacity <= 1.0).then_some(opacity)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason: String| Err(CertError::InvalidWatermark(reason));
+        if self.text.trim().is_empty() {
+            return invalid("the text is empty".to_string());
+        }
+        if let Some(size) = self.size
+            && (!size.is_finite() || size <= 0.0) {
+            return invalid(format!("the size must be a positive number of pixels, got {}", size));
+        }
+        if !self.angle.is_finite() {
+            return invalid(format!("the angle must be a number of degrees, got {}", self.angle));
+        }
+        if !(self.opacity > 0.0 && self.opacity <= 1.0) {
+            return invalid(format!("the opacity must be above 0 and at most 1 (100%), got {}", self.opacity));
+        }
+        if let WatermarkLayout::Tiled { spacing } = self.layout
+            && (!spacing.is_finite() || spacing < 0.0) {
+            return invalid(format!("the tile spacing must be a number of pixels, at least 0, got {}", spacing));
+        }
+        Ok(())
+    }
+
+    /// One line for summaries, such as "DRAFT at 45°, 25% opacity, tiled 20 px apart".
+    pub fn describe(&self) -> String {
+        let layout = match self.layout {
+            WatermarkLayout::Center => "across the center".to_string(),
+            WatermarkLayout::Tiled { spacing } => format!("tiled {} px apart", spacing),
+        };
+        format!("{} at {}°, {:.0}% opacity, {}", self.text, self.angle, self.opacity * 100.0, layout)
+    }
+}
+
+/// A watermark rasterized for one template size: how much of the color each pixel gets.
+#[derive(Debug, Clone)]
+pub struct WatermarkMask {
+    mask: GrayImage,
+    color: Rgba<u8>,
+}
+
+impl WatermarkMask {
+    /// Rasterize `style` with `font` for a template of `dimensions`.
+    pub fn new(style: &WatermarkStyle, font: &Font, (width, height): (u32, u32)) -> Result<Self> {
+        style.validate()?;
+        let (sin, cos) = (-style.angle.to_radians()).sin_cos();
+        let size = style.size.unwrap_or_else(|| match style.layout {
+            WatermarkLayout::Center => {
+                // As large as fits in 90% of the template once rotated, measured at 100 px
+                let (text_width, text_height) = text_extent(font, &style.text, 100.0);
+                let across = text_width * cos.abs() + text_height * sin.abs();
+                let down = text_width * sin.abs() + text_height * cos.abs();
+                100.0 * 0.9 * (width as f32 / across).min(height as f32 / down)
+            }
+            WatermarkLayout::Tiled { .. } => width.min(height) as f32 / 12.0,
+        });
+        let stamp = rotate(&rasterize(font, &style.text, size), sin, cos);
+
+        let mut mask = GrayImage::new(width, height);
+        let opacity = style.opacity;
+        match style.layout {
+            WatermarkLayout::Center => {
+                let x = (width as i64 - stamp.width as i64) / 2;
+                let y = (height as i64 - stamp.height as i64) / 2;
+                stamp_onto(&mut mask, &stamp, x, y, opacity);
+            }
+            WatermarkLayout::Tiled { spacing } => {
+                let step_x = (stamp.width as f32 + spacing).max(1.0) as i64;
+                let step_y = (stamp.height as f32 + spacing).max(1.0) as i64;
+                for (row, y) in (-step_y / 2..height as i64).step_by(step_y as usize).enumerate() {
+                    let shift = if row % 2 == 1 { step_x / 2 } else { 0 };
+                    for x in (-step_x + shift..width as i64).step_by(step_x as usize) {
+                        stamp_onto(&mut mask, &stamp, x, y, opacity);
+                    }
+                }
+            }
+        }
+        Ok(WatermarkMask { mask, color: style.color })
+    }
+
+    /// Blend the watermark over `img`, which must be the size it was made for.
+    pub fn draw(&self, img: &mut RgbaImage) {
+        for (pixel, coverage) in img.pixels_mut().zip(self.mask.pixels()) {
+            if coverage[0] > 0 {
+                blend_over(pixel, self.color, coverage[0] as f32 / 255.0);
+            }
+        }
+    }
+}
+
+/// Draw a watermark over `img` in one go; see [`WatermarkMask`] to draw the same one on many.
+pub fn draw_watermark(img: &mut RgbaImage, font: &Font, style: &WatermarkStyle) -> Result<()> {
+    WatermarkMask::new(style, font, img.dimensions())?.draw(img);
+    Ok(())
+}
+
+// Width and line height of `text` at `size` pixels
+fn text_extent(font: &Font, text: &str, size: f32) -> (f32, f32) {
+    let scale = Scale::uniform(size);
+    let metrics = font.v_metrics(scale);
+    let width = font.layout(text, scale, point(0.0, 0.0))
+        .last()
+        .map_or(0.0, |glyph| glyph.position().x + glyph.unpositioned().h_metrics().advance_width);
+    (width.max(1.0), (metrics.ascent - metrics.descent).max(1.0))
+}
+
+// Coverage of `text` on a canvas just large enough for it, one f32 per pixel
+struct Coverage {
+    width: usize,
+    height: usize,
+    values: Vec<f32>,
+}
+
+fn rasterize(font: &Font, text: &str, size: f32) -> Coverage {
+    let (text_width, text_height) = text_extent(font, text, size);
+    let scale = Scale::uniform(size);
+    let ascent = font.v_metrics(scale).ascent;
+    let (width, height) = (text_width.ceil() as usize + 2, text_height.ceil() as usize + 2);
+    let mut values = vec![0.0f32; width * height];
+    for glyph in font.layout(text, scale, point(1.0, 1.0 + ascent)) {
+        let Some(bounds) = glyph.pixel_bounding_box() else { continue };
+        glyph.draw(|x, y, coverage| {
+            let (x, y) = (bounds.min.x + x as i32, bounds.min.y + y as i32);
+            if x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < height {
+                let cell = &mut values[y as usize * width + x as usize];
+                *cell = cell.max(coverage);
+            }
+        });
+    }
+    Coverage { width, height, values }
+}
+
+// The coverage rotated about its center, on a canvas large enough for all of it; every pixel
+// is sampled bilinearly from where it came from
+fn rotate(coverage: &Coverage, sin: f32, cos: f32) -> Coverage {
+    let (width, height) = (coverage.width as f32, coverage.height as f32);
+    let rotated_width = (width * cos.abs() + height * sin.abs()).ceil() as usize;
+    let rotated_height = (width * sin.abs() + height * cos.abs()).ceil() as usize;
+    let sample = |x: f32, y: f32| {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let at = |x: f32, y: f32| {
+            if x < 0.0 || y < 0.0 || x >= width || y >= height {
+                0.0
+            } else {
+                coverage.values[y as usize * coverage.width + x as usize]
+            }
+        };
+        let top = at(x0, y0) * (1.0 - fx) + at(x0 + 1.0, y0) * fx;
+        let bottom = at(x0, y0 + 1.0) * (1.0 - fx) + at(x0 + 1.0, y0 + 1.0) * fx;
+        top * (1.0 - fy) + bottom * fy
+    };
+    let mut values = vec![0.0f32; rotated_width * rotated_height];
+    for y in 0..rotated_height {
+        for x in 0..rotated_width {
+            // From the rotated pixel's center back to the text, in pixel-center coordinates
+            let dx = x as f32 + 0.5 - rotated_width as f32 / 2.0;
+            let dy = y as f32 + 0.5 - rotated_height as f32 / 2.0;
+            let source_x = dx * cos + dy * sin + width / 2.0 - 0.5;
+            let source_y = -dx * sin + dy * cos + height / 2.0 - 0.5;
+            values[y * rotated_width + x] = sample(source_x, source_y);
+        }
+    }
+    Coverage { width: rotated_width, height: rotated_height, values }
+}
+
+// Add the stamp's coverage at `opacity` with its top-left corner at (x, y); where stamps meet,
+// the stronger one wins rather than darkening the overlap
+fn stamp_onto(mask: &mut GrayImage, stamp: &Coverage, x: i64, y: i64, opacity: f32) {
+    for row in 0..stamp.height {
+        let mask_y = y + row as i64;
+        if mask_y < 0 || mask_y >= mask.height() as i64 {
+            continue;
+        }
+        for column in 0..stamp.width {
+            let mask_x = x + column as i64;
+            if mask_x < 0 || mask_x >= mask.width() as i64 {
+                continue;
+            }
+            let value = (stamp.values[row * stamp.width + column] * opacity * 255.0).round() as u8;
+            let pixel = mask.get_pixel_mut(mask_x as u32, mask_y as u32);
+            *pixel = Luma([pixel[0].max(value)]);
+        }
+    }
+}
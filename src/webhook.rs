@@ -0,0 +1,196 @@
+// src/webhook.rs
+//! Telling another service that a batch finished, by POSTing a JSON summary to a URL.
+//!
+//! The URL and a shared secret come from `notify_url` and `notify_secret` in `certmaker.json`,
+//! or from `CERTMAKER_NOTIFY_URL` and `CERTMAKER_NOTIFY_SECRET`. The secret is sent in the
+//! [`SECRET_HEADER`] header so the receiver can reject anyone else. Sending needs the `remote`
+//! feature; without it [`notify_batch`] fails with [`CertError::WebhookDisabled`].
+//!
+//! ```
+//! use certificate_maker::csvexcelparser::{BatchReport, BatchSettings};
+//! use certificate_maker::webhook::BatchNotification;
+//!
+//! let settings = BatchSettings { csv_file: "excelcsvs/Names.csv".into(), output_dir: "certificates".into(), ..Default::default() };
+//! let notification = BatchNotification::new("run-1", &settings, &BatchReport::default());
+//! assert_eq!(notification.manifest, std::path::Path::new("certificates/manifest.json"));
+//! assert!(notification.failures.is_empty());
+//! ```
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::csvexcelparser::{BatchReport, BatchSettings};
+use crate::error::{CertError, IoContext, Result};
+use crate::manifest::{MANIFEST_FILENAME, hash_bytes};
+
+/// Header carrying the shared secret.
+pub const SECRET_HEADER: &str = "X-Certmaker-Secret";
+/// How long one attempt may take from connecting to the response.
+pub const NOTIFY_TIMEOUT: Duration = Duration::from_secs(10);
+/// Attempts in total; only server errors and network failures are retried.
+pub const NOTIFY_ATTEMPTS: u32 = 3;
+/// Pause before the first retry, doubled before each further one.
+pub const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Where to send notifications; nothing is sent while `url` is unset.
+#[derive(Debug, Clone, Default)]
+pub struct WebhookSettings {
+    pub url: Option<String>,
+    pub secret: Option<String>,
+}
+
+impl WebhookSettings {
+    /// Read `notify_url` and `notify_secret` from a config file (a missing file gives no
+    /// webhook), then apply `CERTMAKER_NOTIFY_URL` and `CERTMAKER_NOTIFY_SECRET`.
+    pub fn load(config_file: impl AsRef<Path>) -> Result<Self> {
+        let path = config_file.as_ref();
+        let mut settings = if path.exists() {
+            let content = std::fs::read_to_string(path)
+                .io_context(|| format!("Failed to read config file: {}", path.display()))?;
+            let parse_error = |source| CertError::Json { context: format!("Failed to parse the notify settings in {}", path.display()), source };
+            let config: serde_json::Value = serde_json::from_str(&content).map_err(parse_error)?;
+            let pick = |key: &str| config.get(key).cloned().unwrap_or_default();
+            WebhookSettings {
+                url: serde_json::from_value(pick("notify_url")).map_err(parse_error)?,
+                secret: serde_json::from_value(pick("notify_secret")).map_err(parse_error)?,
+            }
+        } else {
+            WebhookSettings::default()
+        };
+
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        settings.url = var("CERTMAKER_NOTIFY_URL").or(settings.url).filter(|url| !url.trim().is_empty());
+        settings.secret = var("CERTMAKER_NOTIFY_SECRET").or(settings.secret);
+        Ok(settings)
+    }
+}
+
+/// A row that failed, and why.
+#[derive(Debug, Clone, Serialize)]
+pub struct NotifiedFailure {
+    pub name: String,
+    pub output_file: PathBuf,
+    pub reason: String,
+}
+
+/// The JSON payload POSTed when a batch finishes.
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchNotification {
+    /// Tells the runs of one machine apart; see [`new_run_id`] and [`content_run_id`].
+    pub run_id: String,
+    /// Every name list of the batch, the first one first.
+    pub csv_files: Vec<PathBuf>,
+    pub template: PathBuf,
+    pub output_dir: PathBuf,
+    pub generated: usize,
+    pub skipped_unchanged: usize,
+    pub skipped_existing: usize,
+    pub renamed: usize,
+    pub failed: usize,
+    pub duration_seconds: f64,
+    pub failures: Vec<NotifiedFailure>,
+    pub manifest: PathBuf,
+}
+
+impl BatchNotification {
+    /// Summarize a finished batch.
+    pub fn new(run_id: impl Into<String>, settings: &BatchSettings, report: &BatchReport) -> Self {
+        let failures = report.items.iter()
+            .filter_map(|item| item.error.as_ref().map(|reason| NotifiedFailure {
+                name: item.name.clone(),
+                output_file: item.output_file.clone(),
+                reason: reason.clone(),
+            }))
+            .collect();
+        BatchNotification {
+            run_id: run_id.into(),
+            csv_files: settings.csv_files(),
+            template: settings.template_file.clone(),
+            output_dir: settings.output_dir.clone(),
+            generated: report.generated,
+            skipped_unchanged: report.skipped_unchanged,
+            skipped_existing: report.skipped_existing,
+            renamed: report.renamed,
+            failed: report.failed,
+            duration_seconds: report.duration.as_secs_f64(),
+            failures,
+            manifest: settings.output_dir.join(MANIFEST_FILENAME),
+        }
+    }
+}
+
+/// A run id from the current time and process id, e.g. `1760659200123-4242`.
+pub fn new_run_id() -> String {
+    let millis = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis());
+    format!("{}-{}", millis, std::process::id())
+}
+
+/// A run id that depends only on what the batch covered: a hash of its rows' content hashes,
+/// which take in the drawn text, the template, the font and every drawing setting. Used for
+/// deterministic runs, so the same inputs are reported under the same id.
+pub fn content_run_id(report: &BatchReport) -> String {
+    let hashes: Vec<&str> = report.entries.iter().map(|entry| entry.content_hash.as_str()).collect();
+    hash_bytes(hashes.join("\n").as_bytes())[..16].to_string()
+}
+
+/// POST `notification` to the configured URL, retrying server errors and network failures
+/// up to [`NOTIFY_ATTEMPTS`] times in total. Does nothing when no URL is set.
+#[cfg(feature = "remote")]
+pub fn notify_batch(settings: &WebhookSettings, notification: &BatchNotification) -> Result<()> {
+    let Some(url) = settings.url.as_deref() else {
+        return Ok(());
+    };
+    let webhook_error = |reason: String| CertError::Webhook { url: url.to_string(), reason };
+    let body = serde_json::to_vec(notification)
+        .map_err(|source| CertError::Json { context: "Failed to serialize the batch notification".to_string(), source })?;
+    let client = reqwest::blocking::Client::builder()
+        .timeout(NOTIFY_TIMEOUT)
+        .build()
+        .map_err(|e| webhook_error(e.to_string()))?;
+
+    let mut delay = RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
+        let mut request = client.post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.clone());
+        if let Some(secret) = &settings.secret {
+            request = request.header(SECRET_HEADER, secret);
+        }
+        // A 4xx won't change by asking again, so only 5xx and failed connections are retried
+        let (error, retry) = match request.send() {
+            Ok(response) if response.status().is_success() => return Ok(()),
+            Ok(response) => (format!("HTTP {}", response.status()), response.status().is_server_error()),
+            Err(e) if e.is_timeout() => (format!("no answer within {} s", NOTIFY_TIMEOUT.as_secs()), true),
+            Err(e) => (innermost_cause(&e), e.is_connect() || e.is_request()),
+        };
+        if !retry || attempt == NOTIFY_ATTEMPTS {
+            let tries = if attempt == 1 { String::new() } else { format!(" after {} attempts", attempt) };
+            return Err(webhook_error(format!("{}{}", error, tries)));
+        }
+        std::thread::sleep(delay);
+        delay *= 2;
+        attempt += 1;
+    }
+}
+
+// reqwest's own message repeats the URL; the innermost cause ("Connection refused") says more
+#[cfg(feature = "remote")]
+fn innermost_cause(error: &reqwest::Error) -> String {
+    let mut cause: &dyn std::error::Error = error;
+    while let Some(source) = cause.source() {
+        cause = source;
+    }
+    cause.to_string()
+}
+
+/// Without the `remote` feature a configured webhook can't be reached.
+#[cfg(not(feature = "remote"))]
+pub fn notify_batch(settings: &WebhookSettings, _notification: &BatchNotification) -> Result<()> {
+    match &settings.url {
+        Some(url) => Err(CertError::WebhookDisabled(url.clone())),
+        None => Ok(()),
+    }
+}
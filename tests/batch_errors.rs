@@ -0,0 +1,249 @@
+// tests/batch_errors.rs
+// How a batch fails. A problem with something every row shares (the font, the template, the
+// output directory, the text color, a graphic with the same data on every row) stops the batch
+// before any row is rendered, with one message. A problem with one row's own data fails that
+// row only; the rest are still generated.
+//
+//   cargo test --test batch_errors
+mod common;
+use certificate_maker::csvexcelparser::BatchItem;
+use certificate_maker::error::CertError;
+use certificate_maker::editpng::TextOverflow;
+use certificate_maker::graphics::{GraphicField, GraphicKind, ImageStyle, TextStyle};
+use certificate_maker::job::{Anchor, CertificateJob, RENDER_CHUNK_ROWS};
+use certificate_maker::manifest::{DRY_RUN_MANIFEST_FILENAME, Manifest};
+use certificate_maker::progress::ProgressSink;
+use certificate_maker::source::Record;
+use certificate_maker::timing::Stage;
+use common::{FONT, TEMPLATE, output_dir, test_dir};
+use image::{Rgba, RgbaImage};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+fn job(test: &str, names: &[&str]) -> CertificateJob {
+    CertificateJob::new(TEMPLATE, names.iter().map(|name| name.to_string()).collect())
+        .font(FONT)
+        .output_dir(output_dir(test))
+}
+
+fn photo(data: &str) -> GraphicField {
+    GraphicField {
+        kind: GraphicKind::Image(ImageStyle { width: Some(60), height: None }),
+        data: data.to_string(),
+        x: 300,
+        y: 320,
+        anchor: Anchor::Center,
+    }
+}
+
+fn record(name: &str, photo: Option<&str>) -> Record {
+    let fields = photo.map(|path| vec![("Photo".to_string(), path.to_string())]).unwrap_or_default();
+    Record { name: name.to_string(), fields }
+}
+
+// The single problem a batch stopped with
+fn single_problem(result: Result<impl std::fmt::Debug, CertError>) -> String {
+    match result {
+        Err(CertError::InvalidJob(problems)) => {
+            assert_eq!(problems.len(), 1, "expected one problem, got {:?}", problems);
+            problems[0].clone()
+        }
+        other => panic!("expected the batch to stop before rendering, got {:?}", other),
+    }
+}
+
+fn certificates_in(dir: &Path) -> usize {
+    std::fs::read_dir(dir).map_or(0, |entries| {
+        entries.flatten().filter(|entry| entry.path().extension().is_some_and(|ext| ext == "png")).count()
+    })
+}
+
+#[test]
+fn missing_font_stops_the_batch() {
+    let job = job("missing_font", &["Ada", "Grace", "Mary"]).font("tests/fixtures/no-such-font.ttf");
+    let problem = single_problem(job.run());
+    assert!(problem.contains("no-such-font.ttf"), "{}", problem);
+    assert_eq!(certificates_in(job.output_directory()), 0);
+}
+
+#[test]
+fn missing_template_stops_the_batch() {
+    let job = CertificateJob::new("tests/fixtures/no-such-template.png", vec!["Ada".to_string(), "Grace".to_string()])
+        .font(FONT)
+        .output_dir(output_dir("missing_template"));
+    let problem = single_problem(job.run());
+    assert!(problem.contains("no-such-template.png"), "{}", problem);
+    assert_eq!(certificates_in(job.output_directory()), 0);
+}
+
+#[test]
+fn invalid_color_stops_the_batch() {
+    let job = job("invalid_color", &["Ada", "Grace", "Mary"]).color("#12345G");
+    let problem = single_problem(job.run());
+    assert!(problem.contains("#12345G") && problem.contains("#RRGGBB"), "{}", problem);
+    assert_eq!(certificates_in(job.output_directory()), 0);
+}
+
+// An image with no placeholders is the same file on every row
+#[test]
+fn missing_shared_image_stops_the_batch() {
+    let job = job("missing_shared_image", &["Ada", "Grace"]).graphics(vec![photo("tests/fixtures/no-such-seal.png")]);
+    let problem = single_problem(job.run());
+    assert!(problem.contains("no-such-seal.png"), "{}", problem);
+    assert_eq!(certificates_in(job.output_directory()), 0);
+}
+
+#[test]
+fn output_directory_that_cant_be_created_stops_the_batch() {
+    let job = job("output_under_a_file", &["Ada", "Grace"]).output_dir(Path::new(TEMPLATE).join("out"));
+    match job.run() {
+        Err(CertError::OutputDirUnusable { reason, .. }) => assert!(reason.contains("template.png is a file"), "{}", reason),
+        other => panic!("expected an output directory error, got {:?}", other),
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn read_only_output_directory_stops_the_batch() {
+    use certificate_maker::files::check_writable;
+    use std::os::unix::fs::PermissionsExt;
+
+    let job = job("read_only_output", &["Ada", "Grace", "Mary"]);
+    let dir = job.output_directory().to_path_buf();
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o555)).unwrap();
+    // Permissions don't stop root, so there is nothing to test there
+    if check_writable(&dir).is_ok() {
+        return;
+    }
+    let result = job.run();
+    std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+    match result {
+        Err(CertError::OutputDirUnusable { reason, .. }) => assert_eq!(reason, "permission denied"),
+        other => panic!("expected the batch to stop at the read-only directory, got {:?}", other),
+    }
+    assert_eq!(certificates_in(&dir), 0);
+}
+
+// A photo that is missing, or a row without the column, fails that row; the others are
+// generated, including one whose name can't be used as a file name as it is
+#[test]
+fn row_problems_fail_only_their_row() {
+    let records = vec![
+        record("Ada: \"The Countess\"", Some(TEMPLATE)),
+        record("Grace", Some("tests/fixtures/no-such-photo.png")),
+        record("Mary", None),
+        record("Katherine", Some(TEMPLATE)),
+    ];
+    let names: Vec<&str> = records.iter().map(|record| record.name.as_str()).collect();
+    let job = job("row_problems", &names).graphics(vec![photo("{Photo}")]).records(records.clone());
+
+    let report = job.run().unwrap();
+    assert_eq!((report.generated, report.failed), (2, 2));
+    let error = |name: &str| report.items.iter().find(|item| item.name == name).and_then(|item| item.error.clone());
+    assert!(error("Grace").is_some_and(|e| e.contains("no-such-photo.png")), "{:?}", error("Grace"));
+    assert!(error("Mary").is_some_and(|e| e.contains("Photo")), "{:?}", error("Mary"));
+    assert_eq!(error("Ada: \"The Countess\""), None);
+    assert_eq!(error("Katherine"), None);
+    assert_eq!(certificates_in(job.output_directory()), 2);
+}
+
+// Rows are rendered a chunk at a time; failures in every chunk stay with their row, and each
+// chunk is recorded in the manifest. A dry run goes through the same chunks, only faster
+// Every 100th row, from the 8th, has no course to fill in its course line
+fn failing(row: usize) -> bool {
+    row % 100 == 7
+}
+
+fn course_records(rows: usize) -> Vec<Record> {
+    (0..rows)
+        .map(|row| {
+            let fields = if failing(row) { Vec::new() } else { vec![("Course".to_string(), "Analysis".to_string())] };
+            Record { name: format!("Person {}", row), fields }
+        })
+        .collect()
+}
+
+fn course_line(size: f32, (x, y): (i32, i32)) -> GraphicField {
+    GraphicField {
+        kind: GraphicKind::Text(TextStyle {
+            font: FONT.into(),
+            size,
+            color: "#000000".to_string(),
+            max_width: None,
+            overflow: TextOverflow::default(),
+            ellipsis: None,
+            effect: None,
+        }),
+        data: "{Course}".to_string(),
+        x,
+        y,
+        anchor: Anchor::Center,
+    }
+}
+
+#[test]
+fn rows_fail_alone_in_every_chunk() {
+    let rows = RENDER_CHUNK_ROWS * 2 + 10;
+    let records = course_records(rows);
+    let names: Vec<&str> = records.iter().map(|record| record.name.as_str()).collect();
+    let job = job("chunks", &names).graphics(vec![course_line(20.0, (300, 320))]).records(records.clone()).dry_run(true);
+
+    let report = job.run().unwrap();
+    let failed = (0..rows).filter(|&row| failing(row)).count();
+    assert_eq!((report.generated, report.failed), (rows - failed, failed));
+    assert!(report.items.iter().map(|item| item.index).eq(0..rows));
+    assert!(report.items.iter().all(|item| item.error.is_some() == failing(item.index)));
+    let manifest = Manifest::load_file(job.output_directory().join(DRY_RUN_MANIFEST_FILENAME)).unwrap();
+    assert_eq!(manifest.entries.len(), rows - failed);
+}
+
+// How many certificates manifest.json recorded when the second chunk's first row finished
+struct ManifestWatch {
+    manifest_dir: PathBuf,
+    recorded: Mutex<Option<usize>>,
+}
+
+impl ProgressSink for ManifestWatch {
+    fn on_item(&self, _index: usize, item: &BatchItem) {
+        if item.index == RENDER_CHUNK_ROWS {
+            let manifest = Manifest::load(&self.manifest_dir).unwrap();
+            *self.recorded.lock().unwrap() = Some(manifest.entries.len());
+        }
+    }
+}
+
+// A real run under a memory budget saves the manifest after every chunk, and its report keeps
+// the rows that failed and none of the rest. The template is small so the rows render quickly
+#[test]
+fn budgeted_batches_save_each_chunk_and_keep_only_problem_rows() {
+    let dir = test_dir("budget");
+    let template = dir.join("small.png");
+    RgbaImage::from_pixel(240, 80, Rgba([255, 255, 255, 255])).save(&template).unwrap();
+    let rows = RENDER_CHUNK_ROWS + 10;
+    let records = course_records(rows);
+    let output = dir.join("certificates");
+    let job = CertificateJob::new(&template, records.iter().map(|record| record.name.clone()).collect())
+        .font(FONT)
+        .font_size(12.0)
+        .graphics(vec![course_line(10.0, (120, 65))])
+        .records(records)
+        .output_dir(&output)
+        .memory_budget(1 << 30);
+    let watch = ManifestWatch { manifest_dir: output.clone(), recorded: Mutex::new(None) };
+
+    let report = job.run_with_progress(&watch).unwrap();
+    let failed = (0..rows).filter(|&row| failing(row)).count();
+    assert_eq!((report.generated, report.failed), (rows - failed, failed));
+    assert!(report.items.iter().map(|item| item.index).eq((0..rows).filter(|&row| failing(row))));
+    assert!(report.items.iter().all(|item| item.error.is_some()));
+    assert_eq!(report.entries.len(), rows - failed);
+    assert_eq!(report.performance.stage(Stage::Layout).unwrap().count, rows);
+
+    // The first chunk was on disk before the second finished
+    let first_chunk_failed = (0..RENDER_CHUNK_ROWS).filter(|&row| failing(row)).count();
+    assert_eq!(*watch.recorded.lock().unwrap(), Some(RENDER_CHUNK_ROWS - first_chunk_failed));
+    let manifest = Manifest::load(&output).unwrap();
+    assert_eq!(manifest.entries.len(), rows - failed);
+    assert_eq!(manifest.last_batch.unwrap().outputs.len(), rows - failed);
+}
@@ -0,0 +1,25 @@
+// tests/common/mod.rs
+// What the integration tests share: the fixture template and font most of them draw with, and
+// a directory of its own for each test under target/tmp/<suite>/<test>. Each suite takes it
+// with `mod common;`, so not every suite uses every item.
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+
+// 600x400, recording no DPI
+pub const TEMPLATE: &str = "tests/fixtures/template.png";
+pub const FONT: &str = "tests/fixtures/DejaVuSans-Latin.ttf";
+
+// A path of its own for each test, with nothing there yet; the suite is the test binary's name
+pub fn output_dir(test: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(env!("CARGO_CRATE_NAME")).join(test);
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+// An empty directory of its own for each test
+pub fn test_dir(test: &str) -> PathBuf {
+    let dir = output_dir(test);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
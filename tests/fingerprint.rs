@@ -0,0 +1,105 @@
+// tests/fingerprint.rs
+// Certificates that record how they were made. The fingerprint goes into each PNG's metadata
+// and the manifest, drawn in the corner only when visible; the same settings give the same
+// fingerprint and, in deterministic mode, the same bytes, wherever the batch runs.
+//
+//   cargo test --test fingerprint
+mod common;
+use certificate_maker::fingerprint::{FingerprintMode, read_fingerprint};
+use certificate_maker::job::{CertificateFormat, CertificateJob};
+use certificate_maker::manifest::Manifest;
+use common::{FONT, TEMPLATE, test_dir};
+use std::path::Path;
+
+fn job(output_dir: &Path) -> CertificateJob {
+    CertificateJob::new(TEMPLATE, vec!["Ada Lovelace".to_string(), "Alan Turing".to_string()])
+        .font(FONT)
+        .output_dir(output_dir)
+}
+
+#[test]
+fn hidden_fingerprints_are_recorded_but_not_drawn() {
+    let dir = test_dir("hidden");
+    let plain = job(&dir.join("plain")).run().unwrap();
+    let file = &plain.items[0].output_file;
+    assert_eq!(read_fingerprint(file).unwrap(), None);
+    assert!(plain.fingerprint.is_none());
+    let manifest = Manifest::load(dir.join("plain")).unwrap();
+    assert!(manifest.fingerprints.is_empty() && manifest.entries.iter().all(|entry| entry.fingerprint.is_none()));
+
+    let report = job(&dir.join("hidden")).fingerprint(FingerprintMode::Hidden).run().unwrap();
+    let fingerprint = report.fingerprint.as_deref().unwrap();
+    let hidden = &report.items[0].output_file;
+    assert_eq!(read_fingerprint(hidden).unwrap().as_ref(), Some(fingerprint));
+    assert_eq!(fingerprint.settings["template"]["file"], "template.png");
+    assert_eq!(fingerprint.settings["font_size"], 40.0);
+    // The same pixels as without one
+    assert_eq!(image::open(file).unwrap().to_rgba8(), image::open(hidden).unwrap().to_rgba8());
+
+    let manifest = Manifest::load(dir.join("hidden")).unwrap();
+    assert_eq!(manifest.fingerprints, vec![fingerprint.clone()]);
+    assert!(manifest.entries.iter().all(|entry| entry.fingerprint.as_ref() == Some(&fingerprint.hash)));
+}
+
+#[test]
+fn visible_fingerprints_are_drawn_in_the_corner() {
+    let dir = test_dir("visible");
+    let hidden = job(&dir.join("hidden")).fingerprint(FingerprintMode::Hidden).run().unwrap();
+    let visible = job(&dir.join("visible")).fingerprint(FingerprintMode::Visible).run().unwrap();
+    // Drawing the label doesn't change what it says
+    assert_eq!(hidden.fingerprint, visible.fingerprint);
+
+    let hidden = image::open(&hidden.items[0].output_file).unwrap().to_rgba8();
+    let visible = image::open(&visible.items[0].output_file).unwrap().to_rgba8();
+    let changed: Vec<(u32, u32)> = hidden.enumerate_pixels()
+        .filter(|(x, y, pixel)| visible.get_pixel(*x, *y) != *pixel)
+        .map(|(x, y, _)| (x, y))
+        .collect();
+    assert!(!changed.is_empty());
+    assert!(changed.iter().all(|&(x, y)| x > 400 && y > 350), "{:?}", changed.first());
+}
+
+#[test]
+fn the_same_settings_give_the_same_bytes_anywhere() {
+    let dir = test_dir("deterministic");
+    let run = |output: &str| {
+        let report = job(&dir.join(output)).fingerprint(FingerprintMode::Visible).deterministic(true).run().unwrap();
+        let files: Vec<Vec<u8>> = report.items.iter().map(|item| std::fs::read(&item.output_file).unwrap()).collect();
+        (report.fingerprint.unwrap().hash, files)
+    };
+    assert_eq!(run("first"), run("second"));
+
+    let other = job(&dir.join("third")).fingerprint(FingerprintMode::Visible).font_size(42.0).run().unwrap();
+    assert_ne!(other.fingerprint.unwrap().hash, run("first").0);
+}
+
+#[test]
+fn turning_the_fingerprint_on_renders_again_and_unchanged_rows_keep_theirs() {
+    let dir = test_dir("rerun");
+    assert_eq!(job(&dir).run().unwrap().generated, 2);
+    let report = job(&dir).fingerprint(FingerprintMode::Hidden).run().unwrap();
+    assert_eq!((report.generated, report.skipped_unchanged), (2, 0));
+    let hash = report.fingerprint.unwrap().hash;
+
+    let report = job(&dir).fingerprint(FingerprintMode::Hidden).run().unwrap();
+    assert_eq!((report.generated, report.skipped_unchanged), (0, 2));
+    let manifest = Manifest::load(&dir).unwrap();
+    assert_eq!(manifest.fingerprints.len(), 1);
+    assert!(manifest.entries.iter().all(|entry| entry.fingerprint.as_ref() == Some(&hash)));
+
+    // Off again, nothing is left that refers to it
+    job(&dir).run().unwrap();
+    let manifest = Manifest::load(&dir).unwrap();
+    assert!(manifest.fingerprints.is_empty() && manifest.entries.iter().all(|entry| entry.fingerprint.is_none()));
+}
+
+#[test]
+fn pdfs_record_the_fingerprint_in_the_manifest() {
+    let dir = test_dir("pdf");
+    let report = job(&dir).format(CertificateFormat::Pdf).fingerprint(FingerprintMode::Hidden).run().unwrap();
+    assert_eq!(report.failed, 0);
+    let manifest = Manifest::load(&dir).unwrap();
+    assert_eq!(manifest.fingerprints.len(), 1);
+    assert_eq!(manifest.fingerprints[0].settings["format"], "pdf");
+    assert!(manifest.entries.iter().all(|entry| entry.fingerprint.is_some()));
+}
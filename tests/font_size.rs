@@ -0,0 +1,110 @@
+// tests/font_size.rs
+// Font sizes checked against the template. Text taller than the limit's share of the template,
+// or under the legibility floor, is found with the size to use instead: the floor is in points
+// at the DPI a template records and in pixels when it records none. A batch reports the problem
+// and runs, or refuses to run in strict mode.
+//
+//   cargo test --test font_size
+mod common;
+use certificate_maker::editpng::load_font;
+use certificate_maker::fontsize::{FontSizeIssue, FontSizeLimits, check_font_size, check_template_font_size};
+use certificate_maker::job::CertificateJob;
+use common::{FONT, TEMPLATE, test_dir};
+use std::path::Path;
+
+// A white PNG recording `dpi`
+fn write_png(path: &Path, (width, height): (u32, u32), dpi: u32) {
+    let file = std::io::BufWriter::new(std::fs::File::create(path).unwrap());
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    let ppm = (dpi as f64 / 0.0254).round() as u32;
+    encoder.set_pixel_dims(Some(png::PixelDimensions { xppu: ppm, yppu: ppm, unit: png::Unit::Meter }));
+    encoder.write_header().unwrap().write_image_data(&vec![255; (width * height * 3) as usize]).unwrap();
+}
+
+#[test]
+fn sizes_are_checked_against_the_template_height() {
+    let font = load_font(FONT).unwrap();
+    let limits = FontSizeLimits::default();
+    let check = check_font_size(&font, "Ada Lovelace", 40.0, (600, 400), None, &limits).unwrap();
+    assert!(check.is_ok());
+    assert_eq!((check.text_height, check.max_height, check.min_height), (40, 160.0, 10.0));
+    assert!(check.message().is_none());
+
+    let check = check_font_size(&font, "Ada Lovelace", 400.0, (800, 600), None, &limits).unwrap();
+    assert_eq!(check.issue, Some(FontSizeIssue::TooLarge));
+    let message = check.message().unwrap();
+    assert!(message.contains("401 px tall"), "{}", message);
+    assert!(message.contains("67% of the 600 px high template"), "{}", message);
+    let suggested = check.suggested_size.unwrap();
+    assert!(suggested < 240.0 && suggested > 230.0, "{}", suggested);
+    assert!(check_font_size(&font, "Ada Lovelace", suggested, (800, 600), None, &limits).unwrap().is_ok());
+
+    // Looser limits let it through
+    let loose = FontSizeLimits { max_height_fraction: 0.7, ..limits };
+    assert!(check_font_size(&font, "Ada Lovelace", 400.0, (800, 600), None, &loose).unwrap().is_ok());
+    assert!(FontSizeLimits { max_height_fraction: 0.0, ..limits }.validate().is_err());
+    assert!(FontSizeLimits { min_points: -1.0, ..limits }.validate().is_err());
+}
+
+#[test]
+fn the_legibility_floor_follows_the_dpi() {
+    let dir = test_dir("dpi");
+    let font = load_font(FONT).unwrap();
+    let limits = FontSizeLimits::default();
+
+    // 12 px is readable on a web template but under 3 pt at 300 DPI, short of 6 pt (25 px)
+    assert!(check_template_font_size(TEMPLATE, &font, "Ada Lovelace", 12.0, &limits).unwrap().is_ok());
+    write_png(&dir.join("print.png"), (620, 877), 300);
+    let check = check_template_font_size(dir.join("print.png"), &font, "Ada Lovelace", 12.0, &limits).unwrap();
+    assert_eq!(check.issue, Some(FontSizeIssue::TooSmall));
+    assert_eq!(check.dpi.map(f32::round), Some(300.0));
+    assert_eq!(check.points.map(|points| (points * 10.0).round()), Some(29.0));
+    assert_eq!(check.min_height.round(), 25.0);
+    assert!(check.message().unwrap().contains("12 px tall (2.9 pt at 300 DPI)"), "{:?}", check.message());
+    let suggested = check.suggested_size.unwrap();
+    assert!(check_template_font_size(dir.join("print.png"), &font, "Ada Lovelace", suggested, &limits).unwrap().is_ok());
+    assert!(!check_template_font_size(dir.join("print.png"), &font, "Ada Lovelace", suggested - 1.0, &limits).unwrap().is_ok());
+
+    // Without a DPI the floor is in pixels
+    let check = check_template_font_size(TEMPLATE, &font, "Ada Lovelace", 8.0, &limits).unwrap();
+    assert_eq!(check.issue, Some(FontSizeIssue::TooSmall));
+    assert_eq!((check.dpi, check.points, check.min_height), (None, None, 10.0));
+    assert!(check.message().unwrap().contains("records no DPI"));
+}
+
+#[test]
+fn batches_report_the_problem_or_refuse_in_strict_mode() {
+    let dir = test_dir("batch");
+    let job = CertificateJob::new(TEMPLATE, vec!["Ada Lovelace".to_string()])
+        .font(FONT)
+        .font_size(6.0)
+        .output_dir(&dir)
+        .dry_run(true);
+    let report = job.run().unwrap();
+    let check = report.font_size.as_ref().unwrap();
+    assert_eq!(check.issue, Some(FontSizeIssue::TooSmall));
+    assert_eq!(check.suggested_size, Some(10.0));
+    report.save_json(dir.join("report.json")).unwrap();
+    let saved: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(dir.join("report.json")).unwrap()).unwrap();
+    assert_eq!(saved["font_size"]["issue"], "too_small");
+
+    // A lower floor accepts it
+    let lenient = job.clone().font_size_limits(FontSizeLimits { min_pixels: 5.0, ..FontSizeLimits::default() });
+    assert!(lenient.run().unwrap().font_size.is_none());
+
+    let problems = job.clone().strict_font_size(true).validate().unwrap_err();
+    assert_eq!(problems.len(), 1, "{:?}", problems);
+    assert!(problems[0].contains("below the legibility floor of 10 px; size 10 or more reads"), "{}", problems[0]);
+    assert!(job.font_size(40.0).strict_font_size(true).validate().is_ok());
+}
+
+#[test]
+fn height_limits_parse_as_percentages_or_fractions() {
+    assert_eq!(FontSizeLimits::parse_height_fraction("40%"), Some(0.4));
+    assert_eq!(FontSizeLimits::parse_height_fraction(" 0.25 "), Some(0.25));
+    assert_eq!(FontSizeLimits::parse_height_fraction("100%"), Some(1.0));
+    for bad in ["0", "0%", "150%", "1.5", "forty", ""] {
+        assert_eq!(FontSizeLimits::parse_height_fraction(bad), None, "{}", bad);
+    }
+}
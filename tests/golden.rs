@@ -0,0 +1,105 @@
+// tests/golden.rs
+// Golden-image regression tests: each case renders one certificate on the fixture template and
+// font with CertificateJob::render_image and compares it with tests/golden/<case>.png.
+//
+//   cargo test --test golden
+//   CERTMAKER_UPDATE_GOLDENS=1 cargo test --test golden    # rewrite the goldens after a deliberate change
+//
+// A pixel passes when no channel is more than TOLERANCE off, which absorbs rounding in the
+// antialiasing but not a text box moved by a pixel. A failing case leaves <case>.actual.png and
+// <case>.diff.png (differing pixels in red over a faded golden) under target/tmp/golden/.
+mod common;
+use certificate_maker::editpng::{TextOverflow, save_image};
+use certificate_maker::graphics::{GraphicField, GraphicKind, TextStyle};
+use certificate_maker::job::{Anchor, CertificateJob};
+use common::{FONT, TEMPLATE};
+use image::{ImageFormat, Rgba, RgbaImage};
+use std::path::{Path, PathBuf};
+
+const TOLERANCE: u8 = 3;
+
+fn job(name: &str) -> CertificateJob {
+    CertificateJob::new(TEMPLATE, vec![name.to_string()]).font(FONT)
+}
+
+#[test]
+fn ascii_name() {
+    check("ascii_name", job("Ada Lovelace"));
+}
+
+#[test]
+fn accented_name() {
+    check("accented_name", job("Zoë Ångström-Núñez").font_size(48.0));
+}
+
+// Names wider than the space are set smaller until they fit, rather than wrapped
+#[test]
+fn long_name_fitted() {
+    check("long_name_fitted", job("Bartholomew Featherstonehaugh-Montgomery III").font_size(48.0).max_width(400.0));
+}
+
+#[test]
+fn colored_text() {
+    check("colored_text", job("Grace Hopper").font_size(52.0).color("#1F6FB2"));
+}
+
+#[test]
+fn alpha_text() {
+    check("alpha_text", job("Katherine Johnson").font_size(56.0).color("#C0102080"));
+}
+
+// The name and a second line under it, as a text field
+#[test]
+fn multi_line() {
+    let subtitle = GraphicField {
+        kind: GraphicKind::Text(TextStyle { font: PathBuf::from(FONT), size: 22.0, color: "#5A4632".to_string(), max_width: None, overflow: TextOverflow::Shrink, ellipsis: None, effect: None }),
+        data: "for outstanding contributions, {name}".to_string(),
+        x: 300,
+        y: 250,
+        anchor: Anchor::Center,
+    };
+    check("multi_line", job("Mary Jackson").position(Anchor::Center, 300, 180).graphics(vec![subtitle]));
+}
+
+fn check(case: &str, job: CertificateJob) {
+    let actual = job.render_image(0).unwrap_or_else(|e| panic!("{}: rendering failed: {}", case, e));
+    let golden_path = Path::new("tests/golden").join(format!("{}.png", case));
+    if std::env::var_os("CERTMAKER_UPDATE_GOLDENS").is_some() {
+        std::fs::create_dir_all("tests/golden").unwrap();
+        save_image(&actual, &golden_path, ImageFormat::Png).unwrap();
+        return;
+    }
+    let golden = image::open(&golden_path)
+        .unwrap_or_else(|e| panic!("{}: no golden image at {} ({}); create it with CERTMAKER_UPDATE_GOLDENS=1", case, golden_path.display(), e))
+        .to_rgba8();
+    assert_eq!(actual.dimensions(), golden.dimensions(), "{}: the image size changed", case);
+
+    let (diff, differing, worst) = difference(&golden, &actual);
+    if differing > 0 {
+        let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+        std::fs::create_dir_all(&dir).unwrap();
+        save_image(&actual, dir.join(format!("{}.actual.png", case)), ImageFormat::Png).unwrap();
+        save_image(&diff, dir.join(format!("{}.diff.png", case)), ImageFormat::Png).unwrap();
+        panic!("{}: {} pixels differ from {} by more than {} (up to {}); see {}",
+               case, differing, golden_path.display(), TOLERANCE, worst, dir.display());
+    }
+}
+
+// The diff image, how many pixels are off by more than TOLERANCE and the largest difference
+fn difference(golden: &RgbaImage, actual: &RgbaImage) -> (RgbaImage, usize, u8) {
+    let mut differing = 0;
+    let mut worst = 0;
+    let diff = RgbaImage::from_fn(golden.width(), golden.height(), |x, y| {
+        let (expected, got) = (golden.get_pixel(x, y), actual.get_pixel(x, y));
+        let off = expected.0.iter().zip(got.0).map(|(&a, b)| a.abs_diff(b)).max().unwrap_or(0);
+        worst = worst.max(off);
+        if off > TOLERANCE {
+            differing += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            let faded = |channel: u8| 255 - (255 - channel) / 4;
+            Rgba([faded(expected[0]), faded(expected[1]), faded(expected[2]), 255])
+        }
+    });
+    (diff, differing, worst)
+}
@@ -0,0 +1,90 @@
+// tests/i18n.rs
+// Translations of the interactive tool. The compiled-in catalogs parse, translate only messages
+// the tool still says, and keep to the placeholders of the English message; anything a catalog
+// lacks stays in English.
+//
+//   cargo test --test i18n
+use certificate_maker::i18n::{Catalog, fill, languages, placeholders};
+
+// The sources of the interactive tool, where every tr! and say! message is written
+const SOURCES: [&str; 7] = ["main.rs", "input.rs", "interactive.rs", "display.rs", "watch.rs", "mailing.rs", "tui.rs"];
+
+// The messages given to tr! and say! in the sources, unescaped as the compiler would
+fn messages_in_sources() -> Vec<String> {
+    let mut messages = Vec::new();
+    for file in SOURCES {
+        let source = std::fs::read_to_string(format!("src/{}", file)).unwrap();
+        for start in ["tr!(", "say!("] {
+            for (at, _) in source.match_indices(start) {
+                let Some(literal) = source[at + start.len()..].trim_start().strip_prefix('"') else { continue };
+                let mut message = String::new();
+                let mut chars = literal.chars();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => message.push(match chars.next().unwrap() {
+                            'n' => '\n',
+                            't' => '\t',
+                            other => other,
+                        }),
+                        c => message.push(c),
+                    }
+                }
+                messages.push(message);
+            }
+        }
+    }
+    messages
+}
+
+#[test]
+fn catalogs_are_found_by_locale() {
+    assert_eq!(languages().collect::<Vec<_>>(), vec!["es"]);
+    for locale in ["es", "es_ES.UTF-8", "es-MX", "ES"] {
+        assert_eq!(Catalog::builtin(locale).map(|catalog| catalog.language), Some("es".to_string()), "{}", locale);
+    }
+    for locale in ["en_US.UTF-8", "C", "POSIX", "de", ""] {
+        assert!(Catalog::builtin(locale).is_none(), "{}", locale);
+    }
+}
+
+#[test]
+fn catalogs_translate_messages_the_tool_says() {
+    let messages = messages_in_sources();
+    assert!(messages.len() > 500, "found only {} messages", messages.len());
+    for language in languages() {
+        let text = std::fs::read_to_string(format!("locales/{}.po", language)).unwrap();
+        let catalog = Catalog::from_po(language, &text).unwrap();
+        for (english, translated) in catalog.messages() {
+            assert!(messages.iter().any(|message| message == english), "{}.po translates a message the tool no longer says: {:?}", language, english);
+            let (wanted, used) = (placeholders(english), placeholders(translated));
+            assert!(used.iter().all(|index| wanted.contains(index)), "{}.po: {:?} uses placeholders {:?} of {:?}", language, translated, used, wanted);
+        }
+    }
+}
+
+#[test]
+fn missing_messages_stay_in_english() {
+    let catalog = Catalog::from_po("es", "# comment\nmsgid \"Exit\"\nmsgstr \"Salir\"\n\nmsgid \"Not yet\"\nmsgstr \"\"\n").unwrap();
+    assert_eq!(catalog.translate("Exit"), "Salir");
+    assert_eq!(catalog.translate("Not yet"), "Not yet");
+    assert_eq!(catalog.translate("Never seen"), "Never seen");
+
+    // Continued strings and escapes
+    let catalog = Catalog::from_po("es", "msgid \"\"\n\"\\nSaved \\\"{}\\\"\"\nmsgstr \"\\nGuardado \"\n\"\\\"{}\\\"\"\n").unwrap();
+    assert_eq!(fill(catalog.translate("\nSaved \"{}\""), &[&"a.png"]), "\nGuardado \"a.png\"");
+
+    for broken in ["msgid \"a\"\n", "msgstr \"a\"\n", "msgid a\nmsgstr \"b\"\n", "\"a\"\n"] {
+        assert!(Catalog::from_po("es", broken).is_err(), "{:?}", broken);
+    }
+}
+
+#[test]
+fn arguments_are_filled_in_turn_or_by_position() {
+    assert_eq!(fill("{} of {}", &[&1, &"two"]), "1 of two");
+    assert_eq!(fill("{1} de {0}", &[&1, &"two"]), "two de 1");
+    assert_eq!(fill("{{{}}} {", &[&7]), "{7} {");
+    // Too few arguments leave the placeholder as it is
+    assert_eq!(fill("{} and {}", &[&"one"]), "one and {}");
+    assert_eq!(placeholders("{} {{}} {2} {}"), vec![0, 1, 2]);
+}
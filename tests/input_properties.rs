@@ -0,0 +1,100 @@
+// tests/input_properties.rs
+// Property tests of the helpers fed straight from CSV cells and prompts: arbitrary strings
+// must never panic them, and whatever they return must be usable on every system.
+//
+//   cargo test --test input_properties
+//   PROPTEST_CASES=100000 cargo test --test input_properties    # a longer run
+use certificate_maker::csvexcelparser::certificate_output_path;
+use certificate_maker::editpng::hex_to_rgba;
+use certificate_maker::files::{MAX_FILE_NAME_PART, portable_file_name};
+use certificate_maker::grouping::group_dir_name;
+use image::Rgba;
+use proptest::prelude::*;
+use std::path::{Component, Path};
+
+// Characters some supported system refuses in a file name
+const FORBIDDEN: [char; 9] = ['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+const RESERVED: [&str; 4] = ["CON", "PRN", "AUX", "NUL"];
+
+// Strings that look like colors more often than any::<String>() would
+fn color_like() -> impl Strategy<Value = String> {
+    prop_oneof![
+        any::<String>(),
+        "#?[0-9a-fA-F]{0,10}",
+        "[ #]{0,2}[0-9a-fA-FéÉ+\\- ]{5,9}",
+    ]
+}
+
+// Names with the characters that matter for file names mixed in
+fn name_like() -> impl Strategy<Value = String> {
+    prop_oneof![
+        any::<String>(),
+        "[a-zA-Z .:*?\"<>|/\\\\\\x00-\\x1féñ日本]{0,40}",
+        "(CON|con|Nul|COM1|lpt9|AUX)(\\.[a-z]{0,3})?",
+        "[éa ]{150,300}",
+    ]
+}
+
+fn assert_portable(name: &str) {
+    assert!(!name.chars().any(|c| c.is_control() || FORBIDDEN.contains(&c)), "{:?} has a forbidden character", name);
+    assert!(name.len() <= 255, "{:?} is longer than 255 bytes", name);
+    let stem = name.split('.').next().unwrap_or_default().trim_end().to_ascii_uppercase();
+    let device = RESERVED.contains(&stem.as_str())
+        || (stem.len() == 4 && (stem.starts_with("COM") || stem.starts_with("LPT")) && stem.as_bytes()[3].is_ascii_digit());
+    assert!(!device, "{:?} is a device name on Windows", name);
+}
+
+proptest! {
+    #[test]
+    fn hex_to_rgba_never_panics(color in color_like()) {
+        match hex_to_rgba(&color) {
+            Ok(_) => {
+                let hex = color.trim().trim_start_matches('#');
+                prop_assert!(hex.len() == 6 || hex.len() == 8);
+                prop_assert!(hex.chars().all(|c| c.is_ascii_hexdigit()));
+            }
+            // The message names the input and shows valid syntax
+            Err(e) => {
+                let message = e.to_string();
+                prop_assert!(message.contains(&format!("'{}'", color)), "{}", message);
+                prop_assert!(message.contains("#RRGGBB"), "{}", message);
+            }
+        }
+    }
+
+    #[test]
+    fn hex_to_rgba_round_trips(r: u8, g: u8, b: u8, a: u8, lower: bool) {
+        let rgba = format!("#{:02X}{:02X}{:02X}{:02X}", r, g, b, a);
+        let rgba = if lower { rgba.to_lowercase() } else { rgba };
+        prop_assert_eq!(hex_to_rgba(&rgba).unwrap(), Rgba([r, g, b, a]));
+        prop_assert_eq!(hex_to_rgba(&rgba[..7]).unwrap(), Rgba([r, g, b, 255]));
+    }
+
+    #[test]
+    fn portable_file_names_are_valid_everywhere(text in name_like()) {
+        let name = portable_file_name(&text);
+        prop_assert!(name.len() <= MAX_FILE_NAME_PART);
+        assert_portable(&name);
+    }
+
+    #[test]
+    fn certificate_files_stay_in_the_output_directory(name in name_like()) {
+        let path = certificate_output_path("certs", &name);
+        prop_assert_eq!(path.parent(), Some(Path::new("certs")));
+        prop_assert!(path.components().all(|component| matches!(component, Component::Normal(_))));
+        let file_name = path.file_name().unwrap().to_str().unwrap();
+        prop_assert!(file_name.starts_with("certificate_") && file_name.ends_with(".png"));
+        prop_assert!(!file_name.contains(' '));
+        assert_portable(file_name);
+    }
+
+    #[test]
+    fn group_directories_are_valid_everywhere(value in name_like()) {
+        if let Some(dir) = group_dir_name(&value) {
+            prop_assert!(!dir.is_empty() && dir != "." && dir != "..");
+            prop_assert!(!dir.starts_with([' ', '.']) && !dir.ends_with([' ', '.']), "{:?}", dir);
+            prop_assert!(Path::new(&dir).components().all(|component| matches!(component, Component::Normal(_))));
+            assert_portable(&dir);
+        }
+    }
+}
@@ -0,0 +1,132 @@
+// tests/plan.rs
+// Plans made ahead of a batch. Executing a plan writes the certificates the batch itself would
+// have written, byte for byte, and draws each name where the plan says even after the plan was
+// edited by hand. A row that failed in the plan fails again without stopping the others, and a
+// plan whose template changed, or of another version, is refused.
+//
+//   cargo test --test plan
+mod common;
+use certificate_maker::csvexcelparser::{BatchSettings, run_batch};
+use certificate_maker::error::CertError;
+use certificate_maker::graphics::{GraphicField, GraphicKind, ImageStyle};
+use certificate_maker::job::Anchor;
+use certificate_maker::namecase::NameCase;
+use certificate_maker::plan::{BatchPlan, execute_plan, plan_batch};
+use certificate_maker::progress::NoProgress;
+use certificate_maker::source::Record;
+use common::{FONT, TEMPLATE, test_dir};
+use std::path::PathBuf;
+
+fn settings(output_dir: PathBuf) -> BatchSettings {
+    BatchSettings {
+        template_file: TEMPLATE.into(),
+        font_filename: FONT.into(),
+        font_size: 30.0,
+        hex_color: "#203040".to_string(),
+        x_pos: 300,
+        y_pos: 180,
+        output_dir,
+        name_case: NameCase::Title,
+        ..Default::default()
+    }
+}
+
+fn names(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn executed_plan_matches_the_batch() {
+    let dir = test_dir("matches");
+    let names = names(&["ada lovelace", "grace hopper", "mary jackson"]);
+    let plan = plan_batch(&settings(dir.join("planned")), &names).unwrap();
+    assert_eq!(plan.rows.len(), 3);
+    assert_eq!(plan.rows[0].text, "Ada Lovelace");
+    assert_eq!(plan.rows[0].name, "ada lovelace");
+
+    let planned = execute_plan(&plan, &NoProgress).unwrap();
+    let direct = run_batch(&settings(dir.join("direct")), &names, &NoProgress).unwrap();
+    assert_eq!(planned.generated, 3);
+    for (planned, direct) in planned.items.iter().zip(&direct.items) {
+        assert_eq!(planned.position, direct.position);
+        assert_eq!(std::fs::read(&planned.output_file).unwrap(), std::fs::read(&direct.output_file).unwrap());
+    }
+
+    // The manifest records what a batch would have, so the next plan finds nothing to do
+    let again = plan_batch(&settings(dir.join("planned")), &names).unwrap();
+    assert_eq!((again.rows.len(), again.skipped_unchanged), (0, 3));
+}
+
+#[test]
+fn edited_plan_is_drawn_as_written() {
+    let dir = test_dir("edited");
+    let mut plan = plan_batch(&settings(dir.join("certificates")), &names(&["ada lovelace"])).unwrap();
+    let row = &mut plan.rows[0];
+    row.text = "Lady Ada".to_string();
+    row.x = 20;
+    row.y = 30;
+    row.font_size = 12.0;
+    row.output_file = dir.join("certificates").join("ada.png");
+
+    let report = execute_plan(&plan, &NoProgress).unwrap();
+    let item = &report.items[0];
+    assert_eq!(item.position, (20, 30));
+    assert_eq!(item.output_file, dir.join("certificates").join("ada.png"));
+    let drawn = item.drawn.as_ref().unwrap();
+    assert_eq!((drawn.text.as_str(), drawn.font_size), ("Lady Ada", 12.0));
+    assert!(item.output_file.exists());
+}
+
+// A photo that is missing fails its row in the plan, and again when the plan is executed
+#[test]
+fn failed_rows_fail_again() {
+    let dir = test_dir("failed_rows");
+    let mut settings = settings(dir.join("certificates"));
+    settings.graphics = vec![GraphicField {
+        kind: GraphicKind::Image(ImageStyle { width: Some(60), height: None }),
+        data: "{Photo}".to_string(),
+        x: 300,
+        y: 320,
+        anchor: Anchor::Center,
+    }];
+    settings.records = vec![
+        Record { name: "Ada".to_string(), fields: vec![("Photo".to_string(), TEMPLATE.to_string())] },
+        Record { name: "Grace".to_string(), fields: vec![("Photo".to_string(), "tests/fixtures/no-such-photo.png".to_string())] },
+    ];
+    let plan = plan_batch(&settings, &names(&["Ada", "Grace"])).unwrap();
+    assert!(plan.rows[0].error.is_none());
+    assert!(plan.rows[1].error.as_ref().is_some_and(|error| error.contains("no-such-photo.png")));
+    assert_eq!(plan.renderable(), 1);
+
+    let report = execute_plan(&plan, &NoProgress).unwrap();
+    assert_eq!((report.generated, report.failed), (1, 1));
+    assert_eq!(report.items[1].name, "Grace");
+    assert!(!report.items[1].output_file.exists());
+}
+
+#[test]
+fn outdated_and_foreign_plans_are_refused() {
+    let dir = test_dir("refused");
+    let template = dir.join("template.png");
+    std::fs::copy(TEMPLATE, &template).unwrap();
+    let mut settings = settings(dir.join("certificates"));
+    settings.template_file = template.clone();
+    let plan = plan_batch(&settings, &names(&["Ada"])).unwrap();
+
+    // Saved as CSV it is only for reading
+    plan.save(dir.join("plan.csv")).unwrap();
+    assert!(matches!(BatchPlan::load(dir.join("plan.csv")), Err(CertError::PlanNotRunnable(_))));
+
+    // A newer format
+    plan.save(dir.join("plan.json")).unwrap();
+    let json = std::fs::read_to_string(dir.join("plan.json")).unwrap().replacen("\"version\": 1", "\"version\": 2", 1);
+    std::fs::write(dir.join("plan.json"), json).unwrap();
+    assert!(matches!(BatchPlan::load(dir.join("plan.json")), Err(CertError::PlanVersion { version: 2, .. })));
+
+    // The template changed since the plan measured the names on it
+    let mut image = image::open(&template).unwrap().to_rgba8();
+    image.put_pixel(0, 0, image::Rgba([1, 2, 3, 255]));
+    image.save(&template).unwrap();
+    assert!(matches!(execute_plan(&plan, &NoProgress), Err(CertError::PlanOutdated(path)) if path == template));
+    assert!(!dir.join("certificates").join("Ada.png").exists());
+}
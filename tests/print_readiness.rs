@@ -0,0 +1,143 @@
+// tests/print_readiness.rs
+// Print readiness of templates. A template with enough pixels for its paper, opaque, in sRGB
+// and of a standard paper's shape is ready; each of low resolution, transparency, a missing or
+// foreign color profile, an odd shape and heavy JPEG compression is found on its own, with a
+// hint on what to do.
+//
+//   cargo test --test print_readiness
+mod common;
+use certificate_maker::analysis::{PrintIssue, PrintReadiness, PrintTarget, check_print_readiness};
+use common::test_dir;
+use image::codecs::jpeg::JpegEncoder;
+use image::{Rgb, RgbImage, Rgba, RgbaImage};
+use std::path::Path;
+
+// A6 at 150 DPI is 620x874 pixels, small enough to encode quickly
+fn target() -> PrintTarget {
+    PrintTarget::new("a6", 150.0).unwrap()
+}
+
+// Smooth shading with fine texture, like a scanned or photographic background
+fn artwork(width: u32, height: u32) -> RgbImage {
+    RgbImage::from_fn(width, height, |x, y| {
+        let grain = ((x * 7919 + y * 104729) ^ (x * y)) % 37;
+        Rgb([(x * 200 / width + grain) as u8, (y * 200 / height + grain) as u8, (150 + grain) as u8])
+    })
+}
+
+// A PNG, with the sRGB chunk when asked
+fn write_png(path: &Path, image: &RgbaImage, srgb: bool) {
+    let file = std::io::BufWriter::new(std::fs::File::create(path).unwrap());
+    let mut encoder = png::Encoder::new(file, image.width(), image.height());
+    encoder.set_color(png::ColorType::Rgba);
+    if srgb {
+        encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+    }
+    encoder.write_header().unwrap().write_image_data(image.as_raw()).unwrap();
+}
+
+// A JPEG at `quality`, with an ICC profile described as `profile` when given
+fn write_jpeg(path: &Path, image: &RgbImage, quality: u8, profile: Option<&str>) {
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, quality).encode_image(image).unwrap();
+    if let Some(description) = profile {
+        let profile = icc_profile(description);
+        let mut segment = b"ICC_PROFILE\0\x01\x01".to_vec();
+        segment.extend(&profile);
+        let mut app2 = vec![0xFF, 0xE2];
+        app2.extend(((segment.len() + 2) as u16).to_be_bytes());
+        app2.extend(segment);
+        jpeg.splice(2..2, app2);
+    }
+    std::fs::write(path, jpeg).unwrap();
+}
+
+// The least of an RGB ICC profile: its header and a v2 description tag
+fn icc_profile(description: &str) -> Vec<u8> {
+    let mut profile = vec![0u8; 128];
+    profile[16..20].copy_from_slice(b"RGB ");
+    profile.extend(1u32.to_be_bytes());
+    profile.extend(b"desc");
+    profile.extend(144u32.to_be_bytes());
+    profile.extend((12 + description.len() as u32 + 1).to_be_bytes());
+    profile.extend(b"desc\0\0\0\0");
+    profile.extend((description.len() as u32 + 1).to_be_bytes());
+    profile.extend(description.as_bytes());
+    profile.push(0);
+    let size = profile.len() as u32;
+    profile[0..4].copy_from_slice(&size.to_be_bytes());
+    profile
+}
+
+fn issues(report: &PrintReadiness) -> Vec<PrintIssue> {
+    report.findings.iter().map(|finding| finding.issue).collect()
+}
+
+#[test]
+fn a_print_ready_template_has_no_findings() {
+    let dir = test_dir("ready");
+    let (width, height) = target().pixels(false);
+    assert_eq!((width, height), (620, 874));
+    write_png(&dir.join("ready.png"), &RgbaImage::from_pixel(width, height, Rgba([255, 255, 255, 255])), true);
+    let report = check_print_readiness(dir.join("ready.png"), &target()).unwrap();
+    assert!(report.is_ready(), "{:?}", report.findings);
+    assert_eq!(report.closest_paper, "A6");
+    assert_eq!(report.effective_dpi.round(), 150.0);
+
+    // Turned on its side it is checked against landscape paper
+    write_png(&dir.join("landscape.png"), &RgbaImage::from_pixel(height, width, Rgba([255, 255, 255, 255])), true);
+    assert!(check_print_readiness(dir.join("landscape.png"), &target()).unwrap().is_ready());
+}
+
+#[test]
+fn each_problem_is_found_with_a_hint() {
+    let dir = test_dir("problems");
+    let (width, height) = target().pixels(false);
+
+    // Half the pixels for the paper
+    write_png(&dir.join("small.png"), &RgbaImage::from_pixel(width / 2, height / 2, Rgba([255; 4])), true);
+    let report = check_print_readiness(dir.join("small.png"), &target()).unwrap();
+    assert_eq!(issues(&report), vec![PrintIssue::LowResolution]);
+    assert!(report.findings[0].hint.contains("620x874"), "{:?}", report.findings);
+
+    // See-through corner
+    let mut image = RgbaImage::from_pixel(width, height, Rgba([255; 4]));
+    image.put_pixel(0, 0, Rgba([255, 255, 255, 0]));
+    write_png(&dir.join("transparent.png"), &image, true);
+    assert_eq!(issues(&check_print_readiness(dir.join("transparent.png"), &target()).unwrap()), vec![PrintIssue::Transparency]);
+
+    // No profile at all
+    write_png(&dir.join("unmanaged.png"), &RgbaImage::from_pixel(width, height, Rgba([255; 4])), false);
+    let report = check_print_readiness(dir.join("unmanaged.png"), &target()).unwrap();
+    assert_eq!(issues(&report), vec![PrintIssue::ColorProfile]);
+    assert!(report.findings[0].message.contains("no color profile"));
+
+    // Square
+    write_png(&dir.join("square.png"), &RgbaImage::from_pixel(width, width, Rgba([255; 4])), true);
+    let report = check_print_readiness(dir.join("square.png"), &target()).unwrap();
+    assert!(issues(&report).contains(&PrintIssue::PaperAspect), "{:?}", report.findings);
+    assert!(report.findings.iter().all(|finding| !finding.hint.is_empty() && !finding.hint.contains('\n')));
+}
+
+#[test]
+fn jpeg_profiles_and_compression_are_checked() {
+    let dir = test_dir("jpeg");
+    let (width, height) = target().pixels(false);
+    let artwork = artwork(width, height);
+
+    write_jpeg(&dir.join("srgb.jpg"), &artwork, 95, Some("sRGB IEC61966-2.1"));
+    let report = check_print_readiness(dir.join("srgb.jpg"), &target()).unwrap();
+    assert!(report.is_ready(), "{:?}", report.findings);
+
+    write_jpeg(&dir.join("p3.jpg"), &artwork, 95, Some("Display P3"));
+    let report = check_print_readiness(dir.join("p3.jpg"), &target()).unwrap();
+    assert_eq!(issues(&report), vec![PrintIssue::ColorProfile]);
+    assert!(report.findings[0].message.contains("Display P3"));
+
+    write_jpeg(&dir.join("unmanaged.jpg"), &artwork, 95, None);
+    assert_eq!(issues(&check_print_readiness(dir.join("unmanaged.jpg"), &target()).unwrap()), vec![PrintIssue::ColorProfile]);
+
+    write_jpeg(&dir.join("crushed.jpg"), &artwork, 15, Some("sRGB IEC61966-2.1"));
+    let report = check_print_readiness(dir.join("crushed.jpg"), &target()).unwrap();
+    assert_eq!(issues(&report), vec![PrintIssue::JpegCompression]);
+}
@@ -0,0 +1,49 @@
+// tests/recent_colors.rs
+// Custom colors remembered at the color prompt. They share the state file with the last
+// batch's settings without disturbing them and keep the newest few, once each.
+//
+//   cargo test --test recent_colors
+mod common;
+use certificate_maker::csvexcelparser::{RECENT_COLOR_COUNT, RecentColors};
+use common::test_dir;
+
+#[test]
+fn the_newest_colors_are_kept_once_each() {
+    let mut recent = RecentColors::default();
+    for i in 0..RECENT_COLOR_COUNT + 2 {
+        recent.remember(&format!("#00000{}", i));
+    }
+    assert_eq!(recent.colors, vec!["#000006", "#000005", "#000004", "#000003", "#000002"]);
+
+    // Picked again, whatever its case, it moves to the front
+    recent.remember("#000003");
+    recent.remember("#00000a");
+    recent.remember("#00000A");
+    assert_eq!(recent.colors, vec!["#00000A", "#000003", "#000006", "#000005", "#000004"]);
+}
+
+#[test]
+fn recent_colors_share_the_state_file() {
+    let dir = test_dir("shared");
+    let state = dir.join("state.json");
+
+    // No state file yet: nothing remembered
+    assert_eq!(RecentColors::load_from(&state).unwrap(), RecentColors::default());
+
+    std::fs::write(&state, r#"{"template_path": "CertificateTemplate.png", "font_size": 80.0}"#).unwrap();
+    let mut recent = RecentColors::default();
+    recent.remember("#1A2B3C");
+    recent.remember("palette:brand-red");
+    recent.save_to(&state).unwrap();
+    assert_eq!(RecentColors::load_from(&state).unwrap(), recent);
+
+    let saved: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&state).unwrap()).unwrap();
+    assert_eq!(saved["template_path"], "CertificateTemplate.png");
+    assert_eq!(saved["font_size"], 80.0);
+    assert_eq!(saved["recent_colors"], serde_json::json!(["palette:brand-red", "#1A2B3C"]));
+
+    // A state file that isn't JSON is an error rather than forgotten
+    std::fs::write(&state, "not json").unwrap();
+    assert!(RecentColors::load_from(&state).is_err());
+    assert!(recent.save_to(&state).is_err());
+}
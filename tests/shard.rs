@@ -0,0 +1,171 @@
+// tests/shard.rs
+// A plan split into shards. Every row is rendered by exactly one shard, and merging the shards'
+// manifests gives the manifest a single run of the plan would have written. A shard missing or
+// run twice, shards of different plans or splits, and certificates missing from the directory
+// are refused, every problem at once.
+//
+//   cargo test --test shard
+mod common;
+use certificate_maker::csvexcelparser::BatchSettings;
+use certificate_maker::error::CertError;
+use certificate_maker::graphics::{GraphicField, GraphicKind, ImageStyle};
+use certificate_maker::job::Anchor;
+use certificate_maker::manifest::Manifest;
+use certificate_maker::plan::{BatchPlan, execute_plan, plan_batch};
+use certificate_maker::progress::NoProgress;
+use certificate_maker::shard::{Shard, execute_shard, merge_shard_manifests, shard_manifests_in};
+use certificate_maker::source::Record;
+use common::{FONT, TEMPLATE, test_dir};
+use std::path::PathBuf;
+
+const NAMES: [&str; 5] = ["Ada Lovelace", "Grace Hopper", "Mary Jackson", "Alan Turing", "Edsger Dijkstra"];
+
+fn plan(output_dir: PathBuf, names: &[&str]) -> BatchPlan {
+    let settings = BatchSettings {
+        template_file: TEMPLATE.into(),
+        font_filename: FONT.into(),
+        font_size: 30.0,
+        hex_color: "#203040".to_string(),
+        x_pos: 300,
+        y_pos: 180,
+        output_dir,
+        ..Default::default()
+    };
+    let names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+    plan_batch(&settings, &names).unwrap()
+}
+
+fn refusals(result: Result<impl std::fmt::Debug, CertError>) -> Vec<String> {
+    match result {
+        Err(CertError::ShardsDontAddUp(problems)) => problems,
+        other => panic!("expected the shards to be refused, got {:?}", other),
+    }
+}
+
+#[test]
+fn shards_are_parsed_and_split_by_row() {
+    let shard: Shard = "2/3".parse().unwrap();
+    assert_eq!((shard.index, shard.count), (2, 3));
+    assert_eq!(shard.rows(8), vec![1, 4, 7]);
+    assert_eq!("3/3".parse::<Shard>().unwrap().rows(2), Vec::<usize>::new());
+    for invalid in ["2", "0/3", "4/3", "a/3", "1/0", ""] {
+        assert!(matches!(invalid.parse::<Shard>(), Err(CertError::InvalidShard { .. })), "{}", invalid);
+    }
+}
+
+#[test]
+fn merged_shards_match_the_whole_plan() {
+    let dir = test_dir("whole");
+    let sharded = plan(dir.join("sharded"), &NAMES);
+    let whole = plan(dir.join("whole"), &NAMES);
+    for index in 1..=3 {
+        let report = execute_shard(&sharded, Shard { index, count: 3 }, &NoProgress).unwrap();
+        assert_eq!(report.generated, Shard { index, count: 3 }.rows(NAMES.len()).len());
+    }
+    // Nothing but the shards' own manifests until they are merged
+    assert!(!dir.join("sharded").join("manifest.json").exists());
+    let files = shard_manifests_in(dir.join("sharded")).unwrap();
+    assert_eq!(files.len(), 3);
+
+    let merged = merge_shard_manifests(dir.join("sharded"), &files).unwrap();
+    assert_eq!((merged.shards, merged.certificates), (3, NAMES.len()));
+    assert!(merged.failed.is_empty());
+    execute_plan(&whole, &NoProgress).unwrap();
+    let sharded_manifest = Manifest::load(dir.join("sharded")).unwrap();
+    let whole_manifest = Manifest::load(dir.join("whole")).unwrap();
+    assert_eq!(sharded_manifest.entries.len(), NAMES.len());
+    assert!(sharded_manifest.shard.is_none());
+    assert_eq!(sharded_manifest.last_batch.as_ref().unwrap().outputs.len(), NAMES.len());
+    for entry in &whole_manifest.entries {
+        let file_name = entry.output_file.file_name().unwrap();
+        let merged_entry = sharded_manifest.find(dir.join("sharded").join(file_name)).unwrap();
+        assert_eq!(merged_entry.content_hash, entry.content_hash);
+        assert_eq!(std::fs::read(&merged_entry.output_file).unwrap(), std::fs::read(&entry.output_file).unwrap());
+    }
+
+    // Merged, the rows count as unchanged for the next plan
+    let again = plan(dir.join("sharded"), &NAMES);
+    assert_eq!((again.rows.len(), again.skipped_unchanged), (0, NAMES.len()));
+}
+
+#[test]
+fn missing_and_repeated_shards_are_refused() {
+    let dir = test_dir("missing");
+    let plan = plan(dir.clone(), &NAMES);
+    execute_shard(&plan, Shard { index: 1, count: 3 }, &NoProgress).unwrap();
+    execute_shard(&plan, Shard { index: 1, count: 3 }, &NoProgress).unwrap();
+    execute_shard(&plan, Shard { index: 3, count: 3 }, &NoProgress).unwrap();
+    let files = shard_manifests_in(&dir).unwrap();
+    assert_eq!(files.len(), 3);
+
+    let problems = refusals(merge_shard_manifests(&dir, &files));
+    assert!(problems.iter().any(|problem| problem.contains("shard 1 of 3 was run more than once")), "{:?}", problems);
+    assert!(problems.iter().any(|problem| problem.contains("shard 2 of 3 is missing")), "{:?}", problems);
+    assert!(problems.iter().any(|problem| problem.contains("rendered by no shard: 2, 5")), "{:?}", problems);
+    assert!(!dir.join("manifest.json").exists());
+
+    // The same manifest given twice is still one run
+    let first = files[0].clone();
+    let problems = refusals(merge_shard_manifests(&dir, &[first.clone(), first]));
+    assert!(!problems.iter().any(|problem| problem.contains("more than once")), "{:?}", problems);
+}
+
+#[test]
+fn shards_of_another_plan_or_split_are_refused() {
+    let dir = test_dir("foreign");
+    let plan_a = plan(dir.join("certificates"), &NAMES);
+    let plan_b = plan(dir.join("certificates"), &NAMES[..3]);
+    execute_shard(&plan_a, Shard { index: 1, count: 2 }, &NoProgress).unwrap();
+    execute_shard(&plan_b, Shard { index: 2, count: 2 }, &NoProgress).unwrap();
+    let problems = refusals(merge_shard_manifests(dir.join("certificates"), &shard_manifests_in(dir.join("certificates")).unwrap()));
+    assert!(problems.iter().any(|problem| problem.contains("is from another plan")), "{:?}", problems);
+
+    for file in shard_manifests_in(dir.join("certificates")).unwrap() {
+        std::fs::remove_file(file).unwrap();
+    }
+    execute_shard(&plan_a, Shard { index: 1, count: 2 }, &NoProgress).unwrap();
+    execute_shard(&plan_a, Shard { index: 2, count: 3 }, &NoProgress).unwrap();
+    let problems = refusals(merge_shard_manifests(dir.join("certificates"), &shard_manifests_in(dir.join("certificates")).unwrap()));
+    assert!(problems.iter().any(|problem| problem.contains("shards,")), "{:?}", problems);
+
+    // A manifest that isn't a shard's, and certificates that never arrived
+    Manifest::default().save(dir.join("certificates")).unwrap();
+    let problems = refusals(merge_shard_manifests(dir.join("certificates"), &[dir.join("certificates").join("manifest.json")]));
+    assert!(problems[0].contains("isn't the manifest of a shard"), "{:?}", problems);
+    for file in shard_manifests_in(dir.join("certificates")).unwrap() {
+        std::fs::remove_file(file).unwrap();
+    }
+    execute_shard(&plan_a, Shard { index: 1, count: 2 }, &NoProgress).unwrap();
+    execute_shard(&plan_a, Shard { index: 2, count: 2 }, &NoProgress).unwrap();
+    std::fs::remove_file(&plan_a.rows[1].output_file).unwrap();
+    let problems = refusals(merge_shard_manifests(dir.join("certificates"), &shard_manifests_in(dir.join("certificates")).unwrap()));
+    assert!(problems.iter().any(|problem| problem.contains("aren't on disk")), "{:?}", problems);
+}
+
+// A row that fails in its shard is merged as failed, not as missing
+#[test]
+fn failed_rows_are_reported_by_the_merge() {
+    let dir = test_dir("failed");
+    let mut settings = plan(dir.clone(), &["Ada"]).settings;
+    settings.graphics = vec![GraphicField {
+        kind: GraphicKind::Image(ImageStyle { width: Some(60), height: None }),
+        data: "{Photo}".to_string(),
+        x: 300,
+        y: 320,
+        anchor: Anchor::Center,
+    }];
+    settings.records = vec![
+        Record { name: "Ada".to_string(), fields: vec![("Photo".to_string(), TEMPLATE.to_string())] },
+        Record { name: "Grace".to_string(), fields: vec![("Photo".to_string(), "tests/fixtures/no-such-photo.png".to_string())] },
+    ];
+    let plan = plan_batch(&settings, &["Ada".to_string(), "Grace".to_string()]).unwrap();
+    assert_eq!(execute_shard(&plan, Shard { index: 1, count: 2 }, &NoProgress).unwrap().generated, 1);
+    // Shard 2 has only the failed row, and still records that it ran
+    let report = execute_shard(&plan, Shard { index: 2, count: 2 }, &NoProgress).unwrap();
+    assert_eq!((report.generated, report.failed), (0, 1));
+
+    let merged = merge_shard_manifests(&dir, &shard_manifests_in(&dir).unwrap()).unwrap();
+    assert_eq!(merged.certificates, 1);
+    assert_eq!(merged.failed.len(), 1);
+    assert_eq!(merged.failed[0].index, 1);
+}
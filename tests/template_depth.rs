@@ -0,0 +1,84 @@
+// tests/template_depth.rs
+// Templates that aren't plain 8-bit RGBA. A 16-bit scan keeps its 16 bits per channel in PNG
+// certificates: untouched pixels are the template's own, and only what was drawn is widened
+// from 8 bits. PDFs, or --bit-depth 8, reduce it and say why. An indexed PNG with tRNS keeps
+// its transparent and half-transparent palette entries all the way to the certificate.
+//
+//   cargo test --test template_depth
+mod common;
+use certificate_maker::depth::{DeepColor, DepthPolicy};
+use certificate_maker::editpng::load_template;
+use certificate_maker::job::{CertificateFormat, CertificateJob};
+use common::{FONT, output_dir};
+use image::{DynamicImage, ImageBuffer, Rgb};
+use std::path::Path;
+
+const TEMPLATE_16: &str = "tests/fixtures/template16.png";
+const INDEXED: &str = "tests/fixtures/indexed_trns.png";
+
+fn job(template: &str, test: &str) -> CertificateJob {
+    CertificateJob::new(template, vec!["Ada Lovelace".to_string()])
+        .font(FONT)
+        .font_size(24.0)
+        .output_dir(output_dir(test))
+}
+
+fn rgb16(path: &Path) -> ImageBuffer<Rgb<u16>, Vec<u16>> {
+    match image::open(path).unwrap() {
+        DynamicImage::ImageRgb16(img) => img,
+        other => panic!("expected a 16-bit RGB PNG, got {:?}", other.color()),
+    }
+}
+
+#[test]
+fn sixteen_bit_template_keeps_its_depth() {
+    let report = job(TEMPLATE_16, "keep").run().unwrap();
+    assert_eq!(report.generated, 1);
+    assert_eq!(report.deep_color, Some(DeepColor::Kept));
+
+    let template = rgb16(Path::new(TEMPLATE_16));
+    let certificate = rgb16(&report.items[0].output_file);
+    assert_eq!(certificate.dimensions(), template.dimensions());
+    // Away from the name, every pixel is the scan's own, 8 bits couldn't hold them
+    for (x, y) in [(5, 5), (20, 180), (290, 10), (151, 170)] {
+        assert_eq!(certificate.get_pixel(x, y), template.get_pixel(x, y), "pixel at ({}, {})", x, y);
+        assert_ne!(template.get_pixel(x, y).0[0] % 257, 0);
+    }
+    // The name is drawn in black at the center
+    let drawn = (120..180).any(|x| certificate.get_pixel(x, 100).0[0] < 10_000);
+    assert!(drawn, "no ink where the name should be");
+}
+
+#[test]
+fn sixteen_bit_template_is_reduced_for_pdfs_or_when_asked() {
+    let report = job(TEMPLATE_16, "reduce").depth(DepthPolicy::Reduce).run().unwrap();
+    assert!(matches!(&report.deep_color, Some(DeepColor::Reduced { reason }) if reason.contains("asked for")));
+    assert_eq!(image::open(&report.items[0].output_file).unwrap().color(), image::ColorType::Rgba8);
+
+    let pdf = job(TEMPLATE_16, "pdf").format(CertificateFormat::Pdf);
+    assert!(matches!(pdf.deep_color().unwrap(), Some(DeepColor::Reduced { reason }) if reason.contains("PDF")));
+    // An 8-bit template has no depth to keep
+    assert_eq!(job(INDEXED, "eight_bit").deep_color().unwrap(), None);
+}
+
+#[test]
+fn sixteen_bit_batch_plans_for_the_wider_pixels() {
+    let keep = job(TEMPLATE_16, "memory").threads(2).memory_plan().unwrap();
+    let reduce = job(TEMPLATE_16, "memory").threads(2).depth(DepthPolicy::Reduce).memory_plan().unwrap();
+    assert!(keep.peak_bytes() > reduce.peak_bytes() * 2);
+}
+
+#[test]
+fn indexed_template_keeps_its_transparency() {
+    let template = load_template(INDEXED).unwrap();
+    assert_eq!(template.get_pixel(5, 5).0, [30, 40, 90, 255]);
+    assert_eq!(template.get_pixel(100, 50).0, [200, 160, 40, 128]);
+    assert_eq!(template.get_pixel(150, 130).0[3], 0);
+
+    let report = job(INDEXED, "indexed").run().unwrap();
+    assert_eq!(report.generated, 1);
+    let certificate = image::open(&report.items[0].output_file).unwrap().to_rgba8();
+    assert_eq!(certificate.get_pixel(5, 5).0, [30, 40, 90, 255]);
+    assert_eq!(certificate.get_pixel(30, 50).0, [200, 160, 40, 128]);
+    assert_eq!(certificate.get_pixel(150, 150).0[3], 0);
+}
@@ -0,0 +1,132 @@
+// tests/untrusted_input.rs
+// Name lists from outside, with values meant to run as spreadsheet formulas, control
+// characters and absurdly long fields. Values are cleaned and flagged as they are parsed, CSV
+// reports never start a cell with a formula, and neither the log nor the certificates get a
+// control character.
+//
+//   cargo test --test untrusted_input
+mod common;
+use certificate_maker::csvexcelparser::{BatchSettings, parse_csv_file};
+use certificate_maker::job::CertificateJob;
+use certificate_maker::plan::plan_batch;
+use certificate_maker::runlog::RunLog;
+use certificate_maker::sanitize::{FlaggedValue, MAX_FIELD_CHARS, ValueIssue};
+use common::{FONT, TEMPLATE, test_dir};
+
+const HOSTILE: &str = "tests/fixtures/hostile.csv";
+
+fn flagged(row: usize, column: &str, issue: ValueIssue) -> FlaggedValue {
+    FlaggedValue { row, column: column.to_string(), issue }
+}
+
+#[test]
+fn hostile_values_are_cleaned_and_flagged() {
+    let parsed = parse_csv_file(HOSTILE).unwrap();
+    assert_eq!(parsed.names, [
+        "=HYPERLINK(\"http://evil.example\",\"Click\")",
+        "+cmd|' /C calc'!A0",
+        "-2+3",
+        "@SUM(A1:A9)",
+        "Ada Lovelace",
+        "Alan Turing",
+        "Mary Jackson",
+    ]);
+    let ada = &parsed.records[4];
+    assert_eq!(ada.get("Course"), Some("Analysis"));
+    assert_eq!(ada.get("Note"), Some("first line  second line"));
+
+    // A name of nothing but control characters is no name
+    assert_eq!(parsed.skipped_rows.len(), 1);
+    assert_eq!(parsed.skipped_rows[0].reason, "Empty name");
+
+    let formula = ValueIssue::FormulaPrefix;
+    let control = ValueIssue::ControlCharacters;
+    // The notes -12.5 and -3 are numbers, not formulas
+    assert_eq!(parsed.flagged_values, [
+        flagged(2, "Name", formula),
+        flagged(3, "Name", formula),
+        flagged(4, "Name", formula),
+        flagged(5, "Name", formula),
+        flagged(6, "Name", control),
+        flagged(6, "Course", control),
+        flagged(6, "Note", control),
+        flagged(7, "Name", control),
+    ]);
+    assert_eq!(parsed.flagged_values[0].describe(), "'Name' starts like a spreadsheet formula; CSV reports write it with a leading '");
+}
+
+#[test]
+fn csv_plans_never_start_a_cell_with_a_formula() {
+    let dir = test_dir("plan");
+    let names = parse_csv_file(HOSTILE).unwrap().names;
+    let settings = BatchSettings {
+        template_file: TEMPLATE.into(),
+        font_filename: FONT.into(),
+        font_size: 24.0,
+        hex_color: "#203040".to_string(),
+        x_pos: 300,
+        y_pos: 200,
+        output_dir: dir.join("certificates"),
+        ..Default::default()
+    };
+    let plan = plan_batch(&settings, &names).unwrap();
+    // The JSON plan keeps the names as they are
+    assert_eq!(plan.rows[0].name, names[0]);
+    plan.save(dir.join("plan.csv")).unwrap();
+
+    let mut reader = csv::Reader::from_path(dir.join("plan.csv")).unwrap();
+    let rows: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+    assert_eq!(rows.len(), names.len());
+    for cell in rows.iter().flat_map(|row| row.iter()) {
+        let formula = cell.starts_with(['=', '+', '-', '@']) && cell.parse::<f64>().is_err();
+        assert!(!formula, "{:?} would run as a formula", cell);
+    }
+    assert_eq!(&rows[0][1], "'=HYPERLINK(\"http://evil.example\",\"Click\")");
+    assert_eq!(&rows[3][1], "'@SUM(A1:A9)");
+    assert_eq!(&rows[4][1], "Ada Lovelace");
+}
+
+#[test]
+fn log_lines_keep_to_one_line() {
+    let dir = test_dir("log");
+    let log = RunLog::create(&dir).unwrap();
+    log.line("Generated: Ada\u{7}\r\n[2026-01-01T00:00:00Z] Batch finished");
+    let content = std::fs::read_to_string(log.path()).unwrap();
+    assert_eq!(content.lines().count(), 1, "{:?}", content);
+    assert!(content.ends_with("] Generated: Ada  [2026-01-01T00:00:00Z] Batch finished\n"), "{:?}", content);
+}
+
+#[test]
+fn control_characters_are_not_drawn() {
+    let dir = test_dir("render");
+    let run = |name: &str, output: &str| {
+        let report = CertificateJob::new(TEMPLATE, vec![name.to_string()])
+            .font(FONT)
+            .output_dir(dir.join(output))
+            .run()
+            .unwrap();
+        image::open(&report.items[0].output_file).unwrap().to_rgba8()
+    };
+    assert_eq!(run("Ada\u{7}\u{0}\tLovelace", "hostile"), run("Ada Lovelace", "clean"));
+}
+
+#[test]
+fn absurdly_long_fields_are_flagged_and_still_make_a_certificate() {
+    let dir = test_dir("long");
+    let name = "Ada Lovelace ".repeat(500);
+    std::fs::write(dir.join("long.csv"), format!("Name,Course\n{},Analysis\n", name)).unwrap();
+    let parsed = parse_csv_file(dir.join("long.csv")).unwrap();
+    let chars = name.trim().chars().count();
+    assert!(chars > MAX_FIELD_CHARS);
+    assert_eq!(parsed.flagged_values, [flagged(2, "Name", ValueIssue::TooLong { chars })]);
+    assert!(parsed.flagged_values[0].describe().contains(&chars.to_string()));
+
+    let report = CertificateJob::new(TEMPLATE, parsed.names)
+        .font(FONT)
+        .output_dir(dir.join("certificates"))
+        .run()
+        .unwrap();
+    assert_eq!(report.failed, 0);
+    let file_name = report.items[0].output_file.file_name().unwrap();
+    assert!(file_name.len() <= 255, "{} bytes", file_name.len());
+}